@@ -160,6 +160,7 @@ sqlite3 $HOME/.config/screenpipe/db.sqlite "SELECT * FROM audio_transcriptions O
       case "Healthy":
         return "bg-green-500";
       case "Loading":
+      case "Degraded":
         return "bg-yellow-500";
       case "Unhealthy":
       case "Error":
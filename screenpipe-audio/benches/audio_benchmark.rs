@@ -1,9 +1,13 @@
 // cargo bench --bench audio_benchmark
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use screenpipe_audio::{create_whisper_channel, stt, AudioInput, WhisperModel};
+use screenpipe_audio::{
+    create_whisper_channel, stt, AudioInput, AudioPreprocessing, TranscriptionLanguages,
+    TranscriptionPriority, WhisperModel, WhisperModelConfig,
+};
 use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
+use tokio::sync::watch;
 
 fn generate_large_audio_file(path: &str, duration_secs: u32) {
     use std::process::Command;
@@ -53,12 +57,21 @@ fn benchmark_concurrent_stt(c: &mut Criterion) {
     c.bench_function("concurrent_stt_10x30s", |b| {
         b.iter(|| {
             runtime.block_on(async {
-                let (sender, mut receiver) = create_whisper_channel().await.unwrap();
+                let (sender, mut receiver) = create_whisper_channel(
+                    None,
+                    1,
+                    TranscriptionLanguages::default(),
+                    watch::channel(WhisperModelConfig::default()).1,
+                )
+                .await
+                .unwrap();
 
                 for file in &test_files {
                     let input = AudioInput {
                         path: file.clone(),
                         device: "test_device".to_string(),
+                        priority: TranscriptionPriority::Background,
+                        preprocessing: AudioPreprocessing::default(),
                     };
                     sender.send(input).unwrap();
                 }
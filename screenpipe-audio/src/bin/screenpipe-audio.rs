@@ -8,10 +8,19 @@ use screenpipe_audio::list_audio_devices;
 use screenpipe_audio::parse_audio_device;
 use screenpipe_audio::record_and_transcribe;
 use screenpipe_audio::AudioDevice;
+use screenpipe_audio::AudioDeviceConfig;
+use screenpipe_audio::AudioEncoding;
+use screenpipe_audio::CloudSttConfig;
+use screenpipe_audio::CloudSttProvider;
+use screenpipe_audio::Gpu;
+use screenpipe_audio::TranscriptionLanguages;
+use screenpipe_audio::WhisperModelConfig;
+use screenpipe_audio::WhisperModelSize;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::watch;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -19,15 +28,39 @@ struct Args {
     #[clap(
         short,
         long,
-        help = "Audio device name (can be specified multiple times)"
+        help = "Audio device name or #number from --list-audio-devices (can be specified multiple times)"
     )]
     audio_device: Vec<String>,
 
     #[clap(long, help = "List available audio devices")]
     list_audio_devices: bool,
 
+    #[clap(
+        long,
+        default_value = "tiny",
+        help = "Whisper model: tiny, base, small, medium or large-v3"
+    )]
+    whisper_model: WhisperModelSize,
+
+    #[clap(long, help = "Use the 8-bit quantized whisper model (tiny only)")]
+    whisper_quantized: bool,
+
+    #[clap(
+        long,
+        default_value = "auto",
+        help = "GPU running whisper: auto, cuda, metal or off (requires the cuda or metal feature)"
+    )]
+    gpu: Gpu,
+
     #[clap(long, help = "Disable cloud audio processing")]
     cloud_audio_off: bool,
+
+    #[clap(
+        long,
+        default_value = "deepgram",
+        help = "Cloud transcription provider: deepgram, openai or azure, used when its API key is set in DEEPGRAM_API_KEY, OPENAI_API_KEY or AZURE_SPEECH_KEY"
+    )]
+    cloud_audio_provider: CloudSttProvider,
 }
 
 fn print_devices(devices: &[AudioDevice]) {
@@ -77,8 +110,25 @@ async fn main() -> Result<()> {
 
     let chunk_duration = Duration::from_secs(5);
     let output_path = PathBuf::from("output.mp4");
-    let cloud_audio = !args.cloud_audio_off;
-    let (whisper_sender, mut whisper_receiver) = create_whisper_channel(cloud_audio).await?;
+    // Whisper transcribes without a key of the provider
+    let has_key =
+        std::env::var(args.cloud_audio_provider.api_key_env()).is_ok_and(|key| !key.is_empty());
+    let cloud_stt = (!args.cloud_audio_off && has_key).then(|| CloudSttConfig {
+        provider: args.cloud_audio_provider,
+        ..Default::default()
+    });
+    let (whisper_sender, mut whisper_receiver) = create_whisper_channel(
+        cloud_stt,
+        1,
+        TranscriptionLanguages::default(),
+        watch::channel(WhisperModelConfig {
+            size: args.whisper_model,
+            quantized: args.whisper_quantized,
+            gpu: args.gpu,
+        })
+        .1,
+    )
+    .await?;
     // Spawn threads for each device
     let recording_threads: Vec<_> = devices
         .into_iter()
@@ -100,6 +150,8 @@ async fn main() -> Result<()> {
                     output_path,
                     whisper_sender,
                     device_control_clone,
+                    AudioDeviceConfig::default(),
+                    AudioEncoding::default(),
                 )
             })
         })
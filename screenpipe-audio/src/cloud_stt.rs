@@ -0,0 +1,506 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::stt::{Transcript, TranscriptionLanguage};
+
+#[cfg(feature = "cloud")]
+use hound::{SampleFormat, WavSpec, WavWriter};
+#[cfg(feature = "cloud")]
+use log::{debug, error, info};
+#[cfg(feature = "cloud")]
+use reqwest::blocking::{multipart, Client};
+#[cfg(feature = "cloud")]
+use screenpipe_core::{
+    call_with_resilience_blocking, HttpStatusError, RetryPolicy, TranscriptionSegment,
+};
+#[cfg(feature = "cloud")]
+use serde_json::Value;
+#[cfg(feature = "cloud")]
+use std::io::Cursor;
+
+/// Cloud speech-to-text service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloudSttProvider {
+    #[default]
+    Deepgram,
+    /// OpenAI's transcription API, or any server compatible with it
+    OpenAi,
+    /// Azure AI Speech
+    Azure,
+}
+
+impl CloudSttProvider {
+    pub const ALL: [CloudSttProvider; 3] = [
+        CloudSttProvider::Deepgram,
+        CloudSttProvider::OpenAi,
+        CloudSttProvider::Azure,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloudSttProvider::Deepgram => "deepgram",
+            CloudSttProvider::OpenAi => "openai",
+            CloudSttProvider::Azure => "azure",
+        }
+    }
+
+    /// Environment variable read when no API key is configured
+    pub fn api_key_env(&self) -> &'static str {
+        match self {
+            CloudSttProvider::Deepgram => "DEEPGRAM_API_KEY",
+            CloudSttProvider::OpenAi => "OPENAI_API_KEY",
+            CloudSttProvider::Azure => "AZURE_SPEECH_KEY",
+        }
+    }
+}
+
+impl FromStr for CloudSttProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CloudSttProvider::ALL
+            .into_iter()
+            .find(|provider| provider.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "unknown transcription provider {}, expected deepgram, openai or azure",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for CloudSttProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Cloud transcription of a deployment. Whisper transcribes locally when it fails.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CloudSttConfig {
+    pub provider: CloudSttProvider,
+    /// Read from the provider's environment variable when None, see `api_key_env`
+    pub api_key: Option<String>,
+    /// Base URL replacing the provider's, e.g. a self-hosted OpenAI-compatible server or an
+    /// Azure custom domain
+    pub endpoint: Option<String>,
+    /// Azure region like `westeurope`, AZURE_SPEECH_REGION when None. Unused by the others.
+    pub region: Option<String>,
+    /// Provider's default when None: nova-2 for Deepgram, whisper-1 for OpenAI
+    pub model: Option<String>,
+}
+
+impl CloudSttConfig {
+    #[cfg(feature = "cloud")]
+    fn api_key(&self) -> Result<String> {
+        if let Some(key) = self.api_key.as_ref().filter(|key| !key.is_empty()) {
+            return Ok(key.clone());
+        }
+        match std::env::var(self.provider.api_key_env()) {
+            Ok(key) if !key.is_empty() => Ok(key),
+            _ => Err(anyhow!(
+                "no API key for {}, set {}",
+                self.provider,
+                self.provider.api_key_env()
+            )),
+        }
+    }
+
+    #[cfg(feature = "cloud")]
+    fn endpoint(&self, default: &str) -> String {
+        self.endpoint
+            .as_deref()
+            .unwrap_or(default)
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// Provider transcribing with this configuration, fails on missing credentials
+    pub fn provider(&self) -> Result<Arc<dyn TranscriptionProvider>> {
+        #[cfg(feature = "cloud")]
+        {
+            let api_key = self.api_key()?;
+            let client = Client::new();
+            Ok(match self.provider {
+                CloudSttProvider::Deepgram => Arc::new(Deepgram {
+                    client,
+                    api_key,
+                    endpoint: self.endpoint("https://api.deepgram.com/v1"),
+                    model: self.model.clone().unwrap_or_else(|| "nova-2".to_string()),
+                }),
+                CloudSttProvider::OpenAi => Arc::new(OpenAi {
+                    client,
+                    api_key,
+                    endpoint: self.endpoint("https://api.openai.com/v1"),
+                    model: self
+                        .model
+                        .clone()
+                        .unwrap_or_else(|| "whisper-1".to_string()),
+                }),
+                CloudSttProvider::Azure => {
+                    let endpoint = match (&self.endpoint, &self.region) {
+                        (Some(_), _) => self.endpoint(""),
+                        (None, Some(region)) => {
+                            format!("https://{}.stt.speech.microsoft.com", region)
+                        }
+                        (None, None) => match std::env::var("AZURE_SPEECH_REGION") {
+                            Ok(region) if !region.is_empty() => {
+                                format!("https://{}.stt.speech.microsoft.com", region)
+                            }
+                            _ => {
+                                return Err(anyhow!(
+                                    "azure transcription needs a region or an endpoint"
+                                ))
+                            }
+                        },
+                    };
+                    Arc::new(Azure {
+                        client,
+                        api_key,
+                        endpoint,
+                    })
+                }
+            })
+        }
+        #[cfg(not(feature = "cloud"))]
+        {
+            Err(anyhow!(
+                "screenpipe-audio was built without the `cloud` feature"
+            ))
+        }
+    }
+}
+
+/// Cloud service transcribing speech, built by `CloudSttConfig::provider`
+pub trait TranscriptionProvider: Send + Sync {
+    /// Transcription engine stored with what it transcribed
+    fn name(&self) -> &'static str;
+
+    /// Transcript of 16 kHz mono samples, segments timed from the first sample
+    fn transcribe(&self, audio: &[f32], language: &TranscriptionLanguage) -> Result<Transcript>;
+}
+
+/// 16 kHz mono WAV of the samples, as 32-bit floats or 16-bit integers
+#[cfg(feature = "cloud")]
+fn wav_bytes(audio: &[f32], sample_format: SampleFormat) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: 16000,
+        bits_per_sample: match sample_format {
+            SampleFormat::Float => 32,
+            SampleFormat::Int => 16,
+        },
+        sample_format,
+    };
+    let mut writer = WavWriter::new(&mut cursor, spec)?;
+    for &sample in audio {
+        match sample_format {
+            SampleFormat::Float => writer.write_sample(sample)?,
+            SampleFormat::Int => writer.write_sample((sample.clamp(-1.0, 1.0) * 32767.0) as i16)?,
+        }
+    }
+    writer.finalize()?;
+    Ok(cursor.into_inner())
+}
+
+#[cfg(feature = "cloud")]
+fn log_transcription(provider: &str, transcription: &str, response: &Value) {
+    if transcription.is_empty() {
+        info!(
+            "{} transcription is empty. Full response: {:?}",
+            provider, response
+        );
+    } else {
+        info!(
+            "{} transcription successful. Length: {} characters",
+            provider,
+            transcription.len()
+        );
+    }
+}
+
+#[cfg(feature = "cloud")]
+struct Deepgram {
+    client: Client,
+    api_key: String,
+    endpoint: String,
+    model: String,
+}
+
+#[cfg(feature = "cloud")]
+impl TranscriptionProvider for Deepgram {
+    fn name(&self) -> &'static str {
+        "Deepgram"
+    }
+
+    fn transcribe(
+        &self,
+        audio: &[f32],
+        language: &TranscriptionLanguage,
+    ) -> Result<Transcript> {
+        debug!("Starting Deepgram transcription");
+        let wav_data = wav_bytes(audio, SampleFormat::Float)?;
+        let language_param = match language {
+            TranscriptionLanguage::Auto => "detect_language=true".to_string(),
+            TranscriptionLanguage::Fixed(code) => format!("language={}", code),
+        };
+        let url = format!(
+            "{}/listen?model={}&smart_format=true&{}",
+            self.endpoint, self.model, language_param
+        );
+        let result =
+            call_with_resilience_blocking("deepgram", &RetryPolicy::default(), |timeout| {
+                let resp = self
+                    .client
+                    .post(&url)
+                    .header("Content-Type", "audio/wav")
+                    .header("Authorization", format!("Token {}", self.api_key))
+                    .timeout(timeout)
+                    .body(wav_data.clone())
+                    .send()
+                    .map_err(|e| anyhow!("Failed to send request to Deepgram API: {:?}", e))?;
+                debug!("Received response from Deepgram API");
+                let status = resp.status();
+                if !status.is_success() {
+                    let message = format!("Deepgram API returned {}", status);
+                    return Err(HttpStatusError::new(status.as_u16(), message).into());
+                }
+                let result = resp
+                    .json::<Value>()
+                    .map_err(|e| anyhow!("Failed to parse JSON response: {:?}", e))?;
+                if let Some(err_code) = result.get("err_code") {
+                    error!(
+                        "Deepgram API error code: {:?}, result: {:?}",
+                        err_code, result
+                    );
+                    return Err(anyhow!("Deepgram API error: {:?}", result));
+                }
+                Ok(result)
+            })?;
+
+        let alternative = &result["results"]["channels"][0]["alternatives"][0];
+        let transcription = alternative["transcript"].as_str().unwrap_or("");
+        let words = alternative["words"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+        let segments = words
+            .iter()
+            .filter_map(|word| {
+                Some(TranscriptionSegment {
+                    start: word["start"].as_f64()?,
+                    end: word["end"].as_f64()?,
+                    text: word["punctuated_word"]
+                        .as_str()
+                        .or(word["word"].as_str())?
+                        .to_string(),
+                })
+            })
+            .collect();
+        let detected_language = match language {
+            TranscriptionLanguage::Auto => result["results"]["channels"][0]["detected_language"]
+                .as_str()
+                .map(|code| code.to_string()),
+            TranscriptionLanguage::Fixed(code) => Some(code.to_string()),
+        };
+        log_transcription("Deepgram", transcription, &result);
+        Ok(Transcript {
+            text: transcription.to_string(),
+            language: detected_language,
+            segments,
+        })
+    }
+}
+
+#[cfg(feature = "cloud")]
+struct OpenAi {
+    client: Client,
+    api_key: String,
+    endpoint: String,
+    model: String,
+}
+
+#[cfg(feature = "cloud")]
+impl TranscriptionProvider for OpenAi {
+    fn name(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    fn transcribe(
+        &self,
+        audio: &[f32],
+        language: &TranscriptionLanguage,
+    ) -> Result<Transcript> {
+        debug!("Starting OpenAI transcription");
+        let wav_data = wav_bytes(audio, SampleFormat::Int)?;
+        let url = format!("{}/audio/transcriptions", self.endpoint);
+        let result =
+            call_with_resilience_blocking("openai-stt", &RetryPolicy::default(), |timeout| {
+                // A multipart form can only be sent once
+                let mut form = multipart::Form::new()
+                    .text("model", self.model.clone())
+                    .text("response_format", "verbose_json")
+                    .part(
+                        "file",
+                        multipart::Part::bytes(wav_data.clone())
+                            .file_name("audio.wav")
+                            .mime_str("audio/wav")?,
+                    );
+                if let TranscriptionLanguage::Fixed(code) = language {
+                    form = form.text("language", code.to_string());
+                }
+                let resp = self
+                    .client
+                    .post(&url)
+                    .bearer_auth(&self.api_key)
+                    .timeout(timeout)
+                    .multipart(form)
+                    .send()
+                    .map_err(|e| anyhow!("Failed to send request to OpenAI API: {:?}", e))?;
+                let status = resp.status();
+                if !status.is_success() {
+                    let message = format!("OpenAI API returned {}", status);
+                    return Err(HttpStatusError::new(status.as_u16(), message).into());
+                }
+                resp.json::<Value>()
+                    .map_err(|e| anyhow!("Failed to parse JSON response: {:?}", e))
+            })?;
+
+        let transcription = result["text"].as_str().unwrap_or("").trim();
+        let segments = result["segments"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|segment| {
+                Some(TranscriptionSegment {
+                    start: segment["start"].as_f64()?,
+                    end: segment["end"].as_f64()?,
+                    text: segment["text"].as_str()?.trim().to_string(),
+                })
+            })
+            .collect();
+        // verbose_json names the language in English, e.g. "french"
+        let detected_language = match language {
+            TranscriptionLanguage::Auto => result["language"]
+                .as_str()
+                .and_then(crate::multilingual::language_code)
+                .map(|code| code.to_string()),
+            TranscriptionLanguage::Fixed(code) => Some(code.to_string()),
+        };
+        log_transcription("OpenAI", transcription, &result);
+        Ok(Transcript {
+            text: transcription.to_string(),
+            language: detected_language,
+            segments,
+        })
+    }
+}
+
+#[cfg(feature = "cloud")]
+struct Azure {
+    client: Client,
+    api_key: String,
+    endpoint: String,
+}
+
+/// Locale Azure recognizes a whisper language code in, its most spoken variant
+#[cfg(feature = "cloud")]
+fn azure_locale(code: &str) -> String {
+    let locale = match code {
+        "en" => "en-US",
+        "zh" => "zh-CN",
+        "de" => "de-DE",
+        "es" => "es-ES",
+        "ru" => "ru-RU",
+        "ko" => "ko-KR",
+        "fr" => "fr-FR",
+        "ja" => "ja-JP",
+        "pt" => "pt-BR",
+        "it" => "it-IT",
+        "nl" => "nl-NL",
+        "pl" => "pl-PL",
+        "sv" => "sv-SE",
+        "hi" => "hi-IN",
+        "ar" => "ar-SA",
+        _ => return code.to_string(),
+    };
+    locale.to_string()
+}
+
+#[cfg(feature = "cloud")]
+impl TranscriptionProvider for Azure {
+    fn name(&self) -> &'static str {
+        "Azure"
+    }
+
+    /// The short audio API takes up to 60 seconds and doesn't detect the language,
+    /// English is assumed without a fixed language
+    fn transcribe(
+        &self,
+        audio: &[f32],
+        language: &TranscriptionLanguage,
+    ) -> Result<Transcript> {
+        debug!("Starting Azure transcription");
+        let wav_data = wav_bytes(audio, SampleFormat::Int)?;
+        let code = match language {
+            TranscriptionLanguage::Auto => "en",
+            TranscriptionLanguage::Fixed(code) => *code,
+        };
+        let url = format!(
+            "{}/speech/recognition/conversation/cognitiveservices/v1?language={}&format=simple",
+            self.endpoint,
+            azure_locale(code)
+        );
+        let result =
+            call_with_resilience_blocking("azure-stt", &RetryPolicy::default(), |timeout| {
+                let resp = self
+                    .client
+                    .post(&url)
+                    .header("Ocp-Apim-Subscription-Key", &self.api_key)
+                    .header(
+                        "Content-Type",
+                        "audio/wav; codecs=audio/pcm; samplerate=16000",
+                    )
+                    .timeout(timeout)
+                    .body(wav_data.clone())
+                    .send()
+                    .map_err(|e| anyhow!("Failed to send request to Azure Speech: {:?}", e))?;
+                let status = resp.status();
+                if !status.is_success() {
+                    let message = format!("Azure Speech returned {}", status);
+                    return Err(HttpStatusError::new(status.as_u16(), message).into());
+                }
+                resp.json::<Value>()
+                    .map_err(|e| anyhow!("Failed to parse JSON response: {:?}", e))
+            })?;
+
+        let transcription = match result["RecognitionStatus"].as_str() {
+            Some("Success") => result["DisplayText"].as_str().unwrap_or(""),
+            // Silence or noise only
+            Some("NoMatch") | Some("InitialSilenceTimeout") => "",
+            _ => return Err(anyhow!("Azure Speech error: {:?}", result)),
+        };
+        // One segment for the whole utterance, offsets are in ticks of 100 ns
+        let segments = match (result["Offset"].as_f64(), result["Duration"].as_f64()) {
+            (Some(offset), Some(duration)) if !transcription.is_empty() => {
+                vec![TranscriptionSegment {
+                    start: offset / 1e7,
+                    end: (offset + duration) / 1e7,
+                    text: transcription.to_string(),
+                }]
+            }
+            _ => Vec::new(),
+        };
+        log_transcription("Azure", transcription, &result);
+        Ok(Transcript {
+            text: transcription.to_string(),
+            language: Some(code.to_string()),
+            segments,
+        })
+    }
+}
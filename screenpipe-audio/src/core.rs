@@ -3,7 +3,7 @@ use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::StreamError;
 use log::{debug, error, info, warn};
 use screenpipe_core::find_ffmpeg_path;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -14,7 +14,7 @@ use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tokio::sync::mpsc::{self, UnboundedSender};
 
-use crate::AudioInput;
+use crate::{AudioEncoding, AudioInput, AudioPreprocessing, TranscriptionPriority};
 
 #[derive(Clone)]
 pub struct DeviceControl {
@@ -22,7 +22,64 @@ pub struct DeviceControl {
     pub is_paused: bool,
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Serialize)]
+/// Capture settings of a device, unset ones use the default of the device, and the chunk
+/// duration of the recording
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioDeviceConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+    /// Factor the samples are multiplied by, clipped to [-1, 1]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gain: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_duration_secs: Option<u64>,
+    /// Removes background noise before transcription, off by default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noise_suppression: Option<bool>,
+    /// Levels speech before transcription, off by default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_gain: Option<bool>,
+}
+
+/// Highest `gain` of an `AudioDeviceConfig`, +30 dB
+const MAX_GAIN: f32 = 32.0;
+
+impl AudioDeviceConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.sample_rate == Some(0) {
+            return Err(anyhow!("sample_rate must be at least 1"));
+        }
+        if matches!(self.channels, Some(channels) if channels == 0 || channels > 32) {
+            return Err(anyhow!("channels must be between 1 and 32"));
+        }
+        if matches!(self.gain, Some(gain) if !(0.0..=MAX_GAIN).contains(&gain)) {
+            return Err(anyhow!("gain must be between 0 and {}", MAX_GAIN));
+        }
+        if self.chunk_duration_secs == Some(0) {
+            return Err(anyhow!("chunk_duration_secs must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Cleanup of the chunks of the device before they're transcribed
+    pub fn preprocessing(&self) -> AudioPreprocessing {
+        AudioPreprocessing {
+            noise_suppression: self.noise_suppression.unwrap_or(false),
+            auto_gain: self.auto_gain.unwrap_or(false),
+        }
+    }
+
+    /// Duration of the chunks of the device, `default` when unset
+    pub fn chunk_duration(&self, default: Duration) -> Duration {
+        self.chunk_duration_secs
+            .map_or(default, Duration::from_secs)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize)]
 pub enum DeviceType {
     Input,
     Output,
@@ -39,19 +96,29 @@ impl AudioDevice {
         AudioDevice { name, device_type }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn device_type(&self) -> &DeviceType {
+        &self.device_type
+    }
+
     pub fn from_name(name: &str) -> Result<Self> {
         if name.trim().is_empty() {
             return Err(anyhow!("Device name cannot be empty"));
         }
 
-        let (name, device_type) = if name.to_lowercase().ends_with("(input)") {
+        let name = name.trim();
+        let lowercase = name.to_lowercase();
+        let (name, device_type) = if lowercase.ends_with("(input)") {
             (
-                name.trim_end_matches("(input)").trim().to_string(),
+                name[..name.len() - "(input)".len()].trim().to_string(),
                 DeviceType::Input,
             )
-        } else if name.to_lowercase().ends_with("(output)") {
+        } else if lowercase.ends_with("(output)") {
             (
-                name.trim_end_matches("(output)").trim().to_string(),
+                name[..name.len() - "(output)".len()].trim().to_string(),
                 DeviceType::Output,
             )
         } else {
@@ -78,12 +145,91 @@ impl fmt::Display for AudioDevice {
     }
 }
 
+/// Resolves a device given on the command line or to the API. Besides `name (input)` /
+/// `name (output)`, devices can be selected by their number in `list_audio_devices`
+/// (`#2` or `2`), which works for localized names. Names are matched ignoring case and
+/// whitespace and resolve to the listed name, devices that are not plugged in yet are
+/// returned as given.
 pub fn parse_audio_device(name: &str) -> Result<AudioDevice> {
-    AudioDevice::from_name(name)
+    let selector = name.trim();
+    if let Ok(index) = selector.trim_start_matches('#').parse::<usize>() {
+        let devices = list_audio_devices()?;
+        return index
+            .checked_sub(1)
+            .and_then(|i| devices.get(i))
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "No audio device #{}, {} devices available",
+                    index,
+                    devices.len()
+                )
+            });
+    }
+
+    let device = AudioDevice::from_name(name)?;
+    let listed = match list_audio_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            warn!("Could not list audio devices to resolve {}: {}", device, e);
+            return Ok(device);
+        }
+    };
+    Ok(listed
+        .into_iter()
+        .find(|d| d.device_type == device.device_type && same_name(&d.name, &device.name))
+        .unwrap_or(device))
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.split_whitespace()
+        .map(str::to_lowercase)
+        .eq(b.split_whitespace().map(str::to_lowercase))
+}
+
+/// Devices of the host with their names, duplicated names get a ` #2`, ` #3`... suffix
+/// in enumeration order so each device can be selected. In a Windows service (session 0)
+/// or other headless context enumeration can fail, which gives no devices instead of an
+/// error.
+fn enumerate_devices(host: &cpal::Host, device_type: &DeviceType) -> Vec<(String, cpal::Device)> {
+    let devices = match device_type {
+        DeviceType::Input => host.input_devices(),
+        DeviceType::Output => host.output_devices(),
+    };
+    let devices = match devices {
+        Ok(devices) => devices,
+        Err(e) => {
+            warn!("Failed to enumerate {:?} audio devices: {}", device_type, e);
+            return Vec::new();
+        }
+    };
+
+    let mut named: Vec<(String, cpal::Device)> = Vec::new();
+    for device in devices {
+        let name = match device.name() {
+            Ok(name) => name,
+            Err(e) => {
+                debug!("Skipping audio device without name: {}", e);
+                continue;
+            }
+        };
+        let duplicates = named
+            .iter()
+            .filter(|(n, _)| n == &name || n.starts_with(&format!("{} #", name)))
+            .count();
+        let name = if duplicates == 0 {
+            name
+        } else {
+            format!("{} #{}", name, duplicates + 1)
+        };
+        named.push((name, device));
+    }
+    named
 }
 
 fn get_device_and_config(
     audio_device: &AudioDevice,
+    device_config: &AudioDeviceConfig,
 ) -> Result<(cpal::Device, cpal::SupportedStreamConfig)> {
     let host = match audio_device.device_type {
         #[cfg(target_os = "macos")]
@@ -99,27 +245,53 @@ fn get_device_and_config(
             DeviceType::Output => host.default_output_device(),
         }
     } else {
-        let mut devices = match audio_device.device_type {
-            DeviceType::Input => host.input_devices()?,
-            DeviceType::Output => host.output_devices()?,
-        };
-
-        devices.find(|x| {
-            x.name()
-                .map(|y| {
-                    y == audio_device
-                        .to_string()
-                        .replace(" (input)", "")
-                        .replace(" (output)", "")
-                        .trim()
-                })
-                .unwrap_or(false)
-        })
+        let devices = enumerate_devices(&host, &audio_device.device_type);
+        let index = devices
+            .iter()
+            .position(|(name, _)| name == &audio_device.name)
+            .or_else(|| {
+                devices
+                    .iter()
+                    .position(|(name, _)| same_name(name, &audio_device.name))
+            });
+        index.map(|i| devices.into_iter().nth(i).unwrap().1)
     }
     .ok_or_else(|| anyhow!("Audio device not found"))?;
 
-    let config = audio_device.default_input_config()?;
-    Ok((audio_device, config))
+    let default = audio_device.default_input_config()?;
+    if device_config.sample_rate.is_none() && device_config.channels.is_none() {
+        return Ok((audio_device, default));
+    }
+    let sample_rate = device_config.sample_rate.unwrap_or(default.sample_rate().0);
+    let channels = device_config.channels.unwrap_or(default.channels());
+    let supported = audio_device
+        .supported_input_configs()
+        .ok()
+        .and_then(|mut configs| {
+            configs.find(|c| {
+                c.sample_format() == cpal::SampleFormat::F32
+                    && c.channels() == channels
+                    && c.min_sample_rate().0 <= sample_rate
+                    && sample_rate <= c.max_sample_rate().0
+            })
+        });
+    match supported {
+        Some(config) => Ok((
+            audio_device,
+            config.with_sample_rate(cpal::SampleRate(sample_rate)),
+        )),
+        None => {
+            warn!(
+                "{} can't capture {} Hz with {} channels, using {} Hz with {} channels",
+                audio_device.name().unwrap_or_default(),
+                sample_rate,
+                channels,
+                default.sample_rate().0,
+                default.channels()
+            );
+            Ok((audio_device, default))
+        }
+    }
 }
 
 async fn run_ffmpeg(
@@ -129,6 +301,7 @@ async fn run_ffmpeg(
     output_path: &PathBuf,
     is_running: Arc<AtomicBool>,
     duration: Duration,
+    encoding: AudioEncoding,
 ) -> Result<()> {
     debug!("Starting FFmpeg process");
     let mut command = Command::new(find_ffmpeg_path().unwrap());
@@ -142,14 +315,9 @@ async fn run_ffmpeg(
             &channels.to_string(),
             "-i",
             "pipe:0",
-            "-c:a",
-            "aac",
-            "-b:a",
-            "128k",
-            "-f",
-            "mp4",
-            output_path.to_str().unwrap(),
         ])
+        .args(encoding.ffmpeg_args())
+        .arg(output_path)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
@@ -214,14 +382,19 @@ async fn run_ffmpeg(
     Ok(())
 }
 
+/// Records `duration` of the device into `output_path`, encoded with `encoding`, with the
+/// capture settings of `device_config`, then sends it for transcription
 pub async fn record_and_transcribe(
     audio_device: Arc<AudioDevice>,
     duration: Duration,
     output_path: PathBuf,
     whisper_sender: UnboundedSender<AudioInput>,
     is_running: Arc<AtomicBool>,
+    device_config: AudioDeviceConfig,
+    encoding: AudioEncoding,
 ) -> Result<PathBuf> {
-    let (cpal_audio_device, config) = get_device_and_config(&audio_device)?;
+    let (cpal_audio_device, config) = get_device_and_config(&audio_device, &device_config)?;
+    let gain = device_config.gain.filter(|gain| *gain != 1.0);
     let sample_rate = config.sample_rate().0;
     let channels = config.channels() as u16;
     debug!(
@@ -245,9 +418,20 @@ pub async fn record_and_transcribe(
             cpal::SampleFormat::F32 => cpal_audio_device.build_input_stream(
                 &config.into(),
                 move |data: &[f32], _: &_| {
-                    if is_running_clone_3.load(Ordering::Relaxed) {
-                        let _ = tx.blocking_send(bytemuck::cast_slice(data).to_vec());
+                    if !is_running_clone_3.load(Ordering::Relaxed) {
+                        return;
                     }
+                    let bytes = match gain {
+                        Some(gain) => {
+                            let samples: Vec<f32> = data
+                                .iter()
+                                .map(|sample| (sample * gain).clamp(-1.0, 1.0))
+                                .collect();
+                            bytemuck::cast_slice(&samples).to_vec()
+                        }
+                        None => bytemuck::cast_slice(data).to_vec(),
+                    };
+                    let _ = tx.blocking_send(bytes);
                 },
                 move |err: StreamError| {
                     error!("An error occurred on the audio stream: {}", err);
@@ -289,6 +473,7 @@ pub async fn record_and_transcribe(
         &output_path_clone,
         is_running_clone_4,
         duration,
+        encoding,
     )
     .await;
 
@@ -304,6 +489,8 @@ pub async fn record_and_transcribe(
     if let Err(e) = whisper_sender.send(AudioInput {
         path: output_path_clone_2.to_str().unwrap().to_string(),
         device: audio_device.to_string(),
+        priority: TranscriptionPriority::Background,
+        preprocessing: device_config.preprocessing(),
     }) {
         error!("Failed to send audio to audio model: {}", e);
     }
@@ -316,65 +503,167 @@ pub fn list_audio_devices() -> Result<Vec<AudioDevice>> {
     let host = cpal::default_host();
     let mut devices = Vec::new();
 
-    for device in host.input_devices()? {
-        if let Ok(name) = device.name() {
-            devices.push(AudioDevice::new(name, DeviceType::Input));
-        }
+    for (name, _) in enumerate_devices(&host, &DeviceType::Input) {
+        devices.push(AudioDevice::new(name, DeviceType::Input));
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        // !HACK macos is suppoed to use special macos feature "display capture"
-        // ! see https://github.com/RustAudio/cpal/pull/894
-        let host = cpal::default_host();
-        // let host = cpal::host_from_id(cpal::HostId::ScreenCaptureKit)?;
-        for device in host.output_devices()? {
-            if let Ok(name) = device.name() {
-                devices.push(AudioDevice::new(name, DeviceType::Output));
-            }
-        }
+    // !HACK macos is suppoed to use special macos feature "display capture"
+    // ! see https://github.com/RustAudio/cpal/pull/894
+    // let host = cpal::host_from_id(cpal::HostId::ScreenCaptureKit)?;
+    for (name, _) in enumerate_devices(&host, &DeviceType::Output) {
+        devices.push(AudioDevice::new(name, DeviceType::Output));
     }
 
-    #[cfg(not(target_os = "macos"))]
-    {
-        for device in host.output_devices()? {
-            if let Ok(name) = device.name() {
-                devices.push(AudioDevice::new(name, DeviceType::Output));
-            }
+    Ok(devices)
+}
+
+/// What a device captures, loopback devices are inputs recording what the computer plays
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioDeviceKind {
+    Input,
+    Output,
+    Loopback,
+}
+
+/// Capabilities of a device, for device pickers
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioDeviceInfo {
+    pub kind: AudioDeviceKind,
+    /// Default device of its type on the system
+    pub is_default: bool,
+    /// Common sample rates the device can capture at, ascending
+    pub sample_rates: Vec<u32>,
+}
+
+const COMMON_SAMPLE_RATES: [u32; 10] = [
+    8000, 11025, 16000, 22050, 32000, 44100, 48000, 88200, 96000, 192000,
+];
+
+/// Devices of `list_audio_devices`, in the same order, with their capabilities
+pub fn list_audio_device_info() -> Result<Vec<(AudioDevice, AudioDeviceInfo)>> {
+    let host = cpal::default_host();
+    let mut devices = Vec::new();
+
+    for device_type in [DeviceType::Input, DeviceType::Output] {
+        let default_name = match device_type {
+            DeviceType::Input => host.default_input_device(),
+            DeviceType::Output => host.default_output_device(),
+        }
+        .and_then(|d| d.name().ok());
+        let mut default_found = false;
+
+        for (name, device) in enumerate_devices(&host, &device_type) {
+            // Only the first of duplicated names
+            let is_default = !default_found && device.name().ok() == default_name;
+            default_found |= is_default;
+            let kind = match device_type {
+                DeviceType::Output => AudioDeviceKind::Output,
+                DeviceType::Input if is_loopback_name(&name) => AudioDeviceKind::Loopback,
+                DeviceType::Input => AudioDeviceKind::Input,
+            };
+            let info = AudioDeviceInfo {
+                kind,
+                is_default,
+                sample_rates: supported_sample_rates(&device),
+            };
+            devices.push((AudioDevice::new(name, device_type.clone()), info));
         }
     }
 
     Ok(devices)
 }
 
-pub fn default_input_device() -> Result<AudioDevice> {
+/// PulseAudio / PipeWire monitors, Windows "Stereo Mix" and virtual drivers like BlackHole
+fn is_loopback_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    [
+        "monitor",
+        "stereo mix",
+        "loopback",
+        "blackhole",
+        "soundflower",
+        "what u hear",
+    ]
+    .iter()
+    .any(|pattern| name.contains(pattern))
+}
+
+fn supported_sample_rates(device: &cpal::Device) -> Vec<u32> {
+    // Output devices are captured with an input config too, see get_device_and_config
+    let mut ranges: Vec<(u32, u32)> = device
+        .supported_input_configs()
+        .map(|configs| {
+            configs
+                .map(|c| (c.min_sample_rate().0, c.max_sample_rate().0))
+                .collect()
+        })
+        .unwrap_or_default();
+    if ranges.is_empty() {
+        ranges = device
+            .supported_output_configs()
+            .map(|configs| {
+                configs
+                    .map(|c| (c.min_sample_rate().0, c.max_sample_rate().0))
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    let mut rates: Vec<u32> = COMMON_SAMPLE_RATES
+        .iter()
+        .copied()
+        .filter(|rate| ranges.iter().any(|(min, max)| min <= rate && rate <= max))
+        .chain(ranges.iter().flat_map(|(min, max)| [*min, *max]))
+        .collect();
+    rates.sort_unstable();
+    rates.dedup();
+    rates
+}
+
+/// Current default device of the type, as listed. Without a default device, as in a Windows
+/// service, the first device of the type is used
+pub fn default_device(device_type: DeviceType) -> Result<AudioDevice> {
     let host = cpal::default_host();
-    let device = host.default_input_device().unwrap();
-    info!("Using default input device: {}", device.name()?);
-    Ok(AudioDevice::new(device.name()?, DeviceType::Input))
+    let default = match device_type {
+        DeviceType::Input => host.default_input_device(),
+        DeviceType::Output => host.default_output_device(),
+    };
+    let default_name = default.and_then(|d| d.name().ok());
+    let devices = enumerate_devices(&host, &device_type);
+    let name = match default_name {
+        // the listed name, which can have a duplicate suffix
+        Some(default_name) => devices
+            .iter()
+            .find(|(_, d)| d.name().ok().as_ref() == Some(&default_name))
+            .map(|(name, _)| name.clone())
+            .unwrap_or(default_name),
+        None => {
+            let (name, _) = devices
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("No {:?} audio device found", device_type))?;
+            warn!("No default {:?} audio device, using {}", device_type, name);
+            name
+        }
+    };
+    Ok(AudioDevice::new(name, device_type))
+}
+
+pub fn default_input_device() -> Result<AudioDevice> {
+    let device = default_device(DeviceType::Input)?;
+    info!("Using default input device: {}", device);
+    Ok(device)
 }
 
 pub fn default_output_device() -> Result<AudioDevice> {
+    // !HACK macos is suppoed to use special macos feature "display capture"
+    // ! see https://github.com/RustAudio/cpal/pull/894
+    // let host = cpal::host_from_id(cpal::HostId::ScreenCaptureKit)?;
+    let device = default_device(DeviceType::Output)?;
     #[cfg(target_os = "macos")]
-    {
-        // !HACK macos is suppoed to use special macos feature "display capture"
-        // ! see https://github.com/RustAudio/cpal/pull/894
-        let host = cpal::default_host();
-        // let host = cpal::host_from_id(cpal::HostId::ScreenCaptureKit)?;
-        let device = host
-            .default_output_device()
-            .ok_or_else(|| anyhow!("No default input device found"))?;
-        info!("Using display capture device: {}", device.name()?);
-        return Ok(AudioDevice::new(device.name()?, DeviceType::Output));
-    }
-
+    info!("Using display capture device: {}", device);
     #[cfg(not(target_os = "macos"))]
-    {
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .ok_or_else(|| anyhow!("No default output device found"))?;
-        info!("Using default output device: {}", device.name()?);
-        return Ok(AudioDevice::new(device.name()?, DeviceType::Output));
-    }
+    info!("Using default output device: {}", device);
+    Ok(device)
 }
@@ -0,0 +1,111 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Container and codec of the audio chunks. Opus takes a fraction of the space of AAC for
+/// speech, FLAC and WAV are lossless.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// AAC in MP4
+    #[default]
+    Mp4,
+    /// Opus in Ogg
+    Opus,
+    Flac,
+    /// 16-bit PCM
+    Wav,
+}
+
+impl AudioFormat {
+    /// Name stored with the audio chunks, also the extension of their files
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp4 => "mp4",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "wav",
+        }
+    }
+
+    /// Format of a chunk by the extension of its file, `None` for an unknown one
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "mp4" | "m4a" => Some(AudioFormat::Mp4),
+            "opus" | "ogg" => Some(AudioFormat::Opus),
+            "flac" => Some(AudioFormat::Flac),
+            "wav" => Some(AudioFormat::Wav),
+            _ => None,
+        }
+    }
+
+    /// Bitrate without `--audio-bitrate`, `None` for the lossless formats
+    pub fn default_bitrate_kbps(&self) -> Option<u32> {
+        match self {
+            AudioFormat::Mp4 => Some(128),
+            AudioFormat::Opus => Some(24),
+            AudioFormat::Flac | AudioFormat::Wav => None,
+        }
+    }
+}
+
+impl FromStr for AudioFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "mp4" | "aac" => Ok(AudioFormat::Mp4),
+            "opus" | "ogg" => Ok(AudioFormat::Opus),
+            "flac" => Ok(AudioFormat::Flac),
+            "wav" => Ok(AudioFormat::Wav),
+            _ => Err(anyhow!("unknown audio format {:?}", s)),
+        }
+    }
+}
+
+/// How the audio chunks are encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioEncoding {
+    pub format: AudioFormat,
+    /// Target bitrate in kbit/s, the format's default when `None`: 128 for AAC and 24 for
+    /// Opus. Ignored by FLAC and WAV.
+    pub bitrate_kbps: Option<u32>,
+}
+
+impl AudioEncoding {
+    pub fn validate(&self) -> Result<()> {
+        match (self.format, self.bitrate_kbps) {
+            // The range of libopus
+            (AudioFormat::Opus, Some(bitrate)) if !(6..=510).contains(&bitrate) => {
+                Err(anyhow!("opus bitrate must be between 6 and 510 kbps"))
+            }
+            (AudioFormat::Mp4, Some(bitrate)) if !(8..=512).contains(&bitrate) => {
+                Err(anyhow!("aac bitrate must be between 8 and 512 kbps"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Output arguments of ffmpeg encoding with these settings, the path excluded
+    pub fn ffmpeg_args(&self) -> Vec<String> {
+        let (codec, container) = match self.format {
+            AudioFormat::Mp4 => ("aac", "mp4"),
+            AudioFormat::Opus => ("libopus", "ogg"),
+            AudioFormat::Flac => ("flac", "flac"),
+            AudioFormat::Wav => ("pcm_s16le", "wav"),
+        };
+        let mut args = vec!["-c:a".to_string(), codec.to_string()];
+        if let Some(default) = self.format.default_bitrate_kbps() {
+            let bitrate = self.bitrate_kbps.unwrap_or(default);
+            args.extend(["-b:a".to_string(), format!("{}k", bitrate)]);
+        }
+        if self.format == AudioFormat::Opus {
+            // Tuned for speech
+            args.extend(["-application".to_string(), "voip".to_string()]);
+        }
+        args.extend(["-f".to_string(), container.to_string()]);
+        args
+    }
+}
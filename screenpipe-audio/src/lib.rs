@@ -1,10 +1,33 @@
+//! Audio recording and speech to text of screenpipe.
+//!
+//! The items re-exported at the crate root are the public API of this crate and follow
+//! semver. The `stt`, `pcm_decode` and `synthetic` modules are implementation details and
+//! can change in any release.
+
+mod cloud_stt;
 mod core;
+mod encoding;
 mod multilingual;
+#[doc(hidden)]
 pub mod pcm_decode;
+mod preprocess;
+#[doc(hidden)]
 pub mod stt;
+#[doc(hidden)]
+pub mod synthetic;
+pub use cloud_stt::{CloudSttConfig, CloudSttProvider, TranscriptionProvider};
 pub use core::{
-    default_input_device, default_output_device, list_audio_devices, parse_audio_device,
-    record_and_transcribe, AudioDevice, DeviceControl,
+    default_device, default_input_device, default_output_device, list_audio_device_info,
+    list_audio_devices, parse_audio_device, record_and_transcribe, AudioDevice, AudioDeviceConfig,
+    AudioDeviceInfo, AudioDeviceKind, DeviceControl, DeviceType,
 };
+pub use encoding::{AudioEncoding, AudioFormat};
 pub use pcm_decode::pcm_decode;
-pub use stt::{create_whisper_channel, stt, AudioInput, TranscriptionResult, WhisperModel};
+pub use preprocess::{preprocess_audio, AudioPreprocessing};
+pub use screenpipe_core::{Gpu, TranscriptionSegment};
+pub use stt::{
+    create_whisper_channel, loaded_whisper_model, stt, AudioInput, Transcript,
+    TranscriptionLanguage, TranscriptionLanguages, TranscriptionPriority, TranscriptionQueue,
+    TranscriptionResult, WhisperModel, WhisperModelConfig, WhisperModelSize,
+};
+pub use synthetic::{list_synthetic_audio_files, play_synthetic_audio, SYNTHETIC_DEVICE_NAME};
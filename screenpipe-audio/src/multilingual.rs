@@ -107,12 +107,21 @@ const LANGUAGES: [(&str, &str); 99] = [
     ("su", "sundanese"),
 ];
 
-/// Returns the token id for the selected language.
+/// Code of a language supported by whisper, from its code or English name, case-insensitive
+pub fn language_code(code_or_name: &str) -> Option<&'static str> {
+    let code_or_name = code_or_name.trim().to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(code, name)| *code == code_or_name || *name == code_or_name)
+        .map(|(code, _)| *code)
+}
+
+/// Returns the token id and the code of the detected language.
 pub fn detect_language(
     model: &mut Model,
     tokenizer: &Tokenizer,
     mel: &Tensor,
-) -> Result<u32> {
+) -> Result<(u32, &'static str)> {
     let (_bsize, _, seq_len) = mel.dims3()?;
     let mel = mel.narrow(
         2,
@@ -140,5 +149,5 @@ pub fn detect_language(
     }
     let language = super::stt::token_id(tokenizer, &format!("<|{}|>", probs[0].0 .0))?;
     info!("detected language: {:?}", probs[0].0);
-    Ok(language)
+    Ok((language, probs[0].0 .0))
 }
\ No newline at end of file
@@ -1,4 +1,8 @@
+use anyhow::anyhow;
 use log::debug;
+use screenpipe_core::find_ffmpeg_path;
+use std::path::Path;
+use std::process::Command;
 use symphonia::core::audio::{AudioBufferRef, Signal};
 use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
 use symphonia::core::conv::FromSample;
@@ -11,8 +15,24 @@ where
     samples.extend(data.chan(0).iter().map(|v| f32::from_sample(*v)))
 }
 
-pub fn pcm_decode<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<(Vec<f32>, u32)> {
-    debug!("Starting PCM decoding for {:?}", path.as_ref());
+/// Sample rate of the chunks decoded by ffmpeg, the one Whisper takes
+const FFMPEG_SAMPLE_RATE: u32 = 16000;
+
+/// Samples of the first channel of the audio file at `path`, with their sample rate. The
+/// codecs Symphonia lacks, e.g. Opus, are decoded with ffmpeg.
+pub fn pcm_decode<P: AsRef<Path>>(path: P) -> anyhow::Result<(Vec<f32>, u32)> {
+    symphonia_decode(path.as_ref()).or_else(|e| {
+        debug!(
+            "Symphonia can't decode {:?}, decoding with ffmpeg: {}",
+            path.as_ref(),
+            e
+        );
+        ffmpeg_decode(path.as_ref())
+    })
+}
+
+fn symphonia_decode(path: &Path) -> anyhow::Result<(Vec<f32>, u32)> {
+    debug!("Starting PCM decoding for {:?}", path);
     // Open the media source.
     let src = std::fs::File::open(path)?;
 
@@ -36,15 +56,13 @@ pub fn pcm_decode<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<(Vec<f32
         .tracks()
         .iter()
         .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-        .expect("no supported audio tracks");
+        .ok_or_else(|| anyhow!("no supported audio tracks"))?;
 
     // Use the default options for the decoder.
     let dec_opts: DecoderOptions = Default::default();
 
     // Create a decoder for the track.
-    let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &dec_opts)
-        .expect("unsupported codec");
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &dec_opts)?;
     let track_id = track.id;
     let sample_rate = track.codec_params.sample_rate.unwrap_or(0);
     let mut pcm_data = Vec::new();
@@ -75,3 +93,27 @@ pub fn pcm_decode<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<(Vec<f32
     }
     Ok((pcm_data, sample_rate))
 }
+
+fn ffmpeg_decode(path: &Path) -> anyhow::Result<(Vec<f32>, u32)> {
+    let ffmpeg = find_ffmpeg_path().ok_or_else(|| anyhow!("ffmpeg not found"))?;
+    let output = Command::new(ffmpeg)
+        .arg("-i")
+        .arg(path)
+        .args(["-f", "f32le", "-ac", "1", "-ar"])
+        .arg(FFMPEG_SAMPLE_RATE.to_string())
+        .arg("pipe:1")
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ffmpeg failed to decode {:?}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    let pcm_data = output
+        .stdout
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    Ok((pcm_data, FFMPEG_SAMPLE_RATE))
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use log::debug;
+use nnnoiseless::DenoiseState;
+
+use crate::stt::resample;
+
+/// Cleanup of the audio of a device before it's transcribed, the recorded file is kept as is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AudioPreprocessing {
+    /// RNNoise, removes keyboard clicks, fan hum and other background noise
+    pub noise_suppression: bool,
+    /// Brings quiet and loud speech to the same level
+    pub auto_gain: bool,
+}
+
+impl AudioPreprocessing {
+    pub fn is_enabled(&self) -> bool {
+        self.noise_suppression || self.auto_gain
+    }
+}
+
+/// Sample rate RNNoise works at
+const DENOISE_SAMPLE_RATE: u32 = 48000;
+
+/// Level automatic gain control brings speech to, -20 dBFS
+const AGC_TARGET_RMS: f32 = 0.1;
+/// Most a frame is amplified, +20 dB
+const AGC_MAX_GAIN: f32 = 10.0;
+/// Frames below this level, -50 dBFS, are noise and keep the current gain
+const AGC_NOISE_FLOOR: f32 = 0.003;
+
+/// Samples cleaned up by `preprocessing`, with their sample rate. Noise suppression
+/// resamples to 48 kHz.
+pub fn preprocess_audio(
+    samples: Vec<f32>,
+    sample_rate: u32,
+    preprocessing: AudioPreprocessing,
+) -> Result<(Vec<f32>, u32)> {
+    let (mut samples, sample_rate) = if preprocessing.noise_suppression {
+        suppress_noise(samples, sample_rate)?
+    } else {
+        (samples, sample_rate)
+    };
+    // After noise suppression so the noise isn't amplified
+    if preprocessing.auto_gain {
+        auto_gain(&mut samples, sample_rate);
+    }
+    Ok((samples, sample_rate))
+}
+
+fn suppress_noise(samples: Vec<f32>, sample_rate: u32) -> Result<(Vec<f32>, u32)> {
+    let samples = if sample_rate == DENOISE_SAMPLE_RATE {
+        samples
+    } else {
+        debug!(
+            "Resampling from {} Hz to {} Hz for noise suppression",
+            sample_rate, DENOISE_SAMPLE_RATE
+        );
+        resample(samples, sample_rate, DENOISE_SAMPLE_RATE)?
+    };
+
+    // RNNoise takes samples in the range of i16
+    let scale = i16::MAX as f32;
+    let mut state = DenoiseState::new();
+    let mut input = [0.0; DenoiseState::FRAME_SIZE];
+    let mut output = [0.0; DenoiseState::FRAME_SIZE];
+    let mut denoised = Vec::with_capacity(samples.len());
+    for frame in samples.chunks(DenoiseState::FRAME_SIZE) {
+        // The last frame is padded with silence
+        input.fill(0.0);
+        for (scaled, sample) in input.iter_mut().zip(frame) {
+            *scaled = sample * scale;
+        }
+        state.process_frame(&mut output, &input);
+        denoised.extend(output[..frame.len()].iter().map(|sample| sample / scale));
+    }
+    Ok((denoised, DENOISE_SAMPLE_RATE))
+}
+
+/// Gain following the level of every 10 ms frame, lowered fast on loud speech so it doesn't
+/// clip and raised slowly so pauses aren't pumped up
+fn auto_gain(samples: &mut [f32], sample_rate: u32) {
+    let frame_size = (sample_rate as usize / 100).max(1);
+    let mut gain = 1.0;
+    for frame in samples.chunks_mut(frame_size) {
+        let rms =
+            (frame.iter().map(|sample| sample * sample).sum::<f32>() / frame.len() as f32).sqrt();
+        let target = if rms > AGC_NOISE_FLOOR {
+            (AGC_TARGET_RMS / rms).clamp(1.0 / AGC_MAX_GAIN, AGC_MAX_GAIN)
+        } else {
+            gain
+        };
+        let rate = if target < gain { 0.5 } else { 0.05 };
+        let next = gain + (target - gain) * rate;
+        // Ramped over the frame, a step would click
+        let len = frame.len() as f32;
+        for (i, sample) in frame.iter_mut().enumerate() {
+            let g = gain + (next - gain) * i as f32 / len;
+            *sample = (*sample * g).clamp(-1.0, 1.0);
+        }
+        gain = next;
+    }
+}
@@ -1,3 +1,7 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{Error as E, Result};
@@ -6,20 +10,116 @@ use candle_nn::{ops::softmax, VarBuilder};
 use hf_hub::{api::sync::Api, Repo, RepoType};
 use log::{debug, error, info};
 use rand::{distributions::Distribution, SeedableRng};
+use screenpipe_core::{select_device, set_accelerator, Gpu, TranscriptionSegment};
+use serde::{Deserialize, Serialize};
 use tokenizers::Tokenizer;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::{watch, Semaphore};
 
 use candle_transformers::models::whisper::{self as m, audio, Config};
 use rubato::{
     Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
 };
 
-use crate::{multilingual, pcm_decode::pcm_decode};
+use crate::{
+    multilingual, pcm_decode::pcm_decode, preprocess_audio, AudioPreprocessing, CloudSttConfig,
+    TranscriptionProvider,
+};
 
 use webrtc_vad::{Vad, VadMode};
 
-use hound::{WavSpec, WavWriter};
-use std::io::Cursor;
+/// Size of the Whisper model, bigger ones are more accurate and slower
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WhisperModelSize {
+    #[default]
+    Tiny,
+    Base,
+    Small,
+    Medium,
+    LargeV3,
+}
+
+impl WhisperModelSize {
+    pub const ALL: [WhisperModelSize; 5] = [
+        WhisperModelSize::Tiny,
+        WhisperModelSize::Base,
+        WhisperModelSize::Small,
+        WhisperModelSize::Medium,
+        WhisperModelSize::LargeV3,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WhisperModelSize::Tiny => "tiny",
+            WhisperModelSize::Base => "base",
+            WhisperModelSize::Small => "small",
+            WhisperModelSize::Medium => "medium",
+            WhisperModelSize::LargeV3 => "large-v3",
+        }
+    }
+}
+
+impl FromStr for WhisperModelSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WhisperModelSize::ALL
+            .into_iter()
+            .find(|size| size.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown whisper model: {}", s))
+    }
+}
+
+impl fmt::Display for WhisperModelSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whisper model to transcribe with, downloaded from Hugging Face on first use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct WhisperModelConfig {
+    pub size: WhisperModelSize,
+    /// 8-bit quantized weights, smaller and faster on CPU, only published for tiny
+    #[serde(default)]
+    pub quantized: bool,
+    /// GPU to load the model on, kept when the size changes through `PUT /audio/config`
+    #[serde(default)]
+    pub gpu: Gpu,
+}
+
+impl WhisperModelConfig {
+    /// Fails on a model without weights to download
+    pub fn validate(&self) -> Result<()> {
+        if self.quantized && self.size != WhisperModelSize::Tiny {
+            anyhow::bail!(
+                "no quantized weights for whisper {}, only for tiny",
+                self.size
+            );
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for WhisperModelConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.quantized {
+            true => write!(f, "{} (quantized)", self.size),
+            false => write!(f, "{}", self.size),
+        }
+    }
+}
+
+/// Model the whisper channel transcribes with, may differ from the configured one while it
+/// loads or after it failed to
+static LOADED_WHISPER_MODEL: Mutex<Option<WhisperModelConfig>> = Mutex::new(None);
+
+/// Whisper model transcribing the inputs of `create_whisper_channel`, `None` before it's
+/// loaded
+pub fn loaded_whisper_model() -> Option<WhisperModelConfig> {
+    *LOADED_WHISPER_MODEL.lock().unwrap()
+}
 
 #[derive(Clone)]
 pub struct WhisperModel {
@@ -29,16 +129,34 @@ pub struct WhisperModel {
 }
 
 impl WhisperModel {
+    /// The default model, whisper tiny
     pub fn new() -> Result<Self> {
-        debug!("Initializing WhisperModel");
-        let device = Device::new_metal(0).unwrap_or(Device::new_cuda(0).unwrap_or(Device::Cpu));
-        info!("device = {:?}", device);
+        Self::load(&WhisperModelConfig::default())
+    }
+
+    /// Downloads the model of `model_config` unless it's cached and loads it
+    pub fn load(model_config: &WhisperModelConfig) -> Result<Self> {
+        model_config.validate()?;
+        debug!("Initializing WhisperModel {}", model_config);
+        let (device, accelerator) = select_device(model_config.gpu)?;
+        info!("Loading whisper {} on {:?}", model_config, accelerator);
 
         debug!("Fetching model files");
-        let (config_filename, tokenizer_filename, weights_filename) = {
-            let api = Api::new()?;
+        let api = Api::new()?;
+        let (config_filename, tokenizer_filename, weights_filename) = if model_config.quantized {
+            let repo = api.repo(Repo::with_revision(
+                "lmz/candle-whisper".to_string(),
+                RepoType::Model,
+                "main".to_string(),
+            ));
+            let name = model_config.size.as_str();
+            let config = repo.get(&format!("config-{}.json", name))?;
+            let tokenizer = repo.get(&format!("tokenizer-{}.json", name))?;
+            let model = repo.get(&format!("model-{}-q80.gguf", name))?;
+            (config, tokenizer, model)
+        } else {
             let repo = api.repo(Repo::with_revision(
-                "openai/whisper-tiny".to_string(),
+                format!("openai/whisper-{}", model_config.size),
                 RepoType::Model,
                 "main".to_string(),
             ));
@@ -53,10 +171,20 @@ impl WhisperModel {
         let tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
 
         debug!("Loading model weights");
-        let vb =
-            unsafe { VarBuilder::from_mmaped_safetensors(&[weights_filename], m::DTYPE, &device)? };
-        let model = Model::Normal(m::model::Whisper::load(&vb, config.clone())?);
+        let model = if model_config.quantized {
+            let vb = candle_transformers::quantized_var_builder::VarBuilder::from_gguf(
+                &weights_filename,
+                &device,
+            )?;
+            Model::Quantized(m::quantized_model::Whisper::load(&vb, config)?)
+        } else {
+            let vb = unsafe {
+                VarBuilder::from_mmaped_safetensors(&[weights_filename], m::DTYPE, &device)?
+            };
+            Model::Normal(m::model::Whisper::load(&vb, config)?)
+        };
         debug!("WhisperModel initialization complete");
+        set_accelerator("whisper", accelerator);
         Ok(Self {
             model,
             tokenizer,
@@ -122,6 +250,8 @@ struct Segment {
     start: f64,
     duration: f64,
     dr: DecodingResult,
+    /// Text between two timestamp tokens, timed from the start of the input
+    pieces: Vec<TranscriptionSegment>,
 }
 
 struct Decoder<'a> {
@@ -329,10 +459,11 @@ impl<'a> Decoder<'a> {
                 info!("no speech detected, skipping {seek} {dr:?}");
                 continue;
             }
-            let segment = Segment {
+            let mut segment = Segment {
                 start: time_offset,
                 duration: segment_duration,
                 dr,
+                pieces: Vec::new(),
             };
             if self.timestamps {
                 info!(
@@ -354,6 +485,11 @@ impl<'a> Decoder<'a> {
                                 .decode(&tokens_to_decode, true)
                                 .map_err(E::msg)?;
                             info!("  {:.1}s-{:.1}s: {}", prev_timestamp_s, timestamp_s, text);
+                            segment.pieces.push(TranscriptionSegment {
+                                start: segment.start + prev_timestamp_s as f64,
+                                end: segment.start + timestamp_s as f64,
+                                text: text.trim().to_string(),
+                            });
                             tokens_to_decode.clear()
                         }
                         prev_timestamp_s = timestamp_s;
@@ -368,6 +504,11 @@ impl<'a> Decoder<'a> {
                         .map_err(E::msg)?;
                     if !text.is_empty() {
                         info!("  {:.1}s-...: {}", prev_timestamp_s, text);
+                        segment.pieces.push(TranscriptionSegment {
+                            start: segment.start + prev_timestamp_s as f64,
+                            end: segment.start + segment.duration,
+                            text: text.trim().to_string(),
+                        });
                     }
                     tokens_to_decode.clear()
                 }
@@ -402,84 +543,69 @@ enum Task {
     Translate,
 }
 
-use reqwest::blocking::Client;
-use serde_json::Value;
-
-// Replace the get_deepgram_api_key function with this:
-fn get_deepgram_api_key() -> String {
-    "7ed2a159a094337b01fd8178b914b7ae0e77822d".to_string()
+/// Transcript of an audio file, with `cloud_stt` when given and whisper otherwise
+pub fn stt(
+    file_path: &str,
+    whisper_model: &WhisperModel,
+    cloud_stt: Option<&CloudSttConfig>,
+) -> Result<String> {
+    debug!("Starting speech to text for file: {}", file_path);
+    let cloud_provider = cloud_stt.map(CloudSttConfig::provider).transpose()?;
+    debug!("Decoding PCM data");
+    let (pcm_data, sample_rate) = pcm_decode(file_path)?;
+    stt_pcm(
+        pcm_data,
+        sample_rate,
+        whisper_model,
+        cloud_provider.as_deref(),
+        &TranscriptionLanguage::Auto,
+    )
+    .map(|(transcript, _)| transcript.text)
 }
 
-// TODO: this should use async reqwest not blocking, cause crash issue because all our code is async
-fn transcribe_with_deepgram(api_key: &str, audio_data: &[f32]) -> Result<String> {
-    debug!("Starting Deepgram transcription");
-    let client = Client::new();
-
-    // Create a WAV file in memory
-    let mut cursor = Cursor::new(Vec::new());
-    {
-        let spec = WavSpec {
-            channels: 1,
-            sample_rate: 16000,
-            bits_per_sample: 32,
-            sample_format: hound::SampleFormat::Float,
-        };
-        let mut writer = WavWriter::new(&mut cursor, spec)?;
-        for &sample in audio_data {
-            writer.write_sample(sample)?;
-        }
-        writer.finalize()?;
+/// Seconds of audio of decoded samples
+fn pcm_duration(pcm_data: &[f32], sample_rate: u32) -> f64 {
+    if sample_rate == 0 {
+        return 0.0;
     }
+    pcm_data.len() as f64 / sample_rate as f64
+}
 
-    // Get the WAV data from the cursor
-    let wav_data = cursor.into_inner();
-
-    let response = client.post("https://api.deepgram.com/v1/listen?model=nova-2&smart_format=true")
-        .header("Content-Type", "audio/wav")
-        .header("Authorization", format!("Token {}", api_key))
-        .body(wav_data)
-        .send();
-
-    match response {
-        Ok(resp) => {
-            debug!("Received response from Deepgram API");
-            match resp.json::<Value>() {
-                Ok(result) => {
-                    debug!("Successfully parsed JSON response");
-                    if let Some(err_code) = result.get("err_code") {
-                        error!("Deepgram API error code: {:?}, result: {:?}", err_code, result);
-                        return Err(anyhow::anyhow!("Deepgram API error: {:?}", result));
-                    }
-                    let transcription = result["results"]["channels"][0]["alternatives"][0]["transcript"]
-                        .as_str()
-                        .unwrap_or("");
-                    
-                    if transcription.is_empty() {
-                        info!("Transcription is empty. Full response: {:?}", result);
-                    } else {
-                        info!("Transcription successful. Length: {} characters", transcription.len());
-                    }
-                    
-                    Ok(transcription.to_string())
-                },
-                Err(e) => {
-                    error!("Failed to parse JSON response: {:?}", e);
-                    Err(anyhow::anyhow!("Failed to parse JSON response: {:?}", e))
-                }
-            }
-        },
-        Err(e) => {
-            error!("Failed to send request to Deepgram API: {:?}", e);
-            Err(anyhow::anyhow!("Failed to send request to Deepgram API: {:?}", e))
-        }
+/// Transcribed text with the language spoken and the timing of its segments
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transcript {
+    pub text: String,
+    /// Code of the language spoken, e.g. "en"
+    pub language: Option<String>,
+    /// Seconds from the start of the transcribed audio, empty when the engine doesn't time
+    /// its output
+    pub segments: Vec<TranscriptionSegment>,
+}
+
+/// Seconds into the chunk of a time in its speech, the VAD having dropped the frames
+/// without speech before transcription
+pub fn chunk_time(speech_seconds: f64, kept_frames: &[usize], frame_size: usize) -> f64 {
+    let frame_seconds = frame_size as f64 / m::SAMPLE_RATE as f64;
+    let speech_frames = speech_seconds.max(0.0) / frame_seconds;
+    let position = speech_frames.floor() as usize;
+    match kept_frames.get(position) {
+        Some(&frame) => (frame as f64 + speech_frames - position as f64) * frame_seconds,
+        // Past the speech, e.g. the end of the last segment
+        None => kept_frames
+            .last()
+            .map_or(0.0, |&frame| (frame + 1) as f64 * frame_seconds),
     }
 }
 
-pub fn stt(file_path: &str, whisper_model: &WhisperModel, cloud_audio: bool) -> Result<String> {
-    debug!("Starting speech to text for file: {}", file_path);
+/// Transcript of the chunk and name of the engine that transcribed it
+fn stt_pcm(
+    mut pcm_data: Vec<f32>,
+    sample_rate: u32,
+    whisper_model: &WhisperModel,
+    cloud_provider: Option<&dyn TranscriptionProvider>,
+    language: &TranscriptionLanguage,
+) -> Result<(Transcript, &'static str)> {
     let model = &whisper_model.model;
-    let tokenizer = &whisper_model.tokenizer;
-    let device = &whisper_model.device;
 
     debug!("Loading mel filters");
     let mel_bytes = match model.config().num_mel_bins {
@@ -490,8 +616,6 @@ pub fn stt(file_path: &str, whisper_model: &WhisperModel, cloud_audio: bool) ->
     let mut mel_filters = vec![0f32; mel_bytes.len() / 4];
     <byteorder::LittleEndian as byteorder::ByteOrder>::read_f32_into(mel_bytes, &mut mel_filters);
 
-    debug!("Decoding PCM data");
-    let (mut pcm_data, sample_rate) = pcm_decode(file_path)?;
     if sample_rate != m::SAMPLE_RATE as u32 {
         info!(
             "Resampling from {} Hz to {} Hz",
@@ -510,6 +634,7 @@ pub fn stt(file_path: &str, whisper_model: &WhisperModel, cloud_audio: bool) ->
     debug!("VAD: Filtering out non-speech segments");
     let frame_size = 160; // 10ms frame size for 16kHz audio
     let mut speech_frames = Vec::new();
+    let mut kept_frames = Vec::new();
     for (frame_index, chunk) in pcm_data.chunks(frame_size).enumerate() {
         // Convert f32 to i16
         let i16_chunk: Vec<i16> = chunk.iter().map(|&x| (x * 32767.0) as i16).collect();
@@ -518,6 +643,7 @@ pub fn stt(file_path: &str, whisper_model: &WhisperModel, cloud_audio: bool) ->
                 if is_voice {
                     // debug!("VAD: Speech detected in frame {}", frame_index);
                     speech_frames.extend_from_slice(chunk);
+                    kept_frames.push(frame_index);
                 } else {
                     // debug!("VAD: Non-speech frame {} filtered out", frame_index);
                 }
@@ -535,108 +661,105 @@ pub fn stt(file_path: &str, whisper_model: &WhisperModel, cloud_audio: bool) ->
     // If no speech frames detected, skip processing
     if speech_frames.is_empty() {
         debug!("No speech detected using VAD, skipping audio processing");
-        return Ok("".to_string()); // Return an empty string or consider a more specific "no speech" indicator
+        return Ok((Transcript::default(), WHISPER_ENGINE)); // Return an empty string or consider a more specific "no speech" indicator
     }
 
     debug!("Using {} speech frames out of {} total frames", speech_frames.len() / frame_size, pcm_data.len() / frame_size);
 
-    if cloud_audio {
-        // Deepgram implementation
-        let api_key = get_deepgram_api_key();
-        match transcribe_with_deepgram(&api_key, &speech_frames) {
-            Ok(transcription) => Ok(transcription),
-            Err(e) => {
-                error!("Deepgram transcription failed, falling back to Whisper: {:?}", e);
-                // Existing Whisper implementation
-                debug!("Converting PCM to mel spectrogram");
-                let mel = audio::pcm_to_mel(&model.config(), &speech_frames, &mel_filters);
-                let mel_len = mel.len();
-                debug!("Creating tensor from mel spectrogram");
-                let mel = Tensor::from_vec(
-                    mel,
-                    (
-                        1,
-                        model.config().num_mel_bins,
-                        mel_len / model.config().num_mel_bins,
-                    ),
-                    &device,
-                )?;
-
-                debug!("Detecting language");
-                let language_token = Some(multilingual::detect_language(
-                    &mut model.clone(),
-                    &tokenizer,
-                    &mel,
-                )?);
-                let mut model = model.clone();
-                debug!("Initializing decoder");
-                let mut dc = Decoder::new(
-                    &mut model,
-                    tokenizer,
-                    42,
-                    &device,
-                    language_token,
-                    Some(Task::Transcribe),
-                    true,
-                    false,
-                )?;
-                debug!("Starting decoding process");
-                let segments = dc.run(&mel)?;
-                debug!("Decoding complete");
-                Ok(segments
-                    .iter()
-                    .map(|s| s.dr.text.clone())
-                    .collect::<Vec<String>>()
-                    .join("\n"))
-            }
+    // Engines time their output in the speech they were given
+    let in_chunk = |mut transcript: Transcript| {
+        for segment in &mut transcript.segments {
+            segment.start = chunk_time(segment.start, &kept_frames, frame_size);
+            segment.end = chunk_time(segment.end, &kept_frames, frame_size);
         }
-    } else {
-        // Existing Whisper implementation
-        debug!("Starting Whisper transcription");
-        debug!("Converting PCM to mel spectrogram");
-        let mel = audio::pcm_to_mel(&model.config(), &speech_frames, &mel_filters);
-        let mel_len = mel.len();
-        debug!("Creating tensor from mel spectrogram");
-        let mel = Tensor::from_vec(
-            mel,
-            (
-                1,
-                model.config().num_mel_bins,
-                mel_len / model.config().num_mel_bins,
+        transcript
+    };
+
+    if let Some(provider) = cloud_provider {
+        match provider.transcribe(&speech_frames, language) {
+            Ok(transcript) => return Ok((in_chunk(transcript), provider.name())),
+            Err(e) => error!(
+                "{} transcription failed, falling back to Whisper: {:?}",
+                provider.name(),
+                e
             ),
-            &device,
-        )?;
-
-        debug!("Detecting language");
-        let language_token = Some(multilingual::detect_language(
-            &mut model.clone(),
-            &tokenizer,
-            &mel,
-        )?);
-        let mut model = model.clone();
-        debug!("Initializing decoder");
-        let mut dc = Decoder::new(
-            &mut model,
-            tokenizer,
-            42,
-            &device,
-            language_token,
-            Some(Task::Transcribe),
-            true,
-            false,
-        )?;
-        debug!("Starting decoding process");
-        let segments = dc.run(&mel)?;
-        debug!("Decoding complete");
-        Ok(segments
-            .iter()
-            .map(|s| s.dr.text.clone())
-            .collect::<Vec<String>>()
-            .join("\n"))
+        }
     }
+    debug!("Starting Whisper transcription");
+    let transcript =
+        transcribe_with_whisper(&speech_frames, &mel_filters, whisper_model, language)?;
+    Ok((in_chunk(transcript), WHISPER_ENGINE))
 }
 
-fn resample(input: Vec<f32>, from_sample_rate: u32, to_sample_rate: u32) -> Result<Vec<f32>> {
+/// Transcription engine of what the local model transcribed
+const WHISPER_ENGINE: &str = "Whisper";
+
+/// Transcript in the language detected if `language` is auto
+fn transcribe_with_whisper(
+    speech_frames: &[f32],
+    mel_filters: &[f32],
+    whisper_model: &WhisperModel,
+    language: &TranscriptionLanguage,
+) -> Result<Transcript> {
+    let model = &whisper_model.model;
+    let tokenizer = &whisper_model.tokenizer;
+    let device = &whisper_model.device;
+
+    debug!("Converting PCM to mel spectrogram");
+    let mel = audio::pcm_to_mel(&model.config(), speech_frames, mel_filters);
+    let mel_len = mel.len();
+    debug!("Creating tensor from mel spectrogram");
+    let mel = Tensor::from_vec(
+        mel,
+        (
+            1,
+            model.config().num_mel_bins,
+            mel_len / model.config().num_mel_bins,
+        ),
+        &device,
+    )?;
+
+    let (language_token, language_code) = match language {
+        TranscriptionLanguage::Auto => {
+            debug!("Detecting language");
+            multilingual::detect_language(&mut model.clone(), &tokenizer, &mel)?
+        }
+        TranscriptionLanguage::Fixed(code) => {
+            (token_id(tokenizer, &format!("<|{}|>", code))?, *code)
+        }
+    };
+    let mut model = model.clone();
+    debug!("Initializing decoder");
+    let mut dc = Decoder::new(
+        &mut model,
+        tokenizer,
+        42,
+        &device,
+        Some(language_token),
+        Some(Task::Transcribe),
+        true,
+        false,
+    )?;
+    debug!("Starting decoding process");
+    let segments = dc.run(&mel)?;
+    debug!("Decoding complete");
+    let transcription = segments
+        .iter()
+        .map(|s| s.dr.text.clone())
+        .collect::<Vec<String>>()
+        .join("\n");
+    Ok(Transcript {
+        text: transcription,
+        language: Some(language_code.to_string()),
+        segments: segments.into_iter().flat_map(|s| s.pieces).collect(),
+    })
+}
+
+pub(crate) fn resample(
+    input: Vec<f32>,
+    from_sample_rate: u32,
+    to_sample_rate: u32,
+) -> Result<Vec<f32>> {
     debug!("Resampling audio");
     let params = SincInterpolationParameters {
         sinc_len: 256,
@@ -661,10 +784,115 @@ fn resample(input: Vec<f32>, from_sample_rate: u32, to_sample_rate: u32) -> Resu
     Ok(waves_out.into_iter().next().unwrap())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TranscriptionPriority {
+    /// Continuous recording chunks
+    #[default]
+    Background,
+    /// Requested by a user action (dictation, markers, ingestion), jumps ahead of the backlog
+    OnDemand,
+}
+
 #[derive(Debug, Clone)]
 pub struct AudioInput {
     pub path: String,
     pub device: String,
+    pub priority: TranscriptionPriority,
+    /// Applied to the decoded audio before it's transcribed
+    pub preprocessing: AudioPreprocessing,
+}
+
+/// Language audio is transcribed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TranscriptionLanguage {
+    /// Detected by whisper (or the cloud provider) for every chunk
+    #[default]
+    Auto,
+    /// Whisper language code, e.g. "en"
+    Fixed(&'static str),
+}
+
+impl FromStr for TranscriptionLanguage {
+    type Err = String;
+
+    /// "auto", or a language code or English name supported by whisper
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("auto") {
+            return Ok(TranscriptionLanguage::Auto);
+        }
+        multilingual::language_code(s)
+            .map(TranscriptionLanguage::Fixed)
+            .ok_or_else(|| format!("unknown language: {}", s))
+    }
+}
+
+impl fmt::Display for TranscriptionLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptionLanguage::Auto => write!(f, "auto"),
+            TranscriptionLanguage::Fixed(code) => write!(f, "{}", code),
+        }
+    }
+}
+
+/// Transcription language of every device, by device name
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptionLanguages {
+    /// Used by the devices without their own language
+    pub default: TranscriptionLanguage,
+    pub devices: HashMap<String, TranscriptionLanguage>,
+}
+
+impl TranscriptionLanguages {
+    pub fn for_device(&self, device: &str) -> TranscriptionLanguage {
+        self.devices.get(device).copied().unwrap_or(self.default)
+    }
+}
+
+/// Number of on-demand inputs transcribed in a row before one background input
+/// is let through, so the continuous backlog still drains under on-demand load.
+pub const MAX_ON_DEMAND_STREAK: usize = 4;
+
+/// Two-lane queue feeding the whisper worker.
+#[derive(Default)]
+pub struct TranscriptionQueue {
+    on_demand: VecDeque<AudioInput>,
+    background: VecDeque<AudioInput>,
+    on_demand_streak: usize,
+}
+
+impl TranscriptionQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, input: AudioInput) {
+        match input.priority {
+            TranscriptionPriority::OnDemand => self.on_demand.push_back(input),
+            TranscriptionPriority::Background => self.background.push_back(input),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<AudioInput> {
+        let take_background = self.on_demand.is_empty()
+            || (self.on_demand_streak >= MAX_ON_DEMAND_STREAK && !self.background.is_empty());
+
+        if take_background {
+            self.on_demand_streak = 0;
+            self.background.pop_front()
+        } else {
+            self.on_demand_streak += 1;
+            self.on_demand.pop_front()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.on_demand.len() + self.background.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -673,12 +901,62 @@ pub struct TranscriptionResult {
     pub transcription: Option<String>,
     pub timestamp: u64,
     pub error: Option<String>,
+    /// Seconds of audio in the input, 0 when it could not be decoded
+    pub duration: f64,
+    /// Code of the language spoken, detected unless the device has a fixed language
+    pub language: Option<String>,
+    /// Engine that transcribed the input, e.g. "Whisper" or "Deepgram"
+    pub engine: String,
+    /// Timing of the transcription in the input, empty when the engine doesn't time it
+    pub segments: Vec<TranscriptionSegment>,
 }
-pub async fn create_whisper_channel(cloud_audio: bool) -> Result<(
+/// Transcribes the inputs sent to the returned sender, with `cloud_stt` when given and the
+/// local whisper model otherwise or when the cloud fails
+pub async fn create_whisper_channel(
+    cloud_stt: Option<CloudSttConfig>,
+    transcription_concurrency: usize,
+    languages: TranscriptionLanguages,
+    mut model_config: watch::Receiver<WhisperModelConfig>,
+) -> Result<(
     UnboundedSender<AudioInput>,
     UnboundedReceiver<TranscriptionResult>,
 )> {
-    let whisper_model = WhisperModel::new()?;
+    let cloud_provider = cloud_stt
+        .as_ref()
+        .map(CloudSttConfig::provider)
+        .transpose()?;
+    if let Some(cloud_stt) = &cloud_stt {
+        info!("Transcribing with {}", cloud_stt.provider);
+    }
+    let initial_config = *model_config.borrow_and_update();
+    let (model_sender, model_receiver) =
+        watch::channel(Arc::new(WhisperModel::load(&initial_config)?));
+    *LOADED_WHISPER_MODEL.lock().unwrap() = Some(initial_config);
+    // Loads a newly configured model in the background and swaps it in, the inputs being
+    // transcribed finish with the previous one
+    tokio::spawn(async move {
+        while model_config.changed().await.is_ok() {
+            if model_sender.is_closed() {
+                break;
+            }
+            let config = *model_config.borrow_and_update();
+            info!("Loading whisper model {}", config);
+            match tokio::task::spawn_blocking(move || WhisperModel::load(&config)).await {
+                Ok(Ok(model)) => {
+                    model_sender.send_replace(Arc::new(model));
+                    *LOADED_WHISPER_MODEL.lock().unwrap() = Some(config);
+                    info!("Now transcribing with whisper {}", config);
+                }
+                Ok(Err(e)) => error!(
+                    "Failed to load whisper model {}, keeping the current one: {}",
+                    config, e
+                ),
+                Err(e) => error!("Failed to load whisper model {}: {}", config, e),
+            }
+        }
+    });
+    // Bounds the number of files transcribed at the same time
+    let permits = Arc::new(Semaphore::new(transcription_concurrency.max(1)));
     let (input_sender, mut input_receiver): (
         UnboundedSender<AudioInput>,
         UnboundedReceiver<AudioInput>,
@@ -689,37 +967,87 @@ pub async fn create_whisper_channel(cloud_audio: bool) -> Result<(
     ) = unbounded_channel();
 
     tokio::spawn(async move {
+        let mut queue = TranscriptionQueue::new();
         loop {
-            tokio::select! {
-                Some(input) = input_receiver.recv() => {
-                    let timestamp = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .expect("Time went backwards")
-                        .as_secs();
-
-                    let result = stt(&input.path, &whisper_model, cloud_audio);
-
-                    let transcription_result = match result {
-                        Ok(transcription) => TranscriptionResult {
-                            input: input.clone(),
-                            transcription: Some(transcription),
-                            timestamp,
-                            error: None,
-                        },
-                        Err(e) => TranscriptionResult {
-                            input: input.clone(),
-                            transcription: None,
-                            timestamp,
-                            error: Some(e.to_string()),
-                        },
-                    };
-
-                    if output_sender.send(transcription_result).is_err() {
-                        break;
-                    }
-                }
-                else => break,
+            let permit = match Arc::clone(&permits).acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => break,
+            };
+            // Pull everything pending so on-demand inputs can jump ahead of the backlog
+            while let Ok(input) = input_receiver.try_recv() {
+                queue.push(input);
+            }
+            let input = match queue.pop() {
+                Some(input) => input,
+                None => match input_receiver.recv().await {
+                    Some(input) => input,
+                    None => break,
+                },
+            };
+            debug!(
+                "Transcribing {} ({:?}), {} queued",
+                input.path,
+                input.priority,
+                queue.len()
+            );
+
+            if output_sender.is_closed() {
+                break;
             }
+
+            let whisper_model = Arc::clone(&model_receiver.borrow());
+            let output_sender = output_sender.clone();
+            let language = languages.for_device(&input.device);
+            let cloud_provider = cloud_provider.clone();
+            tokio::task::spawn_blocking(move || {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_secs();
+
+                let mut duration = 0.0;
+                let result = pcm_decode(&input.path).and_then(|(pcm_data, sample_rate)| {
+                    duration = pcm_duration(&pcm_data, sample_rate);
+                    let (pcm_data, sample_rate) = if input.preprocessing.is_enabled() {
+                        preprocess_audio(pcm_data, sample_rate, input.preprocessing)?
+                    } else {
+                        (pcm_data, sample_rate)
+                    };
+                    stt_pcm(
+                        pcm_data,
+                        sample_rate,
+                        &whisper_model,
+                        cloud_provider.as_deref(),
+                        &language,
+                    )
+                });
+
+                let transcription_result = match result {
+                    Ok((transcript, engine)) => TranscriptionResult {
+                        input: input.clone(),
+                        transcription: Some(transcript.text),
+                        timestamp,
+                        error: None,
+                        duration,
+                        language: transcript.language,
+                        engine: engine.to_string(),
+                        segments: transcript.segments,
+                    },
+                    Err(e) => TranscriptionResult {
+                        input: input.clone(),
+                        transcription: None,
+                        timestamp,
+                        error: Some(e.to_string()),
+                        duration,
+                        language: None,
+                        engine: String::new(),
+                        segments: Vec::new(),
+                    },
+                };
+
+                let _ = output_sender.send(transcription_result);
+                drop(permit);
+            });
         }
     });
 
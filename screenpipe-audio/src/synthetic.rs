@@ -0,0 +1,58 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{AudioInput, AudioPreprocessing, TranscriptionPriority};
+
+/// Device name reported for audio played back from a directory
+pub const SYNTHETIC_DEVICE_NAME: &str = "synthetic (input)";
+
+pub fn list_synthetic_audio_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| matches!(ext.to_lowercase().as_str(), "wav" | "mp4" | "mp3"))
+                .unwrap_or(false)
+        })
+        .collect();
+    // Play back in file name order
+    files.sort();
+
+    if files.is_empty() {
+        return Err(anyhow!("No audio files found in {}", dir.display()));
+    }
+    Ok(files)
+}
+
+/// Feeds the audio files (wav, mp4, mp3) of a directory to the whisper channel as if they
+/// were recorded chunks, one every `chunk_duration`, so the pipeline can run without a
+/// microphone (tests, CI).
+pub async fn play_synthetic_audio(
+    dir: PathBuf,
+    chunk_duration: Duration,
+    whisper_sender: UnboundedSender<AudioInput>,
+) -> Result<()> {
+    let files = list_synthetic_audio_files(&dir)?;
+    info!(
+        "Playing back {} synthetic audio files from {}",
+        files.len(),
+        dir.display()
+    );
+
+    for file in files {
+        debug!("Sending synthetic audio file {}", file.display());
+        whisper_sender.send(AudioInput {
+            path: file.to_string_lossy().into_owned(),
+            device: SYNTHETIC_DEVICE_NAME.to_string(),
+            priority: TranscriptionPriority::Background,
+            preprocessing: AudioPreprocessing::default(),
+        })?;
+        tokio::time::sleep(chunk_duration).await;
+    }
+
+    Ok(())
+}
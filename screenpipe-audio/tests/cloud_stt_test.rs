@@ -0,0 +1,107 @@
+#[cfg(all(test, feature = "cloud"))]
+mod tests {
+    use screenpipe_audio::{CloudSttConfig, CloudSttProvider, TranscriptionLanguage};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// URL of a server answering one request with `body`, the request head is sent to the
+    /// returned receiver
+    fn serve_once(body: &'static str) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            sender.send(head).unwrap();
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn test_cloud_stt_requires_a_key() {
+        for provider in CloudSttProvider::ALL {
+            std::env::remove_var(provider.api_key_env());
+            let config = CloudSttConfig {
+                provider,
+                endpoint: Some("http://localhost".to_string()),
+                ..Default::default()
+            };
+            let error = config.provider().err().unwrap().to_string();
+            assert!(error.contains(provider.api_key_env()), "{}", error);
+
+            let empty = CloudSttConfig {
+                api_key: Some(String::new()),
+                ..config.clone()
+            };
+            assert!(empty.provider().is_err(), "{}", provider);
+
+            let configured = CloudSttConfig {
+                api_key: Some("secret".to_string()),
+                ..config
+            };
+            assert!(configured.provider().is_ok(), "{}", provider);
+        }
+    }
+
+    #[test]
+    fn test_deepgram_sends_the_configured_key() {
+        let (url, requests) = serve_once(
+            r#"{"results": {"channels": [{"alternatives": [{"transcript": "hello world", "words": [
+                {"word": "hello", "punctuated_word": "Hello", "start": 0.0, "end": 0.4},
+                {"word": "world", "start": 0.5, "end": 0.9}
+            ]}]}]}}"#,
+        );
+        let provider = CloudSttConfig {
+            provider: CloudSttProvider::Deepgram,
+            api_key: Some("secret".to_string()),
+            endpoint: Some(url),
+            ..Default::default()
+        }
+        .provider()
+        .unwrap();
+
+        let transcript = provider
+            .transcribe(&[0.0; 1600], &TranscriptionLanguage::Fixed("en"))
+            .unwrap();
+        assert_eq!(transcript.text, "hello world");
+        assert_eq!(transcript.language.as_deref(), Some("en"));
+        let words: Vec<&str> = transcript
+            .segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect();
+        assert_eq!(words, vec!["Hello", "world"]);
+
+        let head = requests.recv().unwrap();
+        assert!(head.starts_with("POST /listen?model=nova-2"), "{}", head);
+        assert!(
+            head.to_lowercase()
+                .contains("authorization: token secret\r\n"),
+            "{}",
+            head
+        );
+    }
+}
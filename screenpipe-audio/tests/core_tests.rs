@@ -3,7 +3,9 @@ mod tests {
     use chrono::Utc;
     use log::{debug, LevelFilter};
     use screenpipe_audio::{default_output_device, list_audio_devices, stt, WhisperModel};
-    use screenpipe_audio::{parse_audio_device, record_and_transcribe};
+    use screenpipe_audio::{parse_audio_device, record_and_transcribe, AudioDeviceConfig};
+    use screenpipe_audio::{AudioEncoding, AudioFormat};
+    use screenpipe_audio::{CloudSttConfig, TranscriptionLanguage, TranscriptionLanguages};
     use std::path::PathBuf;
     use std::process::Command;
     use std::str::FromStr;
@@ -38,6 +40,46 @@ mod tests {
         assert_eq!(spec.to_string(), "Test Device (input)");
     }
 
+    #[test]
+    fn test_transcription_languages() {
+        assert_eq!(
+            TranscriptionLanguage::from_str("auto").unwrap(),
+            TranscriptionLanguage::Auto
+        );
+        let french = TranscriptionLanguage::from_str("French").unwrap();
+        assert_eq!(french, TranscriptionLanguage::Fixed("fr"));
+        assert_eq!(french.to_string(), "fr");
+        assert!(TranscriptionLanguage::from_str("klingon").is_err());
+
+        let mut languages = TranscriptionLanguages::default();
+        languages
+            .devices
+            .insert("Meeting Room (input)".to_string(), french);
+        assert_eq!(languages.for_device("Meeting Room (input)"), french);
+        assert_eq!(
+            languages.for_device("MacBook Pro Microphone (input)"),
+            TranscriptionLanguage::Auto
+        );
+    }
+
+    #[test]
+    fn test_parse_audio_device_selectors() {
+        let spec = parse_audio_device("  Test Device (Output) ").unwrap();
+        assert_eq!(spec.to_string(), "Test Device (output)");
+
+        assert!(parse_audio_device("Test Device").is_err());
+        assert!(parse_audio_device("#0").is_err());
+        assert!(parse_audio_device("#100000").is_err());
+
+        // numbers follow list_audio_devices
+        let devices = list_audio_devices().unwrap();
+        if let Some(first) = devices.first() {
+            assert!(parse_audio_device("#1").unwrap() == *first);
+            assert!(parse_audio_device("1").unwrap() == *first);
+            assert!(parse_audio_device(&first.to_string().to_uppercase()).unwrap() == *first);
+        }
+    }
+
     #[test]
     #[ignore]
     fn test_speech_to_text() {
@@ -46,9 +88,9 @@ mod tests {
         println!("Loading audio file");
         let start = std::time::Instant::now();
         let whisper_model = WhisperModel::new().unwrap();
-        let cloud_audio = true; // Set this based on your test requirements
+        let cloud_stt = CloudSttConfig::default(); // Set this based on your test requirements
 
-        let text = stt("./test_data/selah.mp4", &whisper_model, cloud_audio).unwrap();
+        let text = stt("./test_data/selah.mp4", &whisper_model, Some(&cloud_stt)).unwrap();
         let duration = start.elapsed();
 
         println!("Speech to text completed in {:?}", duration);
@@ -79,6 +121,8 @@ mod tests {
             output_path.clone(),
             sender,
             is_running,
+            AudioDeviceConfig::default(),
+            AudioEncoding::default(),
         )
         .await;
         println!("record_and_transcribe completed");
@@ -142,6 +186,8 @@ mod tests {
             output_path.clone(),
             sender,
             is_running,
+            AudioDeviceConfig::default(),
+            AudioEncoding::default(),
         )
         .await
         .unwrap();
@@ -209,9 +255,13 @@ mod tests {
     #[ignore]
     async fn test_audio_transcription() {
         setup();
-        use screenpipe_audio::{create_whisper_channel, record_and_transcribe};
+        use screenpipe_audio::{
+            create_whisper_channel, record_and_transcribe, AudioDeviceConfig,
+            TranscriptionLanguages, WhisperModelConfig,
+        };
         use std::sync::Arc;
         use std::time::Duration;
+        use tokio::sync::watch;
         use tokio::time::timeout;
 
         // 1. start listening to https://music.youtube.com/watch?v=B6WAlAzuJb4&si=775sYWLG0b7XhQIH&t=50
@@ -223,9 +273,15 @@ mod tests {
         let output_path =
             PathBuf::from(format!("test_output_{}.mp4", Utc::now().timestamp_millis()));
         let output_path_2 = output_path.clone();
-        let cloud_audio = true; // Set this based on your test requirements
-        let (whisper_sender, mut whisper_receiver) =
-            create_whisper_channel(cloud_audio).await.unwrap();
+        let cloud_stt = Some(CloudSttConfig::default()); // Set this based on your test requirements
+        let (whisper_sender, mut whisper_receiver) = create_whisper_channel(
+            cloud_stt,
+            1,
+            TranscriptionLanguages::default(),
+            watch::channel(WhisperModelConfig::default()).1,
+        )
+        .await
+        .unwrap();
         let is_running = Arc::new(AtomicBool::new(true));
         // Start recording in a separate thread
         let recording_thread = tokio::spawn(async move {
@@ -237,6 +293,8 @@ mod tests {
                 output_path.clone(),
                 whisper_sender,
                 is_running,
+                AudioDeviceConfig::default(),
+                AudioEncoding::default(),
             )
             .await
             .unwrap();
@@ -287,4 +345,174 @@ mod tests {
         let _ = recording_thread.abort();
         std::fs::remove_file(output_path_2).unwrap_or_default();
     }
+
+    #[test]
+    fn test_transcription_queue_priority_and_fairness() {
+        use screenpipe_audio::stt::MAX_ON_DEMAND_STREAK;
+        use screenpipe_audio::{
+            AudioInput, AudioPreprocessing, TranscriptionPriority, TranscriptionQueue,
+        };
+
+        let input = |path: &str, priority| AudioInput {
+            path: path.to_string(),
+            device: "test_device".to_string(),
+            priority,
+            preprocessing: AudioPreprocessing::default(),
+        };
+
+        let mut queue = TranscriptionQueue::new();
+        queue.push(input("background_0", TranscriptionPriority::Background));
+        queue.push(input("background_1", TranscriptionPriority::Background));
+        for i in 0..MAX_ON_DEMAND_STREAK + 1 {
+            queue.push(input(
+                &format!("on_demand_{}", i),
+                TranscriptionPriority::OnDemand,
+            ));
+        }
+
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop())
+            .map(|input| input.path)
+            .collect();
+
+        // On-demand inputs jump ahead, but a background input gets through after a full streak
+        let mut expected: Vec<String> = (0..MAX_ON_DEMAND_STREAK)
+            .map(|i| format!("on_demand_{}", i))
+            .collect();
+        expected.push("background_0".to_string());
+        expected.push(format!("on_demand_{}", MAX_ON_DEMAND_STREAK));
+        expected.push("background_1".to_string());
+        assert_eq!(order, expected);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_chunk_time() {
+        use screenpipe_audio::stt::chunk_time;
+
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        // 10ms frames, the VAD kept frames 20, 21 and 50
+        let kept_frames = [20, 21, 50];
+        assert!(close(chunk_time(0.0, &kept_frames, 160), 0.2));
+        assert!(close(chunk_time(0.015, &kept_frames, 160), 0.215));
+        // Across the silence dropped
+        assert!(close(chunk_time(0.025, &kept_frames, 160), 0.505));
+        // Past the speech, the end of the last frame kept
+        assert!(close(chunk_time(0.03, &kept_frames, 160), 0.51));
+        assert!(close(chunk_time(1.0, &kept_frames, 160), 0.51));
+        assert!(close(chunk_time(-1.0, &kept_frames, 160), 0.2));
+        assert!(close(chunk_time(1.0, &[], 160), 0.0));
+    }
+
+    #[test]
+    fn test_preprocess_audio() {
+        use screenpipe_audio::{preprocess_audio, AudioPreprocessing};
+
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+        let sine = |amplitude: f32| -> Vec<f32> {
+            (0..16000)
+                .map(|i| amplitude * (i as f32 * 440.0 * std::f32::consts::TAU / 16000.0).sin())
+                .collect()
+        };
+        let auto_gain = AudioPreprocessing {
+            auto_gain: true,
+            ..Default::default()
+        };
+
+        // Nothing to do by default
+        let (samples, sample_rate) =
+            preprocess_audio(sine(0.01), 16000, AudioPreprocessing::default()).unwrap();
+        assert_eq!((samples, sample_rate), (sine(0.01), 16000));
+
+        let (quiet, _) = preprocess_audio(sine(0.01), 16000, auto_gain).unwrap();
+        assert!(rms(&quiet[12000..]) > 0.05, "{}", rms(&quiet[12000..]));
+        let (loud, _) = preprocess_audio(sine(0.9), 16000, auto_gain).unwrap();
+        assert!(rms(&loud[12000..]) < 0.3, "{}", rms(&loud[12000..]));
+        assert!(loud.iter().all(|s| s.abs() <= 1.0));
+        // Silence isn't amplified
+        let (silence, _) = preprocess_audio(vec![0.0; 16000], 16000, auto_gain).unwrap();
+        assert!(silence.iter().all(|s| *s == 0.0));
+
+        // White noise, deterministic
+        let mut seed = 1u32;
+        let noise: Vec<f32> = (0..16000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed >> 8) as f32 / (1 << 24) as f32 * 0.1 - 0.05
+            })
+            .collect();
+        let config = AudioDeviceConfig {
+            noise_suppression: Some(true),
+            ..Default::default()
+        };
+        let (denoised, sample_rate) =
+            preprocess_audio(noise.clone(), 16000, config.preprocessing()).unwrap();
+        assert_eq!(sample_rate, 48000);
+        assert!(rms(&denoised) < rms(&noise) / 2.0);
+    }
+
+    #[test]
+    fn test_audio_encoding() {
+        let args = |encoding: AudioEncoding| encoding.ffmpeg_args().join(" ");
+        assert_eq!(args(AudioEncoding::default()), "-c:a aac -b:a 128k -f mp4");
+        let opus = AudioEncoding {
+            format: AudioFormat::Opus,
+            bitrate_kbps: None,
+        };
+        assert_eq!(args(opus), "-c:a libopus -b:a 24k -application voip -f ogg");
+        // Lossless, the bitrate is ignored
+        let flac = AudioEncoding {
+            format: AudioFormat::Flac,
+            bitrate_kbps: Some(64),
+        };
+        assert_eq!(args(flac), "-c:a flac -f flac");
+
+        assert!(opus.validate().is_ok());
+        assert!(AudioEncoding {
+            bitrate_kbps: Some(1000),
+            ..opus
+        }
+        .validate()
+        .is_err());
+        assert!(flac.validate().is_ok());
+
+        assert_eq!("opus".parse::<AudioFormat>().unwrap(), AudioFormat::Opus);
+        assert!("mp3".parse::<AudioFormat>().is_err());
+        assert_eq!(
+            AudioFormat::from_path("data/mic_2024-09-11_10-00-00.ogg"),
+            Some(AudioFormat::Opus)
+        );
+        assert_eq!(AudioFormat::from_path("data/chunk"), None);
+    }
+
+    #[test]
+    fn test_pcm_decode_wav() {
+        use screenpipe_audio::pcm_decode;
+
+        let path = std::env::temp_dir().join(format!("pcm_decode_{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..16000 {
+            writer.write_sample((i % 100) as i16 * 100).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let (samples, sample_rate) = pcm_decode(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sample_rate, 16000);
+        assert_eq!(samples.len(), 16000);
+
+        // Neither Symphonia nor ffmpeg can decode it, an error instead of a panic
+        let garbage = std::env::temp_dir().join(format!("pcm_decode_{}.opus", std::process::id()));
+        std::fs::write(&garbage, b"not audio").unwrap();
+        let result = pcm_decode(&garbage);
+        std::fs::remove_file(&garbage).unwrap();
+        assert!(result.is_err());
+    }
 }
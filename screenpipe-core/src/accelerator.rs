@@ -0,0 +1,92 @@
+use anyhow::{anyhow, Result};
+use candle::Device;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+/// GPU requested for the local models. `Auto` picks Metal or CUDA when available and
+/// falls back to the CPU, an explicit GPU fails to load instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Gpu {
+    #[default]
+    Auto,
+    Cuda,
+    Metal,
+    Off,
+}
+
+impl Gpu {
+    pub const ALL: [Gpu; 4] = [Gpu::Auto, Gpu::Cuda, Gpu::Metal, Gpu::Off];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Gpu::Auto => "auto",
+            Gpu::Cuda => "cuda",
+            Gpu::Metal => "metal",
+            Gpu::Off => "off",
+        }
+    }
+}
+
+impl FromStr for Gpu {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Gpu::ALL
+            .into_iter()
+            .find(|gpu| gpu.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown gpu {}, expected auto, cuda, metal or off", s))
+    }
+}
+
+impl fmt::Display for Gpu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Hardware a local model runs on, as shown by `/health`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Accelerator {
+    Cpu,
+    Cuda,
+    Metal,
+}
+
+/// Device of a local model for `gpu`
+pub fn select_device(gpu: Gpu) -> Result<(Device, Accelerator)> {
+    match gpu {
+        Gpu::Off => Ok((Device::Cpu, Accelerator::Cpu)),
+        Gpu::Cuda => Device::new_cuda(0)
+            .map(|device| (device, Accelerator::Cuda))
+            .map_err(|e| anyhow!("CUDA unavailable: {}", e)),
+        Gpu::Metal => Device::new_metal(0)
+            .map(|device| (device, Accelerator::Metal))
+            .map_err(|e| anyhow!("Metal unavailable: {}", e)),
+        Gpu::Auto => Ok(select_device(Gpu::Metal)
+            .or_else(|_| select_device(Gpu::Cuda))
+            .unwrap_or((Device::Cpu, Accelerator::Cpu))),
+    }
+}
+
+fn registry() -> &'static Mutex<BTreeMap<String, Accelerator>> {
+    static ACCELERATORS: OnceLock<Mutex<BTreeMap<String, Accelerator>>> = OnceLock::new();
+    ACCELERATORS.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// Records the hardware `model` was loaded on, replacing the previous one
+pub fn set_accelerator(model: &str, accelerator: Accelerator) {
+    registry()
+        .lock()
+        .unwrap()
+        .insert(model.to_string(), accelerator);
+}
+
+/// Hardware of every local model loaded so far in the process, by model
+pub fn accelerators() -> BTreeMap<String, Accelerator> {
+    registry().lock().unwrap().clone()
+}
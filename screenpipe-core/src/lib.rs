@@ -1,4 +1,14 @@
+pub mod accelerator;
+pub use accelerator::{accelerators, select_device, set_accelerator, Accelerator, Gpu};
 pub mod ffmpeg;
 pub use ffmpeg::find_ffmpeg_path;
 pub mod llm;
 pub use llm::*;
+pub mod resilience;
+pub use resilience::{
+    call_with_resilience, call_with_resilience_blocking, circuit_breaker, circuit_breakers,
+    CircuitBreaker, CircuitBreakerStatus, CircuitState, HttpStatusError, RetryPolicy,
+    RetryableError,
+};
+pub mod transcription;
+pub use transcription::TranscriptionSegment;
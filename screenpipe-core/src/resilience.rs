@@ -0,0 +1,352 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt::{self, Display};
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Consecutive failures after which a provider's circuit opens
+const FAILURE_THRESHOLD: u32 = 5;
+/// How long an open circuit rejects calls before letting one through again
+const OPEN_DURATION: Duration = Duration::from_secs(60);
+
+/// Timeout and retries of one call to a cloud provider
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Of each attempt
+    pub timeout: Duration,
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each next one up to `max_delay`
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            timeout: Duration::from_secs(30),
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with full jitter, so retries of parallel calls don't line up
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        exponential.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+/// A provider answering with an HTTP error status. Only 5xx are retried: a 4xx, e.g. a
+/// wrong API key, fails the same way again.
+#[derive(Debug, Clone)]
+pub struct HttpStatusError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl HttpStatusError {
+    pub fn new(status: u16, message: impl Into<String>) -> Self {
+        HttpStatusError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl StdError for HttpStatusError {}
+
+/// Whether a failed call may succeed if made again. Errors other than a 4xx
+/// `HttpStatusError`, e.g. of the transport, are.
+pub trait RetryableError {
+    fn is_retryable(&self) -> bool;
+}
+
+impl RetryableError for HttpStatusError {
+    fn is_retryable(&self) -> bool {
+        self.status >= 500
+    }
+}
+
+impl RetryableError for anyhow::Error {
+    fn is_retryable(&self) -> bool {
+        self.downcast_ref::<HttpStatusError>()
+            .map_or(true, RetryableError::is_retryable)
+    }
+}
+
+impl RetryableError for Box<dyn StdError + Send + Sync> {
+    fn is_retryable(&self) -> bool {
+        self.downcast_ref::<HttpStatusError>()
+            .map_or(true, RetryableError::is_retryable)
+    }
+}
+
+impl RetryableError for String {
+    fn is_retryable(&self) -> bool {
+        true
+    }
+}
+
+impl RetryableError for &str {
+    fn is_retryable(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls fail right away without reaching the provider
+    Open,
+    /// One call goes through as a probe, its result closes or opens the circuit again
+    HalfOpen,
+}
+
+/// State of a provider's circuit, as shown by `/health`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerStatus {
+    pub provider: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+/// Stops calling a provider after `failure_threshold` consecutive failures, for
+/// `open_duration`, then lets one call through to probe it
+pub struct CircuitBreaker {
+    provider: String,
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// Of the call probing the provider while half open
+    probe_started_at: Option<Instant>,
+    last_error: Option<String>,
+}
+
+impl CircuitBreaker {
+    pub fn new(provider: &str, failure_threshold: u32, open_duration: Duration) -> Self {
+        CircuitBreaker {
+            provider: provider.to_string(),
+            failure_threshold: failure_threshold.max(1),
+            open_duration,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let state = self.state.lock().unwrap();
+        match state.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.open_duration => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Whether a call may go through: always when closed, never when open, and when half
+    /// open only if no other call is probing the provider. A probe whose result never came,
+    /// e.g. cancelled, is given up after `open_duration`.
+    pub fn try_acquire(&self) -> bool {
+        let circuit_state = self.state();
+        let mut state = self.state.lock().unwrap();
+        match circuit_state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => {
+                let probing = state.probe_started_at.map_or(false, |started_at| {
+                    started_at.elapsed() < self.open_duration
+                });
+                if !probing {
+                    state.probe_started_at = Some(Instant::now());
+                }
+                !probing
+            }
+        }
+    }
+
+    pub fn status(&self) -> CircuitBreakerStatus {
+        let circuit_state = self.state();
+        let state = self.state.lock().unwrap();
+        CircuitBreakerStatus {
+            provider: self.provider.clone(),
+            state: circuit_state,
+            consecutive_failures: state.consecutive_failures,
+            last_error: state.last_error.clone(),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.opened_at.take().is_some() {
+            info!("{} is back, circuit closed", self.provider);
+        }
+        state.probe_started_at = None;
+        state.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&self, error: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        state.last_error = Some(error.to_string());
+        state.probe_started_at = None;
+        // A failure while half open opens the circuit for another `open_duration`
+        if state.consecutive_failures >= self.failure_threshold {
+            if state.opened_at.is_none() {
+                warn!(
+                    "{} failed {} times in a row, not calling it for {:?}",
+                    self.provider, state.consecutive_failures, self.open_duration
+                );
+            }
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+fn registry() -> &'static Mutex<HashMap<String, Arc<CircuitBreaker>>> {
+    static BREAKERS: OnceLock<Mutex<HashMap<String, Arc<CircuitBreaker>>>> = OnceLock::new();
+    BREAKERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Circuit breaker of a provider, shared by every call to it in the process
+pub fn circuit_breaker(provider: &str) -> Arc<CircuitBreaker> {
+    registry()
+        .lock()
+        .unwrap()
+        .entry(provider.to_string())
+        .or_insert_with(|| {
+            Arc::new(CircuitBreaker::new(
+                provider,
+                FAILURE_THRESHOLD,
+                OPEN_DURATION,
+            ))
+        })
+        .clone()
+}
+
+/// Providers called so far, by name
+pub fn circuit_breakers() -> Vec<CircuitBreakerStatus> {
+    let mut statuses: Vec<CircuitBreakerStatus> = registry()
+        .lock()
+        .unwrap()
+        .values()
+        .map(|breaker| breaker.status())
+        .collect();
+    statuses.sort_by(|a, b| a.provider.cmp(&b.provider));
+    statuses
+}
+
+/// Calls a cloud provider with a timeout per attempt, retries 5xx and transport errors with
+/// jittered backoff, and fails right away while the provider's circuit is open
+pub async fn call_with_resilience<T, E, F, Fut>(
+    provider: &str,
+    policy: &RetryPolicy,
+    mut call: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display + RetryableError,
+{
+    let breaker = circuit_breaker(provider);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        if !breaker.try_acquire() {
+            return Err(anyhow!("{} is unavailable, circuit open", provider));
+        }
+        let error = match tokio::time::timeout(policy.timeout, call()).await {
+            Ok(Ok(value)) => {
+                breaker.record_success();
+                return Ok(value);
+            }
+            Ok(Err(e)) if !e.is_retryable() => {
+                // The provider answered
+                breaker.record_success();
+                return Err(anyhow!("{} failed: {}", provider, e));
+            }
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => format!("timed out after {:?}", policy.timeout),
+        };
+        breaker.record_failure(&error);
+        if attempt >= policy.max_attempts {
+            return Err(anyhow!(
+                "{} failed after {} attempts: {}",
+                provider,
+                attempt,
+                error
+            ));
+        }
+        let delay = policy.backoff(attempt);
+        warn!("{} failed ({}), retrying in {:?}", provider, error, delay);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Same as `call_with_resilience` for blocking calls, which get the timeout to apply
+/// themselves (e.g. to their HTTP client) as they can't be cancelled
+pub fn call_with_resilience_blocking<T, E, F>(
+    provider: &str,
+    policy: &RetryPolicy,
+    mut call: F,
+) -> Result<T>
+where
+    F: FnMut(Duration) -> Result<T, E>,
+    E: Display + RetryableError,
+{
+    let breaker = circuit_breaker(provider);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        if !breaker.try_acquire() {
+            return Err(anyhow!("{} is unavailable, circuit open", provider));
+        }
+        let error = match call(policy.timeout) {
+            Ok(value) => {
+                breaker.record_success();
+                return Ok(value);
+            }
+            Err(e) if !e.is_retryable() => {
+                // The provider answered
+                breaker.record_success();
+                return Err(anyhow!("{} failed: {}", provider, e));
+            }
+            Err(e) => e.to_string(),
+        };
+        breaker.record_failure(&error);
+        if attempt >= policy.max_attempts {
+            return Err(anyhow!(
+                "{} failed after {} attempts: {}",
+                provider,
+                attempt,
+                error
+            ));
+        }
+        let delay = policy.backoff(attempt);
+        warn!("{} failed ({}), retrying in {:?}", provider, error, delay);
+        std::thread::sleep(delay);
+    }
+}
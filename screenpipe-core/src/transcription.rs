@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Timed piece of a transcription, a word or a phrase depending on the engine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionSegment {
+    /// Seconds from the start of the audio chunk
+    pub start: f64,
+    /// Seconds from the start of the audio chunk
+    pub end: f64,
+    pub text: String,
+}
@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_core::{accelerators, select_device, set_accelerator, Accelerator, Gpu};
+
+    #[test]
+    fn test_gpu_from_str() {
+        assert_eq!("CUDA".parse::<Gpu>(), Ok(Gpu::Cuda));
+        assert_eq!(" off ".parse::<Gpu>(), Ok(Gpu::Off));
+        assert!("vulkan".parse::<Gpu>().is_err());
+        assert_eq!(Gpu::default(), Gpu::Auto);
+    }
+
+    #[test]
+    fn test_select_device_off_is_cpu() {
+        let (device, accelerator) = select_device(Gpu::Off).unwrap();
+        assert!(device.is_cpu());
+        assert_eq!(accelerator, Accelerator::Cpu);
+        // Auto never fails, it falls back to the CPU
+        assert!(select_device(Gpu::Auto).is_ok());
+    }
+
+    #[test]
+    fn test_set_accelerator_replaces_previous() {
+        set_accelerator("test-model", Accelerator::Cuda);
+        set_accelerator("test-model", Accelerator::Cpu);
+        assert_eq!(accelerators().get("test-model"), Some(&Accelerator::Cpu));
+    }
+}
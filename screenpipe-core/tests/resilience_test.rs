@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_core::{
+        call_with_resilience, call_with_resilience_blocking, circuit_breaker, circuit_breakers,
+        CircuitBreaker, CircuitState, HttpStatusError, RetryPolicy,
+    };
+    use std::time::Duration;
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_millis(100),
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens_then_half_opens() {
+        let breaker = CircuitBreaker::new("test-breaker", 2, Duration::from_millis(50));
+        breaker.record_failure("500");
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record_failure("500");
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(breaker.status().last_error.as_deref(), Some("500"));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        // A single probe
+        assert!(breaker.try_acquire());
+        assert!(!breaker.try_acquire());
+        breaker.record_failure("500");
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.try_acquire());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.try_acquire());
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.status().consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_call_with_resilience_retries_and_times_out() {
+        let mut calls = 0;
+        let result = call_with_resilience("test-retry", &fast_policy(), || {
+            calls += 1;
+            let attempt = calls;
+            async move {
+                if attempt < 3 {
+                    Err("503")
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(circuit_breaker("test-retry").state(), CircuitState::Closed);
+
+        let result = call_with_resilience("test-timeout", &fast_policy(), || async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok::<_, String>(())
+        })
+        .await;
+        assert!(result.unwrap_err().to_string().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let mut calls = 0;
+        let result = call_with_resilience("test-unauthorized", &fast_policy(), || {
+            calls += 1;
+            async { Err::<(), anyhow::Error>(HttpStatusError::new(401, "401 Unauthorized").into()) }
+        })
+        .await;
+        assert!(result.unwrap_err().to_string().contains("401 Unauthorized"));
+        assert_eq!(calls, 1);
+        assert_eq!(
+            circuit_breaker("test-unauthorized")
+                .status()
+                .consecutive_failures,
+            0
+        );
+
+        let mut calls = 0;
+        let result = call_with_resilience_blocking("test-server-error", &fast_policy(), |_| {
+            calls += 1;
+            Err::<(), anyhow::Error>(HttpStatusError::new(502, "502 Bad Gateway").into())
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_open_circuit_fails_fast() {
+        let mut calls = 0;
+        for _ in 0..2 {
+            let result = call_with_resilience_blocking("test-down", &fast_policy(), |_| {
+                calls += 1;
+                Err::<(), _>("connection refused")
+            });
+            assert!(result.is_err());
+        }
+        // Opened after 5 failures, the 6th call never reaches the provider
+        assert_eq!(calls, 5);
+        assert!(circuit_breakers()
+            .iter()
+            .any(|status| status.provider == "test-down" && status.state == CircuitState::Open));
+    }
+}
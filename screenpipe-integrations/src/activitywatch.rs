@@ -0,0 +1,271 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_ACTIVITYWATCH_URL: &str = "http://localhost:5600";
+
+/// Time between two syncs with ActivityWatch
+pub const ACTIVITYWATCH_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Events fetched from a bucket per request, the older ones with the next requests
+pub const EVENTS_PER_REQUEST: usize = 5000;
+
+/// Type of the bucket screenpipe pushes its app sessions to
+pub const SCREENPIPE_BUCKET_TYPE: &str = "app.screenpipe.app";
+
+type ActivityWatchResult<T> = Result<T, Box<dyn StdError + Send + Sync>>;
+
+/// Event of an ActivityWatch bucket. A heartbeat of the watcher extends the duration of its
+/// last event, so an event pulled again replaces the stored one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityWatchEvent {
+    pub bucket_id: String,
+    /// Type of the bucket, e.g. `afkstatus` or `currentwindow`
+    pub event_type: String,
+    pub timestamp: DateTime<Utc>,
+    /// Seconds
+    pub duration: f64,
+    /// `{"status": "afk"}`, `{"app": ..., "title": ...}`, ...
+    pub data: Value,
+}
+
+#[async_trait]
+pub trait ActivityWatchDatabase {
+    /// Inserts the events, replacing the stored ones with the same bucket and timestamp
+    async fn upsert_activity_events(&self, events: &[ActivityWatchEvent])
+        -> ActivityWatchResult<()>;
+    /// Start of the last stored event of the bucket
+    async fn last_activity_event_time(
+        &self,
+        bucket_id: &str,
+    ) -> ActivityWatchResult<Option<DateTime<Utc>>>;
+    /// (timestamp, app name) of the frames recorded by this machine, in chronological order
+    async fn get_app_timeline(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> ActivityWatchResult<Vec<(DateTime<Utc>, String)>>;
+}
+
+/// Continuous use of one app
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppSession {
+    pub app_name: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Consecutive frames of the same app merged into sessions, a gap longer than `max_gap`
+/// ends the session
+pub fn app_sessions(
+    timeline: &[(DateTime<Utc>, String)],
+    max_gap: ChronoDuration,
+) -> Vec<AppSession> {
+    let mut sessions: Vec<AppSession> = Vec::new();
+    for (timestamp, app_name) in timeline {
+        if app_name.is_empty() {
+            continue;
+        }
+        match sessions.last_mut() {
+            Some(session)
+                if session.app_name == *app_name && *timestamp - session.end <= max_gap =>
+            {
+                session.end = *timestamp;
+            }
+            _ => sessions.push(AppSession {
+                app_name: app_name.clone(),
+                start: *timestamp,
+                end: *timestamp,
+            }),
+        }
+    }
+    sessions
+}
+
+/// Pulls the AFK, window and browser tab events of a local ActivityWatch server into the
+/// database, and optionally pushes the apps screenpipe saw on screen to a bucket of its own
+pub struct ActivityWatchBridge {
+    client: Client,
+    url: String,
+    hostname: String,
+    push: bool,
+    /// End of the last app session pushed
+    pushed_until: Option<DateTime<Utc>>,
+}
+
+impl ActivityWatchBridge {
+    pub fn new(url: &str, hostname: &str, push: bool) -> Self {
+        ActivityWatchBridge {
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            url: url.trim_end_matches('/').to_string(),
+            hostname: hostname.to_string(),
+            push,
+            pushed_until: None,
+        }
+    }
+
+    pub fn screenpipe_bucket_id(&self) -> String {
+        format!("aw-watcher-screenpipe_{}", self.hostname)
+    }
+
+    /// Events pulled from ActivityWatch, and pushes the app sessions recorded since the last sync
+    pub async fn sync<DB: ActivityWatchDatabase + Send + Sync>(
+        &mut self,
+        db: &DB,
+    ) -> ActivityWatchResult<usize> {
+        let pulled = self.pull(db).await?;
+        if self.push {
+            self.push_app_sessions(db).await?;
+        }
+        Ok(pulled)
+    }
+
+    async fn pull<DB: ActivityWatchDatabase + Send + Sync>(
+        &self,
+        db: &DB,
+    ) -> ActivityWatchResult<usize> {
+        let buckets: HashMap<String, Value> = self
+            .client
+            .get(format!("{}/api/0/buckets/", self.url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let screenpipe_bucket_id = self.screenpipe_bucket_id();
+        let mut pulled = 0;
+        for (bucket_id, bucket) in buckets {
+            let bucket_type = bucket.get("type").and_then(Value::as_str).unwrap_or("");
+            let is_bridged = matches!(bucket_type, "afkstatus" | "currentwindow")
+                || bucket_type.starts_with("web.tab");
+            if !is_bridged || bucket_id == screenpipe_bucket_id {
+                continue;
+            }
+
+            // Newest first, a page at a time back to the last event stored. That one may
+            // have grown since, it is fetched again.
+            let since = db.last_activity_event_time(&bucket_id).await?;
+            let mut events = Vec::new();
+            let mut end: Option<DateTime<Utc>> = None;
+            loop {
+                let mut request = self
+                    .client
+                    .get(format!("{}/api/0/buckets/{}/events", self.url, bucket_id))
+                    .query(&[("limit", EVENTS_PER_REQUEST.to_string())]);
+                if let Some(since) = since {
+                    request = request.query(&[("start", since.to_rfc3339())]);
+                }
+                if let Some(end) = end {
+                    request = request.query(&[("end", end.to_rfc3339())]);
+                }
+                let page: Vec<Value> = request.send().await?.error_for_status()?.json().await?;
+                let full = page.len() >= EVENTS_PER_REQUEST;
+                let page: Vec<ActivityWatchEvent> = page
+                    .into_iter()
+                    .filter_map(|event| {
+                        let timestamp = event.get("timestamp")?.as_str()?;
+                        Some(ActivityWatchEvent {
+                            bucket_id: bucket_id.clone(),
+                            event_type: bucket_type.to_string(),
+                            timestamp: DateTime::parse_from_rfc3339(timestamp)
+                                .ok()?
+                                .with_timezone(&Utc),
+                            duration: event.get("duration").and_then(Value::as_f64).unwrap_or(0.0),
+                            data: event.get("data").cloned().unwrap_or_else(|| json!({})),
+                        })
+                    })
+                    .collect();
+                let oldest = page.iter().map(|event| event.timestamp).min();
+                events.extend(page);
+                match oldest {
+                    // The oldest events of the page come again with the next one
+                    Some(oldest) if full && end != Some(oldest) => end = Some(oldest),
+                    _ => break,
+                }
+            }
+            // Stored once every page is in, the next sync starts from the last event stored
+            if events.is_empty() {
+                continue;
+            }
+            db.upsert_activity_events(&events).await?;
+            debug!("Pulled {} events of ActivityWatch bucket {}", events.len(), bucket_id);
+            pulled += events.len();
+        }
+        Ok(pulled)
+    }
+
+    async fn push_app_sessions<DB: ActivityWatchDatabase + Send + Sync>(
+        &mut self,
+        db: &DB,
+    ) -> ActivityWatchResult<()> {
+        let bucket_id = self.screenpipe_bucket_id();
+        let response = self
+            .client
+            .post(format!("{}/api/0/buckets/{}", self.url, bucket_id))
+            .json(&json!({
+                "client": "screenpipe",
+                "type": SCREENPIPE_BUCKET_TYPE,
+                "hostname": self.hostname,
+            }))
+            .send()
+            .await?;
+        // 304 when the bucket already exists
+        if response.status() != StatusCode::NOT_MODIFIED {
+            response.error_for_status()?;
+        }
+
+        let pulsetime = ChronoDuration::from_std(ACTIVITYWATCH_SYNC_INTERVAL)?;
+        let end = Utc::now();
+        let start = self.pushed_until.unwrap_or(end - pulsetime);
+        let timeline = db.get_app_timeline(start, end).await?;
+        for session in app_sessions(&timeline, pulsetime) {
+            // ActivityWatch merges heartbeats of the same app within pulsetime into one event
+            for timestamp in [session.start, session.end] {
+                self.client
+                    .post(format!("{}/api/0/buckets/{}/heartbeat", self.url, bucket_id))
+                    .query(&[("pulsetime", pulsetime.num_seconds().to_string())])
+                    .json(&json!({
+                        "timestamp": timestamp.to_rfc3339(),
+                        "duration": 0,
+                        "data": {"app": session.app_name},
+                    }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            self.pushed_until = Some(session.end);
+        }
+        Ok(())
+    }
+}
+
+/// Syncs with ActivityWatch every `ACTIVITYWATCH_SYNC_INTERVAL`, failures are retried on
+/// the next sync
+pub async fn initialize_activitywatch_loop<DB: ActivityWatchDatabase + Send + Sync + 'static>(
+    mut bridge: ActivityWatchBridge,
+    db: Arc<DB>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ACTIVITYWATCH_SYNC_INTERVAL);
+        info!("ActivityWatch bridge started with {}", bridge.url);
+        loop {
+            interval.tick().await;
+
+            match bridge.sync(db.as_ref()).await {
+                Ok(pulled) => debug!("Synced {} ActivityWatch events", pulled),
+                Err(e) => error!("Error syncing with ActivityWatch at {}: {}", bridge.url, e),
+            }
+        }
+    });
+}
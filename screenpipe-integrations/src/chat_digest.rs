@@ -0,0 +1,308 @@
+use crate::obsidian::{daily_note, local_day_range, single_line, DailyNote, DailyNoteDatabase};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use log::{debug, error, info};
+use serde_json::json;
+use std::error::Error as StdError;
+use std::fmt;
+use std::fmt::Write as _;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Discord rejects longer messages
+const DISCORD_MAX_LENGTH: usize = 2000;
+const TOP_APPS: usize = 5;
+const TOP_SNIPPETS: usize = 3;
+/// Mentions listed of each flagged keyword
+pub const MAX_MENTIONS: u32 = 5;
+const MAX_LINE_LENGTH: usize = 200;
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+type DigestResult<T> = Result<T, Box<dyn StdError + Send + Sync>>;
+
+/// Chat the digest is posted to with an incoming webhook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatPlatform {
+    Slack,
+    Discord,
+}
+
+impl ChatPlatform {
+    pub const ALL: [ChatPlatform; 2] = [ChatPlatform::Slack, ChatPlatform::Discord];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatPlatform::Slack => "slack",
+            ChatPlatform::Discord => "discord",
+        }
+    }
+
+    /// `text` in bold, the platforms don't share the markdown syntax
+    fn bold(&self, text: &str) -> String {
+        match self {
+            ChatPlatform::Slack => format!("*{}*", text),
+            ChatPlatform::Discord => format!("**{}**", text),
+        }
+    }
+}
+
+impl FromStr for ChatPlatform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ChatPlatform::ALL
+            .into_iter()
+            .find(|platform| platform.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown chat platform {}, expected slack or discord", s))
+    }
+}
+
+impl fmt::Display for ChatPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Incoming webhook of a Slack or Discord channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatWebhook {
+    pub platform: ChatPlatform,
+    pub url: String,
+}
+
+/// Where a flagged keyword was seen or heard
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeywordMention {
+    pub timestamp: DateTime<Utc>,
+    /// App name, "audio" for transcriptions
+    pub source: String,
+    /// Text around the keyword
+    pub text: String,
+}
+
+/// Mentions of a flagged keyword during the day
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlaggedKeyword {
+    pub keyword: String,
+    /// All the mentions, more than the ones listed
+    pub count: u64,
+    /// Most recent, up to `MAX_MENTIONS`
+    pub mentions: Vec<KeywordMention>,
+}
+
+#[async_trait]
+pub trait DigestDatabase: DailyNoteDatabase {
+    /// Most recent mentions of `keyword` on screen, in audio, copied or notified
+    async fn find_mentions(
+        &self,
+        keyword: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: u32,
+    ) -> DigestResult<Vec<KeywordMention>>;
+
+    /// Mentions of `keyword`, counted like `find_mentions` finds them
+    async fn count_mentions(
+        &self,
+        keyword: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> DigestResult<u64>;
+}
+
+/// Summary of a day posted to the chat
+pub struct Digest {
+    pub date: NaiveDate,
+    pub note: DailyNote,
+    /// Mentions of each flagged keyword, none for the ones not mentioned
+    pub flagged: Vec<FlaggedKeyword>,
+}
+
+/// Digest of the given local date, with the mentions of `keywords`
+pub async fn build_digest<DB: DigestDatabase + Send + Sync>(
+    date: NaiveDate,
+    keywords: &[String],
+    db: &DB,
+) -> DigestResult<Digest> {
+    let (start, end) = local_day_range(date)?;
+    let mut flagged = Vec::new();
+    for keyword in keywords {
+        let count = db.count_mentions(keyword, start, end).await?;
+        let mentions = if count == 0 {
+            Vec::new()
+        } else {
+            db.find_mentions(keyword, start, end, MAX_MENTIONS).await?
+        };
+        flagged.push(FlaggedKeyword {
+            keyword: keyword.clone(),
+            count,
+            mentions,
+        });
+    }
+    Ok(Digest {
+        date,
+        note: daily_note(date, db).await?,
+        flagged,
+    })
+}
+
+/// Message of `digest`, in the markdown of `platform`
+pub fn render_digest(platform: ChatPlatform, digest: &Digest) -> String {
+    let note = &digest.note;
+    let mut message = String::new();
+    let _ = writeln!(
+        message,
+        "{}",
+        platform.bold(&format!(
+            "screenpipe digest of {}",
+            digest.date.format("%A, %B %-d")
+        ))
+    );
+
+    let total_frames: i64 = note.app_usage.iter().map(|(_, frames)| frames).sum();
+    if total_frames == 0 {
+        let _ = writeln!(message, "No screen activity recorded.");
+    } else {
+        let apps: Vec<String> = note
+            .app_usage
+            .iter()
+            .filter(|(_, frames)| *frames > 0)
+            .take(TOP_APPS)
+            .map(|(app_name, frames)| {
+                let app_name = if app_name.is_empty() {
+                    "unknown"
+                } else {
+                    app_name
+                };
+                format!(
+                    "{} {:.0}%",
+                    app_name,
+                    *frames as f64 * 100.0 / total_frames as f64
+                )
+            })
+            .collect();
+        let _ = writeln!(message, "{} {}", platform.bold("Apps:"), apps.join(", "));
+    }
+    let transcripts = note
+        .transcripts
+        .iter()
+        .filter(|(_, transcription)| !transcription.trim().is_empty())
+        .count();
+    let _ = writeln!(
+        message,
+        "{} {} transcriptions",
+        platform.bold("Heard:"),
+        transcripts
+    );
+    for (timestamp, app_name, text) in note.ocr_snippets.iter().take(TOP_SNIPPETS) {
+        let _ = writeln!(
+            message,
+            "• {} ({}) {}",
+            timestamp.with_timezone(&Local).format("%H:%M"),
+            app_name,
+            shorten(text)
+        );
+    }
+
+    for flagged in &digest.flagged {
+        if flagged.count == 0 {
+            continue;
+        }
+        let _ = writeln!(
+            message,
+            "\n{} mentioned {} times",
+            platform.bold(&flagged.keyword),
+            flagged.count
+        );
+        for mention in &flagged.mentions {
+            let _ = writeln!(
+                message,
+                "• {} ({}) {}",
+                mention.timestamp.with_timezone(&Local).format("%H:%M"),
+                mention.source,
+                shorten(&mention.text)
+            );
+        }
+    }
+    message
+}
+
+/// Body of the webhook request posting `message`
+pub fn webhook_payload(platform: ChatPlatform, message: &str) -> serde_json::Value {
+    match platform {
+        ChatPlatform::Slack => json!({ "text": message }),
+        ChatPlatform::Discord => {
+            let content = if message.chars().count() > DISCORD_MAX_LENGTH {
+                let mut content: String = message.chars().take(DISCORD_MAX_LENGTH - 1).collect();
+                content.push('…');
+                content
+            } else {
+                message.to_string()
+            };
+            json!({ "content": content })
+        }
+    }
+}
+
+/// Posts the digest of the given local date to `webhook`
+pub async fn send_digest<DB: DigestDatabase + Send + Sync>(
+    webhook: &ChatWebhook,
+    date: NaiveDate,
+    keywords: &[String],
+    db: &DB,
+) -> DigestResult<()> {
+    let digest = build_digest(date, keywords, db).await?;
+    let payload = webhook_payload(webhook.platform, &render_digest(webhook.platform, &digest));
+    reqwest::Client::new()
+        .post(&webhook.url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(&payload)
+        .send()
+        .await?
+        .error_for_status()?;
+    info!("Sent the digest of {} to {}", date, webhook.platform);
+    Ok(())
+}
+
+/// Posts the digest of the day to every webhook once a day, at `time` local time. Started
+/// after `time`, the first digest is the next day's, today's may have been sent before a
+/// restart.
+pub async fn initialize_digest_loop<DB: DigestDatabase + Send + Sync + 'static>(
+    webhooks: Vec<ChatWebhook>,
+    keywords: Vec<String>,
+    time: NaiveTime,
+    db: Arc<DB>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        let now = Local::now();
+        let mut last_sent = (now.time() >= time).then(|| now.date_naive());
+        debug!("initialize_digest_loop started, digests sent at {}", time);
+        loop {
+            interval.tick().await;
+
+            let now = Local::now();
+            let today = now.date_naive();
+            if now.time() < time || last_sent == Some(today) {
+                continue;
+            }
+            for webhook in &webhooks {
+                if let Err(e) = send_digest(webhook, today, &keywords, db.as_ref()).await {
+                    error!("Error sending the digest to {}: {}", webhook.platform, e);
+                }
+            }
+            last_sent = Some(today);
+        }
+    });
+}
+
+fn shorten(text: &str) -> String {
+    let text = single_line(text);
+    if text.chars().count() <= MAX_LINE_LENGTH {
+        return text;
+    }
+    let mut short: String = text.chars().take(MAX_LINE_LENGTH).collect();
+    short.push('…');
+    short
+}
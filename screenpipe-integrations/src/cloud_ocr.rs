@@ -0,0 +1,557 @@
+use async_trait::async_trait;
+use base64::Engine as _;
+use image::{codecs::png::PngEncoder, DynamicImage, ImageEncoder};
+use log::debug;
+use reqwest::multipart::{Form, Part};
+use rusty_tesseract::{Data, DataOutput};
+use screenpipe_core::{call_with_resilience, HttpStatusError, RetryPolicy};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+type CloudOcrResult<T> = Result<T, Box<dyn StdError + Send + Sync>>;
+
+/// Cloud OCR service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloudOcrProvider {
+    #[default]
+    Unstructured,
+    /// Google Cloud Vision
+    Google,
+    /// Azure AI Vision, needs the endpoint of the resource
+    Azure,
+}
+
+impl CloudOcrProvider {
+    pub const ALL: [CloudOcrProvider; 3] = [
+        CloudOcrProvider::Unstructured,
+        CloudOcrProvider::Google,
+        CloudOcrProvider::Azure,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloudOcrProvider::Unstructured => "unstructured",
+            CloudOcrProvider::Google => "google",
+            CloudOcrProvider::Azure => "azure",
+        }
+    }
+
+    /// Environment variable read when no API key is configured
+    pub fn api_key_env(&self) -> &'static str {
+        match self {
+            CloudOcrProvider::Unstructured => "UNSTRUCTURED_API_KEY",
+            CloudOcrProvider::Google => "GOOGLE_VISION_API_KEY",
+            CloudOcrProvider::Azure => "AZURE_VISION_KEY",
+        }
+    }
+}
+
+impl FromStr for CloudOcrProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CloudOcrProvider::ALL
+            .into_iter()
+            .find(|provider| provider.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "unknown OCR provider {}, expected unstructured, google or azure",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for CloudOcrProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `[cloud_ocr]` table of the config file, used with `--ocr-engine cloud`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CloudOcrConfig {
+    #[serde(default)]
+    pub provider: CloudOcrProvider,
+    /// Read from the provider's environment variable when None, see `api_key_env`
+    pub api_key: Option<String>,
+    /// Base URL replacing the provider's, e.g. a self-hosted Unstructured API. The endpoint
+    /// of the resource for Azure, e.g. https://<name>.cognitiveservices.azure.com
+    pub endpoint: Option<String>,
+    /// Of each attempt, 30 by default
+    pub timeout_secs: Option<u64>,
+    /// Attempts of each frame before its OCR fails, 3 by default
+    pub max_attempts: Option<u32>,
+}
+
+impl CloudOcrConfig {
+    fn api_key(&self) -> CloudOcrResult<String> {
+        if let Some(key) = self.api_key.as_ref().filter(|key| !key.is_empty()) {
+            return Ok(key.clone());
+        }
+        match std::env::var(self.provider.api_key_env()) {
+            Ok(key) if !key.is_empty() => Ok(key),
+            _ => Err(format!(
+                "no API key for {} OCR, set api_key in [cloud_ocr] or {}",
+                self.provider,
+                self.provider.api_key_env()
+            )
+            .into()),
+        }
+    }
+
+    fn endpoint(&self, default: &str) -> String {
+        self.endpoint
+            .as_deref()
+            .unwrap_or(default)
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            timeout: self
+                .timeout_secs
+                .map_or(default.timeout, Duration::from_secs),
+            max_attempts: self.max_attempts.unwrap_or(default.max_attempts).max(1),
+            ..default
+        }
+    }
+
+    /// Provider reading frames with this configuration, fails on missing credentials
+    pub fn provider(&self) -> CloudOcrResult<Arc<dyn OcrProvider>> {
+        if self.timeout_secs == Some(0) {
+            return Err("timeout_secs of [cloud_ocr] must be positive".into());
+        }
+        let api_key = self.api_key()?;
+        let client = reqwest::Client::new();
+        let retry_policy = self.retry_policy();
+        Ok(match self.provider {
+            CloudOcrProvider::Unstructured => Arc::new(Unstructured {
+                client,
+                api_key,
+                endpoint: self.endpoint("https://api.unstructuredapp.io/general/v0/general"),
+                retry_policy,
+            }),
+            CloudOcrProvider::Google => Arc::new(GoogleVision {
+                client,
+                api_key,
+                endpoint: self.endpoint("https://vision.googleapis.com/v1"),
+                retry_policy,
+            }),
+            CloudOcrProvider::Azure => match &self.endpoint {
+                Some(_) => Arc::new(AzureVision {
+                    client,
+                    api_key,
+                    endpoint: self.endpoint(""),
+                    retry_policy,
+                }),
+                None => return Err("azure OCR needs the endpoint of the resource".into()),
+            },
+        })
+    }
+}
+
+/// Line read by a cloud OCR service
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudOcrLine {
+    pub text: String,
+    /// 0 - 100, `None` when the service reports none
+    pub confidence: Option<f32>,
+    pub words: Vec<CloudOcrWord>,
+}
+
+/// Word of a `CloudOcrLine`, in pixels of the image
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudOcrWord {
+    pub text: String,
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    /// 0 - 100, `None` when the service reports none
+    pub confidence: Option<f32>,
+}
+
+/// Cloud service reading frames, built by `CloudOcrConfig::provider`
+#[async_trait]
+pub trait OcrProvider: Send + Sync {
+    /// OCR engine stored with what it read
+    fn name(&self) -> &'static str;
+
+    /// Lines of the PNG encoded image, with timeouts and retries. Fails instead of returning
+    /// nothing so the frame isn't stored without its text.
+    async fn recognize(&self, png: &[u8]) -> CloudOcrResult<Vec<CloudOcrLine>>;
+}
+
+impl fmt::Debug for dyn OcrProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Text, tesseract-like words and JSON lines of `image`, as the local engines return them
+pub async fn perform_ocr_cloud(
+    provider: &dyn OcrProvider,
+    image: &DynamicImage,
+) -> CloudOcrResult<(String, DataOutput, String)> {
+    let mut png = Vec::new();
+    PngEncoder::new(&mut Cursor::new(&mut png)).write_image(
+        image.as_bytes(),
+        image.width(),
+        image.height(),
+        image.color().into(),
+    )?;
+    let lines = provider.recognize(&png).await?;
+    debug!("{} read {} lines", provider.name(), lines.len());
+
+    let mut data = Vec::new();
+    for (line_index, line) in lines.iter().enumerate() {
+        for (word_index, word) in line.words.iter().enumerate() {
+            data.push(Data {
+                level: 5,
+                page_num: 1,
+                block_num: 1,
+                par_num: 1,
+                line_num: line_index as i32 + 1,
+                word_num: word_index as i32 + 1,
+                left: word.left,
+                top: word.top,
+                width: word.width,
+                height: word.height,
+                // No confidence, left out of the mean the confidence floor checks
+                conf: word.confidence.unwrap_or(-1.0),
+                text: word.text.clone(),
+            });
+        }
+    }
+    let text = lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let json_lines: Vec<HashMap<String, String>> = lines
+        .iter()
+        .map(|line| {
+            HashMap::from([
+                ("text".to_string(), line.text.clone()),
+                (
+                    "confidence".to_string(),
+                    // Kept as new text without a confidence, like Windows OCR
+                    format!("{:.2}", line.confidence.unwrap_or(100.0)),
+                ),
+            ])
+        })
+        .collect();
+    let data_output = DataOutput {
+        output: text.clone(),
+        data,
+    };
+    Ok((
+        text,
+        data_output,
+        serde_json::to_string_pretty(&json_lines)?,
+    ))
+}
+
+/// Response of a successful request, as JSON
+async fn json_response(request: reqwest::RequestBuilder) -> CloudOcrResult<Value> {
+    let response = request.send().await?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(response.json().await?)
+    } else {
+        let message = format!("{}: {}", status, response.text().await.unwrap_or_default());
+        Err(HttpStatusError::new(status.as_u16(), message).into())
+    }
+}
+
+/// Bounding box of a polygon given as points
+fn bounding_box(points: impl Iterator<Item = (f64, f64)>) -> (i32, i32, i32, i32) {
+    let (mut min_x, mut min_y) = (f64::MAX, f64::MAX);
+    let (mut max_x, mut max_y) = (f64::MIN, f64::MIN);
+    for (x, y) in points {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+    if min_x > max_x {
+        return (0, 0, 0, 0);
+    }
+    (
+        min_x.round() as i32,
+        min_y.round() as i32,
+        (max_x - min_x).round() as i32,
+        (max_y - min_y).round() as i32,
+    )
+}
+
+struct Unstructured {
+    client: reqwest::Client,
+    api_key: String,
+    endpoint: String,
+    retry_policy: RetryPolicy,
+}
+
+#[async_trait]
+impl OcrProvider for Unstructured {
+    fn name(&self) -> &'static str {
+        "Unstructured"
+    }
+
+    async fn recognize(&self, png: &[u8]) -> CloudOcrResult<Vec<CloudOcrLine>> {
+        let response = call_with_resilience("unstructured", &self.retry_policy, || async {
+            // A multipart form can only be sent once
+            let part = Part::bytes(png.to_vec())
+                .file_name("image.png")
+                .mime_str("image/png")?;
+            let form = Form::new()
+                .part("files", part)
+                .text("strategy", "auto")
+                .text("coordinates", "true");
+            json_response(
+                self.client
+                    .post(&self.endpoint)
+                    .header("accept", "application/json")
+                    .header("unstructured-api-key", &self.api_key)
+                    .multipart(form),
+            )
+            .await
+        })
+        .await?;
+
+        // Elements are paragraphs, titles, list items..., with the box of the element
+        let elements = response
+            .as_array()
+            .ok_or_else(|| format!("unexpected Unstructured response: {}", response))?;
+        Ok(elements
+            .iter()
+            .filter_map(|element| {
+                let text = element["text"].as_str()?.trim();
+                if text.is_empty() {
+                    return None;
+                }
+                let points = element["metadata"]["coordinates"]["points"]
+                    .as_array()
+                    .map(|points| {
+                        points
+                            .iter()
+                            .filter_map(|point| Some((point[0].as_f64()?, point[1].as_f64()?)))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                let (left, top, width, height) = bounding_box(points.into_iter());
+                Some(CloudOcrLine {
+                    text: text.to_string(),
+                    confidence: None,
+                    words: vec![CloudOcrWord {
+                        text: text.to_string(),
+                        left,
+                        top,
+                        width,
+                        height,
+                        confidence: None,
+                    }],
+                })
+            })
+            .collect())
+    }
+}
+
+struct GoogleVision {
+    client: reqwest::Client,
+    api_key: String,
+    endpoint: String,
+    retry_policy: RetryPolicy,
+}
+
+#[async_trait]
+impl OcrProvider for GoogleVision {
+    fn name(&self) -> &'static str {
+        "GoogleVision"
+    }
+
+    async fn recognize(&self, png: &[u8]) -> CloudOcrResult<Vec<CloudOcrLine>> {
+        let body = json!({
+            "requests": [{
+                "image": { "content": base64::engine::general_purpose::STANDARD.encode(png) },
+                "features": [{ "type": "DOCUMENT_TEXT_DETECTION" }],
+            }]
+        });
+        let url = format!("{}/images:annotate", self.endpoint);
+        let response = call_with_resilience("google-vision", &self.retry_policy, || {
+            json_response(
+                self.client
+                    .post(&url)
+                    // Not in the query, the URL of a failed request is logged
+                    .header("x-goog-api-key", &self.api_key)
+                    .json(&body),
+            )
+        })
+        .await?;
+
+        let annotation = &response["responses"][0];
+        if let Some(message) = annotation["error"]["message"].as_str() {
+            return Err(format!("Google Vision error: {}", message).into());
+        }
+        // Words with their characters, a line ends at a line break
+        let mut lines = Vec::new();
+        let mut line = CloudOcrLine {
+            text: String::new(),
+            confidence: None,
+            words: Vec::new(),
+        };
+        let blocks = annotation["fullTextAnnotation"]["pages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|page| page["blocks"].as_array().into_iter().flatten());
+        for paragraph in
+            blocks.flat_map(|block| block["paragraphs"].as_array().into_iter().flatten())
+        {
+            for word in paragraph["words"].as_array().into_iter().flatten() {
+                let mut text = String::new();
+                let mut ends_line = false;
+                let mut space_after = false;
+                for symbol in word["symbols"].as_array().into_iter().flatten() {
+                    text.push_str(symbol["text"].as_str().unwrap_or_default());
+                    match symbol["property"]["detectedBreak"]["type"].as_str() {
+                        Some("SPACE") | Some("SURE_SPACE") => space_after = true,
+                        Some("EOL_SURE_SPACE") | Some("LINE_BREAK") => ends_line = true,
+                        _ => {}
+                    }
+                }
+                let vertices = word["boundingBox"]["vertices"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|vertex| {
+                        (
+                            vertex["x"].as_f64().unwrap_or(0.0),
+                            vertex["y"].as_f64().unwrap_or(0.0),
+                        )
+                    });
+                let (left, top, width, height) = bounding_box(vertices);
+                line.text.push_str(&text);
+                if space_after {
+                    line.text.push(' ');
+                }
+                line.words.push(CloudOcrWord {
+                    text,
+                    left,
+                    top,
+                    width,
+                    height,
+                    confidence: word["confidence"].as_f64().map(|c| c as f32 * 100.0),
+                });
+                if ends_line {
+                    lines.push(std::mem::replace(
+                        &mut line,
+                        CloudOcrLine {
+                            text: String::new(),
+                            confidence: None,
+                            words: Vec::new(),
+                        },
+                    ));
+                }
+            }
+        }
+        if !line.words.is_empty() {
+            lines.push(line);
+        }
+        for line in &mut lines {
+            line.text = line.text.trim_end().to_string();
+            line.confidence = mean_word_confidence(&line.words);
+        }
+        Ok(lines)
+    }
+}
+
+struct AzureVision {
+    client: reqwest::Client,
+    api_key: String,
+    endpoint: String,
+    retry_policy: RetryPolicy,
+}
+
+#[async_trait]
+impl OcrProvider for AzureVision {
+    fn name(&self) -> &'static str {
+        "AzureVision"
+    }
+
+    async fn recognize(&self, png: &[u8]) -> CloudOcrResult<Vec<CloudOcrLine>> {
+        let url = format!(
+            "{}/computervision/imageanalysis:analyze?api-version=2024-02-01&features=read",
+            self.endpoint
+        );
+        let response = call_with_resilience("azure-vision", &self.retry_policy, || {
+            json_response(
+                self.client
+                    .post(&url)
+                    .header("Ocp-Apim-Subscription-Key", &self.api_key)
+                    .header("Content-Type", "application/octet-stream")
+                    .body(png.to_vec()),
+            )
+        })
+        .await?;
+
+        let polygon = |value: &Value| {
+            bounding_box(value.as_array().into_iter().flatten().map(|point| {
+                (
+                    point["x"].as_f64().unwrap_or(0.0),
+                    point["y"].as_f64().unwrap_or(0.0),
+                )
+            }))
+        };
+        let lines = response["readResult"]["blocks"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|block| block["lines"].as_array().into_iter().flatten())
+            .map(|line| {
+                let words: Vec<CloudOcrWord> = line["words"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|word| {
+                        let (left, top, width, height) = polygon(&word["boundingPolygon"]);
+                        CloudOcrWord {
+                            text: word["text"].as_str().unwrap_or_default().to_string(),
+                            left,
+                            top,
+                            width,
+                            height,
+                            confidence: word["confidence"].as_f64().map(|c| c as f32 * 100.0),
+                        }
+                    })
+                    .collect();
+                CloudOcrLine {
+                    text: line["text"].as_str().unwrap_or_default().to_string(),
+                    confidence: mean_word_confidence(&words),
+                    words,
+                }
+            })
+            .collect();
+        Ok(lines)
+    }
+}
+
+fn mean_word_confidence(words: &[CloudOcrWord]) -> Option<f32> {
+    let confidences: Vec<f32> = words.iter().filter_map(|word| word.confidence).collect();
+    if confidences.is_empty() {
+        return None;
+    }
+    Some(confidences.iter().sum::<f32>() / confidences.len() as f32)
+}
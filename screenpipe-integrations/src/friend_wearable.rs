@@ -1,4 +1,5 @@
 use reqwest::Client;
+use screenpipe_core::{call_with_resilience, HttpStatusError, RetryPolicy};
 use serde_json::json;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
@@ -77,16 +78,21 @@ async fn send_data_to_friend_wearable_with_payload(payload: serde_json::Value) -
     debug!("Sending request to friend endpoint: {}", payload);
 
     let client = Client::new();
-    let response = client.post(endpoint)
-        .json(&payload)
-        .send()
-        .await?;
+    let (client, payload) = (&client, &payload);
+    call_with_resilience("friend-wearable", &RetryPolicy::default(), || async move {
+        let response = client.post(endpoint).json(payload).send().await?;
 
-    let status = response.status();
-    
-    if status.is_success() {
-        Ok(())
-    } else {
-        Err(format!("Unexpected response: {}", status).into())
-    }
+        let status = response.status();
+
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err::<(), Box<dyn StdError + Send + Sync>>(
+                HttpStatusError::new(status.as_u16(), format!("Unexpected response: {}", status))
+                    .into(),
+            )
+        }
+    })
+    .await?;
+    Ok(())
 }
\ No newline at end of file
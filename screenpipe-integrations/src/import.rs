@@ -0,0 +1,395 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::Row;
+use std::error::Error as StdError;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Rows read and handed to the database at once, so years of history are neither held in
+/// memory nor in one transaction
+const IMPORT_BATCH_ROWS: usize = 1000;
+
+type ImportResult<T> = Result<T, Box<dyn StdError + Send + Sync>>;
+
+/// Recall tool whose data is imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportSource {
+    /// Decrypted copy of Rewind's `memoryVault/db-enc.sqlite3`
+    Rewind,
+    /// Windows Recall's `ukg.db`
+    WindowsRecall,
+    /// JSON of ActivityWatch's `GET /api/0/export`
+    ActivityWatch,
+}
+
+impl ImportSource {
+    pub const ALL: [ImportSource; 3] = [
+        ImportSource::Rewind,
+        ImportSource::WindowsRecall,
+        ImportSource::ActivityWatch,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportSource::Rewind => "rewind",
+            ImportSource::WindowsRecall => "windows-recall",
+            ImportSource::ActivityWatch => "activitywatch",
+        }
+    }
+
+    /// `device_id` of the imported rows, keeps them apart from the local recordings
+    pub fn device_id(&self) -> String {
+        format!("import:{}", self.as_str())
+    }
+}
+
+impl FromStr for ImportSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ImportSource::ALL
+            .into_iter()
+            .find(|source| source.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "unknown import source {}, expected rewind, windows-recall or activitywatch",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for ImportSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Screen content of another tool, stored like an OCR'd frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedFrame {
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    /// Text seen on screen, the window title when the tool only tracked windows
+    pub text: String,
+}
+
+/// Speech transcribed by another tool
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedTranscription {
+    pub timestamp: DateTime<Utc>,
+    pub text: String,
+    /// Seconds of speech, when known
+    pub duration: Option<f64>,
+}
+
+/// Rows written by `import_history`, the skipped ones were imported before
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub frames_inserted: usize,
+    pub frames_skipped: usize,
+    pub transcriptions_inserted: usize,
+    pub transcriptions_skipped: usize,
+}
+
+#[async_trait]
+pub trait ImportDatabase {
+    /// Stores one batch of imported rows, skipping the ones already imported from `source`
+    async fn insert_imported(
+        &self,
+        source: ImportSource,
+        origin: &str,
+        frames: &[ImportedFrame],
+        transcriptions: &[ImportedTranscription],
+    ) -> ImportResult<ImportSummary>;
+}
+
+/// Reads the data `source` left at `path` and stores it, a page at a time. Importing the
+/// same data twice only adds what is new.
+pub async fn import_history<DB: ImportDatabase + Send + Sync>(
+    source: ImportSource,
+    path: &Path,
+    db: &DB,
+) -> ImportResult<ImportSummary> {
+    info!("Importing the history of {} ({})", source, path.display());
+    let origin = path.to_string_lossy();
+    let mut summary = ImportSummary::default();
+    match source {
+        ImportSource::Rewind => {
+            let pool = open_read_only(path).await?;
+            let mut after = (0, 0);
+            loop {
+                let frames = read_rewind_frames(&pool, &mut after).await?;
+                if frames.is_empty() {
+                    break;
+                }
+                summary.add(db.insert_imported(source, &origin, &frames, &[]).await?);
+            }
+            let mut after = 0;
+            loop {
+                let transcriptions = read_rewind_transcriptions(&pool, &mut after).await?;
+                if transcriptions.is_empty() {
+                    break;
+                }
+                summary.add(
+                    db.insert_imported(source, &origin, &[], &transcriptions)
+                        .await?,
+                );
+            }
+            pool.close().await;
+        }
+        ImportSource::WindowsRecall => {
+            let pool = open_read_only(path).await?;
+            let mut after = 0;
+            loop {
+                let frames = read_windows_recall(&pool, &mut after).await?;
+                if frames.is_empty() {
+                    break;
+                }
+                summary.add(db.insert_imported(source, &origin, &frames, &[]).await?);
+            }
+            pool.close().await;
+        }
+        // One JSON document, parsed as a whole
+        ImportSource::ActivityWatch => {
+            let export = tokio::fs::read_to_string(path).await?;
+            for frames in parse_activitywatch_export(&export)?.chunks(IMPORT_BATCH_ROWS) {
+                summary.add(db.insert_imported(source, &origin, frames, &[]).await?);
+            }
+        }
+    }
+    info!(
+        "Imported {} frames and {} transcriptions from {}, {} frames and {} transcriptions were imported before",
+        summary.frames_inserted,
+        summary.transcriptions_inserted,
+        source,
+        summary.frames_skipped,
+        summary.transcriptions_skipped
+    );
+    Ok(summary)
+}
+
+impl ImportSummary {
+    fn add(&mut self, batch: ImportSummary) {
+        self.frames_inserted += batch.frames_inserted;
+        self.frames_skipped += batch.frames_skipped;
+        self.transcriptions_inserted += batch.transcriptions_inserted;
+        self.transcriptions_skipped += batch.transcriptions_skipped;
+    }
+}
+
+/// Window and browser tab events of an ActivityWatch export, one frame per event with
+/// the title as text. AFK and other buckets are left out.
+pub fn parse_activitywatch_export(export: &str) -> ImportResult<Vec<ImportedFrame>> {
+    let export: Value = serde_json::from_str(export)?;
+    let buckets = export
+        .get("buckets")
+        .and_then(Value::as_object)
+        .ok_or("ActivityWatch export without buckets")?;
+
+    let mut frames = Vec::new();
+    for (bucket_id, bucket) in buckets {
+        let bucket_type = bucket.get("type").and_then(Value::as_str).unwrap_or("");
+        let is_window = bucket_type == "currentwindow";
+        let is_web = bucket_type.starts_with("web.tab");
+        if !is_window && !is_web {
+            debug!("Skipping ActivityWatch bucket {} ({})", bucket_id, bucket_type);
+            continue;
+        }
+        let events = bucket
+            .get("events")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for event in events {
+            let timestamp = match event.get("timestamp").and_then(Value::as_str) {
+                Some(timestamp) => DateTime::parse_from_rfc3339(timestamp)?.with_timezone(&Utc),
+                None => continue,
+            };
+            let data = event.get("data").cloned().unwrap_or_default();
+            let field = |key: &str| data.get(key).and_then(Value::as_str).unwrap_or("");
+            let (app_name, text) = if is_window {
+                (field("app").to_string(), field("title").to_string())
+            } else {
+                let text = [field("title"), field("url")]
+                    .into_iter()
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                (web_bucket_browser(bucket_id), text)
+            };
+            if text.is_empty() {
+                continue;
+            }
+            frames.push(ImportedFrame {
+                timestamp,
+                app_name,
+                text,
+            });
+        }
+    }
+    frames.sort_by_key(|frame| frame.timestamp);
+    Ok(frames)
+}
+
+/// Browser of a web watcher bucket, e.g. `aw-watcher-web-chrome` -> `chrome`
+fn web_bucket_browser(bucket_id: &str) -> String {
+    bucket_id
+        .strip_prefix("aw-watcher-web-")
+        .and_then(|rest| rest.split('_').next())
+        .unwrap_or("browser")
+        .to_string()
+}
+
+async fn open_read_only(path: &Path) -> ImportResult<SqlitePool> {
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.display()).into());
+    }
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true);
+    Ok(SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await?)
+}
+
+/// Next page of the text of Rewind's frames with the app they were seen in, after the
+/// frame and document `after`, which is moved past them. Rewind encrypts its database with
+/// SQLCipher, the copy has to be decrypted first.
+async fn read_rewind_frames(
+    pool: &SqlitePool,
+    after: &mut (i64, i64),
+) -> ImportResult<Vec<ImportedFrame>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT frame.id AS frame_id, content.id AS doc_id, frame.createdAt AS created_at,
+            segment.bundleID AS app_name, content.c0 AS text
+        FROM frame
+        JOIN segment ON segment.id = frame.segmentId
+        JOIN doc_segment ON doc_segment.frameId = frame.id
+        JOIN searchRanking_content AS content ON content.id = doc_segment.docid
+        WHERE (frame.id, content.id) > (?1, ?2) AND content.c0 IS NOT NULL AND content.c0 != ''
+        ORDER BY frame.id, content.id
+        LIMIT ?3
+        "#,
+    )
+    .bind(after.0)
+    .bind(after.1)
+    .bind(IMPORT_BATCH_ROWS as i64)
+    .fetch_all(pool)
+    .await?;
+    let mut frames = Vec::with_capacity(rows.len());
+    for row in rows {
+        *after = (row.try_get("frame_id")?, row.try_get("doc_id")?);
+        frames.push(ImportedFrame {
+            timestamp: parse_timestamp(row.try_get("created_at")?)?,
+            app_name: row
+                .try_get::<Option<String>, _>("app_name")?
+                .unwrap_or_default(),
+            text: row.try_get("text")?,
+        });
+    }
+    Ok(frames)
+}
+
+/// Next page of the transcripts of the meetings Rewind recorded, one per segment after the
+/// segment `after`, which is moved past them
+async fn read_rewind_transcriptions(
+    pool: &SqlitePool,
+    after: &mut i64,
+) -> ImportResult<Vec<ImportedTranscription>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT segment.id AS segment_id, segment.startDate AS start_date,
+            segment.endDate AS end_date, group_concat(word, ' ') AS text
+        FROM (
+            SELECT * FROM transcript_word WHERE segmentId > ?1 ORDER BY segmentId, timeOffset
+        ) AS transcript_word
+        JOIN segment ON segment.id = transcript_word.segmentId
+        GROUP BY transcript_word.segmentId
+        ORDER BY transcript_word.segmentId
+        LIMIT ?2
+        "#,
+    )
+    .bind(*after)
+    .bind(IMPORT_BATCH_ROWS as i64)
+    .fetch_all(pool)
+    .await?;
+    let mut transcriptions = Vec::with_capacity(rows.len());
+    for row in rows {
+        *after = row.try_get("segment_id")?;
+        let start = parse_timestamp(row.try_get("start_date")?)?;
+        let end = row
+            .try_get::<Option<&str>, _>("end_date")?
+            .and_then(|end| parse_timestamp(end).ok());
+        transcriptions.push(ImportedTranscription {
+            timestamp: start,
+            text: row.try_get("text")?,
+            duration: end.map(|end| (end - start).num_milliseconds() as f64 / 1000.0),
+        });
+    }
+    Ok(transcriptions)
+}
+
+/// Next page of the text Windows Recall extracted from each window capture, with the
+/// window's app, after the text of id `after`, which is moved past them
+async fn read_windows_recall(
+    pool: &SqlitePool,
+    after: &mut i64,
+) -> ImportResult<Vec<ImportedFrame>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT content.id AS id, capture.TimeStamp AS timestamp,
+            COALESCE((
+                SELECT COALESCE(app.Name, app.WindowsAppId, app.Path)
+                FROM WindowCaptureAppRelation AS relation
+                JOIN App AS app ON app.Id = relation.AppId
+                WHERE relation.WindowCaptureId = capture.Id
+                LIMIT 1
+            ), '') AS app_name,
+            content.c2 AS text
+        FROM WindowCaptureTextIndex_content AS content
+        JOIN WindowCapture AS capture ON capture.Id = content.c0
+        WHERE content.id > ?1 AND content.c2 IS NOT NULL AND content.c2 != ''
+        ORDER BY content.id
+        LIMIT ?2
+        "#,
+    )
+    .bind(*after)
+    .bind(IMPORT_BATCH_ROWS as i64)
+    .fetch_all(pool)
+    .await?;
+    let mut frames = Vec::with_capacity(rows.len());
+    for row in rows {
+        *after = row.try_get("id")?;
+        let millis: i64 = row.try_get("timestamp")?;
+        frames.push(ImportedFrame {
+            timestamp: Utc
+                .timestamp_millis_opt(millis)
+                .single()
+                .ok_or_else(|| format!("Invalid Windows Recall timestamp {}", millis))?,
+            app_name: row.try_get("app_name")?,
+            text: row.try_get("text")?,
+        });
+    }
+    Ok(frames)
+}
+
+/// Rewind stores naive UTC timestamps, RFC 3339 is accepted as well
+pub fn parse_timestamp(text: &str) -> ImportResult<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .into_iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .map(|time| Utc.from_utc_datetime(&time))
+        .ok_or_else(|| format!("Invalid timestamp {}", text).into())
+}
@@ -1,2 +1,6 @@
+pub mod activitywatch;
+pub mod chat_digest;
+pub mod cloud_ocr;
 pub mod friend_wearable;
-pub mod unstructured_ocr;
\ No newline at end of file
+pub mod import;
+pub mod obsidian;
@@ -0,0 +1,187 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use log::{debug, error, info};
+use std::error::Error as StdError;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TOP_OCR_SNIPPETS: u32 = 20;
+const MAX_SNIPPET_LENGTH: usize = 280;
+
+type DailyNoteResult<T> = Result<T, Box<dyn StdError + Send + Sync>>;
+
+#[async_trait]
+pub trait DailyNoteDatabase {
+    /// (timestamp, transcription) in chronological order
+    async fn get_transcripts(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> DailyNoteResult<Vec<(DateTime<Utc>, String)>>;
+    /// (first seen, app name, text) of the most frequently seen OCR texts
+    async fn get_top_ocr_snippets(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: u32,
+    ) -> DailyNoteResult<Vec<(DateTime<Utc>, String, String)>>;
+    /// (app name, frame count), most used first
+    async fn get_app_usage(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> DailyNoteResult<Vec<(String, i64)>>;
+}
+
+pub struct DailyNote {
+    pub transcripts: Vec<(DateTime<Utc>, String)>,
+    pub ocr_snippets: Vec<(DateTime<Utc>, String, String)>,
+    pub app_usage: Vec<(String, i64)>,
+}
+
+/// Periodically regenerates today's note, and yesterday's one last time after midnight.
+pub async fn initialize_obsidian_loop<DB: DailyNoteDatabase + Send + Sync + 'static>(
+    vault_path: PathBuf,
+    db: Arc<DB>,
+    period: Duration,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        let mut last_date: Option<NaiveDate> = None;
+        debug!("initialize_obsidian_loop started");
+        loop {
+            interval.tick().await;
+
+            let today = Local::now().date_naive();
+            if let Some(previous) = last_date.filter(|date| *date != today) {
+                if let Err(e) = write_daily_note(&vault_path, previous, db.as_ref()).await {
+                    error!("Error writing Obsidian daily note for {}: {}", previous, e);
+                }
+            }
+            if let Err(e) = write_daily_note(&vault_path, today, db.as_ref()).await {
+                error!("Error writing Obsidian daily note for {}: {}", today, e);
+            }
+            last_date = Some(today);
+        }
+    });
+}
+
+/// Writes `<vault>/screenpipe/<YYYY-MM-DD>.md` for the given local date, replacing
+/// any previous version. Returns the path of the note.
+pub async fn write_daily_note<DB: DailyNoteDatabase + Send + Sync>(
+    vault_path: &Path,
+    date: NaiveDate,
+    db: &DB,
+) -> DailyNoteResult<PathBuf> {
+    let note = daily_note(date, db).await?;
+
+    let notes_dir = vault_path.join("screenpipe");
+    tokio::fs::create_dir_all(&notes_dir).await?;
+    let note_path = notes_dir.join(format!("{}.md", date.format("%Y-%m-%d")));
+    tokio::fs::write(&note_path, render_daily_note(date, &note)).await?;
+
+    info!("Wrote Obsidian daily note {}", note_path.display());
+    Ok(note_path)
+}
+
+/// Transcripts, top screen snippets and app usage of the given local date
+pub async fn daily_note<DB: DailyNoteDatabase + Send + Sync>(
+    date: NaiveDate,
+    db: &DB,
+) -> DailyNoteResult<DailyNote> {
+    let (start, end) = local_day_range(date)?;
+    Ok(DailyNote {
+        transcripts: db.get_transcripts(start, end).await?,
+        ocr_snippets: db
+            .get_top_ocr_snippets(start, end, TOP_OCR_SNIPPETS)
+            .await?,
+        app_usage: db.get_app_usage(start, end).await?,
+    })
+}
+
+pub(crate) fn local_day_range(date: NaiveDate) -> DailyNoteResult<(DateTime<Utc>, DateTime<Utc>)> {
+    let start_of_day = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|time| Local.from_local_datetime(&time).earliest())
+            .map(|time| time.with_timezone(&Utc))
+            .ok_or_else(|| format!("Invalid local date {}", date))
+    };
+    let next_day = date
+        .succ_opt()
+        .ok_or_else(|| format!("No day after {}", date))?;
+    Ok((start_of_day(date)?, start_of_day(next_day)?))
+}
+
+pub fn render_daily_note(date: NaiveDate, note: &DailyNote) -> String {
+    let mut markdown = String::new();
+    let _ = writeln!(markdown, "---");
+    let _ = writeln!(markdown, "date: {}", date.format("%Y-%m-%d"));
+    let _ = writeln!(markdown, "tags: [screenpipe]");
+    let _ = writeln!(markdown, "---");
+    let _ = writeln!(markdown, "# {}", date.format("%A, %B %-d, %Y"));
+
+    let _ = writeln!(markdown, "\n## App usage\n");
+    let total_frames: i64 = note.app_usage.iter().map(|(_, frames)| frames).sum();
+    if total_frames == 0 {
+        let _ = writeln!(markdown, "_No screen activity recorded._");
+    }
+    for (app_name, frames) in note.app_usage.iter().filter(|(_, frames)| *frames > 0) {
+        let app_name = if app_name.is_empty() {
+            "unknown"
+        } else {
+            app_name
+        };
+        let _ = writeln!(
+            markdown,
+            "- {}: {:.0}%",
+            app_name,
+            *frames as f64 * 100.0 / total_frames as f64
+        );
+    }
+
+    let _ = writeln!(markdown, "\n## Transcripts\n");
+    if note.transcripts.is_empty() {
+        let _ = writeln!(markdown, "_No transcripts recorded._");
+    }
+    for (timestamp, transcription) in &note.transcripts {
+        let transcription = transcription.trim();
+        if transcription.is_empty() {
+            continue;
+        }
+        let _ = writeln!(
+            markdown,
+            "- **{}** {}",
+            timestamp.with_timezone(&Local).format("%H:%M"),
+            single_line(transcription)
+        );
+    }
+
+    let _ = writeln!(markdown, "\n## Top screen snippets\n");
+    if note.ocr_snippets.is_empty() {
+        let _ = writeln!(markdown, "_No screen text recorded._");
+    }
+    for (timestamp, app_name, text) in &note.ocr_snippets {
+        let text = single_line(text);
+        if text.is_empty() {
+            continue;
+        }
+        let snippet: String = text.chars().take(MAX_SNIPPET_LENGTH).collect();
+        let ellipsis = if snippet.len() < text.len() { "…" } else { "" };
+        let _ = writeln!(
+            markdown,
+            "- **{}** ({}) {}{}",
+            timestamp.with_timezone(&Local).format("%H:%M"),
+            app_name,
+            snippet,
+            ellipsis
+        );
+    }
+
+    markdown
+}
+
+pub(crate) fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -10,7 +10,10 @@ async fn setup_large_db(size: usize) -> DatabaseManager {
     let mut rng = rand::thread_rng();
 
     for _ in 0..size {
-        let _video_id = db.insert_video_chunk("test_video.mp4").await.unwrap();
+        let _video_id = db
+            .insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
         let frame_id = db.insert_frame().await.unwrap();
         let ocr_text = format!("OCR text {}", rng.gen::<u32>());
         let text_json = format!(r#"{{"text": "{}"}}"#, ocr_text);
@@ -55,9 +58,20 @@ fn bench_search(c: &mut Criterion) {
                     |b| {
                         b.to_async(&rt).iter(|| async {
                             let db = setup_large_db(size).await;
-                            db.search(query, content_type, 100, 0, None, None)
-                                .await
-                                .unwrap()
+                            db.search(
+                                query,
+                                content_type,
+                                100,
+                                0,
+                                None,
+                                None,
+                                None,
+                                None,
+                                None,
+                                None,
+                            )
+                            .await
+                            .unwrap()
                         });
                     },
                 );
@@ -0,0 +1,11 @@
+fn main() {
+    println!("cargo:rerun-if-changed=proto/screenpipe.proto");
+
+    #[cfg(feature = "grpc")]
+    {
+        // Building doesn't need protoc installed
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+        std::env::set_var("PROTOC_INCLUDE", protoc_bin_vendored::include_path().unwrap());
+        tonic_build::compile_protos("proto/screenpipe.proto").unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+use crate::{DatabaseManager, HealthMonitor, Subsystem};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Recordings compared at each check, the last hour against the same hour of the
+/// previous days
+const WINDOW_MINUTES: i64 = 60;
+const BASELINE_DAYS: i64 = 7;
+/// Days screenpipe must have recorded during the window before it is checked
+const MIN_BASELINE_DAYS: usize = 3;
+/// Counts below this share of the usual ones are anomalies
+const DROP_RATIO: f64 = 0.2;
+const MIN_USUAL_FRAMES: f64 = 30.0;
+const MIN_USUAL_AUDIO_CHUNKS: f64 = 10.0;
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Frames and audio chunks recorded by this machine during a time window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct IngestCounts {
+    pub frames: i64,
+    /// Frames where OCR found no text
+    pub empty_frames: i64,
+    pub audio_chunks: i64,
+    /// Chunks transcribed to nothing
+    pub silent_chunks: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    FewFrames,
+    EmptyOcr,
+    FewAudioChunks,
+    SilentAudio,
+}
+
+impl AnomalyKind {
+    /// Part of the recording it is about, only checked while that part records
+    pub fn subsystem(self) -> Subsystem {
+        match self {
+            AnomalyKind::FewFrames | AnomalyKind::EmptyOcr => Subsystem::Vision,
+            AnomalyKind::FewAudioChunks | AnomalyKind::SilentAudio => Subsystem::Audio,
+        }
+    }
+}
+
+/// Recording far from what is usual at this time of day, e.g. OCR returning empty text
+/// for an hour. Recording goes on, so the staleness of `/health` doesn't show it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub message: String,
+    /// First check that found it
+    pub since: DateTime<Utc>,
+    /// Frames or audio chunks, or the share of them that are empty, in the last hour
+    pub current: f64,
+    /// Same measure usually at this time of day
+    pub usual: f64,
+}
+
+/// Usual counts of a window, over the previous days screenpipe recorded during it
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Baseline {
+    frames: f64,
+    empty_ratio: f64,
+    audio_chunks: f64,
+    silent_ratio: f64,
+}
+
+impl Baseline {
+    /// `None` with too few days to tell what is usual
+    fn new(days: &[IngestCounts]) -> Option<Self> {
+        let days: Vec<&IngestCounts> = days
+            .iter()
+            .filter(|day| day.frames > 0 || day.audio_chunks > 0)
+            .collect();
+        if days.len() < MIN_BASELINE_DAYS {
+            return None;
+        }
+        let sum = |count: fn(&IngestCounts) -> i64| days.iter().copied().map(count).sum::<i64>();
+        let (frames, audio_chunks) = (sum(|d| d.frames), sum(|d| d.audio_chunks));
+        Some(Baseline {
+            frames: frames as f64 / days.len() as f64,
+            empty_ratio: ratio(sum(|d| d.empty_frames), frames),
+            audio_chunks: audio_chunks as f64 / days.len() as f64,
+            silent_ratio: ratio(sum(|d| d.silent_chunks), audio_chunks),
+        })
+    }
+}
+
+fn ratio(part: i64, total: i64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+/// (kind, message, current, usual) of what is far from `baseline` in `current`
+fn detect(current: &IngestCounts, baseline: &Baseline) -> Vec<(AnomalyKind, String, f64, f64)> {
+    let mut found = Vec::new();
+    let frames = current.frames as f64;
+    if baseline.frames >= MIN_USUAL_FRAMES && frames < baseline.frames * DROP_RATIO {
+        found.push((
+            AnomalyKind::FewFrames,
+            format!(
+                "{} frames recorded in the last hour, usually {:.0} at this time",
+                current.frames, baseline.frames
+            ),
+            frames,
+            baseline.frames,
+        ));
+    }
+    let empty_ratio = ratio(current.empty_frames, current.frames);
+    if frames >= MIN_USUAL_FRAMES && empty_ratio >= 0.9 && baseline.empty_ratio < 0.5 {
+        found.push((
+            AnomalyKind::EmptyOcr,
+            format!(
+                "OCR found no text in {} of the {} frames of the last hour, usually {:.0}%",
+                current.empty_frames,
+                current.frames,
+                baseline.empty_ratio * 100.0
+            ),
+            empty_ratio,
+            baseline.empty_ratio,
+        ));
+    }
+    let audio_chunks = current.audio_chunks as f64;
+    if baseline.audio_chunks >= MIN_USUAL_AUDIO_CHUNKS
+        && audio_chunks < baseline.audio_chunks * DROP_RATIO
+    {
+        found.push((
+            AnomalyKind::FewAudioChunks,
+            format!(
+                "{} audio chunks recorded in the last hour, usually {:.0} at this time",
+                current.audio_chunks, baseline.audio_chunks
+            ),
+            audio_chunks,
+            baseline.audio_chunks,
+        ));
+    }
+    let silent_ratio = ratio(current.silent_chunks, current.audio_chunks);
+    if audio_chunks >= MIN_USUAL_AUDIO_CHUNKS && silent_ratio >= 0.95 && baseline.silent_ratio < 0.6
+    {
+        found.push((
+            AnomalyKind::SilentAudio,
+            format!(
+                "{} of the {} audio chunks of the last hour transcribed to nothing, usually {:.0}%",
+                current.silent_chunks,
+                current.audio_chunks,
+                baseline.silent_ratio * 100.0
+            ),
+            silent_ratio,
+            baseline.silent_ratio,
+        ));
+    }
+    found
+}
+
+/// Compares what was recorded in the last hour to the same hour of the previous days and
+/// keeps the anomalies found, shown by `/health`. Clones share them.
+#[derive(Clone, Default)]
+pub struct AnomalyDetector {
+    active: Arc<RwLock<Vec<Anomaly>>>,
+    webhook_url: Option<String>,
+    client: reqwest::Client,
+}
+
+impl AnomalyDetector {
+    /// Anomalies starting and ending are also POSTed to `webhook_url` when given
+    pub fn new(webhook_url: Option<String>) -> Self {
+        AnomalyDetector {
+            webhook_url,
+            ..AnomalyDetector::default()
+        }
+    }
+
+    /// Anomalies found by the last check
+    pub fn active(&self) -> Vec<Anomaly> {
+        self.active.read().unwrap().clone()
+    }
+
+    /// Checks the hour before `now`, returns the anomalies found
+    pub async fn check(
+        &self,
+        db: &DatabaseManager,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Anomaly>, sqlx::Error> {
+        self.check_subsystems(db, now, &[Subsystem::Vision, Subsystem::Audio])
+            .await
+    }
+
+    /// Checks the hour before `now` for anomalies of `subsystems` only, e.g. those that
+    /// recorded all along. Anomalies of the others are forgotten without notifying.
+    pub async fn check_subsystems(
+        &self,
+        db: &DatabaseManager,
+        now: DateTime<Utc>,
+        subsystems: &[Subsystem],
+    ) -> Result<Vec<Anomaly>, sqlx::Error> {
+        if subsystems.is_empty() {
+            self.clear();
+            return Ok(Vec::new());
+        }
+        let start = now - ChronoDuration::minutes(WINDOW_MINUTES);
+        let current = db.ingest_counts(start, now).await?;
+        let mut days = Vec::new();
+        for day in 1..=BASELINE_DAYS {
+            let shift = ChronoDuration::days(day);
+            days.push(db.ingest_counts(start - shift, now - shift).await?);
+        }
+        let found = match Baseline::new(&days) {
+            Some(baseline) => detect(&current, &baseline)
+                .into_iter()
+                .filter(|(kind, ..)| subsystems.contains(&kind.subsystem()))
+                .collect(),
+            None => {
+                debug!("Not enough recordings at this time of day to detect anomalies yet");
+                Vec::new()
+            }
+        };
+        Ok(self.update(found, now, subsystems))
+    }
+
+    /// Forgets the anomalies without notifying, e.g. while no recording is expected
+    pub fn clear(&self) {
+        self.active.write().unwrap().clear();
+    }
+
+    fn update(
+        &self,
+        found: Vec<(AnomalyKind, String, f64, f64)>,
+        now: DateTime<Utc>,
+        subsystems: &[Subsystem],
+    ) -> Vec<Anomaly> {
+        let mut active = self.active.write().unwrap();
+        let anomalies: Vec<Anomaly> = found
+            .into_iter()
+            .map(|(kind, message, current, usual)| {
+                let ongoing = active.iter().find(|anomaly| anomaly.kind == kind);
+                let since = ongoing.map_or(now, |anomaly| anomaly.since);
+                if ongoing.is_none() {
+                    warn!("Recording anomaly: {}", message);
+                    self.send(json!({
+                        "event": "anomaly_started",
+                        "kind": kind,
+                        "message": message,
+                        // Readable by chat webhooks, e.g. Slack's
+                        "text": format!("screenpipe: {}", message),
+                    }));
+                }
+                Anomaly {
+                    kind,
+                    message,
+                    since,
+                    current,
+                    usual,
+                }
+            })
+            .collect();
+        for ended in active.iter().filter(|anomaly| {
+            subsystems.contains(&anomaly.kind.subsystem())
+                && !anomalies.iter().any(|found| found.kind == anomaly.kind)
+        }) {
+            info!("Recording anomaly ended: {}", ended.message);
+            self.send(json!({
+                "event": "anomaly_ended",
+                "kind": ended.kind,
+                "since": ended.since,
+                "text": format!("screenpipe: back to normal, {}", ended.message),
+            }));
+        }
+        *active = anomalies.clone();
+        anomalies
+    }
+
+    fn send(&self, payload: serde_json::Value) {
+        let Some(webhook_url) = self.webhook_url.clone() else {
+            return;
+        };
+        let request = self
+            .client
+            .post(&webhook_url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(&payload);
+        tokio::spawn(async move {
+            if let Err(e) = request.send().await.and_then(|r| r.error_for_status()) {
+                warn!("Failed to send anomaly to {}: {}", webhook_url, e);
+            }
+        });
+    }
+}
+
+/// Checks for anomalies every `check_interval`. The screen or audio isn't checked when it
+/// was paused or stopped during the last hour, its counts are expected to drop then.
+pub async fn run_anomaly_detection(
+    db: Arc<DatabaseManager>,
+    detector: AnomalyDetector,
+    vision_control: Arc<AtomicBool>,
+    capture_paused: Arc<AtomicBool>,
+    health: Arc<HealthMonitor>,
+    check_interval: Duration,
+) {
+    let window = ChronoDuration::minutes(WINDOW_MINUTES);
+    // Before start, nothing was recorded
+    let mut vision_stopped = Utc::now();
+    let mut audio_stopped = Utc::now();
+    loop {
+        tokio::time::sleep(check_interval).await;
+        let now = Utc::now();
+        let paused = capture_paused.load(Ordering::SeqCst);
+        if paused || !vision_control.load(Ordering::SeqCst) {
+            vision_stopped = now;
+        }
+        // Audio devices are listed from their first chunk until they stop
+        if paused || health.audio_devices().is_empty() {
+            audio_stopped = now;
+        }
+        let subsystems: Vec<Subsystem> = [
+            (Subsystem::Vision, vision_stopped),
+            (Subsystem::Audio, audio_stopped),
+        ]
+        .into_iter()
+        .filter(|(_, stopped)| now - *stopped >= window)
+        .map(|(subsystem, _)| subsystem)
+        .collect();
+        if let Err(e) = detector.check_subsystems(&db, now, &subsystems).await {
+            error!("Failed to check recording anomalies: {}", e);
+        }
+    }
+}
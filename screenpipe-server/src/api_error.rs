@@ -0,0 +1,184 @@
+use axum::async_trait;
+use axum::extract::{FromRequest, FromRequestParts, Json, Path, Query, Request};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Start of the deserialization errors of times, see `ErrorCode::InvalidTime`
+pub(crate) const INVALID_TIME: &str = "invalid time";
+/// Start of the deserialization errors of content types, see
+/// `ErrorCode::InvalidContentType`
+pub(crate) const INVALID_CONTENT_TYPE: &str = "invalid content_type";
+
+/// Machine-readable kind of an `ApiError`, each answered with its own status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Missing or invalid parameter or body
+    InvalidRequest,
+    /// Time that isn't RFC 3339, e.g. `2024-05-01T09:00:00Z`, or a date that doesn't exist
+    InvalidTime,
+    /// `content_type` other than all, ocr, audio, clipboard and notification
+    InvalidContentType,
+    /// Search query with a syntax error, the syntax is in `details.syntax`
+    InvalidSearchQuery,
+    NotFound,
+    /// Not possible in the current state, e.g. capture paused or a file offloaded
+    Conflict,
+    PayloadTooLarge,
+    UriTooLong,
+    /// Too many requests, retry after the `Retry-After` header
+    RateLimited,
+    /// Ollama, a webhook or another service called failed
+    UpstreamFailed,
+    /// Not done in time, e.g. a frame to capture
+    Timeout,
+    /// Failure of screenpipe, e.g. of the database
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidRequest
+            | ErrorCode::InvalidTime
+            | ErrorCode::InvalidContentType
+            | ErrorCode::InvalidSearchQuery => StatusCode::BAD_REQUEST,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::UriTooLong => StatusCode::URI_TOO_LONG,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::UpstreamFailed => StatusCode::BAD_GATEWAY,
+            ErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Error answered by every endpoint, as `{"code": ..., "error": <message>, "details": ...}`.
+/// The message stays under `error`, where clients read it before the codes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    #[serde(rename = "error")]
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.code.status()
+    }
+
+    /// Error of a request axum couldn't extract, `message` being the one of its rejection.
+    /// Answered with the status of its code, 400 for a body that isn't JSON too.
+    fn rejected(status: StatusCode, message: String) -> Self {
+        let code = if status == StatusCode::PAYLOAD_TOO_LARGE {
+            ErrorCode::PayloadTooLarge
+        } else if message.contains(INVALID_TIME) {
+            ErrorCode::InvalidTime
+        } else if message.contains(INVALID_CONTENT_TYPE) {
+            ErrorCode::InvalidContentType
+        } else {
+            ErrorCode::InvalidRequest
+        };
+        ApiError::new(code, message)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self)).into_response()
+    }
+}
+
+/// `Query` answering an `ApiError` when the query string is invalid
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Query::<T>::from_request_parts(parts, state).await {
+            Ok(Query(value)) => Ok(ApiQuery(value)),
+            Err(rejection) => Err(ApiError::rejected(
+                rejection.status(),
+                rejection.body_text(),
+            )),
+        }
+    }
+}
+
+/// `Path` answering an `ApiError` when a segment is invalid, e.g. an id that isn't a number
+pub struct ApiPath<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Path::<T>::from_request_parts(parts, state).await {
+            Ok(Path(value)) => Ok(ApiPath(value)),
+            Err(rejection) => Err(ApiError::rejected(
+                rejection.status(),
+                rejection.body_text(),
+            )),
+        }
+    }
+}
+
+/// `Json` body answering an `ApiError` when the body is invalid
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(request, state).await {
+            Ok(Json(value)) => Ok(ApiJson(value)),
+            Err(rejection) => Err(ApiError::rejected(
+                rejection.status(),
+                rejection.body_text(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{DatabaseManager, EventKind};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+/// Chunks `prune_chunks` deletes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkKind {
+    Video,
+    Audio,
+    #[default]
+    All,
+}
+
+impl ChunkKind {
+    fn includes_video(self) -> bool {
+        matches!(self, ChunkKind::Video | ChunkKind::All)
+    }
+
+    fn includes_audio(self) -> bool {
+        matches!(self, ChunkKind::Audio | ChunkKind::All)
+    }
+}
+
+/// Chunks of one type recorded by this machine
+#[derive(Debug, Default, Serialize)]
+pub struct ChunkUsage {
+    pub chunks: u64,
+    /// Chunks offloaded to object storage, only their stub is counted in `bytes`
+    pub offloaded: u64,
+    pub bytes: u64,
+    pub oldest: Option<DateTime<Utc>>,
+    pub newest: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DiskUsage {
+    pub total_bytes: u64,
+    pub database_bytes: u64,
+    pub video: ChunkUsage,
+    pub audio: ChunkUsage,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PruneSummary {
+    pub video_chunks: usize,
+    pub audio_chunks: usize,
+    pub freed_bytes: u64,
+    /// Pruned chunks whose offloaded copy is left in object storage
+    pub offloaded: usize,
+    /// Files that couldn't be deleted, their chunks are kept and pruned by the next run
+    pub failed: Vec<String>,
+    /// Nothing was deleted, the summary is what would have been
+    pub dry_run: bool,
+}
+
+/// Disk usage of the database and of the chunks it references, computed from the database
+/// so it works on the data directory of a stopped server as well as through the API
+pub async fn disk_usage(db: &DatabaseManager) -> Result<DiskUsage> {
+    let (chunks, offloaded, oldest, newest) = db.get_video_chunk_stats().await?;
+    let video = ChunkUsage {
+        chunks: chunks as u64,
+        offloaded: offloaded as u64,
+        bytes: files_size(db.list_video_chunks().await?).await,
+        oldest,
+        newest,
+    };
+    let (chunks, offloaded, oldest, newest) = db.get_audio_chunk_stats().await?;
+    let audio = ChunkUsage {
+        chunks: chunks as u64,
+        offloaded: offloaded as u64,
+        bytes: files_size(db.list_audio_chunks().await?).await,
+        oldest,
+        newest,
+    };
+    let database_bytes = db.get_database_size().await? as u64;
+    Ok(DiskUsage {
+        total_bytes: database_bytes + video.bytes + audio.bytes,
+        database_bytes,
+        video,
+        audio,
+    })
+}
+
+/// Deletes the chunks of `kind` recorded by this machine before `before`, their files and
+/// their frames, OCR text and transcriptions. The video chunk being recorded is kept.
+/// Offloaded chunks lose their stub, their copy in object storage is left as is.
+/// A chunk is only deleted from the database once its file is gone.
+/// The database file only shrinks on its next VACUUM.
+pub async fn prune_chunks(
+    db: &DatabaseManager,
+    before: DateTime<Utc>,
+    kind: ChunkKind,
+    dry_run: bool,
+) -> Result<PruneSummary> {
+    let mut summary = PruneSummary {
+        dry_run,
+        ..Default::default()
+    };
+    if kind.includes_video() {
+        for (id, file_path, remote_key) in db.get_video_chunks_to_offload(before).await? {
+            let Some(size) = summary.remove_file(file_path, dry_run).await else {
+                continue;
+            };
+            if !dry_run {
+                db.delete_video_chunk(id).await?;
+            }
+            summary.freed_bytes += size;
+            summary.offloaded += remote_key.is_some() as usize;
+            summary.video_chunks += 1;
+        }
+    }
+    if kind.includes_audio() {
+        for (id, file_path, remote_key) in db.get_audio_chunks_to_offload(before).await? {
+            let Some(size) = summary.remove_file(file_path, dry_run).await else {
+                continue;
+            };
+            if !dry_run {
+                db.delete_audio_chunk(id).await?;
+            }
+            summary.freed_bytes += size;
+            summary.offloaded += remote_key.is_some() as usize;
+            summary.audio_chunks += 1;
+        }
+    }
+    if !dry_run {
+        let message = format!(
+            "Pruned {} video and {} audio chunks recorded before {}, {} bytes freed",
+            summary.video_chunks, summary.audio_chunks, before, summary.freed_bytes
+        );
+        info!("{}", message);
+        if !summary.failed.is_empty() {
+            warn!(
+                "{} chunks are kept as their file couldn't be deleted",
+                summary.failed.len()
+            );
+        }
+        if summary.video_chunks + summary.audio_chunks > 0 {
+            let data = serde_json::json!({
+                "reason": "prune",
+                "before": before,
+                "video_chunks": summary.video_chunks,
+                "audio_chunks": summary.audio_chunks,
+                "freed_bytes": summary.freed_bytes,
+            });
+            db.insert_event(EventKind::ChunksPurged, &message, Some(&data))
+                .await?;
+        }
+    }
+    Ok(summary)
+}
+
+async fn files_size(chunks: Vec<(i64, String)>) -> u64 {
+    let mut size = 0;
+    for (_, file_path) in chunks {
+        if let Ok(metadata) = tokio::fs::metadata(&file_path).await {
+            size += metadata.len();
+        }
+    }
+    size
+}
+
+impl PruneSummary {
+    /// Size of the file, deleted unless `dry_run`. None when it couldn't be deleted, it
+    /// is then listed in `failed`.
+    async fn remove_file(&mut self, file_path: String, dry_run: bool) -> Option<u64> {
+        match remove_chunk_file(&file_path, dry_run).await {
+            Ok(size) => Some(size),
+            Err(e) => {
+                error!("Failed to delete {}: {}", file_path, e);
+                self.failed.push(file_path);
+                None
+            }
+        }
+    }
+}
+
+/// Size of the file, deleted unless `dry_run`. A missing file frees nothing.
+pub(crate) async fn remove_chunk_file(file_path: &str, dry_run: bool) -> std::io::Result<u64> {
+    let size = match tokio::fs::metadata(file_path).await {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(_) => 0,
+    };
+    if !dry_run {
+        if let Err(e) = tokio::fs::remove_file(file_path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e);
+            }
+        }
+    }
+    Ok(size)
+}
@@ -0,0 +1,266 @@
+use crate::{ContentType, DatabaseManager, SearchResult};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::net::IpAddr;
+use std::time::Duration;
+
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+pub const DEFAULT_OLLAMA_MODEL: &str = "llama3.2";
+/// Candidates searched per source kept, re-ranked by the question words they contain
+const CANDIDATES_PER_SOURCE: u32 = 4;
+/// Characters of a source put in the prompt
+const MAX_SOURCE_LENGTH: usize = 1000;
+/// Local models can take minutes on a CPU
+const GENERATE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Words of a question that don't help find where the answer was seen or heard
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "all", "an", "and", "any", "are", "as", "at", "be", "before", "but", "by",
+    "can", "could", "did", "do", "does", "for", "from", "had", "has", "have", "how", "if", "in",
+    "into", "is", "it", "its", "last", "me", "my", "no", "not", "of", "on", "or", "our", "over",
+    "said", "saw", "see", "seen", "should", "show", "so", "tell", "that", "the", "their", "them",
+    "then", "there", "these", "they", "this", "to", "up", "was", "we", "were", "what", "when",
+    "where", "which", "who", "why", "will", "with", "would", "you", "your",
+];
+
+/// Client of the Ollama server answering `POST /ask`. It must run on this machine, the
+/// recordings put in the prompts never leave it.
+#[derive(Debug, Clone)]
+pub struct OllamaClient {
+    url: String,
+    model: String,
+    client: reqwest::Client,
+}
+
+impl OllamaClient {
+    /// Fails when `url` isn't an http(s) URL of this machine
+    pub fn new(url: &str, model: &str) -> Result<Self> {
+        let parsed =
+            reqwest::Url::parse(url).map_err(|e| anyhow!("invalid Ollama URL {}: {}", url, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(anyhow!("invalid Ollama URL {}, expected http(s)", url));
+        }
+        let host = parsed.host_str().unwrap_or_default();
+        let local = host.eq_ignore_ascii_case("localhost")
+            || host
+                .trim_matches(|c| c == '[' || c == ']')
+                .parse::<IpAddr>()
+                .map_or(false, |ip| ip.is_loopback());
+        if !local {
+            return Err(anyhow!(
+                "Ollama must run on this machine so recordings don't leave it, got {}",
+                url
+            ));
+        }
+        Ok(OllamaClient {
+            url: url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Model used when the request doesn't pick one
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Completion of `prompt` by `model`, or the default model
+    pub async fn generate(&self, prompt: &str, model: Option<&str>) -> Result<String> {
+        let response: serde_json::Value = self
+            .client
+            .post(format!("{}/api/generate", self.url))
+            .timeout(GENERATE_TIMEOUT)
+            .json(&json!({
+                "model": model.unwrap_or(&self.model),
+                "prompt": prompt,
+                "stream": false,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        response["response"]
+            .as_str()
+            .map(|answer| answer.trim().to_string())
+            .ok_or_else(|| anyhow!("Ollama response without an answer: {}", response))
+    }
+}
+
+impl Default for OllamaClient {
+    fn default() -> Self {
+        OllamaClient::new(DEFAULT_OLLAMA_URL, DEFAULT_OLLAMA_MODEL).unwrap()
+    }
+}
+
+/// Recorded text given to the model to answer a question, cited as `[id]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AskSource {
+    pub id: usize,
+    pub content_type: ContentType,
+    /// Frame the text was read on, for OCR sources
+    pub frame_id: Option<i64>,
+    /// Chunk the text was heard in, for audio sources
+    pub audio_chunk_id: Option<i64>,
+    pub timestamp: DateTime<Utc>,
+    /// App name, "audio" for transcriptions
+    pub source: String,
+    pub text: String,
+}
+
+/// Lowercase words of `question` worth searching, in order
+pub fn question_words(question: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in question
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+    {
+        if word.chars().count() >= 2
+            && !STOP_WORDS.contains(&word.as_str())
+            && !words.contains(&word)
+        {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Up to `limit` recordings of the time range most likely to answer `question`: those
+/// containing the most words of it, then the most recent. Repeated texts, e.g. a screen
+/// that didn't change, are kept once.
+pub async fn retrieve_sources(
+    db: &DatabaseManager,
+    question: &str,
+    limit: usize,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+) -> Result<Vec<AskSource>, sqlx::Error> {
+    let words = question_words(question);
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+    // Any of the words matches
+    let results = db
+        .search(
+            &words.join(" OR "),
+            ContentType::All,
+            limit as u32 * CANDIDATES_PER_SOURCE,
+            0,
+            start_time,
+            end_time,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+    let mut candidates: Vec<(usize, AskSource)> = results
+        .into_iter()
+        .map(|result| {
+            let source = to_source(result);
+            let text = source.text.to_lowercase();
+            let matched = words
+                .iter()
+                .filter(|word| text.contains(word.as_str()))
+                .count();
+            (matched, source)
+        })
+        .collect();
+    candidates.sort_by(|(a_matched, a), (b_matched, b)| {
+        b_matched.cmp(a_matched).then(b.timestamp.cmp(&a.timestamp))
+    });
+
+    let mut seen = HashSet::new();
+    let mut sources = Vec::new();
+    for (_, mut source) in candidates {
+        if sources.len() == limit {
+            break;
+        }
+        if !seen.insert(source.text.clone()) {
+            continue;
+        }
+        source.id = sources.len() + 1;
+        sources.push(source);
+    }
+    Ok(sources)
+}
+
+fn to_source(result: SearchResult) -> AskSource {
+    let (content_type, frame_id, audio_chunk_id, timestamp, source, text) = match result {
+        SearchResult::OCR(ocr) => (
+            ContentType::OCR,
+            Some(ocr.frame_id),
+            None,
+            ocr.timestamp,
+            ocr.app_name,
+            ocr.ocr_text,
+        ),
+        SearchResult::Audio(audio) => (
+            ContentType::Audio,
+            None,
+            Some(audio.audio_chunk_id),
+            audio.timestamp,
+            "audio".to_string(),
+            audio.transcription,
+        ),
+        SearchResult::Clipboard(clipboard) => (
+            ContentType::Clipboard,
+            None,
+            None,
+            clipboard.timestamp,
+            "clipboard".to_string(),
+            clipboard.text,
+        ),
+        SearchResult::Notification(notification) => (
+            ContentType::Notification,
+            None,
+            None,
+            notification.timestamp,
+            notification.app_name,
+            format!("{}: {}", notification.title, notification.body),
+        ),
+    };
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = match text.char_indices().nth(MAX_SOURCE_LENGTH) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    };
+    AskSource {
+        id: 0,
+        content_type,
+        frame_id,
+        audio_chunk_id,
+        timestamp,
+        source,
+        text,
+    }
+}
+
+/// Prompt asking the model to answer `question` from `sources` only, citing them
+pub fn build_prompt(question: &str, sources: &[AskSource]) -> String {
+    let mut prompt = String::from(
+        "You answer questions about what the user saw on their screen and heard, using the \
+         excerpts of their recordings below. Answer only from the excerpts and cite the ones \
+         you use as [n]. If they don't contain the answer, say you don't know.\n\n",
+    );
+    for source in sources {
+        let _ = writeln!(
+            prompt,
+            "[{}] {} ({}) {}",
+            source.id,
+            source
+                .timestamp
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            source.source,
+            source.text
+        );
+    }
+    let _ = write!(prompt, "\nQuestion: {}\nAnswer:", question.trim());
+    prompt
+}
@@ -0,0 +1,257 @@
+use crate::{audio_device_of, AudioRecord, DatabaseManager, Shutdown, Storage};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use screenpipe_audio::{
+    AudioDeviceConfig, AudioFormat, AudioInput, TranscriptionPriority, TranscriptionResult,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+
+/// Times a chunk whose transcription failed is transcribed again before it's given up on
+const MAX_TRANSCRIPTION_ATTEMPTS: i64 = 3;
+
+/// Chunks sent for transcription at once, the next ones wait until they're transcribed
+const BATCH_SIZE: i64 = 10;
+
+/// How often chunks to transcribe again are looked for
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long the transcription of a chunk is waited for before it's counted as failed, e.g.
+/// when its result was lost to a panic of the transcription task
+pub const IN_FLIGHT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// A chunk sent for transcription again
+struct Retranscription {
+    audio_chunk_id: i64,
+    /// Path of the chunk, the one sent is a decrypted copy when it's encrypted
+    file_path: String,
+    timestamp: DateTime<Utc>,
+    sent_at: Instant,
+    /// Counted as failed after `IN_FLIGHT_TIMEOUT`, its result is still stored if it comes
+    expired: bool,
+}
+
+/// Transcribes again, in the background, the audio chunks without transcription because it
+/// failed, e.g. while the Whisper model couldn't load, or queued with
+/// `POST /audio/retranscribe`. The chunks go through the whisper channel of the recording,
+/// behind the chunks being recorded, or ahead of them when queued.
+#[derive(Clone)]
+pub struct TranscriptionBackfill {
+    db: Arc<DatabaseManager>,
+    /// By the path sent for transcription
+    in_flight: Arc<Mutex<HashMap<String, Retranscription>>>,
+}
+
+impl TranscriptionBackfill {
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        TranscriptionBackfill {
+            db,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Sends the chunks to transcribe again to `whisper_sender`, a batch at a time, until
+    /// the shutdown
+    pub async fn run(
+        self,
+        whisper_sender: UnboundedSender<AudioInput>,
+        audio_device_configs: watch::Receiver<HashMap<String, AudioDeviceConfig>>,
+        shutdown: Shutdown,
+    ) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+                _ = shutdown.requested() => return,
+            }
+            self.expire(IN_FLIGHT_TIMEOUT).await;
+            if self.in_flight() > 0 {
+                continue;
+            }
+            if !self
+                .send_batch(&whisper_sender, &audio_device_configs)
+                .await
+            {
+                // The recording stopped
+                return;
+            }
+        }
+    }
+
+    /// Sends the next batch of chunks to transcribe again to `whisper_sender`, returns false
+    /// once it's closed
+    pub async fn send_batch(
+        &self,
+        whisper_sender: &UnboundedSender<AudioInput>,
+        audio_device_configs: &watch::Receiver<HashMap<String, AudioDeviceConfig>>,
+    ) -> bool {
+        let chunks = match self
+            .db
+            .chunks_to_retranscribe(MAX_TRANSCRIPTION_ATTEMPTS, BATCH_SIZE)
+            .await
+        {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                error!("Failed to get the audio chunks to transcribe again: {}", e);
+                return true;
+            }
+        };
+        if !chunks.is_empty() {
+            info!("Transcribing {} audio chunks again", chunks.len());
+        }
+        for (audio_chunk_id, file_path, timestamp, requested) in chunks {
+            if let Err(e) = self.db.start_retranscription(audio_chunk_id).await {
+                error!("Failed to transcribe {} again: {}", file_path, e);
+                continue;
+            }
+            let path = match self.readable_path(&file_path) {
+                Ok(path) => path,
+                Err(e) => {
+                    self.fail(audio_chunk_id, &file_path, &e.to_string()).await;
+                    continue;
+                }
+            };
+            let device = audio_device_of(&file_path);
+            let preprocessing = audio_device_configs
+                .borrow()
+                .get(&device)
+                .map(AudioDeviceConfig::preprocessing)
+                .unwrap_or_default();
+            self.in_flight.lock().unwrap().insert(
+                path.clone(),
+                Retranscription {
+                    audio_chunk_id,
+                    file_path,
+                    timestamp,
+                    sent_at: Instant::now(),
+                    expired: false,
+                },
+            );
+            // Asked for, ahead of the recording
+            let priority = if requested {
+                TranscriptionPriority::OnDemand
+            } else {
+                TranscriptionPriority::Background
+            };
+            let input = AudioInput {
+                path,
+                device,
+                priority,
+                preprocessing,
+            };
+            if whisper_sender.send(input).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Chunks sent for transcription whose result is waited for
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|retranscription| !retranscription.expired)
+            .count()
+    }
+
+    /// Counts the chunks sent longer than `timeout` ago as failed, for the next batch not to
+    /// wait on results that won't come. They're transcribed again until out of attempts.
+    /// Returns how many.
+    pub async fn expire(&self, timeout: Duration) -> usize {
+        let expired: Vec<(i64, String)> = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .values_mut()
+            .filter(|retranscription| {
+                !retranscription.expired && retranscription.sent_at.elapsed() >= timeout
+            })
+            .map(|retranscription| {
+                retranscription.expired = true;
+                (
+                    retranscription.audio_chunk_id,
+                    retranscription.file_path.clone(),
+                )
+            })
+            .collect();
+        for (audio_chunk_id, file_path) in &expired {
+            self.fail(*audio_chunk_id, file_path, "transcription timed out")
+                .await;
+        }
+        expired.len()
+    }
+
+    /// Stores `result` if it's of a chunk sent by the backfill, else gives it back
+    pub async fn complete(
+        &self,
+        storage: &dyn Storage,
+        result: TranscriptionResult,
+    ) -> Option<TranscriptionResult> {
+        let retranscription = self.in_flight.lock().unwrap().remove(&result.input.path);
+        let Some(retranscription) = retranscription else {
+            return Some(result);
+        };
+        if retranscription.file_path != result.input.path {
+            if let Err(e) = std::fs::remove_file(&result.input.path) {
+                warn!("Failed to remove {}: {}", result.input.path, e);
+            }
+        }
+        let transcription = match (result.error, result.transcription) {
+            (None, Some(transcription)) => transcription,
+            (error, _) => {
+                let error = error.unwrap_or_else(|| "no transcription".to_string());
+                self.fail(
+                    retranscription.audio_chunk_id,
+                    &retranscription.file_path,
+                    &error,
+                )
+                .await;
+                return None;
+            }
+        };
+        debug!("Transcribed {} again", retranscription.file_path);
+        let record = AudioRecord {
+            timestamp: retranscription.timestamp,
+            format: AudioFormat::from_path(&retranscription.file_path)
+                .map(|format| format.name().to_string()),
+            file_path: retranscription.file_path,
+            duration: Some(result.duration),
+            transcription,
+            transcription_engine: result.engine,
+            language: result.language,
+            segments: result.segments,
+            entities: Vec::new(),
+            transcription_error: None,
+        };
+        if let Err(e) = storage.write_retranscription(record).await {
+            error!("Failed to store the transcription of an audio chunk: {}", e);
+        }
+        None
+    }
+
+    async fn fail(&self, audio_chunk_id: i64, file_path: &str, error: &str) {
+        warn!("Failed to transcribe {} again: {}", file_path, error);
+        if let Err(e) = self.db.set_transcription_error(audio_chunk_id, error).await {
+            error!("Failed to record the transcription error: {}", e);
+        }
+    }
+
+    /// Path the chunk can be decoded from, a decrypted copy when it's encrypted. The copy is
+    /// removed by `complete`, or by `encrypt_chunk_dir` on the next start when the result
+    /// never comes.
+    fn readable_path(&self, file_path: &str) -> anyhow::Result<String> {
+        #[cfg(feature = "encryption")]
+        if let Some(key) = self.db.encryption_key() {
+            if let Some(copy) =
+                crate::encryption::decrypted_copy(std::path::Path::new(file_path), key)?
+            {
+                return Ok(copy.keep()?.to_string_lossy().into_owned());
+            }
+        }
+        Ok(file_path.to_string())
+    }
+}
@@ -0,0 +1,411 @@
+use crate::{DatabaseManager, DatabaseSettings, LOCAL_DEVICE_ID};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Written last, a backup without it is incomplete
+const MANIFEST_FILE: &str = "manifest.json";
+const DATABASE_FILE: &str = "db.sqlite";
+const MANIFEST_VERSION: u32 = 1;
+
+/// Content of a backup, checked on restore
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    /// Migration of the database, a newer screenpipe migrates it on start
+    pub schema_version: Option<i64>,
+    pub database_size: u64,
+    pub database_sha256: String,
+    pub media: Vec<BackupMedia>,
+}
+
+/// Video or audio chunk referenced by the database
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupMedia {
+    /// Path in the database
+    pub file_path: String,
+    /// Name in the backup
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BackupSummary {
+    pub media_files: usize,
+    /// Of the database and the media files
+    pub bytes: u64,
+    /// Referenced by the database but gone, e.g. evicted while backing up
+    pub missing_files: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreSummary {
+    pub media_files: usize,
+    pub bytes: u64,
+}
+
+/// Where a backup is written to or restored from
+pub enum BackupLocation {
+    Directory(PathBuf),
+    #[cfg(feature = "s3")]
+    S3 {
+        bucket: Box<s3::Bucket>,
+        prefix: String,
+    },
+}
+
+impl BackupLocation {
+    /// A directory, or `s3://bucket/prefix` (requires the s3 feature) with the credentials
+    /// of AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, AWS_REGION, and AWS_ENDPOINT_URL for
+    /// other S3-compatible services (MinIO, Backblaze B2, ...)
+    pub fn parse(location: &str) -> Result<Self> {
+        match location.strip_prefix("s3://") {
+            #[cfg(feature = "s3")]
+            Some(path) => {
+                let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+                Ok(BackupLocation::S3 {
+                    bucket: open_bucket(bucket)?,
+                    prefix: prefix.trim_matches('/').to_string(),
+                })
+            }
+            #[cfg(not(feature = "s3"))]
+            Some(_) => Err(anyhow!(
+                "s3:// locations require screenpipe to be built with the `s3` feature"
+            )),
+            None if location.is_empty() => Err(anyhow!("location is required")),
+            None => Ok(BackupLocation::Directory(PathBuf::from(location))),
+        }
+    }
+
+    pub(crate) async fn put_file(&self, name: &str, path: &Path) -> Result<()> {
+        match self {
+            BackupLocation::Directory(dir) => {
+                let target = dir.join(name);
+                if let Some(parent) = target.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                tokio::fs::copy(path, target).await?;
+            }
+            #[cfg(feature = "s3")]
+            BackupLocation::S3 { bucket, prefix } => {
+                let mut file = tokio::fs::File::open(path).await?;
+                bucket
+                    .put_object_stream(&mut file, object_key(prefix, name))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn put_bytes(&self, name: &str, content: &[u8]) -> Result<()> {
+        match self {
+            BackupLocation::Directory(dir) => {
+                tokio::fs::create_dir_all(dir).await?;
+                tokio::fs::write(dir.join(name), content).await?;
+            }
+            #[cfg(feature = "s3")]
+            BackupLocation::S3 { bucket, prefix } => {
+                bucket.put_object(object_key(prefix, name), content).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn get_file(&self, name: &str, path: &Path) -> Result<()> {
+        match self {
+            BackupLocation::Directory(dir) => {
+                tokio::fs::copy(dir.join(name), path).await?;
+            }
+            #[cfg(feature = "s3")]
+            BackupLocation::S3 { bucket, prefix } => {
+                let mut file = tokio::fs::File::create(path).await?;
+                bucket
+                    .get_object_to_writer(object_key(prefix, name), &mut file)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_bytes(&self, name: &str) -> Result<Vec<u8>> {
+        match self {
+            BackupLocation::Directory(dir) => Ok(tokio::fs::read(dir.join(name)).await?),
+            #[cfg(feature = "s3")]
+            BackupLocation::S3 { bucket, prefix } => Ok(bucket
+                .get_object(object_key(prefix, name))
+                .await?
+                .bytes()
+                .to_vec()),
+        }
+    }
+}
+
+#[cfg(feature = "s3")]
+fn object_key(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// Bucket with the credentials and region of the AWS_* environment variables
+#[cfg(feature = "s3")]
+pub(crate) fn open_bucket(name: &str) -> Result<Box<s3::Bucket>> {
+    let region_name = std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+    let credentials = s3::creds::Credentials::default()?;
+    match std::env::var("AWS_ENDPOINT_URL") {
+        // Other S3-compatible services rarely support bucket subdomains
+        Ok(endpoint) => {
+            let region = s3::Region::Custom {
+                region: region_name,
+                endpoint,
+            };
+            Ok(s3::Bucket::new(name, region, credentials)?.with_path_style())
+        }
+        Err(_) => Ok(s3::Bucket::new(name, region_name.parse()?, credentials)?),
+    }
+}
+
+/// Size and SHA-256 of a file, read in the blocking pool
+pub(crate) async fn file_digest(path: PathBuf) -> Result<(u64, String)> {
+    tokio::task::spawn_blocking(move || -> Result<(u64, String)> {
+        let mut hasher = Sha256::new();
+        let size = std::io::copy(&mut std::fs::File::open(&path)?, &mut hasher)?;
+        Ok((size, format!("{:x}", hasher.finalize())))
+    })
+    .await?
+}
+
+/// `data/<file name>`, prefixed with a number if another chunk has the same file name
+fn media_name(file_path: &str, names: &mut HashSet<String>) -> String {
+    let file_name = Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "chunk".to_string());
+    let mut name = format!("data/{}", file_name);
+    let mut n = 1;
+    while !names.insert(name.clone()) {
+        name = format!("data/{}-{}", n, file_name);
+        n += 1;
+    }
+    name
+}
+
+/// Consistent snapshot of the database, taken with `VACUUM INTO` so recording goes on
+/// meanwhile, and the video/audio chunks recorded by this machine. Copying the database
+/// file of a running screenpipe gives a corrupt copy in WAL mode.
+pub async fn backup(db: &DatabaseManager, location: &BackupLocation) -> Result<BackupSummary> {
+    let snapshot_path = std::env::temp_dir().join(format!(
+        "screenpipe-backup-{}-{}.sqlite",
+        std::process::id(),
+        Utc::now().timestamp_millis()
+    ));
+    db.backup_into(&snapshot_path).await?;
+    let result = async {
+        let digest = file_digest(snapshot_path.clone()).await?;
+        location.put_file(DATABASE_FILE, &snapshot_path).await?;
+        Ok::<_, anyhow::Error>(digest)
+    }
+    .await;
+    let _ = tokio::fs::remove_file(&snapshot_path).await;
+    let (database_size, database_sha256) = result?;
+
+    let mut summary = BackupSummary {
+        bytes: database_size,
+        ..Default::default()
+    };
+    // Listed after the snapshot, chunks started since are only extra files
+    let mut file_paths = db.list_video_chunks().await?;
+    file_paths.extend(db.list_audio_chunks().await?);
+    let mut names = HashSet::new();
+    let mut media = Vec::new();
+    for (_, file_path) in file_paths {
+        let (size, sha256) = match file_digest(PathBuf::from(&file_path)).await {
+            Ok(digest) => digest,
+            Err(e)
+                if e.downcast_ref::<std::io::Error>()
+                    .map_or(false, |e| e.kind() == ErrorKind::NotFound) =>
+            {
+                warn!("Skipping missing chunk {}", file_path);
+                summary.missing_files.push(file_path);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let name = media_name(&file_path, &mut names);
+        location.put_file(&name, Path::new(&file_path)).await?;
+        summary.media_files += 1;
+        summary.bytes += size;
+        media.push(BackupMedia {
+            file_path,
+            name,
+            size,
+            sha256,
+        });
+    }
+
+    let manifest = BackupManifest {
+        version: MANIFEST_VERSION,
+        created_at: Utc::now(),
+        schema_version: db.schema_version().await?,
+        database_size,
+        database_sha256,
+        media,
+    };
+    location
+        .put_bytes(MANIFEST_FILE, &serde_json::to_vec_pretty(&manifest)?)
+        .await?;
+    info!(
+        "Backed up the database and {} chunks, {} bytes",
+        summary.media_files, summary.bytes
+    );
+    Ok(summary)
+}
+
+/// Restores a backup into `data_dir`, which must not have a database yet. The database
+/// and every chunk are checked against the manifest, nothing is restored if one is
+/// missing or corrupt. Chunks go to `data_dir/data` and the database points to them.
+/// `settings` must have the encryption key of a backup of an encrypted database.
+pub async fn restore_backup(
+    location: &BackupLocation,
+    data_dir: &Path,
+    settings: &DatabaseSettings,
+) -> Result<RestoreSummary> {
+    let db_path = data_dir.join(DATABASE_FILE);
+    if db_path.exists() {
+        return Err(anyhow!(
+            "{} already exists, restore into another data directory",
+            db_path.display()
+        ));
+    }
+    let manifest = location.get_bytes(MANIFEST_FILE).await.map_err(|e| {
+        anyhow!(
+            "No complete backup found, can't read {}: {}",
+            MANIFEST_FILE,
+            e
+        )
+    })?;
+    let manifest: BackupManifest = serde_json::from_slice(&manifest)?;
+    if manifest.version > MANIFEST_VERSION {
+        return Err(anyhow!(
+            "Backup made by a newer screenpipe (manifest version {})",
+            manifest.version
+        ));
+    }
+
+    let chunk_dir = data_dir.join("data");
+    tokio::fs::create_dir_all(&chunk_dir).await?;
+    let restoring_path = data_dir.join(format!("{}.restoring", DATABASE_FILE));
+    let result = restore_into(location, &manifest, &restoring_path, &chunk_dir, settings).await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&restoring_path).await;
+        return result;
+    }
+    tokio::fs::rename(&restoring_path, &db_path).await?;
+    info!(
+        "Restored the database of {} and {} chunks into {}",
+        manifest.created_at,
+        manifest.media.len(),
+        data_dir.display()
+    );
+    result
+}
+
+async fn restore_into(
+    location: &BackupLocation,
+    manifest: &BackupManifest,
+    database_path: &Path,
+    chunk_dir: &Path,
+    settings: &DatabaseSettings,
+) -> Result<RestoreSummary> {
+    location.get_file(DATABASE_FILE, database_path).await?;
+    let (size, sha256) = file_digest(database_path.to_path_buf()).await?;
+    if size != manifest.database_size || sha256 != manifest.database_sha256 {
+        return Err(anyhow!("The database of the backup is corrupt"));
+    }
+    let mut summary = RestoreSummary {
+        bytes: size,
+        ..Default::default()
+    };
+
+    let mut invalid = Vec::new();
+    let mut restored_paths = Vec::new();
+    for media in &manifest.media {
+        let file_name = Path::new(&media.name)
+            .file_name()
+            .ok_or_else(|| anyhow!("Invalid chunk name {} in the manifest", media.name))?;
+        let path = chunk_dir.join(file_name);
+        if let Err(e) = location.get_file(&media.name, &path).await {
+            invalid.push(format!("{} ({})", media.name, e));
+            continue;
+        }
+        let (size, sha256) = file_digest(path.clone()).await?;
+        if size != media.size || sha256 != media.sha256 {
+            invalid.push(format!("{} (corrupt)", media.name));
+            continue;
+        }
+        summary.media_files += 1;
+        summary.bytes += size;
+        restored_paths.push((media.file_path.as_str(), path));
+    }
+    if !invalid.is_empty() {
+        return Err(anyhow!(
+            "{} chunks of the backup are missing or corrupt: {}",
+            invalid.len(),
+            invalid.join(", ")
+        ));
+    }
+
+    let options = SqliteConnectOptions::new().filename(database_path);
+    // The snapshot of an encrypted database is encrypted with the same key
+    #[cfg(feature = "encryption")]
+    let options = match &settings.encryption_key {
+        Some(key) => options.pragma("key", key.sqlcipher_key()),
+        None => options,
+    };
+    #[cfg(not(feature = "encryption"))]
+    let _ = settings;
+    let mut conn = options.connect().await?;
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut conn)
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Can't read the database of the backup, is it encrypted with another key? {}",
+                e
+            )
+        })?;
+    if integrity != "ok" {
+        return Err(anyhow!(
+            "The database of the backup is corrupt: {}",
+            integrity
+        ));
+    }
+    let mut tx = conn.begin().await?;
+    for (file_path, path) in &restored_paths {
+        for table in ["video_chunks", "audio_chunks"] {
+            sqlx::query(&format!(
+                "UPDATE {} SET file_path = ?1 WHERE file_path = ?2 AND device_id = ?3",
+                table
+            ))
+            .bind(path.to_string_lossy())
+            .bind(file_path)
+            .bind(LOCAL_DEVICE_ID)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    conn.close().await?;
+    Ok(summary)
+}
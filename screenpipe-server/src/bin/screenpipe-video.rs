@@ -4,15 +4,17 @@ use env_logger::Env;
 use image::GenericImageView;
 use log::info;
 use screenpipe_server::core::DataOutputWrapper;
-use screenpipe_server::VideoCapture;
-use screenpipe_vision::OcrEngine;
+use screenpipe_server::{LiveSettings, Shutdown, VideoCapture, VideoEncoding, VisionStatus};
+use screenpipe_vision::{CaptureTrigger, OcrEngine, OcrLanguages};
 use serde_json::{json, Value};
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Write};
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 use tokio::sync::mpsc::{channel, Receiver, Sender}; // Correct import
+use tokio::sync::watch;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -49,7 +51,10 @@ async fn main() {
     // create dir if not exists
     std::fs::create_dir_all("data").unwrap_or_default();
     info!("Created data directory {}", output_path);
-    let fps = 10.0;
+    let (_settings_sender, settings) = watch::channel(LiveSettings {
+        fps: 10.0,
+        ..Default::default()
+    });
 
     let new_chunk_callback = {
         move |file_path: &str| {
@@ -59,10 +64,25 @@ async fn main() {
 
     let video_capture = VideoCapture::new(
         output_path,
-        fps,
+        settings,
         new_chunk_callback,
         save_text_files,
         Arc::new(OcrEngine::Tesseract),
+        None,
+        OcrLanguages::default(),
+        None,
+        false,
+        None,
+        None,
+        Vec::new(),
+        None,
+        VideoEncoding::default(),
+        Arc::new(AtomicBool::new(false)),
+        Arc::new(CaptureTrigger::new()),
+        1,
+        None,
+        Arc::new(VisionStatus::new()),
+        Shutdown::new(),
     ); // Pass the cloud_ocr flag
     let (_tx, rx): (Sender<()>, Receiver<()>) = channel(32);
     let rx = Arc::new(Mutex::new(rx));
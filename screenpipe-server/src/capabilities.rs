@@ -0,0 +1,124 @@
+use crate::API_VERSION;
+#[cfg(feature = "audio")]
+use screenpipe_audio::CloudSttProvider;
+use serde::{Deserialize, Serialize};
+
+/// Cargo features of the server, see Cargo.toml
+const FEATURES: [(&str, bool); 10] = [
+    ("recording", cfg!(feature = "recording")),
+    ("audio", cfg!(feature = "audio")),
+    ("cloud", cfg!(feature = "cloud")),
+    ("integrations", cfg!(feature = "integrations")),
+    ("postgres", cfg!(feature = "postgres")),
+    ("encryption", cfg!(feature = "encryption")),
+    ("s3", cfg!(feature = "s3")),
+    ("grpc", cfg!(feature = "grpc")),
+    ("metal", cfg!(feature = "metal")),
+    ("cuda", cfg!(feature = "cuda")),
+];
+
+/// What records the screen or audio on this platform
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureBackend {
+    /// "screen" or "audio"
+    pub kind: String,
+    pub name: String,
+}
+
+/// What this build can do on this platform, for clients to adapt their UI to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub version: String,
+    pub api_version: String,
+    /// "macos", "windows" or "linux"
+    pub platform: String,
+    /// Cargo features the server was built with
+    pub features: Vec<String>,
+    /// Values of --ocr-engine that work on this build
+    pub ocr_engines: Vec<String>,
+    /// "whisper" for local transcription, then the values of --cloud-audio-provider
+    pub transcription_engines: Vec<String>,
+    /// Empty without the recording feature, the server only serves the API
+    pub capture_backends: Vec<CaptureBackend>,
+}
+
+impl Capabilities {
+    pub fn detect() -> Self {
+        let features = FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name.to_string())
+            .collect();
+
+        let mut ocr_engines = vec!["tesseract"];
+        if cfg!(target_os = "windows") {
+            ocr_engines.push("windows-native");
+        }
+        if screenpipe_vision::APPLE_NATIVE_OCR {
+            ocr_engines.push("apple-native");
+        }
+        if cfg!(feature = "cloud") {
+            ocr_engines.push("cloud");
+        }
+
+        let mut capture_backends = Vec::new();
+        if cfg!(feature = "recording") {
+            capture_backends.push(CaptureBackend {
+                kind: "screen".to_string(),
+                name: screen_backend().to_string(),
+            });
+        }
+        if cfg!(feature = "audio") {
+            capture_backends.push(CaptureBackend {
+                kind: "audio".to_string(),
+                name: audio_backend().to_string(),
+            });
+        }
+
+        Capabilities {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            api_version: API_VERSION.to_string(),
+            platform: std::env::consts::OS.to_string(),
+            features,
+            ocr_engines: ocr_engines.into_iter().map(str::to_string).collect(),
+            transcription_engines: transcription_engines()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            capture_backends,
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+fn transcription_engines() -> Vec<&'static str> {
+    let mut engines = vec!["whisper"];
+    if cfg!(feature = "cloud") {
+        engines.extend(CloudSttProvider::ALL.iter().map(CloudSttProvider::as_str));
+    }
+    engines
+}
+
+#[cfg(not(feature = "audio"))]
+fn transcription_engines() -> Vec<&'static str> {
+    Vec::new()
+}
+
+/// What xcap captures the screen with
+fn screen_backend() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "coregraphics",
+        "windows" => "gdi",
+        _ if std::env::var_os("WAYLAND_DISPLAY").is_some() => "wayland",
+        _ => "x11",
+    }
+}
+
+/// The default host of cpal
+fn audio_backend() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "coreaudio",
+        "windows" => "wasapi",
+        _ => "alsa",
+    }
+}
@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::oneshot;
+
+/// A frame captured on request and stored, see `POST /vision/capture_now`. One per request,
+/// of the monitor recorded.
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedFrame {
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    pub text: String,
+}
+
+/// Callers waiting for the next frame requested through the `CaptureTrigger` to be
+/// stored. The recording loop answers them once it wrote the frame.
+#[derive(Clone, Default)]
+pub struct CaptureRequests {
+    waiting: Arc<Mutex<Vec<(Instant, oneshot::Sender<CapturedFrame>)>>>,
+}
+
+impl CaptureRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits for a requested frame captured from now on, request it with the
+    /// `CaptureTrigger` after calling this
+    pub fn wait(&self) -> oneshot::Receiver<CapturedFrame> {
+        let (sender, receiver) = oneshot::channel();
+        self.waiting.lock().unwrap().push((Instant::now(), sender));
+        receiver
+    }
+
+    /// Answers the callers waiting since before `captured_at` with the stored frame. The
+    /// ones that came later wait for the next requested frame.
+    pub fn complete(&self, captured_at: Instant, frame: &CapturedFrame) {
+        let mut waiting = self.waiting.lock().unwrap();
+        let (answered, still_waiting): (Vec<_>, Vec<_>) = waiting
+            .drain(..)
+            .partition(|(requested_at, _)| *requested_at <= captured_at);
+        *waiting = still_waiting;
+        drop(waiting);
+        for (_, sender) in answered {
+            let _ = sender.send(frame.clone());
+        }
+    }
+}
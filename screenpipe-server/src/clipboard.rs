@@ -0,0 +1,122 @@
+use crate::{DatabaseManager, LiveSettings};
+use chrono::Utc;
+use log::{debug, error, info};
+use screenpipe_vision::{
+    clipboard_is_concealed, frontmost_app, SensitiveAction, SensitiveContentFilter,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Records the text copied to the clipboard, with the frontmost app it was copied from,
+/// for `content_type=clipboard` in `/search`. Like the screen, nothing is recorded while
+/// vision is stopped or capture paused. Text copied from the ignored apps, marked concealed
+/// by the app that copied it, e.g. a password manager, or flagged by the sensitive content
+/// filter isn't kept.
+pub struct ClipboardMonitor {
+    db: Arc<DatabaseManager>,
+    settings: watch::Receiver<LiveSettings>,
+    vision_control: Arc<AtomicBool>,
+    capture_paused: Arc<AtomicBool>,
+    sensitive_content: Option<SensitiveContentFilter>,
+    /// Text of the last poll, the clipboard keeps its content until the next copy
+    last_text: Mutex<Option<String>>,
+}
+
+impl ClipboardMonitor {
+    pub fn new(
+        db: Arc<DatabaseManager>,
+        settings: watch::Receiver<LiveSettings>,
+        vision_control: Arc<AtomicBool>,
+        capture_paused: Arc<AtomicBool>,
+        sensitive_content: Option<SensitiveContentFilter>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            db,
+            settings,
+            vision_control,
+            capture_paused,
+            sensitive_content,
+            last_text: Mutex::new(None),
+        })
+    }
+
+    /// Polls the clipboard every `interval`. The app of a copy is the frontmost one when
+    /// the poll notices it, up to `interval` later, another app if the user switched since.
+    pub fn start_monitoring(self: &Arc<Self>, interval: Duration) {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            // What was copied before the start isn't recorded
+            *monitor.last_text.lock().unwrap() = read_clipboard().await;
+            info!("Recording the clipboard every {:?}", interval);
+            loop {
+                tokio::time::sleep(interval).await;
+                monitor.check_clipboard().await;
+            }
+        });
+    }
+
+    async fn check_clipboard(&self) {
+        let Some(text) = read_clipboard().await else {
+            return;
+        };
+        {
+            let mut last_text = self.last_text.lock().unwrap();
+            if last_text.as_deref() == Some(text.as_str()) {
+                return;
+            }
+            // Even while paused, what was copied then isn't recorded on resume
+            *last_text = Some(text.clone());
+        }
+        if !self.vision_control.load(Ordering::SeqCst) || self.capture_paused.load(Ordering::SeqCst)
+        {
+            debug!("Skipping the text copied while capture is stopped or paused");
+            return;
+        }
+        if tokio::task::spawn_blocking(clipboard_is_concealed)
+            .await
+            .unwrap_or(true)
+        {
+            debug!("Skipping the text copied as concealed");
+            return;
+        }
+
+        let app_name = tokio::task::spawn_blocking(frontmost_app)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        if self.settings.borrow().is_ignored(&app_name) {
+            debug!("Skipping the text copied from ignored app {}", app_name);
+            return;
+        }
+        if let Some(filter) = &self.sensitive_content {
+            // Text only keeps the text of flagged frames, the others leave it out
+            if let Some(category) = filter.classify_text(&text) {
+                if filter.action != SensitiveAction::TextOnly {
+                    debug!("Skipping the text copied, flagged as {}", category.as_str());
+                    return;
+                }
+            }
+        }
+        match self.db.insert_clipboard(&text, &app_name, Utc::now()).await {
+            Ok(id) => debug!("Recorded clipboard entry {} from {:?}", id, app_name),
+            Err(e) => error!("Failed to record the clipboard: {}", e),
+        }
+    }
+}
+
+/// Text on the clipboard, `None` when it's empty or holds something else, e.g. an image
+async fn read_clipboard() -> Option<String> {
+    tokio::task::spawn_blocking(|| {
+        let text = arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .map_err(|e| debug!("No text on the clipboard: {}", e))
+            .ok()?;
+        (!text.trim().is_empty()).then_some(text)
+    })
+    .await
+    .ok()
+    .flatten()
+}
@@ -0,0 +1,113 @@
+use crate::{parse_search_query, ContentType, DatabaseManager};
+use chrono::{DateTime, Utc};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Filters of `GET /search` kept by a collection, with the same meaning
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionFilters {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    /// Every type when empty, several give a page of each
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content_type: Vec<ContentType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fuzzy: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_confidence: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+}
+
+impl CollectionFilters {
+    /// Engine to search the text of, `None` for the latest text
+    pub fn engine(&self) -> Option<&str> {
+        self.engine
+            .as_deref()
+            .filter(|engine| !engine.eq_ignore_ascii_case("latest"))
+    }
+}
+
+/// Search saved with `POST /collections`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub filters: CollectionFilters,
+    /// Content matching the filters is tagged with the collection as it arrives, see
+    /// `GET /collections/:id/items`
+    pub pinned: bool,
+    /// Of a pinned collection, the content recorded before was tagged
+    pub tagged_until: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Body of `POST /collections` and `PUT /collections/:id`
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewCollection {
+    pub name: String,
+    #[serde(default)]
+    pub filters: CollectionFilters,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl NewCollection {
+    /// Why the collection can't be searched, e.g. an invalid query
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("A collection needs a name".to_string());
+        }
+        let filters = &self.filters;
+        if let Some(q) = &filters.q {
+            parse_search_query(q).map_err(|e| e.to_string())?;
+        }
+        if filters
+            .min_confidence
+            .map_or(false, |confidence| !(0.0..=100.0).contains(&confidence))
+        {
+            return Err("min_confidence must be between 0 and 100".to_string());
+        }
+        if filters.content_type.len() > 1 && filters.content_type.contains(&ContentType::All) {
+            return Err("content_type all can't be combined with other types".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Content tagged with a pinned collection
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CollectionItem {
+    pub content_type: ContentType,
+    /// Id of the frame, audio transcription, clipboard copy or notification
+    pub content_id: i64,
+    pub timestamp: DateTime<Utc>,
+    /// Latest OCR text, transcription, copied text or notification body
+    pub text: Option<String>,
+    pub app_name: Option<String>,
+    pub tagged_at: DateTime<Utc>,
+}
+
+/// Tags the new content with the pinned collections every `interval`, see
+/// `DatabaseManager::tag_pinned_collections`
+pub async fn run_collection_tagging(db: Arc<DatabaseManager>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        match db.tag_pinned_collections().await {
+            Ok(0) => {}
+            Ok(tagged) => debug!("Tagged {} items with pinned collections", tagged),
+            Err(e) => error!("Failed to tag the new content with collections: {}", e),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{ContentProcessor, FrameRecord};
+use rusty_tesseract::tesseract::output_data::DataOutput;
+use serde::{Deserialize, Serialize};
+
+/// A word OCR read, a line for Apple Vision, with how sure the engine is of it and where it
+/// is in the frame. Stored as a JSON array of these in `ocr_text.words`, e.g.
+/// `[{"text": "invoice", "confidence": 92.5, "left": 10, "top": 40, "width": 60, "height": 12}]`,
+/// `confidence` left out when the engine reports none and the box 0 in frames stored before
+/// it was.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrWord {
+    pub text: String,
+    /// 0 - 100, `None` when the engine reports none for the word
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    /// Bounding box in pixels of the frame, relative to its capture region if any
+    #[serde(default)]
+    pub left: i32,
+    #[serde(default)]
+    pub top: i32,
+    #[serde(default)]
+    pub width: i32,
+    #[serde(default)]
+    pub height: i32,
+}
+
+/// Words of an OCR result with their confidence and bounding box, without the page, block
+/// and line rows Tesseract reports too. A negative `conf` means the engine reports none, the
+/// word keeps its box.
+pub fn ocr_words(data_output: &DataOutput) -> Vec<OcrWord> {
+    data_output
+        .data
+        .iter()
+        .filter(|data| !data.text.trim().is_empty())
+        .map(|data| OcrWord {
+            text: data.text.trim().to_string(),
+            confidence: (data.conf >= 0.0).then_some(data.conf as f64),
+            left: data.left,
+            top: data.top,
+            width: data.width,
+            height: data.height,
+        })
+        .collect()
+}
+
+/// Mean confidence of the `words` with one, `None` without any
+pub fn mean_confidence(words: &[OcrWord]) -> Option<f64> {
+    let confidences: Vec<f64> = words.iter().filter_map(|word| word.confidence).collect();
+    if confidences.is_empty() {
+        return None;
+    }
+    Some(confidences.iter().sum::<f64>() / confidences.len() as f64)
+}
+
+/// Drops the text of the frames OCR read with a mean confidence below `min_confidence`,
+/// mostly garbage read off pictures and videos which bloats the index and matches
+/// searches. Frames without confidence, e.g. of Windows OCR, are kept.
+pub struct ConfidenceFloor {
+    min_confidence: f64,
+}
+
+impl ConfidenceFloor {
+    pub fn new(min_confidence: f64) -> Self {
+        ConfidenceFloor { min_confidence }
+    }
+}
+
+/// Runs first, the other processors don't need to see the dropped text
+impl ContentProcessor for ConfidenceFloor {
+    fn name(&self) -> &str {
+        "confidence-floor"
+    }
+
+    fn process_frame(&self, frame: FrameRecord) -> Option<FrameRecord> {
+        match mean_confidence(&frame.words) {
+            Some(confidence) if confidence < self.min_confidence => None,
+            _ => Some(frame),
+        }
+    }
+}
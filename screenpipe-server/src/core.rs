@@ -1,21 +1,61 @@
-use crate::{DatabaseManager, VideoCapture};
+#[cfg(feature = "recording")]
+use crate::supervisor::supervise;
+#[cfg(feature = "audio")]
+use crate::supervisor::restart_backoff;
+#[cfg(feature = "audio")]
+use crate::backfill::TranscriptionBackfill;
+#[cfg(feature = "recording")]
+use crate::reocr::FrameReocr;
+#[cfg(feature = "audio")]
+use crate::AudioRecord;
+#[cfg(all(feature = "recording", feature = "encryption"))]
+use crate::EncryptedStorage;
+use crate::archive::remove_chunk_file;
+use crate::{DatabaseManager, EventKind, LiveSettings};
+#[cfg(feature = "recording")]
+use crate::{
+    ocr_words, BackupLocation, CaptureRequests, CapturedFrame, ContentProcessors, FrameRecord,
+    HealthMonitor, MirroredStorage, MonitorConfig, ProcessedStorage, Shutdown, Storage, Subsystem,
+    VideoCapture, VideoEncoding, VisionStatus,
+};
 use anyhow::Result;
+#[cfg(feature = "recording")]
 use chrono::Utc;
+#[cfg(feature = "audio")]
 use crossbeam::queue::SegQueue;
-use log::{debug, error, info, warn};
+#[cfg(feature = "recording")]
+use log::debug;
+use log::{error, info, warn};
+#[cfg(feature = "audio")]
 use screenpipe_audio::{
-    create_whisper_channel, record_and_transcribe, AudioDevice, AudioInput, DeviceControl,
-    TranscriptionResult,
+    create_whisper_channel, default_device, play_synthetic_audio, record_and_transcribe,
+    AudioDevice, AudioDeviceConfig, AudioEncoding, AudioFormat, AudioInput, CloudSttConfig,
+    DeviceControl, DeviceType, TranscriptionLanguages, TranscriptionResult, WhisperModelConfig,
 };
+#[cfg(feature = "integrations")]
 use screenpipe_integrations::friend_wearable::{initialize_friend_wearable_loop};
-use screenpipe_vision::OcrEngine;
+#[cfg(feature = "recording")]
+use screenpipe_vision::{
+    CaptureResult, CaptureTrigger, OcrEngine, OcrLanguages, ScreenMask, SensitiveContentFilter,
+};
+#[cfg(feature = "audio")]
 use std::collections::HashMap;
+use std::path::Path;
+#[cfg(feature = "recording")]
 use std::path::PathBuf;
+#[cfg(feature = "recording")]
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+#[cfg(feature = "audio")]
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+#[cfg(feature = "audio")]
+use tokio::sync::Mutex;
+#[cfg(feature = "audio")]
 use tokio::task::JoinHandle;
+#[cfg(feature = "audio")]
+use tokio::time::Instant;
 
 pub enum RecorderControl {
     Pause,
@@ -29,111 +69,515 @@ pub struct DataOutputWrapper {
 }
 
 impl DataOutputWrapper {
+    /// Tesseract records of the OCR output as JSON, the words with their box are typed in
+    /// `ocr_words`
     pub fn to_json(&self) -> String {
-        let data_json: Vec<String> = self.data_output.data.iter().map(|d| {
-            format!(
-                r#"{{"level": {}, "page_num": {}, "block_num": {}, "par_num": {}, "line_num": {}, "word_num": {}, "left": {}, "top": {}, "width": {}, "height": {}, "conf": {}, "text": "{}"}}"#,
-                d.level, d.page_num, d.block_num, d.par_num, d.line_num, d.word_num, d.left, d.top, d.width, d.height, d.conf, d.text
-            )
-        }).collect();
-        format!(
-            r#"{{"output": "{}", "data": [{}]}}"#,
-            self.data_output.output,
-            data_json.join(", ")
-        )
+        let data: Vec<serde_json::Value> = self
+            .data_output
+            .data
+            .iter()
+            .map(|d| {
+                serde_json::json!({
+                    "level": d.level,
+                    "page_num": d.page_num,
+                    "block_num": d.block_num,
+                    "par_num": d.par_num,
+                    "line_num": d.line_num,
+                    "word_num": d.word_num,
+                    "left": d.left,
+                    "top": d.top,
+                    "width": d.width,
+                    "height": d.height,
+                    "conf": d.conf,
+                    "text": d.text,
+                })
+            })
+            .collect();
+        serde_json::json!({"output": self.data_output.output, "data": data}).to_string()
+    }
+}
+
+/// Settings of a recording started with `start_continuous_recording`
+#[cfg(feature = "recording")]
+#[derive(Clone)]
+pub struct RecordingConfig {
+    /// Storage the recordings are mirrored to, next to the local database
+    pub sync_storage: Option<Arc<dyn Storage>>,
+    /// Where offloaded chunks are fetched back from before they're read again
+    pub offload: Option<Arc<BackupLocation>>,
+    pub content_processors: ContentProcessors,
+    pub output_path: Arc<String>,
+    pub audio_chunk_duration: Duration,
+    /// Restarts the vision capture when no frame was recorded for this long
+    pub stall_timeout: Option<Duration>,
+    #[cfg(feature = "audio")]
+    pub follow_default_audio: bool,
+    #[cfg(feature = "audio")]
+    pub audio_encoding: AudioEncoding,
+    pub save_text_files: bool,
+    #[cfg(feature = "audio")]
+    pub cloud_stt: Option<CloudSttConfig>,
+    pub ocr_engine: Arc<OcrEngine>,
+    pub ocr_max_dimension: Option<u32>,
+    pub ocr_languages: OcrLanguages,
+    pub dedup_threshold: Option<f64>,
+    pub differential_ocr: bool,
+    pub sensitive_content: Option<SensitiveContentFilter>,
+    pub screen_mask: Option<ScreenMask>,
+    pub monitors: Vec<MonitorConfig>,
+    pub idle_fps: Option<f64>,
+    pub video_encoding: VideoEncoding,
+    pub ocr_pool_size: usize,
+    pub transcription_concurrency: usize,
+    #[cfg(feature = "audio")]
+    pub transcription_languages: TranscriptionLanguages,
+    /// Frames read from this directory instead of the screen
+    pub synthetic_frames_dir: Option<PathBuf>,
+    /// Audio read from this directory instead of the devices
+    pub synthetic_audio_dir: Option<PathBuf>,
+    pub friend_wearable_uid: Option<String>,
+}
+
+#[cfg(feature = "recording")]
+impl RecordingConfig {
+    /// Records into `output_path` with the defaults of the CLI
+    pub fn new(output_path: impl Into<String>) -> Self {
+        Self {
+            sync_storage: None,
+            offload: None,
+            content_processors: ContentProcessors::new(),
+            output_path: Arc::new(output_path.into()),
+            audio_chunk_duration: Duration::from_secs(30),
+            stall_timeout: None,
+            #[cfg(feature = "audio")]
+            follow_default_audio: false,
+            #[cfg(feature = "audio")]
+            audio_encoding: AudioEncoding::default(),
+            save_text_files: false,
+            #[cfg(feature = "audio")]
+            cloud_stt: None,
+            ocr_engine: Arc::new(OcrEngine::default()),
+            ocr_max_dimension: None,
+            ocr_languages: OcrLanguages::default(),
+            dedup_threshold: None,
+            differential_ocr: false,
+            sensitive_content: None,
+            screen_mask: None,
+            monitors: Vec::new(),
+            idle_fps: None,
+            video_encoding: VideoEncoding::default(),
+            ocr_pool_size: 1,
+            transcription_concurrency: 1,
+            #[cfg(feature = "audio")]
+            transcription_languages: TranscriptionLanguages::default(),
+            synthetic_frames_dir: None,
+            synthetic_audio_dir: None,
+            friend_wearable_uid: None,
+        }
     }
 }
 
+/// State a recording shares with the server, which controls it through these
+#[cfg(feature = "recording")]
+#[derive(Clone)]
+pub struct RecordingHandles {
+    pub settings: watch::Receiver<LiveSettings>,
+    pub vision_control: Arc<AtomicBool>,
+    pub vision_status: Arc<VisionStatus>,
+    pub health: Arc<HealthMonitor>,
+    #[cfg(feature = "audio")]
+    pub audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
+    #[cfg(feature = "audio")]
+    pub audio_device_configs: watch::Receiver<HashMap<String, AudioDeviceConfig>>,
+    #[cfg(feature = "audio")]
+    pub whisper_model: watch::Receiver<WhisperModelConfig>,
+    pub capture_paused: Arc<AtomicBool>,
+    pub capture_trigger: Arc<CaptureTrigger>,
+    pub capture_requests: CaptureRequests,
+    pub shutdown: Shutdown,
+}
+
+/// Records until `vision_control` is unset or `shutdown` is requested. On shutdown the
+/// frames and audio captured so far are written and the open chunks are finalized before it
+/// returns.
+#[cfg(feature = "recording")]
 pub async fn start_continuous_recording(
     db: Arc<DatabaseManager>,
-    output_path: Arc<String>,
-    fps: f64,
-    audio_chunk_duration: Duration,
-    vision_control: Arc<AtomicBool>,
-    audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
-    save_text_files: bool,
-    cloud_audio: bool,
-    ocr_engine: Arc<OcrEngine>,
-    friend_wearable_uid: Option<String>,
+    config: RecordingConfig,
+    handles: RecordingHandles,
 ) -> Result<()> {
+    let RecordingConfig {
+        sync_storage,
+        offload,
+        content_processors,
+        output_path,
+        audio_chunk_duration,
+        stall_timeout,
+        #[cfg(feature = "audio")]
+        follow_default_audio,
+        #[cfg(feature = "audio")]
+        audio_encoding,
+        save_text_files,
+        #[cfg(feature = "audio")]
+        cloud_stt,
+        ocr_engine,
+        ocr_max_dimension,
+        ocr_languages,
+        dedup_threshold,
+        differential_ocr,
+        sensitive_content,
+        screen_mask,
+        monitors,
+        idle_fps,
+        video_encoding,
+        ocr_pool_size,
+        transcription_concurrency,
+        #[cfg(feature = "audio")]
+        transcription_languages,
+        synthetic_frames_dir,
+        synthetic_audio_dir,
+        friend_wearable_uid,
+    } = config;
+    let RecordingHandles {
+        settings,
+        vision_control,
+        vision_status,
+        health,
+        #[cfg(feature = "audio")]
+        audio_devices_control,
+        #[cfg(feature = "audio")]
+        audio_device_configs,
+        #[cfg(feature = "audio")]
+        whisper_model,
+        capture_paused,
+        capture_trigger,
+        capture_requests,
+        shutdown,
+    } = handles;
     info!("Recording now");
+    let _shutdown_guard = shutdown.guard();
+    record_recording_started(&db, &ocr_engine).await;
 
-    let (whisper_sender, whisper_receiver) = create_whisper_channel(cloud_audio).await?;
-
-    let db_manager_video = Arc::clone(&db);
-    let db_manager_audio = Arc::clone(&db);
+    #[cfg(not(feature = "audio"))]
+    {
+        let _ = (audio_chunk_duration, transcription_concurrency);
+        if synthetic_audio_dir.is_some() {
+            warn!("screenpipe was built without the audio feature, ignoring synthetic audio");
+        }
+    }
 
-    let is_running_video = Arc::clone(&vision_control);
+    #[cfg(feature = "audio")]
+    let (whisper_sender, whisper_receiver) = create_whisper_channel(
+        cloud_stt,
+        transcription_concurrency,
+        transcription_languages,
+        whisper_model,
+    )
+    .await?;
 
-    let output_path_video = Arc::clone(&output_path);
-    let output_path_audio = Arc::clone(&output_path);
+    #[cfg(feature = "audio")]
+    if let Some(dir) = synthetic_audio_dir {
+        let whisper_sender = whisper_sender.clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            // Stopped on shutdown so the whisper queue can drain
+            tokio::select! {
+                result = play_synthetic_audio(dir, audio_chunk_duration, whisper_sender) => {
+                    if let Err(e) = result {
+                        error!("Synthetic audio playback error: {}", e);
+                    }
+                }
+                _ = shutdown.requested() => {}
+            }
+        });
+    }
 
-    let friend_wearable_uid_video = friend_wearable_uid.clone();
+    // Recordings always go to the local database, which serves the API
+    let storage: Arc<dyn Storage> = match sync_storage {
+        Some(sync_storage) => Arc::new(MirroredStorage::new(db.clone(), sync_storage)),
+        None => db.clone(),
+    };
+    // Chunks recorded into an encrypted database are encrypted once complete
+    #[cfg(feature = "encryption")]
+    let storage: Arc<dyn Storage> = match db.encryption_key() {
+        Some(key) => Arc::new(EncryptedStorage::new(
+            storage,
+            key.clone(),
+            Path::new(&*output_path),
+        )),
+        None => storage,
+    };
+    // Processed before anything is written, mirrored or encrypted
+    let storage: Arc<dyn Storage> = if content_processors.is_empty() {
+        storage
+    } else {
+        Arc::new(ProcessedStorage::new(storage, content_processors))
+    };
 
     // Initialize friend wearable loop
+    #[cfg(feature = "integrations")]
     if let Some(uid) = &friend_wearable_uid {
         tokio::spawn(initialize_friend_wearable_loop(uid.clone(), Arc::clone(&db)));
     }
+    #[cfg(not(feature = "integrations"))]
+    if friend_wearable_uid.is_some() {
+        warn!("screenpipe was built without the integrations feature, not sending data to friend wearable");
+    }
 
-    let video_handle = tokio::spawn(async move {
-        record_video(
-            db_manager_video,
-            output_path_video,
-            fps,
-            is_running_video,
-            save_text_files,
-            ocr_engine,
-            friend_wearable_uid_video,
+    // Frames queued by `POST /vision/reocr`, read again with the engine of the recording
+    tokio::spawn(
+        FrameReocr::new(
+            Arc::clone(&db),
+            Arc::clone(&storage),
+            &ocr_engine,
+            ocr_languages.clone(),
+            ocr_max_dimension,
+            offload,
         )
-        .await
-    });
-
-    let audio_handle = tokio::spawn(async move {
-        record_audio(
-            db_manager_audio,
-            output_path_audio,
-            audio_chunk_duration,
-            whisper_sender,
-            whisper_receiver,
-            audio_devices_control,
-            friend_wearable_uid,
-            cloud_audio,
-        )
-        .await
-    });
+        .run(shutdown.clone()),
+    );
 
-    let video_result = video_handle.await;
-    let audio_result = audio_handle.await;
+    let video_handle = {
+        let storage = Arc::clone(&storage);
+        let output_path = Arc::clone(&output_path);
+        let capture_paused = Arc::clone(&capture_paused);
+        let health = Arc::clone(&health);
+        let shutdown = shutdown.clone();
+        let friend_wearable_uid = friend_wearable_uid.clone();
+        let stall_timeout =
+            stall_timeout.and_then(|timeout| chrono::Duration::from_std(timeout).ok());
+        tokio::spawn(async move {
+            // Frames aren't expected while capture is paused
+            let mut expected_since = Utc::now();
+            supervise(
+                Subsystem::Vision,
+                &health,
+                &shutdown,
+                || {
+                    record_video(
+                        Arc::clone(&storage),
+                        Arc::clone(&output_path),
+                        settings.clone(),
+                        Arc::clone(&vision_control),
+                        Arc::clone(&capture_paused),
+                        Arc::clone(&capture_trigger),
+                        capture_requests.clone(),
+                        save_text_files,
+                        Arc::clone(&ocr_engine),
+                        ocr_max_dimension,
+                        ocr_languages.clone(),
+                        dedup_threshold,
+                        differential_ocr,
+                        sensitive_content.clone(),
+                        screen_mask.clone(),
+                        monitors.clone(),
+                        idle_fps,
+                        video_encoding.clone(),
+                        ocr_pool_size,
+                        synthetic_frames_dir.clone(),
+                        Arc::clone(&vision_status),
+                        Arc::clone(&health),
+                        shutdown.clone(),
+                        friend_wearable_uid.clone(),
+                    )
+                },
+                |started| {
+                    let Some(stall_timeout) = stall_timeout else {
+                        return false;
+                    };
+                    let now = Utc::now();
+                    if capture_paused.load(Ordering::SeqCst) {
+                        expected_since = now;
+                        return false;
+                    }
+                    let last_progress = vision_status
+                        .last_frame_at()
+                        .map_or(started, |at| at.max(started))
+                        .max(expected_since);
+                    now.signed_duration_since(last_progress) > stall_timeout
+                },
+                || vision_control.load(Ordering::SeqCst),
+            )
+            .await
+        })
+    };
 
-    if let Err(e) = video_result {
+    #[cfg(feature = "audio")]
+    let backfill = TranscriptionBackfill::new(Arc::clone(&db));
+    // Stopped on shutdown so the whisper queue can drain
+    #[cfg(feature = "audio")]
+    tokio::spawn(backfill.clone().run(
+        whisper_sender.clone(),
+        audio_device_configs.clone(),
+        shutdown.clone(),
+    ));
+    #[cfg(feature = "audio")]
+    let audio_tasks = Arc::new(Mutex::new(AudioTasks {
+        whisper_receiver,
+        devices: HashMap::new(),
+        followed: HashMap::new(),
+        backfill,
+        restarts: HashMap::new(),
+    }));
+    #[cfg(feature = "audio")]
+    let audio_handle = {
+        let storage = Arc::clone(&storage);
+        let health = Arc::clone(&health);
+        let shutdown = shutdown.clone();
+        let whisper_sender = whisper_sender.clone();
+        let audio_tasks = Arc::clone(&audio_tasks);
+        let friend_wearable_uid = friend_wearable_uid.clone();
+        tokio::spawn(async move {
+            supervise(
+                Subsystem::Audio,
+                &health,
+                &shutdown,
+                || {
+                    record_audio(
+                        Arc::clone(&storage),
+                        Arc::clone(&output_path),
+                        audio_chunk_duration,
+                        whisper_sender.clone(),
+                        Arc::clone(&audio_tasks),
+                        Arc::clone(&audio_devices_control),
+                        follow_default_audio,
+                        audio_device_configs.clone(),
+                        audio_encoding,
+                        Arc::clone(&capture_paused),
+                        Arc::clone(&health),
+                        shutdown.clone(),
+                        friend_wearable_uid.clone(),
+                    )
+                },
+                // Devices are restarted one by one by `record_audio`, a dead one shows as
+                // stale in /health while the others keep recording
+                |_| false,
+                || true,
+            )
+            .await
+        })
+    };
+
+    if let Err(e) = video_handle.await {
         error!("Video recording error: {:?}", e);
+        health.record_error(Subsystem::Vision, format!("Video recording error: {}", e));
     }
-    if let Err(e) = audio_result {
-        error!("Audio recording error: {:?}", e);
+    #[cfg(feature = "audio")]
+    {
+        if let Err(e) = audio_handle.await {
+            error!("Audio recording error: {:?}", e);
+            health.record_error(Subsystem::Audio, format!("Audio recording error: {}", e));
+        }
+        // The whisper channel closes once the devices stopped and this sender is dropped
+        drop(whisper_sender);
+        finish_audio(
+            &*storage,
+            &audio_tasks,
+            &health,
+            friend_wearable_uid.as_deref(),
+        )
+        .await;
     }
 
     info!("Stopped recording");
+    // Written before returning, the runtime may shut down right after
+    if let Err(e) = db
+        .insert_event(EventKind::RecordingStopped, "Recording stopped", None)
+        .await
+    {
+        error!("Failed to record the recording stop: {}", e);
+    }
     Ok(())
 }
 
+/// Records the start of the recording, and the OCR engine switch when the previous
+/// recording used another one
+#[cfg(feature = "recording")]
+async fn record_recording_started(db: &DatabaseManager, ocr_engine: &OcrEngine) {
+    let ocr_engine = format!("{:?}", ocr_engine);
+    let previous = match db
+        .get_events(&[EventKind::RecordingStarted], None, None, 1, 0)
+        .await
+    {
+        Ok(events) => events.into_iter().next(),
+        Err(e) => {
+            error!("Failed to read the last recording_started event: {}", e);
+            None
+        }
+    };
+    let previous_engine = previous
+        .and_then(|event| event.data)
+        .and_then(|data| data["ocr_engine"].as_str().map(str::to_string));
+
+    let data = serde_json::json!({"ocr_engine": ocr_engine});
+    if let Some(previous_engine) = previous_engine.filter(|engine| *engine != ocr_engine) {
+        let message = format!(
+            "OCR engine changed from {} to {}",
+            previous_engine, ocr_engine
+        );
+        let data = serde_json::json!({"from": previous_engine, "to": ocr_engine});
+        if let Err(e) = db
+            .insert_event(EventKind::OcrEngineChanged, &message, Some(&data))
+            .await
+        {
+            error!("Failed to record the OCR engine change: {}", e);
+        }
+    }
+    let message = format!("Recording started with {} OCR", ocr_engine);
+    if let Err(e) = db
+        .insert_event(EventKind::RecordingStarted, &message, Some(&data))
+        .await
+    {
+        error!("Failed to record the recording start: {}", e);
+    }
+}
+
+#[cfg(feature = "recording")]
 async fn record_video(
-    db: Arc<DatabaseManager>,
+    storage: Arc<dyn Storage>,
     output_path: Arc<String>,
-    fps: f64,
+    settings: watch::Receiver<LiveSettings>,
     is_running: Arc<AtomicBool>,
+    capture_paused: Arc<AtomicBool>,
+    capture_trigger: Arc<CaptureTrigger>,
+    capture_requests: CaptureRequests,
     save_text_files: bool,
     ocr_engine: Arc<OcrEngine>,
+    ocr_max_dimension: Option<u32>,
+    ocr_languages: OcrLanguages,
+    dedup_threshold: Option<f64>,
+    differential_ocr: bool,
+    sensitive_content: Option<SensitiveContentFilter>,
+    screen_mask: Option<ScreenMask>,
+    monitors: Vec<MonitorConfig>,
+    idle_fps: Option<f64>,
+    video_encoding: VideoEncoding,
+    ocr_pool_size: usize,
+    synthetic_frames_dir: Option<PathBuf>,
+    vision_status: Arc<VisionStatus>,
+    health: Arc<HealthMonitor>,
+    shutdown: Shutdown,
     _friend_wearable_uid: Option<String>, // Add underscore
 ) -> Result<()> {
     debug!("record_video: Starting");
-    let db_chunk_callback = Arc::clone(&db);
+    let storage_chunk_callback = Arc::clone(&storage);
+    let health_chunk_callback = Arc::clone(&health);
     let rt = tokio::runtime::Handle::current();
+    let codec = video_encoding.codec_name();
     let new_chunk_callback = move |file_path: &str| {
-        let db_chunk_callback = Arc::clone(&db_chunk_callback);
+        let storage_chunk_callback = Arc::clone(&storage_chunk_callback);
+        let health_chunk_callback = Arc::clone(&health_chunk_callback);
         let file_path = file_path.to_string();
         rt.spawn(async move {
-            if let Err(e) = db_chunk_callback.insert_video_chunk(&file_path).await {
+            if let Err(e) = storage_chunk_callback
+                .insert_video_chunk(&file_path, codec)
+                .await
+            {
                 error!("Failed to insert new video chunk: {}", e);
+                health_chunk_callback.record_error(
+                    Subsystem::Database,
+                    format!("Failed to insert new video chunk: {}", e),
+                );
             }
             debug!("record_video: Inserted new video chunk: {}", file_path);
         });
@@ -141,85 +585,267 @@ async fn record_video(
 
     let video_capture = VideoCapture::new(
         &output_path,
-        fps,
+        settings.clone(),
         new_chunk_callback,
         save_text_files,
         Arc::clone(&ocr_engine),
+        ocr_max_dimension,
+        ocr_languages,
+        dedup_threshold,
+        differential_ocr,
+        sensitive_content,
+        screen_mask,
+        monitors,
+        idle_fps,
+        video_encoding,
+        capture_paused,
+        capture_trigger,
+        ocr_pool_size,
+        synthetic_frames_dir,
+        vision_status,
+        shutdown.clone(),
     );
 
-    while is_running.load(Ordering::SeqCst) {
-        if let Some(frame) = video_capture.ocr_frame_queue.lock().await.pop_front() {
-            match db.insert_frame(&frame.app_name).await {
-                Ok(frame_id) => {
-                    let text_json = serde_json::to_string(&frame.text_json).unwrap_or_default();
-                    let new_text_json_vs_previous_frame =
-                        serde_json::to_string(&frame.new_text_json).unwrap_or_default();
-                    let raw_data_output_from_ocr = DataOutputWrapper {
-                        data_output: frame.data_output,
-                    }
-                    .to_json();
-
-                    if let Err(e) = db
-                        .insert_ocr_text(
-                            frame_id,
-                            &frame.text,
-                            &text_json,
-                            &new_text_json_vs_previous_frame,
-                            &raw_data_output_from_ocr,
-                            &frame.app_name,
-                            Arc::clone(&ocr_engine),
-                        )
-                        .await
-                    {
-                        error!(
-                            "Failed to insert OCR text: {}, skipping frame {}",
-                            e, frame_id
-                        );
-                        continue;
-                    }
-                }
-                Err(e) => {
-                    warn!("Failed to insert frame: {}", e);
-                    tokio::time::sleep(Duration::from_millis(100)).await;
-                    continue;
-                }
-            }
+    while is_running.load(Ordering::SeqCst) && !shutdown.is_requested() {
+        let frame = {
+            let mut ocr_frame_queue = video_capture.ocr_frame_queue.lock().await;
+            let frame = ocr_frame_queue.pop_front();
+            health.set_ocr_queue_depth(ocr_frame_queue.len());
+            frame
+        };
+        if let Some(frame) = frame {
+            write_ocr_frame(&*storage, frame, &ocr_engine, &capture_requests, &health).await;
+        }
+        let fps = settings.borrow().fps;
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs_f64(1.0 / fps)) => {}
+            _ = shutdown.requested() => {}
         }
-        tokio::time::sleep(Duration::from_secs_f64(1.0 / fps)).await;
     }
 
+    if shutdown.is_requested() {
+        let ocr_frame_queue = Arc::clone(&video_capture.ocr_frame_queue);
+        video_capture.wait_stopped().await;
+        let frames: Vec<_> = ocr_frame_queue.lock().await.drain(..).collect();
+        info!(
+            "Writing {} frames captured before the shutdown",
+            frames.len()
+        );
+        for frame in frames {
+            write_ocr_frame(&*storage, frame, &ocr_engine, &capture_requests, &health).await;
+        }
+    }
+
+    storage.flush_writes().await;
     Ok(())
 }
 
+/// Frames requested through the `CaptureTrigger` are written right away, to answer the
+/// `capture_requests` waiting for them
+#[cfg(feature = "recording")]
+async fn write_ocr_frame(
+    storage: &dyn Storage,
+    frame: CaptureResult,
+    ocr_engine: &OcrEngine,
+    capture_requests: &CaptureRequests,
+    health: &HealthMonitor,
+) {
+    let (forced, captured_at) = (frame.forced, frame.timestamp);
+    let text_json = serde_json::to_string(&frame.text_json).unwrap_or_default();
+    let new_text_json_vs_previous_frame =
+        serde_json::to_string(&frame.new_text_json).unwrap_or_default();
+    let words = ocr_words(&frame.data_output);
+    let raw_data_output_from_ocr = DataOutputWrapper {
+        data_output: frame.data_output,
+    }
+    .to_json();
+
+    let record = FrameRecord {
+        timestamp: Utc::now(),
+        app_name: frame.app_name,
+        text: frame.text,
+        raw_text: None,
+        text_json,
+        new_text_json_vs_previous_frame,
+        raw_data_output_from_ocr,
+        ocr_engine: format!("{:?}", ocr_engine),
+        words,
+        entities: Vec::new(),
+        windows: frame.windows,
+        region: Some(frame.region),
+    };
+    if !forced {
+        if let Err(e) = storage.write_frame(record).await {
+            error!("Failed to write frame: {}", e);
+            health.record_error(Subsystem::Database, format!("Failed to write frame: {}", e));
+        }
+        return;
+    }
+    let (timestamp, app_name, text) = (
+        record.timestamp,
+        record.app_name.clone(),
+        record.text.clone(),
+    );
+    match storage.write_frame_now(record).await {
+        Ok(Some(frame_id)) => capture_requests.complete(
+            captured_at,
+            &CapturedFrame {
+                frame_id,
+                timestamp,
+                app_name,
+                text,
+            },
+        ),
+        Ok(None) => debug!("Requested frame dropped, no video chunk yet"),
+        Err(e) => {
+            error!("Failed to write requested frame: {}", e);
+            health.record_error(
+                Subsystem::Database,
+                format!("Failed to write requested frame: {}", e),
+            );
+        }
+    }
+}
+
+/// Audio recording kept across restarts of `record_audio`
+#[cfg(feature = "audio")]
+struct AudioTasks {
+    whisper_receiver: UnboundedReceiver<TranscriptionResult>,
+    /// Capture of each running device, they keep recording while `record_audio` restarts
+    devices: HashMap<String, DeviceTask>,
+    /// Default device of each type recorded with `follow_default_audio`
+    followed: HashMap<DeviceType, AudioDevice>,
+    /// Receives the transcriptions of the chunks it sent
+    backfill: TranscriptionBackfill,
+    /// Devices whose capture ended on its own, e.g. panicked or lost its device, by id
+    restarts: HashMap<String, DeviceRestart>,
+}
+
+#[cfg(feature = "audio")]
+struct DeviceTask {
+    handle: JoinHandle<()>,
+    /// Ends the chunk being recorded early and stops the device
+    stop: Shutdown,
+    /// Ends the chunk being recorded early, nothing is recorded until it's unset
+    paused: watch::Sender<bool>,
+    /// Recorded device, restarted if the task ends on its own
+    device: AudioDevice,
+    started_at: Instant,
+    /// Wait before the restart that started it, None if it wasn't restarted
+    backoff: Option<Duration>,
+}
+
+#[cfg(feature = "audio")]
+struct DeviceRestart {
+    device: AudioDevice,
+    paused: bool,
+    /// When it's queued to start again, None once it is
+    due: Option<Instant>,
+    backoff: Duration,
+}
+
+/// How often the default devices are checked with `follow_default_audio`
+#[cfg(feature = "audio")]
+const DEFAULT_DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+#[cfg(feature = "audio")]
 async fn record_audio(
-    db: Arc<DatabaseManager>,
+    storage: Arc<dyn Storage>,
     output_path: Arc<String>,
     chunk_duration: Duration,
     whisper_sender: UnboundedSender<AudioInput>,
-    mut whisper_receiver: UnboundedReceiver<TranscriptionResult>,
+    tasks: Arc<Mutex<AudioTasks>>,
     audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
+    follow_default_audio: bool,
+    audio_device_configs: watch::Receiver<HashMap<String, AudioDeviceConfig>>,
+    audio_encoding: AudioEncoding,
+    capture_paused: Arc<AtomicBool>,
+    health: Arc<HealthMonitor>,
+    shutdown: Shutdown,
     friend_wearable_uid: Option<String>,
-    cloud_audio: bool,
 ) -> Result<()> {
-    let mut handles: HashMap<String, JoinHandle<()>> = HashMap::new();
+    let mut tasks = tasks.lock().await;
+    let AudioTasks {
+        whisper_receiver,
+        devices,
+        followed,
+        backfill,
+        restarts,
+    } = &mut *tasks;
+    let mut default_checked_at: Option<Instant> = None;
+
+    while !shutdown.is_requested() {
+        if follow_default_audio
+            && default_checked_at.map_or(true, |at| at.elapsed() >= DEFAULT_DEVICE_CHECK_INTERVAL)
+        {
+            default_checked_at = Some(Instant::now());
+            follow_default_devices(followed, devices, &audio_devices_control, &health).await;
+        }
+
+        let now = Instant::now();
+        for (device_id, restart) in restarts.iter_mut() {
+            if restart.due.map_or(false, |due| due <= now) {
+                info!("Restarting the recording of {}", device_id);
+                restart.due = None;
+                audio_devices_control.push((
+                    restart.device.clone(),
+                    DeviceControl {
+                        is_running: true,
+                        is_paused: restart.paused,
+                    },
+                ));
+            }
+        }
 
-    loop {
         while let Some((audio_device, device_control)) = audio_devices_control.pop() {
             debug!("Received audio device: {}", &audio_device);
             let device_id = audio_device.to_string();
 
             if !device_control.is_running {
                 info!("Device control signaled stop for device {}", &audio_device);
-                if let Some(handle) = handles.remove(&device_id) {
-                    handle.abort();
+                restarts.remove(&device_id);
+                if let Some(task) = devices.remove(&device_id) {
+                    task.handle.abort();
+                    task.stop.request();
                     info!("Stopped thread for device {}", &audio_device);
+                    health.events().record(
+                        EventKind::DeviceStopped,
+                        format!("Stopped recording {}", device_id),
+                        Some(serde_json::json!({"device": device_id})),
+                    );
                 }
+                health.remove_audio_device(&device_id);
                 continue;
             }
+            if let Some(task) = devices
+                .get(&device_id)
+                .filter(|task| !task.handle.is_finished())
+            {
+                let was_paused = task.paused.send_replace(device_control.is_paused);
+                if was_paused != device_control.is_paused {
+                    pause_audio_device(&health, &device_id, device_control.is_paused);
+                } else {
+                    debug!("Already recording {}", device_id);
+                }
+                continue;
+            }
+            let backoff = restarts.remove(&device_id).map(|restart| restart.backoff);
+            health.events().record(
+                EventKind::DeviceStarted,
+                format!("Started recording {}", device_id),
+                Some(serde_json::json!({"device": device_id})),
+            );
 
             let output_path_clone = Arc::clone(&output_path);
             let whisper_sender_clone = whisper_sender.clone();
+            let capture_paused = Arc::clone(&capture_paused);
+            let health = Arc::clone(&health);
+            let stop = shutdown.child();
+            let shutdown = stop.clone();
+            let (paused_sender, mut paused) = watch::channel(device_control.is_paused);
+            let audio_device_configs = audio_device_configs.clone();
 
+            let device = audio_device.clone();
             let audio_device = Arc::new(audio_device);
             let device_control = Arc::new(device_control);
 
@@ -232,7 +858,16 @@ async fn record_audio(
                 );
 
                 let mut iteration = 0;
-                loop {
+                while !shutdown.is_requested() {
+                    if capture_paused.load(Ordering::SeqCst) || *paused.borrow() {
+                        tokio::select! {
+                            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                            _ = paused.changed() => {}
+                            _ = shutdown.requested() => {}
+                        }
+                        continue;
+                    }
+
                     iteration += 1;
                     debug!(
                         "Starting iteration {} for device {}",
@@ -247,7 +882,12 @@ async fn record_audio(
 
                     let new_file_name = Utc::now().format("%Y-%m-%d_%H-%M-%S").to_string();
                     let file_path = PathBuf::from(&*output_path_clone)
-                        .join(format!("{}_{}.mp4", audio_device_clone, new_file_name))
+                        .join(format!(
+                            "{}_{}.{}",
+                            audio_device_clone,
+                            new_file_name,
+                            audio_encoding.format.name()
+                        ))
                         .to_str()
                         .expect("Failed to create valid path")
                         .to_string();
@@ -255,14 +895,35 @@ async fn record_audio(
                         "Starting record_and_transcribe for device {} (iteration {})",
                         audio_device_clone, iteration
                     );
-                    let result = record_and_transcribe(
+                    // Changed settings apply from the next chunk
+                    let device_config = audio_device_configs
+                        .borrow()
+                        .get(&audio_device_clone.to_string())
+                        .cloned()
+                        .unwrap_or_default();
+                    let is_recording = Arc::new(AtomicBool::new(device_control_clone.is_running));
+                    let recording = record_and_transcribe(
                         audio_device_clone,
-                        chunk_duration,
+                        device_config.chunk_duration(chunk_duration),
                         file_path.into(),
                         whisper_sender,
-                        Arc::new(AtomicBool::new(device_control_clone.is_running)),
-                    )
-                    .await;
+                        Arc::clone(&is_recording),
+                        device_config,
+                        audio_encoding,
+                    );
+                    tokio::pin!(recording);
+                    let result = tokio::select! {
+                        result = &mut recording => result,
+                        _ = shutdown.requested() => {
+                            // Ends the chunk early, it's still written and transcribed
+                            is_recording.store(false, Ordering::SeqCst);
+                            recording.await
+                        }
+                        _ = until_paused(&mut paused) => {
+                            is_recording.store(false, Ordering::SeqCst);
+                            recording.await
+                        }
+                    };
                     info!(
                         "Finished record_and_transcribe for device {} (iteration {})",
                         audio_device_clone_2, iteration
@@ -274,12 +935,29 @@ async fn record_audio(
                                 "Recording complete for device {} (iteration {}): {:?}",
                                 audio_device, iteration, file_path
                             );
+                            health.chunk_recorded(&audio_device.to_string());
+                            // Not stale while paused
+                            if *paused.borrow() {
+                                health.remove_audio_device(&audio_device.to_string());
+                            }
                         }
                         Err(e) => {
                             error!(
                                 "Error in record_and_transcribe for device {} (iteration {}): {}, stopping thread",
                                 audio_device, iteration, e
                             );
+                            health.record_error(
+                                Subsystem::Audio,
+                                format!("Recording of {} stopped: {}", audio_device, e),
+                            );
+                            health.events().record(
+                                EventKind::DeviceStopped,
+                                format!("Stopped recording {}: {}", audio_device, e),
+                                Some(serde_json::json!({
+                                    "device": audio_device.to_string(),
+                                    "error": e.to_string(),
+                                })),
+                            );
                             break;
                         }
                     }
@@ -291,70 +969,418 @@ async fn record_audio(
                 }
 
                 info!("Exiting audio capture thread for device: {}", &audio_device);
+                // Stopped rather than failed, it's not stale
+                if shutdown.is_requested() {
+                    health.remove_audio_device(&audio_device.to_string());
+                }
             });
 
-            handles.insert(device_id, handle);
+            devices.insert(
+                device_id,
+                DeviceTask {
+                    handle,
+                    stop,
+                    paused: paused_sender,
+                    device,
+                    started_at: Instant::now(),
+                    backoff,
+                },
+            );
         }
 
-        handles.retain(|device_id, handle| {
-            if handle.is_finished() {
-                info!("Handle for device {} has finished", device_id);
-                false
-            } else {
-                true
+        devices.retain(|device_id, task| {
+            if !task.handle.is_finished() {
+                return true;
             }
+            info!("Handle for device {} has finished", device_id);
+            // Failed or panicked rather than stopped
+            if !task.stop.is_requested() {
+                let backoff = restart_backoff(task.backoff, task.started_at.elapsed());
+                warn!(
+                    "Recording of {} ended, restarting it in {}s",
+                    device_id,
+                    backoff.as_secs()
+                );
+                health.task_restarted(
+                    Subsystem::Audio,
+                    format!("Recording of {} ended, restarted", device_id),
+                );
+                restarts.insert(
+                    device_id.clone(),
+                    DeviceRestart {
+                        device: task.device.clone(),
+                        paused: *task.paused.borrow(),
+                        due: Some(Instant::now() + backoff),
+                        backoff,
+                    },
+                );
+            }
+            false
         });
 
         while let Ok(transcription) = whisper_receiver.try_recv() {
             info!("Received transcription");
-            process_audio_result(&db, transcription, friend_wearable_uid.as_deref(), cloud_audio).await;
+            track_transcription(&health, &transcription);
+            if let Some(transcription) = backfill.complete(&*storage, transcription).await {
+                process_audio_result(&*storage, transcription, friend_wearable_uid.as_deref())
+                    .await;
+            }
         }
 
-        tokio::time::sleep(Duration::from_millis(100)).await;
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+            _ = shutdown.requested() => {}
+        }
     }
+
+    Ok(())
 }
 
-async fn process_audio_result(
-    db: &DatabaseManager,
+/// Resolves once the device is paused, right away if it is
+#[cfg(feature = "audio")]
+async fn until_paused(paused: &mut watch::Receiver<bool>) {
+    let gone = paused.wait_for(|paused| *paused).await.is_err();
+    // The device is stopped then
+    if gone {
+        std::future::pending::<()>().await;
+    }
+}
+
+#[cfg(feature = "audio")]
+fn pause_audio_device(health: &HealthMonitor, device_id: &str, is_paused: bool) {
+    let (kind, action) = if is_paused {
+        // Not stale while paused
+        health.remove_audio_device(device_id);
+        (EventKind::DevicePaused, "Paused")
+    } else {
+        (EventKind::DeviceResumed, "Resumed")
+    };
+    health.events().record(
+        kind,
+        format!("{} recording {}", action, device_id),
+        Some(serde_json::json!({"device": device_id})),
+    );
+}
+
+/// Records the default devices instead of the ones they replaced as default. The chunk of
+/// the replaced device is ended early, it's still written and transcribed.
+#[cfg(feature = "audio")]
+async fn follow_default_devices(
+    followed: &mut HashMap<DeviceType, AudioDevice>,
+    devices: &mut HashMap<String, DeviceTask>,
+    audio_devices_control: &SegQueue<(AudioDevice, DeviceControl)>,
+    health: &HealthMonitor,
+) {
+    // Audio output is only recorded on linux
+    let device_types = if cfg!(target_os = "linux") {
+        vec![DeviceType::Input, DeviceType::Output]
+    } else {
+        vec![DeviceType::Input]
+    };
+    for device_type in device_types {
+        // Enumerating devices blocks on some hosts
+        let default = match tokio::task::spawn_blocking({
+            let device_type = device_type.clone();
+            move || default_device(device_type)
+        })
+        .await
+        {
+            Ok(Ok(device)) => device,
+            Ok(Err(e)) => {
+                debug!("No default {:?} audio device to follow: {}", device_type, e);
+                continue;
+            }
+            Err(e) => {
+                error!(
+                    "Failed to get the default {:?} audio device: {}",
+                    device_type, e
+                );
+                continue;
+            }
+        };
+        if followed.get(&device_type) == Some(&default) {
+            continue;
+        }
+
+        if let Some(previous) = followed.insert(device_type, default.clone()) {
+            info!(
+                "Default audio device changed from {} to {}",
+                previous, default
+            );
+            let previous_id = previous.to_string();
+            if let Some(task) = devices.remove(&previous_id) {
+                task.stop.request();
+                health.events().record(
+                    EventKind::DeviceStopped,
+                    format!(
+                        "Stopped recording {}, {} is the default now",
+                        previous, default
+                    ),
+                    Some(serde_json::json!({
+                        "device": previous_id,
+                        "default": default.to_string(),
+                    })),
+                );
+            }
+        }
+        // Started unless it's recorded already
+        audio_devices_control.push((
+            default,
+            DeviceControl {
+                is_running: true,
+                is_paused: false,
+            },
+        ));
+    }
+}
+
+/// Writes, after a shutdown, the chunks the devices recorded until they stopped. Every
+/// sender of the whisper channel other than the devices' must be dropped.
+#[cfg(feature = "audio")]
+async fn finish_audio(
+    storage: &dyn Storage,
+    tasks: &Mutex<AudioTasks>,
+    health: &HealthMonitor,
+    friend_wearable_uid: Option<&str>,
+) {
+    let mut tasks = tasks.lock().await;
+    // The devices send their last chunk to whisper before they stop
+    for (device_id, task) in tasks.devices.drain() {
+        if let Err(e) = task.handle.await {
+            error!("Audio capture of {} failed: {}", device_id, e);
+        }
+    }
+    // The channel closes once the chunks queued are all transcribed
+    info!("Transcribing the audio recorded before the shutdown");
+    while let Some(transcription) = tasks.whisper_receiver.recv().await {
+        track_transcription(health, &transcription);
+        if let Some(transcription) = tasks.backfill.complete(storage, transcription).await {
+            process_audio_result(storage, transcription, friend_wearable_uid).await;
+        }
+    }
+    storage.flush_writes().await;
+}
+
+#[cfg(feature = "audio")]
+fn track_transcription(health: &HealthMonitor, result: &TranscriptionResult) {
+    health.chunk_transcribed();
+    if let Some(e) = &result.error {
+        health.record_error(
+            Subsystem::Transcription,
+            format!("Failed to transcribe {}: {}", result.input.device, e),
+        );
+    }
+}
+
+#[cfg(feature = "audio")]
+pub(crate) async fn process_audio_result(
+    storage: &dyn Storage,
     result: TranscriptionResult,
     _friend_wearable_uid: Option<&str>, // Add underscore
-    cloud_audio: bool,
 ) {
-    if result.error.is_some() || result.transcription.is_none() {
+    // Stored without transcription, it's transcribed again in the background
+    let transcription_error = match (&result.error, &result.transcription) {
+        (None, Some(_)) => None,
+        (error, _) => Some(
+            error
+                .clone()
+                .unwrap_or_else(|| "no transcription".to_string()),
+        ),
+    };
+    if let Some(e) = &transcription_error {
         error!(
-            "Error in audio recording: {}. Not inserting audio result",
-            result.error.unwrap_or_default()
+            "Error in audio recording: {}. Inserting the chunk to transcribe it again",
+            e
         );
-        return;
     }
-    let transcription = result.transcription.unwrap();
-    let transcription_engine = if cloud_audio { "Deepgram" } else { "Whisper" };
+    let transcription = result.transcription.unwrap_or_default();
+    let transcription_engine = result.engine;
 
     info!("Inserting audio chunk: {:?}", result.input.path);
-    match db.insert_audio_chunk(&result.input.path).await {
-        Ok(audio_chunk_id) => {
-            if transcription.is_empty() {
-                return;
+    let record = AudioRecord {
+        timestamp: Utc::now(),
+        file_path: result.input.path.clone(),
+        duration: Some(result.duration),
+        transcription,
+        transcription_engine: transcription_engine.clone(),
+        language: result.language,
+        segments: result.segments,
+        entities: Vec::new(),
+        format: AudioFormat::from_path(&result.input.path).map(|format| format.name().to_string()),
+        transcription_error,
+    };
+    match storage.write_audio(record).await {
+        Ok(()) => debug!(
+            "Wrote audio chunk from device {} using {}",
+            result.input.device, transcription_engine
+        ),
+        Err(e) => error!(
+            "Failed to insert audio chunk for device {}: {}",
+            result.input.device, e
+        ),
+    }
+}
+/// Periodically evicts the oldest video/audio chunks once the data directory
+/// grows past the quota of `settings`. Does nothing while there is no quota,
+/// a new quota applies right away.
+pub async fn enforce_storage_quota(
+    db: Arc<DatabaseManager>,
+    data_dir: Arc<String>,
+    mut settings: watch::Receiver<LiveSettings>,
+    check_interval: Duration,
+) {
+    let mut current_quota = None;
+    loop {
+        let max_disk_usage_gb = settings.borrow_and_update().max_disk_usage_gb;
+        if max_disk_usage_gb != current_quota {
+            match max_disk_usage_gb {
+                Some(gb) => info!("Storage quota enabled: {:.1} GB", gb),
+                None => info!("Storage quota disabled"),
             }
+            current_quota = max_disk_usage_gb;
+        }
+        if let Some(gb) = max_disk_usage_gb {
+            let max_disk_usage = (gb * 1_000_000_000.0) as u64;
+            if let Err(e) = evict_oldest_chunks(&db, &data_dir, max_disk_usage).await {
+                error!("Failed to enforce storage quota: {}", e);
+            }
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(check_interval) => {}
+            Ok(()) = settings.changed() => {}
+        }
+    }
+}
 
-            if let Err(e) = db
-                .insert_audio_transcription(audio_chunk_id, &transcription, 0, transcription_engine)
-                .await
-            {
-                error!(
-                    "Failed to insert audio transcription for device {}: {}",
-                    result.input.device, e
-                );
-            } else {
-                debug!(
-                    "Inserted audio transcription for chunk {} from device {} using {}",
-                    audio_chunk_id, result.input.device, transcription_engine
+/// Deletes the oldest chunks, files and DB rows, until the data directory is under
+/// `max_disk_usage` bytes. Returns the number of bytes freed. A chunk whose file can't be
+/// deleted is kept and skipped, the next check tries it again.
+pub async fn evict_oldest_chunks(
+    db: &DatabaseManager,
+    data_dir: &str,
+    max_disk_usage: u64,
+) -> Result<u64> {
+    let mut usage = {
+        let data_dir = PathBuf::from(data_dir);
+        tokio::task::spawn_blocking(move || dir_size(&data_dir)).await??
+    };
+    if usage <= max_disk_usage {
+        return Ok(0);
+    }
+    warn!(
+        "Disk usage {} bytes exceeds quota of {} bytes, evicting oldest chunks",
+        usage, max_disk_usage
+    );
+
+    let mut freed = 0;
+    let (mut video_chunks, mut audio_chunks) = (0, 0);
+    // Past the chunks whose file couldn't be deleted
+    let (mut video_after, mut audio_after) = (0, 0);
+    let mut failed = 0;
+    while usage > max_disk_usage {
+        let oldest_video = db.get_oldest_video_chunk(video_after).await?;
+        let oldest_audio = db.get_oldest_audio_chunk(audio_after).await?;
+
+        let evict_video = match (&oldest_video, &oldest_audio) {
+            (Some((_, _, video_time)), Some((_, _, audio_time))) => match (video_time, audio_time) {
+                (Some(video_time), Some(audio_time)) => video_time <= audio_time,
+                // Chunks without timestamp predate timestamps, evict them first
+                (None, _) => true,
+                (_, None) => false,
+            },
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => {
+                warn!(
+                    "No chunk left to evict, disk usage is still {} bytes",
+                    usage
                 );
+                break;
+            }
+        };
+
+        let (id, file_path, _) = if evict_video {
+            oldest_video.unwrap()
+        } else {
+            oldest_audio.unwrap()
+        };
+        // The file goes first, its chunk is kept if it can't be deleted
+        let size = match remove_chunk_file(&file_path, false).await {
+            Ok(size) => size,
+            Err(e) => {
+                error!("Failed to delete {}: {}", file_path, e);
+                if evict_video {
+                    video_after = id;
+                } else {
+                    audio_after = id;
+                }
+                failed += 1;
+                continue;
             }
+        };
+        if evict_video {
+            db.delete_video_chunk(id).await?;
+        } else {
+            db.delete_audio_chunk(id).await?;
         }
-        Err(e) => error!(
-            "Failed to insert audio chunk for device {}: {}",
-            result.input.device, e
-        ),
+        info!("Evicted {} ({} bytes)", file_path, size);
+        usage = usage.saturating_sub(size);
+        freed += size;
+        if evict_video {
+            video_chunks += 1;
+        } else {
+            audio_chunks += 1;
+        }
+    }
+
+    if failed > 0 {
+        warn!(
+            "{} chunks are kept as their file couldn't be deleted",
+            failed
+        );
     }
-}
\ No newline at end of file
+    if video_chunks + audio_chunks > 0 {
+        let message = format!(
+            "Storage quota evicted {} video and {} audio chunks, {} bytes",
+            video_chunks, audio_chunks, freed
+        );
+        let data = serde_json::json!({
+            "reason": "quota",
+            "video_chunks": video_chunks,
+            "audio_chunks": audio_chunks,
+            "freed_bytes": freed,
+        });
+        db.insert_event(EventKind::ChunksPurged, &message, Some(&data))
+            .await?;
+    }
+    Ok(freed)
+}
+
+/// Bytes of the files under `path`. Entries removed during the walk, e.g. a chunk renamed
+/// once finalized or evicted, are skipped.
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let Some(entry) = unless_gone(entry)? else {
+            continue;
+        };
+        let Some(metadata) = unless_gone(entry.metadata())? else {
+            continue;
+        };
+        if metadata.is_dir() {
+            size += unless_gone(dir_size(&entry.path()))?.unwrap_or(0);
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// `None` when the file was removed
+fn unless_gone<T>(result: std::io::Result<T>) -> std::io::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
@@ -3,15 +3,58 @@ use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use sqlx::migrate::MigrateDatabase;
 use sqlx::{
-    sqlite::{SqlitePool, SqlitePoolOptions},
+    sqlite::{
+        SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous,
+    },
     FromRow,
 };
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::str::FromStr;
 use std::time::Duration;
 use tokio::time::{timeout, Duration as TokioDuration};
 use crate::chunking::text_chunking_local;
-use screenpipe_vision::OcrEngine;
+use crate::confidence::{mean_confidence, OcrWord};
+use crate::entities::{normalize_entity, Entity, EntityKind, EntitySummary};
+use crate::storage::FrameRecord;
+use crate::sessions::{audio_device_of, segment_sessions, TranscribedChunk, TranscriptSession};
+#[cfg(feature = "encryption")]
+use crate::encryption::EncryptionKey;
+use crate::db_writer::{
+    insert_entities, insert_windows, words_json, BatchWriter, EntitySource, PendingFrame,
+    PendingTranscription, PendingWrite, WRITE_BATCH_INTERVAL, WRITE_BATCH_ROWS,
+};
+use crate::anomaly::IngestCounts;
+use crate::events::{Event, EventKind};
+use crate::health::WriteStats;
+use crate::collections::{Collection, CollectionFilters, CollectionItem, NewCollection};
+use crate::rules::{NewRule, Rule};
+use crate::search_query::{
+    edit_distance, expand_search_query, fuzzy_words, highlight_offsets, max_typos,
+    parse_search_query, Highlight, SearchQueryError, HIGHLIGHT_CLOSE, HIGHLIGHT_END,
+    HIGHLIGHT_OPEN, HIGHLIGHT_START, SNIPPET_ELLIPSIS, SNIPPET_TOKENS,
+};
+use std::collections::HashMap;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use screenpipe_core::TranscriptionSegment;
+use screenpipe_vision::{CaptureRegion, OcrEngine, WindowRegion};
+use std::path::Path;
 use std::sync::Arc;
+#[cfg(feature = "integrations")]
+use screenpipe_integrations::activitywatch::{ActivityWatchDatabase, ActivityWatchEvent};
+#[cfg(feature = "integrations")]
+use screenpipe_integrations::chat_digest::{DigestDatabase, KeywordMention};
+#[cfg(feature = "integrations")]
 use screenpipe_integrations::friend_wearable::FriendWearableDatabase;
+#[cfg(feature = "integrations")]
+use screenpipe_integrations::import::{
+    ImportDatabase, ImportSource, ImportSummary, ImportedFrame, ImportedTranscription,
+};
+#[cfg(feature = "integrations")]
+use screenpipe_integrations::obsidian::DailyNoteDatabase;
+#[cfg(feature = "integrations")]
+use crate::processor::ContentProcessors;
+#[cfg(feature = "integrations")]
 use async_trait::async_trait;
 use std::error::Error as StdError;
 use std::fmt;
@@ -31,6 +74,8 @@ impl StdError for DatabaseError {}
 pub enum SearchResult {
     OCR(OCRResult),
     Audio(AudioResult),
+    Clipboard(ClipboardResult),
+    Notification(NotificationResult),
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -45,15 +90,84 @@ pub struct OCRResult {
     pub offset_index: i64,
     pub app_name: String,
     pub ocr_engine: String,  // Add this line
+    /// Mean confidence of the words, 0 - 100, `None` when the engine reports none
+    pub confidence: Option<f64>,
+    #[serde(skip)]
+    pub(crate) words_json: Option<String>,
+    /// Part of the screen the frame shows, the OCR coordinates are relative to it. `None`
+    /// for frames recorded before it was stored.
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<CaptureRegion>,
+    #[serde(skip)]
+    pub(crate) region_json: Option<String>,
+    /// Context around the matched terms, wrapped in `HIGHLIGHT_START` / `HIGHLIGHT_END`.
+    /// Empty without query.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub snippet: String,
+    /// 0 - 1, how well it matches the query compared to the best match of its content type,
+    /// see `DatabaseManager::search_by_relevance`. `None` outside of searches.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relevance: Option<f64>,
+    /// Character offsets of the matched terms in `ocr_text`
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<Highlight>,
+    #[serde(skip)]
+    pub(crate) highlighted_text: String,
+    /// Raw `frames.timestamp` and id of the OCR row, for the cursor of the next page. A
+    /// frame may have several rows.
+    #[serde(skip)]
+    pub(crate) sort_timestamp: String,
+    #[serde(skip)]
+    pub(crate) ocr_id: i64,
+}
+
+impl OCRResult {
+    /// Words of the frame with their bounding box, empty when the engine reports none
+    pub fn words(&self) -> Vec<OcrWord> {
+        self.words_json
+            .as_deref()
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default()
+    }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Default, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum ContentType {
     #[default]
     All,
     OCR,
     Audio,
+    Clipboard,
+    Notification,
+}
+
+impl ContentType {
+    /// Name in the API and the database, e.g. `ocr`
+    pub fn name(self) -> &'static str {
+        match self {
+            ContentType::All => "all",
+            ContentType::OCR => "ocr",
+            ContentType::Audio => "audio",
+            ContentType::Clipboard => "clipboard",
+            ContentType::Notification => "notification",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ContentType> {
+        [
+            ContentType::All,
+            ContentType::OCR,
+            ContentType::Audio,
+            ContentType::Clipboard,
+            ContentType::Notification,
+        ]
+        .into_iter()
+        .find(|content_type| content_type.name() == name)
+    }
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -64,17 +178,617 @@ pub struct AudioResult {
     pub file_path: String,
     pub offset_index: i64,
     pub transcription_engine: String, // Add this line
+    /// Code of the language spoken, None for transcriptions made before it was stored
+    pub language: Option<String>,
+    /// Context around the matched terms, wrapped in `HIGHLIGHT_START` / `HIGHLIGHT_END`.
+    /// Empty without query.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub snippet: String,
+    /// 0 - 1, how well it matches the query compared to the best match of its content type,
+    /// see `DatabaseManager::search_by_relevance`. `None` outside of searches.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relevance: Option<f64>,
+    /// Character offsets of the matched terms in `transcription`
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<Highlight>,
+    #[serde(skip)]
+    pub(crate) highlighted_text: String,
+    /// Timing of the transcription in the chunk, to start playback at a word. Empty for
+    /// transcriptions made before it was stored.
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<TranscriptionSegment>,
+    #[serde(skip)]
+    pub(crate) segments_json: String,
+    /// Raw `audio_transcriptions.timestamp` and id, for the cursor of the next page
+    #[serde(skip)]
+    pub(crate) sort_timestamp: String,
+    #[serde(skip)]
+    pub(crate) transcription_id: i64,
+}
+
+/// Text copied to the clipboard, see `ClipboardMonitor`
+#[derive(Debug, Serialize, FromRow)]
+pub struct ClipboardResult {
+    pub id: i64,
+    pub text: String,
+    /// App in front when the text was copied, empty when it isn't known
+    pub app_name: String,
+    pub timestamp: DateTime<Utc>,
+    /// Context around the matched terms, wrapped in `HIGHLIGHT_START` / `HIGHLIGHT_END`.
+    /// Empty without query.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub snippet: String,
+    /// 0 - 1, how well it matches the query compared to the best match of its content type,
+    /// see `DatabaseManager::search_by_relevance`. `None` outside of searches.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relevance: Option<f64>,
+    /// Character offsets of the matched terms in `text`
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<Highlight>,
+    #[serde(skip)]
+    pub(crate) highlighted_text: String,
+    /// Raw `clipboard.timestamp`, for the cursor of the next page
+    #[serde(skip)]
+    pub(crate) sort_timestamp: String,
+}
+
+/// Notification shown by the OS, see `NotificationMonitor`
+#[derive(Debug, Serialize, FromRow)]
+pub struct NotificationResult {
+    pub id: i64,
+    /// App that sent the notification
+    pub app_name: String,
+    pub title: String,
+    pub body: String,
+    pub timestamp: DateTime<Utc>,
+    /// Context around the matched terms of the title or body, wrapped in `HIGHLIGHT_START` /
+    /// `HIGHLIGHT_END`. Empty without query.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub snippet: String,
+    /// 0 - 1, how well it matches the query compared to the best match of its content type,
+    /// see `DatabaseManager::search_by_relevance`. `None` outside of searches.
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relevance: Option<f64>,
+    /// Character offsets of the matched terms in `body`
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<Highlight>,
+    #[serde(skip)]
+    pub(crate) highlighted_text: String,
+    /// Raw `notifications.timestamp`, for the cursor of the next page
+    #[serde(skip)]
+    pub(crate) sort_timestamp: String,
+}
+
+/// Results of a search counted by value, see `DatabaseManager::search_facets`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchFacets {
+    /// Most results first, transcriptions have no app
+    pub app_name: Vec<FacetCount>,
+    /// UTC day, like `2024-09-14`, oldest first
+    pub day: Vec<FacetCount>,
+    /// `ocr`, `audio`, `clipboard` or `notification`, most results first
+    pub content_type: Vec<FacetCount>,
+    /// Device id, `local` for this machine, most results first
+    pub device: Vec<FacetCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+/// Counts of `SearchFacets` by value while they're added up
+#[derive(Default)]
+pub(crate) struct FacetTally {
+    app_name: HashMap<String, i64>,
+    day: HashMap<String, i64>,
+    content_type: HashMap<String, i64>,
+    device: HashMap<String, i64>,
+}
+
+impl FacetTally {
+    /// Counts `count` results of `content_type`, apps left empty aren't counted
+    pub fn add(
+        &mut self,
+        content_type: ContentType,
+        app_name: Option<&str>,
+        day: &str,
+        device: &str,
+        count: i64,
+    ) {
+        if let Some(app_name) = app_name.filter(|app_name| !app_name.is_empty()) {
+            *self.app_name.entry(app_name.to_string()).or_default() += count;
+        }
+        *self.day.entry(day.to_string()).or_default() += count;
+        *self
+            .content_type
+            .entry(content_type.name().to_string())
+            .or_default() += count;
+        *self.device.entry(device.to_string()).or_default() += count;
+    }
+
+    /// Adds the counts of `facets`, of other content types
+    pub fn merge(&mut self, facets: SearchFacets) {
+        for (counts, facet) in [
+            (&mut self.app_name, facets.app_name),
+            (&mut self.day, facets.day),
+            (&mut self.content_type, facets.content_type),
+            (&mut self.device, facets.device),
+        ] {
+            for FacetCount { value, count } in facet {
+                *counts.entry(value).or_default() += count;
+            }
+        }
+    }
+
+    pub fn into_facets(self) -> SearchFacets {
+        // Most results first, ties by value
+        let by_count = |counts: HashMap<String, i64>| {
+            let mut counts: Vec<FacetCount> = counts
+                .into_iter()
+                .map(|(value, count)| FacetCount { value, count })
+                .collect();
+            counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+            counts
+        };
+        let mut day: Vec<FacetCount> = self
+            .day
+            .into_iter()
+            .map(|(value, count)| FacetCount { value, count })
+            .collect();
+        day.sort_by(|a, b| a.value.cmp(&b.value));
+        SearchFacets {
+            app_name: by_count(self.app_name),
+            day,
+            content_type: by_count(self.content_type),
+            device: by_count(self.device),
+        }
+    }
+}
+
+/// Content ingested during one bucket of `DatabaseManager::ingest_stats`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IngestBucket {
+    pub start: DateTime<Utc>,
+    pub frames: i64,
+    pub ocr_characters: i64,
+    /// Seconds of audio transcribed, chunks recorded before durations were tracked count 0
+    pub transcription_seconds: f64,
+}
+
+/// What was recorded during one bucket of `DatabaseManager::heatmap`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeatmapBucket {
+    pub start: DateTime<Utc>,
+    pub frames: i64,
+    /// Chunks recorded before durations were tracked count 0
+    pub audio_seconds: f64,
+    pub ocr_characters: i64,
+    pub transcription_characters: i64,
+}
+
+/// Empty buckets of `bucket_secs` covering `start` to `end`, see `DatabaseManager::heatmap`
+pub(crate) fn heatmap_buckets(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    bucket_secs: i64,
+    utc_offset_secs: i64,
+) -> Vec<HeatmapBucket> {
+    let first = (start.timestamp() + utc_offset_secs).div_euclid(bucket_secs);
+    let last = (end.timestamp() + utc_offset_secs).div_euclid(bucket_secs);
+    (first..=last)
+        .map(|bucket| HeatmapBucket {
+            start: DateTime::from_timestamp(bucket * bucket_secs - utc_offset_secs, 0)
+                .unwrap_or_default(),
+            frames: 0,
+            audio_seconds: 0.0,
+            ocr_characters: 0,
+            transcription_characters: 0,
+        })
+        .collect()
+}
+
+/// What this machine recorded during one minute of `DatabaseManager::timeline`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineMinute {
+    pub start: DateTime<Utc>,
+    /// App with the most frames of the minute
+    pub app_name: Option<String>,
+    pub frames: i64,
+    /// Covered by an audio chunk
+    pub audio: bool,
+    /// Covered by an `afkstatus` event saying `afk`, of ActivityWatch or `IdleMonitor`
+    pub idle: bool,
+}
+
+/// Event pulled from an ActivityWatch bucket, see `GET /activity`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActivityEvent {
+    pub id: i64,
+    pub bucket_id: String,
+    /// Type of the bucket, e.g. `afkstatus` or `currentwindow`
+    pub event_type: String,
+    pub timestamp: DateTime<Utc>,
+    /// Seconds
+    pub duration: f64,
+    pub data: serde_json::Value,
+}
+
+/// Moment marked with `POST /marks`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Mark {
+    pub id: i64,
+    pub name: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// `device_id` of the data recorded by this machine, as opposed to ingested from another device
+pub const LOCAL_DEVICE_ID: &str = "local";
+
+/// Device of the screen capture in `capture_states`, the others are audio devices
+pub const VISION_CAPTURE: &str = "vision";
+
+/// Condition on the OCR text of `frames` matching the search of `app`. Where the windows of
+/// a frame were recorded, the text must match in a visible window of the app, not in text
+/// behind it or in another app next to it. Other frames match by their frontmost app.
+fn app_filter(query: &str, app: &str) -> String {
+    format!(
+        r#"({app} IS NULL OR CASE
+            WHEN EXISTS (SELECT 1 FROM frame_windows WHERE frame_windows.frame_id = frames.id)
+            THEN EXISTS (
+                SELECT 1 FROM frame_windows
+                WHERE frame_windows.frame_id = frames.id
+                    AND frame_windows.visible
+                    AND frame_windows.app_name = {app}
+                    AND ({query} IS NULL OR frame_windows.id IN (
+                        SELECT rowid FROM frame_windows_fts WHERE frame_windows_fts MATCH {query}
+                    ))
+            )
+            ELSE frames.app_name = {app}
+        END)"#
+    )
+}
+
+/// Columns of a `CollectionRow`
+const COLLECTION_COLUMNS: &str = "id, name, filters, pinned, tagged_until, created_at, updated_at";
+
+#[derive(FromRow)]
+struct CollectionRow {
+    id: i64,
+    name: String,
+    filters: String,
+    pinned: bool,
+    tagged_until: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+fn into_collection(row: CollectionRow) -> Collection {
+    let filters: CollectionFilters = serde_json::from_str(&row.filters).unwrap_or_else(|e| {
+        warn!("Invalid filters of collection {}: {}", row.id, e);
+        CollectionFilters::default()
+    });
+    Collection {
+        id: row.id,
+        name: row.name,
+        filters,
+        pinned: row.pinned,
+        tagged_until: row.tagged_until,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    }
+}
+
+/// Seconds before the last tagging of a pinned collection searched again, for the content
+/// written since but recorded before
+const TAGGING_OVERLAP_SECS: i64 = 300;
+
+/// Search results tagged at once with a pinned collection
+const TAGGING_PAGE_SIZE: u32 = 500;
+
+/// Transcriptions segmented into sessions at once
+const SESSION_PAGE_SIZE: i64 = 1000;
+
+/// Results counted by `DatabaseManager::search_counts`, of an app, UTC day and device when
+/// grouped
+#[derive(FromRow)]
+struct CountRow {
+    app_name: Option<String>,
+    day: Option<String>,
+    device: Option<String>,
+    count: i64,
+}
+
+/// Grouped by the three first columns of `count_columns`
+const COUNT_GROUP_BY: &str = " GROUP BY 1, 2, 3";
+
+/// Columns of a `CountRow` from the expressions of the app, timestamp and device of a row
+fn count_columns(grouped: bool, app_name: &str, timestamp: &str, device: &str) -> String {
+    if grouped {
+        format!(
+            "{app_name} AS app_name, date({timestamp}) AS day, {device} AS device, COUNT(*) AS count"
+        )
+    } else {
+        "NULL AS app_name, NULL AS day, NULL AS device, COUNT(*) AS count".to_string()
+    }
+}
+
+/// Tables searched for OCR text with their FTS table and the condition on their rows,
+/// aliased `ocr_text`, for the OCR engine in parameter `engine`. Without engine, the latest
+/// text. With one, the latest text it read and, for the frames OCR'd again with another
+/// since, the last version it read.
+fn ocr_sources(engine: Option<&str>, param: &str) -> Vec<(&'static str, &'static str, String)> {
+    let mut sources = vec![(
+        "ocr_text",
+        "ocr_text_fts",
+        format!("({param} IS NULL OR ocr_text.ocr_engine = {param})"),
+    )];
+    if engine.is_some() {
+        sources.push((
+            "ocr_text_versions",
+            "ocr_text_versions_fts",
+            format!(
+                r#"ocr_text.ocr_engine = {param}
+                AND NOT EXISTS (
+                    SELECT 1 FROM ocr_text AS latest
+                    WHERE latest.frame_id = ocr_text.frame_id AND latest.ocr_engine = {param}
+                )
+                AND ocr_text.id = (
+                    SELECT MAX(id) FROM ocr_text_versions AS later
+                    WHERE later.frame_id = ocr_text.frame_id AND later.ocr_engine = {param}
+                )"#
+            ),
+        ));
+    }
+    sources
+}
+
+/// Like `ocr_sources` for the transcriptions, aliased `audio_transcriptions`, of the
+/// transcription engine in parameter `engine`
+fn audio_sources(engine: Option<&str>, param: &str) -> Vec<(&'static str, &'static str, String)> {
+    let mut sources = vec![(
+        "audio_transcriptions",
+        "audio_transcriptions_fts",
+        format!("({param} IS NULL OR audio_transcriptions.transcription_engine = {param})"),
+    )];
+    if engine.is_some() {
+        sources.push((
+            "audio_transcription_versions",
+            "audio_transcription_versions_fts",
+            format!(
+                r#"audio_transcriptions.transcription_engine = {param}
+                AND NOT EXISTS (
+                    SELECT 1 FROM audio_transcriptions AS latest
+                    WHERE latest.audio_chunk_id = audio_transcriptions.audio_chunk_id
+                        AND latest.transcription_engine = {param}
+                )
+                AND audio_transcriptions.replaced_at = (
+                    SELECT MAX(replaced_at) FROM audio_transcription_versions AS later
+                    WHERE later.audio_chunk_id = audio_transcriptions.audio_chunk_id
+                        AND later.transcription_engine = {param}
+                )"#
+            ),
+        ));
+    }
+    sources
+}
+
+/// Order of the rows of `search_ocr` and the like
+#[derive(Debug, Clone, Copy)]
+enum Order {
+    /// Most recent first, for keyset pagination
+    Recent,
+    /// Most relevant first, the relevance halved at the age in hours when set
+    Relevance(Option<f64>),
+}
+
+impl Order {
+    /// `ORDER BY` of rows with `timestamp` and `id`
+    fn order_by(self, timestamp: &str, id: &str) -> String {
+        match self {
+            Order::Recent => format!("{timestamp} DESC, {id} DESC"),
+            Order::Relevance(_) => format!("relevance DESC, {timestamp} DESC, {id} DESC"),
+        }
+    }
+}
+
+/// Relevance of the row of `fts` at `rowid` for the query in `?1`, 0 - 1: its BM25 score
+/// over the best score of the query in `fts`, so the content types searched compare. 1
+/// without query. With a half-life, divided by 1 + the age of `timestamp` over it.
+fn relevance_sql(fts: &str, rowid: &str, timestamp: &str, order: Order) -> String {
+    let score = format!(
+        r#"CASE WHEN ?1 IS NULL THEN 1.0 ELSE COALESCE(
+            (SELECT -bm25({fts}) FROM {fts} WHERE {fts} MATCH ?1 AND rowid = {rowid})
+                / NULLIF((SELECT -rank FROM {fts} WHERE {fts} MATCH ?1 ORDER BY rank LIMIT 1), 0),
+            1.0
+        ) END"#
+    );
+    match order {
+        Order::Relevance(Some(half_life_hours)) => format!(
+            "({score}) / (1.0 + (julianday('now') - julianday({timestamp})) * 24.0 / {half_life_hours})"
+        ),
+        _ => score,
+    }
+}
+
+/// Data recorded on another device, pushed with `POST /ingest`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestBatch {
+    pub device_id: String,
+    #[serde(default)]
+    pub frames: Vec<IngestFrame>,
+    #[serde(default)]
+    pub transcriptions: Vec<IngestTranscription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestFrame {
+    pub timestamp: DateTime<Utc>,
+    /// Video chunk of the frame on the device, frames of the same chunk are grouped
+    pub file_path: String,
+    pub offset_index: i64,
+    #[serde(default)]
+    pub app_name: String,
+    pub text: String,
+    #[serde(default)]
+    pub text_json: String,
+    #[serde(default)]
+    pub ocr_engine: String,
+    /// `text` as the device read it, when a processor corrected it
+    #[serde(skip)]
+    pub raw_text: Option<String>,
+    /// Named in `text`, see `EntityExtractor`
+    #[serde(skip)]
+    pub entities: Vec<Entity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestTranscription {
+    pub timestamp: DateTime<Utc>,
+    /// Audio chunk of the transcription on the device
+    pub file_path: String,
+    #[serde(default)]
+    pub offset_index: i64,
+    pub transcription: String,
+    #[serde(default)]
+    pub transcription_engine: String,
+    /// Seconds of audio of the chunk
+    #[serde(default)]
+    pub duration: Option<f64>,
+    /// Code of the language spoken, e.g. "en"
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Timing of the transcription in the chunk
+    #[serde(default)]
+    pub segments: Vec<TranscriptionSegment>,
+    /// Named in `transcription`, see `EntityExtractor`
+    #[serde(skip)]
+    pub entities: Vec<Entity>,
+}
+
+/// Stored form of transcription segments, NULL when the engine didn't time the transcription
+pub(crate) fn segments_json(segments: &[TranscriptionSegment]) -> Option<String> {
+    if segments.is_empty() {
+        return None;
+    }
+    serde_json::to_string(segments).ok()
+}
+
+/// Stored form of the part of the screen a frame shows, NULL when unknown
+pub(crate) fn capture_region_json(region: Option<&CaptureRegion>) -> Option<String> {
+    region.and_then(|region| serde_json::to_string(region).ok())
+}
+
+fn parse_capture_region(json: Option<String>) -> Option<CaptureRegion> {
+    json.and_then(|json| serde_json::from_str(&json).ok())
+}
+
+/// JSON array of the names of `kinds`, matched with `json_each`
+fn event_kinds_json(kinds: &[EventKind]) -> String {
+    serde_json::to_string(kinds).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Rows written by `DatabaseManager::ingest`, the skipped ones were already ingested
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IngestSummary {
+    pub frames_inserted: usize,
+    pub frames_skipped: usize,
+    pub transcriptions_inserted: usize,
+    pub transcriptions_skipped: usize,
+}
+
+fn content_hash(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Position after the last result of a page in keyset pagination, one per content type
+/// so the merged pages continue each type where it stopped. Opaque to API clients.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchCursor {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) ocr: Option<(String, i64)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) audio: Option<(String, i64)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) clipboard: Option<(String, i64)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) notification: Option<(String, i64)>,
+}
+
+impl SearchCursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap())
+    }
+
+    /// `None` for a cursor this server did not produce
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+/// PRAGMAs applied to every connection of the pool
+#[derive(Debug, Clone)]
+pub struct DatabaseSettings {
+    /// Write-ahead logging, searches keep reading while the recorders write
+    pub wal: bool,
+    pub synchronous: SqliteSynchronous,
+    /// Bytes of the database file memory-mapped for reads, 0 disables it
+    pub mmap_size: u64,
+    /// Only applies to a new database, an existing one keeps its page size until VACUUM
+    pub page_size: u32,
+    /// Encrypts the database with SQLCipher, and the video/audio chunks of the recordings
+    /// stored in it. A plaintext database must be encrypted first with `encrypt_database`.
+    #[cfg(feature = "encryption")]
+    pub encryption_key: Option<EncryptionKey>,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        DatabaseSettings {
+            wal: true,
+            // Safe with WAL, only the last transactions can be lost on power failure
+            synchronous: SqliteSynchronous::Normal,
+            mmap_size: 256 * 1024 * 1024,
+            page_size: 4096,
+            #[cfg(feature = "encryption")]
+            encryption_key: None,
+        }
+    }
 }
 
 pub struct DatabaseManager {
     pub pool: SqlitePool,
+    writer: BatchWriter,
+    #[cfg(feature = "encryption")]
+    encryption_key: Option<EncryptionKey>,
 }
 
 impl DatabaseManager {
     pub async fn new(database_path: &str) -> Result<Self, sqlx::Error> {
+        Self::with_settings(database_path, &DatabaseSettings::default()).await
+    }
+
+    pub async fn with_settings(
+        database_path: &str,
+        settings: &DatabaseSettings,
+    ) -> Result<Self, sqlx::Error> {
         debug!(
-            "Initializing DatabaseManager with database path: {}",
-            database_path
+            "Initializing DatabaseManager with database path: {} and {:?}",
+            database_path, settings
         );
         let connection_string = format!("sqlite:{}", database_path);
 
@@ -83,14 +797,37 @@ impl DatabaseManager {
             sqlx::Sqlite::create_database(&connection_string).await?;
         }
 
+        let journal_mode = if settings.wal {
+            SqliteJournalMode::Wal
+        } else {
+            SqliteJournalMode::Delete
+        };
+        let options = SqliteConnectOptions::from_str(&connection_string)?
+            .page_size(settings.page_size)
+            .journal_mode(journal_mode)
+            .synchronous(settings.synchronous)
+            .pragma("mmap_size", settings.mmap_size.to_string());
+        // sqlx applies the key before the other PRAGMAs, as SQLCipher requires
+        #[cfg(feature = "encryption")]
+        let options = match &settings.encryption_key {
+            Some(key) => options.pragma("key", key.sqlcipher_key()),
+            None => options,
+        };
+
         let pool = SqlitePoolOptions::new()
             .max_connections(10)
             .min_connections(3) // Minimum number of idle connections
             .acquire_timeout(Duration::from_secs(10))
-            .connect(&connection_string)
+            .connect_with(options)
             .await?;
 
-        let db_manager = DatabaseManager { pool };
+        let writer = BatchWriter::spawn(pool.clone(), WRITE_BATCH_ROWS, WRITE_BATCH_INTERVAL);
+        let db_manager = DatabaseManager {
+            pool,
+            writer,
+            #[cfg(feature = "encryption")]
+            encryption_key: settings.encryption_key.clone(),
+        };
 
         // Run migrations after establishing the connection
         if let Err(e) = Self::run_migrations(&db_manager.pool).await {
@@ -102,73 +839,442 @@ impl DatabaseManager {
         Ok(db_manager)
     }
 
+    /// Applies the pending migrations of `src/migrations`, each in its own transaction
     async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-        sqlx::migrate!("./src/migrations").run(pool).await?;
+        let migrator = sqlx::migrate!("./src/migrations");
+        // No table yet on a new database
+        let applied: Vec<i64> =
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = 1")
+                .fetch_all(pool)
+                .await
+                .unwrap_or_default();
+        let pending: Vec<_> = migrator
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .collect();
+        if !applied.is_empty() && !pending.is_empty() {
+            // Can take a while on a large database, say what is going on
+            for migration in &pending {
+                info!(
+                    "Applying migration {} ({})",
+                    migration.version, migration.description
+                );
+            }
+        }
+        migrator.run(pool).await?;
+        Ok(())
+    }
+
+    /// Key of the database, the recordings stored in it encrypt their chunks with it too
+    #[cfg(feature = "encryption")]
+    pub fn encryption_key(&self) -> Option<&EncryptionKey> {
+        self.encryption_key.as_ref()
+    }
+
+    /// Version of the last migration applied
+    pub async fn schema_version(&self) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Consistent copy of the database at `path`, which must not exist. Writes go on
+    /// meanwhile, the copy is of the last committed transaction.
+    pub async fn backup_into(&self, path: &Path) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM INTO ?1")
+            .bind(path.to_string_lossy())
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 
     pub async fn insert_audio_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
+        self.insert_audio_chunk_with_duration(file_path, None).await
+    }
+
+    /// `duration` is the seconds of audio in the chunk
+    pub async fn insert_audio_chunk_with_duration(
+        &self,
+        file_path: &str,
+        duration: Option<f64>,
+    ) -> Result<i64, sqlx::Error> {
+        self.insert_audio_chunk_with_format(file_path, duration, None)
+            .await
+    }
+
+    /// Inserts an audio chunk encoded in `format`, e.g. "opus", `None` when unknown
+    pub async fn insert_audio_chunk_with_format(
+        &self,
+        file_path: &str,
+        duration: Option<f64>,
+        format: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
-        let id = sqlx::query("INSERT INTO audio_chunks (file_path, timestamp) VALUES (?1, ?2)")
-            .bind(file_path)
-            .bind(Utc::now())
-            .execute(&mut *tx)
-            .await?
-            .last_insert_rowid();
+        let id = sqlx::query(
+            "INSERT INTO audio_chunks (file_path, timestamp, duration, format) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(file_path)
+        .bind(Utc::now())
+        .bind(duration)
+        .bind(format)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
         tx.commit().await?;
         Ok(id)
     }
 
-    pub async fn insert_audio_transcription(
+    /// Flags the audio chunk as not transcribed because of `error`, it's transcribed again in
+    /// the background
+    pub async fn set_transcription_error(
         &self,
         audio_chunk_id: i64,
-        transcription: &str,
-        offset_index: i64,
-        transcription_engine: &str,
+        error: &str,
     ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE audio_chunks SET transcription_error = ?1 WHERE id = ?2")
+            .bind(error)
+            .bind(audio_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Flags the audio chunks recorded by this machine since `since` without transcription,
+    /// because it failed or never ran, to be transcribed again with fresh attempts. `all`
+    /// flags the transcribed ones too, their transcription is kept as a version once
+    /// replaced. Returns how many.
+    pub async fn queue_retranscription(
+        &self,
+        since: Option<DateTime<Utc>>,
+        all: bool,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE audio_chunks
+            SET transcription_error = COALESCE(transcription_error, 'not transcribed'),
+                transcription_attempts = 0,
+                transcription_requested = TRUE
+            WHERE device_id = ?3
+                AND (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 OR NOT EXISTS (
+                    SELECT 1 FROM audio_transcriptions
+                    WHERE audio_transcriptions.audio_chunk_id = audio_chunks.id
+                ))
+            "#,
+        )
+        .bind(since)
+        .bind(all)
+        .bind(LOCAL_DEVICE_ID)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Id, file path, timestamp and whether it was queued by `queue_retranscription` of the
+    /// flagged audio chunks transcribed fewer than `max_attempts` times since, the queued
+    /// ones then the oldest first
+    pub async fn chunks_to_retranscribe(
+        &self,
+        max_attempts: i64,
+        limit: i64,
+    ) -> Result<Vec<(i64, String, DateTime<Utc>, bool)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, file_path, timestamp, transcription_requested FROM audio_chunks
+            WHERE device_id = ?3
+                AND transcription_error IS NOT NULL AND transcription_attempts < ?1
+            ORDER BY transcription_requested DESC, timestamp
+            LIMIT ?2
+            "#,
+        )
+        .bind(max_attempts)
+        .bind(limit)
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Counts an attempt at transcribing the flagged audio chunk again
+    pub async fn start_retranscription(&self, audio_chunk_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE audio_chunks SET transcription_attempts = transcription_attempts + 1 WHERE id = ?1",
+        )
+        .bind(audio_chunk_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Clears the flag of the audio chunk at `file_path` once transcribed again, with the
+    /// `duration` decoded, and moves its transcriptions to `audio_transcription_versions`
+    /// for the new one to replace them. Returns its id, `None` if it was deleted since.
+    pub async fn finish_retranscription(
+        &self,
+        file_path: &str,
+        duration: Option<f64>,
+    ) -> Result<Option<i64>, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
+        let audio_chunk_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE audio_chunks
+            SET transcription_error = NULL, transcription_requested = FALSE,
+                duration = COALESCE(?2, duration)
+            WHERE file_path = ?1
+            RETURNING id
+            "#,
+        )
+        .bind(file_path)
+        .bind(duration)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(audio_chunk_id) = audio_chunk_id else {
+            return Ok(None);
+        };
 
-        // Insert the full transcription
         sqlx::query(
-            "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine) VALUES (?1, ?2, ?3, ?4, ?5)",
+            r#"
+            INSERT INTO audio_transcription_versions (id, audio_chunk_id, offset_index,
+                timestamp, transcription, transcription_engine, language, segments, device_id,
+                replaced_at)
+            SELECT id, audio_chunk_id, offset_index, timestamp, transcription,
+                transcription_engine, language, segments, device_id, ?2
+            FROM audio_transcriptions WHERE audio_chunk_id = ?1
+            "#,
         )
         .bind(audio_chunk_id)
-        .bind(transcription)
-        .bind(offset_index)
         .bind(Utc::now())
-        .bind(transcription_engine)
         .execute(&mut *tx)
         .await?;
-
-        // Commit the transaction for the full transcription
+        sqlx::query(
+            "DELETE FROM entities WHERE audio_transcription_id IN (SELECT id FROM audio_transcriptions WHERE audio_chunk_id = ?1)",
+        )
+        .bind(audio_chunk_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM audio_transcriptions WHERE audio_chunk_id = ?1")
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
         tx.commit().await?;
+        Ok(Some(audio_chunk_id))
+    }
 
-        // Now, let's chunk the transcription and insert into chunk tables
-        // const CHUNKING_ENGINE: &str = "candle-jina-bert";
-        // match text_chunking_local(transcription).await {
-        //     Ok(chunks) => {
-        //         info!("Successfully chunked audio transcription into {} chunks", chunks.len());
-        //         for chunk in chunks.iter() {
-        //             if let Err(e) = self.insert_chunked_text(
-        //                 audio_chunk_id,
-        //                 chunk,
-        //                 Utc::now(),
-        //                 transcription_engine,
-        //                 CHUNKING_ENGINE,
-        //                 ContentSource::Audio,
-        //             ).await {
-        //                 error!("Failed to insert chunk into chunked text index: {}", e);
-        //             }
-        //         }
-        //     }
-        //     Err(e) => {
-        //         error!("Failed to chunk audio transcription: {}", e);
-        //         // Fallback to inserting the whole transcription as a single chunk
-        //         if let Err(e) = self.insert_chunked_text(
-        //             audio_chunk_id,
-        //             transcription,
-        //             Utc::now(),
-        //             transcription_engine,
+    /// Queues the frames recorded by this machine between `start` and `end` to be OCR'd
+    /// again from their video chunk with the current engine. Returns how many.
+    pub async fn queue_reocr(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE frames SET reocr_pending = TRUE
+            WHERE timestamp BETWEEN ?1 AND ?2
+                AND video_chunk_id IN (SELECT id FROM video_chunks WHERE device_id = ?3)
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(LOCAL_DEVICE_ID)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Id and file path of the oldest video chunk with frames queued to be OCR'd again. The
+    /// latest chunk waits as ffmpeg may still be writing it.
+    pub async fn next_chunk_to_reocr(&self) -> Result<Option<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, file_path FROM video_chunks
+            WHERE device_id = ?1
+                AND id < (SELECT MAX(id) FROM video_chunks WHERE device_id = ?1)
+                AND EXISTS (
+                    SELECT 1 FROM frames
+                    WHERE frames.video_chunk_id = video_chunks.id AND frames.reocr_pending
+                )
+            ORDER BY id
+            LIMIT 1
+            "#,
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Id, offset index, timestamp, app name and OCR engine of the frames of the video
+    /// chunk queued to be OCR'd again, in order
+    #[allow(clippy::type_complexity)]
+    pub async fn frames_to_reocr(
+        &self,
+        video_chunk_id: i64,
+    ) -> Result<Vec<(i64, i64, DateTime<Utc>, String, Option<String>)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT frames.id, frames.offset_index, frames.timestamp, frames.app_name,
+                ocr_text.ocr_engine
+            FROM frames
+            LEFT JOIN ocr_text ON ocr_text.frame_id = frames.id
+            WHERE frames.video_chunk_id = ?1 AND frames.reocr_pending
+            ORDER BY frames.offset_index
+            "#,
+        )
+        .bind(video_chunk_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Takes the frames of the video chunk off the re-OCR queue, e.g. when it can't be read
+    pub async fn cancel_reocr(&self, video_chunk_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET reocr_pending = FALSE WHERE video_chunk_id = ?1")
+            .bind(video_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Takes the frame off the re-OCR queue, e.g. when it was OCR'd with the current engine
+    pub async fn skip_reocr(&self, frame_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET reocr_pending = FALSE WHERE id = ?1")
+            .bind(frame_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Replaces the OCR text, windows and entities of frame `offset_index` of the local
+    /// video chunk at `video_chunk` with `frame`, OCR'd again, and takes it off the re-OCR
+    /// queue. The replaced text is kept in `ocr_text_versions`. Returns its id, `None` if it
+    /// was deleted since.
+    pub async fn replace_frame_ocr(
+        &self,
+        video_chunk: &str,
+        offset_index: i64,
+        frame: &FrameRecord,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let frame_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE frames SET reocr_pending = FALSE
+            WHERE offset_index = ?2 AND video_chunk_id IN (
+                SELECT id FROM video_chunks WHERE file_path = ?1 AND device_id = ?3
+            )
+            RETURNING id
+            "#,
+        )
+        .bind(video_chunk)
+        .bind(offset_index)
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(frame_id) = frame_id else {
+            return Ok(None);
+        };
+
+        // The text being replaced is kept as a version
+        sqlx::query(
+            r#"
+            INSERT INTO ocr_text_versions (frame_id, text, raw_text, text_json,
+                new_text_json_vs_previous_frame, raw_data_output_from_OCR, ocr_engine,
+                confidence, words, replaced_at)
+            SELECT frame_id, text, raw_text, text_json, new_text_json_vs_previous_frame,
+                raw_data_output_from_OCR, ocr_engine, confidence, words, ?2
+            FROM ocr_text WHERE frame_id = ?1
+            "#,
+        )
+        .bind(frame_id)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            UPDATE ocr_text
+            SET text = ?2, raw_text = ?3, text_json = ?4, new_text_json_vs_previous_frame = ?5,
+                raw_data_output_from_OCR = ?6, ocr_engine = ?7, confidence = ?8, words = ?9
+            WHERE frame_id = ?1
+            "#,
+        )
+        .bind(frame_id)
+        .bind(&frame.text)
+        .bind(&frame.raw_text)
+        .bind(&frame.text_json)
+        .bind(&frame.new_text_json_vs_previous_frame)
+        .bind(&frame.raw_data_output_from_ocr)
+        .bind(&frame.ocr_engine)
+        .bind(mean_confidence(&frame.words))
+        .bind(words_json(&frame.words))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM frame_windows WHERE frame_id = ?1")
+            .bind(frame_id)
+            .execute(&mut *tx)
+            .await?;
+        insert_windows(&mut *tx, frame_id, &frame.windows).await?;
+        sqlx::query("DELETE FROM entities WHERE frame_id = ?1")
+            .bind(frame_id)
+            .execute(&mut *tx)
+            .await?;
+        insert_entities(
+            &mut *tx,
+            EntitySource::Frame(frame_id),
+            frame.timestamp,
+            &frame.entities,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(Some(frame_id))
+    }
+
+    pub async fn insert_audio_transcription(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+        offset_index: i64,
+        transcription_engine: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Insert the full transcription
+        sqlx::query(
+            "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(audio_chunk_id)
+        .bind(transcription)
+        .bind(offset_index)
+        .bind(Utc::now())
+        .bind(transcription_engine)
+        .execute(&mut *tx)
+        .await?;
+
+        // Commit the transaction for the full transcription
+        tx.commit().await?;
+
+        // Now, let's chunk the transcription and insert into chunk tables
+        // const CHUNKING_ENGINE: &str = "candle-jina-bert";
+        // match text_chunking_local(transcription).await {
+        //     Ok(chunks) => {
+        //         info!("Successfully chunked audio transcription into {} chunks", chunks.len());
+        //         for chunk in chunks.iter() {
+        //             if let Err(e) = self.insert_chunked_text(
+        //                 audio_chunk_id,
+        //                 chunk,
+        //                 Utc::now(),
+        //                 transcription_engine,
+        //                 CHUNKING_ENGINE,
+        //                 ContentSource::Audio,
+        //             ).await {
+        //                 error!("Failed to insert chunk into chunked text index: {}", e);
+        //             }
+        //         }
+        //     }
+        //     Err(e) => {
+        //         error!("Failed to chunk audio transcription: {}", e);
+        //         // Fallback to inserting the whole transcription as a single chunk
+        //         if let Err(e) = self.insert_chunked_text(
+        //             audio_chunk_id,
+        //             transcription,
+        //             Utc::now(),
+        //             transcription_engine,
         //             "No_Chunking",
         //             ContentSource::Audio,
         //         ).await {
@@ -180,10 +1286,27 @@ impl DatabaseManager {
         Ok(())
     }
 
-    pub async fn insert_video_chunk(&self, file_path: &str) -> Result<i64, sqlx::Error> {
+    /// Inserts a video chunk of unknown codec, e.g. found on disk, returns its id. Named apart
+    /// from [`crate::Storage::insert_video_chunk`] which it would shadow.
+    pub async fn insert_video_chunk_without_codec(
+        &self,
+        file_path: &str,
+    ) -> Result<i64, sqlx::Error> {
+        self.insert_video_chunk_with_codec(file_path, None).await
+    }
+
+    /// Inserts a video chunk encoded with `codec`, e.g. "hevc", `None` when unknown
+    pub async fn insert_video_chunk_with_codec(
+        &self,
+        file_path: &str,
+        codec: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
+        // Queued frames belong to the previous chunk
+        self.flush_writes().await;
         let mut tx = self.pool.begin().await?;
-        let id = sqlx::query("INSERT INTO video_chunks (file_path) VALUES (?1)")
+        let id = sqlx::query("INSERT INTO video_chunks (file_path, codec) VALUES (?1, ?2)")
             .bind(file_path)
+            .bind(codec)
             .execute(&mut *tx)
             .await?
             .last_insert_rowid();
@@ -191,17 +1314,205 @@ impl DatabaseManager {
         Ok(id)
     }
 
+    /// Queues a frame and its OCR text for the next batch write, for the recording loop
+    /// which doesn't need the frame id. Call `flush_writes` to wait for the write. Waits
+    /// while the write queue is full.
+    pub async fn queue_frame_with_ocr_text(
+        &self,
+        app_name: &str,
+        text: &str,
+        text_json: &str,
+        new_text_json_vs_previous_frame: &str,
+        raw_data_output_from_ocr: &str,
+        ocr_engine: Arc<OcrEngine>,
+    ) {
+        self.writer
+            .push(PendingWrite::Frame(PendingFrame {
+                app_name: app_name.to_string(),
+                timestamp: Utc::now(),
+                text: text.to_string(),
+                raw_text: None,
+                text_json: text_json.to_string(),
+                new_text_json_vs_previous_frame: new_text_json_vs_previous_frame.to_string(),
+                raw_data_output_from_ocr: raw_data_output_from_ocr.to_string(),
+                ocr_engine: format!("{:?}", *ocr_engine),
+                words: Vec::new(),
+                entities: Vec::new(),
+                windows: Vec::new(),
+                region: None,
+            }))
+            .await;
+    }
+
+    /// Queues a transcription for the next batch write, waits while the write queue is full
+    pub async fn queue_audio_transcription(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+        offset_index: i64,
+        transcription_engine: &str,
+    ) {
+        self.writer
+            .push(PendingWrite::Transcription(PendingTranscription {
+                audio_chunk_id,
+                transcription: transcription.to_string(),
+                offset_index,
+                timestamp: Utc::now(),
+                transcription_engine: transcription_engine.to_string(),
+                language: None,
+                segments: None,
+                entities: Vec::new(),
+            }))
+            .await;
+    }
+
+    pub(crate) async fn queue_write(&self, write: PendingWrite) {
+        self.writer.push(write).await;
+    }
+
+    /// Latency of the last batch of writes and the last write error
+    pub fn write_stats(&self) -> WriteStats {
+        self.writer.stats()
+    }
+
+    /// Waits until the queued writes are in the database
+    pub async fn flush_writes(&self) {
+        self.writer.flush().await;
+    }
+
+    /// Stores data recorded on another device in one transaction. Frames and transcriptions
+    /// with the same device, timestamp and content as an ingested one are skipped, so a
+    /// batch can be pushed again after a failure. The `file_path` of their chunks names a
+    /// file of that device, chosen by the client: media readers only open the chunks of
+    /// `LOCAL_DEVICE_ID`.
+    pub async fn ingest(&self, batch: &IngestBatch) -> Result<IngestSummary, sqlx::Error> {
+        let mut summary = IngestSummary::default();
+        let mut tx = self.pool.begin().await?;
+
+        for frame in &batch.frames {
+            let video_chunk_id: Option<i64> = sqlx::query_scalar(
+                "SELECT id FROM video_chunks WHERE device_id = ?1 AND file_path = ?2",
+            )
+            .bind(&batch.device_id)
+            .bind(&frame.file_path)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let video_chunk_id = match video_chunk_id {
+                Some(id) => id,
+                None => {
+                    sqlx::query("INSERT INTO video_chunks (file_path, device_id) VALUES (?1, ?2)")
+                        .bind(&frame.file_path)
+                        .bind(&batch.device_id)
+                        .execute(&mut *tx)
+                        .await?
+                        .last_insert_rowid()
+                }
+            };
+
+            let inserted = sqlx::query(
+                "INSERT OR IGNORE INTO frames (video_chunk_id, offset_index, timestamp, app_name, device_id, content_hash) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .bind(video_chunk_id)
+            .bind(frame.offset_index)
+            .bind(frame.timestamp)
+            .bind(&frame.app_name)
+            .bind(&batch.device_id)
+            .bind(content_hash(&[&frame.app_name, &frame.text]))
+            .execute(&mut *tx)
+            .await?;
+            if inserted.rows_affected() == 0 {
+                summary.frames_skipped += 1;
+                continue;
+            }
+
+            let frame_id = inserted.last_insert_rowid();
+            sqlx::query("INSERT INTO ocr_text (frame_id, text, raw_text, text_json, new_text_json_vs_previous_frame, raw_data_output_from_OCR, app_name, ocr_engine) VALUES (?1, ?2, ?3, ?4, '', '', ?5, ?6)")
+                .bind(frame_id)
+                .bind(&frame.text)
+                .bind(&frame.raw_text)
+                .bind(&frame.text_json)
+                .bind(&frame.app_name)
+                .bind(&frame.ocr_engine)
+                .execute(&mut *tx)
+                .await?;
+            insert_entities(
+                &mut *tx,
+                EntitySource::Frame(frame_id),
+                frame.timestamp,
+                &frame.entities,
+            )
+            .await?;
+            summary.frames_inserted += 1;
+        }
+
+        for transcription in &batch.transcriptions {
+            let audio_chunk_id: Option<i64> = sqlx::query_scalar(
+                "SELECT id FROM audio_chunks WHERE device_id = ?1 AND file_path = ?2",
+            )
+            .bind(&batch.device_id)
+            .bind(&transcription.file_path)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let audio_chunk_id = match audio_chunk_id {
+                Some(id) => id,
+                None => sqlx::query(
+                    "INSERT INTO audio_chunks (file_path, timestamp, duration, device_id) VALUES (?1, ?2, ?3, ?4)",
+                )
+                .bind(&transcription.file_path)
+                .bind(transcription.timestamp)
+                .bind(transcription.duration)
+                .bind(&batch.device_id)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid(),
+            };
+
+            let inserted = sqlx::query(
+                "INSERT OR IGNORE INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine, device_id, content_hash, language, segments) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )
+            .bind(audio_chunk_id)
+            .bind(&transcription.transcription)
+            .bind(transcription.offset_index)
+            .bind(transcription.timestamp)
+            .bind(&transcription.transcription_engine)
+            .bind(&batch.device_id)
+            .bind(content_hash(&[&transcription.transcription]))
+            .bind(&transcription.language)
+            .bind(segments_json(&transcription.segments))
+            .execute(&mut *tx)
+            .await?;
+            if inserted.rows_affected() == 0 {
+                summary.transcriptions_skipped += 1;
+            } else {
+                insert_entities(
+                    &mut *tx,
+                    EntitySource::Transcription(inserted.last_insert_rowid()),
+                    transcription.timestamp,
+                    &transcription.entities,
+                )
+                .await?;
+                summary.transcriptions_inserted += 1;
+            }
+        }
+
+        tx.commit().await?;
+        debug!("Ingested from device {}: {:?}", batch.device_id, summary);
+        Ok(summary)
+    }
+
     pub async fn insert_frame(&self, app_name: &str) -> Result<i64, sqlx::Error> {
         // debug!("Starting insert_frame");
 
         let mut tx = self.pool.begin().await?;
         debug!("insert_frame Transaction started");
 
-        // Get the most recent video_chunk_id
-        let video_chunk_id: Option<i64> =
-            sqlx::query_scalar("SELECT id FROM video_chunks ORDER BY id DESC LIMIT 1")
-                .fetch_optional(&mut *tx)
-                .await?;
+        // Get the most recent video_chunk_id, ingested chunks belong to other devices
+        let video_chunk_id: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM video_chunks WHERE device_id = ?1 ORDER BY id DESC LIMIT 1",
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_optional(&mut *tx)
+        .await?;
         debug!("Fetched most recent video_chunk_id: {:?}", video_chunk_id);
 
         // If no video chunk is found, return 0
@@ -388,6 +1699,12 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Results of `content_type` matching `query`, most recent first. `min_confidence` leaves
+    /// out the frames OCR read with a lower mean confidence, frames without one are kept.
+    /// `entity` keeps the frames and transcriptions naming it, see `normalize_entity`.
+    /// `engine` searches the text an OCR or transcription engine read, kept as a version when
+    /// the content was processed again with another one, instead of the latest text.
+    #[allow(clippy::too_many_arguments)]
     pub async fn search(
         &self,
         query: &str,
@@ -397,151 +1714,1015 @@ impl DatabaseManager {
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
         app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        self.search_ordered(
+            query,
+            content_type,
+            limit,
+            offset,
+            start_time,
+            end_time,
+            app_name,
+            min_confidence,
+            entity,
+            engine,
+            Order::Recent,
+        )
+        .await
+    }
+
+    /// Like `search`, most relevant first across content types, see `relevance` of the
+    /// results. `recency_half_life_hours` favors recent results: the relevance of a result
+    /// that old is halved.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_by_relevance(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        recency_half_life_hours: Option<f64>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        self.search_ordered(
+            query,
+            content_type,
+            limit,
+            offset,
+            start_time,
+            end_time,
+            app_name,
+            min_confidence,
+            entity,
+            engine,
+            Order::Relevance(recency_half_life_hours),
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn search_ordered(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        order: Order,
     ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        let query = fts_query(query)?;
+        let query = query.as_deref();
+        let entity = entity.map(normalize_entity);
+        let entity = entity.as_deref();
+        // Ranked across content types, each returns the page with what precedes it
+        let (fetch, skip) = match order {
+            Order::Recent => (limit, offset),
+            Order::Relevance(_) => (limit.saturating_add(offset), 0),
+        };
         let mut results = Vec::new();
 
         // If app_name is specified, only search OCR content
         if app_name.is_some() {
             let ocr_results = self
-                .search_ocr(query, limit, offset, start_time, end_time, app_name)
+                .search_ocr(
+                    query,
+                    fetch,
+                    skip,
+                    start_time,
+                    end_time,
+                    app_name,
+                    min_confidence,
+                    entity,
+                    engine,
+                    order,
+                    None,
+                )
                 .await?;
             results.extend(ocr_results.into_iter().map(SearchResult::OCR));
         } else {
             // If no app_name is specified, proceed with normal search
             if content_type == ContentType::All || content_type == ContentType::OCR {
                 let ocr_results = self
-                    .search_ocr(query, limit, offset, start_time, end_time, None)
+                    .search_ocr(
+                        query,
+                        fetch,
+                        skip,
+                        start_time,
+                        end_time,
+                        None,
+                        min_confidence,
+                        entity,
+                        engine,
+                        order,
+                        None,
+                    )
                     .await?;
                 results.extend(ocr_results.into_iter().map(SearchResult::OCR));
             }
 
             if content_type == ContentType::All || content_type == ContentType::Audio {
                 let audio_results = self
-                    .search_audio(query, limit, offset, start_time, end_time)
+                    .search_audio(
+                        query, fetch, skip, start_time, end_time, entity, engine, order, None,
+                    )
                     .await?;
                 results.extend(audio_results.into_iter().map(SearchResult::Audio));
             }
-        }
 
-        // Sort results by timestamp in descending order
-        results.sort_by(|a, b| {
-            let timestamp_a = match a {
-                SearchResult::OCR(ocr) => ocr.timestamp,
-                SearchResult::Audio(audio) => audio.timestamp,
-            };
-            let timestamp_b = match b {
-                SearchResult::OCR(ocr) => ocr.timestamp,
-                SearchResult::Audio(audio) => audio.timestamp,
-            };
-            timestamp_b.cmp(&timestamp_a)
-        });
+            // Entities aren't extracted from clipboard copies and notifications, no engine
+            // reads them
+            let entity_free = entity.is_none() && engine.is_none();
+            if entity_free
+                && (content_type == ContentType::All || content_type == ContentType::Clipboard)
+            {
+                let clipboard_results = self
+                    .search_clipboard(query, fetch, skip, start_time, end_time, order, None)
+                    .await?;
+                results.extend(clipboard_results.into_iter().map(SearchResult::Clipboard));
+            }
 
-        // Apply limit after combining and sorting
-        results.truncate(limit as usize);
+            if entity_free
+                && (content_type == ContentType::All || content_type == ContentType::Notification)
+            {
+                let notification_results = self
+                    .search_notifications(query, fetch, skip, start_time, end_time, order, None)
+                    .await?;
+                results.extend(
+                    notification_results
+                        .into_iter()
+                        .map(SearchResult::Notification),
+                );
+            }
+        }
+
+        match order {
+            Order::Recent => {
+                sort_by_recency(&mut results);
+                // Apply limit after combining and sorting
+                results.truncate(limit as usize);
+            }
+            Order::Relevance(_) => {
+                sort_by_relevance(&mut results);
+                results = results
+                    .into_iter()
+                    .skip(offset as usize)
+                    .take(limit as usize)
+                    .collect();
+            }
+        }
 
         Ok(results)
     }
 
-    // Update the search_ocr method
-    async fn search_ocr(
+    /// Like `search` with keyset pagination, which stays fast deep into large databases.
+    /// Returns the page and the cursor of the next one, `None` on the last page.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_page(
         &self,
         query: &str,
+        content_type: ContentType,
         limit: u32,
-        offset: u32,
+        cursor: Option<&SearchCursor>,
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
-        app_name: Option<&str>, // Add this parameter
-    ) -> Result<Vec<OCRResult>, sqlx::Error> {
-        let mut sql = r#"
-            SELECT 
-                ocr_text.frame_id,
-                ocr_text.text as ocr_text,
-                ocr_text.text_json,
-                ocr_text.new_text_json_vs_previous_frame,
-                ocr_text.raw_data_output_from_OCR,
-                frames.timestamp,
-                video_chunks.file_path,
-                frames.offset_index,
-                frames.app_name,
-                ocr_text.ocr_engine
-            FROM 
-                ocr_text
-            JOIN 
-                frames ON ocr_text.frame_id = frames.id
-            JOIN 
-                video_chunks ON frames.video_chunk_id = video_chunks.id
-            WHERE 
-                ocr_text.text LIKE '%' || ?1 || '%'
-                AND (?2 IS NULL OR frames.timestamp >= ?2)
-                AND (?3 IS NULL OR frames.timestamp <= ?3)
-        "#
-        .to_string();
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<(Vec<SearchResult>, Option<SearchCursor>), sqlx::Error> {
+        let query = fts_query(query)?;
+        let query = query.as_deref();
+        let entity = entity.map(normalize_entity);
+        let entity = entity.as_deref();
+        let cursor = cursor.cloned().unwrap_or_default();
+        // One more row than the page tells if there is a next page
+        let fetch = limit + 1;
+        let mut results = Vec::new();
 
-        if app_name.is_some() {
-            sql.push_str(" AND frames.app_name = ?6");
+        // If app_name is specified, only search OCR content
+        if app_name.is_some()
+            || content_type == ContentType::All
+            || content_type == ContentType::OCR
+        {
+            let ocr_results = self
+                .search_ocr(
+                    query,
+                    fetch,
+                    0,
+                    start_time,
+                    end_time,
+                    app_name,
+                    min_confidence,
+                    entity,
+                    engine,
+                    Order::Recent,
+                    cursor.ocr.as_ref(),
+                )
+                .await?;
+            results.extend(ocr_results.into_iter().map(SearchResult::OCR));
+        }
+        if app_name.is_none()
+            && (content_type == ContentType::All || content_type == ContentType::Audio)
+        {
+            let audio_results = self
+                .search_audio(
+                    query,
+                    fetch,
+                    0,
+                    start_time,
+                    end_time,
+                    entity,
+                    engine,
+                    Order::Recent,
+                    cursor.audio.as_ref(),
+                )
+                .await?;
+            results.extend(audio_results.into_iter().map(SearchResult::Audio));
+        }
+        // Entities aren't extracted from clipboard copies and notifications, no engine reads
+        // them
+        let entity_free = entity.is_none() && engine.is_none();
+        if app_name.is_none()
+            && entity_free
+            && (content_type == ContentType::All || content_type == ContentType::Clipboard)
+        {
+            let clipboard_results = self
+                .search_clipboard(
+                    query,
+                    fetch,
+                    0,
+                    start_time,
+                    end_time,
+                    Order::Recent,
+                    cursor.clipboard.as_ref(),
+                )
+                .await?;
+            results.extend(clipboard_results.into_iter().map(SearchResult::Clipboard));
+        }
+        if app_name.is_none()
+            && entity_free
+            && (content_type == ContentType::All || content_type == ContentType::Notification)
+        {
+            let notification_results = self
+                .search_notifications(
+                    query,
+                    fetch,
+                    0,
+                    start_time,
+                    end_time,
+                    Order::Recent,
+                    cursor.notification.as_ref(),
+                )
+                .await?;
+            results.extend(
+                notification_results
+                    .into_iter()
+                    .map(SearchResult::Notification),
+            );
         }
 
-        sql.push_str(
-            r#"
+        sort_by_recency(&mut results);
+        let has_more = results.len() > limit as usize;
+        results.truncate(limit as usize);
+
+        if !has_more {
+            return Ok((results, None));
+        }
+        // Results are sorted, the last of each type is where it continues
+        let mut next = cursor;
+        for result in &results {
+            match result {
+                SearchResult::OCR(ocr) => next.ocr = Some((ocr.sort_timestamp.clone(), ocr.ocr_id)),
+                SearchResult::Audio(audio) => {
+                    next.audio = Some((audio.sort_timestamp.clone(), audio.transcription_id))
+                }
+                SearchResult::Clipboard(clipboard) => {
+                    next.clipboard = Some((clipboard.sort_timestamp.clone(), clipboard.id))
+                }
+                SearchResult::Notification(notification) => {
+                    next.notification =
+                        Some((notification.sort_timestamp.clone(), notification.id))
+                }
+            }
+        }
+        Ok((results, Some(next)))
+    }
+
+    /// OCR results, of `engine` when set, see `ocr_sources`
+    #[allow(clippy::too_many_arguments)]
+    async fn search_ocr(
+        &self,
+        query: Option<&str>,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>, // Add this parameter
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        order: Order,
+        after: Option<&(String, i64)>,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        let (after_timestamp, after_id) = after.cloned().unzip();
+        // Results of several tables are merged, each returns the page with what precedes it
+        let (fetch, skip) = if engine.is_some() {
+            (limit.saturating_add(offset), 0)
+        } else {
+            (limit, offset)
+        };
+        let mut results = Vec::new();
+        for (table, fts, engine_filter) in ocr_sources(engine, "?11") {
+            let sql = r#"
+            SELECT 
+                ocr_text.frame_id,
+                ocr_text.text as ocr_text,
+                ocr_text.text_json,
+                ocr_text.new_text_json_vs_previous_frame,
+                ocr_text.raw_data_output_from_OCR,
+                frames.timestamp,
+                video_chunks.file_path,
+                frames.offset_index,
+                frames.app_name,
+                ocr_text.ocr_engine,
+                ocr_text.confidence,
+                ocr_text.words AS words_json,
+                frames.capture_region AS region_json,
+                frames.timestamp AS sort_timestamp,
+                ocr_text.id AS ocr_id,
+                {relevance} AS relevance,
+                CASE WHEN ?1 IS NULL THEN '' ELSE (
+                    SELECT snippet({fts}, 0, '{start}', '{end}', '{ellipsis}', {tokens})
+                    FROM {fts} WHERE {fts} MATCH ?1 AND rowid = ocr_text.id
+                ) END AS snippet,
+                CASE WHEN ?1 IS NULL THEN '' ELSE (
+                    SELECT highlight({fts}, 0, '{open}', '{close}')
+                    FROM {fts} WHERE {fts} MATCH ?1 AND rowid = ocr_text.id
+                ) END AS highlighted_text
+            FROM 
+                {table} AS ocr_text
+            JOIN 
+                frames ON ocr_text.frame_id = frames.id
+            JOIN 
+                video_chunks ON frames.video_chunk_id = video_chunks.id
+            WHERE 
+                (?1 IS NULL OR ocr_text.id IN (SELECT rowid FROM {fts} WHERE {fts} MATCH ?1))
+                AND (?2 IS NULL OR frames.timestamp >= ?2)
+                AND (?3 IS NULL OR frames.timestamp <= ?3)
+                AND {app_filter}
+                AND (?7 IS NULL OR frames.timestamp < ?7 OR (frames.timestamp = ?7 AND ocr_text.id < ?8))
+                AND (?9 IS NULL OR ocr_text.confidence IS NULL OR ocr_text.confidence >= ?9)
+                AND (?10 IS NULL OR frames.id IN (SELECT frame_id FROM entities WHERE normalized = ?10))
+                AND {engine_filter}
             ORDER BY 
-                frames.timestamp DESC
+                {order_by}
             LIMIT ?4 OFFSET ?5
-            "#,
-        );
-
-        let mut query = sqlx::query_as::<_, OCRResult>(&sql)
-            .bind(query)
-            .bind(start_time)
-            .bind(end_time)
-            .bind(limit)
-            .bind(offset);
+        "#
+            .replace("{start}", HIGHLIGHT_START)
+            .replace("{end}", HIGHLIGHT_END)
+            .replace("{ellipsis}", SNIPPET_ELLIPSIS)
+            .replace("{tokens}", &SNIPPET_TOKENS.to_string())
+            .replace("{open}", HIGHLIGHT_OPEN)
+            .replace("{close}", HIGHLIGHT_CLOSE)
+            .replace("{app_filter}", &app_filter("?1", "?6"))
+            .replace("{engine_filter}", &engine_filter)
+            .replace(
+                "{relevance}",
+                &relevance_sql(fts, "ocr_text.id", "frames.timestamp", order),
+            )
+            .replace("{order_by}", &order.order_by("frames.timestamp", "ocr_text.id"))
+            .replace("{table}", table)
+            .replace("{fts}", fts);
 
-        if let Some(app_name) = app_name {
-            query = query.bind(app_name);
+            results.extend(
+                sqlx::query_as::<_, OCRResult>(&sql)
+                    .bind(query)
+                    .bind(start_time)
+                    .bind(end_time)
+                    .bind(fetch)
+                    .bind(skip)
+                    .bind(app_name)
+                    .bind(&after_timestamp)
+                    .bind(after_id)
+                    .bind(min_confidence)
+                    .bind(entity)
+                    .bind(engine)
+                    .fetch_all(&self.pool)
+                    .await?,
+            );
         }
-
-        query.fetch_all(&self.pool).await
+        if engine.is_some() {
+            results
+                .sort_by(|a, b| (&b.sort_timestamp, b.ocr_id).cmp(&(&a.sort_timestamp, a.ocr_id)));
+            if let Order::Relevance(_) = order {
+                results.sort_by(|a, b| {
+                    b.relevance
+                        .partial_cmp(&a.relevance)
+                        .unwrap_or(Ordering::Equal)
+                });
+            }
+            results = results
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .collect();
+        }
+        for result in &mut results {
+            result.highlights = highlight_offsets(&std::mem::take(&mut result.highlighted_text));
+            result.region = parse_capture_region(result.region_json.take());
+        }
+        Ok(results)
     }
 
+    /// Transcriptions, of `engine` when set, see `audio_sources`
+    #[allow(clippy::too_many_arguments)]
     async fn search_audio(
         &self,
-        query: &str,
+        query: Option<&str>,
         limit: u32,
         offset: u32,
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        order: Order,
+        after: Option<&(String, i64)>,
     ) -> Result<Vec<AudioResult>, sqlx::Error> {
-        sqlx::query_as::<_, AudioResult>(
-            r#"
+        let (after_timestamp, after_id) = after.cloned().unzip();
+        // Results of several tables are merged, each returns the page with what precedes it
+        let (fetch, skip) = if engine.is_some() {
+            (limit.saturating_add(offset), 0)
+        } else {
+            (limit, offset)
+        };
+        let mut results = Vec::new();
+        for (table, fts, engine_filter) in audio_sources(engine, "?9") {
+            let sql = r#"
             SELECT 
                 audio_transcriptions.audio_chunk_id,
                 audio_transcriptions.transcription,
                 audio_transcriptions.timestamp,
                 audio_chunks.file_path,
                 audio_transcriptions.offset_index,
-                audio_transcriptions.transcription_engine
+                audio_transcriptions.transcription_engine,
+                audio_transcriptions.language,
+                COALESCE(audio_transcriptions.segments, '[]') AS segments_json,
+                audio_transcriptions.timestamp AS sort_timestamp,
+                audio_transcriptions.id AS transcription_id,
+                {relevance} AS relevance,
+                CASE WHEN ?1 IS NULL THEN '' ELSE (
+                    SELECT snippet({fts}, 0, '{start}', '{end}', '{ellipsis}', {tokens})
+                    FROM {fts}
+                    WHERE {fts} MATCH ?1 AND rowid = audio_transcriptions.id
+                ) END AS snippet,
+                CASE WHEN ?1 IS NULL THEN '' ELSE (
+                    SELECT highlight({fts}, 0, '{open}', '{close}')
+                    FROM {fts}
+                    WHERE {fts} MATCH ?1 AND rowid = audio_transcriptions.id
+                ) END AS highlighted_text
             FROM 
-                audio_transcriptions
+                {table} AS audio_transcriptions
             JOIN 
                 audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
             WHERE 
-                audio_transcriptions.transcription LIKE '%' || ?1 || '%'
+                (?1 IS NULL OR audio_transcriptions.id IN (SELECT rowid FROM {fts} WHERE {fts} MATCH ?1))
                 AND (?2 IS NULL OR audio_transcriptions.timestamp >= ?2)
                 AND (?3 IS NULL OR audio_transcriptions.timestamp <= ?3)
+                AND (?6 IS NULL OR audio_transcriptions.timestamp < ?6
+                    OR (audio_transcriptions.timestamp = ?6 AND audio_transcriptions.id < ?7))
+                AND (?8 IS NULL OR audio_transcriptions.id IN (SELECT audio_transcription_id FROM entities WHERE normalized = ?8))
+                AND {engine_filter}
             ORDER BY 
-                audio_transcriptions.timestamp DESC
+                {order_by}
+            LIMIT ?4 OFFSET ?5
+        "#
+            .replace("{start}", HIGHLIGHT_START)
+            .replace("{end}", HIGHLIGHT_END)
+            .replace("{ellipsis}", SNIPPET_ELLIPSIS)
+            .replace("{tokens}", &SNIPPET_TOKENS.to_string())
+            .replace("{open}", HIGHLIGHT_OPEN)
+            .replace("{close}", HIGHLIGHT_CLOSE)
+            .replace("{engine_filter}", &engine_filter)
+            .replace(
+                "{relevance}",
+                &relevance_sql(
+                    fts,
+                    "audio_transcriptions.id",
+                    "audio_transcriptions.timestamp",
+                    order,
+                ),
+            )
+            .replace(
+                "{order_by}",
+                &order.order_by("audio_transcriptions.timestamp", "audio_transcriptions.id"),
+            )
+            .replace("{table}", table)
+            .replace("{fts}", fts);
+
+            results.extend(
+                sqlx::query_as::<_, AudioResult>(&sql)
+                    .bind(query)
+                    .bind(start_time)
+                    .bind(end_time)
+                    .bind(fetch)
+                    .bind(skip)
+                    .bind(&after_timestamp)
+                    .bind(after_id)
+                    .bind(entity)
+                    .bind(engine)
+                    .fetch_all(&self.pool)
+                    .await?,
+            );
+        }
+        if engine.is_some() {
+            results.sort_by(|a, b| {
+                (&b.sort_timestamp, b.transcription_id)
+                    .cmp(&(&a.sort_timestamp, a.transcription_id))
+            });
+            if let Order::Relevance(_) = order {
+                results.sort_by(|a, b| {
+                    b.relevance
+                        .partial_cmp(&a.relevance)
+                        .unwrap_or(Ordering::Equal)
+                });
+            }
+            results = results
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .collect();
+        }
+        for result in &mut results {
+            result.highlights = highlight_offsets(&std::mem::take(&mut result.highlighted_text));
+            result.segments = serde_json::from_str(&result.segments_json).unwrap_or_default();
+        }
+        Ok(results)
+    }
+
+    async fn search_clipboard(
+        &self,
+        query: Option<&str>,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        order: Order,
+        after: Option<&(String, i64)>,
+    ) -> Result<Vec<ClipboardResult>, sqlx::Error> {
+        let (after_timestamp, after_id) = after.cloned().unzip();
+        let sql = r#"
+            SELECT
+                clipboard.id,
+                clipboard.text,
+                clipboard.app_name,
+                clipboard.timestamp,
+                clipboard.timestamp AS sort_timestamp,
+                {relevance} AS relevance,
+                CASE WHEN ?1 IS NULL THEN '' ELSE (
+                    SELECT snippet(clipboard_fts, 0, '{start}', '{end}', '{ellipsis}', {tokens})
+                    FROM clipboard_fts WHERE clipboard_fts MATCH ?1 AND rowid = clipboard.id
+                ) END AS snippet,
+                CASE WHEN ?1 IS NULL THEN '' ELSE (
+                    SELECT highlight(clipboard_fts, 0, '{open}', '{close}')
+                    FROM clipboard_fts WHERE clipboard_fts MATCH ?1 AND rowid = clipboard.id
+                ) END AS highlighted_text
+            FROM clipboard
+            WHERE
+                (?1 IS NULL OR clipboard.id IN (SELECT rowid FROM clipboard_fts WHERE clipboard_fts MATCH ?1))
+                AND (?2 IS NULL OR clipboard.timestamp >= ?2)
+                AND (?3 IS NULL OR clipboard.timestamp <= ?3)
+                AND (?6 IS NULL OR clipboard.timestamp < ?6
+                    OR (clipboard.timestamp = ?6 AND clipboard.id < ?7))
+            ORDER BY {order_by}
+            LIMIT ?4 OFFSET ?5
+        "#
+        .replace("{start}", HIGHLIGHT_START)
+        .replace("{end}", HIGHLIGHT_END)
+        .replace("{ellipsis}", SNIPPET_ELLIPSIS)
+        .replace("{tokens}", &SNIPPET_TOKENS.to_string())
+        .replace("{open}", HIGHLIGHT_OPEN)
+        .replace("{close}", HIGHLIGHT_CLOSE)
+        .replace(
+            "{relevance}",
+            &relevance_sql("clipboard_fts", "clipboard.id", "clipboard.timestamp", order),
+        )
+        .replace("{order_by}", &order.order_by("clipboard.timestamp", "clipboard.id"));
+
+        let mut results = sqlx::query_as::<_, ClipboardResult>(&sql)
+            .bind(query)
+            .bind(start_time)
+            .bind(end_time)
+            .bind(limit)
+            .bind(offset)
+            .bind(after_timestamp)
+            .bind(after_id)
+            .fetch_all(&self.pool)
+            .await?;
+        for result in &mut results {
+            result.highlights = highlight_offsets(&std::mem::take(&mut result.highlighted_text));
+        }
+        Ok(results)
+    }
+
+    /// Stores text copied to the clipboard while `app_name` was in front
+    pub async fn insert_clipboard(
+        &self,
+        text: &str,
+        app_name: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let id =
+            sqlx::query("INSERT INTO clipboard (timestamp, text, app_name) VALUES (?1, ?2, ?3)")
+                .bind(timestamp)
+                .bind(text)
+                .bind(app_name)
+                .execute(&self.pool)
+                .await?
+                .last_insert_rowid();
+        Ok(id)
+    }
+
+    async fn search_notifications(
+        &self,
+        query: Option<&str>,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        order: Order,
+        after: Option<&(String, i64)>,
+    ) -> Result<Vec<NotificationResult>, sqlx::Error> {
+        let (after_timestamp, after_id) = after.cloned().unzip();
+        let sql = r#"
+            SELECT
+                notifications.id,
+                notifications.app_name,
+                notifications.title,
+                notifications.body,
+                notifications.timestamp,
+                notifications.timestamp AS sort_timestamp,
+                {relevance} AS relevance,
+                CASE WHEN ?1 IS NULL THEN '' ELSE (
+                    SELECT snippet(notifications_fts, -1, '{start}', '{end}', '{ellipsis}', {tokens})
+                    FROM notifications_fts WHERE notifications_fts MATCH ?1 AND rowid = notifications.id
+                ) END AS snippet,
+                CASE WHEN ?1 IS NULL THEN '' ELSE (
+                    SELECT highlight(notifications_fts, 1, '{open}', '{close}')
+                    FROM notifications_fts WHERE notifications_fts MATCH ?1 AND rowid = notifications.id
+                ) END AS highlighted_text
+            FROM notifications
+            WHERE
+                (?1 IS NULL OR notifications.id IN (SELECT rowid FROM notifications_fts WHERE notifications_fts MATCH ?1))
+                AND (?2 IS NULL OR notifications.timestamp >= ?2)
+                AND (?3 IS NULL OR notifications.timestamp <= ?3)
+                AND (?6 IS NULL OR notifications.timestamp < ?6
+                    OR (notifications.timestamp = ?6 AND notifications.id < ?7))
+            ORDER BY {order_by}
             LIMIT ?4 OFFSET ?5
+        "#
+        .replace("{start}", HIGHLIGHT_START)
+        .replace("{end}", HIGHLIGHT_END)
+        .replace("{ellipsis}", SNIPPET_ELLIPSIS)
+        .replace("{tokens}", &SNIPPET_TOKENS.to_string())
+        .replace("{open}", HIGHLIGHT_OPEN)
+        .replace("{close}", HIGHLIGHT_CLOSE)
+        .replace(
+            "{relevance}",
+            &relevance_sql(
+                "notifications_fts",
+                "notifications.id",
+                "notifications.timestamp",
+                order,
+            ),
+        )
+        .replace(
+            "{order_by}",
+            &order.order_by("notifications.timestamp", "notifications.id"),
+        );
+
+        let mut results = sqlx::query_as::<_, NotificationResult>(&sql)
+            .bind(query)
+            .bind(start_time)
+            .bind(end_time)
+            .bind(limit)
+            .bind(offset)
+            .bind(after_timestamp)
+            .bind(after_id)
+            .fetch_all(&self.pool)
+            .await?;
+        for result in &mut results {
+            result.highlights = highlight_offsets(&std::mem::take(&mut result.highlighted_text));
+        }
+        Ok(results)
+    }
+
+    /// Stores a notification `app_name` showed
+    pub async fn insert_notification(
+        &self,
+        app_name: &str,
+        title: &str,
+        body: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO notifications (timestamp, app_name, title, body) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(timestamp)
+        .bind(app_name)
+        .bind(title)
+        .bind(body)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// Entities named between `start_time` and `end_time`, most mentioned first. `query` keeps
+    /// the ones containing it, case insensitive.
+    pub async fn list_entities(
+        &self,
+        kind: Option<EntityKind>,
+        query: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+    ) -> Result<Vec<EntitySummary>, sqlx::Error> {
+        let rows: Vec<(String, String, String, i64, DateTime<Utc>, DateTime<Utc>)> =
+            sqlx::query_as(
+                r#"
+                SELECT
+                    kind,
+                    (
+                        SELECT latest.value FROM entities AS latest
+                        WHERE latest.kind = entities.kind AND latest.normalized = entities.normalized
+                        ORDER BY latest.id DESC LIMIT 1
+                    ) AS value,
+                    normalized,
+                    COUNT(*) AS mentions,
+                    MIN(timestamp) AS first_seen,
+                    MAX(timestamp) AS last_seen
+                FROM entities
+                WHERE (?1 IS NULL OR kind = ?1)
+                    AND (?2 IS NULL OR instr(normalized, ?2) > 0)
+                    AND (?3 IS NULL OR timestamp >= ?3)
+                    AND (?4 IS NULL OR timestamp <= ?4)
+                GROUP BY kind, normalized
+                ORDER BY mentions DESC, last_seen DESC
+                LIMIT ?5
+                "#,
+            )
+            .bind(kind.map(EntityKind::name))
+            .bind(query.map(normalize_entity))
+            .bind(start_time)
+            .bind(end_time)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(
+                |(kind, value, normalized, mentions, first_seen, last_seen)| {
+                    Some(EntitySummary {
+                        kind: EntityKind::from_name(&kind)?,
+                        value,
+                        normalized,
+                        mentions,
+                        first_seen,
+                        last_seen,
+                    })
+                },
+            )
+            .collect())
+    }
+
+    /// Groups the transcriptions of each machine into sessions split by silences longer than
+    /// `gap`, see `segment_sessions`. The transcriptions written since the last call are read
+    /// by id a page at a time, and joined with the stored sessions within `gap` of them, so
+    /// the ones written late, e.g. imported or transcribed again, land in the right session.
+    /// Returns how many sessions started.
+    pub async fn segment_transcript_sessions(
+        &self,
+        gap: chrono::Duration,
+    ) -> Result<usize, sqlx::Error> {
+        let mut started = 0;
+        loop {
+            let mut tx = self.pool.begin().await?;
+            let last_id: i64 = sqlx::query_scalar(
+                "SELECT last_transcription_id FROM transcript_session_progress WHERE id = 1",
+            )
+            .fetch_optional(&mut *tx)
+            .await?
+            .unwrap_or(0);
+            let rows: Vec<(i64, String, DateTime<Utc>, Option<f64>, String)> = sqlx::query_as(
+                r#"
+                SELECT audio_transcriptions.id, audio_transcriptions.device_id,
+                    audio_transcriptions.timestamp, audio_chunks.duration, audio_chunks.file_path
+                FROM audio_transcriptions
+                JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+                WHERE audio_transcriptions.id > ?1 AND audio_transcriptions.transcription != ''
+                ORDER BY audio_transcriptions.id
+                LIMIT ?2
+                "#,
+            )
+            .bind(last_id)
+            .bind(SESSION_PAGE_SIZE)
+            .fetch_all(&mut *tx)
+            .await?;
+            let Some(&(page_end, ..)) = rows.last() else {
+                break;
+            };
+            let full_page = rows.len() as i64 == SESSION_PAGE_SIZE;
+
+            let mut chunks_by_device: HashMap<String, Vec<TranscribedChunk>> = HashMap::new();
+            for (_, device_id, timestamp, duration, file_path) in rows {
+                chunks_by_device
+                    .entry(device_id)
+                    .or_default()
+                    .push(TranscribedChunk {
+                        timestamp,
+                        duration,
+                        audio_device: audio_device_of(&file_path),
+                    });
+            }
+            for (device_id, mut chunks) in chunks_by_device {
+                chunks.sort_by_key(|chunk| chunk.timestamp);
+                for session in segment_sessions(&chunks, gap) {
+                    let stored: Vec<(i64, DateTime<Utc>, DateTime<Utc>, String)> = sqlx::query_as(
+                        r#"
+                        SELECT id, start_time, end_time, audio_devices FROM transcript_sessions
+                        WHERE device_id = ?1 AND end_time >= ?2 AND start_time <= ?3
+                        ORDER BY start_time
+                        "#,
+                    )
+                    .bind(&device_id)
+                    .bind(session.start_time - gap)
+                    .bind(session.end_time + gap)
+                    .fetch_all(&mut *tx)
+                    .await?;
+                    let (mut start_time, mut end_time) = (session.start_time, session.end_time);
+                    let mut audio_devices: Vec<String> = Vec::new();
+                    for (_, stored_start, stored_end, stored_devices) in &stored {
+                        start_time = start_time.min(*stored_start);
+                        end_time = end_time.max(*stored_end);
+                        audio_devices.extend(
+                            serde_json::from_str::<Vec<String>>(stored_devices).unwrap_or_default(),
+                        );
+                    }
+                    audio_devices.extend(session.audio_devices);
+                    let mut seen = std::collections::HashSet::new();
+                    audio_devices.retain(|device| seen.insert(device.clone()));
+                    let audio_devices = serde_json::to_string(&audio_devices).unwrap_or_default();
+                    // Counted again, a chunk transcribed again has a new transcription
+                    let chunks: i64 = sqlx::query_scalar(
+                        r#"
+                        SELECT COUNT(*) FROM audio_transcriptions
+                        WHERE device_id = ?1 AND timestamp >= ?2 AND timestamp <= ?3
+                            AND transcription != ''
+                        "#,
+                    )
+                    .bind(&device_id)
+                    .bind(start_time)
+                    .bind(end_time)
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                    match stored.split_first() {
+                        Some(((id, ..), merged)) => {
+                            sqlx::query(
+                                r#"
+                                UPDATE transcript_sessions
+                                SET start_time = ?1, end_time = ?2, chunks = ?3, audio_devices = ?4
+                                WHERE id = ?5
+                                "#,
+                            )
+                            .bind(start_time)
+                            .bind(end_time)
+                            .bind(chunks)
+                            .bind(&audio_devices)
+                            .bind(id)
+                            .execute(&mut *tx)
+                            .await?;
+                            // Bridged by the new transcriptions
+                            for (id, ..) in merged {
+                                sqlx::query("DELETE FROM transcript_sessions WHERE id = ?1")
+                                    .bind(id)
+                                    .execute(&mut *tx)
+                                    .await?;
+                            }
+                        }
+                        None => {
+                            sqlx::query(
+                                r#"
+                                INSERT INTO transcript_sessions (device_id, start_time, end_time, chunks, audio_devices)
+                                VALUES (?1, ?2, ?3, ?4, ?5)
+                                "#,
+                            )
+                            .bind(&device_id)
+                            .bind(start_time)
+                            .bind(end_time)
+                            .bind(chunks)
+                            .bind(&audio_devices)
+                            .execute(&mut *tx)
+                            .await?;
+                            started += 1;
+                        }
+                    }
+                }
+            }
+            sqlx::query(
+                r#"
+                INSERT INTO transcript_session_progress (id, last_transcription_id) VALUES (1, ?1)
+                ON CONFLICT (id) DO UPDATE SET last_transcription_id = ?1
+                "#,
+            )
+            .bind(page_end)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            if !full_page {
+                break;
+            }
+        }
+        Ok(started)
+    }
+
+    /// Sessions overlapping `start_time` - `end_time`, most recent first, with their
+    /// transcript. Sessions whose transcriptions were all deleted are left out.
+    pub async fn list_sessions(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+    ) -> Result<Vec<TranscriptSession>, sqlx::Error> {
+        let rows: Vec<(i64, String, DateTime<Utc>, DateTime<Utc>, i64, String)> = sqlx::query_as(
+            r#"
+            SELECT id, device_id, start_time, end_time, chunks, audio_devices
+            FROM transcript_sessions
+            WHERE (?1 IS NULL OR end_time >= ?1)
+                AND (?2 IS NULL OR start_time <= ?2)
+                AND EXISTS (
+                    SELECT 1 FROM audio_transcriptions
+                    WHERE audio_transcriptions.device_id = transcript_sessions.device_id
+                        AND audio_transcriptions.timestamp >= transcript_sessions.start_time
+                        AND audio_transcriptions.timestamp <= transcript_sessions.end_time
+                )
+            ORDER BY start_time DESC
+            LIMIT ?3
             "#,
         )
-        .bind(query)
         .bind(start_time)
         .bind(end_time)
         .bind(limit)
-        .bind(offset)
         .fetch_all(&self.pool)
-        .await
+        .await?;
+        let mut sessions = Vec::with_capacity(rows.len());
+        for (id, device_id, start_time, end_time, chunks, audio_devices) in rows {
+            let transcriptions: Vec<(String,)> = sqlx::query_as(
+                r#"
+                SELECT transcription
+                FROM audio_transcriptions
+                WHERE device_id = ?1 AND timestamp >= ?2 AND timestamp <= ?3
+                    AND transcription != ''
+                ORDER BY timestamp, id
+                "#,
+            )
+            .bind(&device_id)
+            .bind(start_time)
+            .bind(end_time)
+            .fetch_all(&self.pool)
+            .await?;
+            sessions.push(TranscriptSession {
+                id,
+                device_id,
+                start_time,
+                end_time,
+                chunks,
+                audio_devices: serde_json::from_str(&audio_devices).unwrap_or_default(),
+                transcript: transcriptions
+                    .into_iter()
+                    .map(|(transcription,)| transcription)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            });
+        }
+        Ok(sessions)
     }
 
+    /// Video chunk file and offset of a frame recorded by this machine. Ingested frames have
+    /// no file here, see `ingest`.
     pub async fn get_frame(&self, frame_id: i64) -> Result<Option<(String, i64)>, sqlx::Error> {
         sqlx::query_as::<_, (String, i64)>(
             r#"
@@ -553,15 +2734,79 @@ impl DatabaseManager {
             JOIN 
                 video_chunks ON frames.video_chunk_id = video_chunks.id
             WHERE 
-                frames.id = ?1
+                frames.id = ?1 AND video_chunks.device_id = ?2
             "#,
         )
         .bind(frame_id)
+        .bind(LOCAL_DEVICE_ID)
         .fetch_optional(&self.pool)
         .await
     }
 
+    /// File of an audio chunk recorded by this machine, ingested chunks have no file here
+    pub async fn get_audio_chunk_path(&self, chunk_id: i64) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT file_path FROM audio_chunks WHERE id = ?1 AND device_id = ?2")
+            .bind(chunk_id)
+            .bind(LOCAL_DEVICE_ID)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Rewrites a search query so its words also match indexed words within a few typos,
+    /// for OCR misreads like "Iunch" for "lunch". The result is a query for `search` and
+    /// `count_search_results`. Each index gives at most `MAX_VOCAB_TERMS` terms of a length
+    /// close to the word's, so a large vocabulary bounds the work rather than the variants
+    /// found, which may miss terms past them.
+    pub async fn fuzzy_search_query(&self, query: &str) -> Result<String, sqlx::Error> {
+        const MAX_VARIANTS: usize = 16;
+        const MAX_VOCAB_TERMS: i64 = 20_000;
+
+        let words = fuzzy_words(query)?;
+        let mut variants = HashMap::new();
+        for word in words {
+            if variants.contains_key(&word) {
+                continue;
+            }
+            let typos = max_typos(&word);
+            let length = word.chars().count();
+            let terms: Vec<String> = sqlx::query_scalar(
+                r#"
+                SELECT term FROM (
+                    SELECT term FROM ocr_text_fts_vocab
+                    WHERE length(term) BETWEEN ?1 AND ?2 LIMIT ?3
+                )
+                UNION ALL
+                SELECT term FROM (
+                    SELECT term FROM audio_transcriptions_fts_vocab
+                    WHERE length(term) BETWEEN ?1 AND ?2 LIMIT ?3
+                )
+                "#,
+            )
+            .bind((length - typos) as i64)
+            .bind((length + typos) as i64)
+            .bind(MAX_VOCAB_TERMS)
+            .fetch_all(&self.pool)
+            .await?;
+
+            let mut close: Vec<(usize, String)> = terms
+                .into_iter()
+                .filter(|term| term != &word)
+                .map(|term| (edit_distance(&word, &term), term))
+                .filter(|(distance, _)| *distance <= typos)
+                .collect();
+            close.sort();
+            // In both indexes
+            close.dedup();
+            close.truncate(MAX_VARIANTS);
+            debug!("Fuzzy variants of {}: {:?}", word, close);
+            variants.insert(word, close.into_iter().map(|(_, term)| term).collect());
+        }
+
+        Ok(expand_search_query(query, &variants)?)
+    }
+
     // Update the count_search_results method
+    #[allow(clippy::too_many_arguments)]
     pub async fn count_search_results(
         &self,
         query: &str,
@@ -569,33 +2814,153 @@ impl DatabaseManager {
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
         app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
     ) -> Result<usize, sqlx::Error> {
-        let mut total_count = 0;
+        let counts = self
+            .search_counts(
+                query,
+                content_type,
+                start_time,
+                end_time,
+                app_name,
+                min_confidence,
+                entity,
+                engine,
+                false,
+            )
+            .await?;
+        Ok(counts.iter().map(|(_, row)| row.count).sum::<i64>() as usize)
+    }
+
+    /// The results `count_search_results` counts, by app name, UTC day, content type and
+    /// device
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_facets(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<SearchFacets, sqlx::Error> {
+        let counts = self
+            .search_counts(
+                query,
+                content_type,
+                start_time,
+                end_time,
+                app_name,
+                min_confidence,
+                entity,
+                engine,
+                true,
+            )
+            .await?;
+        let mut tally = FacetTally::default();
+        for (content_type, row) in counts {
+            tally.add(
+                content_type,
+                row.app_name.as_deref(),
+                row.day.as_deref().unwrap_or_default(),
+                row.device.as_deref().unwrap_or_default(),
+                row.count,
+            );
+        }
+        Ok(tally.into_facets())
+    }
+
+    /// Counts of the search results of each content type, by app name, day and device when
+    /// `grouped`
+    #[allow(clippy::too_many_arguments)]
+    async fn search_counts(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        grouped: bool,
+    ) -> Result<Vec<(ContentType, CountRow)>, sqlx::Error> {
+        let query = fts_query(query)?;
+        let query = query.as_deref();
+        let entity = entity.map(normalize_entity);
+        let entity = entity.as_deref();
+        let mut counts = Vec::new();
+        let mut add = |content_type: ContentType, rows: Vec<CountRow>| {
+            counts.extend(rows.into_iter().map(|row| (content_type, row)));
+        };
 
         // If app_name is specified, only count OCR results
         if app_name.is_some() {
-            let ocr_count = self
-                .count_ocr_results(query, start_time, end_time, app_name)
+            let ocr_counts = self
+                .count_ocr_results(
+                    query,
+                    start_time,
+                    end_time,
+                    app_name,
+                    min_confidence,
+                    entity,
+                    engine,
+                    grouped,
+                )
                 .await?;
-            total_count += ocr_count;
+            add(ContentType::OCR, ocr_counts);
         } else {
             // If no app_name is specified, proceed with normal counting
             if content_type == ContentType::All || content_type == ContentType::OCR {
-                let ocr_count = self
-                    .count_ocr_results(query, start_time, end_time, None)
+                let ocr_counts = self
+                    .count_ocr_results(
+                        query,
+                        start_time,
+                        end_time,
+                        None,
+                        min_confidence,
+                        entity,
+                        engine,
+                        grouped,
+                    )
                     .await?;
-                total_count += ocr_count;
+                add(ContentType::OCR, ocr_counts);
             }
 
             if content_type == ContentType::All || content_type == ContentType::Audio {
-                let audio_count = self
-                    .count_audio_results(query, start_time, end_time)
+                let audio_counts = self
+                    .count_audio_results(query, start_time, end_time, entity, engine, grouped)
+                    .await?;
+                add(ContentType::Audio, audio_counts);
+            }
+
+            // Entities aren't extracted from clipboard copies and notifications, no engine
+            // reads them
+            let entity_free = entity.is_none() && engine.is_none();
+            if entity_free
+                && (content_type == ContentType::All || content_type == ContentType::Clipboard)
+            {
+                let clipboard_counts = self
+                    .count_clipboard_results(query, start_time, end_time, grouped)
+                    .await?;
+                add(ContentType::Clipboard, clipboard_counts);
+            }
+
+            if entity_free
+                && (content_type == ContentType::All || content_type == ContentType::Notification)
+            {
+                let notification_counts = self
+                    .count_notification_results(query, start_time, end_time, grouped)
                     .await?;
-                total_count += audio_count;
+                add(ContentType::Notification, notification_counts);
             }
         }
 
-        Ok(total_count)
+        Ok(counts)
     }
     pub async fn count_recent_results(
         &self,
@@ -604,111 +2969,1629 @@ impl DatabaseManager {
     ) -> Result<usize, sqlx::Error> {
         let mut total_count = 0;
 
-        let ocr_count: (i64,) = sqlx::query_as(
+        let ocr_count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*)
+            FROM frames
+            JOIN ocr_text ON frames.id = ocr_text.frame_id
+            WHERE 
+                (?1 IS NULL OR frames.timestamp >= ?1)
+                AND (?2 IS NULL OR frames.timestamp <= ?2)
+            "#,
+        )
+        .bind(start_timestamp)
+        .bind(end_timestamp)
+        .fetch_one(&self.pool)
+        .await?;
+
+        total_count += ocr_count.0 as usize;
+
+        let audio_count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*)
+            FROM audio_transcriptions
+            WHERE 
+                (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+            "#,
+        )
+        .bind(start_timestamp)
+        .bind(end_timestamp)
+        .fetch_one(&self.pool)
+        .await?;
+
+        total_count += audio_count.0 as usize;
+
+        Ok(total_count)
+    }
+
+    /// Frames, OCR characters and seconds of audio transcribed between `start` and `end`,
+    /// per bucket of `bucket_secs` aligned on the unix epoch. Empty buckets are included.
+    pub async fn ingest_stats(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket_secs: i64,
+    ) -> Result<Vec<IngestBucket>, sqlx::Error> {
+        let first = start.timestamp().div_euclid(bucket_secs);
+        let last = end.timestamp().div_euclid(bucket_secs);
+        let mut buckets: Vec<IngestBucket> = (first..=last)
+            .map(|bucket| IngestBucket {
+                start: DateTime::from_timestamp(bucket * bucket_secs, 0).unwrap_or_default(),
+                frames: 0,
+                ocr_characters: 0,
+                transcription_seconds: 0.0,
+            })
+            .collect();
+
+        let frames: Vec<(i64, i64, i64)> = sqlx::query_as(
+            r#"
+            SELECT
+                CAST(strftime('%s', frames.timestamp) AS INTEGER) / ?3 AS bucket,
+                COUNT(DISTINCT frames.id),
+                COALESCE(SUM(LENGTH(ocr_text.text)), 0)
+            FROM frames
+            LEFT JOIN ocr_text ON ocr_text.frame_id = frames.id
+            WHERE frames.timestamp >= ?1 AND frames.timestamp <= ?2
+            GROUP BY bucket
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(bucket_secs)
+        .fetch_all(&self.pool)
+        .await?;
+        for (bucket, frame_count, characters) in frames {
+            if let Some(stats) = buckets.get_mut((bucket - first) as usize) {
+                stats.frames = frame_count;
+                stats.ocr_characters = characters;
+            }
+        }
+
+        let audio: Vec<(i64, f64)> = sqlx::query_as(
+            r#"
+            SELECT
+                CAST(strftime('%s', timestamp) AS INTEGER) / ?3 AS bucket,
+                COALESCE(SUM(duration), 0.0)
+            FROM audio_chunks
+            WHERE timestamp >= ?1 AND timestamp <= ?2
+            GROUP BY bucket
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(bucket_secs)
+        .fetch_all(&self.pool)
+        .await?;
+        for (bucket, seconds) in audio {
+            if let Some(stats) = buckets.get_mut((bucket - first) as usize) {
+                stats.transcription_seconds = seconds;
+            }
+        }
+
+        Ok(buckets)
+    }
+
+    /// Frames, seconds of audio and characters of text recorded between `start` and `end`,
+    /// per bucket of `bucket_secs` aligned on the epoch shifted by `utc_offset_secs`, e.g. on
+    /// local midnights. Empty buckets are included.
+    pub async fn heatmap(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket_secs: i64,
+        utc_offset_secs: i64,
+    ) -> Result<Vec<HeatmapBucket>, sqlx::Error> {
+        let mut buckets = heatmap_buckets(start, end, bucket_secs, utc_offset_secs);
+        let first = (start.timestamp() + utc_offset_secs).div_euclid(bucket_secs);
+
+        let frames: Vec<(i64, i64, i64)> = sqlx::query_as(
+            r#"
+            SELECT
+                (CAST(strftime('%s', frames.timestamp) AS INTEGER) + ?4) / ?3 AS bucket,
+                COUNT(DISTINCT frames.id),
+                COALESCE(SUM(LENGTH(ocr_text.text)), 0)
+            FROM frames
+            LEFT JOIN ocr_text ON ocr_text.frame_id = frames.id
+            WHERE frames.timestamp >= ?1 AND frames.timestamp <= ?2
+            GROUP BY bucket
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(bucket_secs)
+        .bind(utc_offset_secs)
+        .fetch_all(&self.pool)
+        .await?;
+        for (bucket, frame_count, characters) in frames {
+            if let Some(heat) = buckets.get_mut((bucket - first) as usize) {
+                heat.frames = frame_count;
+                heat.ocr_characters = characters;
+            }
+        }
+
+        let audio: Vec<(i64, f64, i64)> = sqlx::query_as(
+            r#"
+            SELECT
+                (CAST(strftime('%s', audio_chunks.timestamp) AS INTEGER) + ?4) / ?3 AS bucket,
+                COALESCE(SUM(audio_chunks.duration), 0.0),
+                COALESCE(SUM(transcribed.characters), 0)
+            FROM audio_chunks
+            LEFT JOIN (
+                SELECT audio_chunk_id, SUM(LENGTH(transcription)) AS characters
+                FROM audio_transcriptions
+                GROUP BY audio_chunk_id
+            ) AS transcribed ON transcribed.audio_chunk_id = audio_chunks.id
+            WHERE audio_chunks.timestamp >= ?1 AND audio_chunks.timestamp <= ?2
+            GROUP BY bucket
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(bucket_secs)
+        .bind(utc_offset_secs)
+        .fetch_all(&self.pool)
+        .await?;
+        for (bucket, seconds, characters) in audio {
+            if let Some(heat) = buckets.get_mut((bucket - first) as usize) {
+                heat.audio_seconds = seconds;
+                heat.transcription_characters = characters;
+            }
+        }
+
+        Ok(buckets)
+    }
+
+    /// Every minute from `start` to `end`, aligned on the unix epoch, with what this machine
+    /// recorded during it. Minutes with nothing recorded and no idle event are included.
+    pub async fn timeline(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TimelineMinute>, sqlx::Error> {
+        let first = start.timestamp().div_euclid(60);
+        let last = end.timestamp().div_euclid(60);
+        let mut minutes: Vec<TimelineMinute> = (first..=last)
+            .map(|minute| TimelineMinute {
+                start: DateTime::from_timestamp(minute * 60, 0).unwrap_or_default(),
+                app_name: None,
+                frames: 0,
+                audio: false,
+                idle: false,
+            })
+            .collect();
+        let mut top_app_frames = vec![0; minutes.len()];
+        // Marks the minutes overlapping `from..=to`, in seconds
+        let mut cover = |from: i64, to: i64, mark: fn(&mut TimelineMinute)| {
+            for minute in from.div_euclid(60).max(first)..=to.div_euclid(60).min(last) {
+                mark(&mut minutes[(minute - first) as usize]);
+            }
+        };
+
+        // Audio chunks are inserted once recorded, their timestamp is their end
+        let audio: Vec<(i64, f64)> = sqlx::query_as(
+            r#"
+            SELECT CAST(strftime('%s', timestamp) AS INTEGER), COALESCE(duration, 0.0)
+            FROM audio_chunks
+            WHERE device_id = ?3
+                AND timestamp >= ?1
+                AND CAST(strftime('%s', timestamp) AS INTEGER) - COALESCE(duration, 0.0) <= ?2
+            "#,
+        )
+        .bind(start)
+        .bind(end.timestamp())
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_all(&self.pool)
+        .await?;
+        for (chunk_end, duration) in audio {
+            cover(chunk_end - duration as i64, chunk_end, |minute| {
+                minute.audio = true
+            });
+        }
+
+        let idle: Vec<(i64, f64)> = sqlx::query_as(
+            r#"
+            SELECT CAST(strftime('%s', timestamp) AS INTEGER), duration
+            FROM activity_events
+            WHERE event_type = 'afkstatus'
+                AND json_extract(data, '$.status') = 'afk'
+                AND timestamp <= ?2
+                AND CAST(strftime('%s', timestamp) AS INTEGER) + duration >= ?1
+            "#,
+        )
+        .bind(start.timestamp())
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await?;
+        for (event_start, duration) in idle {
+            cover(event_start, event_start + duration as i64, |minute| {
+                minute.idle = true
+            });
+        }
+
+        let frames: Vec<(i64, String, i64)> = sqlx::query_as(
+            r#"
+            SELECT CAST(strftime('%s', timestamp) AS INTEGER) / 60 AS minute, app_name, COUNT(*)
+            FROM frames
+            WHERE device_id = ?3 AND timestamp >= ?1 AND timestamp <= ?2
+            GROUP BY minute, app_name
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_all(&self.pool)
+        .await?;
+        for (minute, app_name, frame_count) in frames {
+            let index = (minute - first) as usize;
+            let Some(stats) = minutes.get_mut(index) else {
+                continue;
+            };
+            stats.frames += frame_count;
+            if frame_count > top_app_frames[index] && !app_name.is_empty() {
+                top_app_frames[index] = frame_count;
+                stats.app_name = Some(app_name);
+            }
+        }
+
+        Ok(minutes)
+    }
+
+    /// Frames and audio chunks recorded by this machine between `start` and `end`, with
+    /// how many have no text, see `AnomalyDetector`
+    pub async fn ingest_counts(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<IngestCounts, sqlx::Error> {
+        let (frames, empty_frames): (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COUNT(*),
+                COALESCE(SUM(NOT EXISTS (
+                    SELECT 1 FROM ocr_text WHERE ocr_text.frame_id = frames.id AND ocr_text.text != ''
+                )), 0)
+            FROM frames
+            WHERE device_id = ?1 AND timestamp >= ?2 AND timestamp < ?3
+            "#,
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.pool)
+        .await?;
+        let (audio_chunks, silent_chunks): (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COUNT(*),
+                COALESCE(SUM(NOT EXISTS (
+                    SELECT 1 FROM audio_transcriptions
+                    WHERE audio_transcriptions.audio_chunk_id = audio_chunks.id
+                        AND audio_transcriptions.transcription != ''
+                )), 0)
+            FROM audio_chunks
+            WHERE device_id = ?1 AND timestamp >= ?2 AND timestamp < ?3
+            "#,
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(IngestCounts {
+            frames,
+            empty_frames,
+            audio_chunks,
+            silent_chunks,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn count_ocr_results(
+        &self,
+        query: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        grouped: bool,
+    ) -> Result<Vec<CountRow>, sqlx::Error> {
+        let columns = count_columns(
+            grouped,
+            "frames.app_name",
+            "frames.timestamp",
+            "frames.device_id",
+        );
+        let mut counts = Vec::new();
+        for (table, fts, engine_filter) in ocr_sources(engine, "?6") {
+            let mut sql = r#"
+            SELECT {columns}
+            FROM {table} AS ocr_text
+            JOIN frames ON ocr_text.frame_id = frames.id
+            WHERE (?1 IS NULL OR ocr_text.id IN (SELECT rowid FROM {fts} WHERE {fts} MATCH ?1))
+                AND (?2 IS NULL OR frames.timestamp >= ?2)
+                AND (?3 IS NULL OR frames.timestamp <= ?3)
+                AND (?4 IS NULL OR ocr_text.confidence IS NULL OR ocr_text.confidence >= ?4)
+                AND (?5 IS NULL OR frames.id IN (SELECT frame_id FROM entities WHERE normalized = ?5))
+                AND {engine_filter}
+        "#
+            .replace("{columns}", &columns)
+            .replace("{engine_filter}", &engine_filter)
+            .replace("{table}", table)
+            .replace("{fts}", fts);
+
+            if app_name.is_some() {
+                sql.push_str(" AND ");
+                sql.push_str(&app_filter("?1", "?7"));
+            }
+            if grouped {
+                sql.push_str(COUNT_GROUP_BY);
+            }
+
+            let mut count_query = sqlx::query_as::<_, CountRow>(&sql)
+                .bind(query)
+                .bind(start_time)
+                .bind(end_time)
+                .bind(min_confidence)
+                .bind(entity)
+                .bind(engine);
+
+            if let Some(app_name) = app_name {
+                count_query = count_query.bind(app_name);
+            }
+
+            counts.extend(count_query.fetch_all(&self.pool).await?);
+        }
+        Ok(counts)
+    }
+    async fn count_audio_results(
+        &self,
+        query: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        grouped: bool,
+    ) -> Result<Vec<CountRow>, sqlx::Error> {
+        let mut counts = Vec::new();
+        for (table, fts, engine_filter) in audio_sources(engine, "?5") {
+            let mut sql = r#"
+            SELECT {columns}
+            FROM {table} AS audio_transcriptions
+            WHERE (?1 IS NULL OR id IN (SELECT rowid FROM {fts} WHERE {fts} MATCH ?1))
+                AND (?2 IS NULL OR timestamp >= ?2)
+                AND (?3 IS NULL OR timestamp <= ?3)
+                AND (?4 IS NULL OR id IN (SELECT audio_transcription_id FROM entities WHERE normalized = ?4))
+                AND {engine_filter}
+            "#
+            .replace("{columns}", &count_columns(grouped, "NULL", "timestamp", "device_id"))
+            .replace("{engine_filter}", &engine_filter)
+            .replace("{table}", table)
+            .replace("{fts}", fts);
+            if grouped {
+                sql.push_str(COUNT_GROUP_BY);
+            }
+
+            let rows: Vec<CountRow> = sqlx::query_as(&sql)
+                .bind(query)
+                .bind(start_time)
+                .bind(end_time)
+                .bind(entity)
+                .bind(engine)
+                .fetch_all(&self.pool)
+                .await?;
+            counts.extend(rows);
+        }
+        Ok(counts)
+    }
+    async fn count_clipboard_results(
+        &self,
+        query: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        grouped: bool,
+    ) -> Result<Vec<CountRow>, sqlx::Error> {
+        // Copied on this machine
+        let local = format!("'{}'", LOCAL_DEVICE_ID);
+        let mut sql = r#"
+            SELECT {columns}
+            FROM clipboard
+            WHERE (?1 IS NULL OR id IN (SELECT rowid FROM clipboard_fts WHERE clipboard_fts MATCH ?1))
+                AND (?2 IS NULL OR timestamp >= ?2)
+                AND (?3 IS NULL OR timestamp <= ?3)
+            "#
+        .replace("{columns}", &count_columns(grouped, "app_name", "timestamp", &local));
+        if grouped {
+            sql.push_str(COUNT_GROUP_BY);
+        }
+
+        sqlx::query_as(&sql)
+            .bind(query)
+            .bind(start_time)
+            .bind(end_time)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn count_notification_results(
+        &self,
+        query: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        grouped: bool,
+    ) -> Result<Vec<CountRow>, sqlx::Error> {
+        // Shown on this machine
+        let local = format!("'{}'", LOCAL_DEVICE_ID);
+        let mut sql = r#"
+            SELECT {columns}
+            FROM notifications
+            WHERE (?1 IS NULL OR id IN (SELECT rowid FROM notifications_fts WHERE notifications_fts MATCH ?1))
+                AND (?2 IS NULL OR timestamp >= ?2)
+                AND (?3 IS NULL OR timestamp <= ?3)
+            "#
+        .replace("{columns}", &count_columns(grouped, "app_name", "timestamp", &local));
+        if grouped {
+            sql.push_str(COUNT_GROUP_BY);
+        }
+
+        sqlx::query_as(&sql)
+            .bind(query)
+            .bind(start_time)
+            .bind(end_time)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn get_latest_timestamps(
+        &self,
+    ) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>), sqlx::Error> {
+        // Of this machine, data ingested from other devices says nothing about its recording
+        let latest_frame: Option<(DateTime<Utc>,)> = sqlx::query_as(
+            "SELECT timestamp FROM frames WHERE device_id = ?1 ORDER BY timestamp DESC LIMIT 1",
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let latest_audio: Option<(DateTime<Utc>,)> = sqlx::query_as(
+            "SELECT timestamp FROM audio_chunks WHERE device_id = ?1 ORDER BY timestamp DESC LIMIT 1",
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok((latest_frame.map(|f| f.0), latest_audio.map(|a| a.0)))
+    }
+
+    pub async fn get_transcripts(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT timestamp, transcription
+            FROM audio_transcriptions
+            WHERE timestamp >= ?1 AND timestamp < ?2
+            ORDER BY timestamp ASC
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// OCR results of the frames after `frame_id`, oldest first, for live feeds
+    pub async fn get_ocr_after(
+        &self,
+        frame_id: i64,
+        app_name: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        let mut results = sqlx::query_as::<_, OCRResult>(
+            r#"
+            SELECT
+                ocr_text.frame_id,
+                ocr_text.text as ocr_text,
+                ocr_text.text_json,
+                ocr_text.new_text_json_vs_previous_frame,
+                ocr_text.raw_data_output_from_OCR,
+                frames.timestamp,
+                video_chunks.file_path,
+                frames.offset_index,
+                frames.app_name,
+                ocr_text.ocr_engine,
+                ocr_text.confidence,
+                ocr_text.words AS words_json,
+                frames.capture_region AS region_json,
+                frames.timestamp AS sort_timestamp,
+                ocr_text.id AS ocr_id,
+                '' AS snippet,
+                '' AS highlighted_text
+            FROM ocr_text
+            JOIN frames ON ocr_text.frame_id = frames.id
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            WHERE ocr_text.frame_id > ?1 AND (?2 IS NULL OR frames.app_name = ?2)
+            ORDER BY ocr_text.frame_id ASC
+            LIMIT ?3
+            "#,
+        )
+        .bind(frame_id)
+        .bind(app_name)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        for result in &mut results {
+            result.region = parse_capture_region(result.region_json.take());
+        }
+        Ok(results)
+    }
+
+    /// Transcriptions after the one of id `transcription_id`, oldest first, for live feeds
+    pub async fn get_transcriptions_after(
+        &self,
+        transcription_id: i64,
+        limit: u32,
+    ) -> Result<Vec<AudioResult>, sqlx::Error> {
+        let mut results = sqlx::query_as::<_, AudioResult>(
+            r#"
+            SELECT
+                audio_transcriptions.audio_chunk_id,
+                audio_transcriptions.transcription,
+                audio_transcriptions.timestamp,
+                audio_chunks.file_path,
+                audio_transcriptions.offset_index,
+                audio_transcriptions.transcription_engine,
+                audio_transcriptions.language,
+                COALESCE(audio_transcriptions.segments, '[]') AS segments_json,
+                audio_transcriptions.timestamp AS sort_timestamp,
+                audio_transcriptions.id AS transcription_id,
+                '' AS snippet,
+                '' AS highlighted_text
+            FROM audio_transcriptions
+            JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            WHERE audio_transcriptions.id > ?1
+            ORDER BY audio_transcriptions.id ASC
+            LIMIT ?2
+            "#,
+        )
+        .bind(transcription_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        for result in &mut results {
+            result.segments = serde_json::from_str(&result.segments_json).unwrap_or_default();
+        }
+        Ok(results)
+    }
+
+    /// Ids of the last frame with OCR text and of the last transcription, 0 when none
+    pub async fn get_last_content_ids(&self) -> Result<(i64, i64), sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                (SELECT COALESCE(MAX(frame_id), 0) FROM ocr_text),
+                (SELECT COALESCE(MAX(id), 0) FROM audio_transcriptions)
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Most frequently seen OCR texts of the range with the time they were first seen
+    pub async fn get_top_ocr_snippets(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<(DateTime<Utc>, String, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT MIN(frames.timestamp), frames.app_name, ocr_text.text
+            FROM ocr_text
+            JOIN frames ON ocr_text.frame_id = frames.id
+            WHERE frames.timestamp >= ?1 AND frames.timestamp < ?2 AND ocr_text.text != ''
+            GROUP BY ocr_text.text
+            ORDER BY COUNT(*) DESC
+            LIMIT ?3
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Number of frames per app in the range, most used first
+    pub async fn get_app_usage(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT app_name, COUNT(*)
+            FROM frames
+            WHERE timestamp >= ?1 AND timestamp < ?2
+            GROUP BY app_name
+            ORDER BY COUNT(*) DESC
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Events pulled from ActivityWatch between `start` and `end`, oldest first, of the
+    /// bucket type `event_type` when given
+    pub async fn get_activity_events(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        event_type: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ActivityEvent>, sqlx::Error> {
+        let rows: Vec<(i64, String, String, DateTime<Utc>, f64, String)> = sqlx::query_as(
+            r#"
+            SELECT id, bucket_id, event_type, timestamp, duration, data
+            FROM activity_events
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+                AND (?3 IS NULL OR event_type = ?3)
+            ORDER BY timestamp ASC
+            LIMIT ?4
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .bind(event_type)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(id, bucket_id, event_type, timestamp, duration, data)| ActivityEvent {
+                    id,
+                    bucket_id,
+                    event_type,
+                    timestamp,
+                    duration,
+                    data: serde_json::from_str(&data).unwrap_or_default(),
+                },
+            )
+            .collect())
+    }
+
+    /// Inserts ActivityWatch events, replacing the stored ones with the same bucket and
+    /// timestamp. `id` is ignored.
+    pub async fn upsert_activity_events(&self, events: &[ActivityEvent]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for event in events {
+            sqlx::query(
+                r#"
+                INSERT INTO activity_events (bucket_id, event_type, timestamp, duration, data)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT (bucket_id, timestamp)
+                DO UPDATE SET event_type = excluded.event_type, duration = excluded.duration,
+                    data = excluded.data
+                "#,
+            )
+            .bind(&event.bucket_id)
+            .bind(&event.event_type)
+            .bind(event.timestamp)
+            .bind(event.duration)
+            .bind(event.data.to_string())
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn last_activity_event_time(
+        &self,
+        bucket_id: &str,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX(timestamp) FROM activity_events WHERE bucket_id = ?1")
+            .bind(bucket_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn insert_mark(
+        &self,
+        name: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Mark, sqlx::Error> {
+        let id = sqlx::query("INSERT INTO marks (name, timestamp) VALUES (?1, ?2)")
+            .bind(name)
+            .bind(timestamp)
+            .execute(&self.pool)
+            .await?
+            .last_insert_rowid();
+        Ok(Mark {
+            id,
+            name: name.to_string(),
+            timestamp,
+        })
+    }
+
+    /// Marks between `start` and `end`, most recent first, with `query` in their name when
+    /// given, case insensitive
+    pub async fn get_marks(
+        &self,
+        query: Option<&str>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: u32,
+    ) -> Result<Vec<Mark>, sqlx::Error> {
+        let rows: Vec<(i64, String, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT id, name, timestamp
+            FROM marks
+            WHERE (?1 IS NULL OR name LIKE '%' || ?1 || '%')
+                AND (?2 IS NULL OR timestamp >= ?2)
+                AND (?3 IS NULL OR timestamp <= ?3)
+            ORDER BY timestamp DESC
+            LIMIT ?4
+            "#,
+        )
+        .bind(query)
+        .bind(start)
+        .bind(end)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, name, timestamp)| Mark {
+                id,
+                name,
+                timestamp,
+            })
+            .collect())
+    }
+
+    pub async fn get_mark(&self, id: i64) -> Result<Option<Mark>, sqlx::Error> {
+        let row: Option<(i64, String, DateTime<Utc>)> =
+            sqlx::query_as("SELECT id, name, timestamp FROM marks WHERE id = ?1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(row.map(|(id, name, timestamp)| Mark {
+            id,
+            name,
+            timestamp,
+        }))
+    }
+
+    pub async fn insert_event(
+        &self,
+        kind: EventKind,
+        message: &str,
+        data: Option<&serde_json::Value>,
+    ) -> Result<Event, sqlx::Error> {
+        let timestamp = Utc::now();
+        let id = sqlx::query(
+            "INSERT INTO events (timestamp, kind, message, data) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(timestamp)
+        .bind(kind.as_str())
+        .bind(message)
+        .bind(data.map(|data| data.to_string()))
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(Event {
+            id,
+            timestamp,
+            kind,
+            message: message.to_string(),
+            data: data.cloned(),
+        })
+    }
+
+    /// Events of `kinds`, of every kind when empty, between `start` and `end`, most recent
+    /// first
+    pub async fn get_events(
+        &self,
+        kinds: &[EventKind],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Event>, sqlx::Error> {
+        let rows: Vec<(i64, DateTime<Utc>, String, String, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT id, timestamp, kind, message, data
+            FROM events
+            WHERE (?1 = '[]' OR kind IN (SELECT value FROM json_each(?1)))
+                AND (?2 IS NULL OR timestamp >= ?2)
+                AND (?3 IS NULL OR timestamp <= ?3)
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?4 OFFSET ?5
+            "#,
+        )
+        .bind(event_kinds_json(kinds))
+        .bind(start)
+        .bind(end)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            // Kinds of a newer version are skipped
+            .filter_map(|(id, timestamp, kind, message, data)| {
+                Some(Event {
+                    id,
+                    timestamp,
+                    kind: kind.parse().ok()?,
+                    message,
+                    data: data.and_then(|data| serde_json::from_str(&data).ok()),
+                })
+            })
+            .collect())
+    }
+
+    pub async fn count_events(
+        &self,
+        kinds: &[EventKind],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM events
+            WHERE (?1 = '[]' OR kind IN (SELECT value FROM json_each(?1)))
+                AND (?2 IS NULL OR timestamp >= ?2)
+                AND (?3 IS NULL OR timestamp <= ?3)
+            "#,
+        )
+        .bind(event_kinds_json(kinds))
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Remembers that `device`, an audio device or `VISION_CAPTURE`, was started or stopped
+    pub async fn set_capture_state(
+        &self,
+        device: &str,
+        is_running: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO capture_states (device, is_running, updated_at)
+            VALUES (?1, ?2, ?3)
+            ON CONFLICT (device) DO UPDATE SET is_running = ?2, updated_at = ?3
+            "#,
+        )
+        .bind(device)
+        .bind(is_running)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// (device, is_running) of the devices started or stopped before, see `set_capture_state`
+    pub async fn get_capture_states(&self) -> Result<Vec<(String, bool)>, sqlx::Error> {
+        sqlx::query_as("SELECT device, is_running FROM capture_states ORDER BY device ASC")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn insert_rule(&self, rule: &NewRule) -> Result<Rule, sqlx::Error> {
+        let (id, created_at): (i64, DateTime<Utc>) = sqlx::query_as(
+            r#"
+            INSERT INTO rules (name, pattern, is_regex, content_type, webhook_url, cooldown_secs)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            RETURNING id, created_at
+            "#,
+        )
+        .bind(rule.name.trim())
+        .bind(&rule.pattern)
+        .bind(rule.is_regex)
+        .bind(rule.content_type.name())
+        .bind(&rule.webhook_url)
+        .bind(rule.cooldown_secs as i64)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Rule {
+            id,
+            name: rule.name.trim().to_string(),
+            pattern: rule.pattern.clone(),
+            is_regex: rule.is_regex,
+            content_type: rule.content_type,
+            webhook_url: rule.webhook_url.clone(),
+            cooldown_secs: rule.cooldown_secs,
+            created_at,
+        })
+    }
+
+    /// Rules of `POST /rules`, oldest first
+    pub async fn list_rules(&self) -> Result<Vec<Rule>, sqlx::Error> {
+        let rows: Vec<(i64, String, String, bool, String, String, i64, DateTime<Utc>)> =
+            sqlx::query_as(
+                r#"
+                SELECT id, name, pattern, is_regex, content_type, webhook_url, cooldown_secs,
+                    created_at
+                FROM rules
+                ORDER BY id ASC
+                "#,
+            )
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let (id, name, pattern, is_regex, content_type, webhook_url, cooldown, created_at) =
+                    row;
+                Rule {
+                    id,
+                    name,
+                    pattern,
+                    is_regex,
+                    content_type: ContentType::from_name(&content_type).unwrap_or_default(),
+                    webhook_url,
+                    cooldown_secs: cooldown.max(0) as u64,
+                    created_at,
+                }
+            })
+            .collect())
+    }
+
+    /// Whether there was such a rule
+    pub async fn delete_rule(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM rules WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn insert_collection(
+        &self,
+        collection: &NewCollection,
+    ) -> Result<Collection, sqlx::Error> {
+        let now = Utc::now();
+        let tagged_until = collection.pinned.then_some(now);
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO collections (name, filters, pinned, tagged_until, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+            RETURNING id
+            "#,
+        )
+        .bind(collection.name.trim())
+        .bind(serde_json::to_string(&collection.filters).unwrap_or_default())
+        .bind(collection.pinned)
+        .bind(tagged_until)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Collection {
+            id,
+            name: collection.name.trim().to_string(),
+            filters: collection.filters.clone(),
+            pinned: collection.pinned,
+            tagged_until,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Collections of `POST /collections`, oldest first
+    pub async fn list_collections(&self) -> Result<Vec<Collection>, sqlx::Error> {
+        let rows: Vec<CollectionRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM collections ORDER BY id ASC",
+            COLLECTION_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(into_collection).collect())
+    }
+
+    pub async fn get_collection(&self, id: i64) -> Result<Option<Collection>, sqlx::Error> {
+        let row: Option<CollectionRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM collections WHERE id = ?1",
+            COLLECTION_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(into_collection))
+    }
+
+    /// Replaces the name, filters and pin of a collection, `None` without such collection.
+    /// A collection pinned again tags the content arriving from now on, its items are kept.
+    pub async fn update_collection(
+        &self,
+        id: i64,
+        collection: &NewCollection,
+    ) -> Result<Option<Collection>, sqlx::Error> {
+        let row: Option<CollectionRow> = sqlx::query_as(&format!(
+            r#"
+            UPDATE collections
+            SET name = ?2,
+                filters = ?3,
+                tagged_until = CASE WHEN NOT ?4 THEN NULL WHEN pinned THEN tagged_until ELSE ?5 END,
+                pinned = ?4,
+                updated_at = ?5
+            WHERE id = ?1
+            RETURNING {}
+            "#,
+            COLLECTION_COLUMNS
+        ))
+        .bind(id)
+        .bind(collection.name.trim())
+        .bind(serde_json::to_string(&collection.filters).unwrap_or_default())
+        .bind(collection.pinned)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(into_collection))
+    }
+
+    /// Whether there was such a collection, its items go with it
+    pub async fn delete_collection(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM collection_items WHERE collection_id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM collections WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Content tagged with a pinned collection, most recent first
+    pub async fn get_collection_items(
+        &self,
+        id: i64,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<CollectionItem>, sqlx::Error> {
+        #[allow(clippy::type_complexity)]
+        let rows: Vec<(
+            String,
+            i64,
+            DateTime<Utc>,
+            Option<String>,
+            Option<String>,
+            DateTime<Utc>,
+        )> = sqlx::query_as(
+            r#"
+            SELECT
+                items.content_type,
+                items.content_id,
+                items.timestamp,
+                CASE items.content_type
+                    WHEN 'ocr' THEN (SELECT text FROM ocr_text WHERE frame_id = items.content_id LIMIT 1)
+                    WHEN 'audio' THEN COALESCE(
+                        (SELECT transcription FROM audio_transcriptions WHERE id = items.content_id),
+                        (SELECT transcription FROM audio_transcription_versions WHERE id = items.content_id)
+                    )
+                    WHEN 'clipboard' THEN (SELECT text FROM clipboard WHERE id = items.content_id)
+                    WHEN 'notification' THEN (SELECT body FROM notifications WHERE id = items.content_id)
+                END,
+                CASE items.content_type
+                    WHEN 'ocr' THEN (SELECT app_name FROM frames WHERE id = items.content_id)
+                    WHEN 'clipboard' THEN (SELECT app_name FROM clipboard WHERE id = items.content_id)
+                    WHEN 'notification' THEN (SELECT app_name FROM notifications WHERE id = items.content_id)
+                END,
+                items.tagged_at
+            FROM collection_items AS items
+            WHERE items.collection_id = ?1
+            ORDER BY items.timestamp DESC, items.content_id DESC
+            LIMIT ?2 OFFSET ?3
+            "#,
+        )
+        .bind(id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(content_type, content_id, timestamp, text, app_name, tagged_at)| CollectionItem {
+                    content_type: ContentType::from_name(&content_type).unwrap_or_default(),
+                    content_id,
+                    timestamp,
+                    text,
+                    app_name,
+                    tagged_at,
+                },
+            )
+            .collect())
+    }
+
+    /// Tags the content recorded since the last time with the pinned collections it
+    /// matches, returns the items tagged
+    pub async fn tag_pinned_collections(&self) -> Result<usize, sqlx::Error> {
+        let now = Utc::now();
+        let mut tagged = 0;
+        for collection in self.list_collections().await? {
+            let Some(tagged_until) = collection.tagged_until.filter(|_| collection.pinned) else {
+                continue;
+            };
+            // Content is written a little after it's recorded, tagged items are ignored
+            let since = tagged_until - chrono::Duration::seconds(TAGGING_OVERLAP_SECS);
+            match self.tag_collection(&collection, since, now).await {
+                Ok(count) => tagged += count,
+                Err(e) => warn!(
+                    "Failed to tag content with collection {}: {}",
+                    collection.id, e
+                ),
+            }
+        }
+        Ok(tagged)
+    }
+
+    /// Tags the content of `collection` recorded between `since` and `until`
+    async fn tag_collection(
+        &self,
+        collection: &Collection,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<usize, sqlx::Error> {
+        let filters = &collection.filters;
+        let start_time = filters.start_time.map_or(since, |start| start.max(since));
+        let end_time = filters.end_time.map_or(until, |end| end.min(until));
+        let query = match filters.q.as_deref() {
+            Some(q) if filters.fuzzy => self.fuzzy_search_query(q).await?,
+            q => q.unwrap_or_default().to_string(),
+        };
+        let content_types = match filters.content_type.as_slice() {
+            // Outside of the time range of the filters
+            _ if start_time > end_time => Vec::new(),
+            [] => vec![ContentType::All],
+            content_types => content_types.to_vec(),
+        };
+
+        let mut tagged = 0;
+        for content_type in content_types {
+            let mut cursor = None;
+            loop {
+                let (results, next) = self
+                    .search_page(
+                        &query,
+                        content_type,
+                        TAGGING_PAGE_SIZE,
+                        cursor.as_ref(),
+                        Some(start_time),
+                        Some(end_time),
+                        filters.app_name.as_deref(),
+                        filters.min_confidence,
+                        filters.entity.as_deref(),
+                        filters.engine(),
+                    )
+                    .await?;
+                let mut tx = self.pool.begin().await?;
+                for result in &results {
+                    let (content_type, content_id, timestamp) = match result {
+                        SearchResult::OCR(ocr) => (ContentType::OCR, ocr.frame_id, ocr.timestamp),
+                        SearchResult::Audio(audio) => {
+                            (ContentType::Audio, audio.transcription_id, audio.timestamp)
+                        }
+                        SearchResult::Clipboard(clipboard) => {
+                            (ContentType::Clipboard, clipboard.id, clipboard.timestamp)
+                        }
+                        SearchResult::Notification(notification) => (
+                            ContentType::Notification,
+                            notification.id,
+                            notification.timestamp,
+                        ),
+                    };
+                    let inserted = sqlx::query(
+                        r#"
+                        INSERT OR IGNORE INTO collection_items
+                            (collection_id, content_type, content_id, timestamp, tagged_at)
+                        VALUES (?1, ?2, ?3, ?4, ?5)
+                        "#,
+                    )
+                    .bind(collection.id)
+                    .bind(content_type.name())
+                    .bind(content_id)
+                    .bind(timestamp)
+                    .bind(until)
+                    .execute(&mut *tx)
+                    .await?;
+                    tagged += inserted.rows_affected() as usize;
+                }
+                tx.commit().await?;
+                match next {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+        }
+
+        // Unless it was unpinned meanwhile
+        sqlx::query("UPDATE collections SET tagged_until = ?2 WHERE id = ?1 AND pinned")
+            .bind(collection.id)
+            .bind(until)
+            .execute(&self.pool)
+            .await?;
+        Ok(tagged)
+    }
+
+    /// (timestamp, app name) of the frames recorded by this machine, oldest first
+    pub async fn get_app_timeline(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT timestamp, app_name
+            FROM frames
+            WHERE device_id = ?1 AND timestamp > ?2 AND timestamp <= ?3
+            ORDER BY timestamp ASC
+            "#,
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Chunks recorded by this machine, ingested ones have no file here
+    pub async fn list_video_chunks(&self) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, file_path FROM video_chunks WHERE device_id = ?1 ORDER BY id ASC",
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Chunks recorded by this machine, ingested ones have no file here
+    pub async fn list_audio_chunks(&self) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, file_path FROM audio_chunks WHERE device_id = ?1 ORDER BY id ASC",
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// App names of the frames of a video chunk, in frame order
+    /// Records the windows on screen when frame `frame_id` was captured
+    pub async fn insert_frame_windows(
+        &self,
+        frame_id: i64,
+        windows: &[WindowRegion],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        insert_windows(&mut *tx, frame_id, windows).await?;
+        tx.commit().await
+    }
+
+    /// Windows on screen when frame `frame_id` was captured, front to back
+    pub async fn get_frame_windows(
+        &self,
+        frame_id: i64,
+    ) -> Result<Vec<WindowRegion>, sqlx::Error> {
+        let rows: Vec<(String, String, u32, bool, i32, i32, u32, u32, String)> = sqlx::query_as(
+            r#"
+            SELECT app_name, window_name, z_index, visible, x, y, width, height, text
+            FROM frame_windows
+            WHERE frame_id = ?1
+            ORDER BY z_index ASC
+            "#,
+        )
+        .bind(frame_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(app_name, window_name, z_index, visible, x, y, width, height, text)| {
+                    WindowRegion {
+                        app_name,
+                        window_name,
+                        z_index,
+                        visible,
+                        x,
+                        y,
+                        width,
+                        height,
+                        text,
+                    }
+                },
+            )
+            .collect())
+    }
+
+    pub async fn get_frame_app_names(
+        &self,
+        video_chunk_id: i64,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT app_name FROM frames WHERE video_chunk_id = ?1 ORDER BY offset_index ASC",
+        )
+        .bind(video_chunk_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|row| row.0).collect())
+    }
+
+    /// Oldest video chunk recorded by this machine after the one of id `after`, with the
+    /// timestamp of its first frame. The latest chunk is never returned as ffmpeg may still
+    /// be writing it, nor offloaded ones as their local file is a stub.
+    pub async fn get_oldest_video_chunk(
+        &self,
+        after: i64,
+    ) -> Result<Option<(i64, String, Option<DateTime<Utc>>)>, sqlx::Error> {
+        sqlx::query_as(
             r#"
-            SELECT COUNT(*)
-            FROM frames
-            JOIN ocr_text ON frames.id = ocr_text.frame_id
-            WHERE 
-                (?1 IS NULL OR frames.timestamp >= ?1)
-                AND (?2 IS NULL OR frames.timestamp <= ?2)
+            SELECT
+                video_chunks.id,
+                video_chunks.file_path,
+                (SELECT MIN(frames.timestamp) FROM frames WHERE frames.video_chunk_id = video_chunks.id)
+            FROM
+                video_chunks
+            WHERE
+                video_chunks.device_id = ?1
+                AND video_chunks.remote_key IS NULL
+                AND video_chunks.id < (SELECT MAX(id) FROM video_chunks WHERE device_id = ?1)
+                AND video_chunks.id > ?2
+            ORDER BY
+                video_chunks.id ASC
+            LIMIT 1
             "#,
         )
-        .bind(start_timestamp)
-        .bind(end_timestamp)
-        .fetch_one(&self.pool)
-        .await?;
+        .bind(LOCAL_DEVICE_ID)
+        .bind(after)
+        .fetch_optional(&self.pool)
+        .await
+    }
 
-        total_count += ocr_count.0 as usize;
+    /// Oldest audio chunk recorded by this machine after the one of id `after`
+    pub async fn get_oldest_audio_chunk(
+        &self,
+        after: i64,
+    ) -> Result<Option<(i64, String, Option<DateTime<Utc>>)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, file_path, timestamp FROM audio_chunks WHERE device_id = ?1 AND remote_key IS NULL AND id > ?2 ORDER BY id ASC LIMIT 1",
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .bind(after)
+        .fetch_optional(&self.pool)
+        .await
+    }
 
-        let audio_count: (i64,) = sqlx::query_as(
+    /// Number of video chunks recorded by this machine, of those offloaded, and the time
+    /// range of their frames
+    pub async fn get_video_chunk_stats(
+        &self,
+    ) -> Result<(i64, i64, Option<DateTime<Utc>>, Option<DateTime<Utc>>), sqlx::Error> {
+        sqlx::query_as(
             r#"
-            SELECT COUNT(*)
-            FROM audio_transcriptions
-            WHERE 
-                (?1 IS NULL OR timestamp >= ?1)
-                AND (?2 IS NULL OR timestamp <= ?2)
+            SELECT
+                (SELECT COUNT(*) FROM video_chunks WHERE device_id = ?1),
+                (SELECT COUNT(*) FROM video_chunks WHERE device_id = ?1 AND remote_key IS NOT NULL),
+                MIN(frames.timestamp),
+                MAX(frames.timestamp)
+            FROM
+                frames
+                JOIN video_chunks ON video_chunks.id = frames.video_chunk_id
+            WHERE
+                video_chunks.device_id = ?1
             "#,
         )
-        .bind(start_timestamp)
-        .bind(end_timestamp)
+        .bind(LOCAL_DEVICE_ID)
         .fetch_one(&self.pool)
-        .await?;
-
-        total_count += audio_count.0 as usize;
-
-        Ok(total_count)
+        .await
     }
-    async fn count_ocr_results(
-        &self,
-        query: &str,
-        start_time: Option<DateTime<Utc>>,
-        end_time: Option<DateTime<Utc>>,
-        app_name: Option<&str>,
-    ) -> Result<usize, sqlx::Error> {
-        let mut sql = r#"
-            SELECT COUNT(*)
-            FROM ocr_text
-            JOIN frames ON ocr_text.frame_id = frames.id
-            WHERE text LIKE '%' || ?1 || '%'
-                AND (?2 IS NULL OR frames.timestamp >= ?2)
-                AND (?3 IS NULL OR frames.timestamp <= ?3)
-        "#
-        .to_string();
-
-        if app_name.is_some() {
-            sql.push_str(" AND frames.app_name = ?4");
-        }
 
-        let mut query = sqlx::query_as::<_, (i64,)>(&sql)
-            .bind(query)
-            .bind(start_time)
-            .bind(end_time);
+    /// Number of audio chunks recorded by this machine, of those offloaded, and their time range
+    pub async fn get_audio_chunk_stats(
+        &self,
+    ) -> Result<(i64, i64, Option<DateTime<Utc>>, Option<DateTime<Utc>>), sqlx::Error> {
+        sqlx::query_as(
+            "SELECT COUNT(*), COUNT(remote_key), MIN(timestamp), MAX(timestamp) FROM audio_chunks WHERE device_id = ?1",
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .fetch_one(&self.pool)
+        .await
+    }
 
-        if let Some(app_name) = app_name {
-            query = query.bind(app_name);
-        }
+    /// Size of the database file in bytes, free pages included
+    pub async fn get_database_size(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
 
-        let (count,) = query.fetch_one(&self.pool).await?;
-        Ok(count as usize)
+    /// Deletes a video chunk with its frames, their windows, entities, collection items and
+    /// OCR text with its versions
+    pub async fn delete_video_chunk(&self, video_chunk_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "DELETE FROM collection_items WHERE content_type = 'ocr' AND content_id IN (SELECT id FROM frames WHERE video_chunk_id = ?1)",
+        )
+        .bind(video_chunk_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM entities WHERE frame_id IN (SELECT id FROM frames WHERE video_chunk_id = ?1)",
+        )
+        .bind(video_chunk_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM ocr_text WHERE frame_id IN (SELECT id FROM frames WHERE video_chunk_id = ?1)",
+        )
+        .bind(video_chunk_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM ocr_text_versions WHERE frame_id IN (SELECT id FROM frames WHERE video_chunk_id = ?1)",
+        )
+        .bind(video_chunk_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM chunked_text_entries WHERE frame_id IN (SELECT id FROM frames WHERE video_chunk_id = ?1)",
+        )
+        .bind(video_chunk_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM frame_windows WHERE frame_id IN (SELECT id FROM frames WHERE video_chunk_id = ?1)",
+        )
+        .bind(video_chunk_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM frames WHERE video_chunk_id = ?1")
+            .bind(video_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM video_chunks WHERE id = ?1")
+            .bind(video_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
     }
-    async fn count_audio_results(
-        &self,
-        query: &str,
-        start_time: Option<DateTime<Utc>>,
-        end_time: Option<DateTime<Utc>>,
-    ) -> Result<usize, sqlx::Error> {
-        let (count,): (i64,) = sqlx::query_as(
+
+    /// Deletes an audio chunk with its transcriptions, their versions, entities and collection
+    /// items
+    pub async fn delete_audio_chunk(&self, audio_chunk_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
             r#"
-            SELECT COUNT(*)
-            FROM audio_transcriptions
-            WHERE transcription LIKE '%' || ?1 || '%'
-                AND (?2 IS NULL OR timestamp >= ?2)
-                AND (?3 IS NULL OR timestamp <= ?3)
+            DELETE FROM collection_items
+            WHERE content_type = 'audio'
+                AND (content_id IN (SELECT id FROM audio_transcriptions WHERE audio_chunk_id = ?1)
+                    OR content_id IN (SELECT id FROM audio_transcription_versions WHERE audio_chunk_id = ?1))
             "#,
         )
-        .bind(query)
-        .bind(start_time)
-        .bind(end_time)
-        .fetch_one(&self.pool)
+        .bind(audio_chunk_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM entities WHERE audio_transcription_id IN (SELECT id FROM audio_transcriptions WHERE audio_chunk_id = ?1)",
+        )
+        .bind(audio_chunk_id)
+        .execute(&mut *tx)
         .await?;
+        sqlx::query("DELETE FROM audio_transcriptions WHERE audio_chunk_id = ?1")
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM audio_transcription_versions WHERE audio_chunk_id = ?1")
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM chunked_text_entries WHERE audio_chunk_id = ?1")
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM audio_chunks WHERE id = ?1")
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
 
-        Ok(count as usize)
+    /// Video chunks recorded by this machine whose last frame is older than `before`, with
+    /// their key in the offload bucket if already uploaded. The latest chunk is never returned.
+    pub async fn get_video_chunks_to_offload(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<(i64, String, Option<String>)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT video_chunks.id, video_chunks.file_path, video_chunks.remote_key
+            FROM video_chunks
+            WHERE
+                video_chunks.device_id = ?1
+                AND video_chunks.id < (SELECT MAX(id) FROM video_chunks WHERE device_id = ?1)
+                AND (SELECT MAX(frames.timestamp) FROM frames WHERE frames.video_chunk_id = video_chunks.id) < ?2
+            ORDER BY video_chunks.id ASC
+            "#,
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await
     }
-    pub async fn get_latest_timestamps(
+
+    /// Audio chunks recorded by this machine before `before`, with their key in the offload
+    /// bucket if already uploaded
+    pub async fn get_audio_chunks_to_offload(
         &self,
-    ) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>), sqlx::Error> {
-        let latest_frame: Option<(DateTime<Utc>,)> =
-            sqlx::query_as("SELECT timestamp FROM frames ORDER BY timestamp DESC LIMIT 1")
-                .fetch_optional(&self.pool)
-                .await?;
+        before: DateTime<Utc>,
+    ) -> Result<Vec<(i64, String, Option<String>)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, file_path, remote_key FROM audio_chunks WHERE device_id = ?1 AND timestamp < ?2 ORDER BY id ASC",
+        )
+        .bind(LOCAL_DEVICE_ID)
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await
+    }
 
-        let latest_audio: Option<(DateTime<Utc>,)> =
-            sqlx::query_as("SELECT timestamp FROM audio_chunks ORDER BY timestamp DESC LIMIT 1")
-                .fetch_optional(&self.pool)
-                .await?;
+    /// Marks a chunk that can't be read, its frames stay searchable
+    pub async fn mark_video_chunk_corrupt(&self, video_chunk_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE video_chunks SET corrupt = TRUE WHERE id = ?1")
+            .bind(video_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
 
-        Ok((latest_frame.map(|f| f.0), latest_audio.map(|a| a.0)))
+    /// Ids of the chunks of this machine marked with `mark_video_chunk_corrupt`
+    pub async fn list_corrupt_video_chunks(&self) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT id FROM video_chunks WHERE device_id = ?1 AND corrupt")
+            .bind(LOCAL_DEVICE_ID)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn set_video_chunk_remote_key(
+        &self,
+        video_chunk_id: i64,
+        remote_key: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE video_chunks SET remote_key = ?1 WHERE id = ?2")
+            .bind(remote_key)
+            .bind(video_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_audio_chunk_remote_key(
+        &self,
+        audio_chunk_id: i64,
+        remote_key: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE audio_chunks SET remote_key = ?1 WHERE id = ?2")
+            .bind(remote_key)
+            .bind(audio_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
     // Modify the insert_chunked_text method to handle both OCR and audio transcriptions
@@ -863,7 +4746,62 @@ impl Clone for DatabaseManager {
     fn clone(&self) -> Self {
         DatabaseManager {
             pool: self.pool.clone(),
+            writer: self.writer.clone(),
+            #[cfg(feature = "encryption")]
+            encryption_key: self.encryption_key.clone(),
+        }
+    }
+}
+
+/// Most recent first, the order of each content type is kept for equal timestamps
+pub(crate) fn sort_by_recency(results: &mut [SearchResult]) {
+    results.sort_by(|a, b| {
+        let timestamp_a = match a {
+            SearchResult::OCR(ocr) => ocr.timestamp,
+            SearchResult::Audio(audio) => audio.timestamp,
+            SearchResult::Clipboard(clipboard) => clipboard.timestamp,
+            SearchResult::Notification(notification) => notification.timestamp,
+        };
+        let timestamp_b = match b {
+            SearchResult::OCR(ocr) => ocr.timestamp,
+            SearchResult::Audio(audio) => audio.timestamp,
+            SearchResult::Clipboard(clipboard) => clipboard.timestamp,
+            SearchResult::Notification(notification) => notification.timestamp,
+        };
+        timestamp_b.cmp(&timestamp_a)
+    });
+}
+
+/// Most relevant first, most recent first for equal relevance
+pub(crate) fn sort_by_relevance(results: &mut [SearchResult]) {
+    sort_by_recency(results);
+    let relevance = |result: &SearchResult| match result {
+        SearchResult::OCR(ocr) => ocr.relevance,
+        SearchResult::Audio(audio) => audio.relevance,
+        SearchResult::Clipboard(clipboard) => clipboard.relevance,
+        SearchResult::Notification(notification) => notification.relevance,
+    };
+    results.sort_by(|a, b| {
+        relevance(b)
+            .partial_cmp(&relevance(a))
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+pub(crate) fn fts_query(query: &str) -> Result<Option<String>, sqlx::Error> {
+    Ok(parse_search_query(query)?)
+}
+
+/// Message of a search that failed on the query syntax rather than on the database
+pub fn search_syntax_error(error: &sqlx::Error) -> Option<String> {
+    match error {
+        sqlx::Error::Decode(e) => e
+            .downcast_ref::<SearchQueryError>()
+            .map(ToString::to_string),
+        sqlx::Error::Database(e) if e.message().contains("fts5: syntax error") => {
+            Some(format!("Invalid search query: {}", e.message()))
         }
+        _ => None,
     }
 }
 
@@ -882,6 +4820,7 @@ impl ToString for ContentSource {
     }
 }
 
+#[cfg(feature = "integrations")]
 #[async_trait]
 impl FriendWearableDatabase for DatabaseManager {
     async fn get_chunked_data_since_last_request(&self, memory_source: &str, friend_user_id: &str) -> Result<(Vec<String>, i64, i64, DateTime<Utc>, DateTime<Utc>), Box<dyn StdError + Send + Sync>> {
@@ -896,3 +4835,240 @@ impl FriendWearableDatabase for DatabaseManager {
             .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)
     }
 }
+
+#[cfg(feature = "integrations")]
+#[async_trait]
+impl DailyNoteDatabase for DatabaseManager {
+    async fn get_transcripts(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String)>, Box<dyn StdError + Send + Sync>> {
+        self.get_transcripts(start, end)
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)
+    }
+
+    async fn get_top_ocr_snippets(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<(DateTime<Utc>, String, String)>, Box<dyn StdError + Send + Sync>> {
+        self.get_top_ocr_snippets(start, end, limit)
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)
+    }
+
+    async fn get_app_usage(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(String, i64)>, Box<dyn StdError + Send + Sync>> {
+        self.get_app_usage(start, end)
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)
+    }
+}
+
+#[cfg(feature = "integrations")]
+#[async_trait]
+impl DigestDatabase for DatabaseManager {
+    /// `keyword` is searched as a phrase
+    async fn find_mentions(
+        &self,
+        keyword: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<KeywordMention>, Box<dyn StdError + Send + Sync>> {
+        let phrase = format!("\"{}\"", keyword.replace('"', ""));
+        let results = self
+            .search(
+                &phrase,
+                ContentType::All,
+                limit,
+                0,
+                Some(start),
+                Some(end),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)?;
+        let plain = |snippet: &str, text: &str| {
+            let text = if snippet.is_empty() { text } else { snippet };
+            text.replace(HIGHLIGHT_START, "").replace(HIGHLIGHT_END, "")
+        };
+        Ok(results
+            .into_iter()
+            .map(|result| match result {
+                SearchResult::OCR(ocr) => KeywordMention {
+                    timestamp: ocr.timestamp,
+                    text: plain(&ocr.snippet, &ocr.ocr_text),
+                    source: ocr.app_name,
+                },
+                SearchResult::Audio(audio) => KeywordMention {
+                    timestamp: audio.timestamp,
+                    text: plain(&audio.snippet, &audio.transcription),
+                    source: "audio".to_string(),
+                },
+                SearchResult::Clipboard(clipboard) => KeywordMention {
+                    timestamp: clipboard.timestamp,
+                    text: plain(&clipboard.snippet, &clipboard.text),
+                    source: "clipboard".to_string(),
+                },
+                SearchResult::Notification(notification) => KeywordMention {
+                    timestamp: notification.timestamp,
+                    text: plain(&notification.snippet, &notification.body),
+                    source: notification.app_name,
+                },
+            })
+            .collect())
+    }
+
+    async fn count_mentions(
+        &self,
+        keyword: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn StdError + Send + Sync>> {
+        let phrase = format!("\"{}\"", keyword.replace('"', ""));
+        let count = self
+            .count_search_results(
+                &phrase,
+                ContentType::All,
+                Some(start),
+                Some(end),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)?;
+        Ok(count as u64)
+    }
+}
+
+#[cfg(feature = "integrations")]
+impl DatabaseManager {
+    /// Ingests imported rows as another device, so importing twice skips them, with what
+    /// `processors` make of them. They have no media, `origin` stands in for the chunk path.
+    pub(crate) async fn ingest_imported(
+        &self,
+        source: ImportSource,
+        origin: &str,
+        frames: &[ImportedFrame],
+        transcriptions: &[ImportedTranscription],
+        processors: &ContentProcessors,
+    ) -> Result<ImportSummary, Box<dyn StdError + Send + Sync>> {
+        let batch = IngestBatch {
+            device_id: source.device_id(),
+            frames: frames
+                .iter()
+                .map(|frame| IngestFrame {
+                    timestamp: frame.timestamp,
+                    file_path: origin.to_string(),
+                    offset_index: 0,
+                    app_name: frame.app_name.clone(),
+                    text: frame.text.clone(),
+                    text_json: String::new(),
+                    ocr_engine: source.to_string(),
+                    raw_text: None,
+                    entities: Vec::new(),
+                })
+                .collect(),
+            transcriptions: transcriptions
+                .iter()
+                .map(|transcription| IngestTranscription {
+                    timestamp: transcription.timestamp,
+                    file_path: origin.to_string(),
+                    offset_index: 0,
+                    transcription: transcription.text.clone(),
+                    transcription_engine: source.to_string(),
+                    duration: transcription.duration,
+                    language: None,
+                    segments: Vec::new(),
+                    entities: Vec::new(),
+                })
+                .collect(),
+        };
+        let summary = self
+            .ingest(&processors.process_ingest(batch))
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)?;
+        Ok(ImportSummary {
+            frames_inserted: summary.frames_inserted,
+            frames_skipped: summary.frames_skipped,
+            transcriptions_inserted: summary.transcriptions_inserted,
+            transcriptions_skipped: summary.transcriptions_skipped,
+        })
+    }
+}
+
+#[cfg(feature = "integrations")]
+#[async_trait]
+impl ImportDatabase for DatabaseManager {
+    async fn insert_imported(
+        &self,
+        source: ImportSource,
+        origin: &str,
+        frames: &[ImportedFrame],
+        transcriptions: &[ImportedTranscription],
+    ) -> Result<ImportSummary, Box<dyn StdError + Send + Sync>> {
+        self.ingest_imported(
+            source,
+            origin,
+            frames,
+            transcriptions,
+            &ContentProcessors::new(),
+        )
+        .await
+    }
+}
+
+#[cfg(feature = "integrations")]
+#[async_trait]
+impl ActivityWatchDatabase for DatabaseManager {
+    async fn upsert_activity_events(
+        &self,
+        events: &[ActivityWatchEvent],
+    ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+        let events: Vec<ActivityEvent> = events
+            .iter()
+            .map(|event| ActivityEvent {
+                id: 0,
+                bucket_id: event.bucket_id.clone(),
+                event_type: event.event_type.clone(),
+                timestamp: event.timestamp,
+                duration: event.duration,
+                data: event.data.clone(),
+            })
+            .collect();
+        self.upsert_activity_events(&events)
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)
+    }
+
+    async fn last_activity_event_time(
+        &self,
+        bucket_id: &str,
+    ) -> Result<Option<DateTime<Utc>>, Box<dyn StdError + Send + Sync>> {
+        self.last_activity_event_time(bucket_id)
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)
+    }
+
+    async fn get_app_timeline(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String)>, Box<dyn StdError + Send + Sync>> {
+        self.get_app_timeline(start, end)
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn StdError + Send + Sync>)
+    }
+}
@@ -0,0 +1,311 @@
+use crate::db::{capture_region_json, LOCAL_DEVICE_ID};
+use crate::{mean_confidence, Entity, OcrWord, SubsystemError, WriteStats};
+use chrono::{DateTime, Utc};
+use log::{debug, error, warn};
+use screenpipe_vision::{CaptureRegion, WindowRegion};
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+
+/// Rows written in one transaction at most
+pub(crate) const WRITE_BATCH_ROWS: usize = 100;
+/// Buffered rows are written at least this often
+pub(crate) const WRITE_BATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// Writes waiting for the writer at most, pushing more waits until it catches up
+pub(crate) const WRITE_QUEUE_SIZE: usize = 1_000;
+
+/// A captured frame with its OCR text
+pub(crate) struct PendingFrame {
+    pub app_name: String,
+    pub timestamp: DateTime<Utc>,
+    pub text: String,
+    pub raw_text: Option<String>,
+    pub text_json: String,
+    pub new_text_json_vs_previous_frame: String,
+    pub raw_data_output_from_ocr: String,
+    pub ocr_engine: String,
+    pub words: Vec<OcrWord>,
+    pub entities: Vec<Entity>,
+    pub windows: Vec<WindowRegion>,
+    pub region: Option<CaptureRegion>,
+}
+
+pub(crate) struct PendingTranscription {
+    pub audio_chunk_id: i64,
+    pub transcription: String,
+    pub offset_index: i64,
+    pub timestamp: DateTime<Utc>,
+    pub transcription_engine: String,
+    pub language: Option<String>,
+    /// Timing of the transcription in its chunk, JSON
+    pub segments: Option<String>,
+    pub entities: Vec<Entity>,
+}
+
+pub(crate) enum PendingWrite {
+    Frame(PendingFrame),
+    Transcription(PendingTranscription),
+}
+
+enum Command {
+    Write(PendingWrite),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Buffers inserts of the recording loops and writes them in batches, one transaction
+/// every `max_rows` rows or `interval`, instead of a transaction per row. At most
+/// `WRITE_QUEUE_SIZE` writes wait, so a slow disk slows the recording down instead of
+/// filling the memory.
+#[derive(Clone)]
+pub(crate) struct BatchWriter {
+    sender: mpsc::Sender<Command>,
+    stats: Arc<Mutex<WriteStats>>,
+}
+
+impl BatchWriter {
+    pub fn spawn(pool: SqlitePool, max_rows: usize, interval: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel(WRITE_QUEUE_SIZE);
+        let stats = Arc::new(Mutex::new(WriteStats::default()));
+        tokio::spawn(run(pool, receiver, max_rows, interval, stats.clone()));
+        BatchWriter { sender, stats }
+    }
+
+    /// Latency of the last batch written and the last write error
+    pub fn stats(&self) -> WriteStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Waits while the queue is full
+    pub async fn push(&self, write: PendingWrite) {
+        if self.sender.send(Command::Write(write)).await.is_err() {
+            error!("Database writer stopped, dropping write");
+        }
+    }
+
+    /// Waits until everything pushed before is written
+    pub async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        if self.sender.send(Command::Flush(done)).await.is_ok() {
+            let _ = written.await;
+        }
+    }
+}
+
+async fn run(
+    pool: SqlitePool,
+    mut receiver: mpsc::Receiver<Command>,
+    max_rows: usize,
+    interval: Duration,
+    stats: Arc<Mutex<WriteStats>>,
+) {
+    let mut buffer = Vec::new();
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            command = receiver.recv() => match command {
+                Some(Command::Write(write)) => {
+                    buffer.push(write);
+                    if buffer.len() >= max_rows {
+                        write_batch(&pool, &mut buffer, &stats).await;
+                    }
+                }
+                Some(Command::Flush(done)) => {
+                    write_batch(&pool, &mut buffer, &stats).await;
+                    let _ = done.send(());
+                }
+                // Every DatabaseManager is gone
+                None => {
+                    write_batch(&pool, &mut buffer, &stats).await;
+                    break;
+                }
+            },
+            _ = ticker.tick() => write_batch(&pool, &mut buffer, &stats).await,
+        }
+    }
+}
+
+async fn write_batch(pool: &SqlitePool, buffer: &mut Vec<PendingWrite>, stats: &Mutex<WriteStats>) {
+    if buffer.is_empty() {
+        return;
+    }
+    let writes = std::mem::take(buffer);
+    let started = Instant::now();
+    let result = insert_all(pool, &writes).await;
+    stats.lock().unwrap().latency = Some(started.elapsed());
+    match result {
+        Ok(()) => debug!("Wrote batch of {} rows", writes.len()),
+        Err(e) => {
+            // Don't lose the whole batch to one bad row
+            warn!(
+                "Failed to write batch of {} rows: {}, writing them one by one",
+                writes.len(),
+                e
+            );
+            for write in &writes {
+                if let Err(e) = insert_all(pool, std::slice::from_ref(write)).await {
+                    error!("Failed to write row, dropping it: {}", e);
+                    stats.lock().unwrap().last_error = Some(SubsystemError {
+                        message: format!("Failed to write row, dropping it: {}", e),
+                        at: Utc::now(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+async fn insert_all(pool: &SqlitePool, writes: &[PendingWrite]) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for write in writes {
+        match write {
+            PendingWrite::Frame(frame) => {
+                insert_frame(&mut *tx, frame).await?;
+            }
+            PendingWrite::Transcription(transcription) => {
+                let transcription_id = sqlx::query(
+                    "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine, language, segments) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )
+                .bind(transcription.audio_chunk_id)
+                .bind(&transcription.transcription)
+                .bind(transcription.offset_index)
+                .bind(transcription.timestamp)
+                .bind(&transcription.transcription_engine)
+                .bind(&transcription.language)
+                .bind(&transcription.segments)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid();
+                insert_entities(
+                    &mut *tx,
+                    EntitySource::Transcription(transcription_id),
+                    transcription.timestamp,
+                    &transcription.entities,
+                )
+                .await?;
+            }
+        }
+    }
+    tx.commit().await
+}
+
+/// Id of the inserted frame, `None` when it was dropped for lack of a video chunk
+pub(crate) async fn insert_frame(
+    tx: &mut SqliteConnection,
+    frame: &PendingFrame,
+) -> Result<Option<i64>, sqlx::Error> {
+    // Frames go to the most recent local video chunk, like DatabaseManager::insert_frame
+    let video_chunk_id: Option<i64> = sqlx::query_scalar(
+        "SELECT id FROM video_chunks WHERE device_id = ?1 ORDER BY id DESC LIMIT 1",
+    )
+    .bind(LOCAL_DEVICE_ID)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(video_chunk_id) = video_chunk_id else {
+        debug!("No video chunk found, dropping frame");
+        return Ok(None);
+    };
+
+    let offset_index: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(MAX(offset_index), -1) + 1 FROM frames WHERE video_chunk_id = ?1",
+    )
+    .bind(video_chunk_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let frame_id = sqlx::query(
+        "INSERT INTO frames (video_chunk_id, offset_index, timestamp, app_name, capture_region) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )
+    .bind(video_chunk_id)
+    .bind(offset_index)
+    .bind(frame.timestamp)
+    .bind(&frame.app_name)
+    .bind(capture_region_json(frame.region.as_ref()))
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    sqlx::query("INSERT INTO ocr_text (frame_id, text, text_json, new_text_json_vs_previous_frame, raw_data_output_from_OCR, app_name, ocr_engine, raw_text, confidence, words) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)")
+        .bind(frame_id)
+        .bind(&frame.text)
+        .bind(&frame.text_json)
+        .bind(&frame.new_text_json_vs_previous_frame)
+        .bind(&frame.raw_data_output_from_ocr)
+        .bind(&frame.app_name)
+        .bind(&frame.ocr_engine)
+        .bind(&frame.raw_text)
+        .bind(mean_confidence(&frame.words))
+        .bind(words_json(&frame.words))
+        .execute(&mut *tx)
+        .await?;
+    insert_windows(tx, frame_id, &frame.windows).await?;
+    insert_entities(
+        tx,
+        EntitySource::Frame(frame_id),
+        frame.timestamp,
+        &frame.entities,
+    )
+    .await?;
+    Ok(Some(frame_id))
+}
+
+/// Row `entities` are linked to
+pub(crate) enum EntitySource {
+    Frame(i64),
+    Transcription(i64),
+}
+
+pub(crate) async fn insert_entities(
+    conn: &mut SqliteConnection,
+    source: EntitySource,
+    timestamp: DateTime<Utc>,
+    entities: &[Entity],
+) -> Result<(), sqlx::Error> {
+    let (frame_id, transcription_id) = match source {
+        EntitySource::Frame(id) => (Some(id), None),
+        EntitySource::Transcription(id) => (None, Some(id)),
+    };
+    for entity in entities {
+        sqlx::query("INSERT INTO entities (frame_id, audio_transcription_id, kind, value, normalized, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6)")
+            .bind(frame_id)
+            .bind(transcription_id)
+            .bind(entity.kind.name())
+            .bind(&entity.value)
+            .bind(&entity.normalized)
+            .bind(timestamp)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// `words` as stored in `ocr_text.words`, see [`OcrWord`], `None` without words
+pub(crate) fn words_json(words: &[OcrWord]) -> Option<String> {
+    if words.is_empty() {
+        return None;
+    }
+    serde_json::to_string(words).ok()
+}
+
+pub(crate) async fn insert_windows(
+    conn: &mut SqliteConnection,
+    frame_id: i64,
+    windows: &[WindowRegion],
+) -> Result<(), sqlx::Error> {
+    for window in windows {
+        sqlx::query("INSERT INTO frame_windows (frame_id, app_name, window_name, z_index, visible, x, y, width, height, text) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)")
+            .bind(frame_id)
+            .bind(&window.app_name)
+            .bind(&window.window_name)
+            .bind(window.z_index)
+            .bind(window.visible)
+            .bind(window.x)
+            .bind(window.y)
+            .bind(window.width)
+            .bind(window.height)
+            .bind(&window.text)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
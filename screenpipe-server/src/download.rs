@@ -0,0 +1,336 @@
+#[cfg(feature = "encryption")]
+use crate::encryption::{decrypted_copy, EncryptionKey};
+use crate::tiering::fetch_offloaded;
+use crate::BackupLocation;
+use axum::body::Bytes;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use futures::channel::mpsc;
+use futures::{SinkExt, Stream};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+/// Archives stay below the 4 GiB of a zip without Zip64 extensions
+const MAX_ARCHIVE_BYTES: u64 = 3 << 30;
+const READ_BUFFER_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum MediaKind {
+    Frame,
+    Audio,
+}
+
+impl MediaKind {
+    fn directory(self) -> &'static str {
+        match self {
+            MediaKind::Frame => "video",
+            MediaKind::Audio => "audio",
+        }
+    }
+}
+
+/// A frame or audio chunk of a `/download`, an entry of the manifest of the archive
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct DownloadItem {
+    #[serde(rename = "type")]
+    pub kind: MediaKind,
+    /// Frame id or audio chunk id
+    pub id: i64,
+    /// Path of the media in the archive, frames of the same video chunk share it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Frame in the video chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset_index: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Why the media isn't in the archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// File on disk, `None` when there is no such frame or chunk
+    #[serde(skip)]
+    pub source: Option<String>,
+}
+
+impl DownloadItem {
+    pub fn new(kind: MediaKind, id: i64, source: Option<String>) -> Self {
+        DownloadItem {
+            kind,
+            id,
+            file: None,
+            offset_index: None,
+            timestamp: None,
+            error: source.is_none().then(|| "Not found".to_string()),
+            source,
+        }
+    }
+}
+
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    header_offset: u32,
+}
+
+/// Zip written front to back, so it can be streamed without seeking: files are stored
+/// as is, their CRC and size follow them in a data descriptor. Video and audio are
+/// compressed already.
+struct ZipStreamWriter {
+    offset: u64,
+    entries: Vec<ZipEntry>,
+    /// Modification time of every entry, in MS-DOS format
+    dos_time: u16,
+    dos_date: u16,
+}
+
+impl ZipStreamWriter {
+    /// Stored, sizes in the data descriptor, UTF-8 names
+    const FLAGS: u16 = 0x0808;
+    const VERSION: u16 = 20;
+
+    fn new(time: DateTime<Utc>) -> Self {
+        ZipStreamWriter {
+            offset: 0,
+            entries: Vec::new(),
+            dos_time: ((time.hour() << 11) | (time.minute() << 5) | (time.second() / 2)) as u16,
+            dos_date: (((time.year() - 1980).max(0) as u32) << 9 | time.month() << 5 | time.day())
+                as u16,
+        }
+    }
+
+    /// Local header of the next file, its content follows
+    fn start_file(&mut self, name: &str) -> Vec<u8> {
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&Self::VERSION.to_le_bytes());
+        header.extend_from_slice(&Self::FLAGS.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(&self.dos_time.to_le_bytes());
+        header.extend_from_slice(&self.dos_date.to_le_bytes());
+        // CRC and sizes, in the data descriptor
+        header.extend_from_slice(&[0; 12]);
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        self.entries.push(ZipEntry {
+            name: name.to_string(),
+            crc: 0,
+            size: 0,
+            header_offset: self.offset as u32,
+        });
+        self.offset += header.len() as u64;
+        header
+    }
+
+    /// Data descriptor of the file started last, once its `size` bytes are written
+    fn end_file(&mut self, crc: u32, size: u64) -> Vec<u8> {
+        let entry = self.entries.last_mut().expect("no file started");
+        entry.crc = crc;
+        entry.size = size as u32;
+        let mut descriptor = Vec::with_capacity(16);
+        descriptor.extend_from_slice(&0x08074b50u32.to_le_bytes());
+        descriptor.extend_from_slice(&crc.to_le_bytes());
+        descriptor.extend_from_slice(&(size as u32).to_le_bytes());
+        descriptor.extend_from_slice(&(size as u32).to_le_bytes());
+        self.offset += size + descriptor.len() as u64;
+        descriptor
+    }
+
+    /// Central directory and its end record, closing the archive
+    fn finish(self) -> Vec<u8> {
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            directory.extend_from_slice(&Self::VERSION.to_le_bytes());
+            directory.extend_from_slice(&Self::VERSION.to_le_bytes());
+            directory.extend_from_slice(&Self::FLAGS.to_le_bytes());
+            directory.extend_from_slice(&0u16.to_le_bytes());
+            directory.extend_from_slice(&self.dos_time.to_le_bytes());
+            directory.extend_from_slice(&self.dos_date.to_le_bytes());
+            directory.extend_from_slice(&entry.crc.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            // Extra field, comment, disk, internal and external attributes
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&entry.header_offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        end.extend_from_slice(&(self.offset as u32).to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&end);
+        directory
+    }
+}
+
+/// Opens the media of an item with its size, or why it can't be added. An encrypted chunk
+/// is read from a decrypted copy, removed once open.
+async fn open_media(
+    path: &str,
+    offload: Option<&BackupLocation>,
+    #[cfg(feature = "encryption")] key: Option<&EncryptionKey>,
+) -> Result<(File, u64), String> {
+    fetch_offloaded(offload, path)
+        .await
+        .map_err(|e| format!("Failed to fetch it back: {}", e))?;
+    let path = Path::new(path);
+    #[cfg(feature = "encryption")]
+    let copy = match key {
+        Some(key) => {
+            let (source, key) = (path.to_path_buf(), key.clone());
+            tokio::task::spawn_blocking(move || decrypted_copy(&source, &key))
+                .await
+                .map_err(|e| format!("Failed to decrypt: {}", e))?
+                .map_err(|e| format!("Failed to decrypt: {}", e))?
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "encryption"))]
+    let copy: Option<std::path::PathBuf> = None;
+    let file = File::open(copy.as_deref().unwrap_or(path))
+        .await
+        .map_err(|e| format!("Failed to open: {}", e))?;
+    // Removed, the archive reads the open file
+    drop(copy);
+    let size = file
+        .metadata()
+        .await
+        .map_err(|e| format!("Failed to open: {}", e))?
+        .len();
+    Ok((file, size))
+}
+
+/// Name in the archive not taken yet, e.g. `video/monitor_1_2024-08-01.mp4`
+fn archive_name(kind: MediaKind, source: &str, taken: &mut HashSet<String>) -> String {
+    let file_name = Path::new(source)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "media".to_string());
+    let mut name = format!("{}/{}", kind.directory(), file_name);
+    let mut n = 1;
+    while taken.contains(&name) {
+        n += 1;
+        name = format!("{}/{}_{}", kind.directory(), n, file_name);
+    }
+    taken.insert(name.clone());
+    name
+}
+
+type Sender = mpsc::Sender<Result<Bytes, std::io::Error>>;
+
+async fn write_archive(
+    mut items: Vec<DownloadItem>,
+    offload: Option<Arc<BackupLocation>>,
+    #[cfg(feature = "encryption")] key: Option<EncryptionKey>,
+    mut tx: Sender,
+) -> Result<(), mpsc::SendError> {
+    let mut zip = ZipStreamWriter::new(Utc::now());
+    let mut taken = HashSet::new();
+    // Frames of the same chunk point to one copy of it
+    let mut added: HashMap<String, String> = HashMap::new();
+    let mut buffer = vec![0; READ_BUFFER_BYTES];
+
+    for item in &mut items {
+        let Some(source) = item.source.clone() else {
+            continue;
+        };
+        if let Some(name) = added.get(&source) {
+            item.file = Some(name.clone());
+            continue;
+        }
+        let media = open_media(
+            &source,
+            offload.as_deref(),
+            #[cfg(feature = "encryption")]
+            key.as_ref(),
+        )
+        .await;
+        let (file, size) = match media {
+            Ok(media) => media,
+            Err(reason) => {
+                item.error = Some(reason);
+                continue;
+            }
+        };
+        if zip.offset + size > MAX_ARCHIVE_BYTES {
+            item.error = Some("Archive size limit reached".to_string());
+            continue;
+        }
+
+        let name = archive_name(item.kind, &source, &mut taken);
+        tx.send(Ok(Bytes::from(zip.start_file(&name)))).await?;
+        // The size at opening, a chunk still being recorded is cut there
+        let mut reader = file.take(size);
+        let mut hasher = crc32fast::Hasher::new();
+        let mut written = 0u64;
+        loop {
+            match reader.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(read) => {
+                    hasher.update(&buffer[..read]);
+                    written += read as u64;
+                    tx.send(Ok(Bytes::copy_from_slice(&buffer[..read]))).await?;
+                }
+                Err(e) => {
+                    item.error = Some(format!("Failed to read after {} bytes: {}", written, e));
+                    break;
+                }
+            }
+        }
+        tx.send(Ok(Bytes::from(zip.end_file(hasher.finalize(), written))))
+            .await?;
+        item.file = Some(name.clone());
+        added.insert(source, name);
+    }
+
+    let manifest = serde_json::to_vec_pretty(&json!({
+        "generated_at": Utc::now(),
+        "items": items,
+    }))
+    .unwrap_or_default();
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&manifest);
+    tx.send(Ok(Bytes::from(zip.start_file("manifest.json"))))
+        .await?;
+    let size = manifest.len() as u64;
+    tx.send(Ok(Bytes::from(manifest))).await?;
+    tx.send(Ok(Bytes::from(zip.end_file(hasher.finalize(), size))))
+        .await?;
+    tx.send(Ok(Bytes::from(zip.finish()))).await
+}
+
+/// Streams a zip of the media of `items` with a `manifest.json` of what each item is and
+/// where it is in the archive. Offloaded chunks are fetched back from `offload`, encrypted
+/// ones are decrypted with `key`. Media that can't be added, e.g. deleted or failing to be
+/// fetched back, is listed in the manifest with the reason instead of failing the download.
+pub(crate) fn download_stream(
+    items: Vec<DownloadItem>,
+    offload: Option<Arc<BackupLocation>>,
+    #[cfg(feature = "encryption")] key: Option<EncryptionKey>,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+    let (tx, rx) = mpsc::channel(4);
+    tokio::spawn(async move {
+        // Fails once the client is gone, nothing left to do
+        let _ = write_archive(
+            items,
+            offload,
+            #[cfg(feature = "encryption")]
+            key,
+            tx,
+        )
+        .await;
+    });
+    rx
+}
@@ -0,0 +1,345 @@
+use crate::storage::{AudioRecord, FrameRecord, Storage};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, Result};
+use argon2::Argon2;
+use async_trait::async_trait;
+use log::{debug, error, info};
+use rand::RngCore;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tempfile::TempPath;
+
+/// Start of the chunk files encrypted by screenpipe, followed by the nonce and the ciphertext
+const MAGIC: &[u8] = b"SCREENPIPE-ENC1\0";
+const NONCE_LEN: usize = 12;
+/// Start of a plaintext SQLite database
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+/// Argon2 salt of the passphrase, in the data directory
+const SALT_FILE: &str = "encryption.salt";
+/// Directory of the decrypted copies of the chunks, next to them
+const DECRYPTED_DIR: &str = ".decrypted";
+const KEYCHAIN_SERVICE: &str = "screenpipe";
+const KEYCHAIN_USER: &str = "encryption-key";
+
+/// 256-bit key of the database (SQLCipher) and of the video/audio chunks (AES-256-GCM)
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+impl EncryptionKey {
+    pub fn from_bytes(key: [u8; 32]) -> Self {
+        EncryptionKey(key)
+    }
+
+    /// Derived with Argon2id from `passphrase` and a random salt kept in `data_dir`,
+    /// created on first use. Losing the passphrase or the salt loses the recordings.
+    pub fn from_passphrase(passphrase: &str, data_dir: &Path) -> Result<Self> {
+        let salt_path = data_dir.join(SALT_FILE);
+        let salt = match fs::read(&salt_path) {
+            Ok(salt) => salt,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let mut salt = vec![0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                fs::write(&salt_path, &salt)?;
+                salt
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| anyhow!("Failed to derive encryption key: {}", e))?;
+        Ok(EncryptionKey(key))
+    }
+
+    /// Random key kept in the OS keychain (macOS Keychain, Windows Credential Manager,
+    /// Secret Service on Linux), created on first use
+    pub fn from_keychain() -> Result<Self> {
+        let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_USER)?;
+        match entry.get_password() {
+            Ok(hex) => {
+                let bytes = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or(""), 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .map_err(|_| anyhow!("Invalid encryption key in the OS keychain"))?;
+                let key = bytes
+                    .try_into()
+                    .map_err(|_| anyhow!("Invalid encryption key in the OS keychain"))?;
+                Ok(EncryptionKey(key))
+            }
+            Err(keyring::Error::NoEntry) => {
+                let mut key = [0u8; 32];
+                OsRng.fill_bytes(&mut key);
+                entry.set_password(&to_hex(&key))?;
+                info!("Created a new encryption key in the OS keychain");
+                Ok(EncryptionKey(key))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Value of `PRAGMA key`, the raw key so SQLCipher doesn't derive it again
+    pub(crate) fn sqlcipher_key(&self) -> String {
+        format!("\"x'{}'\"", to_hex(&self.0))
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn starts_with(path: &Path, prefix: &[u8]) -> std::io::Result<bool> {
+    let mut start = vec![0u8; prefix.len()];
+    match File::open(path)?.read_exact(&mut start) {
+        Ok(()) => Ok(start == prefix),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether a chunk file was encrypted by `encrypt_file`
+pub fn is_encrypted(path: &Path) -> std::io::Result<bool> {
+    starts_with(path, MAGIC)
+}
+
+/// Encrypts a chunk file in place, through a temporary file so a crash never leaves it
+/// half written. Does nothing to an already encrypted file.
+pub fn encrypt_file(path: &Path, key: &EncryptionKey) -> Result<()> {
+    if is_encrypted(path)? {
+        return Ok(());
+    }
+    let plaintext = fs::read(path)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = key
+        .cipher()
+        .encrypt(&nonce, plaintext.as_ref())
+        .map_err(|_| anyhow!("Failed to encrypt {}", path.display()))?;
+
+    let mut encrypted = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    encrypted.extend_from_slice(MAGIC);
+    encrypted.extend_from_slice(&nonce);
+    encrypted.extend_from_slice(&ciphertext);
+    let tmp_path = path.with_extension("encrypting");
+    fs::write(&tmp_path, encrypted)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Content of a chunk file, decrypted if it was encrypted
+pub fn decrypt_file(path: &Path, key: &EncryptionKey) -> Result<Vec<u8>> {
+    let data = fs::read(path)?;
+    let Some(encrypted) = data.strip_prefix(MAGIC) else {
+        return Ok(data);
+    };
+    if encrypted.len() < NONCE_LEN {
+        return Err(anyhow!("{} is truncated", path.display()));
+    }
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+    key.cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Failed to decrypt {}, wrong key?", path.display()))
+}
+
+/// Directory only the user can read, next to the chunk file, for what is decrypted from it.
+/// Emptied by `encrypt_chunk_dir`.
+pub(crate) fn decrypted_dir(path: &Path) -> std::io::Result<PathBuf> {
+    let dir = path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(DECRYPTED_DIR);
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.recursive(true).create(&dir)?;
+    Ok(dir)
+}
+
+/// Decrypted copy of the chunk file, with the extension of the chunk, for ffmpeg to read.
+/// Only readable by the user, in a private directory next to the chunk, and removed when
+/// dropped. `None` when the file isn't encrypted.
+pub(crate) fn decrypted_copy(path: &Path, key: &EncryptionKey) -> Result<Option<TempPath>> {
+    if !is_encrypted(path)? {
+        return Ok(None);
+    }
+    let plaintext = decrypt_file(path, key)?;
+    let dir = decrypted_dir(path)?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("mp4");
+    let mut copy = tempfile::Builder::new()
+        .suffix(&format!(".{}", extension))
+        .tempfile_in(&dir)?;
+    copy.write_all(&plaintext)?;
+    Ok(Some(copy.into_temp_path()))
+}
+
+/// Encrypts the plaintext files of a chunk directory, e.g. recorded before encryption was
+/// enabled or the last chunk before a shutdown. Returns the number of files encrypted.
+/// Must not run while recording, the chunks being written would be encrypted too.
+pub fn encrypt_chunk_dir(dir: &Path, key: &EncryptionKey) -> Result<usize> {
+    let mut encrypted = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // Decrypted copies left over by a crash
+        if path.file_name().map_or(false, |name| name == DECRYPTED_DIR) {
+            fs::remove_dir_all(&path)?;
+            continue;
+        }
+        // Left over by an interrupted encrypt_file, the chunk itself is still in plaintext
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "encrypting")
+        {
+            fs::remove_file(&path)?;
+            continue;
+        }
+        if !path.is_file() || is_encrypted(&path)? {
+            continue;
+        }
+        encrypt_file(&path, key)?;
+        debug!("Encrypted {}", path.display());
+        encrypted += 1;
+    }
+    Ok(encrypted)
+}
+
+/// Encrypts a plaintext database in place with SQLCipher's `sqlcipher_export`, before it is
+/// opened with the key. Returns whether there was a plaintext database to encrypt.
+pub async fn encrypt_database(database_path: &str, key: &EncryptionKey) -> Result<bool> {
+    let path = Path::new(database_path);
+    if !path.exists() || !starts_with(path, SQLITE_HEADER)? {
+        return Ok(false);
+    }
+    info!("Encrypting the existing database, this can take a while");
+    let encrypted_path = PathBuf::from(format!("{}.encrypting", database_path));
+    if encrypted_path.exists() {
+        fs::remove_file(&encrypted_path)?;
+    }
+
+    let mut conn = SqliteConnectOptions::new().filename(path).connect().await?;
+    sqlx::query(&format!(
+        "ATTACH DATABASE '{}' AS encrypted KEY {}",
+        encrypted_path.to_string_lossy().replace('\'', "''"),
+        key.sqlcipher_key()
+    ))
+    .execute(&mut conn)
+    .await?;
+    sqlx::query("SELECT sqlcipher_export('encrypted')")
+        .execute(&mut conn)
+        .await?;
+    sqlx::query("DETACH DATABASE encrypted")
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+
+    fs::rename(&encrypted_path, path)?;
+    // The write-ahead log of the plaintext database, its content was exported
+    for suffix in ["-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{}", database_path, suffix));
+    }
+    info!("Encrypted the existing database");
+    Ok(true)
+}
+
+/// Encrypts the video and audio chunk files once they're complete, in the background,
+/// and writes everything else to `inner`
+pub struct EncryptedStorage {
+    inner: Arc<dyn Storage>,
+    key: EncryptionKey,
+    /// Only files in this directory are encrypted, not e.g. synthetic audio files
+    chunk_dir: PathBuf,
+    /// Video chunk being written, complete once the next one starts
+    current_video_chunk: Mutex<Option<String>>,
+}
+
+impl EncryptedStorage {
+    pub fn new(inner: Arc<dyn Storage>, key: EncryptionKey, chunk_dir: &Path) -> Self {
+        EncryptedStorage {
+            inner,
+            key,
+            chunk_dir: chunk_dir.to_path_buf(),
+            current_video_chunk: Mutex::new(None),
+        }
+    }
+
+    fn encrypt_in_background(&self, file_path: &str) {
+        let path = PathBuf::from(file_path);
+        if !path.starts_with(&self.chunk_dir) {
+            return;
+        }
+        let key = self.key.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = encrypt_file(&path, &key) {
+                error!("Failed to encrypt {}: {}", path.display(), e);
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl Storage for EncryptedStorage {
+    async fn insert_video_chunk(&self, file_path: &str, codec: &str) -> Result<(), sqlx::Error> {
+        let previous = self
+            .current_video_chunk
+            .lock()
+            .unwrap()
+            .replace(file_path.to_string());
+        if let Some(previous) = previous {
+            self.encrypt_in_background(&previous);
+        }
+        self.inner.insert_video_chunk(file_path, codec).await
+    }
+
+    async fn write_frame(&self, frame: FrameRecord) -> Result<(), sqlx::Error> {
+        self.inner.write_frame(frame).await
+    }
+
+    async fn write_frame_now(&self, frame: FrameRecord) -> Result<Option<i64>, sqlx::Error> {
+        self.inner.write_frame_now(frame).await
+    }
+
+    /// The audio chunk is transcribed by then
+    async fn write_audio(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        let file_path = audio.file_path.clone();
+        self.inner.write_audio(audio).await?;
+        self.encrypt_in_background(&file_path);
+        Ok(())
+    }
+
+    /// The chunk is encrypted already
+    async fn write_retranscription(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        self.inner.write_retranscription(audio).await
+    }
+
+    async fn write_reocr(
+        &self,
+        video_chunk: &str,
+        offset_index: i64,
+        frame: FrameRecord,
+    ) -> Result<(), sqlx::Error> {
+        self.inner
+            .write_reocr(video_chunk, offset_index, frame)
+            .await
+    }
+
+    async fn flush_writes(&self) {
+        self.inner.flush_writes().await;
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{AudioRecord, ContentProcessor, FrameRecord};
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Currency codes of the symbols of the amounts
+const CURRENCY_SYMBOLS: [(char, &str); 4] =
+    [('$', "usd"), ('€', "eur"), ('£', "gbp"), ('¥', "jpy")];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Person,
+    Organization,
+    Url,
+    Date,
+    Amount,
+}
+
+impl EntityKind {
+    /// Name in the API and the database, e.g. `person`
+    pub fn name(self) -> &'static str {
+        match self {
+            EntityKind::Person => "person",
+            EntityKind::Organization => "organization",
+            EntityKind::Url => "url",
+            EntityKind::Date => "date",
+            EntityKind::Amount => "amount",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EntityKind> {
+        [
+            EntityKind::Person,
+            EntityKind::Organization,
+            EntityKind::Url,
+            EntityKind::Date,
+            EntityKind::Amount,
+        ]
+        .into_iter()
+        .find(|kind| kind.name() == name)
+    }
+}
+
+/// Something named in a frame or a transcription
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    pub kind: EntityKind,
+    /// As read, e.g. "ACME Corp."
+    pub value: String,
+    /// What `/search?entity=` compares, e.g. "acme corp", ISO dates and "usd 1234.50"
+    pub normalized: String,
+}
+
+/// An entity with when it was seen, see `DatabaseManager::list_entities`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntitySummary {
+    pub kind: EntityKind,
+    /// Most recent form read
+    pub value: String,
+    pub normalized: String,
+    /// Frames and transcriptions naming it
+    pub mentions: i64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Lowercase `value` without repeated spaces nor trailing punctuation, how the names are
+/// compared
+pub fn normalize_entity(value: &str) -> String {
+    value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['.', ',', ';', ':', '!', '?'])
+        .to_lowercase()
+}
+
+/// Finds people, organizations, URLs, dates and amounts in text with patterns, no model:
+/// people after a title or before their email, organizations before a legal form such as
+/// "Inc" or "GmbH". Fast enough for every frame, it favors precision over recall.
+pub struct EntityExtractor {
+    url: Regex,
+    date: Regex,
+    amount: Regex,
+    organization: Regex,
+    person: Regex,
+}
+
+impl Default for EntityExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntityExtractor {
+    pub fn new() -> Self {
+        let month = r"(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec)[a-z]*\.?";
+        let number = r"\d{1,3}(?:,\d{3})+(?:\.\d{1,2})?|\d+(?:\.\d{1,2})?";
+        let name = r"[A-Z][a-z]+(?:[ \t]+[A-Z][a-z]+){0,2}";
+        EntityExtractor {
+            url: Regex::new(r#"(?i)\b(?:https?://|www\.)[^\s<>"'()\[\]{}]+"#).unwrap(),
+            date: Regex::new(&format!(
+                r"\b(?:\d{{4}}-\d{{2}}-\d{{2}}|{month}[ \t]+\d{{1,2}}(?:st|nd|rd|th)?,?[ \t]+\d{{4}}|\d{{1,2}}(?:st|nd|rd|th)?[ \t]+{month},?[ \t]+\d{{4}})\b"
+            ))
+            .unwrap(),
+            amount: Regex::new(&format!(
+                r"(?:[$€£¥][ \t]?(?:{number})|\b(?:{number})[ \t]?(?i:usd|eur|gbp|jpy|chf|cad|aud)\b|\b(?i:usd|eur|gbp|jpy|chf|cad|aud)[ \t]?(?:{number}))"
+            ))
+            .unwrap(),
+            organization: Regex::new(
+                r"\b(?:[A-Z][\w&'-]*[ \t]+){1,4}(?:Inc|Corp|Corporation|LLC|Ltd|GmbH|AG|SA|SAS|PLC|Co|Company|Group|Labs|Technologies|Foundation|University|Bank)\b\.?",
+            )
+            .unwrap(),
+            person: Regex::new(&format!(
+                r"\b(?:(?:Mr|Mrs|Ms|Dr|Prof)\.?[ \t]+(?P<titled>{name})|(?P<mailed>{name})[ \t]*<[^\s<>@]+@[^\s<>]+>)"
+            ))
+            .unwrap(),
+        }
+    }
+
+    /// Entities of `text`, each once, in the order of the kinds then of the text
+    pub fn extract(&self, text: &str) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = Vec::new();
+        let mut add = |kind, value: &str, normalized: String| {
+            let exists = entities
+                .iter()
+                .any(|entity| entity.kind == kind && entity.normalized == normalized);
+            if !value.is_empty() && !exists {
+                entities.push(Entity {
+                    kind,
+                    value: value.to_string(),
+                    normalized,
+                });
+            }
+        };
+        for found in self.person.captures_iter(text) {
+            if let Some(name) = found.name("titled").or_else(|| found.name("mailed")) {
+                add(
+                    EntityKind::Person,
+                    name.as_str(),
+                    normalize_entity(name.as_str()),
+                );
+            }
+        }
+        for found in self.organization.find_iter(text) {
+            let value = found.as_str().trim_end_matches('.');
+            add(EntityKind::Organization, value, normalize_entity(value));
+        }
+        for found in self.url.find_iter(text) {
+            let value = found
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?']);
+            add(
+                EntityKind::Url,
+                value,
+                normalize_entity(value).trim_end_matches('/').to_string(),
+            );
+        }
+        for found in self.date.find_iter(text) {
+            let normalized =
+                iso_date(found.as_str()).unwrap_or_else(|| normalize_entity(found.as_str()));
+            add(EntityKind::Date, found.as_str(), normalized);
+        }
+        for found in self.amount.find_iter(text) {
+            add(
+                EntityKind::Amount,
+                found.as_str(),
+                normalize_amount(found.as_str()),
+            );
+        }
+        entities
+    }
+}
+
+/// `2024-09-08` of "Sep 8th, 2024" or "8 September 2024", `None` for other formats
+fn iso_date(date: &str) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        return Some(date.to_string());
+    }
+    let mut month = None;
+    let mut numbers = Vec::new();
+    for word in date.split(|c: char| c.is_whitespace() || c == ',' || c == '.') {
+        let digits: String = word.chars().take_while(char::is_ascii_digit).collect();
+        if !digits.is_empty() {
+            numbers.push(digits.parse::<u32>().ok()?);
+        } else if word.len() >= 3 {
+            let prefix = word.get(..3)?.to_lowercase();
+            month = MONTHS.iter().position(|name| *name == prefix);
+        }
+    }
+    match (month, numbers.as_slice()) {
+        (Some(month), &[day, year]) => {
+            NaiveDate::from_ymd_opt(year as i32, month as u32 + 1, day).map(|d| d.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// "usd 1234.50" of "$1,234.50" or "1234.5 USD"
+fn normalize_amount(amount: &str) -> String {
+    let code: String = amount
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .collect::<String>()
+        .to_lowercase();
+    let currency = CURRENCY_SYMBOLS
+        .iter()
+        .find(|(symbol, _)| amount.contains(*symbol))
+        .map_or(code.as_str(), |(_, code)| code);
+    let number: String = amount
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let number = match number.split_once('.') {
+        Some((units, cents)) => format!("{}.{:0<2}", units, cents),
+        None => number,
+    };
+    format!("{} {}", currency, number)
+}
+
+/// Runs last, on the text the other processors kept and corrected
+impl ContentProcessor for EntityExtractor {
+    fn name(&self) -> &str {
+        "entities"
+    }
+
+    fn process_frame(&self, mut frame: FrameRecord) -> Option<FrameRecord> {
+        frame.entities = self.extract(&frame.text);
+        Some(frame)
+    }
+
+    fn process_audio(&self, mut audio: AudioRecord) -> Option<AudioRecord> {
+        audio.entities = self.extract(&audio.transcription);
+        Some(audio)
+    }
+}
@@ -0,0 +1,115 @@
+use crate::DatabaseManager;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Kind of an `Event`, stored by its snake_case name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    RecordingStarted,
+    RecordingStopped,
+    /// Screen recording started or stopped through the API
+    VisionStarted,
+    VisionStopped,
+    /// Recording of an audio device started or stopped
+    DeviceStarted,
+    DeviceStopped,
+    /// Recording of an audio device paused or resumed through the API
+    DevicePaused,
+    DeviceResumed,
+    /// Recording started with another OCR engine than the previous time
+    OcrEngineChanged,
+    /// Recording task restarted after it failed, panicked or stalled
+    TaskRestarted,
+    /// Error of a subsystem, not recorded again while it repeats within a minute
+    Error,
+    /// Chunks deleted by the storage quota or `prune_chunks`
+    ChunksPurged,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::RecordingStarted => "recording_started",
+            EventKind::RecordingStopped => "recording_stopped",
+            EventKind::VisionStarted => "vision_started",
+            EventKind::VisionStopped => "vision_stopped",
+            EventKind::DeviceStarted => "device_started",
+            EventKind::DeviceStopped => "device_stopped",
+            EventKind::DevicePaused => "device_paused",
+            EventKind::DeviceResumed => "device_resumed",
+            EventKind::OcrEngineChanged => "ocr_engine_changed",
+            EventKind::TaskRestarted => "task_restarted",
+            EventKind::Error => "error",
+            EventKind::ChunksPurged => "chunks_purged",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Deserialize::deserialize(
+            serde::de::value::StrDeserializer::<serde::de::value::Error>::new(s),
+        )
+        .map_err(|_| format!("unknown event kind {:?}", s))
+    }
+}
+
+/// Entry of the operational history served by `GET /events`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub kind: EventKind,
+    pub message: String,
+    /// Details of the kind, e.g. the device or the chunks purged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+/// Records events from code that can't wait for the database, they are written in the
+/// background in order. Clones share the same writer. Without a database, e.g. in tests,
+/// events are only logged.
+#[derive(Clone, Default)]
+pub struct EventLog {
+    sender: Option<mpsc::UnboundedSender<(EventKind, String, Option<Value>)>>,
+}
+
+impl EventLog {
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some((kind, message, data)) = receiver.recv().await {
+                if let Err(e) = db.insert_event(kind, &message, data.as_ref()).await {
+                    error!("Failed to record {} event: {}", kind, e);
+                }
+            }
+        });
+        EventLog {
+            sender: Some(sender),
+        }
+    }
+
+    pub fn record(&self, kind: EventKind, message: impl Into<String>, data: Option<Value>) {
+        let message = message.into();
+        info!("Event {}: {}", kind, message);
+        if let Some(sender) = &self.sender {
+            // Only fails once the runtime shuts down
+            let _ = sender.send((kind, message, data));
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use crate::server::into_content_item;
+use crate::{ContentType, DatabaseManager, SearchResult};
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const EXPORT_PAGE_SIZE: u32 = 1000;
+
+#[derive(Debug, Deserialize, PartialEq, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Markdown => "md",
+        }
+    }
+
+    fn header(&self) -> String {
+        match self {
+            ExportFormat::Json => "[".to_string(),
+            ExportFormat::Csv => "type,timestamp,app_name,file_path,offset_index,text\n".to_string(),
+            ExportFormat::Markdown => "# screenpipe export\n\n".to_string(),
+        }
+    }
+
+    fn footer(&self) -> String {
+        match self {
+            ExportFormat::Json => "]\n".to_string(),
+            ExportFormat::Csv | ExportFormat::Markdown => String::new(),
+        }
+    }
+
+    fn format_item(&self, item: SearchResult, first: bool) -> String {
+        let notification_text;
+        let (kind, timestamp, app_name, file_path, offset_index, text) = match &item {
+            SearchResult::OCR(ocr) => (
+                "OCR",
+                ocr.timestamp,
+                ocr.app_name.as_str(),
+                ocr.file_path.as_str(),
+                ocr.offset_index,
+                ocr.ocr_text.as_str(),
+            ),
+            SearchResult::Audio(audio) => (
+                "Audio",
+                audio.timestamp,
+                "",
+                audio.file_path.as_str(),
+                audio.offset_index,
+                audio.transcription.as_str(),
+            ),
+            SearchResult::Clipboard(clipboard) => (
+                "Clipboard",
+                clipboard.timestamp,
+                clipboard.app_name.as_str(),
+                "",
+                0,
+                clipboard.text.as_str(),
+            ),
+            SearchResult::Notification(notification) => {
+                notification_text = format!("{}\n{}", notification.title, notification.body);
+                (
+                    "Notification",
+                    notification.timestamp,
+                    notification.app_name.as_str(),
+                    "",
+                    0,
+                    notification_text.as_str(),
+                )
+            }
+        };
+
+        match self {
+            ExportFormat::Json => {
+                // Same schema as the /search results
+                let json = serde_json::to_string(&into_content_item(item)).unwrap_or_default();
+                if first {
+                    json
+                } else {
+                    format!(",{}", json)
+                }
+            }
+            ExportFormat::Csv => format!(
+                "{},{},{},{},{},{}\n",
+                kind,
+                timestamp.to_rfc3339(),
+                csv_escape(app_name),
+                csv_escape(file_path),
+                offset_index,
+                csv_escape(text)
+            ),
+            ExportFormat::Markdown => {
+                let source = if app_name.is_empty() {
+                    kind.to_string()
+                } else {
+                    format!("{} ({})", kind, app_name)
+                };
+                format!("## {} - {}\n\n{}\n\n", timestamp.to_rfc3339(), source, text.trim())
+            }
+        }
+    }
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+struct ExportState {
+    db: Arc<DatabaseManager>,
+    format: ExportFormat,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    content_types: Vec<ContentType>,
+    offset: u32,
+    started: bool,
+    first_item: bool,
+    finished: bool,
+}
+
+/// Streams every OCR text, transcript, clipboard copy and/or notification of the range, one
+/// page of results per chunk, so large exports never have to be held in memory.
+pub fn export_stream(
+    db: Arc<DatabaseManager>,
+    format: ExportFormat,
+    content_type: ContentType,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+) -> impl Stream<Item = Result<String, sqlx::Error>> {
+    let content_types = match content_type {
+        ContentType::All => vec![
+            ContentType::OCR,
+            ContentType::Audio,
+            ContentType::Clipboard,
+            ContentType::Notification,
+        ],
+        content_type => vec![content_type],
+    };
+    let state = ExportState {
+        db,
+        format,
+        start_time,
+        // Pin the end of the range so pages don't shift while recording goes on
+        end_time: Some(end_time.unwrap_or_else(Utc::now)),
+        content_types,
+        offset: 0,
+        started: false,
+        first_item: true,
+        finished: false,
+    };
+
+    futures::stream::unfold(state, |mut state| async move {
+        if state.finished {
+            return None;
+        }
+        if !state.started {
+            state.started = true;
+            return Some((Ok(state.format.header()), state));
+        }
+
+        while let Some(&content_type) = state.content_types.first() {
+            let page = match state
+                .db
+                .search(
+                    "",
+                    content_type,
+                    EXPORT_PAGE_SIZE,
+                    state.offset,
+                    state.start_time,
+                    state.end_time,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+            {
+                Ok(page) => page,
+                Err(e) => {
+                    state.finished = true;
+                    return Some((Err(e), state));
+                }
+            };
+
+            if page.len() < EXPORT_PAGE_SIZE as usize {
+                state.content_types.remove(0);
+                state.offset = 0;
+            } else {
+                state.offset += EXPORT_PAGE_SIZE;
+            }
+            if page.is_empty() {
+                continue;
+            }
+
+            let mut chunk = String::new();
+            for item in page {
+                chunk.push_str(&state.format.format_item(item, state.first_item));
+                state.first_item = false;
+            }
+            return Some((Ok(chunk), state));
+        }
+
+        state.finished = true;
+        Some((Ok(state.format.footer()), state))
+    })
+}
@@ -0,0 +1,483 @@
+use crate::db::search_syntax_error;
+use crate::plugin::{ApiPluginLayer, ApiPlugins};
+use crate::search_query::Highlight;
+use crate::server::control_vision;
+#[cfg(feature = "audio")]
+use crate::server::{control_device, list_devices, save_capture_state};
+use crate::{
+    ApiLimits, AppState, AudioResult, ClipboardResult, ContentType, DatabaseManager,
+    NotificationResult, OCRResult, SearchCursor,
+};
+use chrono::{DateTime, Utc};
+use log::{debug, error};
+#[cfg(feature = "audio")]
+use screenpipe_audio::parse_audio_device;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::service::Routes;
+use tonic::{Request, Response, Status};
+
+/// Types and services generated from `proto/screenpipe.proto`, e.g.
+/// `proto::screenpipe_client::ScreenpipeClient` for Rust clients
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("screenpipe.v1");
+}
+
+use proto::screenpipe_server::{Screenpipe, ScreenpipeServer};
+
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+/// How often the live feeds look for what was stored since, writes are batched every
+/// 500ms anyway
+const STREAM_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Results sent per poll at most, the rest follow at the next one
+const STREAM_BATCH: u32 = 100;
+/// Results buffered for a slow client before the feed waits for it
+const STREAM_BUFFER: usize = 256;
+
+fn timestamp(time: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn from_timestamp(time: Option<prost_types::Timestamp>) -> Result<Option<DateTime<Utc>>, Status> {
+    time.map(|time| {
+        DateTime::from_timestamp(time.seconds, time.nanos.max(0) as u32)
+            .ok_or_else(|| Status::invalid_argument("Timestamp out of range"))
+    })
+    .transpose()
+}
+
+fn highlights(highlights: Vec<Highlight>) -> Vec<proto::Highlight> {
+    highlights
+        .into_iter()
+        .map(|highlight| proto::Highlight {
+            start: highlight.start as u32,
+            end: highlight.end as u32,
+        })
+        .collect()
+}
+
+fn ocr_result(ocr: OCRResult) -> proto::OcrResult {
+    proto::OcrResult {
+        frame_id: ocr.frame_id,
+        text: ocr.ocr_text,
+        timestamp: Some(timestamp(ocr.timestamp)),
+        file_path: ocr.file_path,
+        offset_index: ocr.offset_index,
+        app_name: ocr.app_name,
+        snippet: ocr.snippet,
+        highlights: highlights(ocr.highlights),
+    }
+}
+
+fn clipboard_result(clipboard: ClipboardResult) -> proto::ClipboardResult {
+    proto::ClipboardResult {
+        id: clipboard.id,
+        text: clipboard.text,
+        timestamp: Some(timestamp(clipboard.timestamp)),
+        app_name: clipboard.app_name,
+        snippet: clipboard.snippet,
+        highlights: highlights(clipboard.highlights),
+    }
+}
+
+fn notification_result(notification: NotificationResult) -> proto::NotificationResult {
+    proto::NotificationResult {
+        id: notification.id,
+        app_name: notification.app_name,
+        title: notification.title,
+        body: notification.body,
+        timestamp: Some(timestamp(notification.timestamp)),
+        snippet: notification.snippet,
+        highlights: highlights(notification.highlights),
+    }
+}
+
+fn audio_result(audio: AudioResult) -> proto::AudioResult {
+    proto::AudioResult {
+        id: audio.transcription_id,
+        chunk_id: audio.audio_chunk_id,
+        transcription: audio.transcription,
+        timestamp: Some(timestamp(audio.timestamp)),
+        file_path: audio.file_path,
+        offset_index: audio.offset_index,
+        language: audio.language,
+        snippet: audio.snippet,
+        highlights: highlights(audio.highlights),
+        segments: audio
+            .segments
+            .into_iter()
+            .map(|segment| proto::TranscriptionSegment {
+                start: segment.start,
+                end: segment.end,
+                text: segment.text,
+            })
+            .collect(),
+    }
+}
+
+fn search_error(e: sqlx::Error) -> Status {
+    match search_syntax_error(&e) {
+        Some(message) => Status::invalid_argument(message),
+        None => {
+            error!("Failed to search for content: {}", e);
+            Status::internal(format!("Failed to search for content: {}", e))
+        }
+    }
+}
+
+/// Sends what `fetch` returns after `after_id` to the client every `STREAM_POLL_INTERVAL`,
+/// until the client cancels
+fn live_feed<T, F, Fut>(
+    db: Arc<DatabaseManager>,
+    mut after_id: i64,
+    fetch: F,
+) -> ReceiverStream<Result<T, Status>>
+where
+    T: Send + 'static,
+    F: Fn(Arc<DatabaseManager>, i64) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = Result<Vec<(i64, T)>, sqlx::Error>> + Send,
+{
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(async move {
+        while !tx.is_closed() {
+            match fetch(db.clone(), after_id).await {
+                Ok(results) => {
+                    let full = results.len() as u32 == STREAM_BATCH;
+                    for (id, result) in results {
+                        after_id = id;
+                        if tx.send(Ok(result)).await.is_err() {
+                            return;
+                        }
+                    }
+                    if full {
+                        continue;
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to read the live feed: {}", e);
+                    let _ = tx.send(Err(Status::internal(e.to_string()))).await;
+                    return;
+                }
+            }
+            tokio::time::sleep(STREAM_POLL_INTERVAL).await;
+        }
+        debug!("Live feed client gone");
+    });
+    ReceiverStream::new(rx)
+}
+
+/// gRPC API over the same state as the REST one, see `proto/screenpipe.proto`
+pub struct GrpcService {
+    state: Arc<AppState>,
+}
+
+impl GrpcService {
+    pub fn new(state: Arc<AppState>) -> Self {
+        GrpcService { state }
+    }
+
+    fn vision_status(&self) -> proto::VisionStatus {
+        proto::VisionStatus {
+            is_running: self.state.vision_control.load(Ordering::SeqCst),
+            is_paused: self.state.capture_paused.load(Ordering::SeqCst),
+            foreground_app: self.state.vision_status.foreground_app(),
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    async fn control_device(
+        &self,
+        request: proto::AudioDeviceRequest,
+        is_running: bool,
+    ) -> Result<Response<proto::AudioDeviceStatus>, Status> {
+        let device = parse_audio_device(&request.device_id)
+            .map_err(|_| Status::invalid_argument("Invalid device ID"))?;
+        control_device(&self.state, device, is_running);
+        save_capture_state(&self.state, &request.device_id, is_running).await;
+        Ok(Response::new(proto::AudioDeviceStatus {
+            id: request.device_id,
+            is_running,
+        }))
+    }
+
+    #[cfg(not(feature = "audio"))]
+    async fn control_device(
+        &self,
+        _: proto::AudioDeviceRequest,
+        _: bool,
+    ) -> Result<Response<proto::AudioDeviceStatus>, Status> {
+        Err(Status::unimplemented("screenpipe was built without audio"))
+    }
+
+    /// Id the live feeds start after when the client gives none, what is stored next
+    async fn after_id(&self, after_id: Option<i64>, ocr: bool) -> Result<i64, Status> {
+        if let Some(after_id) = after_id {
+            return Ok(after_id);
+        }
+        let (frame_id, transcription_id) = self
+            .state
+            .db
+            .get_last_content_ids()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(if ocr { frame_id } else { transcription_id })
+    }
+}
+
+#[tonic::async_trait]
+impl Screenpipe for GrpcService {
+    async fn search(
+        &self,
+        request: Request<proto::SearchRequest>,
+    ) -> Result<Response<proto::SearchResponse>, Status> {
+        let request = request.into_inner();
+        debug!("gRPC search: query='{}'", request.query);
+        let db = &self.state.db;
+        let query = if request.fuzzy {
+            db.fuzzy_search_query(&request.query)
+                .await
+                .map_err(search_error)?
+        } else {
+            request.query.clone()
+        };
+        let cursor = match request.cursor.as_deref() {
+            Some(cursor) => Some(
+                SearchCursor::decode(cursor)
+                    .ok_or_else(|| Status::invalid_argument("Invalid cursor"))?,
+            ),
+            None => None,
+        };
+        // app_name only searches the screen, like GET /search
+        let content_type = match (request.app_name.is_some(), request.content_type()) {
+            (true, _) | (false, proto::ContentType::Ocr) => ContentType::OCR,
+            (false, proto::ContentType::Audio) => ContentType::Audio,
+            (false, proto::ContentType::Clipboard) => ContentType::Clipboard,
+            (false, proto::ContentType::Notification) => ContentType::Notification,
+            (false, proto::ContentType::All) => ContentType::All,
+        };
+        let limit = match request.limit {
+            0 => DEFAULT_SEARCH_LIMIT,
+            limit => limit,
+        };
+        let start_time = from_timestamp(request.start_time)?;
+        let end_time = from_timestamp(request.end_time)?;
+        let app_name = request.app_name.as_deref();
+
+        let (results, next_cursor) = db
+            .search_page(
+                &query,
+                content_type,
+                limit,
+                cursor.as_ref(),
+                start_time,
+                end_time,
+                app_name,
+                None,
+                None,
+                None,
+            )
+            .await
+            .map_err(search_error)?;
+        let total = db
+            .count_search_results(
+                &query,
+                content_type,
+                start_time,
+                end_time,
+                app_name,
+                None,
+                None,
+                None,
+            )
+            .await
+            .map_err(search_error)?;
+        Ok(Response::new(proto::SearchResponse {
+            results: results
+                .into_iter()
+                .map(|result| proto::SearchResult {
+                    content: Some(match result {
+                        crate::SearchResult::OCR(ocr) => {
+                            proto::search_result::Content::Ocr(ocr_result(ocr))
+                        }
+                        crate::SearchResult::Audio(audio) => {
+                            proto::search_result::Content::Audio(audio_result(audio))
+                        }
+                        crate::SearchResult::Clipboard(clipboard) => {
+                            proto::search_result::Content::Clipboard(clipboard_result(clipboard))
+                        }
+                        crate::SearchResult::Notification(notification) => {
+                            proto::search_result::Content::Notification(notification_result(
+                                notification,
+                            ))
+                        }
+                    }),
+                })
+                .collect(),
+            total: total as u64,
+            next_cursor: next_cursor.map(|cursor| cursor.encode()),
+        }))
+    }
+
+    async fn start_vision(
+        &self,
+        _: Request<proto::VisionRequest>,
+    ) -> Result<Response<proto::VisionStatus>, Status> {
+        control_vision(&self.state, true).await;
+        Ok(Response::new(self.vision_status()))
+    }
+
+    async fn stop_vision(
+        &self,
+        _: Request<proto::VisionRequest>,
+    ) -> Result<Response<proto::VisionStatus>, Status> {
+        control_vision(&self.state, false).await;
+        Ok(Response::new(self.vision_status()))
+    }
+
+    async fn get_vision_status(
+        &self,
+        _: Request<proto::VisionRequest>,
+    ) -> Result<Response<proto::VisionStatus>, Status> {
+        Ok(Response::new(self.vision_status()))
+    }
+
+    #[cfg(feature = "audio")]
+    async fn list_audio_devices(
+        &self,
+        _: Request<proto::ListAudioDevicesRequest>,
+    ) -> Result<Response<proto::ListAudioDevicesResponse>, Status> {
+        let devices = list_devices(&self.state)
+            .into_iter()
+            .map(|device| proto::AudioDevice {
+                id: device.id,
+                name: device.name,
+                is_default: device.info.is_default,
+                is_running: device.is_running,
+            })
+            .collect();
+        Ok(Response::new(proto::ListAudioDevicesResponse { devices }))
+    }
+
+    #[cfg(not(feature = "audio"))]
+    async fn list_audio_devices(
+        &self,
+        _: Request<proto::ListAudioDevicesRequest>,
+    ) -> Result<Response<proto::ListAudioDevicesResponse>, Status> {
+        Err(Status::unimplemented("screenpipe was built without audio"))
+    }
+
+    async fn start_audio_device(
+        &self,
+        request: Request<proto::AudioDeviceRequest>,
+    ) -> Result<Response<proto::AudioDeviceStatus>, Status> {
+        self.control_device(request.into_inner(), true).await
+    }
+
+    async fn stop_audio_device(
+        &self,
+        request: Request<proto::AudioDeviceRequest>,
+    ) -> Result<Response<proto::AudioDeviceStatus>, Status> {
+        self.control_device(request.into_inner(), false).await
+    }
+
+    type StreamOcrStream = ReceiverStream<Result<proto::OcrResult, Status>>;
+
+    async fn stream_ocr(
+        &self,
+        request: Request<proto::StreamRequest>,
+    ) -> Result<Response<Self::StreamOcrStream>, Status> {
+        let request = request.into_inner();
+        let after_id = self.after_id(request.after_id, true).await?;
+        debug!("gRPC OCR feed from frame {}", after_id);
+        let app_name = Arc::new(request.app_name);
+        Ok(Response::new(live_feed(
+            self.state.db.clone(),
+            after_id,
+            move |db, after_id| {
+                let app_name = app_name.clone();
+                async move {
+                    let results = db
+                        .get_ocr_after(after_id, app_name.as_deref(), STREAM_BATCH)
+                        .await?;
+                    Ok::<Vec<_>, sqlx::Error>(
+                        results
+                            .into_iter()
+                            .map(|ocr| (ocr.frame_id, ocr_result(ocr)))
+                            .collect(),
+                    )
+                }
+            },
+        )))
+    }
+
+    type StreamTranscriptionsStream = ReceiverStream<Result<proto::AudioResult, Status>>;
+
+    async fn stream_transcriptions(
+        &self,
+        request: Request<proto::StreamRequest>,
+    ) -> Result<Response<Self::StreamTranscriptionsStream>, Status> {
+        let after_id = self.after_id(request.into_inner().after_id, false).await?;
+        debug!("gRPC transcription feed from {}", after_id);
+        Ok(Response::new(live_feed(
+            self.state.db.clone(),
+            after_id,
+            |db, after_id| async move {
+                let results = db.get_transcriptions_after(after_id, STREAM_BATCH).await?;
+                Ok::<Vec<_>, sqlx::Error>(
+                    results
+                        .into_iter()
+                        .map(|audio| (audio.transcription_id, audio_result(audio)))
+                        .collect(),
+                )
+            },
+        )))
+    }
+}
+
+/// Serves the gRPC API on `listener` behind the same `plugins` and `limits` as the REST API,
+/// e.g. the auth of a plugin. Its requests are rate limited apart from the REST ones.
+pub async fn serve_grpc(
+    state: Arc<AppState>,
+    listener: TcpListener,
+    plugins: ApiPlugins,
+    limits: ApiLimits,
+) -> Result<(), std::io::Error> {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto::Builder;
+    use hyper_util::service::TowerToHyperService;
+
+    let routes = Routes::new(ScreenpipeServer::new(GrpcService::new(state)));
+    let app = limits.apply(
+        routes
+            .into_axum_router()
+            .layer(ApiPluginLayer::new(plugins)),
+    );
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            // e.g. out of file descriptors, the listener itself still works
+            Err(e) => {
+                error!("Failed to accept a gRPC connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("gRPC connection error: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,267 @@
+use crate::{EventKind, EventLog};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use sysinfo::{DiskExt, System, SystemExt};
+
+/// Seconds within which the same error of a subsystem is recorded as an event once
+const REPEATED_ERROR_SECS: i64 = 60;
+
+/// `[health]` table of the config file, when `/health` calls a part of the recording
+/// stale or degraded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthThresholds {
+    /// Seconds without a frame before the screen recording is stale
+    pub frame_stale_secs: u64,
+    /// Seconds without an audio chunk of a device before it is stale, more than
+    /// --audio-chunk-duration
+    pub audio_stale_secs: u64,
+    /// Seconds after the start during which nothing is stale yet
+    pub startup_grace_secs: u64,
+    /// Frames waiting for OCR
+    pub max_ocr_queue: usize,
+    /// Audio chunks recorded and waiting for their transcription
+    pub max_whisper_backlog: usize,
+    /// Free space of the disk of the data directory, in GB
+    pub min_free_disk_gb: f64,
+    /// Milliseconds the last batch of rows took to be written to the database
+    pub max_db_write_ms: u64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        HealthThresholds {
+            frame_stale_secs: 60,
+            audio_stale_secs: 60,
+            startup_grace_secs: 120,
+            max_ocr_queue: 50,
+            max_whisper_backlog: 10,
+            min_free_disk_gb: 1.0,
+            max_db_write_ms: 2000,
+        }
+    }
+}
+
+impl HealthThresholds {
+    pub fn validate(&self) -> Result<()> {
+        if self.frame_stale_secs == 0 || self.audio_stale_secs == 0 {
+            return Err(anyhow!(
+                "frame_stale_secs and audio_stale_secs must be at least 1"
+            ));
+        }
+        if !self.min_free_disk_gb.is_finite() || self.min_free_disk_gb < 0.0 {
+            return Err(anyhow!("min_free_disk_gb must be 0 or more"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn frame_stale(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.frame_stale_secs as i64)
+    }
+
+    pub(crate) fn audio_stale(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.audio_stale_secs as i64)
+    }
+
+    pub(crate) fn startup_grace(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.startup_grace_secs as i64)
+    }
+}
+
+/// Part of the recording `/health` reports on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    /// Screen capture, frames and video chunks
+    Vision,
+    Ocr,
+    /// Audio capture, chunks of the devices
+    Audio,
+    Transcription,
+    Database,
+    Disk,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubsystemError {
+    pub message: String,
+    pub at: DateTime<Utc>,
+}
+
+/// State of a subsystem in `/health`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubsystemHealth {
+    pub name: Subsystem,
+    /// `OK`, `Stale`, `Degraded`, `No data`, `Paused` or `Loading`
+    pub status: String,
+    pub message: String,
+    pub last_error: Option<SubsystemError>,
+    /// Times its task was restarted after it exited, panicked or stalled
+    #[serde(default)]
+    pub restarts: u32,
+}
+
+/// Freshness of an audio device in `/health`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioDeviceHealth {
+    pub device: String,
+    pub last_chunk_at: DateTime<Utc>,
+    /// `OK`, `Stale` or `Paused`
+    pub status: String,
+}
+
+/// Writes of the database shown by `/health`, see `ApiStorage::write_stats`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WriteStats {
+    /// Time the last batch of rows took to be written
+    pub latency: Option<Duration>,
+    pub last_error: Option<SubsystemError>,
+}
+
+/// Activity and errors of the recording, updated by the recording loops and read by
+/// `/health`
+#[derive(Default)]
+pub struct HealthMonitor {
+    thresholds: HealthThresholds,
+    /// Directory of which the disk's free space is reported
+    data_dir: Option<PathBuf>,
+    ocr_queue: AtomicUsize,
+    whisper_backlog: AtomicUsize,
+    /// Last chunk recorded by each running audio device
+    audio_devices: Mutex<BTreeMap<String, DateTime<Utc>>>,
+    errors: Mutex<BTreeMap<Subsystem, SubsystemError>>,
+    restarts: Mutex<BTreeMap<Subsystem, u32>>,
+    events: EventLog,
+}
+
+impl HealthMonitor {
+    pub fn new(thresholds: HealthThresholds, data_dir: Option<PathBuf>) -> Self {
+        HealthMonitor {
+            thresholds,
+            data_dir,
+            ..Default::default()
+        }
+    }
+
+    pub fn thresholds(&self) -> &HealthThresholds {
+        &self.thresholds
+    }
+
+    /// Errors and restarts are also recorded to `events`
+    pub fn with_events(mut self, events: EventLog) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn events(&self) -> &EventLog {
+        &self.events
+    }
+
+    /// Also recorded as an event, unless it repeats the last error within a minute
+    pub fn record_error(&self, subsystem: Subsystem, message: impl Into<String>) {
+        let message = message.into();
+        if self.set_error(subsystem, message.clone()) {
+            self.events.record(
+                EventKind::Error,
+                message,
+                Some(json!({ "subsystem": subsystem })),
+            );
+        }
+    }
+
+    /// Whether the error is new, not the last one repeated within `REPEATED_ERROR_SECS`
+    fn set_error(&self, subsystem: Subsystem, message: String) -> bool {
+        let at = Utc::now();
+        let error = SubsystemError { message, at };
+        let previous = self.errors.lock().unwrap().insert(subsystem, error.clone());
+        previous.map_or(true, |previous| {
+            previous.message != error.message
+                || (at - previous.at).num_seconds() >= REPEATED_ERROR_SECS
+        })
+    }
+
+    pub fn last_error(&self, subsystem: Subsystem) -> Option<SubsystemError> {
+        self.errors.lock().unwrap().get(&subsystem).cloned()
+    }
+
+    /// The task of `subsystem` was restarted, `reason` becomes its last error
+    pub fn task_restarted(&self, subsystem: Subsystem, reason: impl Into<String>) {
+        *self.restarts.lock().unwrap().entry(subsystem).or_default() += 1;
+        let reason = reason.into();
+        self.set_error(subsystem, reason.clone());
+        self.events.record(
+            EventKind::TaskRestarted,
+            reason,
+            Some(json!({ "subsystem": subsystem })),
+        );
+    }
+
+    pub fn restarts(&self, subsystem: Subsystem) -> u32 {
+        self.restarts
+            .lock()
+            .unwrap()
+            .get(&subsystem)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_ocr_queue_depth(&self, depth: usize) {
+        self.ocr_queue.store(depth, Ordering::Relaxed);
+    }
+
+    pub fn ocr_queue_depth(&self) -> usize {
+        self.ocr_queue.load(Ordering::Relaxed)
+    }
+
+    /// A chunk of `device` was recorded and sent to transcription
+    pub fn chunk_recorded(&self, device: &str) {
+        self.audio_devices
+            .lock()
+            .unwrap()
+            .insert(device.to_string(), Utc::now());
+        self.whisper_backlog.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A transcription came back, of a chunk of a device or a synthetic one
+    pub fn chunk_transcribed(&self) {
+        let _ = self
+            .whisper_backlog
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+
+    pub fn whisper_backlog(&self) -> usize {
+        self.whisper_backlog.load(Ordering::Relaxed)
+    }
+
+    /// The device stopped, it isn't stale
+    pub fn remove_audio_device(&self, device: &str) {
+        self.audio_devices.lock().unwrap().remove(device);
+    }
+
+    pub fn audio_devices(&self) -> BTreeMap<String, DateTime<Utc>> {
+        self.audio_devices.lock().unwrap().clone()
+    }
+
+    /// Free bytes of the disk holding the data directory, `None` without one
+    pub fn free_disk_bytes(&self) -> Option<u64> {
+        let data_dir = self.data_dir.as_deref()?;
+        let data_dir = data_dir
+            .canonicalize()
+            .unwrap_or_else(|_| data_dir.to_path_buf());
+        let mut system = System::new();
+        system.refresh_disks_list();
+        system
+            .disks()
+            .iter()
+            .filter(|disk| data_dir.starts_with(disk.mount_point()))
+            .max_by_key(|disk| disk.mount_point().components().count())
+            .map(|disk| disk.available_space())
+    }
+}
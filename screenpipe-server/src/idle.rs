@@ -0,0 +1,184 @@
+use crate::{ActivityEvent, DatabaseManager};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info};
+use serde_json::json;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Bucket of the `afkstatus` events of the idle periods, next to the ActivityWatch ones
+pub const IDLE_BUCKET_ID: &str = "screenpipe-idle";
+
+/// Pauses capture when there was no keyboard/mouse input for `idle_timeout`
+/// or the screen is locked, and resumes it on activity. With a database, idle periods
+/// are recorded as `afkstatus` events, for `GET /timeline`.
+pub struct IdleMonitor {
+    idle_timeout: Duration,
+    capture_paused: Arc<AtomicBool>,
+    db: Option<Arc<DatabaseManager>>,
+    /// Start of the current idle period
+    idle_since: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl IdleMonitor {
+    pub fn new(
+        idle_timeout: Duration,
+        capture_paused: Arc<AtomicBool>,
+        db: Option<Arc<DatabaseManager>>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            idle_timeout,
+            capture_paused,
+            db,
+            idle_since: Mutex::new(None),
+        })
+    }
+
+    pub fn start_monitoring(self: &Arc<Self>, interval: Duration) {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                monitor.check_idle().await;
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    async fn check_idle(&self) {
+        let idle_time = system_idle_time();
+        let locked = is_screen_locked();
+        debug!("Idle time: {:?}, screen locked: {}", idle_time, locked);
+
+        let is_idle = locked || idle_time.map_or(false, |t| t >= self.idle_timeout);
+        let was_idle = self.capture_paused.swap(is_idle, Ordering::SeqCst);
+
+        if is_idle && !was_idle {
+            info!(
+                "User idle (screen locked: {}), pausing video and audio capture",
+                locked
+            );
+        } else if !is_idle && was_idle {
+            info!("User activity detected, resuming video and audio capture");
+        }
+
+        let now = Utc::now();
+        let since = {
+            let mut idle_since = self.idle_since.lock().unwrap();
+            if is_idle && idle_since.is_none() {
+                // The input stopped `idle_time` ago, the lock screen just now
+                let idle_time = idle_time
+                    .filter(|_| !locked)
+                    .and_then(|t| chrono::Duration::from_std(t).ok())
+                    .unwrap_or_else(chrono::Duration::zero);
+                *idle_since = Some(now - idle_time);
+            }
+            match is_idle {
+                true => *idle_since,
+                false => idle_since.take(),
+            }
+        };
+        if let Some(since) = since {
+            self.record_idle(since, now).await;
+        }
+    }
+
+    /// Writes the idle period, extended on every check until activity resumes
+    async fn record_idle(&self, since: DateTime<Utc>, until: DateTime<Utc>) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        let event = ActivityEvent {
+            id: 0,
+            bucket_id: IDLE_BUCKET_ID.to_string(),
+            event_type: "afkstatus".to_string(),
+            timestamp: since,
+            duration: (until - since).num_milliseconds() as f64 / 1000.0,
+            data: json!({"status": "afk"}),
+        };
+        if let Err(e) = db.upsert_activity_events(&[event]).await {
+            error!("Failed to record the idle period: {}", e);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn system_idle_time() -> Option<Duration> {
+    let output = Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .ok()?;
+    let output_str = String::from_utf8_lossy(&output.stdout);
+
+    // HIDIdleTime is in nanoseconds
+    for line in output_str.lines() {
+        if line.contains("\"HIDIdleTime\"") {
+            let nanos: u64 = line.split('=').nth(1)?.trim().parse().ok()?;
+            return Some(Duration::from_nanos(nanos));
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn is_screen_locked() -> bool {
+    Command::new("ioreg")
+        .args(["-n", "Root", "-d1"])
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout).contains("\"CGSSessionScreenIsLocked\"=Yes")
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+fn system_idle_time() -> Option<Duration> {
+    // xprintidle prints the X11 idle time in milliseconds
+    let output = Command::new("xprintidle").output().ok()?;
+    let millis: u64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+    Some(Duration::from_millis(millis))
+}
+
+#[cfg(target_os = "linux")]
+fn is_screen_locked() -> bool {
+    Command::new("loginctl")
+        .args(["show-session", "self", "-p", "LockedHint", "--value"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "yes")
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "windows")]
+fn system_idle_time() -> Option<Duration> {
+    use windows::Win32::System::SystemInformation::GetTickCount;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    let mut last_input = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    unsafe {
+        if !GetLastInputInfo(&mut last_input).as_bool() {
+            return None;
+        }
+        let millis = GetTickCount().wrapping_sub(last_input.dwTime);
+        Some(Duration::from_millis(millis as u64))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn is_screen_locked() -> bool {
+    // The idle timeout covers the lock screen on Windows
+    false
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn system_idle_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn is_screen_locked() -> bool {
+    false
+}
@@ -1,18 +1,197 @@
+//! Recording, storage and HTTP API of screenpipe.
+//!
+//! The items re-exported at the crate root are the public API of this crate and follow
+//! semver: they are only removed or changed in a breaking way in a new major version
+//! (minor version while below 1.0). Module paths such as `screenpipe_server::core` are
+//! implementation details and can change in any release, import from the crate root instead.
+//!
+//! Types of `screenpipe-vision` and `screenpipe-audio` needed to call this API are re-exported
+//! too, so depending on this crate alone is enough.
+//!
+//! Subsystems can be compiled out with cargo features, all enabled by default:
+//! - `recording`: screen recording, without it only the API over an existing database is built
+//! - `audio`: audio recording and transcription, implies `recording`
+//! - `cloud`: cloud OCR (Unstructured, Google Vision, Azure) and cloud transcription
+//! - `integrations`: friend wearable and Obsidian daily notes
+//!
+//! And off by default:
+//! - `postgres`: `PostgresStorage`, to copy recordings to a PostgreSQL server
+//! - `encryption`: SQLCipher database and AES-256-GCM encrypted video/audio chunks, see
+//!   `DatabaseSettings::encryption_key`
+//! - `s3`: backups and offloading of old chunks to S3-compatible object storage, see
+//!   `BackupLocation` and `offload_chunks`
+//! - `cuda`, `metal`: local transcription on an NVIDIA or Apple GPU, see `Gpu`
+
+mod anomaly;
+mod api_error;
+mod archive;
+mod ask;
+#[cfg(feature = "audio")]
+mod backfill;
+mod backup;
+mod capabilities;
+mod capture_requests;
+#[doc(hidden)]
+pub mod chunking;
+#[cfg(feature = "recording")]
+mod clipboard;
+mod collections;
+mod confidence;
+#[doc(hidden)]
 pub mod core;
 mod db;
+mod db_writer;
+mod diagnostics;
+mod download;
+mod entities;
+#[cfg(feature = "encryption")]
+mod encryption;
+mod events;
+mod export;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod health;
+#[cfg(feature = "recording")]
+mod idle;
+mod live_settings;
+#[doc(hidden)]
 pub mod logs;
+mod mcp;
+mod memory_storage;
+#[cfg(feature = "recording")]
+mod notifications;
 mod plugin;
+mod processor;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod rate_limit;
+#[cfg(feature = "recording")]
+mod replay;
+mod recovery;
+#[cfg(feature = "recording")]
+mod reocr;
 mod resource_monitor;
+mod rules;
+mod search_query;
 mod server;
+mod sessions;
+mod shutdown;
+mod spellcheck;
+mod storage;
+#[cfg(feature = "recording")]
+mod supervisor;
+mod tiering;
+mod timeline;
+#[cfg(feature = "recording")]
 mod video;
-pub mod chunking;
+#[cfg(feature = "recording")]
+mod video_encoding;
+mod vision_status;
 
-pub use core::{start_continuous_recording, RecorderControl};
-pub use db::{ContentType, DatabaseManager, SearchResult};
+pub use anomaly::{
+    run_anomaly_detection, Anomaly, AnomalyDetector, AnomalyKind, IngestCounts,
+};
+pub use api_error::{ApiError, ApiJson, ApiPath, ApiQuery, ErrorCode};
+pub use archive::{disk_usage, prune_chunks, ChunkKind, ChunkUsage, DiskUsage, PruneSummary};
+pub use ask::{
+    build_prompt, question_words, retrieve_sources, AskSource, OllamaClient, DEFAULT_OLLAMA_MODEL,
+    DEFAULT_OLLAMA_URL,
+};
+#[cfg(feature = "audio")]
+pub use backfill::{TranscriptionBackfill, IN_FLIGHT_TIMEOUT};
+pub use backup::{
+    backup, restore_backup, BackupLocation, BackupManifest, BackupMedia, BackupSummary,
+    RestoreSummary,
+};
+pub use capabilities::{Capabilities, CaptureBackend};
+pub use capture_requests::{CaptureRequests, CapturedFrame};
+#[cfg(feature = "recording")]
+pub use clipboard::ClipboardMonitor;
+pub use collections::{
+    run_collection_tagging, Collection, CollectionFilters, CollectionItem, NewCollection,
+};
+pub use confidence::{mean_confidence, ocr_words, ConfidenceFloor, OcrWord};
+#[cfg(feature = "recording")]
+pub use core::{start_continuous_recording, RecordingConfig, RecordingHandles};
+pub use core::{enforce_storage_quota, evict_oldest_chunks, DataOutputWrapper, RecorderControl};
+pub use db::{
+    search_syntax_error, ActivityEvent, AudioResult, ClipboardResult, ContentSource, ContentType,
+    DatabaseError, DatabaseManager, DatabaseSettings, FacetCount, HeatmapBucket, IngestBatch,
+    IngestBucket, IngestFrame, IngestSummary, IngestTranscription, Mark, NotificationResult,
+    OCRResult, SearchCursor, SearchFacets, SearchResult, TimelineMinute, LOCAL_DEVICE_ID,
+    VISION_CAPTURE,
+};
+pub use diagnostics::Diagnostics;
+#[cfg(feature = "encryption")]
+pub use encryption::{
+    decrypt_file, encrypt_chunk_dir, encrypt_database, encrypt_file, is_encrypted,
+    EncryptedStorage, EncryptionKey,
+};
+pub use entities::{normalize_entity, Entity, EntityExtractor, EntityKind, EntitySummary};
+pub use events::{Event, EventKind, EventLog};
+pub use export::ExportFormat;
+#[cfg(feature = "grpc")]
+pub use grpc::{proto, serve_grpc, GrpcService};
+pub use health::{
+    AudioDeviceHealth, HealthMonitor, HealthThresholds, Subsystem, SubsystemError,
+    SubsystemHealth, WriteStats,
+};
+#[cfg(feature = "recording")]
+pub use idle::{IdleMonitor, IDLE_BUCKET_ID};
+pub use live_settings::{
+    load_config_file, max_log_level, monitor_config, set_log_level, watch_config_file, ConfigFile,
+    DigestConfig, LiveSettings, MonitorConfig,
+};
 pub use logs::MultiWriter;
+pub use mcp::{McpServer, MCP_PROTOCOL_VERSION};
+pub use memory_storage::MemoryStorage;
+#[cfg(feature = "recording")]
+pub use notifications::NotificationMonitor;
+pub use plugin::{ApiPlugin, ApiPluginLayer, ApiPlugins, RequestSummary};
+pub use processor::{ContentProcessor, ContentProcessors, ProcessedStorage};
+#[cfg(feature = "integrations")]
+pub use processor::ProcessedImport;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStorage;
+pub use rate_limit::{ApiLimitLayer, ApiLimitService, ApiLimits};
+#[cfg(feature = "recording")]
+pub use replay::{replay_archive, ReplaySummary};
+pub use recovery::{recover_chunks, RecoverySummary};
 pub use resource_monitor::{ResourceMonitor, RestartSignal};
+pub use rules::{run_rule_checks, NewRule, Rule, RuleCursor, RuleEngine};
+pub use search_query::{
+    parse_search_query, Highlight, SearchQueryError, HIGHLIGHT_END, HIGHLIGHT_START,
+    SEARCH_QUERY_SYNTAX,
+};
+pub use server::export;
 pub use server::health_check;
 pub use server::AppState;
+pub use server::AskResponse;
 pub use server::HealthCheckResponse;
 pub use server::Server;
-pub use video::VideoCapture;
\ No newline at end of file
+pub use server::{api_routes, create_router, storage_routes, versioned_routes, API_VERSION};
+pub use sessions::{
+    audio_device_of, run_session_segmentation, segment_sessions, SessionBounds, TranscribedChunk,
+    TranscriptSession,
+};
+pub use shutdown::{Shutdown, ShutdownGuard};
+pub use spellcheck::{SpellChecker, SpellDictionary};
+pub use storage::{open_storage, ApiStorage, AudioRecord, FrameRecord, MirroredStorage, Storage};
+pub use tiering::{ensure_local, offload_chunks, run_offload, OffloadSummary};
+pub use timeline::{local_day_range, timeline_segments, TimelineSegment, TimelineState};
+#[cfg(feature = "recording")]
+pub use video::VideoCapture;
+#[cfg(feature = "recording")]
+pub use video_encoding::{VideoCodec, VideoEncoder, VideoEncoding};
+pub use vision_status::{MonitorStatus, VisionStatus};
+
+#[cfg(feature = "audio")]
+pub use screenpipe_audio::{
+    AudioDevice, AudioDeviceConfig, AudioDeviceInfo, AudioDeviceKind, AudioEncoding, AudioFormat,
+    CloudSttConfig, CloudSttProvider, DeviceControl, DeviceType, TranscriptionLanguage,
+    TranscriptionLanguages, WhisperModelConfig, WhisperModelSize,
+};
+pub use screenpipe_core::{
+    Accelerator, CircuitBreakerStatus, CircuitState, Gpu, TranscriptionSegment,
+};
+pub use screenpipe_vision::{CaptureResult, CaptureTrigger, OcrEngine, OcrLanguages};
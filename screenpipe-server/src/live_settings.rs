@@ -0,0 +1,358 @@
+use crate::{ApiLimits, HealthThresholds};
+use anyhow::{anyhow, Result};
+use chrono::NaiveTime;
+use log::{info, warn, LevelFilter};
+use screenpipe_vision::CaptureCrop;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+
+/// Settings applied while recording, without a restart. The config file overrides the
+/// defaults of the command line and is watched for changes, flags given explicitly win.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveSettings {
+    pub fps: f64,
+    /// Frames of these apps are neither recorded nor OCRed, compared case-insensitively
+    pub ignored_apps: Vec<String>,
+    /// Storage quota in GB, unlimited if `None`
+    pub max_disk_usage_gb: Option<f64>,
+    pub log_level: LevelFilter,
+}
+
+/// Defaults of the command line
+impl Default for LiveSettings {
+    fn default() -> Self {
+        LiveSettings {
+            fps: 1.0,
+            ignored_apps: Vec::new(),
+            max_disk_usage_gb: None,
+            log_level: LevelFilter::Info,
+        }
+    }
+}
+
+impl LiveSettings {
+    pub fn is_ignored(&self, app_name: &str) -> bool {
+        self.ignored_apps
+            .iter()
+            .any(|ignored| ignored.eq_ignore_ascii_case(app_name))
+    }
+}
+
+/// Content of the TOML config file, every key is optional and overrides the default of its
+/// flag, not a flag given on the command line. Only the `LiveSettings` keys, fps,
+/// ignored_apps, max_disk_usage and log_level, are applied while running, the others on start.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub fps: Option<f64>,
+    /// Same as --idle-fps
+    pub idle_fps: Option<f64>,
+    pub ignored_apps: Option<Vec<String>>,
+    /// GB
+    pub max_disk_usage: Option<f64>,
+    pub log_level: Option<String>,
+    pub port: Option<u16>,
+    pub grpc_port: Option<u16>,
+    pub disable_audio: Option<bool>,
+    /// Same as --capture-clipboard
+    pub capture_clipboard: Option<bool>,
+    /// Same as --capture-notifications
+    pub capture_notifications: Option<bool>,
+    /// Same as --audio-device
+    pub audio_devices: Option<Vec<String>>,
+    /// Same as --follow-default-audio
+    pub follow_default_audio: Option<bool>,
+    /// `[audio_device_configs."<device>"]` tables, capture settings of each audio device by
+    /// name, same as `PUT /audio/devices/:id/config`
+    #[cfg(feature = "audio")]
+    pub audio_device_configs: Option<HashMap<String, screenpipe_audio::AudioDeviceConfig>>,
+    /// Same values as --ocr-engine, e.g. "apple-native"
+    pub ocr_engine: Option<String>,
+    pub ocr_languages: Option<Vec<String>>,
+    /// Same as --app-ocr-languages, the languages of each app by name
+    pub app_ocr_languages: Option<HashMap<String, Vec<String>>>,
+    pub offload_to: Option<String>,
+    pub offload_after_days: Option<usize>,
+    pub cloud_audio_api_key: Option<String>,
+    pub anomaly_webhook_url: Option<String>,
+    /// `[[monitors]]` tables, settings of each monitor
+    pub monitors: Option<Vec<MonitorConfig>>,
+    /// `[digest]` table, the summary of the day posted to Slack or Discord
+    pub digest: Option<DigestConfig>,
+    /// `[cloud_ocr]` table, the OCR service of --ocr-engine cloud
+    #[cfg(feature = "cloud")]
+    pub cloud_ocr: Option<screenpipe_vision::CloudOcrConfig>,
+    /// `[api_limits]` table, rate, body and query string limits of the API
+    pub api_limits: Option<ApiLimits>,
+    /// `[health]` table, when `/health` calls the recording stale or degraded
+    pub health: Option<HealthThresholds>,
+}
+
+/// Summary of the day, app usage, transcriptions and the mentions of the flagged keywords,
+/// posted to the incoming webhooks of Slack or Discord channels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DigestConfig {
+    pub slack_webhook_url: Option<String>,
+    pub discord_webhook_url: Option<String>,
+    /// Local time it is sent at, "HH:MM", 18:00 by default
+    pub time: Option<String>,
+    /// Listed with where they were seen or heard, searched like `/search?q=`
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+impl DigestConfig {
+    pub fn send_time(&self) -> Result<NaiveTime> {
+        match &self.time {
+            Some(time) => NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| anyhow!("invalid digest time {:?}, expected HH:MM", time)),
+            None => Ok(NaiveTime::from_hms_opt(18, 0, 0).unwrap()),
+        }
+    }
+
+    /// Webhook URL of "slack" or "discord"
+    pub fn webhook_url(&self, platform: &str) -> Option<&str> {
+        match platform {
+            "slack" => self.slack_webhook_url.as_deref(),
+            "discord" => self.discord_webhook_url.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Capture settings of the monitor named `name`, compared case-insensitively. Monitors
+/// without settings are enabled and use `fps`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonitorConfig {
+    pub name: String,
+    /// Disabled monitors are never recorded, the first enabled one is
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Overrides `fps` for this monitor
+    pub fps: Option<f64>,
+    /// Margins cut off every frame, "<top>,<right>,<bottom>,<left>" in pixels or percents,
+    /// e.g. "0,0,40,0" to leave out a ticker at the bottom, see `CaptureCrop`
+    pub crop: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl MonitorConfig {
+    pub fn matches(&self, monitor_name: &str) -> bool {
+        self.name.trim().eq_ignore_ascii_case(monitor_name.trim())
+    }
+
+    pub fn capture_crop(&self) -> Result<Option<CaptureCrop>> {
+        self.crop
+            .as_deref()
+            .map(|crop| {
+                crop.parse()
+                    .map_err(|e| anyhow!("invalid crop of monitor {}: {}", self.name, e))
+            })
+            .transpose()
+    }
+}
+
+/// Settings of `monitor_name` in `monitors`, `None` if it has none
+pub fn monitor_config<'a>(
+    monitors: &'a [MonitorConfig],
+    monitor_name: &str,
+) -> Option<&'a MonitorConfig> {
+    monitors
+        .iter()
+        .find(|monitor| monitor.matches(monitor_name))
+}
+
+impl ConfigFile {
+    /// The config file at `path`, empty if there is no file
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ConfigFile::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let file: ConfigFile = toml::from_str(&content)?;
+        if file.offload_after_days == Some(0) {
+            return Err(anyhow!("offload_after_days must be at least 1"));
+        }
+        if file.idle_fps.map_or(false, |fps| fps <= 0.0) {
+            return Err(anyhow!("idle_fps must be greater than 0"));
+        }
+        if file.follow_default_audio == Some(true) && file.audio_devices.is_some() {
+            return Err(anyhow!(
+                "follow_default_audio can't be combined with audio_devices"
+            ));
+        }
+        #[cfg(feature = "audio")]
+        for (device, config) in file.audio_device_configs.iter().flatten() {
+            config
+                .validate()
+                .map_err(|e| anyhow!("invalid config of audio device {}: {}", device, e))?;
+        }
+        for monitor in file.monitors.iter().flatten() {
+            if monitor.fps.map_or(false, |fps| fps <= 0.0) {
+                return Err(anyhow!(
+                    "fps of monitor {} must be greater than 0",
+                    monitor.name
+                ));
+            }
+            monitor.capture_crop()?;
+        }
+        if let Some(api_limits) = &file.api_limits {
+            api_limits.validate()?;
+        }
+        if let Some(health) = &file.health {
+            health.validate()?;
+        }
+        if let Some(digest) = &file.digest {
+            digest.send_time()?;
+            if digest.slack_webhook_url.is_none() && digest.discord_webhook_url.is_none() {
+                return Err(anyhow!(
+                    "digest needs slack_webhook_url or discord_webhook_url"
+                ));
+            }
+        }
+        Ok(file)
+    }
+
+    /// Keys applied on start only, which need a restart when they change
+    fn startup_keys(&self) -> ConfigFile {
+        ConfigFile {
+            fps: None,
+            ignored_apps: None,
+            max_disk_usage: None,
+            log_level: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// `base` overridden by the config file at `path`, `base` as is if there is no file. The
+/// keys of `flags`, given on the command line, win over the file. A key removed from the
+/// file goes back to its `base` value.
+pub fn load_config_file(
+    path: &Path,
+    base: &LiveSettings,
+    flags: &ConfigFile,
+) -> Result<LiveSettings> {
+    apply_config_file(&ConfigFile::read(path)?, base, flags)
+}
+
+fn apply_config_file(
+    file: &ConfigFile,
+    base: &LiveSettings,
+    flags: &ConfigFile,
+) -> Result<LiveSettings> {
+    let settings = apply_live_keys(file, base.clone())?;
+    apply_live_keys(flags, settings)
+}
+
+fn apply_live_keys(file: &ConfigFile, mut settings: LiveSettings) -> Result<LiveSettings> {
+    if let Some(fps) = file.fps {
+        if fps <= 0.0 {
+            return Err(anyhow!("fps must be greater than 0"));
+        }
+        settings.fps = fps;
+    }
+    if let Some(ignored_apps) = &file.ignored_apps {
+        settings.ignored_apps = ignored_apps.clone();
+    }
+    if let Some(max_disk_usage) = file.max_disk_usage {
+        if max_disk_usage <= 0.0 {
+            return Err(anyhow!("max_disk_usage must be greater than 0"));
+        }
+        settings.max_disk_usage_gb = Some(max_disk_usage);
+    }
+    if let Some(log_level) = &file.log_level {
+        settings.log_level = log_level
+            .parse()
+            .map_err(|_| anyhow!("invalid log_level {:?}", log_level))?;
+    }
+    Ok(settings)
+}
+
+/// Reloads the config file whenever it's modified and publishes the new settings on
+/// `settings`, receivers are notified through `watch::Receiver::changed`. `flags` win over
+/// the file, see `load_config_file`. The log level is applied here, see `set_log_level`.
+/// An invalid file is ignored, the previous settings are kept. Changes of the keys applied
+/// on start are only logged.
+pub async fn watch_config_file(
+    path: PathBuf,
+    base: LiveSettings,
+    flags: ConfigFile,
+    settings: watch::Sender<LiveSettings>,
+    poll_interval: Duration,
+) {
+    let mut last_modified = modified_time(&path);
+    let mut startup_keys = ConfigFile::read(&path)
+        .map(|file| file.startup_keys())
+        .unwrap_or_default();
+    loop {
+        tokio::time::sleep(poll_interval).await;
+        let modified = modified_time(&path);
+        if modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        let file = match ConfigFile::read(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Ignoring invalid config file {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let new_settings = match apply_config_file(&file, &base, &flags) {
+            Ok(new_settings) => new_settings,
+            Err(e) => {
+                warn!("Ignoring invalid config file {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if file.startup_keys() != startup_keys {
+            warn!(
+                "Settings of {} applied on start changed, restart screenpipe to apply them",
+                path.display()
+            );
+            startup_keys = file.startup_keys();
+        }
+        if *settings.borrow() == new_settings {
+            continue;
+        }
+        info!("Config changed, now using {:?}", new_settings);
+        set_log_level(new_settings.log_level);
+        settings.send_replace(new_settings);
+    }
+}
+
+/// Applies the log level of the settings, never below the one asked for by `RUST_LOG`
+pub fn set_log_level(level: LevelFilter) {
+    log::set_max_level(max_log_level(
+        level,
+        std::env::var("RUST_LOG").ok().as_deref(),
+    ));
+}
+
+/// Highest of `level` and the levels of the `rust_log` filters
+pub fn max_log_level(level: LevelFilter, rust_log: Option<&str>) -> LevelFilter {
+    let env_level = rust_log
+        .map(|filters| {
+            env_logger::Builder::new()
+                .parse_filters(filters)
+                .build()
+                .filter()
+        })
+        .unwrap_or(LevelFilter::Off);
+    level.max(env_level)
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
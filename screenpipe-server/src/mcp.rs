@@ -0,0 +1,360 @@
+use crate::db::search_syntax_error;
+use crate::search_query::SEARCH_QUERY_SYNTAX;
+use crate::{ContentType, DatabaseManager, SearchResult};
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, error, info};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Revision of the Model Context Protocol implemented
+pub const MCP_PROTOCOL_VERSION: &str = "2024-11-05";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+const MAX_SEARCH_LIMIT: u32 = 100;
+/// Characters of text returned per result, long OCR pages would fill the context of the
+/// model otherwise
+const MAX_TEXT_CHARS: usize = 2000;
+/// Frames of the same app closer than this are one span of the timeline
+const TIMELINE_GAP_SECS: i64 = 300;
+const MAX_TIMELINE_TRANSCRIPTIONS: u32 = 200;
+
+#[derive(Deserialize)]
+struct SearchArguments {
+    #[serde(default)]
+    query: String,
+    #[serde(default)]
+    content_type: ContentType,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    app_name: Option<String>,
+    limit: Option<u32>,
+}
+
+/// Time range of a tool, the last 24 hours by default
+#[derive(Deserialize)]
+struct RangeArguments {
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+}
+
+impl RangeArguments {
+    fn range(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let end = self.end_time.unwrap_or_else(Utc::now);
+        let start = self.start_time.unwrap_or(end - Duration::hours(24));
+        (start, end)
+    }
+}
+
+/// Failure of a tool call, reported to the model rather than as a protocol error
+struct ToolError(String);
+
+impl From<sqlx::Error> for ToolError {
+    fn from(e: sqlx::Error) -> Self {
+        ToolError(search_syntax_error(&e).unwrap_or_else(|| format!("Database error: {}", e)))
+    }
+}
+
+fn parse_arguments<T: for<'de> Deserialize<'de>>(arguments: Value) -> Result<T, ToolError> {
+    serde_json::from_value(arguments).map_err(|e| ToolError(format!("Invalid arguments: {}", e)))
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_TEXT_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn time_property(description: &str) -> Value {
+    json!({"type": "string", "format": "date-time", "description": description})
+}
+
+fn tool_definitions() -> Value {
+    json!([
+        {
+            "name": "search_screen_history",
+            "description": format!("Full-text search of what was on the user's screen (OCR), what was said (audio transcriptions), what was copied (clipboard) and the notifications received, most recent first. Query syntax:\n{}", SEARCH_QUERY_SYNTAX),
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "Words to search, empty for everything in the time range"},
+                    "content_type": {"type": "string", "enum": ["all", "ocr", "audio", "clipboard", "notification"], "default": "all"},
+                    "start_time": time_property("Only results after this RFC 3339 time"),
+                    "end_time": time_property("Only results before this RFC 3339 time"),
+                    "app_name": {"type": "string", "description": "Only screen text of this app, e.g. Slack"},
+                    "limit": {"type": "integer", "minimum": 1, "maximum": MAX_SEARCH_LIMIT, "default": DEFAULT_SEARCH_LIMIT}
+                }
+            }
+        },
+        {
+            "name": "get_timeline",
+            "description": "What the user did in a time range, oldest first: the apps on screen as spans of time and what was said. Defaults to the last 24 hours.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "start_time": time_property("Start of the range, RFC 3339"),
+                    "end_time": time_property("End of the range, RFC 3339")
+                }
+            }
+        },
+        {
+            "name": "get_app_usage",
+            "description": "Apps on screen in a time range with the share of recorded frames of each, most used first. Defaults to the last 24 hours.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "start_time": time_property("Start of the range, RFC 3339"),
+                    "end_time": time_property("End of the range, RFC 3339")
+                }
+            }
+        }
+    ])
+}
+
+/// Model Context Protocol server over the recordings, so MCP clients such as Claude
+/// Desktop can search them. Speaks JSON-RPC with one message per line, see `serve`.
+pub struct McpServer {
+    db: Arc<DatabaseManager>,
+}
+
+impl McpServer {
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        McpServer { db }
+    }
+
+    /// Answers the messages read from `reader` on `writer` until `reader` ends, e.g. stdin
+    /// and stdout with the stdio transport
+    pub async fn serve<R, W>(&self, reader: R, mut writer: W) -> std::io::Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        info!("Serving MCP, protocol {}", MCP_PROTOCOL_VERSION);
+        let mut lines = reader.lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str(&line) {
+                Ok(message) => self.handle(message).await,
+                Err(e) => Some(error_response(
+                    Value::Null,
+                    PARSE_ERROR,
+                    format!("Parse error: {}", e),
+                )),
+            };
+            if let Some(response) = response {
+                writer.write_all(response.to_string().as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                writer.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Response to a JSON-RPC message, `None` for notifications
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            return Some(error_response(
+                message.get("id").cloned().unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                "Not a JSON-RPC request".to_string(),
+            ));
+        };
+        // Notifications, e.g. notifications/initialized, have no id and get no response
+        let id = message.get("id").cloned()?;
+        debug!("MCP request {}", method);
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => json!({
+                "protocolVersion": MCP_PROTOCOL_VERSION,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "screenpipe", "version": env!("CARGO_PKG_VERSION")},
+            }),
+            "ping" => json!({}),
+            "tools/list" => json!({"tools": tool_definitions()}),
+            "tools/call" => {
+                let Some(name) = params.get("name").and_then(Value::as_str) else {
+                    return Some(error_response(
+                        id,
+                        INVALID_PARAMS,
+                        "Missing tool name".to_string(),
+                    ));
+                };
+                let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+                let output = match name {
+                    "search_screen_history" => self.search(arguments).await,
+                    "get_timeline" => self.timeline(arguments).await,
+                    "get_app_usage" => self.app_usage(arguments).await,
+                    _ => {
+                        return Some(error_response(
+                            id,
+                            INVALID_PARAMS,
+                            format!("Unknown tool: {}", name),
+                        ))
+                    }
+                };
+                match output {
+                    Ok(output) => json!({
+                        "content": [{"type": "text", "text": output.to_string()}],
+                        "isError": false,
+                    }),
+                    Err(ToolError(message)) => {
+                        error!("MCP tool {} failed: {}", name, message);
+                        json!({
+                            "content": [{"type": "text", "text": message}],
+                            "isError": true,
+                        })
+                    }
+                }
+            }
+            _ => {
+                return Some(error_response(
+                    id,
+                    METHOD_NOT_FOUND,
+                    format!("Method not found: {}", method),
+                ))
+            }
+        };
+        Some(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+    }
+
+    async fn search(&self, arguments: Value) -> Result<Value, ToolError> {
+        let arguments: SearchArguments = parse_arguments(arguments)?;
+        let limit = arguments
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT);
+        let results = self
+            .db
+            .search(
+                &arguments.query,
+                arguments.content_type,
+                limit,
+                0,
+                arguments.start_time,
+                arguments.end_time,
+                arguments.app_name.as_deref(),
+                None,
+                None,
+                None,
+            )
+            .await?;
+        let results: Vec<Value> = results
+            .into_iter()
+            .map(|result| match result {
+                SearchResult::OCR(ocr) => json!({
+                    "type": "ocr",
+                    "timestamp": ocr.timestamp,
+                    "app_name": ocr.app_name,
+                    "text": truncate(&ocr.ocr_text),
+                    "frame_id": ocr.frame_id,
+                }),
+                SearchResult::Audio(audio) => json!({
+                    "type": "audio",
+                    "timestamp": audio.timestamp,
+                    "text": truncate(&audio.transcription),
+                    "language": audio.language,
+                    "audio_chunk_id": audio.audio_chunk_id,
+                }),
+                SearchResult::Clipboard(clipboard) => json!({
+                    "type": "clipboard",
+                    "timestamp": clipboard.timestamp,
+                    "app_name": clipboard.app_name,
+                    "text": truncate(&clipboard.text),
+                }),
+                SearchResult::Notification(notification) => json!({
+                    "type": "notification",
+                    "timestamp": notification.timestamp,
+                    "app_name": notification.app_name,
+                    "title": notification.title,
+                    "text": truncate(&notification.body),
+                }),
+            })
+            .collect();
+        Ok(json!({"results": results}))
+    }
+
+    async fn timeline(&self, arguments: Value) -> Result<Value, ToolError> {
+        let (start, end) = parse_arguments::<RangeArguments>(arguments)?.range();
+        let mut spans: Vec<(String, DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+        for (timestamp, app_name) in self.db.get_app_timeline(start, end).await? {
+            match spans.last_mut() {
+                Some((app, _, span_end))
+                    if *app == app_name
+                        && (timestamp - *span_end).num_seconds() <= TIMELINE_GAP_SECS =>
+                {
+                    *span_end = timestamp;
+                }
+                _ => spans.push((app_name, timestamp, timestamp)),
+            }
+        }
+        let mut transcriptions = self
+            .db
+            .search(
+                "",
+                ContentType::Audio,
+                MAX_TIMELINE_TRANSCRIPTIONS,
+                0,
+                Some(start),
+                Some(end),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        transcriptions.reverse();
+        Ok(json!({
+            "start_time": start,
+            "end_time": end,
+            "apps": spans
+                .into_iter()
+                .map(|(app_name, start, end)| {
+                    json!({"app_name": app_name, "start": start, "end": end})
+                })
+                .collect::<Vec<_>>(),
+            "transcriptions": transcriptions
+                .into_iter()
+                .filter_map(|result| match result {
+                    SearchResult::Audio(audio) => Some(json!({
+                        "timestamp": audio.timestamp,
+                        "text": truncate(&audio.transcription),
+                    })),
+                    SearchResult::OCR(_)
+                    | SearchResult::Clipboard(_)
+                    | SearchResult::Notification(_) => None,
+                })
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    async fn app_usage(&self, arguments: Value) -> Result<Value, ToolError> {
+        let (start, end) = parse_arguments::<RangeArguments>(arguments)?.range();
+        let usage = self.db.get_app_usage(start, end).await?;
+        let total: i64 = usage.iter().map(|(_, frames)| frames).sum();
+        Ok(json!({
+            "start_time": start,
+            "end_time": end,
+            "apps": usage
+                .into_iter()
+                .map(|(app_name, frames)| json!({
+                    "app_name": app_name,
+                    "frames": frames,
+                    "percent": (frames as f64 * 1000.0 / total as f64).round() / 10.0,
+                }))
+                .collect::<Vec<_>>(),
+        }))
+    }
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
@@ -0,0 +1,878 @@
+use crate::db::{fts_query, heatmap_buckets, sort_by_recency, sort_by_relevance, FacetTally};
+use crate::{
+    ActivityEvent, ApiStorage, AudioResult, ContentType, Event, EventKind, HeatmapBucket,
+    IngestBatch, IngestBucket, IngestSummary, Mark, OCRResult, SearchCursor, SearchFacets,
+    SearchResult, TimelineMinute, LOCAL_DEVICE_ID,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use screenpipe_core::TranscriptionSegment;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// Words the mock search ignores, they are operators of `SEARCH_QUERY_SYNTAX`
+const OPERATORS: [&str; 4] = ["AND", "OR", "NOT", "NEAR"];
+
+/// `ApiStorage` in memory, to test the API handlers without SQLite. Searches go through
+/// the same query parser, then match the frames and transcriptions containing every word
+/// of the query, case insensitive, the relevance counts the query words instead of BM25.
+/// Operators, snippets, highlights, OCR confidence and word boxes are left out, and no
+/// entities are extracted.
+#[derive(Default)]
+pub struct MemoryStorage {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Default)]
+struct MemoryData {
+    frames: Vec<MemoryFrame>,
+    transcriptions: Vec<MemoryTranscription>,
+    activity_events: Vec<ActivityEvent>,
+    marks: Vec<Mark>,
+    events: Vec<Event>,
+    capture_states: BTreeMap<String, bool>,
+    /// Error of every call, see `MemoryStorage::fail_with`
+    failure: Option<String>,
+}
+
+#[derive(Clone)]
+struct MemoryFrame {
+    id: i64,
+    device_id: String,
+    timestamp: DateTime<Utc>,
+    file_path: String,
+    offset_index: i64,
+    app_name: String,
+    text: String,
+    ocr_engine: String,
+}
+
+#[derive(Clone)]
+struct MemoryTranscription {
+    id: i64,
+    device_id: String,
+    timestamp: DateTime<Utc>,
+    file_path: String,
+    offset_index: i64,
+    transcription: String,
+    transcription_engine: String,
+    /// Seconds of audio
+    duration: Option<f64>,
+    language: Option<String>,
+    segments: Vec<TranscriptionSegment>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+
+    /// Stores a frame recorded by this machine, returns its id
+    pub fn add_frame(&self, timestamp: DateTime<Utc>, app_name: &str, text: &str) -> i64 {
+        let mut data = self.data.lock().unwrap();
+        let id = data.frames.len() as i64 + 1;
+        data.frames.push(MemoryFrame {
+            id,
+            device_id: LOCAL_DEVICE_ID.to_string(),
+            timestamp,
+            file_path: format!("memory/video_{}.mp4", id),
+            offset_index: 0,
+            app_name: app_name.to_string(),
+            text: text.to_string(),
+            ocr_engine: "Tesseract".to_string(),
+        });
+        id
+    }
+
+    /// Stores a transcription recorded by this machine, returns the id of its audio chunk
+    pub fn add_transcription(
+        &self,
+        timestamp: DateTime<Utc>,
+        transcription: &str,
+        language: Option<&str>,
+    ) -> i64 {
+        let mut data = self.data.lock().unwrap();
+        let id = data.transcriptions.len() as i64 + 1;
+        data.transcriptions.push(MemoryTranscription {
+            id,
+            device_id: LOCAL_DEVICE_ID.to_string(),
+            timestamp,
+            file_path: format!("memory/audio_{}.mp4", id),
+            offset_index: 0,
+            transcription: transcription.to_string(),
+            transcription_engine: "Whisper".to_string(),
+            duration: None,
+            language: language.map(str::to_string),
+            segments: Vec::new(),
+        });
+        id
+    }
+
+    pub fn add_activity_event(&self, event: ActivityEvent) {
+        self.data.lock().unwrap().activity_events.push(event);
+    }
+
+    /// Stores an event, returns its id
+    pub fn add_event(&self, timestamp: DateTime<Utc>, kind: EventKind, message: &str) -> i64 {
+        let mut data = self.data.lock().unwrap();
+        let id = data.events.len() as i64 + 1;
+        data.events.push(Event {
+            id,
+            timestamp,
+            kind,
+            message: message.to_string(),
+            data: None,
+        });
+        id
+    }
+
+    /// Last state saved by `set_capture_state`
+    pub fn capture_state(&self, device: &str) -> Option<bool> {
+        self.data
+            .lock()
+            .unwrap()
+            .capture_states
+            .get(device)
+            .copied()
+    }
+
+    /// Every following call fails with `message` as a database error
+    pub fn fail_with(&self, message: &str) {
+        self.data.lock().unwrap().failure = Some(message.to_string());
+    }
+
+    fn data(&self) -> Result<MutexGuard<'_, MemoryData>, sqlx::Error> {
+        let data = self.data.lock().unwrap();
+        if let Some(message) = &data.failure {
+            return Err(sqlx::Error::Protocol(message.clone()));
+        }
+        Ok(data)
+    }
+
+    /// Matching results, `limit` of each content type after `offset` and the cursor, most
+    /// recent first
+    #[allow(clippy::too_many_arguments)]
+    fn find(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: usize,
+        offset: usize,
+        cursor: &SearchCursor,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        let words = query_words(query)?;
+        let data = self.data()?;
+        // No entities are extracted, nothing names one
+        if entity.is_some() {
+            return Ok(Vec::new());
+        }
+        let in_range = |timestamp: &DateTime<Utc>| {
+            start_time.map_or(true, |start| *timestamp >= start)
+                && end_time.map_or(true, |end| *timestamp <= end)
+        };
+        let before = |after: &Option<(String, i64)>, timestamp: &str, id: i64| match after {
+            Some((after_timestamp, after_id)) => {
+                (timestamp, id) < (after_timestamp.as_str(), *after_id)
+            }
+            None => true,
+        };
+        let mut results = Vec::new();
+
+        // If app_name is specified, only search OCR content. Clipboard copies and notifications
+        // aren't kept.
+        if app_name.is_some() || matches!(content_type, ContentType::All | ContentType::OCR) {
+            let mut frames: Vec<(String, &MemoryFrame)> = data
+                .frames
+                .iter()
+                .map(|frame| (sort_timestamp(&frame.timestamp), frame))
+                .filter(|(sort_timestamp, frame)| {
+                    in_range(&frame.timestamp)
+                        && app_name.map_or(true, |app_name| frame.app_name == app_name)
+                        && engine.map_or(true, |engine| frame.ocr_engine == engine)
+                        && contains_all(&frame.text, &words)
+                        && before(&cursor.ocr, sort_timestamp.as_str(), frame.id)
+                })
+                .collect();
+            frames.sort_by(|(a, frame_a), (b, frame_b)| (b, frame_b.id).cmp(&(a, frame_a.id)));
+            let best = frames
+                .iter()
+                .map(|(_, frame)| occurrences(&frame.text, &words))
+                .max();
+            results.extend(frames.into_iter().skip(offset).take(limit).map(
+                |(sort_timestamp, frame)| {
+                    SearchResult::OCR(OCRResult {
+                        frame_id: frame.id,
+                        ocr_text: frame.text.clone(),
+                        text_json: String::new(),
+                        new_text_json_vs_previous_frame: String::new(),
+                        raw_data_output_from_ocr: String::new(),
+                        timestamp: frame.timestamp,
+                        file_path: frame.file_path.clone(),
+                        offset_index: frame.offset_index,
+                        app_name: frame.app_name.clone(),
+                        ocr_engine: frame.ocr_engine.clone(),
+                        confidence: None,
+                        words_json: None,
+                        region: None,
+                        region_json: None,
+                        snippet: String::new(),
+                        relevance: Some(relevance(&frame.text, &words, best)),
+                        highlights: Vec::new(),
+                        highlighted_text: String::new(),
+                        sort_timestamp,
+                        ocr_id: frame.id,
+                    })
+                },
+            ));
+        }
+        if app_name.is_none() && matches!(content_type, ContentType::All | ContentType::Audio) {
+            let mut transcriptions: Vec<(String, &MemoryTranscription)> = data
+                .transcriptions
+                .iter()
+                .map(|audio| (sort_timestamp(&audio.timestamp), audio))
+                .filter(|(sort_timestamp, audio)| {
+                    in_range(&audio.timestamp)
+                        && engine.map_or(true, |engine| audio.transcription_engine == engine)
+                        && contains_all(&audio.transcription, &words)
+                        && before(&cursor.audio, sort_timestamp.as_str(), audio.id)
+                })
+                .collect();
+            transcriptions
+                .sort_by(|(a, audio_a), (b, audio_b)| (b, audio_b.id).cmp(&(a, audio_a.id)));
+            let best = transcriptions
+                .iter()
+                .map(|(_, audio)| occurrences(&audio.transcription, &words))
+                .max();
+            results.extend(transcriptions.into_iter().skip(offset).take(limit).map(
+                |(sort_timestamp, audio)| {
+                    SearchResult::Audio(AudioResult {
+                        audio_chunk_id: audio.id,
+                        transcription: audio.transcription.clone(),
+                        timestamp: audio.timestamp,
+                        file_path: audio.file_path.clone(),
+                        offset_index: audio.offset_index,
+                        transcription_engine: audio.transcription_engine.clone(),
+                        language: audio.language.clone(),
+                        snippet: String::new(),
+                        relevance: Some(relevance(&audio.transcription, &words, best)),
+                        highlights: Vec::new(),
+                        highlighted_text: String::new(),
+                        segments: audio.segments.clone(),
+                        segments_json: String::new(),
+                        sort_timestamp,
+                        transcription_id: audio.id,
+                    })
+                },
+            ));
+        }
+
+        sort_by_recency(&mut results);
+        Ok(results)
+    }
+}
+
+/// Lowercase words the results must contain, after the query is validated like a real search
+fn query_words(query: &str) -> Result<Vec<String>, sqlx::Error> {
+    fts_query(query)?;
+    Ok(query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !OPERATORS.contains(word))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+fn contains_all(text: &str, words: &[String]) -> bool {
+    let text = text.to_lowercase();
+    words.iter().all(|word| text.contains(word.as_str()))
+}
+
+/// Times the words of the query appear in `text`, the mock BM25 score
+fn occurrences(text: &str, words: &[String]) -> usize {
+    let text = text.to_lowercase();
+    words
+        .iter()
+        .map(|word| text.matches(word.as_str()).count())
+        .sum()
+}
+
+/// Score of `text` over the `best` score of its content type, 1 without query words
+fn relevance(text: &str, words: &[String], best: Option<usize>) -> f64 {
+    match best {
+        Some(best) if best > 0 => occurrences(text, words) as f64 / best as f64,
+        _ => 1.0,
+    }
+}
+
+/// Sorts like the timestamp, the keyset cursor compares these strings
+fn sort_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+#[async_trait]
+impl ApiStorage for MemoryStorage {
+    async fn search(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        _min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        let mut results = self.find(
+            query,
+            content_type,
+            limit as usize,
+            offset as usize,
+            &SearchCursor::default(),
+            start_time,
+            end_time,
+            app_name,
+            entity,
+            engine,
+        )?;
+        results.truncate(limit as usize);
+        Ok(results)
+    }
+
+    async fn search_by_relevance(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        _min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        recency_half_life_hours: Option<f64>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        let mut results = self.find(
+            query,
+            content_type,
+            usize::MAX,
+            0,
+            &SearchCursor::default(),
+            start_time,
+            end_time,
+            app_name,
+            entity,
+            engine,
+        )?;
+        if let Some(half_life_hours) = recency_half_life_hours {
+            let now = Utc::now();
+            for result in &mut results {
+                let (timestamp, relevance) = match result {
+                    SearchResult::OCR(ocr) => (ocr.timestamp, &mut ocr.relevance),
+                    SearchResult::Audio(audio) => (audio.timestamp, &mut audio.relevance),
+                    _ => continue,
+                };
+                let age_hours = (now - timestamp).num_seconds() as f64 / 3600.0;
+                *relevance = relevance.map(|score| score / (1.0 + age_hours / half_life_hours));
+            }
+        }
+        sort_by_relevance(&mut results);
+        Ok(results
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn search_page(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        _min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<(Vec<SearchResult>, Option<SearchCursor>), sqlx::Error> {
+        let cursor = cursor.cloned().unwrap_or_default();
+        // One more row than the page tells if there is a next page
+        let mut results = self.find(
+            query,
+            content_type,
+            limit as usize + 1,
+            0,
+            &cursor,
+            start_time,
+            end_time,
+            app_name,
+            entity,
+            engine,
+        )?;
+        let has_more = results.len() > limit as usize;
+        results.truncate(limit as usize);
+        if !has_more {
+            return Ok((results, None));
+        }
+        let mut next = cursor;
+        for result in &results {
+            match result {
+                SearchResult::OCR(ocr) => next.ocr = Some((ocr.sort_timestamp.clone(), ocr.ocr_id)),
+                SearchResult::Audio(audio) => {
+                    next.audio = Some((audio.sort_timestamp.clone(), audio.transcription_id))
+                }
+                SearchResult::Clipboard(clipboard) => {
+                    next.clipboard = Some((clipboard.sort_timestamp.clone(), clipboard.id))
+                }
+                SearchResult::Notification(notification) => {
+                    next.notification =
+                        Some((notification.sort_timestamp.clone(), notification.id))
+                }
+            }
+        }
+        Ok((results, Some(next)))
+    }
+
+    async fn count_search_results(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        _min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<usize, sqlx::Error> {
+        let results = self.find(
+            query,
+            content_type,
+            usize::MAX,
+            0,
+            &SearchCursor::default(),
+            start_time,
+            end_time,
+            app_name,
+            entity,
+            engine,
+        )?;
+        Ok(results.len())
+    }
+
+    async fn search_facets(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        _min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<SearchFacets, sqlx::Error> {
+        let results = self.find(
+            query,
+            content_type,
+            usize::MAX,
+            0,
+            &SearchCursor::default(),
+            start_time,
+            end_time,
+            app_name,
+            entity,
+            engine,
+        )?;
+        let data = self.data()?;
+        let mut tally = FacetTally::default();
+        for result in results {
+            let (content_type, app_name, timestamp, device) = match &result {
+                SearchResult::OCR(ocr) => (
+                    ContentType::OCR,
+                    Some(ocr.app_name.as_str()),
+                    ocr.timestamp,
+                    data.frames
+                        .iter()
+                        .find(|frame| frame.id == ocr.frame_id)
+                        .map(|frame| frame.device_id.as_str()),
+                ),
+                SearchResult::Audio(audio) => (
+                    ContentType::Audio,
+                    None,
+                    audio.timestamp,
+                    data.transcriptions
+                        .iter()
+                        .find(|transcription| transcription.id == audio.transcription_id)
+                        .map(|transcription| transcription.device_id.as_str()),
+                ),
+                // Not kept
+                SearchResult::Clipboard(_) | SearchResult::Notification(_) => continue,
+            };
+            tally.add(
+                content_type,
+                app_name,
+                &timestamp.format("%Y-%m-%d").to_string(),
+                device.unwrap_or(LOCAL_DEVICE_ID),
+                1,
+            );
+        }
+        Ok(tally.into_facets())
+    }
+
+    async fn fuzzy_search_query(&self, query: &str) -> Result<String, sqlx::Error> {
+        self.data()?;
+        Ok(query.to_string())
+    }
+
+    async fn get_latest_timestamps(
+        &self,
+    ) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>), sqlx::Error> {
+        let data = self.data()?;
+        let latest_frame = data
+            .frames
+            .iter()
+            .filter(|frame| frame.device_id == LOCAL_DEVICE_ID)
+            .map(|frame| frame.timestamp)
+            .max();
+        let latest_audio = data
+            .transcriptions
+            .iter()
+            .filter(|audio| audio.device_id == LOCAL_DEVICE_ID)
+            .map(|audio| audio.timestamp)
+            .max();
+        Ok((latest_frame, latest_audio))
+    }
+
+    async fn ingest_stats(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket_secs: i64,
+    ) -> Result<Vec<IngestBucket>, sqlx::Error> {
+        let data = self.data()?;
+        let first = start.timestamp().div_euclid(bucket_secs);
+        let last = end.timestamp().div_euclid(bucket_secs);
+        let mut buckets: Vec<IngestBucket> = (first..=last)
+            .map(|bucket| IngestBucket {
+                start: DateTime::from_timestamp(bucket * bucket_secs, 0).unwrap_or_default(),
+                frames: 0,
+                ocr_characters: 0,
+                transcription_seconds: 0.0,
+            })
+            .collect();
+        let bucket_of = |timestamp: &DateTime<Utc>| {
+            (timestamp.timestamp().div_euclid(bucket_secs) - first) as usize
+        };
+        for frame in data
+            .frames
+            .iter()
+            .filter(|f| f.timestamp >= start && f.timestamp <= end)
+        {
+            if let Some(stats) = buckets.get_mut(bucket_of(&frame.timestamp)) {
+                stats.frames += 1;
+                stats.ocr_characters += frame.text.chars().count() as i64;
+            }
+        }
+        for audio in data
+            .transcriptions
+            .iter()
+            .filter(|a| a.timestamp >= start && a.timestamp <= end)
+        {
+            if let Some(stats) = buckets.get_mut(bucket_of(&audio.timestamp)) {
+                stats.transcription_seconds += audio.duration.unwrap_or(0.0);
+            }
+        }
+        Ok(buckets)
+    }
+
+    async fn heatmap(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket_secs: i64,
+        utc_offset_secs: i64,
+    ) -> Result<Vec<HeatmapBucket>, sqlx::Error> {
+        let data = self.data()?;
+        let mut buckets = heatmap_buckets(start, end, bucket_secs, utc_offset_secs);
+        let first = (start.timestamp() + utc_offset_secs).div_euclid(bucket_secs);
+        let bucket_of = |timestamp: &DateTime<Utc>| {
+            ((timestamp.timestamp() + utc_offset_secs).div_euclid(bucket_secs) - first) as usize
+        };
+        for frame in data
+            .frames
+            .iter()
+            .filter(|f| f.timestamp >= start && f.timestamp <= end)
+        {
+            if let Some(heat) = buckets.get_mut(bucket_of(&frame.timestamp)) {
+                heat.frames += 1;
+                heat.ocr_characters += frame.text.chars().count() as i64;
+            }
+        }
+        for audio in data
+            .transcriptions
+            .iter()
+            .filter(|a| a.timestamp >= start && a.timestamp <= end)
+        {
+            if let Some(heat) = buckets.get_mut(bucket_of(&audio.timestamp)) {
+                heat.audio_seconds += audio.duration.unwrap_or(0.0);
+                heat.transcription_characters += audio.transcription.chars().count() as i64;
+            }
+        }
+        Ok(buckets)
+    }
+
+    async fn timeline(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TimelineMinute>, sqlx::Error> {
+        let data = self.data()?;
+        let first = start.timestamp().div_euclid(60);
+        let last = end.timestamp().div_euclid(60);
+        let covers = |minute: i64, from: i64, to: i64| {
+            minute >= from.div_euclid(60) && minute <= to.div_euclid(60)
+        };
+        Ok((first..=last)
+            .map(|minute| {
+                let frames: Vec<&MemoryFrame> = data
+                    .frames
+                    .iter()
+                    .filter(|f| {
+                        f.device_id == LOCAL_DEVICE_ID
+                            && f.timestamp >= start
+                            && f.timestamp <= end
+                            && f.timestamp.timestamp().div_euclid(60) == minute
+                    })
+                    .collect();
+                let mut app_frames: BTreeMap<&str, i64> = BTreeMap::new();
+                for frame in frames.iter().filter(|f| !f.app_name.is_empty()) {
+                    *app_frames.entry(&frame.app_name).or_default() += 1;
+                }
+                TimelineMinute {
+                    start: DateTime::from_timestamp(minute * 60, 0).unwrap_or_default(),
+                    app_name: app_frames
+                        .into_iter()
+                        .max_by_key(|(_, count)| *count)
+                        .map(|(app_name, _)| app_name.to_string()),
+                    frames: frames.len() as i64,
+                    // Transcriptions are their own audio chunk, timestamped at its end
+                    audio: data.transcriptions.iter().any(|audio| {
+                        let chunk_end = audio.timestamp.timestamp();
+                        audio.device_id == LOCAL_DEVICE_ID
+                            && audio.timestamp >= start
+                            && covers(
+                                minute,
+                                chunk_end - audio.duration.unwrap_or(0.0) as i64,
+                                chunk_end,
+                            )
+                    }),
+                    idle: data.activity_events.iter().any(|event| {
+                        let event_start = event.timestamp.timestamp();
+                        event.event_type == "afkstatus"
+                            && event.data["status"] == "afk"
+                            && covers(minute, event_start, event_start + event.duration as i64)
+                    }),
+                }
+            })
+            .collect())
+    }
+
+    async fn ingest(&self, batch: &IngestBatch) -> Result<IngestSummary, sqlx::Error> {
+        let mut data = self.data()?;
+        let mut summary = IngestSummary::default();
+        for frame in &batch.frames {
+            let exists = data.frames.iter().any(|stored| {
+                stored.device_id == batch.device_id
+                    && stored.timestamp == frame.timestamp
+                    && stored.text == frame.text
+            });
+            if exists {
+                summary.frames_skipped += 1;
+                continue;
+            }
+            let id = data.frames.len() as i64 + 1;
+            data.frames.push(MemoryFrame {
+                id,
+                device_id: batch.device_id.clone(),
+                timestamp: frame.timestamp,
+                file_path: frame.file_path.clone(),
+                offset_index: frame.offset_index,
+                app_name: frame.app_name.clone(),
+                text: frame.text.clone(),
+                ocr_engine: frame.ocr_engine.clone(),
+            });
+            summary.frames_inserted += 1;
+        }
+        for transcription in &batch.transcriptions {
+            let exists = data.transcriptions.iter().any(|stored| {
+                stored.device_id == batch.device_id
+                    && stored.timestamp == transcription.timestamp
+                    && stored.transcription == transcription.transcription
+            });
+            if exists {
+                summary.transcriptions_skipped += 1;
+                continue;
+            }
+            let id = data.transcriptions.len() as i64 + 1;
+            data.transcriptions.push(MemoryTranscription {
+                id,
+                device_id: batch.device_id.clone(),
+                timestamp: transcription.timestamp,
+                file_path: transcription.file_path.clone(),
+                offset_index: transcription.offset_index,
+                transcription: transcription.transcription.clone(),
+                transcription_engine: transcription.transcription_engine.clone(),
+                duration: transcription.duration,
+                language: transcription.language.clone(),
+                segments: transcription.segments.clone(),
+            });
+            summary.transcriptions_inserted += 1;
+        }
+        Ok(summary)
+    }
+
+    async fn get_activity_events(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        event_type: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ActivityEvent>, sqlx::Error> {
+        let data = self.data()?;
+        let mut events: Vec<ActivityEvent> = data
+            .activity_events
+            .iter()
+            .filter(|event| {
+                start.map_or(true, |start| event.timestamp >= start)
+                    && end.map_or(true, |end| event.timestamp <= end)
+                    && event_type.map_or(true, |event_type| event.event_type == event_type)
+            })
+            .cloned()
+            .collect();
+        events.sort_by_key(|event| event.timestamp);
+        events.truncate(limit as usize);
+        Ok(events)
+    }
+
+    async fn schema_version(&self) -> Result<Option<i64>, sqlx::Error> {
+        self.data()?;
+        Ok(None)
+    }
+
+    /// Transcriptions are their own audio chunk
+    async fn get_audio_chunk_path(&self, chunk_id: i64) -> Result<Option<String>, sqlx::Error> {
+        Ok(self
+            .data()?
+            .transcriptions
+            .iter()
+            .find(|audio| audio.id == chunk_id && audio.device_id == LOCAL_DEVICE_ID)
+            .map(|audio| audio.file_path.clone()))
+    }
+
+    async fn get_frame(&self, frame_id: i64) -> Result<Option<(String, i64)>, sqlx::Error> {
+        Ok(self
+            .data()?
+            .frames
+            .iter()
+            .find(|frame| frame.id == frame_id && frame.device_id == LOCAL_DEVICE_ID)
+            .map(|frame| (frame.file_path.clone(), frame.offset_index)))
+    }
+
+    async fn insert_mark(&self, name: &str, timestamp: DateTime<Utc>) -> Result<Mark, sqlx::Error> {
+        let mut data = self.data()?;
+        let mark = Mark {
+            id: data.marks.len() as i64 + 1,
+            name: name.to_string(),
+            timestamp,
+        };
+        data.marks.push(mark.clone());
+        Ok(mark)
+    }
+
+    async fn get_marks(
+        &self,
+        query: Option<&str>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: u32,
+    ) -> Result<Vec<Mark>, sqlx::Error> {
+        let query = query.map(str::to_lowercase);
+        let mut marks: Vec<Mark> = self
+            .data()?
+            .marks
+            .iter()
+            .filter(|mark| {
+                query
+                    .as_ref()
+                    .map_or(true, |query| mark.name.to_lowercase().contains(query))
+                    && start.map_or(true, |start| mark.timestamp >= start)
+                    && end.map_or(true, |end| mark.timestamp <= end)
+            })
+            .cloned()
+            .collect();
+        marks.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        marks.truncate(limit as usize);
+        Ok(marks)
+    }
+
+    async fn get_mark(&self, id: i64) -> Result<Option<Mark>, sqlx::Error> {
+        Ok(self.data()?.marks.iter().find(|mark| mark.id == id).cloned())
+    }
+
+    async fn set_capture_state(&self, device: &str, is_running: bool) -> Result<(), sqlx::Error> {
+        self.data()?
+            .capture_states
+            .insert(device.to_string(), is_running);
+        Ok(())
+    }
+
+    async fn get_events(
+        &self,
+        kinds: &[EventKind],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Event>, sqlx::Error> {
+        let mut events = matching_events(&self.data()?, kinds, start, end);
+        events.sort_by(|a, b| (b.timestamp, b.id).cmp(&(a.timestamp, a.id)));
+        Ok(events
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn count_events(
+        &self,
+        kinds: &[EventKind],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        Ok(matching_events(&self.data()?, kinds, start, end).len() as i64)
+    }
+}
+
+fn matching_events(
+    data: &MemoryData,
+    kinds: &[EventKind],
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Vec<Event> {
+    data.events
+        .iter()
+        .filter(|event| {
+            (kinds.is_empty() || kinds.contains(&event.kind))
+                && start.map_or(true, |start| event.timestamp >= start)
+                && end.map_or(true, |end| event.timestamp <= end)
+        })
+        .cloned()
+        .collect()
+}
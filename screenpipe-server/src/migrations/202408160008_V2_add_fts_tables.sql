@@ -0,0 +1,30 @@
+-- Full-text search indexes over OCR text and transcriptions, content is read from the source tables
+CREATE VIRTUAL TABLE IF NOT EXISTS ocr_text_fts USING fts5(text, content='ocr_text');
+CREATE VIRTUAL TABLE IF NOT EXISTS audio_transcriptions_fts USING fts5(transcription, content='audio_transcriptions', content_rowid='id');
+
+-- Index existing data
+INSERT INTO ocr_text_fts(ocr_text_fts) VALUES('rebuild');
+INSERT INTO audio_transcriptions_fts(audio_transcriptions_fts) VALUES('rebuild');
+
+-- Keep the indexes in sync
+CREATE TRIGGER IF NOT EXISTS ocr_text_fts_insert AFTER INSERT ON ocr_text BEGIN
+    INSERT INTO ocr_text_fts(rowid, text) VALUES (new.rowid, new.text);
+END;
+CREATE TRIGGER IF NOT EXISTS ocr_text_fts_delete AFTER DELETE ON ocr_text BEGIN
+    INSERT INTO ocr_text_fts(ocr_text_fts, rowid, text) VALUES ('delete', old.rowid, old.text);
+END;
+CREATE TRIGGER IF NOT EXISTS ocr_text_fts_update AFTER UPDATE OF text ON ocr_text BEGIN
+    INSERT INTO ocr_text_fts(ocr_text_fts, rowid, text) VALUES ('delete', old.rowid, old.text);
+    INSERT INTO ocr_text_fts(rowid, text) VALUES (new.rowid, new.text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS audio_transcriptions_fts_insert AFTER INSERT ON audio_transcriptions BEGIN
+    INSERT INTO audio_transcriptions_fts(rowid, transcription) VALUES (new.id, new.transcription);
+END;
+CREATE TRIGGER IF NOT EXISTS audio_transcriptions_fts_delete AFTER DELETE ON audio_transcriptions BEGIN
+    INSERT INTO audio_transcriptions_fts(audio_transcriptions_fts, rowid, transcription) VALUES ('delete', old.id, old.transcription);
+END;
+CREATE TRIGGER IF NOT EXISTS audio_transcriptions_fts_update AFTER UPDATE OF transcription ON audio_transcriptions BEGIN
+    INSERT INTO audio_transcriptions_fts(audio_transcriptions_fts, rowid, transcription) VALUES ('delete', old.id, old.transcription);
+    INSERT INTO audio_transcriptions_fts(rowid, transcription) VALUES (new.id, new.transcription);
+END;
@@ -0,0 +1,3 @@
+-- Terms of the full-text search indexes, used by fuzzy search to find variants of misread words
+CREATE VIRTUAL TABLE IF NOT EXISTS ocr_text_fts_vocab USING fts5vocab(ocr_text_fts, 'row');
+CREATE VIRTUAL TABLE IF NOT EXISTS audio_transcriptions_fts_vocab USING fts5vocab(audio_transcriptions_fts, 'row');
@@ -0,0 +1,3 @@
+-- Keyset pagination of search results orders by (timestamp, id)
+CREATE INDEX IF NOT EXISTS idx_frames_timestamp_id ON frames(timestamp, id);
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_timestamp_id ON audio_transcriptions(timestamp, id);
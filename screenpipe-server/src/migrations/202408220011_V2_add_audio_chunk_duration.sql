@@ -0,0 +1,2 @@
+-- Seconds of audio of each chunk, NULL for chunks recorded before it was tracked
+ALTER TABLE audio_chunks ADD COLUMN duration REAL;
@@ -0,0 +1,16 @@
+-- Device the data was recorded on, 'local' for the recordings of this machine
+ALTER TABLE video_chunks ADD COLUMN device_id TEXT NOT NULL DEFAULT 'local';
+ALTER TABLE frames ADD COLUMN device_id TEXT NOT NULL DEFAULT 'local';
+ALTER TABLE audio_chunks ADD COLUMN device_id TEXT NOT NULL DEFAULT 'local';
+ALTER TABLE audio_transcriptions ADD COLUMN device_id TEXT NOT NULL DEFAULT 'local';
+
+-- Hash of the content of ingested rows, the same content pushed twice is skipped
+ALTER TABLE frames ADD COLUMN content_hash TEXT;
+ALTER TABLE audio_transcriptions ADD COLUMN content_hash TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_video_chunks_device_id ON video_chunks(device_id, file_path);
+CREATE INDEX IF NOT EXISTS idx_audio_chunks_device_id ON audio_chunks(device_id, file_path);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_frames_ingest_dedupe
+    ON frames(device_id, timestamp, content_hash) WHERE content_hash IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_audio_transcriptions_ingest_dedupe
+    ON audio_transcriptions(device_id, timestamp, content_hash) WHERE content_hash IS NOT NULL;
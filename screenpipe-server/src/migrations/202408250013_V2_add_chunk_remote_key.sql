@@ -0,0 +1,3 @@
+-- Key of the chunk in the offload bucket, its local file is then a stub fetched back on demand
+ALTER TABLE video_chunks ADD COLUMN remote_key TEXT;
+ALTER TABLE audio_chunks ADD COLUMN remote_key TEXT;
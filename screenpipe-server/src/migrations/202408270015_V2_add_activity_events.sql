@@ -0,0 +1,14 @@
+-- Events pulled from ActivityWatch buckets: AFK status, windows and browser tabs
+CREATE TABLE IF NOT EXISTS activity_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    bucket_id TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    duration REAL NOT NULL DEFAULT 0,
+    data TEXT NOT NULL DEFAULT '{}'
+);
+
+-- Heartbeats extend the last event of a bucket, pulled again it replaces the stored one
+CREATE UNIQUE INDEX IF NOT EXISTS idx_activity_events_bucket_timestamp
+    ON activity_events(bucket_id, timestamp);
+CREATE INDEX IF NOT EXISTS idx_activity_events_timestamp ON activity_events(timestamp);
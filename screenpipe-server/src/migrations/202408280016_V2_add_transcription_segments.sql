@@ -0,0 +1,3 @@
+-- JSON array of {start, end, text}, seconds from the start of the audio chunk.
+-- NULL for transcriptions made before it was stored.
+ALTER TABLE audio_transcriptions ADD COLUMN segments TEXT;
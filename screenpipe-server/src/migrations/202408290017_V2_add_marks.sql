@@ -0,0 +1,8 @@
+-- Moments marked with POST /marks, e.g. from a global hotkey
+CREATE TABLE IF NOT EXISTS marks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    timestamp TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_marks_timestamp ON marks(timestamp);
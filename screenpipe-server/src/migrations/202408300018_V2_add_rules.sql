@@ -0,0 +1,11 @@
+-- Keywords and regexes watched in incoming content, matches are POSTed to the webhook
+CREATE TABLE IF NOT EXISTS rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    is_regex BOOLEAN NOT NULL DEFAULT FALSE,
+    content_type TEXT NOT NULL DEFAULT 'all',
+    webhook_url TEXT NOT NULL,
+    cooldown_secs INTEGER NOT NULL DEFAULT 300,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,25 @@
+-- OCR text as read before spellcheck corrected it, NULL when nothing was corrected
+ALTER TABLE ocr_text ADD COLUMN raw_text TEXT;
+
+-- Index it next to the corrected text, so both are searchable
+DROP TRIGGER IF EXISTS ocr_text_fts_insert;
+DROP TRIGGER IF EXISTS ocr_text_fts_delete;
+DROP TRIGGER IF EXISTS ocr_text_fts_update;
+DROP TABLE IF EXISTS ocr_text_fts_vocab;
+DROP TABLE IF EXISTS ocr_text_fts;
+
+CREATE VIRTUAL TABLE ocr_text_fts USING fts5(text, raw_text, content='ocr_text');
+INSERT INTO ocr_text_fts(ocr_text_fts) VALUES('rebuild');
+
+CREATE TRIGGER ocr_text_fts_insert AFTER INSERT ON ocr_text BEGIN
+    INSERT INTO ocr_text_fts(rowid, text, raw_text) VALUES (new.rowid, new.text, new.raw_text);
+END;
+CREATE TRIGGER ocr_text_fts_delete AFTER DELETE ON ocr_text BEGIN
+    INSERT INTO ocr_text_fts(ocr_text_fts, rowid, text, raw_text) VALUES ('delete', old.rowid, old.text, old.raw_text);
+END;
+CREATE TRIGGER ocr_text_fts_update AFTER UPDATE OF text, raw_text ON ocr_text BEGIN
+    INSERT INTO ocr_text_fts(ocr_text_fts, rowid, text, raw_text) VALUES ('delete', old.rowid, old.text, old.raw_text);
+    INSERT INTO ocr_text_fts(rowid, text, raw_text) VALUES (new.rowid, new.text, new.raw_text);
+END;
+
+CREATE VIRTUAL TABLE ocr_text_fts_vocab USING fts5vocab(ocr_text_fts, 'row');
@@ -0,0 +1,33 @@
+-- Windows on screen when a frame was captured, front to back, with the OCR text in the
+-- visible part of each of them
+CREATE TABLE IF NOT EXISTS frame_windows (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER NOT NULL,
+    app_name TEXT NOT NULL,
+    window_name TEXT NOT NULL DEFAULT '',
+    z_index INTEGER NOT NULL,
+    visible BOOLEAN NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    text TEXT NOT NULL DEFAULT '',
+    FOREIGN KEY (frame_id) REFERENCES frames(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_frame_windows_frame_id ON frame_windows(frame_id);
+CREATE INDEX IF NOT EXISTS idx_frame_windows_app_name ON frame_windows(app_name);
+
+-- Search by app matches the text of the app's windows only, not text behind them
+CREATE VIRTUAL TABLE IF NOT EXISTS frame_windows_fts USING fts5(text, content='frame_windows', content_rowid='id');
+
+CREATE TRIGGER IF NOT EXISTS frame_windows_fts_insert AFTER INSERT ON frame_windows BEGIN
+    INSERT INTO frame_windows_fts(rowid, text) VALUES (new.id, new.text);
+END;
+CREATE TRIGGER IF NOT EXISTS frame_windows_fts_delete AFTER DELETE ON frame_windows BEGIN
+    INSERT INTO frame_windows_fts(frame_windows_fts, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+CREATE TRIGGER IF NOT EXISTS frame_windows_fts_update AFTER UPDATE OF text ON frame_windows BEGIN
+    INSERT INTO frame_windows_fts(frame_windows_fts, rowid, text) VALUES ('delete', old.id, old.text);
+    INSERT INTO frame_windows_fts(rowid, text) VALUES (new.id, new.text);
+END;
@@ -0,0 +1,7 @@
+-- Whether each audio device and the screen ('vision') was last started or stopped through
+-- the API, restored on start
+CREATE TABLE IF NOT EXISTS capture_states (
+    device TEXT PRIMARY KEY,
+    is_running BOOLEAN NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
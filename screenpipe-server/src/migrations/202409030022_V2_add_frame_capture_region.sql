@@ -0,0 +1,3 @@
+-- Part of the screen a frame shows as JSON, with the monitor and the offset of its OCR
+-- coordinates on the screen. NULL for frames recorded before it was stored.
+ALTER TABLE frames ADD COLUMN capture_region TEXT;
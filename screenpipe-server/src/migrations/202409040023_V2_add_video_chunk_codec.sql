@@ -0,0 +1,3 @@
+-- Codec the chunk is encoded with, e.g. "h264" or "hevc". NULL for chunks recorded before
+-- it was stored, which are H.264 or MPEG-4.
+ALTER TABLE video_chunks ADD COLUMN codec TEXT;
@@ -0,0 +1,24 @@
+-- Text copied to the clipboard, with the app in front when it was copied, see
+-- --capture-clipboard
+CREATE TABLE IF NOT EXISTS clipboard (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    text TEXT NOT NULL,
+    app_name TEXT NOT NULL DEFAULT ''
+);
+
+-- Keyset pagination of search results orders by (timestamp, id)
+CREATE INDEX IF NOT EXISTS idx_clipboard_timestamp_id ON clipboard(timestamp, id);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS clipboard_fts USING fts5(text, content='clipboard', content_rowid='id');
+
+CREATE TRIGGER IF NOT EXISTS clipboard_fts_insert AFTER INSERT ON clipboard BEGIN
+    INSERT INTO clipboard_fts(rowid, text) VALUES (new.id, new.text);
+END;
+CREATE TRIGGER IF NOT EXISTS clipboard_fts_delete AFTER DELETE ON clipboard BEGIN
+    INSERT INTO clipboard_fts(clipboard_fts, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+CREATE TRIGGER IF NOT EXISTS clipboard_fts_update AFTER UPDATE OF text ON clipboard BEGIN
+    INSERT INTO clipboard_fts(clipboard_fts, rowid, text) VALUES ('delete', old.id, old.text);
+    INSERT INTO clipboard_fts(rowid, text) VALUES (new.id, new.text);
+END;
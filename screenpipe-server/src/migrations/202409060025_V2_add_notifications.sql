@@ -0,0 +1,24 @@
+-- Notifications shown by the OS, see --capture-notifications
+CREATE TABLE IF NOT EXISTS notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    app_name TEXT NOT NULL DEFAULT '',
+    title TEXT NOT NULL DEFAULT '',
+    body TEXT NOT NULL DEFAULT ''
+);
+
+-- Keyset pagination of search results orders by (timestamp, id)
+CREATE INDEX IF NOT EXISTS idx_notifications_timestamp_id ON notifications(timestamp, id);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS notifications_fts USING fts5(title, body, content='notifications', content_rowid='id');
+
+CREATE TRIGGER IF NOT EXISTS notifications_fts_insert AFTER INSERT ON notifications BEGIN
+    INSERT INTO notifications_fts(rowid, title, body) VALUES (new.id, new.title, new.body);
+END;
+CREATE TRIGGER IF NOT EXISTS notifications_fts_delete AFTER DELETE ON notifications BEGIN
+    INSERT INTO notifications_fts(notifications_fts, rowid, title, body) VALUES ('delete', old.id, old.title, old.body);
+END;
+CREATE TRIGGER IF NOT EXISTS notifications_fts_update AFTER UPDATE OF title, body ON notifications BEGIN
+    INSERT INTO notifications_fts(notifications_fts, rowid, title, body) VALUES ('delete', old.id, old.title, old.body);
+    INSERT INTO notifications_fts(rowid, title, body) VALUES (new.id, new.title, new.body);
+END;
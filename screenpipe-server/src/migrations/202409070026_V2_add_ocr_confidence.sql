@@ -0,0 +1,5 @@
+-- Mean confidence of the words OCR read in the frame, 0 - 100, and the words with their
+-- confidence as JSON. NULL for frames recorded before they were stored and for engines
+-- reporting no confidence.
+ALTER TABLE ocr_text ADD COLUMN confidence REAL;
+ALTER TABLE ocr_text ADD COLUMN words TEXT;
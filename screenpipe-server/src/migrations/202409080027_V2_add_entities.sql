@@ -0,0 +1,18 @@
+-- People, organizations, URLs, dates and amounts named in the frames and transcriptions,
+-- see --extract-entities. Each row belongs to a frame or a transcription.
+CREATE TABLE IF NOT EXISTS entities (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER,
+    audio_transcription_id INTEGER,
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    normalized TEXT NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    FOREIGN KEY (frame_id) REFERENCES frames(id),
+    FOREIGN KEY (audio_transcription_id) REFERENCES audio_transcriptions(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_entities_normalized ON entities(normalized);
+CREATE INDEX IF NOT EXISTS idx_entities_frame_id ON entities(frame_id);
+CREATE INDEX IF NOT EXISTS idx_entities_audio_transcription_id ON entities(audio_transcription_id);
+CREATE INDEX IF NOT EXISTS idx_entities_timestamp ON entities(timestamp);
@@ -0,0 +1,15 @@
+-- Calls and meetings: the transcriptions of a machine grouped by the silences between them,
+-- see DatabaseManager::segment_transcript_sessions. Only the boundaries are stored, the
+-- transcript is read from audio_transcriptions.
+CREATE TABLE IF NOT EXISTS transcript_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id TEXT NOT NULL DEFAULT 'local',
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    chunks INTEGER NOT NULL,
+    -- JSON array of the audio device names
+    audio_devices TEXT NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX IF NOT EXISTS idx_transcript_sessions_device_id ON transcript_sessions(device_id, start_time);
+CREATE INDEX IF NOT EXISTS idx_transcript_sessions_start_time ON transcript_sessions(start_time);
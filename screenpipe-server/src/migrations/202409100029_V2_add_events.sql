@@ -0,0 +1,13 @@
+-- Operational history served by GET /events: recording started and stopped, devices,
+-- restarts, errors and purges, see EventKind
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    kind TEXT NOT NULL,
+    message TEXT NOT NULL,
+    -- JSON object, details of the kind
+    data TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events(timestamp);
+CREATE INDEX IF NOT EXISTS idx_events_kind ON events(kind, timestamp);
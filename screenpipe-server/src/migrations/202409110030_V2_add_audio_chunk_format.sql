@@ -0,0 +1,3 @@
+-- Format the chunk is encoded in, e.g. "mp4" or "opus". NULL for chunks recorded before
+-- it was stored, which are mp4.
+ALTER TABLE audio_chunks ADD COLUMN format TEXT;
@@ -0,0 +1,5 @@
+-- Why transcribing the chunk failed, NULL once it's transcribed. Chunks with an error are
+-- transcribed again in the background until they ran out of attempts.
+ALTER TABLE audio_chunks ADD COLUMN transcription_error TEXT;
+ALTER TABLE audio_chunks ADD COLUMN transcription_attempts INTEGER NOT NULL DEFAULT 0;
+CREATE INDEX IF NOT EXISTS idx_audio_chunks_transcription_error ON audio_chunks(transcription_error) WHERE transcription_error IS NOT NULL;
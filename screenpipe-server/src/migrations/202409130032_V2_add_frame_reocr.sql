@@ -0,0 +1,4 @@
+-- Frames queued by `POST /vision/reocr` to be OCR'd again from their video chunk with the
+-- current engine, cleared once done
+ALTER TABLE frames ADD COLUMN reocr_pending BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE INDEX IF NOT EXISTS idx_frames_reocr_pending ON frames(video_chunk_id) WHERE reocr_pending;
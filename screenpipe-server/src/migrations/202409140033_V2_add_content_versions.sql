@@ -0,0 +1,50 @@
+-- OCR text a frame had before it was OCR'd again with `POST /vision/reocr`, the text of
+-- ocr_text is always the latest. Searchable by engine, see `engine` of `/search`.
+CREATE TABLE IF NOT EXISTS ocr_text_versions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    raw_text TEXT,
+    text_json TEXT,
+    new_text_json_vs_previous_frame TEXT,
+    raw_data_output_from_OCR TEXT,
+    ocr_engine TEXT NOT NULL,
+    confidence REAL,
+    words TEXT,
+    replaced_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (frame_id) REFERENCES frames(id)
+);
+CREATE INDEX IF NOT EXISTS idx_ocr_text_versions_frame_id ON ocr_text_versions(frame_id);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS ocr_text_versions_fts USING fts5(text, raw_text, content='ocr_text_versions', content_rowid='id');
+CREATE TRIGGER IF NOT EXISTS ocr_text_versions_fts_insert AFTER INSERT ON ocr_text_versions BEGIN
+    INSERT INTO ocr_text_versions_fts(rowid, text, raw_text) VALUES (new.id, new.text, new.raw_text);
+END;
+CREATE TRIGGER IF NOT EXISTS ocr_text_versions_fts_delete AFTER DELETE ON ocr_text_versions BEGIN
+    INSERT INTO ocr_text_versions_fts(ocr_text_versions_fts, rowid, text, raw_text) VALUES ('delete', old.id, old.text, old.raw_text);
+END;
+
+-- Transcriptions an audio chunk had before it was transcribed again with
+-- `POST /audio/retranscribe?all=true`, keeping their id
+CREATE TABLE IF NOT EXISTS audio_transcription_versions (
+    id INTEGER PRIMARY KEY,
+    audio_chunk_id INTEGER NOT NULL,
+    offset_index INTEGER NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    transcription TEXT NOT NULL,
+    transcription_engine TEXT NOT NULL,
+    language TEXT,
+    segments TEXT,
+    device_id TEXT NOT NULL,
+    replaced_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (audio_chunk_id) REFERENCES audio_chunks(id)
+);
+CREATE INDEX IF NOT EXISTS idx_audio_transcription_versions_audio_chunk_id ON audio_transcription_versions(audio_chunk_id);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS audio_transcription_versions_fts USING fts5(transcription, content='audio_transcription_versions', content_rowid='id');
+CREATE TRIGGER IF NOT EXISTS audio_transcription_versions_fts_insert AFTER INSERT ON audio_transcription_versions BEGIN
+    INSERT INTO audio_transcription_versions_fts(rowid, transcription) VALUES (new.id, new.transcription);
+END;
+CREATE TRIGGER IF NOT EXISTS audio_transcription_versions_fts_delete AFTER DELETE ON audio_transcription_versions BEGIN
+    INSERT INTO audio_transcription_versions_fts(audio_transcription_versions_fts, rowid, transcription) VALUES ('delete', old.id, old.transcription);
+END;
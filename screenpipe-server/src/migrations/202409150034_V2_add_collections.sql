@@ -0,0 +1,27 @@
+-- Saved searches of `POST /collections`, run with `GET /collections/:id/results`
+CREATE TABLE IF NOT EXISTS collections (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    -- JSON of the `/search` filters, see `CollectionFilters`
+    filters TEXT NOT NULL DEFAULT '{}',
+    pinned BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Of pinned collections, the content recorded before was tagged
+    tagged_until TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+
+-- Content that matched a pinned collection as it arrived
+CREATE TABLE IF NOT EXISTS collection_items (
+    collection_id INTEGER NOT NULL,
+    -- ocr, audio, clipboard or notification
+    content_type TEXT NOT NULL,
+    -- Frame, audio transcription, clipboard or notification id
+    content_id INTEGER NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    tagged_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (collection_id, content_type, content_id),
+    FOREIGN KEY (collection_id) REFERENCES collections(id)
+);
+CREATE INDEX IF NOT EXISTS idx_collection_items_timestamp ON collection_items(collection_id, timestamp);
+CREATE INDEX IF NOT EXISTS idx_collection_items_content ON collection_items(content_type, content_id);
@@ -0,0 +1,3 @@
+-- Chunks of this machine found unreadable after a crash, see `recover_chunks`. Their row
+-- and frames are kept so the OCR text stays searchable
+ALTER TABLE video_chunks ADD COLUMN corrupt BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,9 @@
+-- Id of the last transcription segmented into transcript_sessions, see
+-- DatabaseManager::segment_transcript_sessions. The sessions stored so far are segmented
+-- again from the first transcription.
+CREATE TABLE IF NOT EXISTS transcript_session_progress (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    last_transcription_id INTEGER NOT NULL
+);
+
+DELETE FROM transcript_sessions;
@@ -0,0 +1,59 @@
+-- ocr_text_fts read rows by the implicit rowid of ocr_text, which VACUUM may renumber and
+-- point the index at other rows. The table is rebuilt with an INTEGER PRIMARY KEY, keeping
+-- the rowids, and indexed by it.
+DROP TRIGGER IF EXISTS ocr_text_fts_insert;
+DROP TRIGGER IF EXISTS ocr_text_fts_delete;
+DROP TRIGGER IF EXISTS ocr_text_fts_update;
+DROP TABLE IF EXISTS ocr_text_fts_vocab;
+DROP TABLE IF EXISTS ocr_text_fts;
+
+CREATE TABLE ocr_text_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    unique_text_lines_24hr TEXT,
+    unique_text_lines_1hr TEXT,
+    unique_text_lines_1m TEXT,
+    text_json TEXT,
+    new_text_json_vs_previous_frame TEXT,
+    raw_data_output_from_ocr TEXT,
+    app_name TEXT NOT NULL DEFAULT '',
+    ocr_engine TEXT NOT NULL DEFAULT 'unknown',
+    raw_text TEXT,
+    confidence REAL,
+    words TEXT
+);
+INSERT INTO ocr_text_new (id, frame_id, text, unique_text_lines_24hr, unique_text_lines_1hr,
+    unique_text_lines_1m, text_json, new_text_json_vs_previous_frame, raw_data_output_from_ocr,
+    app_name, ocr_engine, raw_text, confidence, words)
+SELECT rowid, frame_id, text, unique_text_lines_24hr, unique_text_lines_1hr,
+    unique_text_lines_1m, text_json, new_text_json_vs_previous_frame, raw_data_output_from_ocr,
+    app_name, ocr_engine, raw_text, confidence, words
+FROM ocr_text;
+DROP TABLE ocr_text;
+ALTER TABLE ocr_text_new RENAME TO ocr_text;
+
+CREATE INDEX idx_ocr_text_frame_id ON ocr_text(frame_id);
+CREATE INDEX idx_ocr_text_text ON ocr_text(text);
+CREATE INDEX idx_ocr_text_unique_text_lines_24hr ON ocr_text(unique_text_lines_24hr);
+CREATE INDEX idx_ocr_text_unique_text_lines_1hr ON ocr_text(unique_text_lines_1hr);
+CREATE INDEX idx_ocr_text_unique_text_lines_1m ON ocr_text(unique_text_lines_1m);
+CREATE INDEX idx_ocr_text_text_json ON ocr_text(text_json);
+CREATE INDEX idx_ocr_text_new_text_json_vs_previous_frame ON ocr_text(new_text_json_vs_previous_frame);
+CREATE INDEX idx_ocr_text_raw_data_output_from_ocr ON ocr_text(raw_data_output_from_ocr);
+
+CREATE VIRTUAL TABLE ocr_text_fts USING fts5(text, raw_text, content='ocr_text', content_rowid='id');
+INSERT INTO ocr_text_fts(ocr_text_fts) VALUES('rebuild');
+
+CREATE TRIGGER ocr_text_fts_insert AFTER INSERT ON ocr_text BEGIN
+    INSERT INTO ocr_text_fts(rowid, text, raw_text) VALUES (new.id, new.text, new.raw_text);
+END;
+CREATE TRIGGER ocr_text_fts_delete AFTER DELETE ON ocr_text BEGIN
+    INSERT INTO ocr_text_fts(ocr_text_fts, rowid, text, raw_text) VALUES ('delete', old.id, old.text, old.raw_text);
+END;
+CREATE TRIGGER ocr_text_fts_update AFTER UPDATE OF text, raw_text ON ocr_text BEGIN
+    INSERT INTO ocr_text_fts(ocr_text_fts, rowid, text, raw_text) VALUES ('delete', old.id, old.text, old.raw_text);
+    INSERT INTO ocr_text_fts(rowid, text, raw_text) VALUES (new.id, new.text, new.raw_text);
+END;
+
+CREATE VIRTUAL TABLE ocr_text_fts_vocab USING fts5vocab(ocr_text_fts, 'row');
@@ -0,0 +1,4 @@
+-- Whether the chunk was queued to be transcribed again with `POST /audio/retranscribe`,
+-- rather than because transcribing it failed while recording. Requested chunks jump ahead
+-- of the recording in the whisper channel, cleared once transcribed.
+ALTER TABLE audio_chunks ADD COLUMN transcription_requested BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,10 @@
+-- The same content pushed twice is skipped within its chunk only: the same text can be on
+-- two monitors, each recorded to its own video chunk, or heard by two audio devices at the
+-- same time.
+DROP INDEX IF EXISTS idx_frames_ingest_dedupe;
+DROP INDEX IF EXISTS idx_audio_transcriptions_ingest_dedupe;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_frames_ingest_dedupe
+    ON frames(device_id, video_chunk_id, timestamp, content_hash) WHERE content_hash IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_audio_transcriptions_ingest_dedupe
+    ON audio_transcriptions(device_id, audio_chunk_id, timestamp, content_hash)
+    WHERE content_hash IS NOT NULL;
@@ -0,0 +1,53 @@
+-- Same content as the SQLite schema, with the machine that recorded each chunk so
+-- several machines can share one server
+CREATE TABLE IF NOT EXISTS video_chunks (
+    id BIGSERIAL PRIMARY KEY,
+    machine TEXT NOT NULL,
+    file_path TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS frames (
+    id BIGSERIAL PRIMARY KEY,
+    video_chunk_id BIGINT NOT NULL REFERENCES video_chunks(id),
+    offset_index BIGINT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    app_name TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS ocr_text (
+    frame_id BIGINT PRIMARY KEY REFERENCES frames(id),
+    text TEXT NOT NULL,
+    text_json TEXT,
+    new_text_json_vs_previous_frame TEXT,
+    raw_data_output_from_ocr TEXT,
+    app_name TEXT NOT NULL DEFAULT '',
+    ocr_engine TEXT NOT NULL DEFAULT 'unknown',
+    text_search TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', text)) STORED
+);
+
+CREATE TABLE IF NOT EXISTS audio_chunks (
+    id BIGSERIAL PRIMARY KEY,
+    machine TEXT NOT NULL,
+    file_path TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    duration DOUBLE PRECISION
+);
+
+CREATE TABLE IF NOT EXISTS audio_transcriptions (
+    id BIGSERIAL PRIMARY KEY,
+    audio_chunk_id BIGINT NOT NULL REFERENCES audio_chunks(id),
+    offset_index BIGINT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transcription TEXT NOT NULL,
+    transcription_engine TEXT NOT NULL DEFAULT 'Whisper',
+    transcription_search TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', transcription)) STORED
+);
+
+CREATE INDEX IF NOT EXISTS idx_video_chunks_machine_id ON video_chunks(machine, id);
+CREATE INDEX IF NOT EXISTS idx_frames_video_chunk_id ON frames(video_chunk_id);
+CREATE INDEX IF NOT EXISTS idx_frames_timestamp ON frames(timestamp);
+CREATE INDEX IF NOT EXISTS idx_ocr_text_search ON ocr_text USING GIN (text_search);
+CREATE INDEX IF NOT EXISTS idx_audio_chunks_machine_timestamp ON audio_chunks(machine, timestamp);
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_timestamp ON audio_transcriptions(timestamp);
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_search ON audio_transcriptions USING GIN (transcription_search);
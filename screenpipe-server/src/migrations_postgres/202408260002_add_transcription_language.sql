@@ -0,0 +1,2 @@
+-- Code of the language spoken, e.g. "en", NULL for transcriptions made before it was stored
+ALTER TABLE audio_transcriptions ADD COLUMN IF NOT EXISTS language TEXT;
@@ -0,0 +1,2 @@
+-- OCR text as read before spellcheck corrected it, NULL when nothing was corrected
+ALTER TABLE ocr_text ADD COLUMN IF NOT EXISTS raw_text TEXT;
@@ -0,0 +1,19 @@
+-- Windows on screen when a frame was captured, front to back, with the OCR text in the
+-- visible part of each of them
+CREATE TABLE IF NOT EXISTS frame_windows (
+    id BIGSERIAL PRIMARY KEY,
+    frame_id BIGINT NOT NULL REFERENCES frames(id),
+    app_name TEXT NOT NULL,
+    window_name TEXT NOT NULL DEFAULT '',
+    z_index INTEGER NOT NULL,
+    visible BOOLEAN NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    width BIGINT NOT NULL,
+    height BIGINT NOT NULL,
+    text TEXT NOT NULL DEFAULT '',
+    text_search TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', text)) STORED
+);
+
+CREATE INDEX IF NOT EXISTS idx_frame_windows_frame_id ON frame_windows(frame_id);
+CREATE INDEX IF NOT EXISTS idx_frame_windows_text_search ON frame_windows USING GIN (text_search);
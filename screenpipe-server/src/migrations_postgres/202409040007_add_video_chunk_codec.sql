@@ -0,0 +1,2 @@
+-- Codec the chunk is encoded with, e.g. "h264" or "hevc"
+ALTER TABLE video_chunks ADD COLUMN codec TEXT;
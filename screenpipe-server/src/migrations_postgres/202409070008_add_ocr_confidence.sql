@@ -0,0 +1,3 @@
+-- Mean confidence of the words OCR read in the frame and the words with their confidence
+ALTER TABLE ocr_text ADD COLUMN confidence DOUBLE PRECISION;
+ALTER TABLE ocr_text ADD COLUMN words TEXT;
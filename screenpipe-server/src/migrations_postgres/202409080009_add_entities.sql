@@ -0,0 +1,15 @@
+-- People, organizations, URLs, dates and amounts named in the frames and transcriptions,
+-- see --extract-entities. Each row belongs to a frame or a transcription.
+CREATE TABLE IF NOT EXISTS entities (
+    id BIGSERIAL PRIMARY KEY,
+    frame_id BIGINT REFERENCES frames(id),
+    audio_transcription_id BIGINT REFERENCES audio_transcriptions(id),
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    normalized TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_entities_normalized ON entities(normalized);
+CREATE INDEX IF NOT EXISTS idx_entities_frame_id ON entities(frame_id);
+CREATE INDEX IF NOT EXISTS idx_entities_audio_transcription_id ON entities(audio_transcription_id);
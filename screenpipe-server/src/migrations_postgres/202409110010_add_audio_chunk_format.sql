@@ -0,0 +1,2 @@
+-- Format the chunk is encoded in, e.g. "mp4" or "opus"
+ALTER TABLE audio_chunks ADD COLUMN format TEXT;
@@ -0,0 +1,2 @@
+-- Why transcribing the chunk failed, NULL once it's transcribed
+ALTER TABLE audio_chunks ADD COLUMN transcription_error TEXT;
@@ -0,0 +1,345 @@
+use crate::{DatabaseManager, LiveSettings};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Notification read from the OS
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+#[derive(Debug, Clone, PartialEq)]
+struct OsNotification {
+    app_name: String,
+    title: String,
+    body: String,
+    timestamp: DateTime<Utc>,
+}
+
+/// Records the notifications the OS shows, for `content_type=notification` in `/search`.
+/// They are read from the Notification Center database on macOS, which needs the Full Disk
+/// Access permission, and from the notification listener on Windows, which needs the
+/// notification access. Notifications of the ignored apps aren't kept.
+pub struct NotificationMonitor {
+    db: Arc<DatabaseManager>,
+    settings: watch::Receiver<LiveSettings>,
+}
+
+impl NotificationMonitor {
+    pub fn new(db: Arc<DatabaseManager>, settings: watch::Receiver<LiveSettings>) -> Arc<Self> {
+        Arc::new(Self { db, settings })
+    }
+
+    pub fn start_monitoring(self: &Arc<Self>, interval: Duration) {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            // What was shown before the start isn't recorded
+            let mut reader = match platform::NotificationReader::new().await {
+                Ok(reader) => reader,
+                Err(e) => {
+                    warn!("Can't record notifications: {}", e);
+                    return;
+                }
+            };
+            info!("Recording notifications every {:?}", interval);
+            loop {
+                tokio::time::sleep(interval).await;
+                match reader.new_notifications().await {
+                    Ok(notifications) => {
+                        for notification in notifications {
+                            monitor.record(notification).await;
+                        }
+                    }
+                    Err(e) => debug!("Failed to read notifications: {}", e),
+                }
+            }
+        });
+    }
+
+    async fn record(&self, notification: OsNotification) {
+        if self.settings.borrow().is_ignored(&notification.app_name) {
+            debug!(
+                "Skipping the notification of ignored app {}",
+                notification.app_name
+            );
+            return;
+        }
+        if let Err(e) = self
+            .db
+            .insert_notification(
+                &notification.app_name,
+                &notification.title,
+                &notification.body,
+                notification.timestamp,
+            )
+            .await
+        {
+            error!("Failed to record a notification: {}", e);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::OsNotification;
+    use anyhow::{anyhow, Result};
+    use chrono::{DateTime, Utc};
+    use sqlx::sqlite::SqliteConnectOptions;
+    use sqlx::{ConnectOptions, Row};
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    /// Seconds between the Unix epoch and the Core Data one, 2001-01-01
+    const CORE_DATA_EPOCH: f64 = 978_307_200.0;
+
+    /// Reads the records usernoted stores of every notification delivered
+    pub struct NotificationReader {
+        db_path: PathBuf,
+        /// Last record read
+        last_id: i64,
+        /// Names of the apps by bundle identifier
+        app_names: HashMap<String, String>,
+    }
+
+    impl NotificationReader {
+        pub async fn new() -> Result<Self> {
+            let db_path = database_path()
+                .ok_or_else(|| anyhow!("the Notification Center database wasn't found"))?;
+            let mut reader = NotificationReader {
+                db_path,
+                last_id: 0,
+                app_names: HashMap::new(),
+            };
+            let mut connection = reader.connect().await?;
+            let (last_id,): (Option<i64>,) = sqlx::query_as("SELECT MAX(rec_id) FROM record")
+                .fetch_one(&mut connection)
+                .await?;
+            reader.last_id = last_id.unwrap_or(0);
+            Ok(reader)
+        }
+
+        async fn connect(&self) -> Result<sqlx::SqliteConnection> {
+            SqliteConnectOptions::new()
+                .filename(&self.db_path)
+                .read_only(true)
+                .connect()
+                .await
+                .map_err(|e| {
+                    anyhow!(
+                        "can't open {} (does screenpipe have Full Disk Access?): {}",
+                        self.db_path.display(),
+                        e
+                    )
+                })
+        }
+
+        pub async fn new_notifications(&mut self) -> Result<Vec<OsNotification>> {
+            let mut connection = self.connect().await?;
+            let rows = sqlx::query(
+                r#"
+                SELECT record.rec_id, app.identifier, record.data, record.delivered_date
+                FROM record JOIN app ON record.app_id = app.app_id
+                WHERE record.rec_id > ?1
+                ORDER BY record.rec_id
+                "#,
+            )
+            .bind(self.last_id)
+            .fetch_all(&mut connection)
+            .await?;
+
+            let mut notifications = Vec::new();
+            for row in rows {
+                self.last_id = row.try_get("rec_id")?;
+                let bundle_id: String = row.try_get("identifier")?;
+                let data: Vec<u8> = row.try_get("data")?;
+                let delivered: f64 = row.try_get("delivered_date")?;
+                let Some((title, body)) = parse_record(&data) else {
+                    continue;
+                };
+                let seconds = delivered + CORE_DATA_EPOCH;
+                let timestamp =
+                    DateTime::from_timestamp(seconds as i64, (seconds.fract() * 1e9) as u32)
+                        .unwrap_or_else(Utc::now);
+                notifications.push(OsNotification {
+                    app_name: self.app_name(&bundle_id),
+                    title,
+                    body,
+                    timestamp,
+                });
+            }
+            Ok(notifications)
+        }
+
+        /// Name of the app of `bundle_id` as Finder shows it, e.g. "Slack" for
+        /// com.tinyspeck.slackmacgap, the identifier if the app isn't found
+        fn app_name(&mut self, bundle_id: &str) -> String {
+            self.app_names
+                .entry(bundle_id.to_string())
+                .or_insert_with(|| {
+                    Command::new("mdfind")
+                        .arg(format!("kMDItemCFBundleIdentifier == '{}'", bundle_id))
+                        .output()
+                        .ok()
+                        .and_then(|output| {
+                            let paths = String::from_utf8_lossy(&output.stdout).into_owned();
+                            let path = PathBuf::from(paths.lines().next()?);
+                            Some(path.file_stem()?.to_string_lossy().into_owned())
+                        })
+                        .unwrap_or_else(|| bundle_id.to_string())
+                })
+                .clone()
+        }
+    }
+
+    /// Database of macOS 15, and of the earlier versions in the per-user temporary directory
+    fn database_path() -> Option<PathBuf> {
+        let sequoia =
+            dirs::home_dir()?.join("Library/Group Containers/group.com.apple.usernoted/db2/db");
+        if sequoia.exists() {
+            return Some(sequoia);
+        }
+        let output = Command::new("getconf")
+            .arg("DARWIN_USER_DIR")
+            .output()
+            .ok()?;
+        let user_dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let legacy = PathBuf::from(user_dir).join("com.apple.notificationcenter/db2/db");
+        legacy.exists().then_some(legacy)
+    }
+
+    /// Title, with the subtitle if any, and body of the binary plist of a record
+    fn parse_record(data: &[u8]) -> Option<(String, String)> {
+        let value = plist::Value::from_reader(Cursor::new(data)).ok()?;
+        let request = value.as_dictionary()?.get("req")?.as_dictionary()?;
+        let text = |key: &str| {
+            request
+                .get(key)
+                .and_then(|value| value.as_string())
+                .map(|text| text.trim().to_string())
+                .unwrap_or_default()
+        };
+        let (title, subtitle, body) = (text("titl"), text("subt"), text("body"));
+        let title = match subtitle.is_empty() {
+            true => title,
+            false => format!("{} - {}", title, subtitle),
+        };
+        (!title.is_empty() || !body.is_empty()).then_some((title, body))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::OsNotification;
+    use anyhow::{anyhow, Result};
+    use chrono::{DateTime, Utc};
+    use std::collections::HashSet;
+    use windows::UI::Notifications::Management::{
+        UserNotificationListener, UserNotificationListenerAccessStatus,
+    };
+    use windows::UI::Notifications::{KnownNotificationBindings, NotificationKinds};
+
+    /// Seconds between 1601-01-01, the epoch of Windows timestamps, and the Unix one
+    const WINDOWS_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+    /// Lists the toasts in the action center, the ones not listed before are new
+    pub struct NotificationReader {
+        seen: HashSet<u32>,
+    }
+
+    impl NotificationReader {
+        pub async fn new() -> Result<Self> {
+            let (seen, _) =
+                tokio::task::spawn_blocking(|| list_notifications(&HashSet::new())).await??;
+            Ok(NotificationReader { seen })
+        }
+
+        pub async fn new_notifications(&mut self) -> Result<Vec<OsNotification>> {
+            let seen = std::mem::take(&mut self.seen);
+            let (listed, notifications) =
+                tokio::task::spawn_blocking(move || list_notifications(&seen)).await??;
+            // Dismissed toasts aren't listed anymore, their ids can be reused
+            self.seen = listed;
+            Ok(notifications)
+        }
+    }
+
+    /// Ids of the toasts listed, and the ones not in `seen`
+    fn list_notifications(seen: &HashSet<u32>) -> Result<(HashSet<u32>, Vec<OsNotification>)> {
+        let listener = UserNotificationListener::Current()?;
+        if listener.RequestAccessAsync()?.get()? != UserNotificationListenerAccessStatus::Allowed {
+            return Err(anyhow!(
+                "notification access is denied, allow it in Settings > Privacy & security > Notifications"
+            ));
+        }
+        let toast_generic = KnownNotificationBindings::ToastGeneric()?;
+        let mut listed = HashSet::new();
+        let mut notifications = Vec::new();
+        for notification in listener
+            .GetNotificationsAsync(NotificationKinds::Toast)?
+            .get()?
+        {
+            let id = notification.Id()?;
+            listed.insert(id);
+            if seen.contains(&id) {
+                continue;
+            }
+            let app_name = notification
+                .AppInfo()
+                .and_then(|app| app.DisplayInfo())
+                .and_then(|info| info.DisplayName())
+                .map(|name| name.to_string())
+                .unwrap_or_default();
+            // The first text of a toast is its title, the others its body
+            let texts: Vec<String> = match notification
+                .Notification()?
+                .Visual()?
+                .GetBinding(&toast_generic)
+            {
+                Ok(binding) => binding
+                    .GetTextElements()?
+                    .into_iter()
+                    .filter_map(|text| text.Text().ok())
+                    .map(|text| text.to_string().trim().to_string())
+                    .filter(|text| !text.is_empty())
+                    .collect(),
+                Err(_) => continue,
+            };
+            let Some((title, body)) = texts.split_first() else {
+                continue;
+            };
+            let ticks = notification.CreationTime()?.UniversalTime;
+            let timestamp = DateTime::from_timestamp(
+                ticks / 10_000_000 - WINDOWS_EPOCH_OFFSET,
+                (ticks % 10_000_000 * 100) as u32,
+            )
+            .unwrap_or_else(Utc::now);
+            notifications.push(OsNotification {
+                app_name,
+                title: title.clone(),
+                body: body.join("\n"),
+                timestamp,
+            });
+        }
+        Ok((listed, notifications))
+    }
+}
+
+// The notification daemons of Linux desktops don't keep what they showed
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use super::OsNotification;
+    use anyhow::{anyhow, Result};
+
+    pub struct NotificationReader;
+
+    impl NotificationReader {
+        pub async fn new() -> Result<Self> {
+            Err(anyhow!("notifications are only read on macOS and Windows"))
+        }
+
+        pub async fn new_notifications(&mut self) -> Result<Vec<OsNotification>> {
+            Ok(Vec::new())
+        }
+    }
+}
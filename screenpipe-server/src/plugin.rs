@@ -1,46 +1,156 @@
+use axum::body::Body;
+use axum::http::{Method, Request, Uri};
+use axum::response::Response;
 use futures::future::BoxFuture;
+use log::error;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use tower::{Layer, Service};
 
+/// Extension of the API, called on every request. A plugin that panics is skipped for that
+/// request and counted in `ApiPlugins::failures`, the request goes on without it.
+pub trait ApiPlugin: Send + Sync + 'static {
+    /// Name in the logs and failure counts
+    fn name(&self) -> &str;
+
+    /// Called before the handler. Returning a response answers the request right away, e.g.
+    /// a 401 of an auth plugin: the handler and the next plugins' `on_request` are skipped.
+    fn on_request(&self, _request: &Request<Body>) -> Option<Response> {
+        None
+    }
+
+    /// Called once the request is answered, also when a plugin answered it
+    fn on_response(&self, _request: &RequestSummary, _response: &Response, _latency: Duration) {}
+}
+
+/// Request seen by `ApiPlugin::on_response`, the request itself went to the handler
+#[derive(Debug, Clone)]
+pub struct RequestSummary {
+    pub method: Method,
+    pub uri: Uri,
+}
+
+struct RegisteredPlugin {
+    plugin: Box<dyn ApiPlugin>,
+    failures: AtomicU64,
+}
+
+/// Plugins of the API in the order they run: `on_request` in registration order, and
+/// `on_response` in reverse so the first plugin sees the response last. Clones share the
+/// failure counts.
+#[derive(Clone, Default)]
+pub struct ApiPlugins {
+    plugins: Vec<Arc<RegisteredPlugin>>,
+}
+
+impl ApiPlugins {
+    pub fn new() -> Self {
+        ApiPlugins::default()
+    }
+
+    /// Adds a plugin after the ones already registered
+    pub fn with(mut self, plugin: impl ApiPlugin) -> Self {
+        self.plugins.push(Arc::new(RegisteredPlugin {
+            plugin: Box::new(plugin),
+            failures: AtomicU64::new(0),
+        }));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Panics of each plugin since the server started, by name
+    pub fn failures(&self) -> Vec<(String, u64)> {
+        self.plugins
+            .iter()
+            .map(|registered| {
+                (
+                    registered.plugin.name().to_string(),
+                    registered.failures.load(Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+
+    /// Runs `call` on the plugin, a panic is logged and counted instead of taking down the
+    /// request
+    fn isolated<T>(
+        registered: &RegisteredPlugin,
+        hook: &str,
+        call: impl FnOnce() -> T,
+    ) -> Option<T> {
+        match catch_unwind(AssertUnwindSafe(call)) {
+            Ok(result) => Some(result),
+            Err(_) => {
+                registered.failures.fetch_add(1, Ordering::Relaxed);
+                error!(
+                    "API plugin {} panicked in {}, skipping it for this request",
+                    registered.plugin.name(),
+                    hook
+                );
+                None
+            }
+        }
+    }
+
+    /// Response of the first plugin answering the request itself
+    fn on_request(&self, request: &Request<Body>) -> Option<Response> {
+        self.plugins.iter().find_map(|registered| {
+            Self::isolated(registered, "on_request", || {
+                registered.plugin.on_request(request)
+            })
+            .flatten()
+        })
+    }
+
+    fn on_response(&self, request: &RequestSummary, response: &Response, latency: Duration) {
+        for registered in self.plugins.iter().rev() {
+            Self::isolated(registered, "on_response", || {
+                registered.plugin.on_response(request, response, latency)
+            });
+        }
+    }
+}
+
+/// Runs the `ApiPlugins` around the routes it wraps
 #[derive(Clone)]
-pub struct ApiPluginLayer<F> {
-    plugin: F,
+pub struct ApiPluginLayer {
+    plugins: Arc<ApiPlugins>,
 }
 
-impl<F> ApiPluginLayer<F>
-where
-    F: Clone,
-{
-    pub fn new(plugin: F) -> Self {
-        Self { plugin }
+impl ApiPluginLayer {
+    pub fn new(plugins: ApiPlugins) -> Self {
+        Self {
+            plugins: Arc::new(plugins),
+        }
     }
 }
 
-impl<S, F> Layer<S> for ApiPluginLayer<F>
-where
-    F: Clone + Send + Sync + 'static,
-{
-    type Service = ApiPluginService<S, F>;
+impl<S> Layer<S> for ApiPluginLayer {
+    type Service = ApiPluginService<S>;
 
     fn layer(&self, service: S) -> <Self as Layer<S>>::Service {
         ApiPluginService {
             inner: service,
-            plugin: self.plugin.clone(),
+            plugins: self.plugins.clone(),
         }
     }
 }
 
 #[derive(Clone)]
-pub struct ApiPluginService<S, F> {
+pub struct ApiPluginService<S> {
     inner: S,
-    plugin: F,
+    plugins: Arc<ApiPlugins>,
 }
 
-impl<S, F, R> Service<R> for ApiPluginService<S, F>
+impl<S> Service<Request<Body>> for ApiPluginService<S>
 where
-    S: Service<R> + Clone + Send + 'static,
-    F: Fn(&R) + Clone + Send + Sync + 'static,
-    R: Send + 'static,
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
     S::Future: Send,
 {
     type Response = S::Response;
@@ -51,10 +161,26 @@ where
         self.inner.poll_ready(cx)
     }
 
-    fn call(&mut self, request: R) -> Self::Future {
-        let plugin = self.plugin.clone();
-        let mut inner = self.inner.clone();
-        plugin(&request);
-        Box::pin(async move { inner.call(request).await })
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let plugins = Arc::clone(&self.plugins);
+        // The service polled ready is the one to call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            if plugins.is_empty() {
+                return inner.call(request).await;
+            }
+            let start = Instant::now();
+            let summary = RequestSummary {
+                method: request.method().clone(),
+                uri: request.uri().clone(),
+            };
+            let response = match plugins.on_request(&request) {
+                Some(response) => response,
+                None => inner.call(request).await?,
+            };
+            plugins.on_response(&summary, &response, start.elapsed());
+            Ok(response)
+        })
     }
 }
@@ -0,0 +1,338 @@
+use crate::db::{capture_region_json, segments_json};
+use crate::db_writer::words_json;
+use crate::storage::{AudioRecord, FrameRecord, Storage};
+use crate::{mean_confidence, Entity};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use screenpipe_vision::WindowRegion;
+use sqlx::postgres::{PgConnection, PgPool, PgPoolOptions};
+use std::time::Duration;
+
+/// Stores recordings in a PostgreSQL server, which can be shared by several machines.
+/// Searchable with SQL, e.g. `WHERE text_search @@ websearch_to_tsquery('simple', 'invoice')`.
+pub struct PostgresStorage {
+    pub pool: PgPool,
+    /// Recorded with every chunk, tells apart the machines sharing the server
+    machine: String,
+}
+
+impl PostgresStorage {
+    pub async fn new(url: &str, machine: &str) -> Result<Self, sqlx::Error> {
+        debug!("Connecting to PostgreSQL as machine {}", machine);
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .acquire_timeout(Duration::from_secs(10))
+            .connect(url)
+            .await?;
+        sqlx::migrate!("./src/migrations_postgres")
+            .run(&pool)
+            .await?;
+        info!("PostgreSQL storage ready");
+        Ok(PostgresStorage {
+            pool,
+            machine: machine.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn insert_video_chunk(&self, file_path: &str, codec: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO video_chunks (machine, file_path, codec, timestamp) VALUES ($1, $2, $3, NOW())",
+        )
+        .bind(&self.machine)
+        .bind(file_path)
+        .bind(codec)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn write_frame(&self, frame: FrameRecord) -> Result<(), sqlx::Error> {
+        self.write_frame_now(frame).await?;
+        Ok(())
+    }
+
+    async fn write_frame_now(&self, frame: FrameRecord) -> Result<Option<i64>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // Frames go to the most recent video chunk of this machine
+        let video_chunk_id: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM video_chunks WHERE machine = $1 ORDER BY id DESC LIMIT 1",
+        )
+        .bind(&self.machine)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(video_chunk_id) = video_chunk_id else {
+            debug!("No video chunk found, dropping frame");
+            return Ok(None);
+        };
+
+        let frame_id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO frames (video_chunk_id, offset_index, timestamp, app_name, capture_region)
+            SELECT $1, COALESCE(MAX(offset_index), -1) + 1, $2, $3, $4
+            FROM frames WHERE video_chunk_id = $1
+            RETURNING id
+            "#,
+        )
+        .bind(video_chunk_id)
+        .bind(frame.timestamp)
+        .bind(&frame.app_name)
+        .bind(capture_region_json(frame.region.as_ref()))
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO ocr_text (frame_id, text, text_json, new_text_json_vs_previous_frame,
+                raw_data_output_from_ocr, app_name, ocr_engine, raw_text, confidence, words)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(frame_id)
+        .bind(&frame.text)
+        .bind(&frame.text_json)
+        .bind(&frame.new_text_json_vs_previous_frame)
+        .bind(&frame.raw_data_output_from_ocr)
+        .bind(&frame.app_name)
+        .bind(&frame.ocr_engine)
+        .bind(&frame.raw_text)
+        .bind(mean_confidence(&frame.words))
+        .bind(words_json(&frame.words))
+        .execute(&mut *tx)
+        .await?;
+
+        insert_windows(&mut tx, frame_id, &frame.windows).await?;
+        insert_entities(
+            &mut tx,
+            Some(frame_id),
+            None,
+            frame.timestamp,
+            &frame.entities,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(Some(frame_id))
+    }
+
+    async fn write_audio(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let audio_chunk_id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO audio_chunks (machine, file_path, timestamp, duration, format,
+                transcription_error)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            "#,
+        )
+        .bind(&self.machine)
+        .bind(&audio.file_path)
+        .bind(audio.timestamp)
+        .bind(audio.duration)
+        .bind(&audio.format)
+        .bind(&audio.transcription_error)
+        .fetch_one(&mut *tx)
+        .await?;
+        insert_transcription(&mut tx, audio_chunk_id, &audio).await?;
+        tx.commit().await
+    }
+
+    async fn write_retranscription(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let audio_chunk_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE audio_chunks
+            SET transcription_error = NULL, duration = COALESCE($3, duration)
+            WHERE machine = $1 AND file_path = $2
+            RETURNING id
+            "#,
+        )
+        .bind(&self.machine)
+        .bind(&audio.file_path)
+        .bind(audio.duration)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(audio_chunk_id) = audio_chunk_id {
+            // Only the latest transcription is mirrored, versions stay local
+            sqlx::query(
+                r#"
+                DELETE FROM entities WHERE audio_transcription_id IN (
+                    SELECT id FROM audio_transcriptions WHERE audio_chunk_id = $1
+                )
+                "#,
+            )
+            .bind(audio_chunk_id)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("DELETE FROM audio_transcriptions WHERE audio_chunk_id = $1")
+                .bind(audio_chunk_id)
+                .execute(&mut *tx)
+                .await?;
+            insert_transcription(&mut tx, audio_chunk_id, &audio).await?;
+        }
+        tx.commit().await
+    }
+
+    async fn write_reocr(
+        &self,
+        video_chunk: &str,
+        offset_index: i64,
+        frame: FrameRecord,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let frame_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT frames.id FROM frames
+            JOIN video_chunks ON video_chunks.id = frames.video_chunk_id
+            WHERE video_chunks.machine = $1 AND video_chunks.file_path = $2
+                AND frames.offset_index = $3
+            "#,
+        )
+        .bind(&self.machine)
+        .bind(video_chunk)
+        .bind(offset_index)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(frame_id) = frame_id else {
+            debug!(
+                "Frame {} of {} isn't stored, dropping its OCR",
+                offset_index, video_chunk
+            );
+            return Ok(());
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE ocr_text
+            SET text = $2, raw_text = $3, text_json = $4, new_text_json_vs_previous_frame = $5,
+                raw_data_output_from_ocr = $6, ocr_engine = $7, confidence = $8, words = $9
+            WHERE frame_id = $1
+            "#,
+        )
+        .bind(frame_id)
+        .bind(&frame.text)
+        .bind(&frame.raw_text)
+        .bind(&frame.text_json)
+        .bind(&frame.new_text_json_vs_previous_frame)
+        .bind(&frame.raw_data_output_from_ocr)
+        .bind(&frame.ocr_engine)
+        .bind(mean_confidence(&frame.words))
+        .bind(words_json(&frame.words))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM frame_windows WHERE frame_id = $1")
+            .bind(frame_id)
+            .execute(&mut *tx)
+            .await?;
+        insert_windows(&mut tx, frame_id, &frame.windows).await?;
+        sqlx::query("DELETE FROM entities WHERE frame_id = $1")
+            .bind(frame_id)
+            .execute(&mut *tx)
+            .await?;
+        insert_entities(
+            &mut tx,
+            Some(frame_id),
+            None,
+            frame.timestamp,
+            &frame.entities,
+        )
+        .await?;
+        tx.commit().await
+    }
+
+    /// Every write is committed before it returns
+    async fn flush_writes(&self) {}
+}
+
+async fn insert_windows(
+    conn: &mut PgConnection,
+    frame_id: i64,
+    windows: &[WindowRegion],
+) -> Result<(), sqlx::Error> {
+    for window in windows {
+        sqlx::query(
+            r#"
+            INSERT INTO frame_windows (frame_id, app_name, window_name, z_index, visible,
+                x, y, width, height, text)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(frame_id)
+        .bind(&window.app_name)
+        .bind(&window.window_name)
+        .bind(window.z_index as i32)
+        .bind(window.visible)
+        .bind(window.x)
+        .bind(window.y)
+        .bind(window.width as i64)
+        .bind(window.height as i64)
+        .bind(&window.text)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Inserts the transcription of the audio chunk, nothing when it's empty
+async fn insert_transcription(
+    conn: &mut PgConnection,
+    audio_chunk_id: i64,
+    audio: &AudioRecord,
+) -> Result<(), sqlx::Error> {
+    if audio.transcription.is_empty() {
+        return Ok(());
+    }
+    let transcription_id: i64 = sqlx::query_scalar(
+        r#"
+        INSERT INTO audio_transcriptions (audio_chunk_id, offset_index, timestamp,
+            transcription, transcription_engine, language, segments)
+        VALUES ($1, 0, $2, $3, $4, $5, $6::jsonb)
+        RETURNING id
+        "#,
+    )
+    .bind(audio_chunk_id)
+    .bind(audio.timestamp)
+    .bind(&audio.transcription)
+    .bind(&audio.transcription_engine)
+    .bind(&audio.language)
+    .bind(segments_json(&audio.segments))
+    .fetch_one(&mut *conn)
+    .await?;
+    insert_entities(
+        conn,
+        None,
+        Some(transcription_id),
+        audio.timestamp,
+        &audio.entities,
+    )
+    .await
+}
+
+async fn insert_entities(
+    conn: &mut PgConnection,
+    frame_id: Option<i64>,
+    transcription_id: Option<i64>,
+    timestamp: DateTime<Utc>,
+    entities: &[Entity],
+) -> Result<(), sqlx::Error> {
+    for entity in entities {
+        sqlx::query(
+            r#"
+            INSERT INTO entities (frame_id, audio_transcription_id, kind, value, normalized,
+                timestamp)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(frame_id)
+        .bind(transcription_id)
+        .bind(entity.kind.name())
+        .bind(&entity.value)
+        .bind(&entity.normalized)
+        .bind(timestamp)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,305 @@
+#[cfg(feature = "integrations")]
+use crate::DatabaseManager;
+use crate::{AudioRecord, FrameRecord, IngestBatch, IngestFrame, IngestTranscription, Storage};
+use async_trait::async_trait;
+use log::{debug, error};
+#[cfg(feature = "integrations")]
+use screenpipe_integrations::import::{
+    ImportDatabase, ImportSource, ImportSummary, ImportedFrame, ImportedTranscription,
+};
+#[cfg(feature = "integrations")]
+use std::error::Error as StdError;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Runs on every OCR result and transcription before it is stored, whether recorded,
+/// pushed to `/ingest` or imported, e.g. to detect the language or drop what shouldn't be
+/// kept. Processors run on the recording loops, keep them fast.
+pub trait ContentProcessor: Send + Sync + 'static {
+    fn name(&self) -> &str;
+
+    /// The frame to store, `None` drops its text. The frame itself stays so the video
+    /// offsets of the next frames are kept.
+    fn process_frame(&self, frame: FrameRecord) -> Option<FrameRecord> {
+        Some(frame)
+    }
+
+    /// The transcription to store, `None` drops it. The audio chunk itself stays.
+    fn process_audio(&self, audio: AudioRecord) -> Option<AudioRecord> {
+        Some(audio)
+    }
+}
+
+struct RegisteredProcessor {
+    processor: Box<dyn ContentProcessor>,
+    failures: AtomicU64,
+}
+
+/// Processors of the recorded content in the order they run, each one gets the output of
+/// the previous. One that panics is skipped for that item, see `failures`.
+#[derive(Clone, Default)]
+pub struct ContentProcessors {
+    processors: Vec<Arc<RegisteredProcessor>>,
+}
+
+impl ContentProcessors {
+    pub fn new() -> Self {
+        ContentProcessors::default()
+    }
+
+    pub fn with(mut self, processor: impl ContentProcessor) -> Self {
+        self.processors.push(Arc::new(RegisteredProcessor {
+            processor: Box::new(processor),
+            failures: AtomicU64::new(0),
+        }));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// Panics of each processor since start by name, shared by the clones
+    pub fn failures(&self) -> Vec<(String, u64)> {
+        self.processors
+            .iter()
+            .map(|registered| {
+                (
+                    registered.processor.name().to_string(),
+                    registered.failures.load(Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+
+    /// Runs every processor on `item`, a processor that panics leaves it as it was. `None`
+    /// once a processor drops it.
+    fn run<T: Clone>(
+        &self,
+        mut item: T,
+        process: impl Fn(&dyn ContentProcessor, T) -> Option<T>,
+    ) -> Option<T> {
+        for registered in &self.processors {
+            let processor = registered.processor.as_ref();
+            match catch_unwind(AssertUnwindSafe(|| process(processor, item.clone()))) {
+                Ok(Some(processed)) => item = processed,
+                Ok(None) => {
+                    debug!("Content processor {} dropped an item", processor.name());
+                    return None;
+                }
+                Err(_) => {
+                    registered.failures.fetch_add(1, Ordering::Relaxed);
+                    error!(
+                        "Content processor {} panicked, skipping it for this item",
+                        processor.name()
+                    );
+                }
+            }
+        }
+        Some(item)
+    }
+
+    pub fn process_frame(&self, frame: FrameRecord) -> Option<FrameRecord> {
+        self.run(frame, |processor, frame| processor.process_frame(frame))
+    }
+
+    pub fn process_audio(&self, audio: AudioRecord) -> Option<AudioRecord> {
+        self.run(audio, |processor, audio| processor.process_audio(audio))
+    }
+
+    /// The frame the processors made, stored without text when one discarded it
+    fn processed_frame(&self, frame: FrameRecord) -> FrameRecord {
+        match self.process_frame(frame.clone()) {
+            Some(frame) => frame,
+            None => FrameRecord {
+                text: String::new(),
+                raw_text: None,
+                text_json: "[]".to_string(),
+                new_text_json_vs_previous_frame: String::new(),
+                raw_data_output_from_ocr: String::new(),
+                words: Vec::new(),
+                entities: Vec::new(),
+                windows: Vec::new(),
+                ..frame
+            },
+        }
+    }
+
+    /// The audio the processors made, stored without transcription when one discarded it
+    fn processed_audio(&self, audio: AudioRecord) -> AudioRecord {
+        match self.process_audio(audio.clone()) {
+            Some(audio) => audio,
+            None => AudioRecord {
+                transcription: String::new(),
+                language: None,
+                segments: Vec::new(),
+                entities: Vec::new(),
+                ..audio
+            },
+        }
+    }
+
+    /// The batch to ingest with what the processors make of its frames and transcriptions.
+    /// Ingested rows have no OCR words or windows, processors only see their text.
+    pub fn process_ingest(&self, batch: IngestBatch) -> IngestBatch {
+        if self.is_empty() {
+            return batch;
+        }
+        IngestBatch {
+            frames: batch
+                .frames
+                .into_iter()
+                .map(|frame| self.process_ingest_frame(frame))
+                .collect(),
+            transcriptions: batch
+                .transcriptions
+                .into_iter()
+                .map(|transcription| self.process_ingest_transcription(transcription))
+                .collect(),
+            ..batch
+        }
+    }
+
+    fn process_ingest_frame(&self, frame: IngestFrame) -> IngestFrame {
+        let processed = self.processed_frame(FrameRecord {
+            timestamp: frame.timestamp,
+            app_name: frame.app_name.clone(),
+            text: frame.text.clone(),
+            raw_text: frame.raw_text.clone(),
+            text_json: frame.text_json.clone(),
+            new_text_json_vs_previous_frame: String::new(),
+            raw_data_output_from_ocr: String::new(),
+            ocr_engine: frame.ocr_engine.clone(),
+            words: Vec::new(),
+            entities: frame.entities.clone(),
+            windows: Vec::new(),
+            region: None,
+        });
+        IngestFrame {
+            app_name: processed.app_name,
+            text: processed.text,
+            raw_text: processed.raw_text,
+            text_json: processed.text_json,
+            entities: processed.entities,
+            ..frame
+        }
+    }
+
+    fn process_ingest_transcription(
+        &self,
+        transcription: IngestTranscription,
+    ) -> IngestTranscription {
+        let processed = self.processed_audio(AudioRecord {
+            timestamp: transcription.timestamp,
+            file_path: transcription.file_path.clone(),
+            duration: transcription.duration,
+            transcription: transcription.transcription.clone(),
+            transcription_engine: transcription.transcription_engine.clone(),
+            language: transcription.language.clone(),
+            segments: transcription.segments.clone(),
+            entities: transcription.entities.clone(),
+            format: None,
+            transcription_error: None,
+        });
+        IngestTranscription {
+            transcription: processed.transcription,
+            language: processed.language,
+            segments: processed.segments,
+            entities: processed.entities,
+            ..transcription
+        }
+    }
+}
+
+/// Writes to `inner` what the `ContentProcessors` make of it
+pub struct ProcessedStorage {
+    inner: Arc<dyn Storage>,
+    processors: ContentProcessors,
+}
+
+impl ProcessedStorage {
+    pub fn new(inner: Arc<dyn Storage>, processors: ContentProcessors) -> Self {
+        ProcessedStorage { inner, processors }
+    }
+}
+
+#[async_trait]
+impl Storage for ProcessedStorage {
+    async fn insert_video_chunk(&self, file_path: &str, codec: &str) -> Result<(), sqlx::Error> {
+        self.inner.insert_video_chunk(file_path, codec).await
+    }
+
+    async fn write_frame(&self, frame: FrameRecord) -> Result<(), sqlx::Error> {
+        self.inner
+            .write_frame(self.processors.processed_frame(frame))
+            .await
+    }
+
+    async fn write_frame_now(&self, frame: FrameRecord) -> Result<Option<i64>, sqlx::Error> {
+        self.inner
+            .write_frame_now(self.processors.processed_frame(frame))
+            .await
+    }
+
+    async fn write_audio(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        self.inner
+            .write_audio(self.processors.processed_audio(audio))
+            .await
+    }
+
+    async fn write_retranscription(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        self.inner
+            .write_retranscription(self.processors.processed_audio(audio))
+            .await
+    }
+
+    async fn write_reocr(
+        &self,
+        video_chunk: &str,
+        offset_index: i64,
+        frame: FrameRecord,
+    ) -> Result<(), sqlx::Error> {
+        self.inner
+            .write_reocr(
+                video_chunk,
+                offset_index,
+                self.processors.processed_frame(frame),
+            )
+            .await
+    }
+
+    async fn flush_writes(&self) {
+        self.inner.flush_writes().await;
+    }
+}
+
+/// Imports into the database what the `ContentProcessors` make of the imported rows
+#[cfg(feature = "integrations")]
+pub struct ProcessedImport<'a> {
+    db: &'a DatabaseManager,
+    processors: ContentProcessors,
+}
+
+#[cfg(feature = "integrations")]
+impl<'a> ProcessedImport<'a> {
+    pub fn new(db: &'a DatabaseManager, processors: ContentProcessors) -> Self {
+        ProcessedImport { db, processors }
+    }
+}
+
+#[cfg(feature = "integrations")]
+#[async_trait]
+impl ImportDatabase for ProcessedImport<'_> {
+    async fn insert_imported(
+        &self,
+        source: ImportSource,
+        origin: &str,
+        frames: &[ImportedFrame],
+        transcriptions: &[ImportedTranscription],
+    ) -> Result<ImportSummary, Box<dyn StdError + Send + Sync>> {
+        self.db
+            .ingest_imported(source, origin, frames, transcriptions, &self.processors)
+            .await
+    }
+}
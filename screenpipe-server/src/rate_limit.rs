@@ -0,0 +1,201 @@
+use crate::{ApiError, ErrorCode};
+use anyhow::{anyhow, Result};
+use axum::body::Body;
+use axum::extract::DefaultBodyLimit;
+use axum::http::{header, HeaderValue, Method, Request};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+/// `[api_limits]` table of the config file, limits of the API so a client flooding it,
+/// e.g. with huge searches, can't starve the database writes of the recorder
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiLimits {
+    /// Requests answered per second on average, 0 for no limit. `/health` and CORS
+    /// preflights aren't limited.
+    pub requests_per_second: f64,
+    /// Requests answered at once above the average
+    pub burst: u32,
+    /// Bytes of a request body, `/ingest` takes up to 64 MB whatever the limit
+    pub max_body_bytes: usize,
+    /// Bytes of the query string, e.g. the `q` of `/search`
+    pub max_query_length: usize,
+}
+
+impl Default for ApiLimits {
+    fn default() -> Self {
+        ApiLimits {
+            requests_per_second: 50.0,
+            burst: 100,
+            max_body_bytes: 2 * 1024 * 1024,
+            max_query_length: 8 * 1024,
+        }
+    }
+}
+
+impl ApiLimits {
+    pub fn validate(&self) -> Result<()> {
+        if !self.requests_per_second.is_finite() || self.requests_per_second < 0.0 {
+            return Err(anyhow!("requests_per_second must be 0 or more"));
+        }
+        if self.requests_per_second > 0.0 && self.burst == 0 {
+            return Err(anyhow!("burst must be at least 1"));
+        }
+        if self.max_body_bytes == 0 || self.max_query_length == 0 {
+            return Err(anyhow!(
+                "max_body_bytes and max_query_length must be at least 1"
+            ));
+        }
+        Ok(())
+    }
+
+    /// `router` answering 429, 413 and 414 to the requests over the limits
+    pub fn apply<S>(&self, router: Router<S>) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        router
+            .layer(ApiLimitLayer::new(self))
+            .layer(DefaultBodyLimit::max(self.max_body_bytes))
+    }
+}
+
+/// Longest `Retry-After` answered
+const MAX_WAIT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Tokens refilled at `rate` per second up to `burst`, one taken per request
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    /// Tokens left and when they were counted
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, burst: u32) -> Self {
+        TokenBucket {
+            rate,
+            burst: burst as f64,
+            state: Mutex::new((burst as f64, Instant::now())),
+        }
+    }
+
+    /// Takes a token, or the time until the next one otherwise
+    fn acquire(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let (tokens, counted_at) = &mut *state;
+        let now = Instant::now();
+        *tokens =
+            (*tokens + now.duration_since(*counted_at).as_secs_f64() * self.rate).min(self.burst);
+        *counted_at = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            // Too long to count for tiny rates, e.g. 1e-20
+            let wait = Duration::try_from_secs_f64((1.0 - *tokens) / self.rate);
+            Err(wait.unwrap_or(MAX_WAIT).min(MAX_WAIT))
+        }
+    }
+}
+
+/// Rate and query string limits of `ApiLimits` around the routes it wraps, shared by
+/// every client
+#[derive(Clone)]
+pub struct ApiLimitLayer {
+    bucket: Option<Arc<TokenBucket>>,
+    max_query_length: usize,
+}
+
+impl ApiLimitLayer {
+    pub fn new(limits: &ApiLimits) -> Self {
+        Self {
+            bucket: (limits.requests_per_second > 0.0)
+                .then(|| Arc::new(TokenBucket::new(limits.requests_per_second, limits.burst))),
+            max_query_length: limits.max_query_length,
+        }
+    }
+
+    /// Response to a request over the limits
+    fn reject(&self, request: &Request<Body>) -> Option<Response> {
+        // CORS preflights of browsers, answered before their request is counted
+        if request.method() == Method::OPTIONS {
+            return None;
+        }
+        let query_length = request.uri().query().map_or(0, str::len);
+        if query_length > self.max_query_length {
+            return Some(
+                ApiError::new(
+                    ErrorCode::UriTooLong,
+                    format!(
+                        "query string of {} bytes, at most {} accepted",
+                        query_length, self.max_query_length
+                    ),
+                )
+                .into_response(),
+            );
+        }
+        // Health checks tell the server is up even while it's flooded
+        if request.uri().path().ends_with("/health") {
+            return None;
+        }
+        let wait = self.bucket.as_ref()?.acquire().err()?;
+        let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+        let mut response = ApiError::new(
+            ErrorCode::RateLimited,
+            format!("too many requests, retry in {}s", retry_after),
+        )
+        .into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        Some(response)
+    }
+}
+
+impl<S> Layer<S> for ApiLimitLayer {
+    type Service = ApiLimitService<S>;
+
+    fn layer(&self, service: S) -> <Self as Layer<S>>::Service {
+        ApiLimitService {
+            inner: service,
+            limits: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiLimitService<S> {
+    inner: S,
+    limits: ApiLimitLayer,
+}
+
+impl<S> Service<Request<Body>> for ApiLimitService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if let Some(response) = self.limits.reject(&request) {
+            return Box::pin(async move { Ok(response) });
+        }
+        // The service polled ready is the one to call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move { inner.call(request).await })
+    }
+}
@@ -0,0 +1,230 @@
+use crate::DatabaseManager;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use log::{debug, info, warn};
+use screenpipe_core::find_ffmpeg_path;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Extension of the file a chunk is remuxed into, left over if the repair was interrupted
+const REPAIR_EXTENSION: &str = "repairing.mp4";
+
+#[derive(Debug, Default, Serialize)]
+pub struct RecoverySummary {
+    /// Chunks cut off mid-write, remuxed so they play again
+    pub repaired: usize,
+    /// Empty or unreadable chunk files without a row, deleted
+    pub removed: usize,
+    /// Empty or unreadable chunks with a row, kept and marked corrupt along with their
+    /// frames, whose text is still searchable
+    pub corrupt: usize,
+    /// Chunk files which had no row in the database
+    pub backfilled: usize,
+    /// Rows whose file is gone, left as is since the data directory may just be unmounted
+    pub missing: usize,
+}
+
+/// How the writing of an mp4 ended, from its top-level boxes
+#[derive(Debug, PartialEq, Eq)]
+enum ChunkState {
+    Complete,
+    /// Cut off after its `moov` and some media, what's before the cut can be remuxed
+    Truncated,
+    /// Empty, or cut off before its `moov` or its first media was written
+    Unrecoverable,
+    /// Not an mp4, e.g. an encrypted chunk or the stub of an offloaded one
+    Unknown,
+}
+
+/// Video chunks are named after the time they were started, see `save_frames_as_video`
+fn is_video_chunk_name(name: &str) -> bool {
+    name.strip_suffix(".mp4").map_or(false, |stem| {
+        NaiveDateTime::parse_from_str(stem, "%Y-%m-%d_%H-%M-%S").is_ok()
+    })
+}
+
+fn read_box_header(file: &mut File, offset: u64) -> std::io::Result<Option<(u64, [u8; 4])>> {
+    let mut header = [0u8; 8];
+    file.seek(SeekFrom::Start(offset))?;
+    match file.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let kind = [header[4], header[5], header[6], header[7]];
+    let size = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+        // Extends to the end of the file, like the mdat of a chunk ffmpeg didn't finalize
+        0 => file.metadata()?.len() - offset,
+        1 => {
+            let mut large_size = [0u8; 8];
+            match file.read_exact(&mut large_size) {
+                Ok(()) => u64::from_be_bytes(large_size),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+        size => size as u64,
+    };
+    Ok(Some((size, kind)))
+}
+
+fn chunk_state(path: &Path) -> std::io::Result<ChunkState> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let (mut has_moov, mut has_media, mut truncated) = (false, false, false);
+    let mut offset = 0;
+    while offset < len {
+        let Some((size, kind)) = read_box_header(&mut file, offset)? else {
+            truncated = true;
+            break;
+        };
+        if offset == 0 && &kind != b"ftyp" {
+            return Ok(ChunkState::Unknown);
+        }
+        if size < 8 || offset + size > len {
+            truncated = true;
+            break;
+        }
+        match &kind {
+            b"moov" => has_moov = true,
+            b"mdat" => has_media = true,
+            _ => {}
+        }
+        offset += size;
+    }
+    Ok(match (has_moov && has_media, truncated) {
+        (false, _) => ChunkState::Unrecoverable,
+        (true, true) => ChunkState::Truncated,
+        (true, false) => ChunkState::Complete,
+    })
+}
+
+/// Rewrites the complete fragments of a chunk cut off mid-write into a finalized mp4
+async fn remux(ffmpeg_path: &Path, path: &Path) -> Result<()> {
+    let repaired = path.with_extension(REPAIR_EXTENSION);
+    let output = Command::new(ffmpeg_path)
+        .args(["-y", "-i"])
+        .arg(path)
+        .args(["-c", "copy"])
+        .arg(&repaired)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await?;
+    let repaired_state = {
+        let repaired = repaired.clone();
+        tokio::task::spawn_blocking(move || chunk_state(&repaired)).await?
+    };
+    if !output.status.success() || !matches!(repaired_state, Ok(ChunkState::Complete)) {
+        let _ = tokio::fs::remove_file(&repaired).await;
+        return Err(anyhow!(
+            "ffmpeg couldn't remux it: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    tokio::fs::rename(&repaired, path).await?;
+    Ok(())
+}
+
+/// Brings the video chunks of `chunk_dir` and the database back in sync after a crash or
+/// power loss, before recording starts. Chunks cut off mid-write are remuxed, empty and
+/// unreadable ones are marked corrupt, or deleted when nothing refers to them, and chunk
+/// files without a row get one. Encrypted chunks, stubs of offloaded ones and chunks
+/// marked corrupt before are left as is.
+pub async fn recover_chunks(db: &DatabaseManager, chunk_dir: &Path) -> Result<RecoverySummary> {
+    let mut summary = RecoverySummary::default();
+    let mut rows: HashMap<PathBuf, i64> = db
+        .list_video_chunks()
+        .await?
+        .into_iter()
+        .map(|(id, file_path)| (PathBuf::from(file_path), id))
+        .collect();
+    let corrupt: HashSet<i64> = db.list_corrupt_video_chunks().await?.into_iter().collect();
+
+    let mut chunks = Vec::new();
+    let mut entries = tokio::fs::read_dir(chunk_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if name.ends_with(&format!(".{}", REPAIR_EXTENSION)) {
+            // The chunk itself wasn't replaced yet
+            tokio::fs::remove_file(&path).await?;
+        } else if is_video_chunk_name(name) {
+            chunks.push(path);
+        }
+    }
+    chunks.sort();
+
+    let ffmpeg_path = find_ffmpeg_path();
+    for path in chunks {
+        let row = rows.remove(&path);
+        if row.map_or(false, |id| corrupt.contains(&id)) {
+            continue;
+        }
+        let state = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || chunk_state(&path)).await??
+        };
+        debug!("Video chunk {} is {:?}", path.display(), state);
+        let readable = match state {
+            ChunkState::Unknown => continue,
+            ChunkState::Complete => true,
+            ChunkState::Truncated => {
+                let Some(ffmpeg_path) = &ffmpeg_path else {
+                    warn!("ffmpeg not found, can't repair {}", path.display());
+                    continue;
+                };
+                match remux(ffmpeg_path, &path).await {
+                    Ok(()) => {
+                        info!("Repaired the video chunk {}", path.display());
+                        summary.repaired += 1;
+                        true
+                    }
+                    Err(e) => {
+                        warn!("Failed to repair {}: {}", path.display(), e);
+                        false
+                    }
+                }
+            }
+            ChunkState::Unrecoverable => false,
+        };
+        let file_path = path.to_string_lossy();
+        match (readable, row) {
+            (false, Some(id)) => {
+                db.mark_video_chunk_corrupt(id).await?;
+                warn!(
+                    "The video chunk {} is unreadable, marked corrupt",
+                    file_path
+                );
+                summary.corrupt += 1;
+            }
+            (false, None) => {
+                tokio::fs::remove_file(&path).await?;
+                warn!("Removed the unreadable video chunk {}", file_path);
+                summary.removed += 1;
+            }
+            (true, Some(_)) => {}
+            (true, None) => {
+                db.insert_video_chunk_without_codec(&file_path).await?;
+                info!("Added the video chunk {} to the database", file_path);
+                summary.backfilled += 1;
+            }
+        }
+    }
+
+    summary.missing = rows.keys().filter(|path| !path.exists()).count();
+    if summary.missing > 0 {
+        warn!(
+            "{} video chunks of the database have no file, were they moved?",
+            summary.missing
+        );
+    }
+    Ok(summary)
+}
@@ -0,0 +1,229 @@
+use crate::core::DataOutputWrapper;
+use crate::replay::extract_frames;
+use crate::tiering::fetch_offloaded;
+use crate::{ocr_words, BackupLocation, DatabaseManager, FrameRecord, Shutdown, Storage};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use screenpipe_vision::{process_ocr_task, OcrEngine, OcrLanguages};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc::channel, Mutex};
+
+/// How often frames queued to be OCR'd again are looked for
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// OCRs again, in the background, the frames queued with `POST /vision/reocr`, with the
+/// engine of the recording. Their video chunk is decoded one at a time, oldest first, and
+/// the new OCR replaces the stored one, kept as a version, through the recording's storage,
+/// so processors and mirrors see it too.
+pub(crate) struct FrameReocr {
+    db: Arc<DatabaseManager>,
+    storage: Arc<dyn Storage>,
+    ocr_engine: Arc<OcrEngine>,
+    ocr_languages: OcrLanguages,
+    ocr_max_dimension: Option<u32>,
+    /// Where chunks are offloaded to, offloaded chunks are fetched back from it
+    offload: Option<Arc<BackupLocation>>,
+}
+
+impl FrameReocr {
+    pub fn new(
+        db: Arc<DatabaseManager>,
+        storage: Arc<dyn Storage>,
+        ocr_engine: &OcrEngine,
+        ocr_languages: OcrLanguages,
+        ocr_max_dimension: Option<u32>,
+        offload: Option<Arc<BackupLocation>>,
+    ) -> Self {
+        FrameReocr {
+            db,
+            storage,
+            ocr_engine: Arc::new(ocr_engine.for_recorded_frames()),
+            ocr_languages,
+            ocr_max_dimension,
+            offload,
+        }
+    }
+
+    /// OCRs the queued frames again until the shutdown
+    pub async fn run(self, shutdown: Shutdown) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+                _ = shutdown.requested() => return,
+            }
+            while !shutdown.is_requested() {
+                let (video_chunk_id, file_path) = match self.db.next_chunk_to_reocr().await {
+                    Ok(Some(chunk)) => chunk,
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!("Failed to get the video chunk to OCR again: {}", e);
+                        break;
+                    }
+                };
+                if let Err(e) = self
+                    .reocr_chunk(video_chunk_id, &file_path, &shutdown)
+                    .await
+                {
+                    // Retried on the next check
+                    error!("Failed to OCR the frames of {} again: {}", file_path, e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// OCRs the queued frames of the chunk again, they're taken off the queue when the
+    /// chunk can't be read
+    async fn reocr_chunk(
+        &self,
+        video_chunk_id: i64,
+        file_path: &str,
+        shutdown: &Shutdown,
+    ) -> Result<(), sqlx::Error> {
+        let frames = self.db.frames_to_reocr(video_chunk_id).await?;
+        info!("OCR'ing {} frames of {} again", frames.len(), file_path);
+        let frames_dir = match self.frames_dir(video_chunk_id, file_path) {
+            Ok(frames_dir) => frames_dir,
+            Err(e) => {
+                warn!("Can't OCR the frames of {} again: {}", file_path, e);
+                return self.db.cancel_reocr(video_chunk_id).await;
+            }
+        };
+        let result = match self.extract(file_path, &frames_dir).await {
+            Ok(()) => {
+                self.reocr_frames(file_path, frames, &frames_dir, shutdown)
+                    .await
+            }
+            Err(e) => {
+                warn!("Can't OCR the frames of {} again: {}", file_path, e);
+                self.db.cancel_reocr(video_chunk_id).await
+            }
+        };
+        if frames_dir.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&frames_dir) {
+                warn!("Failed to remove {}: {}", frames_dir.display(), e);
+            }
+        }
+        result
+    }
+
+    /// Directory the frames of the chunk are extracted to, next to the chunk and only readable
+    /// by the user when the chunks are encrypted
+    fn frames_dir(&self, video_chunk_id: i64, file_path: &str) -> std::io::Result<PathBuf> {
+        let name = format!("screenpipe_reocr_{}", video_chunk_id);
+        #[cfg(feature = "encryption")]
+        if self.db.encryption_key().is_some() {
+            return Ok(crate::encryption::decrypted_dir(Path::new(file_path))?.join(name));
+        }
+        let _ = file_path;
+        Ok(std::env::temp_dir().join(name))
+    }
+
+    /// Extracts the frames of the chunk to `frames_dir`, fetched back first when it's
+    /// offloaded and from a decrypted copy when it's encrypted
+    async fn extract(&self, file_path: &str, frames_dir: &Path) -> anyhow::Result<()> {
+        fetch_offloaded(self.offload.as_deref(), file_path).await?;
+        let path = Path::new(file_path);
+        #[cfg(feature = "encryption")]
+        if let Some(key) = self.db.encryption_key() {
+            if let Some(copy) = crate::encryption::decrypted_copy(path, key)? {
+                // Removed once the frames are extracted
+                return extract_frames(&copy.to_string_lossy(), frames_dir).await;
+            }
+        }
+        extract_frames(file_path, frames_dir).await
+    }
+
+    async fn reocr_frames(
+        &self,
+        file_path: &str,
+        frames: Vec<(i64, i64, DateTime<Utc>, String, Option<String>)>,
+        frames_dir: &Path,
+        shutdown: &Shutdown,
+    ) -> Result<(), sqlx::Error> {
+        let engine_name = format!("{:?}", self.ocr_engine);
+        let previous_text_json = Arc::new(Mutex::new(None));
+        let previous_data_output = Arc::new(Mutex::new(None));
+        let (result_tx, mut result_rx) = channel(1);
+
+        for (frame_id, offset_index, timestamp, app_name, ocr_engine) in frames {
+            // The frames left stay queued for the next start
+            if shutdown.is_requested() {
+                return Ok(());
+            }
+            if ocr_engine.as_deref() == Some(engine_name.as_str()) {
+                debug!("Frame {} was OCR'd with {} already", frame_id, engine_name);
+                self.db.skip_reocr(frame_id).await?;
+                continue;
+            }
+            // ffmpeg numbers the images from 1
+            let image_path = frames_dir.join(format!("{:06}.png", offset_index + 1));
+            let image = match image::open(&image_path) {
+                Ok(image) => image,
+                Err(e) => {
+                    warn!("No frame {} in {}: {}", offset_index, file_path, e);
+                    self.db.skip_reocr(frame_id).await?;
+                    continue;
+                }
+            };
+            let windows = self.db.get_frame_windows(frame_id).await?;
+            if let Err(e) = process_ocr_task(
+                Arc::new(image),
+                offset_index as u64,
+                Instant::now(),
+                result_tx.clone(),
+                &previous_text_json,
+                false,
+                Arc::clone(&self.ocr_engine),
+                app_name.clone(),
+                windows,
+                self.ocr_max_dimension,
+                self.ocr_languages.for_app(&app_name),
+                None,
+                &previous_data_output,
+                None,
+            )
+            .await
+            {
+                warn!(
+                    "Failed to OCR frame {} of {}: {}",
+                    offset_index, file_path, e
+                );
+                self.db.skip_reocr(frame_id).await?;
+                continue;
+            }
+            let Some(frame) = result_rx.recv().await else {
+                return Ok(());
+            };
+
+            let text_json = serde_json::to_string(&frame.text_json).unwrap_or_default();
+            let new_text_json_vs_previous_frame =
+                serde_json::to_string(&frame.new_text_json).unwrap_or_default();
+            let words = ocr_words(&frame.data_output);
+            let raw_data_output_from_ocr = DataOutputWrapper {
+                data_output: frame.data_output,
+            }
+            .to_json();
+            let record = FrameRecord {
+                timestamp,
+                app_name,
+                text: frame.text,
+                raw_text: None,
+                text_json,
+                new_text_json_vs_previous_frame,
+                raw_data_output_from_ocr,
+                ocr_engine: engine_name.clone(),
+                words,
+                entities: Vec::new(),
+                windows: frame.windows,
+                region: None,
+            };
+            self.storage
+                .write_reocr(file_path, offset_index, record)
+                .await?;
+        }
+        Ok(())
+    }
+}
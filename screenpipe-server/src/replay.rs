@@ -0,0 +1,252 @@
+#[cfg(feature = "audio")]
+use crate::core::process_audio_result;
+use crate::core::DataOutputWrapper;
+use crate::DatabaseManager;
+use anyhow::{anyhow, Result};
+#[cfg(feature = "audio")]
+use log::error;
+use log::{debug, info, warn};
+#[cfg(feature = "audio")]
+use screenpipe_audio::{
+    create_whisper_channel, play_synthetic_audio, CloudSttConfig, TranscriptionLanguages,
+    WhisperModelConfig,
+};
+use screenpipe_core::find_ffmpeg_path;
+use screenpipe_vision::{process_ocr_task, OcrEngine, SyntheticCaptureSource};
+#[cfg(feature = "audio")]
+use std::collections::HashMap;
+use std::path::Path;
+#[cfg(feature = "audio")]
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+#[cfg(feature = "audio")]
+use std::time::Duration;
+use std::time::Instant;
+use tokio::process::Command;
+#[cfg(feature = "audio")]
+use tokio::sync::watch;
+use tokio::sync::{mpsc::channel, Mutex};
+
+#[derive(Debug, Default)]
+pub struct ReplaySummary {
+    pub video_chunks: usize,
+    pub frames: usize,
+    pub audio_chunks: usize,
+}
+
+/// Re-runs the chunks recorded in `archive_dir` (a screenpipe data directory) through
+/// the current OCR and transcription pipeline into a scratch database in `output_dir`.
+/// Frames and audio chunks are processed one at a time in recording order, so two
+/// replays of the same archive give the same result.
+pub async fn replay_archive(
+    archive_dir: &Path,
+    output_dir: &Path,
+    ocr_engine: Arc<OcrEngine>,
+    #[cfg(feature = "audio")] cloud_stt: Option<CloudSttConfig>,
+) -> Result<ReplaySummary> {
+    let archive_db_path = archive_dir.join("db.sqlite");
+    if !archive_db_path.exists() {
+        return Err(anyhow!("No database found at {}", archive_db_path.display()));
+    }
+    let scratch_db_path = output_dir.join("db.sqlite");
+    if scratch_db_path.exists() {
+        return Err(anyhow!(
+            "{} already exists, use another output directory",
+            scratch_db_path.display()
+        ));
+    }
+    std::fs::create_dir_all(output_dir)?;
+
+    let archive_db = DatabaseManager::new(&archive_db_path.to_string_lossy()).await?;
+    let scratch_db = DatabaseManager::new(&scratch_db_path.to_string_lossy()).await?;
+    let mut summary = ReplaySummary::default();
+
+    for (video_chunk_id, file_path) in archive_db.list_video_chunks().await? {
+        if !Path::new(&file_path).exists() {
+            warn!("Skipping missing video chunk {}", file_path);
+            continue;
+        }
+        let frames_dir = output_dir.join("frames").join(video_chunk_id.to_string());
+        extract_frames(&file_path, &frames_dir).await?;
+        let app_names = archive_db.get_frame_app_names(video_chunk_id).await?;
+
+        scratch_db
+            .insert_video_chunk_without_codec(&file_path)
+            .await?;
+        summary.frames +=
+            replay_frames(&scratch_db, &frames_dir, &app_names, Arc::clone(&ocr_engine)).await?;
+        summary.video_chunks += 1;
+
+        std::fs::remove_dir_all(&frames_dir)?;
+        info!("Replayed video chunk {}", file_path);
+    }
+
+    summary.audio_chunks = replay_audio(
+        &archive_db,
+        &scratch_db,
+        output_dir,
+        #[cfg(feature = "audio")]
+        cloud_stt,
+    )
+    .await?;
+
+    Ok(summary)
+}
+
+pub(crate) async fn extract_frames(video_path: &str, frames_dir: &Path) -> Result<()> {
+    let ffmpeg_path = find_ffmpeg_path().ok_or_else(|| anyhow!("ffmpeg not found"))?;
+    std::fs::create_dir_all(frames_dir)?;
+
+    // passthrough keeps exactly one image per encoded frame, matching frames.offset_index
+    let output = Command::new(ffmpeg_path)
+        .args(["-i", video_path, "-vsync", "passthrough"])
+        .arg(frames_dir.join("%06d.png"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to extract frames from {}: {}",
+            video_path,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+async fn replay_frames(
+    db: &DatabaseManager,
+    frames_dir: &Path,
+    app_names: &[String],
+    ocr_engine: Arc<OcrEngine>,
+) -> Result<usize> {
+    let mut source = SyntheticCaptureSource::new(frames_dir)?;
+    let previous_text_json = Arc::new(Mutex::new(None));
+    let previous_data_output = Arc::new(Mutex::new(None));
+    let (result_tx, mut result_rx) = channel(1);
+
+    for frame_number in 0..source.len() {
+        let (image, _, _) = source.capture();
+        let app_name = app_names.get(frame_number).cloned().unwrap_or_default();
+
+        process_ocr_task(
+            Arc::new(image),
+            frame_number as u64,
+            Instant::now(),
+            result_tx.clone(),
+            &previous_text_json,
+            false,
+            Arc::clone(&ocr_engine),
+            app_name,
+            Vec::new(),
+            None,
+            &[],
+            None,
+            &previous_data_output,
+            None,
+        )
+        .await?;
+        let frame = result_rx
+            .recv()
+            .await
+            .ok_or_else(|| anyhow!("OCR result channel closed"))?;
+
+        let frame_id = db.insert_frame(&frame.app_name).await?;
+        let text_json = serde_json::to_string(&frame.text_json).unwrap_or_default();
+        let new_text_json_vs_previous_frame =
+            serde_json::to_string(&frame.new_text_json).unwrap_or_default();
+        let raw_data_output_from_ocr = DataOutputWrapper {
+            data_output: frame.data_output,
+        }
+        .to_json();
+        db.insert_ocr_text(
+            frame_id,
+            &frame.text,
+            &text_json,
+            &new_text_json_vs_previous_frame,
+            &raw_data_output_from_ocr,
+            &frame.app_name,
+            Arc::clone(&ocr_engine),
+        )
+        .await?;
+        debug!("Replayed frame {} of {}", frame_number, frames_dir.display());
+    }
+
+    Ok(source.len())
+}
+
+#[cfg(not(feature = "audio"))]
+async fn replay_audio(
+    archive_db: &DatabaseManager,
+    _scratch_db: &DatabaseManager,
+    _output_dir: &Path,
+) -> Result<usize> {
+    let audio_chunks = archive_db.list_audio_chunks().await?.len();
+    if audio_chunks > 0 {
+        warn!(
+            "screenpipe was built without the audio feature, skipping {} audio chunks",
+            audio_chunks
+        );
+    }
+    Ok(0)
+}
+
+#[cfg(feature = "audio")]
+async fn replay_audio(
+    archive_db: &DatabaseManager,
+    scratch_db: &DatabaseManager,
+    output_dir: &Path,
+    cloud_stt: Option<CloudSttConfig>,
+) -> Result<usize> {
+    // Copies numbered in recording order, mapped back to the original chunk path
+    let audio_dir = output_dir.join("audio");
+    std::fs::create_dir_all(&audio_dir)?;
+    let mut original_paths: HashMap<String, String> = HashMap::new();
+    for (index, (_, file_path)) in archive_db.list_audio_chunks().await?.into_iter().enumerate() {
+        let source = PathBuf::from(&file_path);
+        let Some(file_name) = source.file_name() else {
+            continue;
+        };
+        if !source.exists() {
+            warn!("Skipping missing audio chunk {}", file_path);
+            continue;
+        }
+        let copy = audio_dir.join(format!("{:06}_{}", index, file_name.to_string_lossy()));
+        std::fs::copy(&source, &copy)?;
+        original_paths.insert(copy.to_string_lossy().into_owned(), file_path);
+    }
+    if original_paths.is_empty() {
+        return Ok(0);
+    }
+
+    let (whisper_sender, mut whisper_receiver) = create_whisper_channel(
+        cloud_stt,
+        1,
+        TranscriptionLanguages::default(),
+        watch::channel(WhisperModelConfig::default()).1,
+    )
+    .await?;
+    play_synthetic_audio(audio_dir.clone(), Duration::ZERO, whisper_sender).await?;
+
+    let mut replayed = 0;
+    while let Some(mut result) = whisper_receiver.recv().await {
+        match original_paths.get(&result.input.path) {
+            Some(original_path) => result.input.path = original_path.clone(),
+            None => {
+                error!("Unexpected transcription for {}", result.input.path);
+                continue;
+            }
+        }
+        process_audio_result(scratch_db, result, None).await;
+        replayed += 1;
+        if replayed == original_paths.len() {
+            break;
+        }
+    }
+
+    scratch_db.flush_writes().await;
+    std::fs::remove_dir_all(&audio_dir)?;
+    Ok(replayed)
+}
@@ -0,0 +1,313 @@
+use crate::server::into_content_item;
+use crate::{ContentType, DatabaseManager, SearchResult};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// OCR results or transcriptions read at once by `RuleEngine::check_stored`
+const RULE_CHECK_BATCH: u32 = 500;
+
+/// Keyword or regex watched in incoming OCR text and transcriptions, a match is POSTed to
+/// `webhook_url`. See `POST /rules`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Rule {
+    pub id: i64,
+    pub name: String,
+    /// Keyword, case insensitive, or a regex with `is_regex`
+    pub pattern: String,
+    pub is_regex: bool,
+    pub content_type: ContentType,
+    pub webhook_url: String,
+    /// Seconds without webhook after a match, text stays on screen for many frames
+    pub cooldown_secs: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of `POST /rules`
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewRule {
+    pub name: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub is_regex: bool,
+    #[serde(default)]
+    pub content_type: ContentType,
+    pub webhook_url: String,
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+fn default_cooldown_secs() -> u64 {
+    300
+}
+
+impl NewRule {
+    /// Why the rule can't be watched, e.g. an invalid regex
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.pattern.trim().is_empty() {
+            return Err("A rule needs a name and a pattern".to_string());
+        }
+        if self.is_regex {
+            Matcher::regex(&self.pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+        }
+        match reqwest::Url::parse(&self.webhook_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+            _ => Err(format!("Invalid webhook URL: {}", self.webhook_url)),
+        }
+    }
+}
+
+enum Matcher {
+    /// Lowercase
+    Keyword(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn regex(pattern: &str) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(pattern).case_insensitive(true).build()
+    }
+
+    fn new(rule: &Rule) -> Result<Self, regex::Error> {
+        Ok(if rule.is_regex {
+            Matcher::Regex(Self::regex(&rule.pattern)?)
+        } else {
+            Matcher::Keyword(rule.pattern.to_lowercase())
+        })
+    }
+
+    /// Text matched in `text`
+    fn find(&self, text: &str) -> Option<String> {
+        match self {
+            Matcher::Keyword(keyword) => text
+                .to_lowercase()
+                .contains(keyword.as_str())
+                .then(|| keyword.clone()),
+            Matcher::Regex(regex) => regex.find(text).map(|found| found.as_str().to_string()),
+        }
+    }
+}
+
+struct CompiledRule {
+    rule: Rule,
+    matcher: Matcher,
+    last_sent: Mutex<Option<Instant>>,
+}
+
+impl CompiledRule {
+    /// Whether the cooldown is over, starting a new one when it is
+    fn start_cooldown(&self) -> bool {
+        let mut last_sent = self.last_sent.lock().unwrap();
+        let cooldown = Duration::from_secs(self.rule.cooldown_secs);
+        if last_sent.map_or(false, |sent| sent.elapsed() < cooldown) {
+            return false;
+        }
+        *last_sent = Some(Instant::now());
+        true
+    }
+}
+
+/// Rules checked on incoming content. Clones share the rules: the API changes them while
+/// `run_rule_checks` checks them.
+#[derive(Clone, Default)]
+pub struct RuleEngine {
+    rules: Arc<RwLock<Vec<Arc<CompiledRule>>>>,
+    client: reqwest::Client,
+}
+
+impl RuleEngine {
+    pub fn new() -> Self {
+        RuleEngine::default()
+    }
+
+    /// Watches the rules stored in `db` from now on
+    pub async fn reload(&self, db: &DatabaseManager) -> Result<(), sqlx::Error> {
+        self.set_rules(db.list_rules().await?);
+        Ok(())
+    }
+
+    /// Watches `rules` instead of the current ones, the cooldowns of the rules kept go on
+    pub fn set_rules(&self, rules: Vec<Rule>) {
+        let mut current = self.rules.write().unwrap();
+        let mut cooldowns: HashMap<i64, Option<Instant>> = current
+            .iter()
+            .map(|compiled| (compiled.rule.id, *compiled.last_sent.lock().unwrap()))
+            .collect();
+        *current = rules
+            .into_iter()
+            .filter_map(|rule| match Matcher::new(&rule) {
+                Ok(matcher) => Some(Arc::new(CompiledRule {
+                    last_sent: Mutex::new(cooldowns.remove(&rule.id).flatten()),
+                    matcher,
+                    rule,
+                })),
+                Err(e) => {
+                    warn!("Skipping rule {} with an invalid regex: {}", rule.name, e);
+                    None
+                }
+            })
+            .collect();
+        info!("Watching {} rules", current.len());
+    }
+
+    /// Sends the webhook of every rule matching the text of `result`, with `result` as
+    /// `/search` returns it
+    pub fn check(&self, result: SearchResult) {
+        let (content_type, text) = match &result {
+            SearchResult::OCR(ocr) => (ContentType::OCR, ocr.ocr_text.as_str()),
+            SearchResult::Audio(audio) => (ContentType::Audio, audio.transcription.as_str()),
+            _ => return,
+        };
+        if text.is_empty() {
+            return;
+        }
+        let mut matches = Vec::new();
+        for compiled in self.rules.read().unwrap().iter() {
+            let rule = &compiled.rule;
+            if rule.content_type != ContentType::All && rule.content_type != content_type {
+                continue;
+            }
+            let Some(matched) = compiled.matcher.find(text) else {
+                continue;
+            };
+            if !compiled.start_cooldown() {
+                debug!("Rule {} matched during its cooldown", rule.name);
+                continue;
+            }
+            matches.push((rule.clone(), matched));
+        }
+        if matches.is_empty() {
+            return;
+        }
+
+        let item = match serde_json::to_value(into_content_item(result)) {
+            Ok(item) => item,
+            Err(e) => {
+                error!("Failed to serialize the content matched by a rule: {}", e);
+                return;
+            }
+        };
+        let place = match content_type {
+            ContentType::Audio => "in a transcription",
+            _ => "on screen",
+        };
+        for (rule, matched) in matches {
+            let mut payload = item.clone();
+            // Readable by chat webhooks, e.g. Slack's
+            payload["text"] = json!(format!(
+                "screenpipe rule \"{}\" matched \"{}\" {}",
+                rule.name, matched, place
+            ));
+            payload["rule"] = json!({"id": rule.id, "name": rule.name, "pattern": rule.pattern});
+            payload["matched"] = json!(matched);
+            self.send(rule.webhook_url, rule.name, payload);
+        }
+    }
+
+    /// Checks the rules on the OCR text and transcriptions stored after `cursor`, and moves
+    /// it past them
+    pub async fn check_stored(
+        &self,
+        db: &DatabaseManager,
+        cursor: &mut RuleCursor,
+    ) -> Result<(), sqlx::Error> {
+        if self.rules.read().unwrap().is_empty() {
+            // Content stored while there were no rules isn't checked by the next ones
+            (cursor.frame_id, cursor.transcription_id) = db.get_last_content_ids().await?;
+            return Ok(());
+        }
+        loop {
+            let results = db
+                .get_ocr_after(cursor.frame_id, None, RULE_CHECK_BATCH)
+                .await?;
+            let full = results.len() as u32 == RULE_CHECK_BATCH;
+            for ocr in results {
+                cursor.frame_id = ocr.frame_id;
+                if ocr.timestamp >= cursor.since {
+                    self.check(SearchResult::OCR(ocr));
+                }
+            }
+            if !full {
+                break;
+            }
+        }
+        loop {
+            let results = db
+                .get_transcriptions_after(cursor.transcription_id, RULE_CHECK_BATCH)
+                .await?;
+            let full = results.len() as u32 == RULE_CHECK_BATCH;
+            for audio in results {
+                cursor.transcription_id = audio.transcription_id;
+                if audio.timestamp >= cursor.since {
+                    self.check(SearchResult::Audio(audio));
+                }
+            }
+            if !full {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&self, webhook_url: String, rule_name: String, payload: serde_json::Value) {
+        let request = self
+            .client
+            .post(&webhook_url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(&payload);
+        tokio::spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => info!("Sent webhook of rule {} to {}", rule_name, webhook_url),
+                Err(e) => warn!("Failed to send webhook of rule {}: {}", rule_name, e),
+            }
+        });
+    }
+}
+
+/// Where `RuleEngine::check_stored` is in the stored content
+#[derive(Debug, Clone, Copy)]
+pub struct RuleCursor {
+    pub frame_id: i64,
+    pub transcription_id: i64,
+    /// Content recorded before isn't checked, e.g. imported history
+    pub since: DateTime<Utc>,
+}
+
+impl RuleCursor {
+    /// After the content stored so far
+    pub async fn now(db: &DatabaseManager) -> Result<Self, sqlx::Error> {
+        let (frame_id, transcription_id) = db.get_last_content_ids().await?;
+        Ok(RuleCursor {
+            frame_id,
+            transcription_id,
+            since: Utc::now(),
+        })
+    }
+}
+
+/// Checks `rules` on the content stored every `interval`, once stored so their webhooks get
+/// the frame or chunk of the match, whether it was recorded, pushed to `/ingest` or imported
+pub async fn run_rule_checks(db: Arc<DatabaseManager>, rules: RuleEngine, interval: Duration) {
+    let mut cursor = loop {
+        match RuleCursor::now(&db).await {
+            Ok(cursor) => break cursor,
+            Err(e) => error!(
+                "Failed to read the last stored content for the rules: {}",
+                e
+            ),
+        }
+        tokio::time::sleep(interval).await;
+    };
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Err(e) = rules.check_stored(&db, &mut cursor).await {
+            error!("Failed to check the rules on the new content: {}", e);
+        }
+    }
+}
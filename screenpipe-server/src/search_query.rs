@@ -0,0 +1,308 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+
+/// Returned with query syntax errors so clients can show it to users
+pub const SEARCH_QUERY_SYNTAX: &str = r#"word            matches the word
+"exact phrase"  matches the words next to each other, in this order
+invoi*          matches words starting with invoi
+foo bar         matches both words, same as foo AND bar
+foo OR bar      matches either word
+foo NOT bar     matches foo but not bar
+(foo OR bar)    groups
+Operators must be uppercase, other punctuation is ignored. With fuzzy=true, words of 4
+characters or more also match variants with a typo (2 from 8 characters)."#;
+
+#[derive(Debug, PartialEq)]
+pub struct SearchQueryError(String);
+
+impl fmt::Display for SearchQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid search query: {}", self.0)
+    }
+}
+
+impl StdError for SearchQueryError {}
+
+/// Searches fail with it as a decode error of their query, see `search_syntax_error`
+impl From<SearchQueryError> for sqlx::Error {
+    fn from(e: SearchQueryError) -> Self {
+        sqlx::Error::Decode(Box::new(e))
+    }
+}
+
+enum Token {
+    Term {
+        text: String,
+        phrase: bool,
+        prefix: bool,
+    },
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+/// Translates a user query into a FTS5 MATCH expression. Words and phrases are
+/// quoted so punctuation never reaches the FTS5 parser, only the operators of
+/// `SEARCH_QUERY_SYNTAX` are passed through. Returns `None` for a query without
+/// terms, which matches everything.
+pub fn parse_search_query(query: &str) -> Result<Option<String>, SearchQueryError> {
+    let tokens = tokenize(query)?;
+
+    let mut expression: Vec<String> = Vec::new();
+    let mut expect_term = true;
+    let mut depth = 0;
+    for token in tokens {
+        match token {
+            Token::Term { text, prefix, .. } => {
+                if !expect_term {
+                    expression.push("AND".to_string());
+                }
+                expression.push(quote(&text, prefix));
+                expect_term = false;
+            }
+            Token::Open => {
+                if !expect_term {
+                    expression.push("AND".to_string());
+                }
+                expression.push("(".to_string());
+                depth += 1;
+                expect_term = true;
+            }
+            Token::Close => {
+                if depth == 0 {
+                    return Err(SearchQueryError("unmatched )".to_string()));
+                }
+                if expect_term {
+                    return Err(SearchQueryError(
+                        "expected a word or phrase before )".to_string(),
+                    ));
+                }
+                expression.push(")".to_string());
+                depth -= 1;
+            }
+            Token::Operator(operator) => {
+                if expect_term {
+                    return Err(SearchQueryError(format!(
+                        "expected a word or phrase before {}",
+                        operator
+                    )));
+                }
+                expression.push(operator.to_string());
+                expect_term = true;
+            }
+        }
+    }
+
+    if expression.is_empty() {
+        return Ok(None);
+    }
+    if expect_term {
+        return Err(SearchQueryError(format!(
+            "expected a word or phrase after {}",
+            expression.last().unwrap()
+        )));
+    }
+    if depth > 0 {
+        return Err(SearchQueryError("unmatched (".to_string()));
+    }
+    Ok(Some(expression.join(" ")))
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, SearchQueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err(SearchQueryError("unterminated \"".to_string())),
+                    }
+                }
+                let prefix = chars.next_if_eq(&'*').is_some();
+                if is_searchable(&phrase) {
+                    tokens.push(Token::Term {
+                        text: phrase,
+                        phrase: true,
+                        prefix,
+                    });
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"()\"".contains(*c)) {
+                    word.push(c);
+                }
+                match word.as_str() {
+                    "AND" => tokens.push(Token::Operator("AND")),
+                    "OR" => tokens.push(Token::Operator("OR")),
+                    "NOT" => tokens.push(Token::Operator("NOT")),
+                    _ => {
+                        let (word, prefix) = match word.strip_suffix('*') {
+                            Some(stem) => (stem, true),
+                            None => (word.as_str(), false),
+                        };
+                        if word.contains('*') {
+                            return Err(SearchQueryError(
+                                "* is only allowed at the end of a word".to_string(),
+                            ));
+                        }
+                        if is_searchable(word) {
+                            tokens.push(Token::Term {
+                                text: word.to_string(),
+                                phrase: false,
+                                prefix,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Terms without letters or digits have nothing to search for
+fn is_searchable(text: &str) -> bool {
+    text.chars().any(char::is_alphanumeric)
+}
+
+/// FTS5 string literal
+fn quote(text: &str, prefix: bool) -> String {
+    let quoted = format!("\"{}\"", text.replace('"', "\"\""));
+    if prefix {
+        quoted + "*"
+    } else {
+        quoted
+    }
+}
+
+/// Number of typos tolerated in a word by fuzzy search, short words have to be exact
+pub(crate) fn max_typos(word: &str) -> usize {
+    match word.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Words of the query fuzzy search looks up variants for, lowercased. Phrases and prefixes
+/// stay exact.
+pub(crate) fn fuzzy_words(query: &str) -> Result<Vec<String>, SearchQueryError> {
+    let mut words = Vec::new();
+    for token in tokenize(query)? {
+        if let Token::Term {
+            text,
+            phrase: false,
+            prefix: false,
+        } = token
+        {
+            let word = text.to_lowercase();
+            if word.chars().all(char::is_alphanumeric) && max_typos(&word) > 0 {
+                words.push(word);
+            }
+        }
+    }
+    Ok(words)
+}
+
+/// Rewrites the query in `SEARCH_QUERY_SYNTAX` with each word of `variants` replaced by
+/// a group of the word OR its variants
+pub(crate) fn expand_search_query(
+    query: &str,
+    variants: &HashMap<String, Vec<String>>,
+) -> Result<String, SearchQueryError> {
+    let mut expanded = Vec::new();
+    for token in tokenize(query)? {
+        expanded.push(match token {
+            Token::Term {
+                text,
+                phrase: false,
+                prefix: false,
+            } => match variants.get(&text.to_lowercase()) {
+                Some(words) if !words.is_empty() => {
+                    let mut group = vec![quote(&text, false)];
+                    group.extend(words.iter().map(|w| quote(w, false)));
+                    format!("( {} )", group.join(" OR "))
+                }
+                _ => quote(&text, false),
+            },
+            Token::Term { text, prefix, .. } => quote(&text, prefix),
+            Token::Operator(operator) => operator.to_string(),
+            Token::Open => "(".to_string(),
+            Token::Close => ")".to_string(),
+        });
+    }
+    Ok(expanded.join(" "))
+}
+
+/// Levenshtein distance in characters
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Wraps the matched terms in `snippet`
+pub const HIGHLIGHT_START: &str = "<mark>";
+pub const HIGHLIGHT_END: &str = "</mark>";
+pub(crate) const SNIPPET_ELLIPSIS: &str = "…";
+/// Maximum number of tokens in a snippet, FTS5 allows up to 64
+pub(crate) const SNIPPET_TOKENS: u32 = 24;
+// Private use characters, they can't collide with recognized text
+pub(crate) const HIGHLIGHT_OPEN: &str = "\u{E000}";
+pub(crate) const HIGHLIGHT_CLOSE: &str = "\u{E001}";
+
+/// Matched term, as character offsets into the full text
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Reads the offsets out of the FTS5 `highlight()` output, built with
+/// `HIGHLIGHT_OPEN` / `HIGHLIGHT_CLOSE`
+pub(crate) fn highlight_offsets(highlighted: &str) -> Vec<Highlight> {
+    let mut highlights = Vec::new();
+    let mut offset = 0;
+    let mut start = None;
+    for c in highlighted.chars() {
+        match c {
+            '\u{E000}' => start = Some(offset),
+            '\u{E001}' => {
+                if let Some(start) = start.take() {
+                    highlights.push(Highlight { start, end: offset });
+                }
+            }
+            _ => offset += 1,
+        }
+    }
+    highlights
+}
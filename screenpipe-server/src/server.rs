@@ -1,60 +1,262 @@
 use axum::{
-    extract::{Json as JsonExt, Query, State},
-    http::StatusCode,
-    response::Json as JsonResponse,
-    routing::{get, post},
+    body::Body,
+    extract::{DefaultBodyLimit, State},
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json as JsonResponse, Response},
+    routing::{delete, get, post},
     serve, Router,
 };
+#[cfg(feature = "audio")]
 use crossbeam::queue::SegQueue;
 
-use crate::{ContentType, DatabaseManager, SearchResult};
-use chrono::{DateTime, Utc};
-use log::{debug, error, info};
-use screenpipe_audio::{AudioDevice, DeviceControl};
+use crate::api_error::{INVALID_CONTENT_TYPE, INVALID_TIME};
+use crate::db::{search_syntax_error, FacetTally};
+use crate::diagnostics::{anonymize, build_archive, read_log_tail, redact_config};
+use crate::download::{download_stream, DownloadItem, MediaKind};
+use crate::search_query::{Highlight, SEARCH_QUERY_SYNTAX};
+use crate::tiering::fetch_offloaded;
+use crate::timeline::{local_day_range, timeline_segments, TimelineSegment};
+use crate::{
+    backup, build_prompt, disk_usage, prune_chunks, retrieve_sources, ActivityEvent, Anomaly,
+    AnomalyDetector, ApiError, ApiJson, ApiLimits, ApiPath, ApiQuery, ApiStorage, AskSource,
+    AudioDeviceHealth, BackupLocation, BackupSummary, Capabilities, CaptureRequests, CapturedFrame,
+    ChunkKind, Collection, CollectionItem, ContentProcessors, ContentType, DatabaseManager,
+    Diagnostics, DigestConfig, DiskUsage, EntityKind, EntitySummary, ErrorCode, Event, EventKind,
+    HealthMonitor, HeatmapBucket, IngestBatch, IngestBucket, IngestSummary, Mark, MonitorStatus,
+    NewCollection, NewRule, OCRResult, OcrWord, OllamaClient, PruneSummary, Rule, RuleEngine,
+    SearchCursor, SearchFacets, SearchResult, Subsystem, SubsystemHealth, TranscriptSession,
+    VisionStatus, LOCAL_DEVICE_ID, VISION_CAPTURE,
+};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use log::{debug, error, info, warn};
+#[cfg(feature = "audio")]
+use screenpipe_audio::{
+    list_audio_device_info, loaded_whisper_model, parse_audio_device, AudioDevice,
+    AudioDeviceConfig, AudioDeviceInfo, AudioDeviceKind, DeviceControl, DeviceType,
+    WhisperModelConfig, WhisperModelSize,
+};
+use screenpipe_vision::{CaptureTrigger, WindowRegion};
+use screenpipe_core::{
+    accelerators, circuit_breakers, Accelerator, CircuitBreakerStatus, TranscriptionSegment,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+#[cfg(feature = "audio")]
+use std::collections::HashMap;
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     net::SocketAddr,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     time::Duration,
 };
 use tokio::net::TcpListener;
+#[cfg(feature = "audio")]
+use tokio::sync::watch;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
 use tower_http::trace::TraceLayer;
 use tower_http::{cors::CorsLayer, trace::DefaultMakeSpan};
 
-use crate::plugin::ApiPluginLayer;
-
-pub struct AppState {
-    pub db: Arc<DatabaseManager>,
+use crate::export::{export_stream, ExportFormat};
+#[cfg(feature = "grpc")]
+use crate::grpc::serve_grpc;
+use crate::plugin::{ApiPluginLayer, ApiPlugins};
+#[cfg(feature = "integrations")]
+use screenpipe_integrations::chat_digest::{send_digest, ChatPlatform, ChatWebhook};
+#[cfg(feature = "integrations")]
+use screenpipe_integrations::obsidian::write_daily_note;
+
+/// State of the API handlers, `S` is where they read from. The server runs on the
+/// `DatabaseManager`, handler tests can run on a `MemoryStorage`.
+pub struct AppState<S = DatabaseManager> {
+    pub db: Arc<S>,
     pub vision_control: Arc<AtomicBool>,
+    pub vision_status: Arc<VisionStatus>,
+    #[cfg(feature = "audio")]
     pub audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
-    pub devices_status: HashMap<AudioDevice, DeviceControl>,
+    /// Last control sent to each device
+    #[cfg(feature = "audio")]
+    pub devices_status: Mutex<HashMap<AudioDevice, DeviceControl>>,
+    /// Whisper model of the recording loop, swapped on change
+    #[cfg(feature = "audio")]
+    pub whisper_model: watch::Sender<WhisperModelConfig>,
+    /// Capture settings of each audio device by id, read by the recording for every chunk
+    #[cfg(feature = "audio")]
+    pub audio_device_configs: watch::Sender<HashMap<String, AudioDeviceConfig>>,
     pub app_start_time: DateTime<Utc>,
+    pub capture_paused: Arc<AtomicBool>,
+    /// Requested by `POST /marks` and `POST /vision/capture_now` for an immediate capture
+    pub capture_trigger: Arc<CaptureTrigger>,
+    /// Callers of `POST /vision/capture_now` waiting for their frame
+    pub capture_requests: CaptureRequests,
+    /// Rules checked by the recording, reloaded when `/rules` changes them
+    pub rules: RuleEngine,
+    /// Anomalies of the recording shown by `/health`
+    pub anomalies: AnomalyDetector,
+    /// Queues, audio devices and errors of the recording shown by `/health`
+    pub health: Arc<HealthMonitor>,
+    pub obsidian_vault_path: Option<PathBuf>,
+    /// `[digest]` of the config file, sent by `POST /integrations/:platform/test`
+    pub digest: Option<DigestConfig>,
+    /// Local model answering `POST /ask`
+    pub ollama: OllamaClient,
+    pub diagnostics: Diagnostics,
+    /// Where chunks are offloaded to with --offload-to, to fetch them back when read
+    pub offload: Option<Arc<BackupLocation>>,
+    /// Directory chunks are recorded to, media is only served from files in it. `None`
+    /// serves none.
+    pub media_dir: Option<PathBuf>,
+    /// Locations `POST /backup` may write to, none disables it
+    pub backup_locations: Vec<String>,
+    /// Run on what `/ingest` receives, the ones of the recording
+    pub content_processors: ContentProcessors,
+}
+
+impl<S> AppState<S> {
+    /// State of a server reading from `db` with nothing recording: vision stopped, no audio
+    /// devices and default settings, to be overridden field by field
+    pub fn new(db: Arc<S>) -> Self {
+        AppState {
+            db,
+            vision_control: Arc::new(AtomicBool::new(false)),
+            vision_status: Arc::new(VisionStatus::new()),
+            #[cfg(feature = "audio")]
+            audio_devices_control: Arc::new(SegQueue::new()),
+            #[cfg(feature = "audio")]
+            devices_status: Mutex::new(HashMap::new()),
+            #[cfg(feature = "audio")]
+            whisper_model: watch::channel(WhisperModelConfig::default()).0,
+            #[cfg(feature = "audio")]
+            audio_device_configs: watch::channel(HashMap::new()).0,
+            app_start_time: Utc::now(),
+            capture_paused: Arc::new(AtomicBool::new(false)),
+            capture_trigger: Arc::new(CaptureTrigger::new()),
+            capture_requests: CaptureRequests::new(),
+            rules: RuleEngine::new(),
+            anomalies: AnomalyDetector::new(None),
+            health: Arc::new(HealthMonitor::default()),
+            obsidian_vault_path: None,
+            digest: None,
+            ollama: OllamaClient::default(),
+            diagnostics: Diagnostics::default(),
+            offload: None,
+            media_dir: None,
+            backup_locations: Vec::new(),
+            content_processors: ContentProcessors::new(),
+        }
+    }
 }
 
+#[cfg(feature = "audio")]
 #[derive(Deserialize)]
 pub(crate) struct DeviceRequest {
     device_id: String,
 }
 
+#[cfg(feature = "audio")]
+#[derive(Deserialize)]
+pub(crate) struct BulkDeviceRequest {
+    /// Only devices of this kind, all devices when absent
+    #[serde(default)]
+    kind: Option<DeviceKind>,
+}
+
+#[cfg(feature = "audio")]
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DeviceKind {
+    Input,
+    Output,
+}
+
 // Update the SearchQuery struct
 #[derive(Deserialize)]
 pub(crate) struct SearchQuery {
     q: Option<String>,
     #[serde(flatten)]
     pagination: PaginationQuery,
-    #[serde(default)]
-    content_type: ContentType,
-    #[serde(default)]
+    /// One type, or several separated by commas for a page of each, e.g. `ocr,audio`
+    #[serde(default, deserialize_with = "deserialize_content_types")]
+    content_type: Vec<ContentType>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
     start_time: Option<DateTime<Utc>>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
     end_time: Option<DateTime<Utc>>,
     #[serde(default)]
     app_name: Option<String>, // Add this line
+    /// Also match words within a few typos, for OCR misreads
+    #[serde(default, deserialize_with = "deserialize_bool_from_string")]
+    fuzzy: bool,
+    /// Leave out the frames OCR read with a lower mean confidence, 0 - 100. Frames without
+    /// confidence are kept.
+    #[serde(default, deserialize_with = "deserialize_optional_float_from_string")]
+    min_confidence: Option<f64>,
+    /// Return the bounding boxes of the matched words of the OCR results, of every word
+    /// without query
+    #[serde(default, deserialize_with = "deserialize_bool_from_string")]
+    include_boxes: bool,
+    /// Keep the frames and transcriptions naming it, e.g. `ACME Corp`, see `GET /entities`
+    #[serde(default)]
+    entity: Option<String>,
+    /// Search the text an OCR or transcription engine read, e.g. `Tesseract`, also where it
+    /// was processed again with another since. `latest`, the default, searches the latest
+    /// text.
+    #[serde(default)]
+    engine: Option<String>,
+    /// `recent` (default) or `relevance`, across content types. Pages of relevance are
+    /// taken with `offset`, not `cursor`.
+    #[serde(default)]
+    sort: SearchSort,
+    /// With `sort=relevance`, the age at which the relevance of a result is halved, like
+    /// `12h` or `7d`
+    #[serde(default)]
+    recency_boost: Option<String>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SearchSort {
+    /// Most recent first
+    #[default]
+    Recent,
+    /// Most relevant first, see `DatabaseManager::search_by_relevance`
+    Relevance,
+}
+
+impl SearchQuery {
+    fn engine(&self) -> Option<&str> {
+        self.engine
+            .as_deref()
+            .filter(|engine| !engine.eq_ignore_ascii_case("latest"))
+    }
+}
+
+#[cfg(feature = "integrations")]
+#[derive(Deserialize)]
+pub struct ObsidianExportRequest {
+    /// Local date of the note, default to today
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    date: Option<NaiveDate>,
+    /// Override of the configured vault path
+    #[serde(default)]
+    vault_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    start: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    end: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_content_type")]
+    content_type: ContentType,
 }
 
 #[derive(Deserialize)]
@@ -65,6 +267,9 @@ pub(crate) struct PaginationQuery {
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_number_from_string")]
     offset: u32,
+    /// `next_cursor` of the previous page, instead of `offset`
+    #[serde(default)]
+    cursor: Option<String>,
 }
 
 fn deserialize_number_from_string<'de, D>(deserializer: D) -> Result<u32, D::Error>
@@ -75,6 +280,92 @@ where
     s.parse().map_err(serde::de::Error::custom)
 }
 
+fn deserialize_optional_float_from_string<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    s.map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_bool_from_string<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+fn deserialize_content_types<'de, D>(deserializer: D) -> Result<Vec<ContentType>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    let mut content_types = Vec::new();
+    for value in s.split(',').map(str::trim).filter(|value| !value.is_empty()) {
+        let content_type = parse_content_type(value).map_err(serde::de::Error::custom)?;
+        if !content_types.contains(&content_type) {
+            content_types.push(content_type);
+        }
+    }
+    Ok(content_types)
+}
+
+fn deserialize_content_type<'de, D>(deserializer: D) -> Result<ContentType, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    parse_content_type(s.trim()).map_err(serde::de::Error::custom)
+}
+
+fn parse_content_type(value: &str) -> Result<ContentType, String> {
+    serde::Deserialize::deserialize(
+        serde::de::value::StrDeserializer::<serde::de::value::Error>::new(value),
+    )
+    .map_err(|_| {
+        format!(
+            "{} {:?}, expected all, ocr, audio, clipboard or notification",
+            INVALID_CONTENT_TYPE, value
+        )
+    })
+}
+
+/// RFC 3339 time, answered as an `ErrorCode::InvalidTime` otherwise
+fn deserialize_optional_time<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    s.map(|s| {
+        s.parse::<DateTime<Utc>>().map_err(|_| {
+            serde::de::Error::custom(format!(
+                "{} {:?}, expected RFC 3339 like 2024-05-01T09:00:00Z",
+                INVALID_TIME, s
+            ))
+        })
+    })
+    .transpose()
+}
+
+/// `YYYY-MM-DD` date, answered as an `ErrorCode::InvalidTime` otherwise
+fn deserialize_optional_date<'de, D>(deserializer: D) -> Result<Option<NaiveDate>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    s.map(|s| {
+        s.parse::<NaiveDate>().map_err(|_| {
+            serde::de::Error::custom(format!(
+                "{} {:?}, expected a date like 2024-05-01",
+                INVALID_TIME, s
+            ))
+        })
+    })
+    .transpose()
+}
+
 #[derive(Deserialize)]
 struct DateRangeQuery {
     #[allow(dead_code)] // TODO
@@ -93,11 +384,25 @@ pub(crate) struct PaginatedResponse<T> {
     pagination: PaginationInfo,
 }
 
+/// Response of `/search`: one page, or a page of each type of a `content_type` list
+#[derive(Serialize)]
+#[serde(untagged)]
+pub(crate) enum SearchResponse {
+    Page(PaginatedResponse<ContentItem>),
+    ByType {
+        /// Pages by content type, e.g. `ocr`, each with its own pagination
+        by_type: BTreeMap<&'static str, PaginatedResponse<ContentItem>>,
+    },
+}
+
 #[derive(Serialize)]
 struct PaginationInfo {
     limit: u32,
     offset: u32,
     total: i64,
+    /// Cursor of the next page, absent on the last page or with `offset`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -105,6 +410,8 @@ struct PaginationInfo {
 pub(crate) enum ContentItem {
     OCR(OCRContent),
     Audio(AudioContent),
+    Clipboard(ClipboardContent),
+    Notification(NotificationContent),
 }
 
 #[derive(Serialize)]
@@ -115,6 +422,19 @@ pub(crate) struct OCRContent {
     file_path: String,
     offset_index: i64,
     app_name: String, // Add this line
+    /// Mean confidence of the words, 0 - 100
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confidence: Option<f64>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    snippet: String,
+    /// 0 - 1, how well it matches the query next to the best match of its content type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relevance: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    highlights: Vec<Highlight>,
+    /// Words with their bounding box in the frame, with `include_boxes`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    boxes: Vec<OcrWord>,
 }
 
 #[derive(Serialize)]
@@ -124,12 +444,68 @@ pub(crate) struct AudioContent {
     timestamp: DateTime<Utc>,
     file_path: String,
     offset_index: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    snippet: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relevance: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    highlights: Vec<Highlight>,
+    /// Seconds from the start of the chunk of each word or phrase
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    segments: Vec<TranscriptionSegment>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ClipboardContent {
+    id: i64,
+    text: String,
+    timestamp: DateTime<Utc>,
+    app_name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    snippet: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relevance: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    highlights: Vec<Highlight>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct NotificationContent {
+    id: i64,
+    app_name: String,
+    title: String,
+    body: String,
+    timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    snippet: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relevance: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    highlights: Vec<Highlight>,
 }
 
+#[cfg(feature = "audio")]
 #[derive(Serialize)]
 pub(crate) struct DeviceStatus {
     id: String,
     is_running: bool,
+    /// Running but not recording, see `/audio/pause`
+    is_paused: bool,
+}
+
+#[cfg(feature = "audio")]
+#[derive(Serialize)]
+pub(crate) struct DeviceListItem {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    /// kind (input, output or loopback), is_default and sample_rates
+    #[serde(flatten)]
+    pub(crate) info: AudioDeviceInfo,
+    /// Whether it was last told to capture
+    pub(crate) is_running: bool,
+    pub(crate) is_paused: bool,
 }
 
 #[derive(Serialize)]
@@ -137,11 +513,24 @@ pub(crate) struct RecordingStatus {
     is_running: bool,
 }
 
+#[derive(Serialize)]
+pub(crate) struct VisionStatusResponse {
+    is_running: bool,
+    /// Paused while the user is idle
+    is_paused: bool,
+    foreground_app: Option<String>,
+    monitors: Vec<MonitorStatus>,
+}
+
 // Helper functions
 fn default_limit() -> u32 {
     20
 }
 
+// Ranked and merged pages read every result up to the page, of each content type
+const MAX_SEARCH_LIMIT: u32 = 1000;
+const MAX_SEARCH_OFFSET: u32 = 100_000;
+
 #[derive(Serialize, Deserialize)]
 pub struct HealthCheckResponse {
     pub status: String,
@@ -151,15 +540,48 @@ pub struct HealthCheckResponse {
     pub audio_status: String,
     pub message: String,
     pub verbose_instructions: Option<String>,
+    #[serde(default)]
+    pub idle: bool,
+    /// Circuit breakers of the cloud providers called so far
+    #[serde(default)]
+    pub cloud_providers: Vec<CircuitBreakerStatus>,
+    /// Hardware each local model loaded so far runs on, e.g. `{"whisper": "cuda"}`
+    #[serde(default)]
+    pub accelerators: BTreeMap<String, Accelerator>,
+    /// Recording far from usual though not stale, e.g. OCR returning empty text for an
+    /// hour. The status is `Degraded` when there are some and all else is fine
+    #[serde(default)]
+    pub anomalies: Vec<Anomaly>,
+
+    /// State of each part of the recording, to tell which one stopped. The status is
+    /// `Degraded` when one is and all else is fine
+    #[serde(default)]
+    pub subsystems: Vec<SubsystemHealth>,
+    /// Last chunk of each audio device recording
+    #[serde(default)]
+    pub audio_devices: Vec<AudioDeviceHealth>,
+    /// Frames captured and waiting for OCR
+    #[serde(default)]
+    pub ocr_queue_depth: usize,
+    /// Audio chunks recorded and waiting for their transcription
+    #[serde(default)]
+    pub whisper_backlog: usize,
+    /// Free space of the disk of the data directory
+    #[serde(default)]
+    pub free_disk_bytes: Option<u64>,
+    /// Time the last batch of rows took to be written to the database
+    #[serde(default)]
+    pub db_write_latency_ms: Option<u64>,
+    /// Panics of each content processor since start, the ones that never failed aren't
+    /// listed
+    #[serde(default)]
+    pub content_processor_failures: BTreeMap<String, u64>,
 }
 
-pub(crate) async fn search(
-    Query(query): Query<SearchQuery>,
-    State(state): State<Arc<AppState>>,
-) -> Result<
-    JsonResponse<PaginatedResponse<ContentItem>>,
-    (StatusCode, JsonResponse<serde_json::Value>),
-> {
+pub(crate) async fn search<S: ApiStorage>(
+    ApiQuery(query): ApiQuery<SearchQuery>,
+    State(state): State<Arc<AppState<S>>>,
+) -> Result<JsonResponse<SearchResponse>, ApiError> {
     info!(
         "Received search request: query='{}', content_type={:?}, limit={}, offset={}, start_time={:?}, end_time={:?}, app_name={:?}",
         query.q.as_deref().unwrap_or(""),
@@ -171,34 +593,258 @@ pub(crate) async fn search(
         query.app_name
     );
 
-    let query_str = query.q.as_deref().unwrap_or("");
+    if query.pagination.limit > MAX_SEARCH_LIMIT || query.pagination.offset > MAX_SEARCH_OFFSET {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!(
+                "limit must be at most {} and offset at most {}",
+                MAX_SEARCH_LIMIT, MAX_SEARCH_OFFSET
+            ),
+        ));
+    }
+
+    let query_str = search_query_str(&state, &query).await?;
+    let query_str = query_str.as_str();
+
+    let recency_half_life_hours = match query.recency_boost.as_deref() {
+        Some(_) if query.sort != SearchSort::Relevance => {
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "recency_boost needs sort=relevance",
+            ))
+        }
+        Some(boost) => Some(
+            parse_duration_secs(boost).ok_or_else(|| {
+                ApiError::new(
+                    ErrorCode::InvalidRequest,
+                    "recency_boost must look like 30s, 5m, 1h or 1d",
+                )
+            })? as f64
+                / 3600.0,
+        ),
+        None => None,
+    };
+
+    let cursor = match query.pagination.cursor.as_deref() {
+        Some(_) if query.pagination.offset > 0 => {
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "Use either cursor or offset",
+            ))
+        }
+        Some(_) if query.sort == SearchSort::Relevance => {
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "Use offset with sort=relevance",
+            ))
+        }
+        Some(cursor) => Some(
+            SearchCursor::decode(cursor)
+                .ok_or_else(|| ApiError::new(ErrorCode::InvalidRequest, "Invalid cursor"))?,
+        ),
+        None => None,
+    };
+
+    if query.content_type.len() > 1 {
+        // A cursor continues the page of one type, e.g. `content_type=ocr&cursor=...`
+        if cursor.is_some() {
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "Use a cursor with a single content_type",
+            ));
+        }
+        let mut by_type = BTreeMap::new();
+        for &content_type in &query.content_type {
+            // app_name only searches the screen
+            if query.app_name.is_some() && content_type != ContentType::OCR {
+                continue;
+            }
+            let page = search_content_type(
+                &state,
+                &query,
+                query_str,
+                content_type,
+                None,
+                recency_half_life_hours,
+            )
+            .await?;
+            by_type.insert(content_type.name(), page);
+        }
+        return Ok(JsonResponse(SearchResponse::ByType { by_type }));
+    }
 
     // If app_name is specified, force content_type to OCR
     let content_type = if query.app_name.is_some() {
         ContentType::OCR
     } else {
-        query.content_type
+        query.content_type.first().copied().unwrap_or_default()
     };
+    let page = search_content_type(
+        &state,
+        &query,
+        query_str,
+        content_type,
+        cursor.as_ref(),
+        recency_half_life_hours,
+    )
+    .await?;
+    Ok(JsonResponse(SearchResponse::Page(page)))
+}
 
-    let results = state
-        .db
-        .search(
-            query_str,
-            content_type,
-            query.pagination.limit,
-            query.pagination.offset,
-            query.start_time,
-            query.end_time,
-            query.app_name.as_deref(),
+/// Validates the filters of `query` shared by `/search` and `/search/facets`, returns the
+/// query to search, with the words within a few typos with `fuzzy`
+async fn search_query_str<S: ApiStorage>(
+    state: &AppState<S>,
+    query: &SearchQuery,
+) -> Result<String, ApiError> {
+    if query
+        .min_confidence
+        .map_or(false, |confidence| !(0.0..=100.0).contains(&confidence))
+    {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            "min_confidence must be between 0 and 100",
+        ));
+    }
+    if query.content_type.len() > 1 && query.content_type.contains(&ContentType::All) {
+        return Err(ApiError::new(
+            ErrorCode::InvalidContentType,
+            "content_type all can't be combined with other types",
+        ));
+    }
+
+    let query_str = query.q.as_deref().unwrap_or("");
+    if !query.fuzzy {
+        return Ok(query_str.to_string());
+    }
+    state.db.fuzzy_search_query(query_str).await.map_err(|e| {
+        if let Some(message) = search_syntax_error(&e) {
+            return invalid_search_query(message);
+        }
+        error!("Failed to expand fuzzy search query: {}", e);
+        ApiError::new(
+            ErrorCode::Internal,
+            format!("Failed to search for content: {}", e),
         )
-        .await
-        .map_err(|e| {
-            error!("Failed to search for content: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                JsonResponse(json!({"error": format!("Failed to search for content: {}", e)})),
+    })
+}
+
+/// Counts of the results of `/search` with the same filters, by app, day, content type and
+/// device, e.g. for filter chips. Pagination and sorting are ignored.
+pub(crate) async fn search_facets<S: ApiStorage>(
+    ApiQuery(query): ApiQuery<SearchQuery>,
+    State(state): State<Arc<AppState<S>>>,
+) -> Result<JsonResponse<SearchFacets>, ApiError> {
+    let query_str = search_query_str(&state, &query).await?;
+    // Several content types are counted one at a time, app_name only counts the screen
+    let content_types = match query.content_type.as_slice() {
+        _ if query.app_name.is_some() => vec![ContentType::OCR],
+        [] => vec![ContentType::All],
+        content_types => content_types.to_vec(),
+    };
+
+    let mut tally = FacetTally::default();
+    for content_type in content_types {
+        let facets = state
+            .db
+            .search_facets(
+                &query_str,
+                content_type,
+                query.start_time,
+                query.end_time,
+                query.app_name.as_deref(),
+                query.min_confidence,
+                query.entity.as_deref(),
+                query.engine(),
             )
-        })?;
+            .await
+            .map_err(|e| {
+                if let Some(message) = search_syntax_error(&e) {
+                    return invalid_search_query(message);
+                }
+                error!("Failed to count search results: {}", e);
+                ApiError::new(
+                    ErrorCode::Internal,
+                    format!("Failed to count search results: {}", e),
+                )
+            })?;
+        tally.merge(facets);
+    }
+    Ok(JsonResponse(tally.into_facets()))
+}
+
+/// One page of `content_type` results with their total
+async fn search_content_type<S: ApiStorage>(
+    state: &AppState<S>,
+    query: &SearchQuery,
+    query_str: &str,
+    content_type: ContentType,
+    cursor: Option<&SearchCursor>,
+    recency_half_life_hours: Option<f64>,
+) -> Result<PaginatedResponse<ContentItem>, ApiError> {
+    // Offset pagination past the first page, keyset pagination otherwise
+    let search = if query.sort == SearchSort::Relevance {
+        state
+            .db
+            .search_by_relevance(
+                query_str,
+                content_type,
+                query.pagination.limit,
+                query.pagination.offset,
+                query.start_time,
+                query.end_time,
+                query.app_name.as_deref(),
+                query.min_confidence,
+                query.entity.as_deref(),
+                query.engine(),
+                recency_half_life_hours,
+            )
+            .await
+            .map(|results| (results, None))
+    } else if query.pagination.offset > 0 {
+        state
+            .db
+            .search(
+                query_str,
+                content_type,
+                query.pagination.limit,
+                query.pagination.offset,
+                query.start_time,
+                query.end_time,
+                query.app_name.as_deref(),
+                query.min_confidence,
+                query.entity.as_deref(),
+                query.engine(),
+            )
+            .await
+            .map(|results| (results, None))
+    } else {
+        state
+            .db
+            .search_page(
+                query_str,
+                content_type,
+                query.pagination.limit,
+                cursor,
+                query.start_time,
+                query.end_time,
+                query.app_name.as_deref(),
+                query.min_confidence,
+                query.entity.as_deref(),
+                query.engine(),
+            )
+            .await
+    };
+    let (results, next_cursor) = search.map_err(|e| {
+        if let Some(message) = search_syntax_error(&e) {
+            return invalid_search_query(message);
+        }
+        error!("Failed to search for content: {}", e);
+        ApiError::new(
+            ErrorCode::Internal,
+            format!("Failed to search for content: {}", e),
+        )
+    })?;
 
     let total = state
         .db
@@ -208,154 +854,1609 @@ pub(crate) async fn search(
             query.start_time,
             query.end_time,
             query.app_name.as_deref(),
+            query.min_confidence,
+            query.entity.as_deref(),
+            query.engine(),
         )
         .await
         .map_err(|e| {
+            if let Some(message) = search_syntax_error(&e) {
+                return invalid_search_query(message);
+            }
             error!("Failed to count search results: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                JsonResponse(json!({"error": format!("Failed to count search results: {}", e)})),
+            ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to count search results: {}", e),
             )
         })?;
 
-    info!("Search completed: found {} results", total);
-    Ok(JsonResponse(PaginatedResponse {
-        data: results.into_iter().map(into_content_item).collect(),
+    info!(
+        "Search of {:?} completed: found {} results",
+        content_type, total
+    );
+    Ok(PaginatedResponse {
+        data: results
+            .into_iter()
+            .map(|result| {
+                let boxes = match &result {
+                    SearchResult::OCR(ocr) if query.include_boxes => matched_words(ocr),
+                    _ => Vec::new(),
+                };
+                let mut item = into_content_item(result);
+                if let ContentItem::OCR(ocr) = &mut item {
+                    ocr.boxes = boxes;
+                }
+                item
+            })
+            .collect(),
         pagination: PaginationInfo {
             limit: query.pagination.limit,
             offset: query.pagination.offset,
             total: total as i64,
+            next_cursor: next_cursor.map(|cursor| cursor.encode()),
         },
-    }))
+    })
 }
-pub(crate) async fn start_device(
-    State(state): State<Arc<AppState>>,
-    JsonExt(payload): JsonExt<DeviceRequest>,
-) -> Result<JsonResponse<DeviceStatus>, (StatusCode, JsonResponse<serde_json::Value>)> {
+#[cfg(feature = "audio")]
+pub(crate) async fn start_device<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiJson(payload): ApiJson<DeviceRequest>,
+) -> Result<JsonResponse<DeviceStatus>, ApiError> {
     debug!("Received start device request: {}", payload.device_id);
     // Create an AudioDevice from the device_id string
-    let audio_device = match AudioDevice::from_name(&payload.device_id) {
+    let audio_device = match parse_audio_device(&payload.device_id) {
         Ok(device) => device,
         Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                JsonResponse(json!({"error": "Invalid device ID"})),
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "Invalid device ID",
             ))
         }
     };
 
-    let device_control = DeviceControl {
-        is_running: true,
-        is_paused: false,
-    };
-
-    state
-        .audio_devices_control
-        .push((audio_device, device_control));
+    control_device(&state, audio_device, true);
+    save_capture_state(&state, &payload.device_id, true).await;
 
     Ok(JsonResponse(DeviceStatus {
         id: payload.device_id,
         is_running: true,
+        is_paused: false,
     }))
 }
 
-pub(crate) async fn stop_device(
-    State(state): State<Arc<AppState>>,
-    JsonExt(payload): JsonExt<DeviceRequest>,
-) -> Result<JsonResponse<DeviceStatus>, (StatusCode, JsonResponse<serde_json::Value>)> {
+#[cfg(feature = "audio")]
+pub(crate) async fn stop_device<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiJson(payload): ApiJson<DeviceRequest>,
+) -> Result<JsonResponse<DeviceStatus>, ApiError> {
     debug!("Received stop device request: {}", payload.device_id);
     // Create an AudioDevice from the device_id string
-    let audio_device = match AudioDevice::from_name(&payload.device_id) {
+    let audio_device = match parse_audio_device(&payload.device_id) {
         Ok(device) => device,
         Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                JsonResponse(json!({"error": "Invalid device ID"})),
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "Invalid device ID",
             ))
         }
     };
-    let device_control = DeviceControl {
-        is_running: false,
-        is_paused: false,
-    };
-
-    state
-        .audio_devices_control
-        .push((audio_device, device_control));
+    control_device(&state, audio_device, false);
+    save_capture_state(&state, &payload.device_id, false).await;
 
     Ok(JsonResponse(DeviceStatus {
         id: payload.device_id,
         is_running: false,
+        is_paused: false,
     }))
 }
 
-pub(crate) async fn start_recording(
-    State(state): State<Arc<AppState>>,
-) -> JsonResponse<RecordingStatus> {
-    state.vision_control.store(true, Ordering::SeqCst);
-    JsonResponse(RecordingStatus { is_running: true })
+/// Stops recording a running device without closing it, resuming it is immediate
+#[cfg(feature = "audio")]
+pub(crate) async fn pause_device<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiJson(payload): ApiJson<DeviceRequest>,
+) -> Result<JsonResponse<DeviceStatus>, ApiError> {
+    set_device_paused(&state, payload.device_id, true)
 }
 
-pub(crate) async fn stop_recording(
-    State(state): State<Arc<AppState>>,
-) -> JsonResponse<RecordingStatus> {
-    state.vision_control.store(false, Ordering::SeqCst);
-    JsonResponse(RecordingStatus { is_running: false })
+#[cfg(feature = "audio")]
+pub(crate) async fn resume_device<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiJson(payload): ApiJson<DeviceRequest>,
+) -> Result<JsonResponse<DeviceStatus>, ApiError> {
+    set_device_paused(&state, payload.device_id, false)
 }
 
-pub(crate) async fn get_recording_status(
-    State(state): State<Arc<AppState>>,
-) -> JsonResponse<RecordingStatus> {
-    let is_running = state.vision_control.load(Ordering::SeqCst);
-    JsonResponse(RecordingStatus { is_running })
+/// Pauses or resumes a running device, it's not remembered for the next start
+#[cfg(feature = "audio")]
+fn set_device_paused<S>(
+    state: &AppState<S>,
+    device_id: String,
+    is_paused: bool,
+) -> Result<JsonResponse<DeviceStatus>, ApiError> {
+    let audio_device = parse_audio_device(&device_id)
+        .map_err(|_| ApiError::new(ErrorCode::InvalidRequest, "Invalid device ID"))?;
+    let device_control = DeviceControl {
+        is_running: true,
+        is_paused,
+    };
+    {
+        let mut status = state.devices_status.lock().unwrap();
+        match status.get(&audio_device) {
+            Some(control) if control.is_running => {}
+            Some(_) => {
+                return Err(ApiError::new(
+                    ErrorCode::Conflict,
+                    "Device is stopped, start it instead",
+                ))
+            }
+            None => return Err(ApiError::new(ErrorCode::NotFound, "Device not found")),
+        }
+        status.insert(audio_device.clone(), device_control.clone());
+    }
+    state
+        .audio_devices_control
+        .push((audio_device, device_control));
+
+    Ok(JsonResponse(DeviceStatus {
+        id: device_id,
+        is_running: true,
+        is_paused,
+    }))
 }
 
-pub(crate) async fn get_device_status(
-    State(state): State<Arc<AppState>>,
-    JsonExt(payload): JsonExt<DeviceRequest>,
-) -> Result<JsonResponse<DeviceStatus>, (StatusCode, JsonResponse<serde_json::Value>)> {
-    // Create an AudioDevice from the device_id string
-    let audio_device = match AudioDevice::from_name(&payload.device_id) {
-        Ok(device) => device,
-        Err(_) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                JsonResponse(json!({"error": "Invalid device ID"})),
-            ))
-        }
-    };
-    if let Some(device_control) = state.devices_status.get(&audio_device) {
-        Ok(JsonResponse(DeviceStatus {
-            id: payload.device_id,
-            is_running: device_control.is_running,
-        }))
-    } else {
-        Err((
-            StatusCode::NOT_FOUND,
-            JsonResponse(json!({"error": "Device not found"})),
-        ))
+#[cfg(feature = "audio")]
+pub(crate) async fn start_all_devices<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    payload: Option<ApiJson<BulkDeviceRequest>>,
+) -> JsonResponse<Vec<DeviceStatus>> {
+    let kind = payload.and_then(|ApiJson(request)| request.kind);
+    let devices = control_all_devices(&state, kind, true);
+    for device in &devices {
+        save_capture_state(&state, &device.id, true).await;
     }
+    JsonResponse(devices)
 }
 
-pub(crate) async fn get_devices(
-    State(state): State<Arc<AppState>>,
+#[cfg(feature = "audio")]
+pub(crate) async fn stop_all_devices<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    payload: Option<ApiJson<BulkDeviceRequest>>,
 ) -> JsonResponse<Vec<DeviceStatus>> {
-    let devices = state
+    let kind = payload.and_then(|ApiJson(request)| request.kind);
+    let devices = control_all_devices(&state, kind, false);
+    for device in &devices {
+        save_capture_state(&state, &device.id, false).await;
+    }
+    JsonResponse(devices)
+}
+
+/// Enqueues the control for every known device of the kind
+#[cfg(feature = "audio")]
+fn control_all_devices<S>(
+    state: &AppState<S>,
+    kind: Option<DeviceKind>,
+    is_running: bool,
+) -> Vec<DeviceStatus> {
+    let mut devices: Vec<AudioDevice> = state
         .devices_status
-        .iter()
-        .map(|(audio_device, device_control)| DeviceStatus {
-            id: audio_device.to_string(),
-            is_running: device_control.is_running,
+        .lock()
+        .unwrap()
+        .keys()
+        .filter(|device| match kind {
+            None => true,
+            Some(DeviceKind::Input) => device.device_type() == &DeviceType::Input,
+            Some(DeviceKind::Output) => device.device_type() == &DeviceType::Output,
         })
+        .cloned()
         .collect();
-    JsonResponse(devices)
+    devices.sort_by_key(|device| device.to_string());
+    debug!(
+        "Received {} all devices request for {} devices",
+        if is_running { "start" } else { "stop" },
+        devices.len()
+    );
+
+    devices
+        .into_iter()
+        .map(|device| {
+            let id = device.to_string();
+            control_device(state, device, is_running);
+            DeviceStatus {
+                id,
+                is_running,
+                is_paused: false,
+            }
+        })
+        .collect()
 }
 
-pub async fn health_check(State(state): State<Arc<AppState>>) -> JsonResponse<HealthCheckResponse> {
-    let (last_frame, last_audio) = match state.db.get_latest_timestamps().await {
-        Ok((frame, audio)) => (frame, audio),
-        Err(e) => {
-            error!("Failed to get latest timestamps: {}", e);
+/// Sends the control to the recording loop and remembers it for the status endpoints
+#[cfg(feature = "audio")]
+pub(crate) fn control_device<S>(state: &AppState<S>, device: AudioDevice, is_running: bool) {
+    let device_control = DeviceControl {
+        is_running,
+        is_paused: false,
+    };
+    state
+        .devices_status
+        .lock()
+        .unwrap()
+        .insert(device.clone(), device_control.clone());
+    state.audio_devices_control.push((device, device_control));
+}
+
+/// Remembers the state of an audio device or `VISION_CAPTURE` for the next start. The
+/// capture already changed, a failure is only logged.
+pub(crate) async fn save_capture_state<S: ApiStorage>(
+    state: &AppState<S>,
+    device: &str,
+    is_running: bool,
+) {
+    if let Err(e) = state.db.set_capture_state(device, is_running).await {
+        error!("Failed to save the capture state of {}: {}", device, e);
+    }
+}
+
+/// Starts or stops the screen recording for the REST and gRPC APIs, remembered for the next
+/// start and in `/events`
+pub(crate) async fn control_vision<S: ApiStorage>(state: &AppState<S>, is_running: bool) {
+    state.vision_control.store(is_running, Ordering::SeqCst);
+    save_capture_state(state, VISION_CAPTURE, is_running).await;
+    let (kind, message) = if is_running {
+        (EventKind::VisionStarted, "Screen recording started")
+    } else {
+        (EventKind::VisionStopped, "Screen recording stopped")
+    };
+    state.health.events().record(kind, message, None);
+}
+
+pub(crate) async fn start_recording<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+) -> JsonResponse<RecordingStatus> {
+    control_vision(&state, true).await;
+    JsonResponse(RecordingStatus { is_running: true })
+}
+
+pub(crate) async fn stop_recording<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+) -> JsonResponse<RecordingStatus> {
+    control_vision(&state, false).await;
+    JsonResponse(RecordingStatus { is_running: false })
+}
+
+pub(crate) async fn get_recording_status<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+) -> JsonResponse<VisionStatusResponse> {
+    JsonResponse(VisionStatusResponse {
+        is_running: state.vision_control.load(Ordering::SeqCst),
+        is_paused: state.capture_paused.load(Ordering::SeqCst),
+        foreground_app: state.vision_status.foreground_app(),
+        monitors: state.vision_status.monitors(),
+    })
+}
+
+/// Time `/vision/capture_now` waits for its frame to be OCR'd and stored at most
+const CAPTURE_NOW_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Captures the screen right away at full resolution, OCRs and stores the frame, and
+/// answers with its id and text, for an authoritative "what's on screen right now". Only
+/// the monitor recorded is captured, see `VideoCapture::new`, the other monitors aren't.
+pub(crate) async fn capture_now<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+) -> Result<JsonResponse<CapturedFrame>, ApiError> {
+    if !state.vision_control.load(Ordering::SeqCst) || state.capture_paused.load(Ordering::SeqCst) {
+        return Err(ApiError::new(
+            ErrorCode::Conflict,
+            "Screen capture is stopped or paused",
+        ));
+    }
+    let frame = state.capture_requests.wait();
+    state.capture_trigger.request();
+    match tokio::time::timeout(CAPTURE_NOW_TIMEOUT, frame).await {
+        Ok(Ok(frame)) => {
+            info!("Captured frame {} on request", frame.frame_id);
+            Ok(JsonResponse(frame))
+        }
+        // Dropped on the way, e.g. an ignored app or sensitive content
+        _ => Err(ApiError::new(
+            ErrorCode::Timeout,
+            format!(
+                "No frame was stored within {}s, it may have been dropped as sensitive or from an ignored app",
+                CAPTURE_NOW_TIMEOUT.as_secs()
+            ),
+        )),
+    }
+}
+
+#[cfg(feature = "audio")]
+pub(crate) async fn get_device_status<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiJson(payload): ApiJson<DeviceRequest>,
+) -> Result<JsonResponse<DeviceStatus>, ApiError> {
+    // Create an AudioDevice from the device_id string
+    let audio_device = match parse_audio_device(&payload.device_id) {
+        Ok(device) => device,
+        Err(_) => {
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "Invalid device ID",
+            ))
+        }
+    };
+    if let Some(device_control) = state.devices_status.lock().unwrap().get(&audio_device) {
+        Ok(JsonResponse(DeviceStatus {
+            id: payload.device_id,
+            is_running: device_control.is_running,
+            is_paused: device_control.is_paused,
+        }))
+    } else {
+        Err(ApiError::new(ErrorCode::NotFound, "Device not found"))
+    }
+}
+
+#[cfg(feature = "audio")]
+pub(crate) async fn get_devices<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+) -> JsonResponse<Vec<DeviceListItem>> {
+    JsonResponse(list_devices(&state))
+}
+
+/// Devices plugged in and the ones controlled before, with whether they capture
+#[cfg(feature = "audio")]
+pub(crate) fn list_devices<S>(state: &AppState<S>) -> Vec<DeviceListItem> {
+    let listed = list_audio_device_info().unwrap_or_else(|e| {
+        error!("Failed to list audio devices: {}", e);
+        Vec::new()
+    });
+    let status = state.devices_status.lock().unwrap();
+
+    let is_running = |device: &AudioDevice| {
+        status
+            .get(device)
+            .map(|control| control.is_running)
+            .unwrap_or(false)
+    };
+    let is_paused = |device: &AudioDevice| {
+        status
+            .get(device)
+            .map(|control| control.is_paused)
+            .unwrap_or(false)
+    };
+    let mut devices: Vec<DeviceListItem> = listed
+        .iter()
+        .map(|(device, info)| DeviceListItem {
+            id: device.to_string(),
+            name: device.name().to_string(),
+            info: info.clone(),
+            is_running: is_running(device),
+            is_paused: is_paused(device),
+        })
+        .collect();
+    // Devices controlled but not plugged in anymore
+    for device in status.keys() {
+        if listed.iter().all(|(d, _)| d != device) {
+            devices.push(DeviceListItem {
+                id: device.to_string(),
+                name: device.name().to_string(),
+                info: AudioDeviceInfo {
+                    kind: match device.device_type() {
+                        DeviceType::Input => AudioDeviceKind::Input,
+                        DeviceType::Output => AudioDeviceKind::Output,
+                    },
+                    is_default: false,
+                    sample_rates: Vec::new(),
+                },
+                is_running: is_running(device),
+                is_paused: is_paused(device),
+            });
+        }
+    }
+    devices
+}
+
+#[cfg(feature = "audio")]
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AudioConfig {
+    whisper_model: WhisperModelSize,
+    #[serde(default)]
+    quantized: bool,
+}
+
+/// The whisper model transcribing, which stays the previous one while a new one loads or
+/// when it failed to, the configured one before the recording loads it
+#[cfg(feature = "audio")]
+pub(crate) async fn get_audio_config<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+) -> JsonResponse<AudioConfig> {
+    let config = loaded_whisper_model().unwrap_or_else(|| *state.whisper_model.borrow());
+    JsonResponse(AudioConfig {
+        whisper_model: config.size,
+        quantized: config.quantized,
+    })
+}
+
+/// Switches the whisper model. It's downloaded if needed and loaded in the background,
+/// the current model keeps transcribing until then.
+#[cfg(feature = "audio")]
+pub(crate) async fn update_audio_config<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiJson(payload): ApiJson<AudioConfig>,
+) -> Result<JsonResponse<AudioConfig>, ApiError> {
+    let config = WhisperModelConfig {
+        size: payload.whisper_model,
+        quantized: payload.quantized,
+        gpu: state.whisper_model.borrow().gpu,
+    };
+    if let Err(e) = config.validate() {
+        return Err(ApiError::new(ErrorCode::InvalidRequest, e.to_string()));
+    }
+    state.whisper_model.send_if_modified(|current| {
+        let modified = *current != config;
+        if modified {
+            info!("Switching to whisper model {}", config);
+        }
+        *current = config;
+        modified
+    });
+    Ok(JsonResponse(payload))
+}
+
+#[cfg(feature = "audio")]
+fn parse_device_id(device_id: &str) -> Result<AudioDevice, ApiError> {
+    parse_audio_device(device_id)
+        .map_err(|_| ApiError::new(ErrorCode::InvalidRequest, "Invalid device ID"))
+}
+
+/// Capture settings of the device, empty when it uses the defaults
+#[cfg(feature = "audio")]
+pub(crate) async fn get_device_config<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiPath(device_id): ApiPath<String>,
+) -> Result<JsonResponse<AudioDeviceConfig>, ApiError> {
+    let device_id = parse_device_id(&device_id)?.to_string();
+    let config = state.audio_device_configs.borrow().get(&device_id).cloned();
+    Ok(JsonResponse(config.unwrap_or_default()))
+}
+
+/// Replaces the capture settings of the device, used from its next chunk. A sample rate or
+/// channel count the device can't capture falls back to its default.
+#[cfg(feature = "audio")]
+pub(crate) async fn update_device_config<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiPath(device_id): ApiPath<String>,
+    ApiJson(config): ApiJson<AudioDeviceConfig>,
+) -> Result<JsonResponse<AudioDeviceConfig>, ApiError> {
+    let device_id = parse_device_id(&device_id)?.to_string();
+    if let Err(e) = config.validate() {
+        return Err(ApiError::new(ErrorCode::InvalidRequest, e.to_string()));
+    }
+    state.audio_device_configs.send_if_modified(|configs| {
+        let previous = if config == AudioDeviceConfig::default() {
+            configs.remove(&device_id)
+        } else {
+            configs.insert(device_id.clone(), config.clone())
+        };
+        let modified = previous.unwrap_or_default() != config;
+        if modified {
+            info!("Capture settings of {} changed to {:?}", device_id, config);
+        }
+        modified
+    });
+    Ok(JsonResponse(config))
+}
+
+#[cfg(feature = "audio")]
+#[derive(Deserialize)]
+pub(crate) struct RetranscribeQuery {
+    /// Every chunk without transcription when unset
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    since: Option<DateTime<Utc>>,
+    /// Also the transcribed chunks, their transcription is kept as a version searchable with
+    /// the `engine` of `/search`
+    #[serde(default, deserialize_with = "deserialize_bool_from_string")]
+    all: bool,
+}
+
+#[cfg(feature = "audio")]
+#[derive(Serialize)]
+pub(crate) struct RetranscribeResponse {
+    queued: u64,
+}
+
+/// Queues the audio chunks recorded since `since` without transcription, because it failed
+/// or never ran, or every one with `all`, to be transcribed again in the background while
+/// recording
+#[cfg(feature = "audio")]
+pub(crate) async fn retranscribe(
+    State(state): State<Arc<AppState>>,
+    ApiQuery(query): ApiQuery<RetranscribeQuery>,
+) -> Result<JsonResponse<RetranscribeResponse>, ApiError> {
+    match state.db.queue_retranscription(query.since, query.all).await {
+        Ok(queued) => {
+            info!("Queued {} audio chunks to transcribe again", queued);
+            Ok(JsonResponse(RetranscribeResponse { queued }))
+        }
+        Err(e) => {
+            error!("Failed to queue audio chunks to transcribe again: {}", e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to queue audio chunks: {}", e),
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "recording")]
+#[derive(Deserialize)]
+pub(crate) struct ReocrRequest {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+}
+
+#[cfg(feature = "recording")]
+#[derive(Serialize)]
+pub(crate) struct ReocrResponse {
+    queued: u64,
+}
+
+/// Queues the frames recorded between `start_time` and `end_time` to be OCR'd again from
+/// their video chunks with the engine of the recording, e.g. a better one than they were
+/// recorded with. Their OCR text is replaced in the background while recording.
+#[cfg(feature = "recording")]
+pub(crate) async fn reocr(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<ReocrRequest>,
+) -> Result<JsonResponse<ReocrResponse>, ApiError> {
+    if request.start_time >= request.end_time {
+        return Err(ApiError::new(
+            ErrorCode::InvalidTime,
+            "start_time must be before end_time",
+        ));
+    }
+    match state
+        .db
+        .queue_reocr(request.start_time, request.end_time)
+        .await
+    {
+        Ok(queued) => {
+            info!("Queued {} frames to OCR again", queued);
+            Ok(JsonResponse(ReocrResponse { queued }))
+        }
+        Err(e) => {
+            error!("Failed to queue frames to OCR again: {}", e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to queue frames: {}", e),
+            ))
+        }
+    }
+}
+
+/// More buckets than a graph can show, likely a mistake in the parameters
+const MAX_STATS_BUCKETS: i64 = 10_000;
+
+#[derive(Deserialize)]
+pub(crate) struct IngestStatsQuery {
+    /// Width of a bucket like `30s`, `5m`, `1h` or `1d`
+    #[serde(default = "default_stats_bucket")]
+    bucket: String,
+    /// How far back from now, same format
+    #[serde(default = "default_stats_range")]
+    range: String,
+}
+
+fn default_stats_bucket() -> String {
+    "5m".to_string()
+}
+
+fn default_stats_range() -> String {
+    "24h".to_string()
+}
+
+#[derive(Serialize)]
+pub(crate) struct IngestStatsResponse {
+    bucket_seconds: i64,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    buckets: Vec<IngestBucket>,
+}
+
+/// Seconds of a duration like `30s`, `5m`, `1h` or `1d`
+fn parse_duration_secs(duration: &str) -> Option<i64> {
+    let unit = duration.chars().last()?;
+    let value: i64 = duration[..duration.len() - unit.len_utf8()].parse().ok()?;
+    let unit_secs = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    (value > 0).then(|| value.checked_mul(unit_secs)).flatten()
+}
+
+pub(crate) async fn ingest_stats<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiQuery(query): ApiQuery<IngestStatsQuery>,
+) -> Result<JsonResponse<IngestStatsResponse>, ApiError> {
+    let (Some(bucket_seconds), Some(range_seconds)) = (
+        parse_duration_secs(&query.bucket),
+        parse_duration_secs(&query.range),
+    ) else {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            "bucket and range must look like 30s, 5m, 1h or 1d",
+        ));
+    };
+    if range_seconds / bucket_seconds > MAX_STATS_BUCKETS {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!(
+                "at most {} buckets, use a larger bucket or a smaller range",
+                MAX_STATS_BUCKETS
+            ),
+        ));
+    }
+
+    let end = Utc::now();
+    // Out of the range of dates, e.g. 100000000d
+    let Some(start) = chrono::Duration::from_std(Duration::from_secs(range_seconds as u64))
+        .ok()
+        .and_then(|range| end.checked_sub_signed(range))
+    else {
+        return Err(ApiError::new(ErrorCode::InvalidRequest, "range goes back too far"));
+    };
+    match state.db.ingest_stats(start, end, bucket_seconds).await {
+        Ok(buckets) => Ok(JsonResponse(IngestStatsResponse {
+            bucket_seconds,
+            start,
+            end,
+            buckets,
+        })),
+        Err(e) => {
+            error!("Failed to compute ingest stats: {}", e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to compute ingest stats: {}", e),
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HeatmapGranularity {
+    Hour,
+    #[default]
+    Day,
+}
+
+impl HeatmapGranularity {
+    fn bucket_secs(self) -> i64 {
+        match self {
+            HeatmapGranularity::Hour => 60 * 60,
+            HeatmapGranularity::Day => 24 * 60 * 60,
+        }
+    }
+
+    /// Days shown without `start`: a year of days, like a contribution graph, or a week
+    /// of hours
+    fn default_days(self) -> i64 {
+        match self {
+            HeatmapGranularity::Hour => 7,
+            HeatmapGranularity::Day => 365,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct HeatmapQuery {
+    #[serde(default)]
+    granularity: HeatmapGranularity,
+    /// First local date
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    start: Option<NaiveDate>,
+    /// Last local date, included, default to today
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    end: Option<NaiveDate>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct HeatmapResponse {
+    granularity: HeatmapGranularity,
+    start: DateTime<Utc>,
+    /// End of the last day, or now when it's today
+    end: DateTime<Utc>,
+    buckets: Vec<HeatmapBucket>,
+}
+
+/// How much was recorded per local hour or day between two dates: frames and seconds of
+/// audio, and characters of OCR text and transcriptions
+pub(crate) async fn heatmap<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiQuery(query): ApiQuery<HeatmapQuery>,
+) -> Result<JsonResponse<HeatmapResponse>, ApiError> {
+    let granularity = query.granularity;
+    let last_day = query.end.unwrap_or_else(|| Local::now().date_naive());
+    let first_day = query
+        .start
+        .unwrap_or_else(|| last_day - chrono::Duration::days(granularity.default_days() - 1));
+    if first_day > last_day {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            "start must not be after end",
+        ));
+    }
+    let days = (last_day - first_day).num_days() + 1;
+    if days * 24 * 60 * 60 / granularity.bucket_secs() > MAX_STATS_BUCKETS {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!(
+                "at most {} buckets, use a larger granularity or a shorter range",
+                MAX_STATS_BUCKETS
+            ),
+        ));
+    }
+    // Days follow the current UTC offset, those across a DST change are off by its hour
+    let offset = *Local::now().offset();
+    let midnight = |day: NaiveDate| {
+        day.and_hms_opt(0, 0, 0)
+            .and_then(|time| offset.from_local_datetime(&time).single())
+            .map(|time| time.with_timezone(&Utc))
+    };
+    let (Some(start), Some(end_of_range)) =
+        (midnight(first_day), last_day.succ_opt().and_then(midnight))
+    else {
+        return Err(ApiError::new(
+            ErrorCode::InvalidTime,
+            format!("Invalid local dates {} - {}", first_day, last_day),
+        ));
+    };
+    let end = end_of_range.min(Utc::now());
+
+    // The end is exclusive, the first second of the next day isn't part of it
+    match state
+        .db
+        .heatmap(
+            start,
+            end - chrono::Duration::seconds(1),
+            granularity.bucket_secs(),
+            offset.local_minus_utc() as i64,
+        )
+        .await
+    {
+        Ok(buckets) => Ok(JsonResponse(HeatmapResponse {
+            granularity,
+            start,
+            end,
+            buckets,
+        })),
+        Err(e) => {
+            error!("Failed to compute the heatmap: {}", e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to compute the heatmap: {}", e),
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TimelineQuery {
+    /// Local date, default to today
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    day: Option<NaiveDate>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TimelineResponse {
+    day: NaiveDate,
+    start: DateTime<Utc>,
+    /// End of the day, or now for today
+    end: DateTime<Utc>,
+    segments: Vec<TimelineSegment>,
+}
+
+/// What was recorded during a day, minute by minute: the app on screen, whether audio was
+/// recorded, whether the user was idle, and the gaps where capture was off
+pub(crate) async fn timeline<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiQuery(query): ApiQuery<TimelineQuery>,
+) -> Result<JsonResponse<TimelineResponse>, ApiError> {
+    let day = query.day.unwrap_or_else(|| Local::now().date_naive());
+    let Some((start, end_of_day)) = local_day_range(day) else {
+        return Err(ApiError::new(
+            ErrorCode::InvalidTime,
+            format!("Invalid local date {}", day),
+        ));
+    };
+    let end = end_of_day.min(Utc::now());
+    if end <= start {
+        return Ok(JsonResponse(TimelineResponse {
+            day,
+            start,
+            end: start,
+            segments: Vec::new(),
+        }));
+    }
+
+    // The end is exclusive, the first minute of the next day isn't part of it
+    match state
+        .db
+        .timeline(start, end - chrono::Duration::seconds(1))
+        .await
+    {
+        Ok(minutes) => Ok(JsonResponse(TimelineResponse {
+            day,
+            start,
+            end,
+            segments: timeline_segments(&minutes),
+        })),
+        Err(e) => {
+            error!("Failed to compute the timeline: {}", e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to compute the timeline: {}", e),
+            ))
+        }
+    }
+}
+
+/// Activity events returned by one `/activity` call at most
+const MAX_ACTIVITY_EVENTS: u32 = 10_000;
+
+#[derive(Deserialize)]
+pub(crate) struct ActivityQuery {
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    end_time: Option<DateTime<Utc>>,
+    /// Bucket type, e.g. `afkstatus` or `currentwindow`
+    #[serde(default)]
+    event_type: Option<String>,
+    #[serde(default = "default_activity_limit")]
+    limit: u32,
+}
+
+fn default_activity_limit() -> u32 {
+    1000
+}
+
+/// Events pulled from ActivityWatch with `--activitywatch-url`, oldest first
+pub(crate) async fn activity_events<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiQuery(query): ApiQuery<ActivityQuery>,
+) -> Result<JsonResponse<Vec<ActivityEvent>>, ApiError> {
+    match state
+        .db
+        .get_activity_events(
+            query.start_time,
+            query.end_time,
+            query.event_type.as_deref(),
+            query.limit.min(MAX_ACTIVITY_EVENTS),
+        )
+        .await
+    {
+        Ok(events) => Ok(JsonResponse(events)),
+        Err(e) => {
+            error!("Failed to get activity events: {}", e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to get activity events: {}", e),
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateMarkRequest {
+    name: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CreateMarkResponse {
+    #[serde(flatten)]
+    mark: Mark,
+    /// Whether a capture of the screen was requested, not while capture is paused
+    capture_requested: bool,
+}
+
+/// Marks the current moment and captures the screen right away at full resolution, so
+/// what was on screen is searchable next to the mark. Meant for a global hotkey, e.g.
+/// `curl -X POST localhost:3030/v1/marks -d '{"name": "bug repro"}'`.
+pub(crate) async fn create_mark<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiJson(request): ApiJson<CreateMarkRequest>,
+) -> Result<JsonResponse<CreateMarkResponse>, ApiError> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            "The name of a mark can't be empty",
+        ));
+    }
+    let mark = state.db.insert_mark(name, Utc::now()).await.map_err(|e| {
+        error!("Failed to insert mark: {}", e);
+        ApiError::new(ErrorCode::Internal, format!("Failed to insert mark: {}", e))
+    })?;
+    let capture_requested = !state.capture_paused.load(Ordering::SeqCst);
+    if capture_requested {
+        state.capture_trigger.request();
+    }
+    info!("Marked \"{}\" at {}", mark.name, mark.timestamp);
+    Ok(JsonResponse(CreateMarkResponse {
+        mark,
+        capture_requested,
+    }))
+}
+
+/// Marks returned by one `/marks` call at most
+const MAX_MARKS: u32 = 10_000;
+
+#[derive(Deserialize)]
+pub(crate) struct MarksQuery {
+    /// Text in the name of the marks
+    #[serde(default)]
+    q: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default = "default_marks_limit")]
+    limit: u32,
+}
+
+fn default_marks_limit() -> u32 {
+    100
+}
+
+/// Marks, most recent first
+pub(crate) async fn list_marks<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiQuery(query): ApiQuery<MarksQuery>,
+) -> Result<JsonResponse<Vec<Mark>>, ApiError> {
+    match state
+        .db
+        .get_marks(
+            query.q.as_deref().filter(|q| !q.is_empty()),
+            query.start_time,
+            query.end_time,
+            query.limit.min(MAX_MARKS),
+        )
+        .await
+    {
+        Ok(marks) => Ok(JsonResponse(marks)),
+        Err(e) => {
+            error!("Failed to get marks: {}", e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to get marks: {}", e),
+            ))
+        }
+    }
+}
+
+/// Content around a mark returned by one `/marks/:id` call at most
+const MAX_MARK_CONTENT: u32 = 1000;
+
+#[derive(Deserialize)]
+pub(crate) struct MarkQuery {
+    /// Seconds before and after the mark of the content returned with it
+    #[serde(default = "default_mark_window")]
+    window: u32,
+    #[serde(default = "default_mark_content_limit")]
+    limit: u32,
+}
+
+fn default_mark_window() -> u32 {
+    60
+}
+
+fn default_mark_content_limit() -> u32 {
+    100
+}
+
+#[derive(Serialize)]
+pub(crate) struct MarkResponse {
+    mark: Mark,
+    /// Frames and transcriptions within the window around the mark, most recent first
+    content: Vec<ContentItem>,
+}
+
+/// A mark with the content recorded around it
+pub(crate) async fn get_mark<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiPath(id): ApiPath<i64>,
+    ApiQuery(query): ApiQuery<MarkQuery>,
+) -> Result<JsonResponse<MarkResponse>, ApiError> {
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to get mark {}: {}", id, e);
+        ApiError::new(ErrorCode::Internal, format!("Failed to get mark: {}", e))
+    };
+    let mark = match state.db.get_mark(id).await.map_err(internal_error)? {
+        Some(mark) => mark,
+        None => {
+            return Err(ApiError::new(
+                ErrorCode::NotFound,
+                format!("No mark with id {}", id),
+            ))
+        }
+    };
+    let window = chrono::Duration::seconds(query.window as i64);
+    let results = state
+        .db
+        .search(
+            "",
+            ContentType::All,
+            query.limit.min(MAX_MARK_CONTENT),
+            0,
+            Some(mark.timestamp - window),
+            Some(mark.timestamp + window),
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .map_err(internal_error)?;
+    Ok(JsonResponse(MarkResponse {
+        mark,
+        content: results.into_iter().map(into_content_item).collect(),
+    }))
+}
+
+/// Frames and transcriptions of one `/ingest` batch at most
+const MAX_INGEST_ITEMS: usize = 10_000;
+/// Request body of one `/ingest` batch at most
+const MAX_INGEST_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Stores a batch of data recorded by another screenpipe, e.g. a laptop pushing to a home server
+pub(crate) async fn ingest<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiJson(batch): ApiJson<IngestBatch>,
+) -> Result<JsonResponse<IngestSummary>, ApiError> {
+    let device_id = batch.device_id.trim().to_string();
+    if device_id.is_empty() || device_id == LOCAL_DEVICE_ID {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!("device_id is required and can't be \"{}\"", LOCAL_DEVICE_ID),
+        ));
+    }
+    let items = batch.frames.len() + batch.transcriptions.len();
+    if items > MAX_INGEST_ITEMS {
+        return Err(ApiError::new(
+            ErrorCode::PayloadTooLarge,
+            format!(
+                "at most {} frames and transcriptions per batch",
+                MAX_INGEST_ITEMS
+            ),
+        ));
+    }
+
+    let processors = state.content_processors.clone();
+    let batch = tokio::task::spawn_blocking(move || processors.process_ingest(batch))
+        .await
+        .map_err(|e| {
+            ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to process batch: {}", e),
+            )
+        })?;
+
+    match state.db.ingest(&batch).await {
+        Ok(summary) => {
+            info!(
+                "Ingested {} frames and {} transcriptions from {} ({} duplicates skipped)",
+                summary.frames_inserted,
+                summary.transcriptions_inserted,
+                device_id,
+                summary.frames_skipped + summary.transcriptions_skipped
+            );
+            Ok(JsonResponse(summary))
+        }
+        Err(e) => {
+            error!("Failed to ingest batch from {}: {}", device_id, e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to ingest batch: {}", e),
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct BackupRequest {
+    /// One of the --backup-location, the first one by default
+    #[serde(default)]
+    location: Option<String>,
+}
+
+pub(crate) async fn create_backup(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<BackupRequest>,
+) -> Result<JsonResponse<BackupSummary>, ApiError> {
+    // Any caller of the API could otherwise write the whole recording anywhere
+    let target = match request.location.as_deref().map(str::trim) {
+        Some(location) => state
+            .backup_locations
+            .iter()
+            .find(|allowed| allowed.trim() == location),
+        None => state.backup_locations.first(),
+    };
+    let target = match target {
+        Some(target) => target,
+        None if state.backup_locations.is_empty() => {
+            return Err(ApiError::new(
+                ErrorCode::Conflict,
+                "Backups over the API are disabled, start screenpipe with --backup-location",
+            ))
+        }
+        None => {
+            return Err(ApiError::new(
+                ErrorCode::InvalidRequest,
+                "location must be one of the --backup-location of screenpipe",
+            ))
+        }
+    };
+    let location = BackupLocation::parse(target.trim())
+        .map_err(|e| ApiError::new(ErrorCode::InvalidRequest, e.to_string()))?;
+
+    match backup(&state.db, &location).await {
+        Ok(summary) => Ok(JsonResponse(summary)),
+        Err(e) => {
+            error!("Failed to back up to {}: {}", target, e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to back up: {}", e),
+            ))
+        }
+    }
+}
+
+pub(crate) async fn storage_usage(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<DiskUsage>, ApiError> {
+    match disk_usage(&state.db).await {
+        Ok(usage) => Ok(JsonResponse(usage)),
+        Err(e) => {
+            error!("Failed to compute disk usage: {}", e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to compute disk usage: {}", e),
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PruneRequest {
+    before: DateTime<Utc>,
+    #[serde(rename = "type", default)]
+    kind: ChunkKind,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub(crate) async fn prune(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<PruneRequest>,
+) -> Result<JsonResponse<PruneSummary>, ApiError> {
+    match prune_chunks(&state.db, request.before, request.kind, request.dry_run).await {
+        Ok(summary) => Ok(JsonResponse(summary)),
+        Err(e) => {
+            error!("Failed to prune chunks before {}: {}", request.before, e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to prune: {}", e),
+            ))
+        }
+    }
+}
+
+fn rules_error(e: sqlx::Error) -> ApiError {
+    error!("Failed to update rules: {}", e);
+    ApiError::new(
+        ErrorCode::Internal,
+        format!("Failed to update rules: {}", e),
+    )
+}
+
+/// Watches a keyword or regex in what gets recorded from now on, matches are POSTed to
+/// the webhook of the rule
+pub(crate) async fn create_rule(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<NewRule>,
+) -> Result<JsonResponse<Rule>, ApiError> {
+    request
+        .validate()
+        .map_err(|e| ApiError::new(ErrorCode::InvalidRequest, e))?;
+    let rule = state.db.insert_rule(&request).await.map_err(rules_error)?;
+    state.rules.reload(&state.db).await.map_err(rules_error)?;
+    info!("Added rule {} watching \"{}\"", rule.name, rule.pattern);
+    Ok(JsonResponse(rule))
+}
+
+/// Entities returned by one `/entities` call at most
+const MAX_ENTITIES: u32 = 1000;
+
+#[derive(Deserialize)]
+pub(crate) struct EntitiesQuery {
+    #[serde(default)]
+    kind: Option<EntityKind>,
+    /// Part of the name, case insensitive
+    #[serde(default)]
+    q: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default = "default_entities_limit")]
+    limit: u32,
+}
+
+fn default_entities_limit() -> u32 {
+    100
+}
+
+/// People, organizations, URLs, dates and amounts named in what was recorded with
+/// `--extract-entities`, most mentioned first. `normalized` is what `/search?entity=` takes.
+pub(crate) async fn list_entities(
+    State(state): State<Arc<AppState>>,
+    ApiQuery(query): ApiQuery<EntitiesQuery>,
+) -> Result<JsonResponse<Vec<EntitySummary>>, ApiError> {
+    state
+        .db
+        .list_entities(
+            query.kind,
+            query.q.as_deref(),
+            query.start_time,
+            query.end_time,
+            query.limit.min(MAX_ENTITIES),
+        )
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("Failed to list entities: {}", e);
+            ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to list entities: {}", e),
+            )
+        })
+}
+
+/// Sessions returned at most by `/sessions`, their transcripts are long
+const MAX_SESSIONS: u32 = 100;
+
+#[derive(Deserialize)]
+pub(crate) struct SessionsQuery {
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default = "default_sessions_limit")]
+    limit: u32,
+}
+
+fn default_sessions_limit() -> u32 {
+    20
+}
+
+/// Calls and meetings: the transcriptions grouped by the silences between them, most recent
+/// first, each with its transcript. Sessions are updated every 10 minutes.
+pub(crate) async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    ApiQuery(query): ApiQuery<SessionsQuery>,
+) -> Result<JsonResponse<Vec<TranscriptSession>>, ApiError> {
+    state
+        .db
+        .list_sessions(
+            query.start_time,
+            query.end_time,
+            query.limit.min(MAX_SESSIONS),
+        )
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("Failed to list sessions: {}", e);
+            ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to list sessions: {}", e),
+            )
+        })
+}
+
+/// Most sources given to the model, the prompt must fit its context
+const MAX_ASK_SOURCES: usize = 50;
+
+#[derive(Deserialize)]
+pub(crate) struct AskRequest {
+    question: String,
+    /// Recordings given to the model
+    #[serde(default = "default_ask_limit")]
+    limit: usize,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    end_time: Option<DateTime<Utc>>,
+    /// Ollama model, the one of `--ollama-model` by default
+    #[serde(default)]
+    model: Option<String>,
+}
+
+fn default_ask_limit() -> usize {
+    10
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AskResponse {
+    pub answer: String,
+    /// Empty when nothing recorded matched the question and the model wasn't asked
+    pub model: String,
+    pub sources: Vec<AskSource>,
+    /// Frames of the OCR sources
+    pub frame_ids: Vec<i64>,
+}
+
+/// Answers a question about the recordings with the local Ollama model, from the ones
+/// found by full text search
+pub(crate) async fn ask(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<AskRequest>,
+) -> Result<JsonResponse<AskResponse>, ApiError> {
+    if request.question.trim().is_empty() {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            "question is empty",
+        ));
+    }
+    let sources = retrieve_sources(
+        &state.db,
+        &request.question,
+        request.limit.clamp(1, MAX_ASK_SOURCES),
+        request.start_time,
+        request.end_time,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to search the sources of a question: {}", e);
+        ApiError::new(
+            ErrorCode::Internal,
+            format!("Failed to search the recordings: {}", e),
+        )
+    })?;
+    let frame_ids = sources
+        .iter()
+        .filter_map(|source| source.frame_id)
+        .collect();
+    if sources.is_empty() {
+        return Ok(JsonResponse(AskResponse {
+            answer: "Nothing recorded matches the question.".to_string(),
+            model: String::new(),
+            sources,
+            frame_ids,
+        }));
+    }
+
+    let model = request
+        .model
+        .unwrap_or_else(|| state.ollama.model().to_string());
+    let answer = state
+        .ollama
+        .generate(&build_prompt(&request.question, &sources), Some(&model))
+        .await
+        .map_err(|e| {
+            error!("Failed to query Ollama: {}", e);
+            ApiError::new(
+                ErrorCode::UpstreamFailed,
+                format!("Failed to query Ollama: {}", e),
+            )
+        })?;
+    Ok(JsonResponse(AskResponse {
+        answer,
+        model,
+        sources,
+        frame_ids,
+    }))
+}
+
+/// Windows on screen when the frame was captured, front to back, with which were visible
+/// and the OCR text in each. Empty for frames recorded without them.
+pub(crate) async fn get_frame_windows(
+    State(state): State<Arc<AppState>>,
+    ApiPath(frame_id): ApiPath<i64>,
+) -> Result<JsonResponse<Vec<WindowRegion>>, ApiError> {
+    state
+        .db
+        .get_frame_windows(frame_id)
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("Failed to get windows of frame {}: {}", frame_id, e);
+            ApiError::new(ErrorCode::Internal, format!("Failed to get windows: {}", e))
+        })
+}
+
+pub(crate) async fn list_rules(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Vec<Rule>>, ApiError> {
+    Ok(JsonResponse(state.db.list_rules().await.map_err(rules_error)?))
+}
+
+pub(crate) async fn delete_rule(
+    State(state): State<Arc<AppState>>,
+    ApiPath(id): ApiPath<i64>,
+) -> Result<StatusCode, ApiError> {
+    if !state.db.delete_rule(id).await.map_err(rules_error)? {
+        return Err(ApiError::new(
+            ErrorCode::NotFound,
+            format!("No rule {}", id),
+        ));
+    }
+    state.rules.reload(&state.db).await.map_err(rules_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn collections_error(e: sqlx::Error) -> ApiError {
+    error!("Failed to update collections: {}", e);
+    ApiError::new(
+        ErrorCode::Internal,
+        format!("Failed to update collections: {}", e),
+    )
+}
+
+fn no_collection(id: i64) -> ApiError {
+    ApiError::new(ErrorCode::NotFound, format!("No collection {}", id))
+}
+
+async fn find_collection(db: &DatabaseManager, id: i64) -> Result<Collection, ApiError> {
+    db.get_collection(id)
+        .await
+        .map_err(collections_error)?
+        .ok_or_else(|| no_collection(id))
+}
+
+/// Saves the filters of a search under a name, a pinned collection tags the content
+/// matching them from now on
+pub(crate) async fn create_collection(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<NewCollection>,
+) -> Result<JsonResponse<Collection>, ApiError> {
+    request
+        .validate()
+        .map_err(|e| ApiError::new(ErrorCode::InvalidRequest, e))?;
+    let collection = state
+        .db
+        .insert_collection(&request)
+        .await
+        .map_err(collections_error)?;
+    info!("Added collection {} \"{}\"", collection.id, collection.name);
+    Ok(JsonResponse(collection))
+}
+
+pub(crate) async fn list_collections(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Vec<Collection>>, ApiError> {
+    let collections = state
+        .db
+        .list_collections()
+        .await
+        .map_err(collections_error)?;
+    Ok(JsonResponse(collections))
+}
+
+pub(crate) async fn get_collection(
+    State(state): State<Arc<AppState>>,
+    ApiPath(id): ApiPath<i64>,
+) -> Result<JsonResponse<Collection>, ApiError> {
+    find_collection(&state.db, id).await.map(JsonResponse)
+}
+
+pub(crate) async fn update_collection(
+    State(state): State<Arc<AppState>>,
+    ApiPath(id): ApiPath<i64>,
+    ApiJson(request): ApiJson<NewCollection>,
+) -> Result<JsonResponse<Collection>, ApiError> {
+    request
+        .validate()
+        .map_err(|e| ApiError::new(ErrorCode::InvalidRequest, e))?;
+    match state
+        .db
+        .update_collection(id, &request)
+        .await
+        .map_err(collections_error)?
+    {
+        Some(collection) => Ok(JsonResponse(collection)),
+        None => Err(no_collection(id)),
+    }
+}
+
+pub(crate) async fn delete_collection(
+    State(state): State<Arc<AppState>>,
+    ApiPath(id): ApiPath<i64>,
+) -> Result<StatusCode, ApiError> {
+    if !state
+        .db
+        .delete_collection(id)
+        .await
+        .map_err(collections_error)?
+    {
+        return Err(no_collection(id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Paging and ordering of `GET /collections/:id/results`, as in `GET /search`
+#[derive(Deserialize)]
+pub(crate) struct CollectionResultsQuery {
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+    #[serde(default, deserialize_with = "deserialize_bool_from_string")]
+    include_boxes: bool,
+    #[serde(default)]
+    sort: SearchSort,
+    #[serde(default)]
+    recency_boost: Option<String>,
+}
+
+/// Runs the search saved by the collection, answers as `GET /search`
+pub(crate) async fn collection_results(
+    State(state): State<Arc<AppState>>,
+    ApiPath(id): ApiPath<i64>,
+    ApiQuery(query): ApiQuery<CollectionResultsQuery>,
+) -> Result<JsonResponse<SearchResponse>, ApiError> {
+    let filters = find_collection(&state.db, id).await?.filters;
+    let query = SearchQuery {
+        q: filters.q,
+        pagination: query.pagination,
+        content_type: filters.content_type,
+        start_time: filters.start_time,
+        end_time: filters.end_time,
+        app_name: filters.app_name,
+        fuzzy: filters.fuzzy,
+        min_confidence: filters.min_confidence,
+        include_boxes: query.include_boxes,
+        entity: filters.entity,
+        engine: filters.engine,
+        sort: query.sort,
+        recency_boost: query.recency_boost,
+    };
+    search(ApiQuery(query), State(state)).await
+}
+
+/// Items returned by one `/collections/:id/items` call at most
+const MAX_COLLECTION_ITEMS: u32 = 1000;
+
+#[derive(Deserialize)]
+pub(crate) struct CollectionItemsQuery {
+    #[serde(default = "default_limit")]
+    limit: u32,
+    #[serde(default)]
+    offset: u32,
+}
+
+/// Content a pinned collection tagged as it arrived, most recent first
+pub(crate) async fn list_collection_items(
+    State(state): State<Arc<AppState>>,
+    ApiPath(id): ApiPath<i64>,
+    ApiQuery(query): ApiQuery<CollectionItemsQuery>,
+) -> Result<JsonResponse<Vec<CollectionItem>>, ApiError> {
+    find_collection(&state.db, id).await?;
+    let items = state
+        .db
+        .get_collection_items(id, query.limit.min(MAX_COLLECTION_ITEMS), query.offset)
+        .await
+        .map_err(collections_error)?;
+    Ok(JsonResponse(items))
+}
+
+pub async fn health_check<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+) -> JsonResponse<HealthCheckResponse> {
+    let (last_frame, last_audio) = match state.db.get_latest_timestamps().await {
+        Ok((frame, audio)) => (frame, audio),
+        Err(e) => {
+            error!("Failed to get latest timestamps: {}", e);
             (None, None)
         }
     };
@@ -363,55 +2464,139 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> JsonResponse<He
     debug!("Last audio timestamp: {:?}", last_audio);
 
     let now = Utc::now();
-    let threshold = Duration::from_secs(60);
-    let loading_threshold = Duration::from_secs(120);
-
-    let app_start_time = state.app_start_time;
-    let time_since_start = now.signed_duration_since(app_start_time);
-
-    if time_since_start < chrono::Duration::from_std(loading_threshold).unwrap() {
-        return JsonResponse(HealthCheckResponse {
-            status: "Loading".to_string(),
-            last_frame_timestamp: last_frame,
-            last_audio_timestamp: last_audio,
-            frame_status: "Loading".to_string(),
-            audio_status: "Loading".to_string(),
-            message: "The application is still initializing. Please wait...".to_string(),
-            verbose_instructions: None,
-        });
-    }
-
-    let frame_status = match last_frame {
-        Some(timestamp)
-            if now.signed_duration_since(timestamp)
-                < chrono::Duration::from_std(threshold).unwrap() =>
-        {
-            "OK"
+    let health = &state.health;
+    let thresholds = health.thresholds();
+    let cloud_providers = circuit_breakers();
+    let accelerators = accelerators();
+    let anomalies = state.anomalies.active();
+
+    // No new data is expected while capture is paused for inactivity
+    let idle = state.capture_paused.load(Ordering::SeqCst);
+    let loading = now.signed_duration_since(state.app_start_time) < thresholds.startup_grace();
+    let freshness = |timestamp: Option<DateTime<Utc>>, stale: chrono::Duration| {
+        if idle {
+            return "Paused";
+        }
+        if loading {
+            return "Loading";
+        }
+        match timestamp {
+            Some(timestamp) if now.signed_duration_since(timestamp) < stale => "OK",
+            Some(_) => "Stale",
+            None => "No data",
         }
-        Some(_) => "Stale",
-        None => "No data",
     };
 
-    let audio_status = match last_audio {
-        Some(timestamp)
-            if now.signed_duration_since(timestamp)
-                < chrono::Duration::from_std(threshold).unwrap() =>
-        {
-            "OK"
-        }
-        Some(_) => "Stale",
-        None => "No data",
+    let frame_status = freshness(last_frame, thresholds.frame_stale());
+    let audio_devices: Vec<AudioDeviceHealth> = health
+        .audio_devices()
+        .into_iter()
+        .map(|(device, last_chunk_at)| AudioDeviceHealth {
+            status: freshness(Some(last_chunk_at), thresholds.audio_stale()).to_string(),
+            device,
+            last_chunk_at,
+        })
+        .collect();
+    let stale_devices: Vec<&str> = audio_devices
+        .iter()
+        .filter(|device| device.status == "Stale")
+        .map(|device| device.device.as_str())
+        .collect();
+    // A dead device is stale even while another one records
+    let audio_status = match freshness(last_audio, thresholds.audio_stale()) {
+        "OK" if !stale_devices.is_empty() => "Stale",
+        status => status,
     };
 
-    let (overall_status, message, verbose_instructions) = if frame_status == "OK"
-        && audio_status == "OK"
-    {
+    let ocr_queue_depth = health.ocr_queue_depth();
+    let whisper_backlog = health.whisper_backlog();
+    let free_disk_bytes = health.free_disk_bytes();
+    let writes = state.db.write_stats();
+    let db_write_latency_ms = writes.latency.map(|latency| latency.as_millis() as u64);
+    let content_processor_failures = state
+        .content_processors
+        .failures()
+        .into_iter()
+        .filter(|(_, failures)| *failures > 0)
+        .collect();
+    let degraded_if = |degraded: bool| if degraded { "Degraded" } else { "OK" };
+    let subsystem = |name: Subsystem, status: &str, message: String| SubsystemHealth {
+        name,
+        status: status.to_string(),
+        message,
+        last_error: health.last_error(name),
+        restarts: health.restarts(name),
+    };
+    let audio_message = if stale_devices.is_empty() {
+        time_since(now, last_audio, "audio chunk")
+    } else {
+        format!(
+            "No audio chunk within {}s from {}",
+            thresholds.audio_stale_secs,
+            stale_devices.join(", ")
+        )
+    };
+    let mut database = subsystem(
+        Subsystem::Database,
+        degraded_if(db_write_latency_ms.map_or(false, |ms| ms > thresholds.max_db_write_ms)),
+        match db_write_latency_ms {
+            Some(ms) => format!("Last batch of rows written in {} ms", ms),
+            None => "Nothing written yet".to_string(),
+        },
+    );
+    database.last_error = database
+        .last_error
+        .into_iter()
+        .chain(writes.last_error)
+        .max_by_key(|error| error.at);
+    let min_free_disk_bytes = (thresholds.min_free_disk_gb * 1_000_000_000.0) as u64;
+    let subsystems = vec![
+        subsystem(
+            Subsystem::Vision,
+            frame_status,
+            time_since(now, last_frame, "frame"),
+        ),
+        subsystem(
+            Subsystem::Ocr,
+            degraded_if(ocr_queue_depth > thresholds.max_ocr_queue),
+            format!("{} frames waiting for OCR", ocr_queue_depth),
+        ),
+        subsystem(Subsystem::Audio, audio_status, audio_message),
+        subsystem(
+            Subsystem::Transcription,
+            degraded_if(whisper_backlog > thresholds.max_whisper_backlog),
+            format!("{} audio chunks waiting for transcription", whisper_backlog),
+        ),
+        database,
+        match free_disk_bytes {
+            Some(free) => subsystem(
+                Subsystem::Disk,
+                degraded_if(free < min_free_disk_bytes),
+                format!("{:.1} GB free", free as f64 / 1_000_000_000.0),
+            ),
+            None => subsystem(Subsystem::Disk, "OK", "Free space unknown".to_string()),
+        },
+    ];
+
+    let degraded: Vec<&str> = subsystems
+        .iter()
+        .filter(|subsystem| subsystem.status == "Degraded")
+        .map(|subsystem| subsystem.message.as_str())
+        .chain(anomalies.iter().map(|anomaly| anomaly.message.as_str()))
+        .collect();
+    let (overall_status, message, verbose_instructions) = if idle {
         (
-            "Healthy",
-            "All systems are functioning normally.".to_string(),
+            "Idle",
+            "Capture is paused because the user is idle or the screen is locked.".to_string(),
             None,
         )
-    } else {
+    } else if loading {
+        (
+            "Loading",
+            "The application is still initializing. Please wait...".to_string(),
+            None,
+        )
+    } else if frame_status != "OK" || audio_status != "OK" {
         (
             "Unhealthy",
             format!("Some systems are not functioning properly. Frame status: {}, Audio status: {}", frame_status, audio_status),
@@ -421,6 +2606,18 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> JsonResponse<He
                   3. If the problem persists, please contact support with the details of this health check at louis@screenpi.pe.\n\
                   4. Last, here are some FAQ to help you troubleshoot: https://github.com/louis030195/screen-pipe/blob/main/content/docs/NOTES.md".to_string())
         )
+    } else if !degraded.is_empty() {
+        (
+            "Degraded",
+            format!("Recording runs but looks unusual: {}", degraded.join("; ")),
+            None,
+        )
+    } else {
+        (
+            "Healthy",
+            "All systems are functioning normally.".to_string(),
+            None,
+        )
     };
 
     JsonResponse(HealthCheckResponse {
@@ -431,11 +2628,562 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> JsonResponse<He
         audio_status: audio_status.to_string(),
         message,
         verbose_instructions,
+        idle,
+        cloud_providers,
+        accelerators,
+        anomalies,
+        subsystems,
+        audio_devices,
+        ocr_queue_depth,
+        whisper_backlog,
+        free_disk_bytes,
+        db_write_latency_ms,
+        content_processor_failures,
     })
 }
 
+/// "Last frame 12s ago" or "No frame recorded yet"
+fn time_since(now: DateTime<Utc>, timestamp: Option<DateTime<Utc>>, what: &str) -> String {
+    match timestamp {
+        Some(timestamp) => format!(
+            "Last {} {}s ago",
+            what,
+            now.signed_duration_since(timestamp).num_seconds()
+        ),
+        None => format!("No {} recorded yet", what),
+    }
+}
+
+/// Content type of an audio chunk by extension. Chunks are recorded as mp4 by default, which
+/// would be served as video/mp4 otherwise.
+fn audio_content_type(file_path: &str) -> &'static str {
+    let extension = std::path::Path::new(file_path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "mp4" | "m4a" => "audio/mp4",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" | "opus" => "audio/ogg",
+        "flac" => "audio/flac",
+        _ => "application/octet-stream",
+    }
+}
+
+/// `file_path` resolved when it is a file in `media_dir`, `None` otherwise, e.g. outside
+/// of it, leaving it through a symlink or gone
+pub(crate) async fn media_path(media_dir: Option<&Path>, file_path: &str) -> Option<PathBuf> {
+    let media_dir = tokio::fs::canonicalize(media_dir?).await.ok()?;
+    let path = tokio::fs::canonicalize(file_path).await.ok()?;
+    path.starts_with(&media_dir).then_some(path)
+}
+
+/// Audio file of a chunk, e.g. the `chunk_id` of a transcription found by `/search`.
+/// Supports Range requests so players can seek without downloading the whole file. Only
+/// chunks recorded by this machine in the `media_dir` are served.
+pub(crate) async fn get_audio_chunk<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiPath(chunk_id): ApiPath<i64>,
+    request: Request<Body>,
+) -> Response {
+    let error = |code: ErrorCode, message: String| ApiError::new(code, message).into_response();
+    let not_found = || error(ErrorCode::NotFound, format!("No audio chunk {}", chunk_id));
+    let file_path = match state.db.get_audio_chunk_path(chunk_id).await {
+        Ok(Some(file_path)) => file_path,
+        Ok(None) => return not_found(),
+        Err(e) => {
+            error!("Failed to get audio chunk {}: {}", chunk_id, e);
+            return error(
+                ErrorCode::Internal,
+                format!("Failed to get audio chunk: {}", e),
+            );
+        }
+    };
+
+    let Some(path) = media_path(state.media_dir.as_deref(), &file_path).await else {
+        return not_found();
+    };
+
+    if let Err(e) = fetch_offloaded(state.offload.as_deref(), &path.to_string_lossy()).await {
+        let code = match state.offload {
+            Some(_) => ErrorCode::UpstreamFailed,
+            None => ErrorCode::Conflict,
+        };
+        return error(
+            code,
+            format!("Audio chunk {} can't be read: {}", chunk_id, e),
+        );
+    }
+    // Served from a decrypted copy, removed once the response has it open
+    #[cfg(feature = "encryption")]
+    let copy = match state.db.encryption_key().cloned() {
+        Some(key) => {
+            let source = path.clone();
+            let copy = tokio::task::spawn_blocking(move || {
+                crate::encryption::decrypted_copy(&source, &key)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|copy| copy);
+            match copy {
+                Ok(copy) => copy,
+                // Gone, answered with a 404 below
+                Err(e)
+                    if e.downcast_ref::<std::io::Error>()
+                        .map_or(false, |e| e.kind() == std::io::ErrorKind::NotFound) =>
+                {
+                    None
+                }
+                Err(e) => {
+                    error!("Failed to decrypt audio chunk {}: {}", chunk_id, e);
+                    return error(
+                        ErrorCode::Internal,
+                        format!("Failed to decrypt audio chunk: {}", e),
+                    );
+                }
+            }
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "encryption"))]
+    let copy: Option<PathBuf> = None;
+
+    // Answers 404 when the file is gone since, e.g. pruned
+    let mut response = ServeFile::new(copy.as_deref().unwrap_or(&path))
+        .oneshot(request)
+        .await
+        .unwrap_or_else(|never| match never {})
+        .map(Body::new);
+    drop(copy);
+    if response.status().is_success() {
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(audio_content_type(&file_path)),
+        );
+    }
+    response
+}
+
+/// Frames and audio chunks of one `/download` at most
+const MAX_DOWNLOAD_ITEMS: usize = 1000;
+
+#[derive(Deserialize)]
+pub(crate) struct DownloadRequest {
+    #[serde(default)]
+    frame_ids: Vec<i64>,
+    #[serde(default)]
+    audio_chunk_ids: Vec<i64>,
+    /// Media of the results of a search, with the IDs
+    #[serde(default)]
+    search: Option<DownloadSearch>,
+}
+
+/// Filters of `/search`
+#[derive(Deserialize)]
+pub(crate) struct DownloadSearch {
+    #[serde(default)]
+    q: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_content_type")]
+    content_type: ContentType,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default = "default_download_limit")]
+    limit: u32,
+}
+
+fn default_download_limit() -> u32 {
+    100
+}
+
+/// Video chunk file and offset of a frame to download, only for the chunks recorded by
+/// this machine in `media_dir`, never the paths of ingested chunks nor files elsewhere
+async fn frame_source<S: ApiStorage>(
+    db: &S,
+    media_dir: Option<&Path>,
+    frame_id: i64,
+) -> Result<Option<(String, i64)>, sqlx::Error> {
+    let Some((file_path, offset_index)) = db.get_frame(frame_id).await? else {
+        return Ok(None);
+    };
+    Ok(media_path(media_dir, &file_path)
+        .await
+        .map(|path| (path.to_string_lossy().into_owned(), offset_index)))
+}
+
+/// Like `frame_source` for the file of an audio chunk
+async fn audio_source<S: ApiStorage>(
+    db: &S,
+    media_dir: Option<&Path>,
+    chunk_id: i64,
+) -> Result<Option<String>, sqlx::Error> {
+    let Some(file_path) = db.get_audio_chunk_path(chunk_id).await? else {
+        return Ok(None);
+    };
+    Ok(media_path(media_dir, &file_path)
+        .await
+        .map(|path| path.to_string_lossy().into_owned()))
+}
+
+/// Streams a zip of the video and audio chunks behind frames, audio chunks or search
+/// results, with a `manifest.json` mapping each of them to its file in the archive. Only
+/// chunks recorded by this machine in the `media_dir` are added, the others are listed
+/// as not found.
+pub(crate) async fn download<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiJson(request): ApiJson<DownloadRequest>,
+) -> Result<Response, ApiError> {
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list media to download: {}", e);
+        ApiError::new(ErrorCode::Internal, format!("Failed to list media: {}", e))
+    };
+    let selected = request.frame_ids.len() + request.audio_chunk_ids.len();
+    if selected > MAX_DOWNLOAD_ITEMS {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!(
+                "At most {} frames and audio chunks per download",
+                MAX_DOWNLOAD_ITEMS
+            ),
+        ));
+    }
+
+    let (db, media_dir) = (&*state.db, state.media_dir.as_deref());
+    let mut items = Vec::new();
+    for id in request.frame_ids {
+        let frame = frame_source(db, media_dir, id)
+            .await
+            .map_err(internal_error)?;
+        let mut item = DownloadItem::new(
+            MediaKind::Frame,
+            id,
+            frame.as_ref().map(|(file_path, _)| file_path.clone()),
+        );
+        item.offset_index = frame.map(|(_, offset_index)| offset_index);
+        items.push(item);
+    }
+    for id in request.audio_chunk_ids {
+        let file_path = audio_source(db, media_dir, id)
+            .await
+            .map_err(internal_error)?;
+        items.push(DownloadItem::new(MediaKind::Audio, id, file_path));
+    }
+    if let Some(search) = request.search {
+        let limit = search
+            .limit
+            .min((MAX_DOWNLOAD_ITEMS - items.len()) as u32);
+        let results = state
+            .db
+            .search(
+                search.q.as_deref().unwrap_or(""),
+                search.content_type,
+                limit,
+                0,
+                search.start_time,
+                search.end_time,
+                search.app_name.as_deref(),
+                None,
+                None,
+                None,
+            )
+            .await
+            .map_err(|e| match search_syntax_error(&e) {
+                Some(message) => invalid_search_query(message),
+                None => internal_error(e),
+            })?;
+        for result in results.into_iter().take(MAX_DOWNLOAD_ITEMS - items.len()) {
+            let item = match result {
+                SearchResult::OCR(ocr) => {
+                    let file_path = frame_source(db, media_dir, ocr.frame_id)
+                        .await
+                        .map_err(internal_error)?
+                        .map(|(file_path, _)| file_path);
+                    let mut item = DownloadItem::new(MediaKind::Frame, ocr.frame_id, file_path);
+                    item.offset_index = Some(ocr.offset_index);
+                    item.timestamp = Some(ocr.timestamp);
+                    item
+                }
+                SearchResult::Audio(audio) => {
+                    let file_path = audio_source(db, media_dir, audio.audio_chunk_id)
+                        .await
+                        .map_err(internal_error)?;
+                    let mut item =
+                        DownloadItem::new(MediaKind::Audio, audio.audio_chunk_id, file_path);
+                    item.timestamp = Some(audio.timestamp);
+                    item
+                }
+                // Text only, nothing to download
+                SearchResult::Clipboard(_) | SearchResult::Notification(_) => continue,
+            };
+            items.push(item);
+        }
+    }
+    if items.is_empty() {
+        return Err(ApiError::new(
+            ErrorCode::InvalidRequest,
+            "Nothing to download, pass frame_ids, audio_chunk_ids or a search with results",
+        ));
+    }
+
+    info!("Streaming a download of {} frames and audio chunks", items.len());
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"screenpipe-download.zip\"".to_string(),
+            ),
+        ],
+        Body::from_stream(download_stream(
+            items,
+            state.offload.clone(),
+            #[cfg(feature = "encryption")]
+            state.db.encryption_key().cloned(),
+        )),
+    )
+        .into_response())
+}
+
+/// Settings screenpipe runs with: the flags overridden by the config file, with the
+/// values reloaded since the start. Secrets are redacted.
+pub(crate) async fn get_config<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+) -> JsonResponse<serde_json::Value> {
+    JsonResponse(redact_config(
+        &state.diagnostics.effective_config(),
+        &mut Vec::new(),
+    ))
+}
+
+/// Features, OCR engines, transcription engines and capture backends of this build
+pub(crate) async fn get_capabilities() -> JsonResponse<Capabilities> {
+    JsonResponse(Capabilities::detect())
+}
+
+/// Zip of the log tail, health, pipeline metrics and config for a bug report, with
+/// secrets, credentials and the home directory stripped
+pub(crate) async fn diagnostics<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+) -> Result<Response, ApiError> {
+    let health = health_check(State(state.clone())).await.0;
+    let vision = get_recording_status(State(state.clone())).await.0;
+    #[cfg(feature = "audio")]
+    let audio_devices = get_devices(State(state.clone())).await.0;
+    #[cfg(not(feature = "audio"))]
+    let audio_devices: Vec<serde_json::Value> = Vec::new();
+
+    let end = Utc::now();
+    let ingest = state
+        .db
+        .ingest_stats(end - chrono::Duration::hours(24), end, 3600)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to compute ingest stats for diagnostics: {}", e);
+            Vec::new()
+        });
+    let schema_version = state.db.schema_version().await.ok().flatten();
+    let metrics = json!({
+        "schema_version": schema_version,
+        "vision": vision,
+        "audio_devices": audio_devices,
+        "ingest_last_24h": ingest,
+    });
+    let system = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "uptime_secs": end.signed_duration_since(state.app_start_time).num_seconds(),
+        "generated_at": end,
+    });
+
+    let mut secrets = Vec::new();
+    let config = redact_config(&state.diagnostics.effective_config(), &mut secrets);
+    let log = match &state.diagnostics.log_path {
+        Some(path) => read_log_tail(path).unwrap_or_else(|e| {
+            error!("Failed to read log for diagnostics: {}", e);
+            format!("Failed to read log: {}", e)
+        }),
+        None => String::new(),
+    };
+
+    let to_json = |value: &serde_json::Value| serde_json::to_vec_pretty(value).unwrap_or_default();
+    let files = vec![
+        ("system.json", to_json(&system)),
+        ("health.json", to_json(&json!(health))),
+        ("metrics.json", to_json(&metrics)),
+        ("config.json", to_json(&config)),
+        ("screenpipe.log", anonymize(&log, &secrets).into_bytes()),
+    ];
+    match build_archive(files) {
+        Ok(archive) => Ok((
+            [
+                (header::CONTENT_TYPE, "application/zip".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!(
+                        "attachment; filename=\"screenpipe-diagnostics-{}.zip\"",
+                        end.format("%Y%m%d-%H%M%S")
+                    ),
+                ),
+            ],
+            archive,
+        )
+            .into_response()),
+        Err(e) => {
+            error!("Failed to build diagnostics archive: {}", e);
+            Err(ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to build diagnostics archive: {}", e),
+            ))
+        }
+    }
+}
+
+pub async fn export(
+    ApiQuery(query): ApiQuery<ExportQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    info!(
+        "Received export request: format={:?}, content_type={:?}, start={:?}, end={:?}",
+        query.format, query.content_type, query.start, query.end
+    );
+
+    let stream = export_stream(
+        Arc::clone(&state.db),
+        query.format,
+        query.content_type,
+        query.start,
+        query.end,
+    );
+
+    (
+        [
+            (header::CONTENT_TYPE, query.format.mime_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"screenpipe-export.{}\"",
+                    query.format.extension()
+                ),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+#[cfg(feature = "integrations")]
+pub async fn export_obsidian(
+    State(state): State<Arc<AppState>>,
+    payload: Option<ApiJson<ObsidianExportRequest>>,
+) -> Result<JsonResponse<serde_json::Value>, ApiError> {
+    let (date, vault_path) = match payload {
+        Some(ApiJson(request)) => (request.date, request.vault_path),
+        None => (None, None),
+    };
+    let date = date.unwrap_or_else(|| Local::now().date_naive());
+    let vault_path = vault_path
+        .or_else(|| state.obsidian_vault_path.clone())
+        .ok_or_else(|| {
+            ApiError::new(
+                ErrorCode::InvalidRequest,
+                "No Obsidian vault path configured or provided",
+            )
+        })?;
+
+    let note_path = write_daily_note(&vault_path, date, state.db.as_ref())
+        .await
+        .map_err(|e| {
+            error!("Failed to write Obsidian daily note: {}", e);
+            ApiError::new(
+                ErrorCode::Internal,
+                format!("Failed to write daily note: {}", e),
+            )
+        })?;
+
+    Ok(JsonResponse(json!({
+        "date": date.format("%Y-%m-%d").to_string(),
+        "path": note_path.to_string_lossy(),
+    })))
+}
+
+/// Sends the digest of today so far to the Slack or Discord webhook of the config file, to
+/// check it is set up
+#[cfg(feature = "integrations")]
+pub async fn test_chat_digest(
+    State(state): State<Arc<AppState>>,
+    ApiPath(platform): ApiPath<String>,
+) -> Result<JsonResponse<serde_json::Value>, ApiError> {
+    let bad_request = |message: String| ApiError::new(ErrorCode::InvalidRequest, message);
+    let platform: ChatPlatform = platform.parse().map_err(bad_request)?;
+    let digest = state.digest.as_ref();
+    let url = digest
+        .and_then(|digest| digest.webhook_url(platform.as_str()))
+        .ok_or_else(|| bad_request(format!("No {} webhook configured in [digest]", platform)))?;
+    let webhook = ChatWebhook {
+        platform,
+        url: url.to_string(),
+    };
+    let keywords = digest
+        .map(|digest| digest.keywords.clone())
+        .unwrap_or_default();
+    let date = Local::now().date_naive();
+    send_digest(&webhook, date, &keywords, state.db.as_ref())
+        .await
+        .map_err(|e| {
+            error!("Failed to send the digest to {}: {}", platform, e);
+            ApiError::new(
+                ErrorCode::UpstreamFailed,
+                format!("Failed to send the digest: {}", e),
+            )
+        })?;
+
+    Ok(JsonResponse(json!({
+        "platform": platform.as_str(),
+        "date": date.format("%Y-%m-%d").to_string(),
+    })))
+}
+
 // Helper functions
-fn into_content_item(result: SearchResult) -> ContentItem {
+fn invalid_search_query(message: String) -> ApiError {
+    debug!("{}", message);
+    ApiError::new(ErrorCode::InvalidSearchQuery, message)
+        .with_details(json!({ "syntax": SEARCH_QUERY_SYNTAX }))
+}
+
+/// Words of `ocr` containing a highlighted term, every word without highlights
+fn matched_words(ocr: &OCRResult) -> Vec<OcrWord> {
+    let words = ocr.words();
+    if ocr.highlights.is_empty() {
+        return words;
+    }
+    let terms: Vec<String> = ocr
+        .highlights
+        .iter()
+        .map(|highlight| {
+            ocr.ocr_text
+                .chars()
+                .skip(highlight.start)
+                .take(highlight.end - highlight.start)
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .collect();
+    words
+        .into_iter()
+        .filter(|word| {
+            let text = word.text.to_lowercase();
+            terms.iter().any(|term| text.contains(term.as_str()))
+        })
+        .collect()
+}
+
+pub(crate) fn into_content_item(result: SearchResult) -> ContentItem {
     match result {
         SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
             frame_id: ocr.frame_id,
@@ -444,6 +3192,11 @@ fn into_content_item(result: SearchResult) -> ContentItem {
             file_path: ocr.file_path,
             offset_index: ocr.offset_index,
             app_name: ocr.app_name, // Add this line
+            confidence: ocr.confidence,
+            snippet: ocr.snippet,
+            relevance: ocr.relevance,
+            highlights: ocr.highlights,
+            boxes: Vec::new(),
         }),
         SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
             chunk_id: audio.audio_chunk_id,
@@ -451,15 +3204,269 @@ fn into_content_item(result: SearchResult) -> ContentItem {
             timestamp: audio.timestamp,
             file_path: audio.file_path,
             offset_index: audio.offset_index,
+            language: audio.language,
+            snippet: audio.snippet,
+            relevance: audio.relevance,
+            highlights: audio.highlights,
+            segments: audio.segments,
         }),
+        SearchResult::Clipboard(clipboard) => ContentItem::Clipboard(ClipboardContent {
+            id: clipboard.id,
+            text: clipboard.text,
+            timestamp: clipboard.timestamp,
+            app_name: clipboard.app_name,
+            snippet: clipboard.snippet,
+            relevance: clipboard.relevance,
+            highlights: clipboard.highlights,
+        }),
+        SearchResult::Notification(notification) => {
+            ContentItem::Notification(NotificationContent {
+                id: notification.id,
+                app_name: notification.app_name,
+                title: notification.title,
+                body: notification.body,
+                timestamp: notification.timestamp,
+                snippet: notification.snippet,
+                relevance: notification.relevance,
+                highlights: notification.highlights,
+            })
+        }
+    }
+}
+
+/// Events returned by one `/events` call at most
+const MAX_EVENTS: u32 = 1000;
+
+#[derive(Deserialize)]
+pub(crate) struct EventsQuery {
+    /// Comma-separated kinds, e.g. `error,task_restarted`, all kinds when empty
+    #[serde(default, deserialize_with = "deserialize_event_kinds")]
+    kind: Vec<EventKind>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_time")]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default = "default_events_limit")]
+    limit: u32,
+    #[serde(default)]
+    offset: u32,
+}
+
+fn default_events_limit() -> u32 {
+    100
+}
+
+fn deserialize_event_kinds<'de, D>(deserializer: D) -> Result<Vec<EventKind>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    let mut kinds = Vec::new();
+    for value in s
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        let kind: EventKind = value.parse().map_err(serde::de::Error::custom)?;
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    Ok(kinds)
+}
+
+/// Operational history, most recent first: recordings and devices started and stopped,
+/// errors, restarts and purges
+pub(crate) async fn list_events<S: ApiStorage>(
+    State(state): State<Arc<AppState<S>>>,
+    ApiQuery(query): ApiQuery<EventsQuery>,
+) -> Result<JsonResponse<PaginatedResponse<Event>>, ApiError> {
+    let limit = query.limit.min(MAX_EVENTS);
+    let (events, total) = tokio::try_join!(
+        state.db.get_events(
+            &query.kind,
+            query.start_time,
+            query.end_time,
+            limit,
+            query.offset,
+        ),
+        state
+            .db
+            .count_events(&query.kind, query.start_time, query.end_time),
+    )
+    .map_err(|e| {
+        error!("Failed to get events: {}", e);
+        ApiError::new(ErrorCode::Internal, format!("Failed to get events: {}", e))
+    })?;
+    Ok(JsonResponse(PaginatedResponse {
+        data: events,
+        pagination: PaginationInfo {
+            limit,
+            offset: query.offset,
+            total,
+            next_cursor: None,
+        },
+    }))
+}
+
+/// Version prefix of the routes, `/v1/search`
+pub const API_VERSION: &str = "v1";
+
+/// Routes of the API served from any `ApiStorage`, without version prefix
+pub fn storage_routes<S: ApiStorage>() -> Router<Arc<AppState<S>>> {
+    let app = Router::new()
+        .route("/search", get(search))
+        .route("/search/facets", get(search_facets));
+    #[cfg(feature = "audio")]
+    let app = app
+        .route("/audio/start", post(start_device))
+        .route("/audio/stop", post(stop_device))
+        .route("/audio/pause", post(pause_device))
+        .route("/audio/resume", post(resume_device))
+        .route("/audio/start_all", post(start_all_devices))
+        .route("/audio/stop_all", post(stop_all_devices))
+        .route("/audio/status", post(get_device_status))
+        .route("/audio/list", get(get_devices))
+        .route(
+            "/audio/config",
+            get(get_audio_config).put(update_audio_config),
+        )
+        .route(
+            "/audio/devices/:id/config",
+            get(get_device_config).put(update_device_config),
+        );
+    app.route("/audio/:chunk_id", get(get_audio_chunk))
+        .route("/vision/start", post(start_recording))
+        .route("/vision/stop", post(stop_recording))
+        .route("/vision/status", get(get_recording_status))
+        .route("/vision/capture_now", post(capture_now))
+        .route("/health", get(health_check))
+        .route("/stats/ingest", get(ingest_stats))
+        .route("/analytics/heatmap", get(heatmap))
+        .route("/activity", get(activity_events))
+        .route("/timeline", get(timeline))
+        .route("/marks", get(list_marks).post(create_mark))
+        .route("/marks/:id", get(get_mark))
+        .route("/events", get(list_events))
+        .route("/download", post(download))
+        .route("/admin/diagnostics", post(diagnostics))
+        .route("/config", get(get_config))
+        .route("/capabilities", get(get_capabilities))
+        .route(
+            "/ingest",
+            post(ingest).layer(DefaultBodyLimit::max(MAX_INGEST_BODY_BYTES)),
+        )
+}
+
+/// Routes of the API, without version prefix. Backups, storage and exports work on the
+/// SQLite database itself.
+pub fn api_routes() -> Router<Arc<AppState>> {
+    let app = storage_routes();
+    #[cfg(feature = "integrations")]
+    let app = app
+        .route("/export/obsidian", post(export_obsidian))
+        .route("/integrations/:platform/test", post(test_chat_digest));
+    #[cfg(feature = "audio")]
+    let app = app.route("/audio/retranscribe", post(retranscribe));
+    #[cfg(feature = "recording")]
+    let app = app.route("/vision/reocr", post(reocr));
+    app.route("/backup", post(create_backup))
+        .route("/storage", get(storage_usage))
+        .route("/storage/prune", post(prune))
+        .route("/export", get(export))
+        .route("/frames/:id/windows", get(get_frame_windows))
+        .route("/entities", get(list_entities))
+        .route("/sessions", get(list_sessions))
+        .route("/ask", post(ask))
+        .route("/rules", get(list_rules).post(create_rule))
+        .route("/rules/:id", delete(delete_rule))
+        .route(
+            "/collections",
+            get(list_collections).post(create_collection),
+        )
+        .route(
+            "/collections/:id",
+            get(get_collection)
+                .put(update_collection)
+                .delete(delete_collection),
+        )
+        .route("/collections/:id/results", get(collection_results))
+        .route("/collections/:id/items", get(list_collection_items))
+}
+
+/// Routes under `/v1`, plus the unversioned paths clients used before, which answer the
+/// same with deprecation headers pointing to their versioned path
+pub fn versioned_routes() -> Router<Arc<AppState>> {
+    let routes = api_routes();
+    Router::new()
+        .nest(&format!("/{}", API_VERSION), routes.clone())
+        .merge(routes.layer(middleware::from_fn(deprecate_unversioned)))
+}
+
+/// The app served by `Server`: the versioned routes with `plugins`, CORS and request
+/// tracing around them
+pub fn create_router(state: Arc<AppState>, plugins: ApiPlugins, limits: &ApiLimits) -> Router {
+    // https://github.com/tokio-rs/console
+    // Limited inside CORS, so browsers can read the 429s
+    limits
+        .apply(versioned_routes().layer(ApiPluginLayer::new(plugins)))
+        .layer(CorsLayer::permissive())
+        .layer(
+            // https://github.com/tokio-rs/axum/blob/main/examples/tracing-aka-logging/src/main.rs
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::new().include_headers(true)),
+        )
+        .with_state(state)
+}
+
+async fn deprecate_unversioned(request: Request<Body>, next: Next) -> Response {
+    // RFC 8594 successor link
+    let successor = format!(
+        "</{}{}>; rel=\"successor-version\"",
+        API_VERSION,
+        request.uri().path()
+    );
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert(header::LINK, link);
     }
+    response
 }
 
 pub struct Server {
     db: Arc<DatabaseManager>,
     addr: SocketAddr,
     vision_control: Arc<AtomicBool>,
+    vision_status: Arc<VisionStatus>,
+    #[cfg(feature = "audio")]
     audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
+    #[cfg(feature = "audio")]
+    whisper_model: watch::Sender<WhisperModelConfig>,
+    #[cfg(feature = "audio")]
+    audio_device_configs: watch::Sender<HashMap<String, AudioDeviceConfig>>,
+    capture_paused: Arc<AtomicBool>,
+    capture_trigger: Arc<CaptureTrigger>,
+    capture_requests: CaptureRequests,
+    rules: RuleEngine,
+    anomalies: AnomalyDetector,
+    health: Arc<HealthMonitor>,
+    obsidian_vault_path: Option<PathBuf>,
+    digest: Option<DigestConfig>,
+    ollama: OllamaClient,
+    limits: ApiLimits,
+    diagnostics: Diagnostics,
+    offload: Option<Arc<BackupLocation>>,
+    media_dir: Option<PathBuf>,
+    backup_locations: Vec<String>,
+    content_processors: ContentProcessors,
+    #[cfg(feature = "grpc")]
+    grpc_addr: Option<SocketAddr>,
+    /// Whether the API is served on `addr`, see `without_tcp`
+    tcp: bool,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
 }
 
 impl Server {
@@ -467,56 +3474,216 @@ impl Server {
         db: Arc<DatabaseManager>,
         addr: SocketAddr,
         vision_control: Arc<AtomicBool>,
-        audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
+        vision_status: Arc<VisionStatus>,
+        #[cfg(feature = "audio")] audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
+        #[cfg(feature = "audio")] whisper_model: watch::Sender<WhisperModelConfig>,
+        #[cfg(feature = "audio")] audio_device_configs: watch::Sender<
+            HashMap<String, AudioDeviceConfig>,
+        >,
+        capture_paused: Arc<AtomicBool>,
+        capture_trigger: Arc<CaptureTrigger>,
+        capture_requests: CaptureRequests,
+        rules: RuleEngine,
+        anomalies: AnomalyDetector,
+        obsidian_vault_path: Option<PathBuf>,
+        diagnostics: Diagnostics,
     ) -> Self {
         Server {
             db,
             addr,
             vision_control,
+            vision_status,
+            #[cfg(feature = "audio")]
             audio_devices_control,
+            #[cfg(feature = "audio")]
+            whisper_model,
+            #[cfg(feature = "audio")]
+            audio_device_configs,
+            capture_paused,
+            capture_trigger,
+            capture_requests,
+            rules,
+            anomalies,
+            health: Arc::new(HealthMonitor::default()),
+            obsidian_vault_path,
+            digest: None,
+            ollama: OllamaClient::default(),
+            limits: ApiLimits::default(),
+            diagnostics,
+            offload: None,
+            media_dir: None,
+            backup_locations: Vec::new(),
+            content_processors: ContentProcessors::new(),
+            #[cfg(feature = "grpc")]
+            grpc_addr: None,
+            tcp: true,
+            #[cfg(unix)]
+            unix_socket: None,
         }
     }
 
-    pub async fn start<F>(
+    /// Also serves the API on a Unix socket at `path`, only readable and writable by the
+    /// user running screenpipe, unlike loopback TCP which every user of the machine reaches
+    #[cfg(unix)]
+    pub fn with_unix_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Doesn't listen on the TCP address, with `with_unix_socket` no network port is opened
+    pub fn without_tcp(mut self) -> Self {
+        self.tcp = false;
+        self
+    }
+
+    /// Digest sent by `POST /integrations/:platform/test`
+    pub fn with_digest(mut self, digest: DigestConfig) -> Self {
+        self.digest = Some(digest);
+        self
+    }
+
+    /// Ollama server answering `POST /ask`, `OllamaClient::default()` otherwise
+    pub fn with_ollama(mut self, ollama: OllamaClient) -> Self {
+        self.ollama = ollama;
+        self
+    }
+
+    /// Rate, body and query string limits of the API, `ApiLimits::default()` otherwise
+    pub fn with_limits(mut self, limits: ApiLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Where chunks are offloaded to, to fetch them back when their media is read
+    pub fn with_offload(mut self, remote: Arc<BackupLocation>) -> Self {
+        self.offload = Some(remote);
+        self
+    }
+
+    /// Directory chunks are recorded to, the only one media is served from. Without it no
+    /// media is served.
+    pub fn with_media_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.media_dir = Some(dir.into());
+        self
+    }
+
+    /// Directories or s3://bucket/prefix `POST /backup` may write to, the first one by
+    /// default. Backups over the API are refused without any.
+    pub fn with_backup_locations(mut self, locations: Vec<String>) -> Self {
+        self.backup_locations = locations;
+        self
+    }
+
+    /// Processors run on what `/ingest` receives, the ones given to the recording so
+    /// `/health` counts the failures of both
+    pub fn with_content_processors(mut self, processors: ContentProcessors) -> Self {
+        self.content_processors = processors;
+        self
+    }
+
+    /// Health of the recording shown by `/health`, the one given to
+    /// `start_continuous_recording`
+    pub fn with_health(mut self, health: Arc<HealthMonitor>) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Also serves the gRPC API on `addr`, see `proto/screenpipe.proto`. It goes through the
+    /// plugins and limits of the REST API too.
+    #[cfg(feature = "grpc")]
+    pub fn with_grpc(mut self, addr: SocketAddr) -> Self {
+        self.grpc_addr = Some(addr);
+        self
+    }
+
+    /// Serves the API until a listener fails, with `plugins` around every route, the gRPC
+    /// ones included
+    pub async fn start(
         self,
-        device_status: HashMap<AudioDevice, DeviceControl>,
-        api_plugin: F,
-    ) -> Result<(), std::io::Error>
-    where
-        F: Fn(&axum::http::Request<axum::body::Body>) + Clone + Send + Sync + 'static,
-    {
+        #[cfg(feature = "audio")] device_status: HashMap<AudioDevice, DeviceControl>,
+        plugins: ApiPlugins,
+    ) -> Result<(), std::io::Error> {
         // TODO could init w audio devices
         let app_state = Arc::new(AppState {
             db: self.db,
             vision_control: self.vision_control,
+            vision_status: self.vision_status,
+            #[cfg(feature = "audio")]
             audio_devices_control: self.audio_devices_control,
-            devices_status: device_status,
+            #[cfg(feature = "audio")]
+            devices_status: Mutex::new(device_status),
+            #[cfg(feature = "audio")]
+            whisper_model: self.whisper_model,
+            #[cfg(feature = "audio")]
+            audio_device_configs: self.audio_device_configs,
             app_start_time: Utc::now(),
+            capture_paused: self.capture_paused,
+            capture_trigger: self.capture_trigger,
+            capture_requests: self.capture_requests,
+            rules: self.rules,
+            anomalies: self.anomalies,
+            health: self.health,
+            obsidian_vault_path: self.obsidian_vault_path,
+            digest: self.digest,
+            ollama: self.ollama,
+            diagnostics: self.diagnostics,
+            offload: self.offload,
+            media_dir: self.media_dir,
+            backup_locations: self.backup_locations,
+            content_processors: self.content_processors,
         });
 
-        // https://github.com/tokio-rs/console
-        let app = Router::new()
-            .route("/search", get(search))
-            .route("/audio/start", post(start_device))
-            .route("/audio/stop", post(stop_device))
-            .route("/audio/status", post(get_device_status))
-            .route("/audio/list", get(get_devices))
-            .route("/vision/start", post(start_recording))
-            .route("/vision/stop", post(stop_recording))
-            .route("/vision/status", get(get_recording_status))
-            .route("/health", get(health_check))
-            .layer(ApiPluginLayer::new(api_plugin))
-            .layer(CorsLayer::permissive())
-            .layer(
-                // https://github.com/tokio-rs/axum/blob/main/examples/tracing-aka-logging/src/main.rs
-                TraceLayer::new_for_http()
-                    .make_span_with(DefaultMakeSpan::new().include_headers(true)),
-            )
-            .with_state(app_state);
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_addr) = self.grpc_addr {
+            let listener = TcpListener::bind(grpc_addr).await?;
+            info!("Starting gRPC server on {}", grpc_addr);
+            let (app_state, plugins, limits) =
+                (app_state.clone(), plugins.clone(), self.limits.clone());
+            tokio::spawn(async move {
+                if let Err(e) = serve_grpc(app_state, listener, plugins, limits).await {
+                    error!("gRPC server error: {}", e);
+                }
+            });
+        }
 
-        info!("Starting server on {}", self.addr);
+        let app = create_router(app_state, plugins, &self.limits);
+
+        #[cfg(unix)]
+        let unix_socket = match &self.unix_socket {
+            Some(path) => Some(bind_unix_socket(path)?),
+            None => None,
+        };
+        #[cfg(not(unix))]
+        let unix_socket: Option<std::convert::Infallible> = None;
+        let (tcp, addr) = (self.tcp, self.addr);
+        if !tcp && unix_socket.is_none() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "The server needs a TCP address or a Unix socket to listen on",
+            ));
+        }
 
-        match serve(TcpListener::bind(self.addr).await?, app.into_make_service()).await {
+        let tcp = async {
+            if !tcp {
+                return std::future::pending::<Result<(), std::io::Error>>().await;
+            }
+            info!("Starting server on {}", addr);
+            serve(TcpListener::bind(addr).await?, app.clone().into_make_service()).await
+        };
+        let unix = async {
+            match unix_socket {
+                #[cfg(unix)]
+                Some(listener) => serve_unix_socket(listener, app.clone()).await,
+                #[cfg(not(unix))]
+                Some(never) => match never {},
+                None => std::future::pending::<Result<(), std::io::Error>>().await,
+            }
+        };
+        let result = tokio::select! {
+            result = tcp => result,
+            result = unix => result,
+        };
+        match result {
             Ok(_) => {
                 info!("Server stopped gracefully");
                 Ok(())
@@ -529,6 +3696,77 @@ impl Server {
     }
 }
 
+/// Listens on `path`, replacing the socket of a previous run
+#[cfg(unix)]
+fn bind_unix_socket(path: &std::path::Path) -> Result<tokio::net::UnixListener, std::io::Error> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} exists and isn't a socket", path.display()),
+            ));
+        }
+    }
+    // Other users of the machine can't connect. The socket is bound in a directory only we
+    // can enter and moved to `path` once it's 0600, so they can't connect in between either.
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => std::path::Path::new("."),
+    };
+    let staging = parent.join(format!(
+        ".screenpipe-{}-{}",
+        std::process::id(),
+        Utc::now().timestamp_subsec_nanos()
+    ));
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("sock");
+    let listener = tokio::net::UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    if let Err(e) = std::fs::remove_dir_all(&staging) {
+        warn!("Failed to remove {}: {}", staging.display(), e);
+    }
+    let listener = listener?;
+    info!("Starting server on unix:{}", path.display());
+    Ok(listener)
+}
+
+/// Serves `app` to the connections of `listener`
+#[cfg(unix)]
+async fn serve_unix_socket(
+    listener: tokio::net::UnixListener,
+    app: Router,
+) -> Result<(), std::io::Error> {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto::Builder;
+    use hyper_util::service::TowerToHyperService;
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            // e.g. out of file descriptors, the listener itself still works
+            Err(e) => {
+                error!("Failed to accept a unix socket connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Unix socket connection error: {}", e);
+            }
+        });
+    }
+}
+
 // Curl commands for reference:
 // # 1. Basic search query
 // # curl "http://localhost:3030/search?q=test&limit=5&offset=0"
@@ -0,0 +1,101 @@
+use crate::DatabaseManager;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A transcribed audio chunk, what sessions are made of
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscribedChunk {
+    pub timestamp: DateTime<Utc>,
+    /// Seconds of audio, `None` for chunks recorded before it was stored
+    pub duration: Option<f64>,
+    /// See `audio_device_of`
+    pub audio_device: String,
+}
+
+/// Start, end and devices of a session, see `segment_sessions`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionBounds {
+    pub start_time: DateTime<Utc>,
+    /// End of the last chunk
+    pub end_time: DateTime<Utc>,
+    pub chunks: i64,
+    pub audio_devices: Vec<String>,
+}
+
+/// Transcriptions of a machine without a long silence between them, e.g. a call or a
+/// meeting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptSession {
+    pub id: i64,
+    /// Machine that recorded it, see `LOCAL_DEVICE_ID`
+    pub device_id: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// Transcribed chunks
+    pub chunks: i64,
+    /// Audio devices heard, e.g. the microphone and the speakers of a call
+    pub audio_devices: Vec<String>,
+    /// Transcriptions in order, one per line
+    pub transcript: String,
+}
+
+/// Audio device of the chunk at `file_path`, named "<device>_<date>_<time>.<format>" by the
+/// recording loop, e.g. "MacBook Pro Microphone (input)"
+pub fn audio_device_of(file_path: &str) -> String {
+    let stem = Path::new(file_path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(file_path);
+    match stem.rsplitn(3, '_').nth(2) {
+        Some(device) => device.to_string(),
+        None => stem.to_string(),
+    }
+}
+
+/// Groups `chunks`, sorted by time, into sessions, a new one starts after nothing was
+/// transcribed for longer than `gap` on any audio device
+pub fn segment_sessions(chunks: &[TranscribedChunk], gap: ChronoDuration) -> Vec<SessionBounds> {
+    let mut sessions: Vec<SessionBounds> = Vec::new();
+    for chunk in chunks {
+        let duration =
+            ChronoDuration::milliseconds((chunk.duration.unwrap_or(0.0) * 1000.0) as i64);
+        let end_time = chunk.timestamp + duration;
+        match sessions.last_mut() {
+            Some(session) if chunk.timestamp - session.end_time <= gap => {
+                session.end_time = session.end_time.max(end_time);
+                session.chunks += 1;
+                if !session.audio_devices.contains(&chunk.audio_device) {
+                    session.audio_devices.push(chunk.audio_device.clone());
+                }
+            }
+            _ => sessions.push(SessionBounds {
+                start_time: chunk.timestamp,
+                end_time,
+                chunks: 1,
+                audio_devices: vec![chunk.audio_device.clone()],
+            }),
+        }
+    }
+    sessions
+}
+
+/// Segments the new transcriptions into sessions every `interval`, see
+/// `DatabaseManager::segment_transcript_sessions`
+pub async fn run_session_segmentation(
+    db: Arc<DatabaseManager>,
+    gap: ChronoDuration,
+    interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        match db.segment_transcript_sessions(gap).await {
+            Ok(0) => {}
+            Ok(started) => debug!("{} transcript sessions started", started),
+            Err(e) => error!("Failed to segment the transcriptions into sessions: {}", e),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Coordinates a graceful shutdown: the recording loops stop capturing once it's requested,
+/// then write what they captured and finalize their chunks before dropping their
+/// `ShutdownGuard`. Clones share the same state.
+#[derive(Clone)]
+pub struct Shutdown {
+    requested: Arc<watch::Sender<bool>>,
+    /// Tasks holding a `ShutdownGuard`
+    running: Arc<watch::Sender<usize>>,
+}
+
+/// Marks a task the shutdown waits for, until it's dropped
+pub struct ShutdownGuard {
+    running: Arc<watch::Sender<usize>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown {
+            requested: Arc::new(watch::channel(false).0),
+            running: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    /// Asks every task to stop, the ones already stopping keep going
+    pub fn request(&self) {
+        self.requested.send_replace(true);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Resolves once a shutdown is requested, right away if it already was
+    pub async fn requested(&self) {
+        let mut requested = self.requested.subscribe();
+        // The sender lives as long as `self`
+        let _ = requested.wait_for(|requested| *requested).await;
+    }
+
+    /// Shutdown requested along with this one, or on its own to stop a single task, e.g. an
+    /// audio device. Its guards aren't waited for by this one.
+    pub fn child(&self) -> Shutdown {
+        let child = Shutdown::new();
+        let (parent, requested) = (self.clone(), child.clone());
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = parent.requested() => requested.request(),
+                _ = requested.requested() => {}
+            }
+        });
+        child
+    }
+
+    pub fn guard(&self) -> ShutdownGuard {
+        self.running.send_modify(|running| *running += 1);
+        ShutdownGuard {
+            running: Arc::clone(&self.running),
+        }
+    }
+
+    /// Waits for every guard to be dropped, false if some are left after `timeout`
+    pub async fn wait(&self, timeout: Duration) -> bool {
+        let mut running = self.running.subscribe();
+        tokio::time::timeout(timeout, running.wait_for(|running| *running == 0))
+            .await
+            .is_ok()
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        self.running.send_modify(|running| *running -= 1);
+    }
+}
@@ -0,0 +1,320 @@
+use crate::{ContentProcessor, FrameRecord};
+use log::info;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+
+/// Characters of a word indexed by its deletes, SymSpell's prefix length. Longer words are
+/// compared in full once a prefix matches.
+const PREFIX_LENGTH: usize = 7;
+/// Most edits corrected, two for long words only
+const MAX_EDIT_DISTANCE: usize = 2;
+/// Shorter words are left as read, there are too many words one edit away from them
+const MIN_WORD_LENGTH: usize = 4;
+const LONG_WORD_LENGTH: usize = 8;
+
+/// Tesseract languages written without spaces between words, which can't be corrected word
+/// by word
+const UNSPACED_LANGUAGES: [&str; 10] = [
+    "chi_sim", "chi_tra", "jpn", "jpn_vert", "tha", "lao", "khm", "mya", "bod", "dzo",
+];
+
+/// Lowercase of `word` in `language`, a Tesseract language code. Turkish and Azerbaijani
+/// have a dotted and a dotless I.
+fn lowercase(language: &str, word: &str) -> String {
+    if matches!(language, "tur" | "aze") {
+        word.chars()
+            .flat_map(|c| match c {
+                'I' => vec!['ı'],
+                'İ' => vec!['i'],
+                c => c.to_lowercase().collect(),
+            })
+            .collect()
+    } else {
+        word.to_lowercase()
+    }
+}
+
+fn capitalize(language: &str, word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some('i') if matches!(language, "tur" | "aze") => format!("İ{}", chars.as_str()),
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Deletes of up to `MAX_EDIT_DISTANCE` characters of `word`, `word` included
+fn deletes(word: &[char]) -> HashSet<String> {
+    let mut found = HashSet::from([word.iter().collect::<String>()]);
+    let mut current = vec![word.to_vec()];
+    for _ in 0..MAX_EDIT_DISTANCE {
+        let mut next = Vec::new();
+        for word in &current {
+            for i in 0..word.len() {
+                let mut deleted = word.clone();
+                deleted.remove(i);
+                if found.insert(deleted.iter().collect()) {
+                    next.push(deleted);
+                }
+            }
+        }
+        current = next;
+    }
+    found
+}
+
+/// Optimal string alignment distance: insertions, deletions, substitutions and swaps of
+/// adjacent characters
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Words of a language with how often they are used, looked up SymSpell-style: the deletes
+/// of the words are indexed, so a misread word is matched by its own deletes.
+pub struct SpellDictionary {
+    language: String,
+    /// Lowercase words and counts
+    words: Vec<(String, u64)>,
+    index: HashMap<String, usize>,
+    deletes: HashMap<String, Vec<usize>>,
+}
+
+impl SpellDictionary {
+    /// Dictionary of `language`, a Tesseract language code such as "eng" or "deu"
+    pub fn new(language: &str, words: impl IntoIterator<Item = (String, u64)>) -> Self {
+        let mut dictionary = SpellDictionary {
+            language: language.to_string(),
+            words: Vec::new(),
+            index: HashMap::new(),
+            deletes: HashMap::new(),
+        };
+        for (word, count) in words {
+            let word = lowercase(language, word.trim());
+            if word.is_empty() {
+                continue;
+            }
+            if let Some(&i) = dictionary.index.get(&word) {
+                dictionary.words[i].1 += count;
+                continue;
+            }
+            let i = dictionary.words.len();
+            let prefix: Vec<char> = word.chars().take(PREFIX_LENGTH).collect();
+            for delete in deletes(&prefix) {
+                dictionary.deletes.entry(delete).or_default().push(i);
+            }
+            dictionary.index.insert(word.clone(), i);
+            dictionary.words.push((word, count));
+        }
+        dictionary
+    }
+
+    /// Reads a frequency dictionary, one word per line optionally followed by its count,
+    /// e.g. "the 23135851162". Lines starting with # are comments.
+    pub fn load(language: &str, path: &Path) -> io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut words = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let word = fields.next().unwrap_or_default().to_string();
+            let count = match fields.next() {
+                Some(count) => count.parse().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid count on line {}: {}", number + 1, line),
+                    )
+                })?,
+                None => 1,
+            };
+            words.push((word, count));
+        }
+        let dictionary = SpellDictionary::new(language, words);
+        info!(
+            "Loaded {} words of {} from {}",
+            dictionary.len(),
+            language,
+            path.display()
+        );
+        Ok(dictionary)
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.index.contains_key(&lowercase(&self.language, word))
+    }
+
+    /// (word, distance, count) of the closest word within `max_distance` edits of
+    /// `word`, lowercase
+    fn closest(&self, word: &[char], max_distance: usize) -> Option<(&str, usize, u64)> {
+        let prefix = &word[..word.len().min(PREFIX_LENGTH)];
+        let mut best: Option<(&str, usize, u64)> = None;
+        let mut seen = HashSet::new();
+        for delete in deletes(prefix) {
+            let Some(candidates) = self.deletes.get(&delete) else {
+                continue;
+            };
+            for &i in candidates {
+                if !seen.insert(i) {
+                    continue;
+                }
+                let (candidate, count) = &self.words[i];
+                let candidate_chars: Vec<char> = candidate.chars().collect();
+                if candidate_chars.len().abs_diff(word.len()) > max_distance {
+                    continue;
+                }
+                let distance = edit_distance(word, &candidate_chars);
+                if distance > max_distance {
+                    continue;
+                }
+                let better = best.map_or(true, |(_, best_distance, best_count)| {
+                    (distance, std::cmp::Reverse(*count))
+                        < (best_distance, std::cmp::Reverse(best_count))
+                });
+                if better {
+                    best = Some((candidate, distance, *count));
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Corrects the words OCR misread, e.g. "quarteriy" for "quarterly" in small fonts, with
+/// the dictionaries of the OCR languages. Words found in none of them are replaced by the
+/// closest word, one edit away at most, two for long words. Names, code and acronyms are
+/// left as read: mixed case, uppercase, short words and words with digits.
+#[derive(Default)]
+pub struct SpellChecker {
+    dictionaries: Vec<SpellDictionary>,
+}
+
+impl SpellChecker {
+    pub fn new() -> Self {
+        SpellChecker::default()
+    }
+
+    /// Adds a dictionary, tried after the ones already added when words are as close in
+    /// several languages. Languages written without spaces can't be corrected.
+    pub fn with(mut self, dictionary: SpellDictionary) -> Result<Self, String> {
+        if UNSPACED_LANGUAGES.contains(&dictionary.language()) {
+            return Err(format!(
+                "Spellcheck isn't supported for {}, its words aren't separated by spaces",
+                dictionary.language()
+            ));
+        }
+        self.dictionaries.push(dictionary);
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dictionaries.is_empty()
+    }
+
+    /// `text` with its misread words corrected, spacing and punctuation are kept
+    pub fn correct(&self, text: &str) -> String {
+        let mut corrected = String::with_capacity(text.len());
+        let mut word = String::new();
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+                continue;
+            }
+            corrected.push_str(&self.correct_word(&word));
+            word.clear();
+            corrected.push(c);
+        }
+        corrected.push_str(&self.correct_word(&word));
+        corrected
+    }
+
+    fn correct_word(&self, word: &str) -> String {
+        let length = word.chars().count();
+        let digits = word.chars().filter(|c| c.is_numeric()).count();
+        let uppercase = word.chars().filter(|c| c.is_uppercase()).count();
+        let capitalized = word.chars().next().map_or(false, char::is_uppercase);
+        // A digit misread for a letter, e.g. "rep0rt", but not "v2" or "2024"
+        if length < MIN_WORD_LENGTH
+            || digits > 1
+            || word.chars().next().map_or(true, char::is_numeric)
+            || uppercase > usize::from(capitalized)
+            || self.dictionaries.iter().any(|d| d.contains(word))
+        {
+            return word.to_string();
+        }
+        let max_distance = if length >= LONG_WORD_LENGTH {
+            MAX_EDIT_DISTANCE
+        } else {
+            1
+        };
+        let mut best: Option<(&SpellDictionary, &str, usize)> = None;
+        for dictionary in &self.dictionaries {
+            let chars: Vec<char> = lowercase(&dictionary.language, word).chars().collect();
+            if let Some((candidate, distance, _)) = dictionary.closest(&chars, max_distance) {
+                if best.map_or(true, |(_, _, best_distance)| distance < best_distance) {
+                    best = Some((dictionary, candidate, distance));
+                }
+            }
+        }
+        match best {
+            Some((dictionary, candidate, _)) if capitalized => {
+                capitalize(&dictionary.language, candidate)
+            }
+            Some((_, candidate, _)) => candidate.to_string(),
+            None => word.to_string(),
+        }
+    }
+}
+
+/// Runs before the other processors, which see the corrected text. The text as read is
+/// kept in `raw_text` and stays searchable.
+impl ContentProcessor for SpellChecker {
+    fn name(&self) -> &str {
+        "spellcheck"
+    }
+
+    fn process_frame(&self, mut frame: FrameRecord) -> Option<FrameRecord> {
+        for window in &mut frame.windows {
+            window.text = self.correct(&window.text);
+        }
+        let corrected = self.correct(&frame.text);
+        if corrected != frame.text {
+            let raw_text = std::mem::replace(&mut frame.text, corrected);
+            frame.raw_text.get_or_insert(raw_text);
+        }
+        Some(frame)
+    }
+}
@@ -0,0 +1,810 @@
+use crate::db::segments_json;
+use crate::db_writer::{insert_frame, PendingFrame, PendingTranscription, PendingWrite};
+use crate::{
+    ActivityEvent, ContentType, DatabaseManager, Entity, Event, EventKind, HeatmapBucket,
+    IngestBatch, IngestBucket, IngestSummary, Mark, OcrWord, SearchCursor, SearchFacets,
+    SearchResult, TimelineMinute, WriteStats,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::{debug, error, warn};
+use screenpipe_core::TranscriptionSegment;
+use screenpipe_vision::{CaptureRegion, WindowRegion};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot};
+
+/// A captured frame with its OCR text, as written by the recording loop
+#[derive(Debug, Clone)]
+pub struct FrameRecord {
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    pub text: String,
+    /// `text` as OCR read it, when a processor corrected it, e.g. `SpellChecker`
+    pub raw_text: Option<String>,
+    pub text_json: String,
+    pub new_text_json_vs_previous_frame: String,
+    pub raw_data_output_from_ocr: String,
+    pub ocr_engine: String,
+    /// Words OCR read with their confidence, see `ocr_words`
+    pub words: Vec<OcrWord>,
+    /// Named in `text`, see `EntityExtractor`
+    pub entities: Vec<Entity>,
+    /// Windows on screen with the text in each, front to back
+    pub windows: Vec<WindowRegion>,
+    /// Part of the screen the frame shows, `None` when unknown
+    pub region: Option<CaptureRegion>,
+}
+
+/// A recorded audio chunk with its transcription, empty when nothing was said
+#[derive(Debug, Clone)]
+pub struct AudioRecord {
+    pub timestamp: DateTime<Utc>,
+    pub file_path: String,
+    /// Seconds of audio
+    pub duration: Option<f64>,
+    pub transcription: String,
+    pub transcription_engine: String,
+    /// Code of the language spoken, e.g. "en"
+    pub language: Option<String>,
+    /// Timing of the transcription in the chunk, empty when the engine doesn't time it
+    pub segments: Vec<TranscriptionSegment>,
+    /// Named in `transcription`, see `EntityExtractor`
+    pub entities: Vec<Entity>,
+    /// Format the chunk is encoded in, e.g. "opus", `None` when unknown
+    pub format: Option<String>,
+    /// Why transcribing the chunk failed, `transcription` is empty then. It's transcribed
+    /// again in the background.
+    pub transcription_error: Option<String>,
+}
+
+/// Where the recording loops write what they capture. The embedded SQLite
+/// `DatabaseManager` is the default, `open_storage` picks one from a connection string.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Frames written after this belong to the chunk, encoded with `codec`, e.g. "h264"
+    async fn insert_video_chunk(&self, file_path: &str, codec: &str) -> Result<(), sqlx::Error>;
+
+    async fn write_frame(&self, frame: FrameRecord) -> Result<(), sqlx::Error>;
+
+    /// Writes the frame after the ones written before, without waiting for a batch, and
+    /// returns its id. `None` when it was dropped, e.g. before the first video chunk.
+    async fn write_frame_now(&self, frame: FrameRecord) -> Result<Option<i64>, sqlx::Error>;
+
+    async fn write_audio(&self, audio: AudioRecord) -> Result<(), sqlx::Error>;
+
+    /// Stores the transcription of the audio chunk at `audio.file_path`, written before
+    /// without one because transcribing it failed
+    async fn write_retranscription(&self, audio: AudioRecord) -> Result<(), sqlx::Error>;
+
+    /// Replaces the OCR of frame `offset_index` of the video chunk at `video_chunk` with
+    /// `frame`, read again from the chunk with another engine
+    async fn write_reocr(
+        &self,
+        video_chunk: &str,
+        offset_index: i64,
+        frame: FrameRecord,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Waits until everything written before is stored
+    async fn flush_writes(&self);
+}
+
+#[async_trait]
+impl Storage for DatabaseManager {
+    async fn insert_video_chunk(&self, file_path: &str, codec: &str) -> Result<(), sqlx::Error> {
+        DatabaseManager::insert_video_chunk_with_codec(self, file_path, Some(codec)).await?;
+        Ok(())
+    }
+
+    async fn write_frame(&self, frame: FrameRecord) -> Result<(), sqlx::Error> {
+        // Written in batches, see DatabaseManager::queue_frame_with_ocr_text
+        self.queue_write(PendingWrite::Frame(pending_frame(frame)))
+            .await;
+        Ok(())
+    }
+
+    async fn write_frame_now(&self, frame: FrameRecord) -> Result<Option<i64>, sqlx::Error> {
+        // The queued frames come first in the chunk
+        DatabaseManager::flush_writes(self).await;
+        let mut tx = self.pool.begin().await?;
+        let frame_id = insert_frame(&mut *tx, &pending_frame(frame)).await?;
+        tx.commit().await?;
+        Ok(frame_id)
+    }
+
+    async fn write_audio(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        let audio_chunk_id = self
+            .insert_audio_chunk_with_format(
+                &audio.file_path,
+                audio.duration,
+                audio.format.as_deref(),
+            )
+            .await?;
+        if let Some(error) = &audio.transcription_error {
+            self.set_transcription_error(audio_chunk_id, error).await?;
+        }
+        self.queue_transcription(audio_chunk_id, audio).await;
+        Ok(())
+    }
+
+    async fn write_retranscription(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        match self
+            .finish_retranscription(&audio.file_path, audio.duration)
+            .await?
+        {
+            Some(audio_chunk_id) => self.queue_transcription(audio_chunk_id, audio).await,
+            None => debug!(
+                "Audio chunk {} was deleted, dropping its transcription",
+                audio.file_path
+            ),
+        }
+        Ok(())
+    }
+
+    async fn write_reocr(
+        &self,
+        video_chunk: &str,
+        offset_index: i64,
+        frame: FrameRecord,
+    ) -> Result<(), sqlx::Error> {
+        // Frames queued before are written first, the OCR of one of them may be replaced
+        DatabaseManager::flush_writes(self).await;
+        if self
+            .replace_frame_ocr(video_chunk, offset_index, &frame)
+            .await?
+            .is_none()
+        {
+            debug!(
+                "Frame {} of {} was deleted, dropping its OCR",
+                offset_index, video_chunk
+            );
+        }
+        Ok(())
+    }
+
+    async fn flush_writes(&self) {
+        DatabaseManager::flush_writes(self).await;
+    }
+}
+
+impl DatabaseManager {
+    /// Writes the transcription of the audio chunk in the next batch, nothing when it's empty
+    async fn queue_transcription(&self, audio_chunk_id: i64, audio: AudioRecord) {
+        if audio.transcription.is_empty() {
+            return;
+        }
+        self.queue_write(PendingWrite::Transcription(PendingTranscription {
+            audio_chunk_id,
+            transcription: audio.transcription,
+            offset_index: 0,
+            timestamp: audio.timestamp,
+            transcription_engine: audio.transcription_engine,
+            language: audio.language,
+            segments: segments_json(&audio.segments),
+            entities: audio.entities,
+        }))
+        .await;
+    }
+}
+
+fn pending_frame(frame: FrameRecord) -> PendingFrame {
+    PendingFrame {
+        app_name: frame.app_name,
+        timestamp: frame.timestamp,
+        text: frame.text,
+        raw_text: frame.raw_text,
+        text_json: frame.text_json,
+        new_text_json_vs_previous_frame: frame.new_text_json_vs_previous_frame,
+        raw_data_output_from_ocr: frame.raw_data_output_from_ocr,
+        ocr_engine: frame.ocr_engine,
+        words: frame.words,
+        entities: frame.entities,
+        windows: frame.windows,
+        region: frame.region,
+    }
+}
+
+/// What the API handlers read, and write with `POST /ingest`. The server runs on
+/// `DatabaseManager`, handler tests on `MemoryStorage`.
+#[async_trait]
+pub trait ApiStorage: Send + Sync + 'static {
+    /// Results of one page with offset pagination, see `DatabaseManager::search`
+    #[allow(clippy::too_many_arguments)]
+    async fn search(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error>;
+
+    /// Results of one page with offset pagination, most relevant first, see
+    /// `DatabaseManager::search_by_relevance`
+    #[allow(clippy::too_many_arguments)]
+    async fn search_by_relevance(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        recency_half_life_hours: Option<f64>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error>;
+
+    /// Results of one page with keyset pagination and the cursor of the next page
+    #[allow(clippy::too_many_arguments)]
+    async fn search_page(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<(Vec<SearchResult>, Option<SearchCursor>), sqlx::Error>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn count_search_results(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<usize, sqlx::Error>;
+
+    /// See `DatabaseManager::search_facets`
+    #[allow(clippy::too_many_arguments)]
+    async fn search_facets(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<SearchFacets, sqlx::Error>;
+
+    /// The query with the words within a few typos of its words
+    async fn fuzzy_search_query(&self, query: &str) -> Result<String, sqlx::Error>;
+
+    /// Last frame and last audio chunk recorded by this machine
+    async fn get_latest_timestamps(
+        &self,
+    ) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>), sqlx::Error>;
+
+    async fn ingest_stats(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket_secs: i64,
+    ) -> Result<Vec<IngestBucket>, sqlx::Error>;
+
+    /// See `DatabaseManager::heatmap`
+    async fn heatmap(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket_secs: i64,
+        utc_offset_secs: i64,
+    ) -> Result<Vec<HeatmapBucket>, sqlx::Error>;
+
+    /// See `DatabaseManager::timeline`
+    async fn timeline(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TimelineMinute>, sqlx::Error>;
+
+    async fn ingest(&self, batch: &IngestBatch) -> Result<IngestSummary, sqlx::Error>;
+
+    async fn get_activity_events(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        event_type: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ActivityEvent>, sqlx::Error>;
+
+    /// Last migration applied, `None` when the storage has no migrations
+    async fn schema_version(&self) -> Result<Option<i64>, sqlx::Error>;
+
+    /// File of an audio chunk recorded by this machine, `None` when there is no such chunk
+    /// or it was ingested from another device
+    async fn get_audio_chunk_path(&self, chunk_id: i64) -> Result<Option<String>, sqlx::Error>;
+
+    /// Video chunk file and offset of a frame recorded by this machine, `None` when there is
+    /// no such frame or it was ingested from another device
+    async fn get_frame(&self, frame_id: i64) -> Result<Option<(String, i64)>, sqlx::Error>;
+
+    async fn insert_mark(&self, name: &str, timestamp: DateTime<Utc>) -> Result<Mark, sqlx::Error>;
+
+    /// See `DatabaseManager::get_marks`
+    async fn get_marks(
+        &self,
+        query: Option<&str>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: u32,
+    ) -> Result<Vec<Mark>, sqlx::Error>;
+
+    async fn get_mark(&self, id: i64) -> Result<Option<Mark>, sqlx::Error>;
+
+    /// See `DatabaseManager::set_capture_state`
+    async fn set_capture_state(&self, device: &str, is_running: bool) -> Result<(), sqlx::Error>;
+
+    /// See `DatabaseManager::get_events`
+    async fn get_events(
+        &self,
+        kinds: &[EventKind],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Event>, sqlx::Error>;
+
+    async fn count_events(
+        &self,
+        kinds: &[EventKind],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error>;
+
+    /// Writes of the recording, nothing is known of storages it doesn't write to
+    fn write_stats(&self) -> WriteStats {
+        WriteStats::default()
+    }
+
+    /// Key the chunks of the recordings are encrypted with, to decrypt the media served
+    #[cfg(feature = "encryption")]
+    fn encryption_key(&self) -> Option<&crate::EncryptionKey> {
+        None
+    }
+}
+
+#[async_trait]
+impl ApiStorage for DatabaseManager {
+    async fn search(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        DatabaseManager::search(
+            self,
+            query,
+            content_type,
+            limit,
+            offset,
+            start_time,
+            end_time,
+            app_name,
+            min_confidence,
+            entity,
+            engine,
+        )
+        .await
+    }
+
+    async fn search_by_relevance(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+        recency_half_life_hours: Option<f64>,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        DatabaseManager::search_by_relevance(
+            self,
+            query,
+            content_type,
+            limit,
+            offset,
+            start_time,
+            end_time,
+            app_name,
+            min_confidence,
+            entity,
+            engine,
+            recency_half_life_hours,
+        )
+        .await
+    }
+
+    async fn search_page(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        cursor: Option<&SearchCursor>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<(Vec<SearchResult>, Option<SearchCursor>), sqlx::Error> {
+        DatabaseManager::search_page(
+            self,
+            query,
+            content_type,
+            limit,
+            cursor,
+            start_time,
+            end_time,
+            app_name,
+            min_confidence,
+            entity,
+            engine,
+        )
+        .await
+    }
+
+    async fn count_search_results(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<usize, sqlx::Error> {
+        DatabaseManager::count_search_results(
+            self,
+            query,
+            content_type,
+            start_time,
+            end_time,
+            app_name,
+            min_confidence,
+            entity,
+            engine,
+        )
+        .await
+    }
+
+    async fn search_facets(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        min_confidence: Option<f64>,
+        entity: Option<&str>,
+        engine: Option<&str>,
+    ) -> Result<SearchFacets, sqlx::Error> {
+        DatabaseManager::search_facets(
+            self,
+            query,
+            content_type,
+            start_time,
+            end_time,
+            app_name,
+            min_confidence,
+            entity,
+            engine,
+        )
+        .await
+    }
+
+    async fn fuzzy_search_query(&self, query: &str) -> Result<String, sqlx::Error> {
+        DatabaseManager::fuzzy_search_query(self, query).await
+    }
+
+    async fn get_latest_timestamps(
+        &self,
+    ) -> Result<(Option<DateTime<Utc>>, Option<DateTime<Utc>>), sqlx::Error> {
+        DatabaseManager::get_latest_timestamps(self).await
+    }
+
+    async fn ingest_stats(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket_secs: i64,
+    ) -> Result<Vec<IngestBucket>, sqlx::Error> {
+        DatabaseManager::ingest_stats(self, start, end, bucket_secs).await
+    }
+
+    async fn heatmap(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket_secs: i64,
+        utc_offset_secs: i64,
+    ) -> Result<Vec<HeatmapBucket>, sqlx::Error> {
+        DatabaseManager::heatmap(self, start, end, bucket_secs, utc_offset_secs).await
+    }
+
+    async fn timeline(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TimelineMinute>, sqlx::Error> {
+        DatabaseManager::timeline(self, start, end).await
+    }
+
+    async fn ingest(&self, batch: &IngestBatch) -> Result<IngestSummary, sqlx::Error> {
+        DatabaseManager::ingest(self, batch).await
+    }
+
+    async fn get_activity_events(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        event_type: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ActivityEvent>, sqlx::Error> {
+        DatabaseManager::get_activity_events(self, start, end, event_type, limit).await
+    }
+
+    async fn schema_version(&self) -> Result<Option<i64>, sqlx::Error> {
+        DatabaseManager::schema_version(self).await
+    }
+
+    async fn get_audio_chunk_path(&self, chunk_id: i64) -> Result<Option<String>, sqlx::Error> {
+        DatabaseManager::get_audio_chunk_path(self, chunk_id).await
+    }
+
+    async fn get_frame(&self, frame_id: i64) -> Result<Option<(String, i64)>, sqlx::Error> {
+        DatabaseManager::get_frame(self, frame_id).await
+    }
+
+    async fn insert_mark(&self, name: &str, timestamp: DateTime<Utc>) -> Result<Mark, sqlx::Error> {
+        DatabaseManager::insert_mark(self, name, timestamp).await
+    }
+
+    async fn get_marks(
+        &self,
+        query: Option<&str>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: u32,
+    ) -> Result<Vec<Mark>, sqlx::Error> {
+        DatabaseManager::get_marks(self, query, start, end, limit).await
+    }
+
+    async fn get_mark(&self, id: i64) -> Result<Option<Mark>, sqlx::Error> {
+        DatabaseManager::get_mark(self, id).await
+    }
+
+    async fn set_capture_state(&self, device: &str, is_running: bool) -> Result<(), sqlx::Error> {
+        DatabaseManager::set_capture_state(self, device, is_running).await
+    }
+
+    async fn get_events(
+        &self,
+        kinds: &[EventKind],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Event>, sqlx::Error> {
+        DatabaseManager::get_events(self, kinds, start, end, limit, offset).await
+    }
+
+    async fn count_events(
+        &self,
+        kinds: &[EventKind],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        DatabaseManager::count_events(self, kinds, start, end).await
+    }
+
+    fn write_stats(&self) -> WriteStats {
+        DatabaseManager::write_stats(self)
+    }
+
+    #[cfg(feature = "encryption")]
+    fn encryption_key(&self) -> Option<&crate::EncryptionKey> {
+        DatabaseManager::encryption_key(self)
+    }
+}
+
+/// Opens the storage of a connection string: `postgres://` or `postgresql://` for a
+/// PostgreSQL server (requires the `postgres` feature), anything else is a SQLite database.
+/// `machine` tells apart the data of several machines sharing a server.
+pub async fn open_storage(url: &str, machine: &str) -> Result<Arc<dyn Storage>, sqlx::Error> {
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        {
+            let storage = crate::postgres::PostgresStorage::new(url, machine).await?;
+            return Ok(Arc::new(storage));
+        }
+        #[cfg(not(feature = "postgres"))]
+        {
+            let _ = machine;
+            return Err(sqlx::Error::Configuration(
+                "screenpipe was built without the postgres feature".into(),
+            ));
+        }
+    }
+    Ok(Arc::new(
+        DatabaseManager::new(url.trim_start_matches("sqlite:")).await?,
+    ))
+}
+
+/// Writes waiting for the mirror storage, the next ones are dropped until it catches up
+const MIRROR_QUEUE: usize = 1000;
+
+enum MirrorCommand {
+    VideoChunk(String, String),
+    Frame(FrameRecord),
+    Audio(AudioRecord),
+    Retranscription(AudioRecord),
+    Reocr(String, i64, FrameRecord),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Writes to `primary` and copies every write to `mirror` in the background, in order,
+/// so a slow or unreachable mirror never holds up recording. Writes are dropped while
+/// `MIRROR_QUEUE` of them wait for the mirror, see `dropped_writes`.
+pub struct MirroredStorage {
+    primary: Arc<dyn Storage>,
+    sender: mpsc::Sender<MirrorCommand>,
+    dropped: AtomicU64,
+}
+
+impl MirroredStorage {
+    pub fn new(primary: Arc<dyn Storage>, mirror: Arc<dyn Storage>) -> Self {
+        Self::with_capacity(primary, mirror, MIRROR_QUEUE)
+    }
+
+    /// Mirrors writes while at most `capacity` of them wait for `mirror`
+    pub fn with_capacity(
+        primary: Arc<dyn Storage>,
+        mirror: Arc<dyn Storage>,
+        capacity: usize,
+    ) -> Self {
+        let (sender, mut receiver) = mpsc::channel(capacity);
+        tokio::spawn(async move {
+            while let Some(command) = receiver.recv().await {
+                let result = match command {
+                    MirrorCommand::VideoChunk(file_path, codec) => {
+                        mirror.insert_video_chunk(&file_path, &codec).await
+                    }
+                    MirrorCommand::Frame(frame) => mirror.write_frame(frame).await,
+                    MirrorCommand::Audio(audio) => mirror.write_audio(audio).await,
+                    MirrorCommand::Retranscription(audio) => {
+                        mirror.write_retranscription(audio).await
+                    }
+                    MirrorCommand::Reocr(video_chunk, offset_index, frame) => {
+                        mirror.write_reocr(&video_chunk, offset_index, frame).await
+                    }
+                    MirrorCommand::Flush(done) => {
+                        mirror.flush_writes().await;
+                        let _ = done.send(());
+                        Ok(())
+                    }
+                };
+                if let Err(e) = result {
+                    error!("Failed to write to mirror storage, dropping write: {}", e);
+                }
+            }
+        });
+        MirroredStorage {
+            primary,
+            sender,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Writes the mirror missed as it was behind
+    pub fn dropped_writes(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn mirror(&self, command: MirrorCommand) {
+        match self.sender.try_send(command) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                // Once per stall rather than per write
+                if dropped.is_power_of_two() {
+                    warn!(
+                        "Mirror storage is behind, {} writes dropped so far",
+                        dropped
+                    );
+                }
+            }
+            Err(TrySendError::Closed(_)) => {
+                error!("Mirror storage writer stopped, dropping write");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for MirroredStorage {
+    async fn insert_video_chunk(&self, file_path: &str, codec: &str) -> Result<(), sqlx::Error> {
+        self.mirror(MirrorCommand::VideoChunk(
+            file_path.to_string(),
+            codec.to_string(),
+        ));
+        self.primary.insert_video_chunk(file_path, codec).await
+    }
+
+    async fn write_frame(&self, frame: FrameRecord) -> Result<(), sqlx::Error> {
+        self.mirror(MirrorCommand::Frame(frame.clone()));
+        self.primary.write_frame(frame).await
+    }
+
+    async fn write_frame_now(&self, frame: FrameRecord) -> Result<Option<i64>, sqlx::Error> {
+        self.mirror(MirrorCommand::Frame(frame.clone()));
+        self.primary.write_frame_now(frame).await
+    }
+
+    async fn write_audio(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        self.mirror(MirrorCommand::Audio(audio.clone()));
+        self.primary.write_audio(audio).await
+    }
+
+    async fn write_retranscription(&self, audio: AudioRecord) -> Result<(), sqlx::Error> {
+        self.mirror(MirrorCommand::Retranscription(audio.clone()));
+        self.primary.write_retranscription(audio).await
+    }
+
+    async fn write_reocr(
+        &self,
+        video_chunk: &str,
+        offset_index: i64,
+        frame: FrameRecord,
+    ) -> Result<(), sqlx::Error> {
+        self.mirror(MirrorCommand::Reocr(
+            video_chunk.to_string(),
+            offset_index,
+            frame.clone(),
+        ));
+        self.primary
+            .write_reocr(video_chunk, offset_index, frame)
+            .await
+    }
+
+    async fn flush_writes(&self) {
+        self.primary.flush_writes().await;
+        let (done, flushed) = oneshot::channel();
+        // Waits for room rather than dropping it
+        if self.sender.send(MirrorCommand::Flush(done)).await.is_ok() {
+            let _ = flushed.await;
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{HealthMonitor, Shutdown, Subsystem};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use std::future::Future;
+use std::time::Duration;
+
+/// Wait before the first restart, doubled after each restart in a row
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// How often `stalled` is asked
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wait before restarting a task that ran for `ran_for`, `previous` being the wait before
+/// its last restart, None if it never restarted. Doubles in a crash loop, back to the
+/// minimum after a run that lasted.
+pub(crate) fn restart_backoff(previous: Option<Duration>, ran_for: Duration) -> Duration {
+    match previous {
+        Some(previous) if ran_for <= MAX_BACKOFF => (previous * 2).min(MAX_BACKOFF),
+        _ => MIN_BACKOFF,
+    }
+}
+
+/// Runs the task `start` returns and restarts it, after an exponential backoff, when it
+/// exits, panics or `stalled` says it stopped making progress. `stalled` gets when the
+/// current run started. Returns once the task ended on shutdown or while `keep_running`
+/// is false, e.g. when vision was turned off through the API.
+pub(crate) async fn supervise<F, Fut>(
+    subsystem: Subsystem,
+    health: &HealthMonitor,
+    shutdown: &Shutdown,
+    mut start: F,
+    mut stalled: impl FnMut(DateTime<Utc>) -> bool,
+    keep_running: impl Fn() -> bool,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let mut backoff = None;
+    loop {
+        let started = Utc::now();
+        let mut task = tokio::spawn(start());
+        let reason = loop {
+            tokio::select! {
+                result = &mut task => break match result {
+                    Ok(Ok(())) => "exited".to_string(),
+                    Ok(Err(e)) => format!("failed: {}", e),
+                    Err(e) => format!("panicked: {}", e),
+                },
+                _ = tokio::time::sleep(STALL_CHECK_INTERVAL) => {
+                    // Draining what was captured before the shutdown isn't a stall
+                    if !shutdown.is_requested() && stalled(started) {
+                        task.abort();
+                        let _ = (&mut task).await;
+                        break "stalled".to_string();
+                    }
+                }
+            }
+        };
+        if shutdown.is_requested() || !keep_running() {
+            return;
+        }
+
+        let ran_for = Utc::now()
+            .signed_duration_since(started)
+            .to_std()
+            .unwrap_or_default();
+        let wait = restart_backoff(backoff, ran_for);
+        backoff = Some(wait);
+        error!(
+            "{:?} task {}, restarting it in {}s",
+            subsystem,
+            reason,
+            wait.as_secs()
+        );
+        health.task_restarted(subsystem, format!("Task {}, restarted", reason));
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = shutdown.requested() => return,
+        }
+        if !keep_running() {
+            warn!("{:?} was turned off, not restarting it", subsystem);
+            return;
+        }
+        info!("Restarting the {:?} task", subsystem);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::backup::file_digest;
+use crate::{BackupLocation, DatabaseManager};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// First bytes of a stub, media files can't start with them
+const STUB_MAGIC: &[u8] = b"screenpipe-offloaded-chunk\n";
+/// Stubs are a few hundred bytes, anything bigger is a media file
+const MAX_STUB_SIZE: u64 = 4096;
+
+/// What's left locally of an offloaded chunk, at the path of the chunk
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkStub {
+    key: String,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OffloadSummary {
+    /// Chunks replaced by a stub, uploaded or fetched back before
+    pub offloaded: usize,
+    pub uploaded_bytes: u64,
+    pub freed_bytes: u64,
+    pub failed: usize,
+}
+
+/// The stub at `path`, None if it's a media file
+async fn read_stub(path: &Path) -> Result<Option<ChunkStub>> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<Option<ChunkStub>> {
+        let mut content = Vec::new();
+        std::fs::File::open(&path)?
+            .take(MAX_STUB_SIZE)
+            .read_to_end(&mut content)?;
+        match content.strip_prefix(STUB_MAGIC) {
+            Some(json) => Ok(Some(serde_json::from_slice(json)?)),
+            None => Ok(None),
+        }
+    })
+    .await?
+}
+
+/// Replaces the file at `path` by a stub, atomically so readers never see a partial file
+async fn write_stub(path: &Path, stub: &ChunkStub) -> Result<u64> {
+    let mut content = STUB_MAGIC.to_vec();
+    content.extend(serde_json::to_vec(stub)?);
+    let temp_path = sibling_temp_path(path, "stub");
+    tokio::fs::write(&temp_path, &content).await?;
+    tokio::fs::rename(&temp_path, path).await?;
+    Ok(content.len() as u64)
+}
+
+/// Unique per call, concurrent fetches of the same chunk don't write the same file
+fn sibling_temp_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{:x}.{}", rand::random::<u64>(), suffix));
+    PathBuf::from(name)
+}
+
+fn remote_key(kind: &str, id: i64, file_path: &str) -> String {
+    let file_name = Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "chunk".to_string());
+    format!("{}/{}-{}", kind, id, file_name)
+}
+
+/// Uploads the video/audio chunks recorded by this machine before `before` to `remote` and
+/// replaces their local file with a stub, which `ensure_local` fetches back on demand.
+/// Chunks uploaded before and fetched back since are turned back into stubs without
+/// uploading them again. Offloaded chunks are left out of the storage quota eviction, and
+/// backups copy their stub. A chunk that fails is retried on the next call.
+pub async fn offload_chunks(
+    db: &DatabaseManager,
+    remote: &BackupLocation,
+    before: DateTime<Utc>,
+) -> Result<OffloadSummary> {
+    let mut summary = OffloadSummary::default();
+    let video_chunks = db.get_video_chunks_to_offload(before).await?;
+    let audio_chunks = db.get_audio_chunks_to_offload(before).await?;
+    let chunks = video_chunks
+        .into_iter()
+        .map(|chunk| ("video", chunk))
+        .chain(audio_chunks.into_iter().map(|chunk| ("audio", chunk)));
+
+    for (kind, (id, file_path, key)) in chunks {
+        match offload_chunk(db, remote, kind, id, &file_path, key).await {
+            Ok(Some((uploaded, freed))) => {
+                summary.offloaded += 1;
+                summary.uploaded_bytes += uploaded;
+                summary.freed_bytes += freed;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to offload {}: {}", file_path, e);
+                summary.failed += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Bytes uploaded and freed, None if there was nothing to do
+async fn offload_chunk(
+    db: &DatabaseManager,
+    remote: &BackupLocation,
+    kind: &str,
+    id: i64,
+    file_path: &str,
+    key: Option<String>,
+) -> Result<Option<(u64, u64)>> {
+    let path = Path::new(file_path);
+    // Evicted or deleted by hand, nothing to offload
+    if !tokio::fs::try_exists(path).await? || read_stub(path).await?.is_some() {
+        return Ok(None);
+    }
+
+    let (size, sha256) = file_digest(path.to_path_buf()).await?;
+    let (key, uploaded) = match key {
+        Some(key) => (key, 0),
+        None => {
+            let key = remote_key(kind, id, file_path);
+            remote.put_file(&key, path).await?;
+            // Only stubbed once the database knows where the chunk is
+            match kind {
+                "video" => db.set_video_chunk_remote_key(id, &key).await?,
+                _ => db.set_audio_chunk_remote_key(id, &key).await?,
+            }
+            (key, size)
+        }
+    };
+    let stub_size = write_stub(path, &ChunkStub { key, size, sha256 }).await?;
+    debug!("Offloaded {} ({} bytes)", file_path, size);
+    Ok(Some((uploaded, size.saturating_sub(stub_size))))
+}
+
+/// Whether the file is the stub of an offloaded chunk
+pub(crate) async fn is_offloaded(path: &Path) -> bool {
+    matches!(read_stub(path).await, Ok(Some(_)))
+}
+
+/// Fetches the chunk at `file_path` back from `remote` if it was offloaded, so it can be
+/// read as a media file. Returns whether it was fetched. The download is checked against
+/// the size and SHA-256 of the chunk before replacing the stub.
+pub async fn ensure_local(remote: &BackupLocation, file_path: &str) -> Result<bool> {
+    let path = Path::new(file_path);
+    let stub = match read_stub(path).await? {
+        Some(stub) => stub,
+        None => return Ok(false),
+    };
+    let temp_path = sibling_temp_path(path, "fetching");
+    let fetched = async {
+        remote.get_file(&stub.key, &temp_path).await?;
+        let (size, sha256) = file_digest(temp_path.clone()).await?;
+        if size != stub.size || sha256 != stub.sha256 {
+            return Err(anyhow!("{} is corrupt in the offload bucket", stub.key));
+        }
+        tokio::fs::rename(&temp_path, path).await?;
+        Ok(())
+    }
+    .await;
+    if fetched.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    fetched?;
+    info!(
+        "Fetched offloaded chunk {} ({} bytes)",
+        file_path, stub.size
+    );
+    Ok(true)
+}
+
+/// Makes the chunk at `file_path` readable, fetching it back from `remote`, where chunks
+/// are offloaded to, when it was offloaded
+pub(crate) async fn fetch_offloaded(
+    remote: Option<&BackupLocation>,
+    file_path: &str,
+) -> Result<()> {
+    if !is_offloaded(Path::new(file_path)).await {
+        return Ok(());
+    }
+    let remote =
+        remote.ok_or_else(|| anyhow!("offloaded, and --offload-to isn't set to fetch it back"))?;
+    ensure_local(remote, file_path).await?;
+    Ok(())
+}
+
+/// Offloads the chunks older than `offload_after` every `check_interval`
+pub async fn run_offload(
+    db: Arc<DatabaseManager>,
+    remote: Arc<BackupLocation>,
+    offload_after: Duration,
+    check_interval: Duration,
+) {
+    info!(
+        "Offloading chunks older than {} days",
+        offload_after.as_secs() / 86_400
+    );
+    let offload_after = match chrono::Duration::from_std(offload_after) {
+        Ok(offload_after) => offload_after,
+        Err(e) => {
+            error!("Invalid offload delay: {}", e);
+            return;
+        }
+    };
+    loop {
+        match offload_chunks(&db, &remote, Utc::now() - offload_after).await {
+            Ok(summary) if summary.offloaded > 0 || summary.failed > 0 => info!(
+                "Offloaded {} chunks ({} bytes uploaded, {} bytes freed), {} failed",
+                summary.offloaded, summary.uploaded_bytes, summary.freed_bytes, summary.failed
+            ),
+            Ok(_) => {}
+            Err(e) => error!("Failed to offload chunks: {}", e),
+        }
+        tokio::time::sleep(check_interval).await;
+    }
+}
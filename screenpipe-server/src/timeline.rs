@@ -0,0 +1,73 @@
+use crate::TimelineMinute;
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineState {
+    /// Frames or audio were recorded
+    Active,
+    /// The user was away, capture is paused with --idle-timeout
+    Idle,
+    /// Nothing was recorded, capture was off
+    Gap,
+}
+
+/// Consecutive minutes of `DatabaseManager::timeline` in the same state, on the same app
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineSegment {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub state: TimelineState,
+    /// App with the most frames of each minute
+    pub app_name: Option<String>,
+    pub frames: i64,
+    /// Audio was recorded during every minute
+    pub audio: bool,
+}
+
+/// Start and end of a day in the local time zone of the server
+pub fn local_day_range(day: NaiveDate) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let start_of_day = |day: NaiveDate| {
+        day.and_hms_opt(0, 0, 0)
+            .and_then(|time| Local.from_local_datetime(&time).earliest())
+            .map(|time| time.with_timezone(&Utc))
+    };
+    Some((start_of_day(day)?, start_of_day(day.succ_opt()?)?))
+}
+
+/// Merges the minutes into segments, a minute covered by an idle event is idle even if
+/// something was recorded during it
+pub fn timeline_segments(minutes: &[TimelineMinute]) -> Vec<TimelineSegment> {
+    let mut segments: Vec<TimelineSegment> = Vec::new();
+    for minute in minutes {
+        let state = if minute.idle {
+            TimelineState::Idle
+        } else if minute.frames > 0 || minute.audio {
+            TimelineState::Active
+        } else {
+            TimelineState::Gap
+        };
+        let end = minute.start + chrono::Duration::minutes(1);
+        match segments.last_mut() {
+            Some(segment)
+                if segment.end == minute.start
+                    && segment.state == state
+                    && segment.app_name == minute.app_name
+                    && segment.audio == minute.audio =>
+            {
+                segment.end = end;
+                segment.frames += minute.frames;
+            }
+            _ => segments.push(TimelineSegment {
+                start: minute.start,
+                end,
+                state,
+                app_name: minute.app_name.clone(),
+                frames: minute.frames,
+                audio: minute.audio,
+            }),
+        }
+    }
+    segments
+}
@@ -1,17 +1,24 @@
+use crate::{monitor_config, LiveSettings, MonitorConfig, Shutdown, VideoEncoding, VisionStatus};
 use chrono::Utc;
+use image::DynamicImage;
 use image::ImageFormat::{self};
 use log::{debug, error, info, warn};
 use screenpipe_core::find_ffmpeg_path;
-use screenpipe_vision::{continuous_capture, get_monitor, CaptureResult, OcrEngine};
+use screenpipe_vision::{
+    continuous_capture, find_monitor, CaptureResult, CaptureSource, CaptureTrigger, OcrEngine,
+    OcrLanguages, ScreenMask, SensitiveContentFilter, SyntheticCaptureSource, SYNTHETIC_APP_NAME,
+};
 use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::io::AsyncWriteExt;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::{Child, ChildStdin, Command};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
 
 use std::time::Duration;
 
@@ -19,17 +26,41 @@ const MAX_FPS: f64 = 30.0; // Adjust based on your needs
 
 pub struct VideoCapture {
     frame_queue: Arc<Mutex<VecDeque<CaptureResult>>>,
-    video_frame_queue: Arc<Mutex<VecDeque<CaptureResult>>>,
+    video_frame_queue: Arc<Mutex<VecDeque<Arc<DynamicImage>>>>,
     pub ocr_frame_queue: Arc<Mutex<VecDeque<CaptureResult>>>,
+    capture_thread: JoinHandle<()>,
+    video_thread: JoinHandle<()>,
 }
 
 impl VideoCapture {
+    /// The FPS and ignored apps of `settings` apply from their next change on.
+    /// Frames flagged by `sensitive_content` are skipped, blurred or turned to text only.
+    /// The regions of `screen_mask` are hidden from every frame before OCR and encoding.
+    /// The first monitor enabled in `monitors` is recorded, with its FPS and crop.
+    /// With `idle_fps` capture slows down to it while the screen barely changes.
+    /// Chunks are encoded with `encoding`, check it with `VideoEncoding::or_available`.
+    /// Capture stops once `shutdown` is requested, see `wait_stopped`.
     pub fn new(
         output_path: &str,
-        fps: f64,
+        settings: watch::Receiver<LiveSettings>,
         new_chunk_callback: impl Fn(&str) + Send + Sync + 'static,
         save_text_files: bool,
         ocr_engine: Arc<OcrEngine>,
+        ocr_max_dimension: Option<u32>,
+        ocr_languages: OcrLanguages,
+        dedup_threshold: Option<f64>,
+        differential_ocr: bool,
+        sensitive_content: Option<SensitiveContentFilter>,
+        screen_mask: Option<ScreenMask>,
+        monitors: Vec<MonitorConfig>,
+        idle_fps: Option<f64>,
+        encoding: VideoEncoding,
+        capture_paused: Arc<AtomicBool>,
+        capture_trigger: Arc<CaptureTrigger>,
+        ocr_pool_size: usize,
+        synthetic_frames_dir: Option<PathBuf>,
+        vision_status: Arc<VisionStatus>,
+        shutdown: Shutdown,
     ) -> Self {
         info!("Starting new video capture");
         let frame_queue = Arc::new(Mutex::new(VecDeque::new()));
@@ -42,47 +73,153 @@ impl VideoCapture {
         let capture_video_frame_queue = video_frame_queue.clone();
         let capture_ocr_frame_queue = ocr_frame_queue.clone();
         let (result_sender, mut result_receiver) = channel(512);
-        let _capture_thread = tokio::spawn(async move {
-            continuous_capture(
-                result_sender,
-                Duration::from_secs_f64(1.0 / fps),
-                save_text_files,
-                ocr_engine,
-                get_monitor().await,
-            )
-            .await;
-        });
-
-        info!("Started capture thread");
+        let capture_source = match synthetic_frames_dir {
+            Some(dir) => match SyntheticCaptureSource::new(&dir) {
+                Ok(source) => Some((
+                    CaptureSource::Synthetic(source),
+                    SYNTHETIC_APP_NAME.to_string(),
+                )),
+                Err(e) => {
+                    error!(
+                        "Failed to load synthetic frames from {}: {}",
+                        dir.display(),
+                        e
+                    );
+                    None
+                }
+            },
+            None => match find_monitor(|name| {
+                monitor_config(&monitors, name).map_or(true, |monitor| monitor.enabled)
+            }) {
+                Some(monitor) => {
+                    let name = monitor.name().to_string();
+                    Some((CaptureSource::Monitor(monitor), name))
+                }
+                None => {
+                    error!("Every monitor is disabled, no screen to record");
+                    None
+                }
+            },
+        };
+        let monitor = capture_source
+            .as_ref()
+            .and_then(|(_, name)| monitor_config(&monitors, name));
+        let fps = monitor.and_then(|monitor| monitor.fps);
+        // Validated with the config file
+        let crop = monitor.and_then(|monitor| monitor.capture_crop().ok().flatten());
+        if let (Some(monitor), Some(fps)) = (monitor, fps) {
+            info!("Recording monitor {} at {} FPS", monitor.name, fps);
+        }
+        if let (Some(monitor), Some(crop)) = (monitor, &crop) {
+            info!("Cropping {} off monitor {}", crop, monitor.name);
+        }
 
-        // Spawn another thread to handle receiving and queueing the results
-        let _queue_thread = tokio::spawn(async move {
-            while let Some(result) = result_receiver.recv().await {
-                let frame_number = result.frame_number;
-                debug!("Received frame {} for queueing", frame_number);
-                let mut queue = capture_frame_queue.lock().await;
-                let mut video_queue = capture_video_frame_queue.lock().await;
-                let mut ocr_queue = capture_ocr_frame_queue.lock().await;
-                queue.push_back(result.clone());
-                video_queue.push_back(result.clone());
-                ocr_queue.push_back(result);
-                debug!("Frame {} pushed to queues. Queue length: {}, Video queue length: {}, OCR queue length: {}", frame_number, queue.len(), video_queue.len(), ocr_queue.len());
-
-                // Clear the old queue after processing
-                if queue.len() > 1 {
-                    queue.pop_front();
+        let queue_settings = settings.clone();
+        let video_settings = settings.clone();
+        let ocr_settings = settings.clone();
+        let interval = capture_interval(settings, fps);
+        let idle_interval = idle_fps.map(|fps| Duration::from_secs_f64(1.0 / fps));
+        // Set once the last frame is queued, the video thread then finalizes its chunk
+        let capture_stopped = Arc::new(AtomicBool::new(false));
+        let video_capture_stopped = Arc::clone(&capture_stopped);
+        let capture_thread = tokio::spawn(async move {
+            let Some((capture_source, monitor_name)) = capture_source else {
+                capture_stopped.store(true, Ordering::SeqCst);
+                return;
+            };
+            vision_status.add_monitor(&monitor_name);
+
+            // Spawn another thread to handle receiving and queueing the results
+            let queue_thread = tokio::spawn(async move {
+                while let Some(result) = result_receiver.recv().await {
+                    let frame_number = result.frame_number;
+                    debug!("Received frame {} for queueing", frame_number);
+                    vision_status.record_frame(&monitor_name, result.timestamp, &result.app_name);
+                    // Dropped before any queue so video offsets and OCR frames stay in step
+                    if queue_settings.borrow().is_ignored(&result.app_name) {
+                        debug!(
+                            "Frame {} of ignored app {} dropped",
+                            frame_number, result.app_name
+                        );
+                        continue;
+                    }
+                    let result = match &sensitive_content {
+                        Some(filter) => {
+                            let filter = filter.clone();
+                            match tokio::task::spawn_blocking(move || filter.apply(result)).await {
+                                Ok(Some(result)) => result,
+                                Ok(None) => {
+                                    debug!("Sensitive frame {} dropped", frame_number);
+                                    continue;
+                                }
+                                Err(e) => {
+                                    error!(
+                                        "Sensitive content check of frame {} failed: {}",
+                                        frame_number, e
+                                    );
+                                    continue;
+                                }
+                            }
+                        }
+                        None => result,
+                    };
+                    let mut queue = capture_frame_queue.lock().await;
+                    let mut video_queue = capture_video_frame_queue.lock().await;
+                    let mut ocr_queue = capture_ocr_frame_queue.lock().await;
+                    // The encoder only needs the pixels, share the frame instead of copying it
+                    video_queue.push_back(Arc::clone(&result.image));
+                    queue.push_back(result.clone());
+                    ocr_queue.push_back(result);
+                    debug!("Frame {} pushed to queues. Queue length: {}, Video queue length: {}, OCR queue length: {}", frame_number, queue.len(), video_queue.len(), ocr_queue.len());
+
+                    // Clear the old queue after processing
+                    if queue.len() > 1 {
+                        queue.pop_front();
+                    }
                 }
+            });
+
+            tokio::select! {
+                _ = continuous_capture(
+                    result_sender,
+                    interval,
+                    idle_interval,
+                    save_text_files,
+                    ocr_engine,
+                    capture_source,
+                    ocr_max_dimension,
+                    ocr_languages,
+                    dedup_threshold,
+                    differential_ocr,
+                    screen_mask,
+                    crop,
+                    capture_paused,
+                    capture_trigger,
+                    ocr_pool_size,
+                    Arc::new(move |app_name: &str| ocr_settings.borrow().is_ignored(app_name)),
+                ) => {}
+                _ = shutdown.requested() => info!("Stopping screen capture"),
+            }
+            // The OCR tasks in flight still send their results, the queue thread ends after
+            if let Err(e) = queue_thread.await {
+                error!("Frame queue thread failed: {}", e);
             }
+            capture_stopped.store(true, Ordering::SeqCst);
         });
 
+        info!("Started capture thread");
+
         let video_frame_queue_clone = video_frame_queue.clone();
         let output_path = output_path.to_string();
-        let _video_thread = tokio::spawn(async move {
+        let video_thread = tokio::spawn(async move {
             save_frames_as_video(
                 &video_frame_queue_clone,
                 &output_path,
+                video_settings,
                 fps,
+                &encoding,
                 new_chunk_callback_clone,
+                video_capture_stopped,
             )
             .await;
         });
@@ -91,6 +228,19 @@ impl VideoCapture {
             frame_queue,
             video_frame_queue,
             ocr_frame_queue,
+            capture_thread,
+            video_thread,
+        }
+    }
+
+    /// Waits, after a shutdown was requested, for the capture to stop and the chunk being
+    /// written to be finalized. The frames OCR'd until then are left in `ocr_frame_queue`.
+    pub async fn wait_stopped(mut self) {
+        if let Err(e) = (&mut self.capture_thread).await {
+            error!("Capture thread failed: {}", e);
+        }
+        if let Err(e) = (&mut self.video_thread).await {
+            error!("Video thread failed: {}", e);
         }
     }
 
@@ -101,15 +251,47 @@ impl VideoCapture {
         queue.pop_front()
     }
 
-    pub fn get_video_frame_queue(&self) -> Arc<Mutex<VecDeque<CaptureResult>>> {
+    pub fn get_video_frame_queue(&self) -> Arc<Mutex<VecDeque<Arc<DynamicImage>>>> {
         Arc::clone(&self.video_frame_queue)
     }
 }
+/// Capture stops with the `VideoCapture`, e.g. when its recording task is restarted
+impl Drop for VideoCapture {
+    fn drop(&mut self) {
+        self.capture_thread.abort();
+        self.video_thread.abort();
+    }
+}
+
+/// Interval between captures following the FPS of `settings`, or `fps` of the monitor
+fn capture_interval(
+    mut settings: watch::Receiver<LiveSettings>,
+    fps: Option<f64>,
+) -> watch::Receiver<Duration> {
+    let (interval_tx, interval_rx) = watch::channel(Duration::from_secs_f64(
+        1.0 / fps.unwrap_or(settings.borrow().fps),
+    ));
+    tokio::spawn(async move {
+        while settings.changed().await.is_ok() {
+            let interval = Duration::from_secs_f64(1.0 / fps.unwrap_or(settings.borrow().fps));
+            interval_tx.send_if_modified(|current| {
+                let modified = *current != interval;
+                *current = interval;
+                modified
+            });
+        }
+    });
+    interval_rx
+}
+
 async fn save_frames_as_video(
-    frame_queue: &Arc<Mutex<VecDeque<CaptureResult>>>,
+    frame_queue: &Arc<Mutex<VecDeque<Arc<DynamicImage>>>>,
     output_path: &str,
-    fps: f64,
+    settings: watch::Receiver<LiveSettings>,
+    monitor_fps: Option<f64>,
+    encoding: &VideoEncoding,
     new_chunk_callback: Arc<dyn Fn(&str) + Send + Sync>,
+    capture_stopped: Arc<AtomicBool>,
 ) {
     debug!("Starting save_frames_as_video function");
     let frames_per_video = 30; // Adjust this value as needed
@@ -118,36 +300,36 @@ async fn save_frames_as_video(
     let sender = Arc::new(sender);
     let mut current_ffmpeg: Option<Child> = None;
     let mut current_stdin: Option<ChildStdin> = None;
+    // Frame rate of the current chunk, a new FPS applies from the next chunk
+    let mut fps = monitor_fps.unwrap_or(settings.borrow().fps);
 
     loop {
         if frame_count % frames_per_video == 0 || current_ffmpeg.is_none() {
+            fps = monitor_fps.unwrap_or(settings.borrow().fps);
             debug!("Starting new FFmpeg process");
             // Close previous FFmpeg process if exists
             if let Some(child) = current_ffmpeg.take() {
-                drop(current_stdin.take()); // Ensure stdin is closed
-                let output = child
-                    .wait_with_output()
-                    .await
-                    .expect("ffmpeg process failed");
-                debug!("FFmpeg process exited with status: {}", output.status);
-                if !output.status.success() {
-                    error!("FFmpeg stderr: {}", String::from_utf8_lossy(&output.stderr));
-                }
+                finish_ffmpeg_process(child, current_stdin.take()).await;
             }
 
             // Wait for at least one frame before starting a new FFmpeg process
             let first_frame = loop {
+                // Read before the queue, frames are all queued once it's set
+                let stopped = capture_stopped.load(Ordering::SeqCst);
                 if let Some(result) = frame_queue.lock().await.pop_front() {
                     debug!("Got first frame for new chunk");
                     break result;
                 }
+                if stopped {
+                    debug!("Capture stopped, no chunk left to write");
+                    return;
+                }
                 tokio::time::sleep(Duration::from_millis(10)).await;
             };
 
             // Encode the first frame
             let mut buffer = Vec::new();
             first_frame
-                .image
                 .write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)
                 .expect("Failed to encode first frame");
 
@@ -163,7 +345,7 @@ async fn save_frames_as_video(
             // Call the callback with the new video chunk file path
             new_chunk_callback(&output_file);
 
-            match start_ffmpeg_process(&output_file, fps).await {
+            match start_ffmpeg_process(&output_file, fps, encoding).await {
                 Ok(mut child) => {
                     let mut stdin = child.stdin.take().expect("Failed to open stdin");
                     let stderr = child.stderr.take().expect("Failed to open stderr");
@@ -205,15 +387,14 @@ async fn save_frames_as_video(
             }
         }
 
-        if let Some(result) = frame_queue.lock().await.pop_front() {
+        let stopped = capture_stopped.load(Ordering::SeqCst);
+        if let Some(image) = frame_queue.lock().await.pop_front() {
             debug!("Processing frame in video.rs"); // {}", frame_count + 1
             let sender = Arc::clone(&sender);
 
             tokio::spawn(async move {
                 let mut buffer = Vec::new();
-                match result
-                    .image
-                    .write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)
+                match image.write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)
                 {
                     Ok(_) => {
                         sender
@@ -224,6 +405,22 @@ async fn save_frames_as_video(
                     Err(e) => error!("Failed to encode image as PNG: {}", e),
                 }
             });
+        } else if stopped {
+            // Every frame is queued, write the ones still being encoded and end the chunk
+            drop(sender);
+            while let Some(buffer) = receiver.recv().await {
+                if let Some(stdin) = current_stdin.as_mut() {
+                    if let Err(e) = stdin.write_all(buffer.as_slice()).await {
+                        error!("Failed to write frame to ffmpeg: {}", e);
+                        break;
+                    }
+                }
+            }
+            if let Some(child) = current_ffmpeg.take() {
+                finish_ffmpeg_process(child, current_stdin.take()).await;
+            }
+            info!("Finalized the last video chunk");
+            return;
         } else {
             // debug!("No frames in queue, waiting...");
             tokio::time::sleep(Duration::from_millis(10)).await;
@@ -240,7 +437,7 @@ async fn save_frames_as_video(
                 debug!("Wrote frame {} to FFmpeg", frame_count);
 
                 // Flush every second
-                if frame_count % fps as usize == 0 {
+                if frame_count % (fps as usize).max(1) == 0 {
                     debug!("Flushing FFmpeg input");
                     if let Err(e) = stdin.flush().await {
                         error!("Failed to flush FFmpeg input: {}", e);
@@ -256,9 +453,25 @@ async fn save_frames_as_video(
     }
 }
 
-use std::env;
+/// Closes the input of `child` so it writes the end of the mp4, and waits for it to exit
+async fn finish_ffmpeg_process(child: Child, stdin: Option<ChildStdin>) {
+    drop(stdin);
+    match child.wait_with_output().await {
+        Ok(output) => {
+            debug!("FFmpeg process exited with status: {}", output.status);
+            if !output.status.success() {
+                error!("FFmpeg stderr: {}", String::from_utf8_lossy(&output.stderr));
+            }
+        }
+        Err(e) => error!("FFmpeg process failed: {}", e),
+    }
+}
 
-async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child, anyhow::Error> {
+async fn start_ffmpeg_process(
+    output_file: &str,
+    fps: f64,
+    encoding: &VideoEncoding,
+) -> Result<Child, anyhow::Error> {
     // Overriding fps with max fps if over the max and warning user
     let fps = if fps > MAX_FPS {
         warn!("Overriding FPS from {} to {}", fps, MAX_FPS);
@@ -270,6 +483,7 @@ async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child, anyh
     info!("Starting FFmpeg process for file: {}", output_file);
     let fps_str = fps.to_string();
     let mut command = Command::new(find_ffmpeg_path().unwrap());
+    let encoding_args = encoding.ffmpeg_args();
     let mut args = vec![
         "-f",
         "image2pipe",
@@ -280,23 +494,18 @@ async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child, anyh
         "-i",
         "-",
     ];
-
-    if env::consts::OS == "windows" {
-        // TODO switch back to libx264 when ffmpeg is updated in pre_build.js
-        // Use MPEG-4 encoder for Windows
-        args.extend_from_slice(&[
-            "-vcodec",
-            "mpeg4",
-            "-q:v",
-            "5", // Adjust quality (1-31, lower is better)
-            "-preset",
-            "ultrafast",
-        ]);
-    } else {
-        // Use libx264 for other platforms
-        args.extend_from_slice(&["-vcodec", "libx264", "-preset", "ultrafast", "-crf", "23"]);
-    }
-
+    // TODO switch Windows back to libx264 when ffmpeg is updated in pre_build.js, it's MPEG-4
+    // by default there
+    args.extend(encoding_args.iter().map(String::as_str));
+
+    // Fragmented, so a chunk cut off by a crash keeps what was written up to its last
+    // fragment, see `recover_chunks`
+    args.extend_from_slice(&[
+        "-movflags",
+        "+frag_keyframe+empty_moov+default_base_moof",
+        "-frag_duration",
+        "5000000",
+    ]);
     args.extend_from_slice(&["-pix_fmt", "yuv420p", output_file]);
 
     command
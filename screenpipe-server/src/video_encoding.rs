@@ -0,0 +1,250 @@
+use log::{info, warn};
+use screenpipe_core::find_ffmpeg_path;
+use serde::{Deserialize, Serialize};
+
+/// Compression format of the video chunks, H.265 and AV1 take less space than H.264 for
+/// the same quality but cost more to encode and aren't supported by every encoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    Hevc,
+    Av1,
+}
+
+impl VideoCodec {
+    /// Name stored with the video chunks
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::Hevc => "hevc",
+            VideoCodec::Av1 => "av1",
+        }
+    }
+
+    fn default_crf(&self) -> u8 {
+        match self {
+            VideoCodec::H264 => 23,
+            VideoCodec::Hevc => 28,
+            VideoCodec::Av1 => 35,
+        }
+    }
+}
+
+/// FFmpeg encoder writing the video chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoEncoder {
+    /// libx264, libx265 or libsvtav1 on the CPU
+    Software,
+    /// MPEG-4 part 2, for the ffmpeg builds without libx264
+    Mpeg4,
+    /// Apple's hardware encoder, macOS only
+    VideoToolbox,
+    /// NVIDIA GPUs
+    Nvenc,
+    /// Intel Quick Sync
+    Qsv,
+}
+
+impl VideoEncoder {
+    /// The encoder used without --video-encoder, the bundled ffmpeg of Windows lacks libx264
+    pub fn platform_default() -> Self {
+        if cfg!(target_os = "windows") {
+            VideoEncoder::Mpeg4
+        } else {
+            VideoEncoder::Software
+        }
+    }
+
+    /// Name in `ffmpeg -encoders` of the encoder writing `codec`, `None` if it can't. MPEG-4
+    /// has a single codec of its own, it stands in for H.264.
+    pub fn ffmpeg_name(&self, codec: VideoCodec) -> Option<&'static str> {
+        let name = match (self, codec) {
+            (VideoEncoder::Mpeg4, VideoCodec::H264) => "mpeg4",
+            (VideoEncoder::Mpeg4, _) => return None,
+            (VideoEncoder::Software, VideoCodec::H264) => "libx264",
+            (VideoEncoder::Software, VideoCodec::Hevc) => "libx265",
+            (VideoEncoder::Software, VideoCodec::Av1) => "libsvtav1",
+            (VideoEncoder::VideoToolbox, VideoCodec::H264) => "h264_videotoolbox",
+            (VideoEncoder::VideoToolbox, VideoCodec::Hevc) => "hevc_videotoolbox",
+            (VideoEncoder::VideoToolbox, VideoCodec::Av1) => return None,
+            (VideoEncoder::Nvenc, VideoCodec::H264) => "h264_nvenc",
+            (VideoEncoder::Nvenc, VideoCodec::Hevc) => "hevc_nvenc",
+            (VideoEncoder::Nvenc, VideoCodec::Av1) => "av1_nvenc",
+            (VideoEncoder::Qsv, VideoCodec::H264) => "h264_qsv",
+            (VideoEncoder::Qsv, VideoCodec::Hevc) => "hevc_qsv",
+            (VideoEncoder::Qsv, VideoCodec::Av1) => "av1_qsv",
+        };
+        Some(name)
+    }
+
+    fn default_preset(&self, codec: VideoCodec) -> Option<&'static str> {
+        match (self, codec) {
+            // SVT-AV1 presets are numbered, the higher the faster
+            (VideoEncoder::Software, VideoCodec::Av1) => Some("12"),
+            (VideoEncoder::Software, _) => Some("ultrafast"),
+            (VideoEncoder::Nvenc, _) => Some("p1"),
+            (VideoEncoder::Qsv, _) => Some("veryfast"),
+            (VideoEncoder::Mpeg4 | VideoEncoder::VideoToolbox, _) => None,
+        }
+    }
+}
+
+/// How the video chunks are encoded. A `bitrate` takes precedence over the `crf`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoEncoding {
+    pub encoder: VideoEncoder,
+    pub codec: VideoCodec,
+    /// Speed/size trade-off, named as the encoder names it, e.g. "veryfast" for libx264 or
+    /// "p4" for NVENC. The fastest one when `None`.
+    pub preset: Option<String>,
+    /// Constant quality from 0 (lossless) to 51, lower is better. The codec's default when
+    /// `None`: 23 for H.264, 28 for H.265 and 35 for AV1. Ignored by MPEG-4 and VideoToolbox.
+    pub crf: Option<u8>,
+    /// Target bitrate as ffmpeg takes it, e.g. "2M"
+    pub bitrate: Option<String>,
+}
+
+impl Default for VideoEncoding {
+    fn default() -> Self {
+        VideoEncoding {
+            encoder: VideoEncoder::platform_default(),
+            codec: VideoCodec::H264,
+            preset: None,
+            crf: None,
+            bitrate: None,
+        }
+    }
+}
+
+impl VideoEncoding {
+    /// Name of the codec stored with the video chunks, "mpeg4" for the MPEG-4 encoder
+    pub fn codec_name(&self) -> &'static str {
+        match self.encoder {
+            VideoEncoder::Mpeg4 => "mpeg4",
+            _ => self.written_codec().name(),
+        }
+    }
+
+    /// Name in `ffmpeg -encoders` of the encoder, `None` if it can't write the codec
+    pub fn ffmpeg_encoder(&self) -> Option<&'static str> {
+        self.encoder.ffmpeg_name(self.codec)
+    }
+
+    /// The codec, H.264 if the encoder can't write it
+    fn written_codec(&self) -> VideoCodec {
+        match self.ffmpeg_encoder() {
+            Some(_) => self.codec,
+            None => VideoCodec::H264,
+        }
+    }
+
+    /// Output arguments of ffmpeg encoding with these settings
+    pub fn ffmpeg_args(&self) -> Vec<String> {
+        let codec = self.written_codec();
+        let encoder = self.encoder.ffmpeg_name(codec).unwrap_or("libx264");
+        let mut args = vec!["-vcodec".to_string(), encoder.to_string()];
+        if let Some(preset) = self
+            .preset
+            .as_deref()
+            .or(self.encoder.default_preset(codec))
+        {
+            args.extend(["-preset".to_string(), preset.to_string()]);
+        }
+        if codec == VideoCodec::Hevc {
+            // The tag QuickTime and Safari play H.265 in MP4 with
+            args.extend(["-tag:v".to_string(), "hvc1".to_string()]);
+        }
+        if let Some(bitrate) = &self.bitrate {
+            args.extend(["-b:v".to_string(), bitrate.clone()]);
+            return args;
+        }
+        let crf = self.crf.unwrap_or(codec.default_crf()).to_string();
+        let quality = match self.encoder {
+            VideoEncoder::Software => ["-crf", crf.as_str()],
+            VideoEncoder::Nvenc => ["-cq", crf.as_str()],
+            VideoEncoder::Qsv => ["-global_quality", crf.as_str()],
+            // Its quality scale isn't the CRF one
+            VideoEncoder::Mpeg4 => ["-q:v", "5"],
+            // Has no constant quality mode on every Mac, it follows a bitrate
+            VideoEncoder::VideoToolbox => ["-b:v", "2M"],
+        };
+        args.extend(quality.map(str::to_string));
+        args
+    }
+
+    /// These settings, or the closest ffmpeg can encode with if it lacks the encoder or
+    /// codec selected, e.g. NVENC without an NVIDIA GPU driver: H.264 with the same encoder,
+    /// else H.264 with the default encoder
+    pub async fn or_available(self) -> Self {
+        let default = VideoEncoding {
+            encoder: VideoEncoder::platform_default(),
+            codec: VideoCodec::H264,
+            // Presets are named differently by every encoder
+            preset: None,
+            ..self.clone()
+        };
+        if self.encoder == default.encoder && self.codec == VideoCodec::H264 {
+            return self;
+        }
+        let h264 = VideoEncoding {
+            codec: VideoCodec::H264,
+            preset: None,
+            ..self.clone()
+        };
+        for candidate in [self.clone(), h264] {
+            let Some(encoder) = candidate.ffmpeg_encoder() else {
+                continue;
+            };
+            // The default encoder may still lack H.265 or AV1, e.g. libx264 alone
+            let assumed =
+                candidate.encoder == default.encoder && candidate.codec == VideoCodec::H264;
+            if assumed || encoder_available(encoder).await {
+                if candidate.codec != self.codec {
+                    warn!(
+                        "ffmpeg can't encode {} with {:?}, falling back to {}",
+                        self.codec.name(),
+                        self.encoder,
+                        encoder
+                    );
+                } else {
+                    info!("Encoding video chunks with {}", encoder);
+                }
+                return candidate;
+            }
+        }
+        warn!(
+            "ffmpeg can't encode with {:?}, falling back to {}",
+            self.encoder,
+            default.ffmpeg_encoder().unwrap_or("libx264")
+        );
+        default
+    }
+}
+
+/// Whether ffmpeg can encode with `encoder`. Listed hardware encoders can still lack their
+/// device, they are tried on a single frame.
+async fn encoder_available(encoder: &str) -> bool {
+    let Some(ffmpeg) = find_ffmpeg_path() else {
+        return false;
+    };
+    let output = tokio::process::Command::new(ffmpeg)
+        .args([
+            "-hide_banner",
+            "-f",
+            "lavfi",
+            "-i",
+            "color=black:size=256x256",
+            "-frames:v",
+            "1",
+            "-vcodec",
+            encoder,
+            "-f",
+            "null",
+            "-",
+        ])
+        .output()
+        .await;
+    matches!(output, Ok(output) if output.status.success())
+}
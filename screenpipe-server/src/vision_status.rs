@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Window over which the achieved FPS is measured
+const FPS_WINDOW: Duration = Duration::from_secs(60);
+
+/// Activity of the capture loops, updated for every OCR'd frame and read by `/vision/status`
+#[derive(Default)]
+pub struct VisionStatus {
+    monitors: Mutex<HashMap<String, MonitorActivity>>,
+}
+
+#[derive(Default)]
+struct MonitorActivity {
+    frames: VecDeque<Instant>,
+    last_frame_at: Option<DateTime<Utc>>,
+    last_ocr_latency: Option<Duration>,
+    app_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorStatus {
+    pub name: String,
+    /// OCR'd frames per second over the last minute, duplicates skipped don't count
+    pub fps: f64,
+    pub last_frame_at: Option<DateTime<Utc>>,
+    /// From the capture of the last frame to its OCR result
+    pub last_ocr_latency_ms: Option<u64>,
+    /// App in the foreground at the last frame
+    pub foreground_app: Option<String>,
+}
+
+impl VisionStatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called once a monitor's capture started, before any frame
+    pub fn add_monitor(&self, name: &str) {
+        self.monitors
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default();
+    }
+
+    /// `captured_at` is when the frame was captured, before OCR
+    pub fn record_frame(&self, monitor: &str, captured_at: Instant, app_name: &str) {
+        let now = Instant::now();
+        let mut monitors = self.monitors.lock().unwrap();
+        let activity = monitors.entry(monitor.to_string()).or_default();
+        activity.frames.push_back(now);
+        while activity
+            .frames
+            .front()
+            .map_or(false, |frame| now.duration_since(*frame) > FPS_WINDOW)
+        {
+            activity.frames.pop_front();
+        }
+        activity.last_frame_at = Some(Utc::now());
+        activity.last_ocr_latency = Some(now.saturating_duration_since(captured_at));
+        activity.app_name = Some(app_name.to_string());
+    }
+
+    /// Monitors by name
+    pub fn monitors(&self) -> Vec<MonitorStatus> {
+        let now = Instant::now();
+        let monitors = self.monitors.lock().unwrap();
+        let mut statuses: Vec<MonitorStatus> = monitors
+            .iter()
+            .map(|(name, activity)| {
+                let frames = activity
+                    .frames
+                    .iter()
+                    .filter(|frame| now.duration_since(**frame) <= FPS_WINDOW)
+                    .count();
+                MonitorStatus {
+                    name: name.clone(),
+                    fps: frames as f64 / FPS_WINDOW.as_secs_f64(),
+                    last_frame_at: activity.last_frame_at,
+                    last_ocr_latency_ms: activity
+                        .last_ocr_latency
+                        .map(|latency| latency.as_millis() as u64),
+                    foreground_app: activity.app_name.clone(),
+                }
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    /// Most recent frame of any monitor
+    pub fn last_frame_at(&self) -> Option<DateTime<Utc>> {
+        self.monitors
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|activity| activity.last_frame_at)
+            .max()
+    }
+
+    /// App in the foreground at the most recent frame of any monitor
+    pub fn foreground_app(&self) -> Option<String> {
+        self.monitors()
+            .into_iter()
+            .filter(|monitor| monitor.last_frame_at.is_some())
+            .max_by_key(|monitor| monitor.last_frame_at)
+            .and_then(|monitor| monitor.foreground_app)
+    }
+}
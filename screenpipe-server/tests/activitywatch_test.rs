@@ -0,0 +1,162 @@
+#[cfg(all(test, feature = "integrations"))]
+mod tests {
+    use axum::extract::Query;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use screenpipe_integrations::activitywatch::{
+        app_sessions, ActivityWatchBridge, ActivityWatchDatabase, ActivityWatchEvent, AppSession,
+        EVENTS_PER_REQUEST,
+    };
+    use screenpipe_server::DatabaseManager;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_app_sessions_merge_consecutive_frames() {
+        let start = Utc.with_ymd_and_hms(2024, 8, 1, 9, 0, 0).unwrap();
+        let at = |seconds: i64| start + Duration::seconds(seconds);
+        let timeline = vec![
+            (at(0), "Code".to_string()),
+            (at(1), "Code".to_string()),
+            (at(2), "".to_string()),
+            (at(3), "Firefox".to_string()),
+            (at(4), "Code".to_string()),
+            // Back after a break
+            (at(600), "Code".to_string()),
+        ];
+
+        let sessions = app_sessions(&timeline, Duration::seconds(60));
+        assert_eq!(
+            sessions,
+            vec![
+                AppSession {
+                    app_name: "Code".to_string(),
+                    start: at(0),
+                    end: at(1),
+                },
+                AppSession {
+                    app_name: "Firefox".to_string(),
+                    start: at(3),
+                    end: at(3),
+                },
+                AppSession {
+                    app_name: "Code".to_string(),
+                    start: at(4),
+                    end: at(4),
+                },
+                AppSession {
+                    app_name: "Code".to_string(),
+                    start: at(600),
+                    end: at(600),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pulled_events_replace_their_previous_version() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let timestamp = Utc.with_ymd_and_hms(2024, 8, 1, 9, 0, 0).unwrap();
+        let event = |duration: f64| ActivityWatchEvent {
+            bucket_id: "aw-watcher-afk_laptop".to_string(),
+            event_type: "afkstatus".to_string(),
+            timestamp,
+            duration,
+            data: json!({"status": "not-afk"}),
+        };
+
+        ActivityWatchDatabase::upsert_activity_events(&db, &[event(5.0)])
+            .await
+            .unwrap();
+        // The watcher's heartbeats extended the event since
+        ActivityWatchDatabase::upsert_activity_events(&db, &[event(65.0)])
+            .await
+            .unwrap();
+
+        let events = db
+            .get_activity_events(None, None, Some("afkstatus"), 100)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].duration, 65.0);
+        assert_eq!(events[0].data, json!({"status": "not-afk"}));
+        assert_eq!(
+            ActivityWatchDatabase::last_activity_event_time(&db, "aw-watcher-afk_laptop")
+                .await
+                .unwrap(),
+            Some(timestamp)
+        );
+        assert!(db
+            .get_activity_events(None, None, Some("currentwindow"), 100)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+    #[tokio::test]
+    async fn test_pull_pages_back_to_the_last_event() {
+        let start = Utc.with_ymd_and_hms(2024, 8, 1, 9, 0, 0).unwrap();
+        let timestamps: Arc<Vec<DateTime<Utc>>> = Arc::new(
+            (0..EVENTS_PER_REQUEST as i64 * 2 + 10)
+                .map(|i| start + Duration::seconds(i))
+                .collect(),
+        );
+        // Like ActivityWatch, newest first within start and end, up to limit
+        let events = move |Query(query): Query<HashMap<String, String>>| {
+            let timestamps = timestamps.clone();
+            async move {
+                let bound = |name: &str| {
+                    query
+                        .get(name)
+                        .map(|time| DateTime::parse_from_rfc3339(time).unwrap())
+                };
+                let limit: usize = query["limit"].parse().unwrap();
+                let (since, end) = (bound("start"), bound("end"));
+                let events: Vec<Value> = timestamps
+                    .iter()
+                    .rev()
+                    .filter(|timestamp| since.map_or(true, |since| **timestamp >= since))
+                    .filter(|timestamp| end.map_or(true, |end| **timestamp <= end))
+                    .take(limit)
+                    .map(|timestamp| {
+                        json!({
+                            "timestamp": timestamp.to_rfc3339(),
+                            "duration": 1.0,
+                            "data": {"status": "not-afk"},
+                        })
+                    })
+                    .collect();
+                Json(events)
+            }
+        };
+        let app = Router::new()
+            .route(
+                "/api/0/buckets/",
+                get(|| async { Json(json!({"aw-watcher-afk_laptop": {"type": "afkstatus"}})) }),
+            )
+            .route("/api/0/buckets/:bucket_id/events", get(events));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let mut bridge = ActivityWatchBridge::new(&url, "laptop", false);
+        // The events of the page boundaries are fetched twice
+        assert_eq!(bridge.sync(&db).await.unwrap(), EVENTS_PER_REQUEST * 2 + 12);
+        let stored = db
+            .get_activity_events(None, None, Some("afkstatus"), 100_000)
+            .await
+            .unwrap();
+        assert_eq!(stored.len(), EVENTS_PER_REQUEST * 2 + 10);
+        assert_eq!(
+            ActivityWatchDatabase::last_activity_event_time(&db, "aw-watcher-afk_laptop")
+                .await
+                .unwrap(),
+            Some(start + Duration::seconds(EVENTS_PER_REQUEST as i64 * 2 + 9))
+        );
+
+        // Only the last event, it may have grown
+        assert_eq!(bridge.sync(&db).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,203 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use chrono::{DateTime, Duration, Utc};
+    use screenpipe_server::{
+        versioned_routes, AnomalyDetector, AnomalyKind, AppState, DatabaseManager,
+        HealthCheckResponse, Subsystem,
+    };
+    use screenpipe_vision::OcrEngine;
+    use serde_json::Value;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+    use tower::ServiceExt;
+
+    /// URL of a webhook forwarding what it receives to the returned channel
+    async fn webhook() -> (String, mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(payload): Json<Value>| async move {
+                let _ = tx.send(payload);
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/hook", addr), rx)
+    }
+
+    /// Frames recorded at `at` where OCR found `text`
+    async fn record_frames(db: &DatabaseManager, at: DateTime<Utc>, count: usize, text: &str) {
+        for _ in 0..count {
+            let frame_id = db.insert_frame("code").await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "[]",
+                "[]",
+                "{}",
+                "code",
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+            sqlx::query("UPDATE frames SET timestamp = ?1 WHERE id = ?2")
+                .bind(at)
+                .bind(frame_id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn next(rx: &mut mpsc::UnboundedReceiver<Value>) -> Value {
+        tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_ingest_counts() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let now = Utc::now();
+        record_frames(&db, now - Duration::minutes(10), 3, "fn main").await;
+        record_frames(&db, now - Duration::minutes(20), 2, "").await;
+        record_frames(&db, now - Duration::hours(2), 4, "").await;
+        let chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(chunk_id, "hello", 0)
+            .await
+            .unwrap();
+        db.insert_audio_chunk("silence.mp4").await.unwrap();
+
+        let counts = db
+            .ingest_counts(now - Duration::hours(1), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(counts.frames, 5);
+        assert_eq!(counts.empty_frames, 2);
+        assert_eq!(counts.audio_chunks, 2);
+        assert_eq!(counts.silent_chunks, 1);
+    }
+
+    #[tokio::test]
+    async fn test_anomaly_detection() {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let (url, mut rx) = webhook().await;
+        let detector = AnomalyDetector::new(Some(url));
+        let now = Utc::now();
+        // OCR broke: frames keep coming, all without text
+        record_frames(&db, now - Duration::minutes(30), 40, "").await;
+
+        for day in 1..=2 {
+            let at = now - Duration::days(day) - Duration::minutes(30);
+            record_frames(&db, at, 40, "quarterly report").await;
+        }
+        // Two days aren't enough to know what is usual
+        assert!(detector.check(&db, now).await.unwrap().is_empty());
+
+        let at = now - Duration::days(3) - Duration::minutes(30);
+        record_frames(&db, at, 40, "quarterly report").await;
+        let anomalies = detector.check(&db, now).await.unwrap();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::EmptyOcr);
+        assert_eq!(anomalies[0].current, 1.0);
+        assert_eq!(anomalies[0].usual, 0.0);
+        assert_eq!(detector.active(), anomalies);
+        let started = next(&mut rx).await;
+        assert_eq!(started["event"], "anomaly_started");
+        assert_eq!(started["kind"], "empty_ocr");
+        assert!(started["text"]
+            .as_str()
+            .unwrap()
+            .contains("OCR found no text"));
+
+        // Degrades the health check, which is otherwise healthy
+        record_frames(&db, Utc::now(), 1, "quarterly report").await;
+        let chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(chunk_id, "hello", 0)
+            .await
+            .unwrap();
+        let app_state = Arc::new(AppState {
+            vision_control: Arc::new(AtomicBool::new(true)),
+            app_start_time: now - Duration::hours(1),
+            anomalies: detector.clone(),
+            ..AppState::new(db.clone())
+        });
+        let response = versioned_routes()
+            .with_state(app_state)
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let health: HealthCheckResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(health.status, "Degraded");
+        assert_eq!(health.frame_status, "OK");
+        assert_eq!(health.anomalies, anomalies);
+
+        // Still there at the next check, since the first one
+        let later = now + Duration::minutes(10);
+        let anomalies = detector.check(&db, later).await.unwrap();
+        assert_eq!(anomalies[0].since, now);
+
+        // OCR works again
+        record_frames(&db, later - Duration::minutes(5), 200, "quarterly report").await;
+        assert!(detector.check(&db, later).await.unwrap().is_empty());
+        assert!(detector.active().is_empty());
+        let ended = next(&mut rx).await;
+        assert_eq!(ended["event"], "anomaly_ended");
+        assert_eq!(ended["kind"], "empty_ocr");
+    }
+
+    #[tokio::test]
+    async fn test_anomalies_of_recording_subsystems() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let detector = AnomalyDetector::new(None);
+        let now = Utc::now();
+        record_frames(&db, now - Duration::minutes(30), 40, "").await;
+        for day in 1..=3 {
+            let at = now - Duration::days(day) - Duration::minutes(30);
+            record_frames(&db, at, 40, "quarterly report").await;
+        }
+
+        // Audio alone recorded all along, the screen isn't checked
+        let anomalies = detector
+            .check_subsystems(&db, now, &[Subsystem::Audio])
+            .await
+            .unwrap();
+        assert!(anomalies.is_empty());
+
+        let anomalies = detector
+            .check_subsystems(&db, now, &[Subsystem::Vision])
+            .await
+            .unwrap();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind.subsystem(), Subsystem::Vision);
+
+        // The screen stopped, its anomaly is forgotten
+        detector
+            .check_subsystems(&db, now, &[Subsystem::Audio])
+            .await
+            .unwrap();
+        assert!(detector.active().is_empty());
+    }
+}
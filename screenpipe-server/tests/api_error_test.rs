@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{header, Request, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::{get, post};
+    use axum::Router;
+    use screenpipe_server::{ApiError, ApiJson, ApiPath, ApiQuery, ErrorCode};
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    #[derive(Deserialize)]
+    struct Page {
+        limit: u32,
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/items",
+                get(|ApiQuery(page): ApiQuery<Page>| async move { page.limit.to_string() })
+                    .post(|ApiJson(page): ApiJson<Page>| async move { page.limit.to_string() }),
+            )
+            .route(
+                "/items/:id",
+                get(|ApiPath(id): ApiPath<i64>| async move { id.to_string() }),
+            )
+            .route(
+                "/missing",
+                post(|| async { ApiError::new(ErrorCode::NotFound, "No item 3") }),
+            )
+    }
+
+    async fn send(request: Request<Body>) -> (StatusCode, Value) {
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[test]
+    fn test_error_code_status() {
+        for (code, status) in [
+            (ErrorCode::InvalidRequest, StatusCode::BAD_REQUEST),
+            (ErrorCode::InvalidTime, StatusCode::BAD_REQUEST),
+            (ErrorCode::InvalidContentType, StatusCode::BAD_REQUEST),
+            (ErrorCode::InvalidSearchQuery, StatusCode::BAD_REQUEST),
+            (ErrorCode::NotFound, StatusCode::NOT_FOUND),
+            (ErrorCode::Conflict, StatusCode::CONFLICT),
+            (ErrorCode::PayloadTooLarge, StatusCode::PAYLOAD_TOO_LARGE),
+            (ErrorCode::UriTooLong, StatusCode::URI_TOO_LONG),
+            (ErrorCode::RateLimited, StatusCode::TOO_MANY_REQUESTS),
+            (ErrorCode::UpstreamFailed, StatusCode::BAD_GATEWAY),
+            (ErrorCode::Timeout, StatusCode::GATEWAY_TIMEOUT),
+            (ErrorCode::Internal, StatusCode::INTERNAL_SERVER_ERROR),
+        ] {
+            assert_eq!(code.status(), status, "{:?}", code);
+        }
+        assert_eq!(
+            serde_json::to_value(ErrorCode::InvalidContentType).unwrap(),
+            "invalid_content_type"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_body() {
+        let error = ApiError::new(ErrorCode::NotFound, "No item 3");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({"code": "not_found", "error": "No item 3"})
+        );
+        let error = error.with_details(json!({"id": 3}));
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({"code": "not_found", "error": "No item 3", "details": {"id": 3}})
+        );
+
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: ApiError = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, ErrorCode::NotFound);
+        assert_eq!(error.details, Some(json!({"id": 3})));
+
+        let (status, body) = send(Request::post("/missing").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn test_rejections() {
+        for uri in ["/items?limit=ten", "/items", "/items/abc"] {
+            let (status, body) = send(Request::get(uri).body(Body::empty()).unwrap()).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+            assert_eq!(body["code"], "invalid_request", "{}", uri);
+            assert!(!body["error"].as_str().unwrap().is_empty(), "{}", uri);
+        }
+
+        let (status, body) = send(
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from("{\"limit\": -1}"))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+
+        // Without a JSON content type
+        let (status, body) = send(
+            Request::post("/items")
+                .body(Body::from("{\"limit\": 1}"))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+    }
+}
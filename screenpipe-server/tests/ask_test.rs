@@ -0,0 +1,167 @@
+#[cfg(test)]
+mod tests {
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use chrono::Utc;
+    use screenpipe_server::{
+        build_prompt, question_words, retrieve_sources, AskSource, ContentType, DatabaseManager,
+        OllamaClient,
+    };
+    use screenpipe_vision::OcrEngine;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    /// URL of an Ollama server answering `answer`, forwarding the requests to the returned
+    /// channel
+    async fn ollama(answer: &'static str) -> (String, mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/api/generate",
+            post(move |Json(request): Json<Value>| async move {
+                let _ = tx.send(request);
+                Json(json!({ "response": answer, "done": true }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/", addr), rx)
+    }
+
+    #[test]
+    fn test_question_words() {
+        assert_eq!(
+            question_words("When is the budget meeting? Budget for Q3"),
+            vec!["budget", "meeting", "q3"]
+        );
+        assert!(question_words("what was it?").is_empty());
+    }
+
+    #[test]
+    fn test_ollama_stays_local() {
+        for url in [
+            "http://localhost:11434",
+            "http://127.0.0.1:11434/",
+            "http://[::1]:11434",
+        ] {
+            assert!(OllamaClient::new(url, "llama3.2").is_ok(), "{}", url);
+        }
+        for url in [
+            "https://ollama.example.com",
+            "http://192.168.1.20:11434",
+            "ftp://localhost",
+            "localhost:11434",
+        ] {
+            assert!(OllamaClient::new(url, "llama3.2").is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        let source = AskSource {
+            id: 1,
+            content_type: ContentType::Audio,
+            frame_id: None,
+            audio_chunk_id: Some(3),
+            timestamp: Utc::now(),
+            source: "audio".to_string(),
+            text: "the budget meeting is on Friday".to_string(),
+        };
+        let prompt = build_prompt(" When is the budget meeting? ", &[source]);
+        assert!(prompt.contains("(audio) the budget meeting is on Friday\n"));
+        assert!(prompt.contains("[1] "));
+        assert!(prompt.ends_with("Question: When is the budget meeting?\nAnswer:"));
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_sources() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for (app_name, text) in [
+            ("calendar", "Budget meeting moved to Friday"),
+            ("calendar", "Budget meeting moved to Friday"),
+            ("excel", "budget.xlsx"),
+            ("slack", "lunch on Friday?"),
+        ] {
+            let frame_id = db.insert_frame(app_name).await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                "",
+                "",
+                app_name,
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+        let chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(chunk_id, "so the budget meeting is at 3", 0, "whisper")
+            .await
+            .unwrap();
+        db.flush_writes().await;
+
+        let sources = retrieve_sources(&db, "When is the budget meeting?", 10, None, None)
+            .await
+            .unwrap();
+        // Both words first, the repeated screen once, lunch doesn't match
+        let mut best: Vec<&str> = sources[..2]
+            .iter()
+            .map(|source| source.source.as_str())
+            .collect();
+        best.sort();
+        assert_eq!(best, vec!["audio", "calendar"]);
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[2].text, "budget.xlsx");
+        assert_eq!(
+            sources.iter().map(|source| source.id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        let audio = sources
+            .iter()
+            .find(|source| source.content_type == ContentType::Audio)
+            .unwrap();
+        assert_eq!(audio.audio_chunk_id, Some(chunk_id));
+        assert_eq!(audio.frame_id, None);
+
+        let top = retrieve_sources(&db, "budget meeting", 1, None, None)
+            .await
+            .unwrap();
+        assert_eq!(top.len(), 1);
+        assert!(retrieve_sources(&db, "what was it?", 10, None, None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(retrieve_sources(&db, "budget", 10, Some(Utc::now()), None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_generate() {
+        let (url, mut requests) = ollama("  On Friday [1].\n").await;
+        let client = OllamaClient::new(&url, "llama3.2").unwrap();
+        assert_eq!(client.model(), "llama3.2");
+
+        let answer = client.generate("Question: when?", None).await.unwrap();
+        assert_eq!(answer, "On Friday [1].");
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request["model"], "llama3.2");
+        assert_eq!(request["prompt"], "Question: when?");
+        assert_eq!(request["stream"], false);
+
+        client
+            .generate("Question: when?", Some("mistral"))
+            .await
+            .unwrap();
+        assert_eq!(requests.recv().await.unwrap()["model"], "mistral");
+
+        let unreachable = OllamaClient::new("http://127.0.0.1:1", "llama3.2").unwrap();
+        assert!(unreachable.generate("Question: when?", None).await.is_err());
+    }
+}
@@ -0,0 +1,159 @@
+#[cfg(all(test, feature = "audio"))]
+mod tests {
+    use chrono::Utc;
+    use screenpipe_audio::{AudioInput, TranscriptionPriority, TranscriptionResult};
+    use screenpipe_server::{AudioRecord, DatabaseManager, TranscriptionBackfill};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::{mpsc, watch};
+
+    async fn setup() -> (Arc<DatabaseManager>, TranscriptionBackfill) {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        db.write_audio(AudioRecord {
+            timestamp: Utc::now(),
+            file_path: "failed.mp4".to_string(),
+            duration: None,
+            transcription: String::new(),
+            transcription_engine: "Whisper".to_string(),
+            language: None,
+            segments: Vec::new(),
+            entities: Vec::new(),
+            format: None,
+            transcription_error: Some("model failed to load".to_string()),
+        })
+        .await
+        .unwrap();
+        db.flush_writes().await;
+        let backfill = TranscriptionBackfill::new(db.clone());
+        (db, backfill)
+    }
+
+    /// Sends the next batch, returns the chunk sent
+    async fn send(backfill: &TranscriptionBackfill) -> AudioInput {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let configs = watch::channel(HashMap::new()).1;
+        assert!(backfill.send_batch(&sender, &configs).await);
+        let input = receiver.try_recv().unwrap();
+        assert!(receiver.try_recv().is_err());
+        input
+    }
+
+    fn result(input: AudioInput, transcription: Result<&str, &str>) -> TranscriptionResult {
+        TranscriptionResult {
+            input,
+            transcription: transcription.ok().map(str::to_string),
+            timestamp: 0,
+            error: transcription.err().map(str::to_string),
+            duration: 12.5,
+            language: None,
+            engine: "Whisper".to_string(),
+            segments: Vec::new(),
+        }
+    }
+
+    async fn transcription_error(db: &DatabaseManager) -> Option<String> {
+        sqlx::query_scalar("SELECT transcription_error FROM audio_chunks")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_backfill_complete() {
+        let (db, backfill) = setup().await;
+
+        let input = send(&backfill).await;
+        assert_eq!(input.path, "failed.mp4");
+        assert_eq!(input.priority, TranscriptionPriority::Background);
+        assert_eq!(backfill.in_flight(), 1);
+
+        // Failed again, sent in the next batch
+        assert!(backfill
+            .complete(&*db, result(input, Err("decoding failed")))
+            .await
+            .is_none());
+        assert_eq!(backfill.in_flight(), 0);
+        assert_eq!(
+            transcription_error(&db).await.as_deref(),
+            Some("decoding failed")
+        );
+
+        let input = send(&backfill).await;
+        assert!(backfill
+            .complete(&*db, result(input, Ok("hello again")))
+            .await
+            .is_none());
+        db.flush_writes().await;
+        assert_eq!(transcription_error(&db).await, None);
+        assert!(db.chunks_to_retranscribe(3, 10).await.unwrap().is_empty());
+
+        // Results of the recording are given back
+        let input = AudioInput {
+            path: "recorded.mp4".to_string(),
+            device: "microphone".to_string(),
+            priority: TranscriptionPriority::Background,
+            preprocessing: Default::default(),
+        };
+        assert!(backfill
+            .complete(&*db, result(input, Ok("live")))
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_backfill_requested_first() {
+        let (db, backfill) = setup().await;
+        db.write_audio(AudioRecord {
+            timestamp: Utc::now(),
+            file_path: "recorded.mp4".to_string(),
+            duration: None,
+            transcription: "hello".to_string(),
+            transcription_engine: "Whisper".to_string(),
+            language: None,
+            segments: Vec::new(),
+            entities: Vec::new(),
+            format: None,
+            transcription_error: None,
+        })
+        .await
+        .unwrap();
+        db.flush_writes().await;
+        assert_eq!(db.queue_retranscription(None, true).await.unwrap(), 2);
+
+        // Both asked for, ahead of the recording
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let configs = watch::channel(HashMap::new()).1;
+        assert!(backfill.send_batch(&sender, &configs).await);
+        for _ in 0..2 {
+            let input = receiver.try_recv().unwrap();
+            assert_eq!(input.priority, TranscriptionPriority::OnDemand);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backfill_expire() {
+        let (db, backfill) = setup().await;
+
+        let input = send(&backfill).await;
+        assert_eq!(backfill.expire(Duration::from_secs(60)).await, 0);
+        assert_eq!(backfill.in_flight(), 1);
+
+        // The result was lost, the next batch doesn't wait for it
+        assert_eq!(backfill.expire(Duration::ZERO).await, 1);
+        assert_eq!(backfill.in_flight(), 0);
+        assert_eq!(backfill.expire(Duration::ZERO).await, 0);
+        assert_eq!(
+            transcription_error(&db).await.as_deref(),
+            Some("transcription timed out")
+        );
+
+        // Stored if it comes after all
+        assert!(backfill
+            .complete(&*db, result(input, Ok("late")))
+            .await
+            .is_none());
+        db.flush_writes().await;
+        assert_eq!(transcription_error(&db).await, None);
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_server::{
+        backup, restore_backup, BackupLocation, DatabaseManager, DatabaseSettings,
+    };
+    use std::path::Path;
+
+    async fn setup_recordings(data_dir: &Path) -> DatabaseManager {
+        let chunk_dir = data_dir.join("data");
+        std::fs::create_dir_all(&chunk_dir).unwrap();
+        let db = DatabaseManager::new(&data_dir.join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        let video = chunk_dir.join("2024-08-24_10-00-00.mp4");
+        std::fs::write(&video, b"video").unwrap();
+        db.insert_video_chunk_without_codec(&video.to_string_lossy())
+            .await
+            .unwrap();
+        let audio = chunk_dir.join("mic_2024-08-24_10-00-00.mp4");
+        std::fs::write(&audio, b"audio").unwrap();
+        db.insert_audio_chunk(&audio.to_string_lossy())
+            .await
+            .unwrap();
+        // Evicted, the backup goes on without it
+        db.insert_audio_chunk(&chunk_dir.join("gone.mp4").to_string_lossy())
+            .await
+            .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = setup_recordings(&dir.path().join("original")).await;
+        let backup_dir = dir.path().join("backup");
+        let location = BackupLocation::Directory(backup_dir.clone());
+
+        let summary = backup(&db, &location).await.unwrap();
+        assert_eq!(summary.media_files, 2);
+        assert_eq!(summary.missing_files.len(), 1);
+        assert!(backup_dir.join("manifest.json").exists());
+
+        let restored_dir = dir.path().join("restored");
+        let restored = restore_backup(&location, &restored_dir, &DatabaseSettings::default())
+            .await
+            .unwrap();
+        assert_eq!(restored.media_files, 2);
+        // Never over an existing database
+        assert!(
+            restore_backup(&location, &restored_dir, &DatabaseSettings::default())
+                .await
+                .is_err()
+        );
+
+        let restored_db = DatabaseManager::new(&restored_dir.join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        let video_chunks = restored_db.list_video_chunks().await.unwrap();
+        assert_eq!(video_chunks.len(), 1);
+        assert!(video_chunks[0]
+            .1
+            .starts_with(&*restored_dir.to_string_lossy()));
+        assert_eq!(std::fs::read(&video_chunks[0].1).unwrap(), b"video");
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_corrupt_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = setup_recordings(&dir.path().join("original")).await;
+        let backup_dir = dir.path().join("backup");
+        let location = BackupLocation::Directory(backup_dir.clone());
+        backup(&db, &location).await.unwrap();
+
+        std::fs::write(
+            backup_dir.join("data").join("2024-08-24_10-00-00.mp4"),
+            b"bit rot",
+        )
+        .unwrap();
+        let restored_dir = dir.path().join("restored");
+        let error = restore_backup(&location, &restored_dir, &DatabaseSettings::default())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("corrupt"));
+        assert!(!restored_dir.join("db.sqlite").exists());
+
+        // Incomplete without its manifest
+        std::fs::remove_file(backup_dir.join("manifest.json")).unwrap();
+        assert!(
+            restore_backup(&location, &restored_dir, &DatabaseSettings::default())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_server::{ContentType, DatabaseManager, SearchResult};
+    use screenpipe_vision::OcrEngine;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_queued_writes() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("first.mp4")
+            .await
+            .unwrap();
+        // More than one batch
+        for i in 0..250 {
+            db.queue_frame_with_ocr_text(
+                "app",
+                &format!("frame {}", i),
+                "",
+                "",
+                "",
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await;
+        }
+        let audio_chunk_id = db.insert_audio_chunk("audio.mp4").await.unwrap();
+        db.queue_audio_transcription(audio_chunk_id, "hello from audio", 0, "Whisper")
+            .await;
+        db.flush_writes().await;
+
+        let count = db
+            .count_search_results("", ContentType::OCR, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(count, 250);
+        let results = db
+            .search(
+                "audio",
+                ContentType::Audio,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+
+        // Frames queued before a new chunk stay in the previous one
+        db.queue_frame_with_ocr_text(
+            "app",
+            "last frame",
+            "",
+            "",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await;
+        db.insert_video_chunk_without_codec("second.mp4")
+            .await
+            .unwrap();
+        let results = db
+            .search(
+                "\"last frame\"",
+                ContentType::OCR,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        match &results[..] {
+            [SearchResult::OCR(ocr)] => {
+                assert_eq!(ocr.file_path, "first.mp4");
+                assert_eq!(ocr.offset_index, 250);
+            }
+            _ => panic!("Expected one OCR result"),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use screenpipe_server::{ContentType, DatabaseManager, SearchResult};
+
+    async fn setup_test_db() -> DatabaseManager {
+        DatabaseManager::new("sqlite::memory:").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_search_clipboard() {
+        let db = setup_test_db().await;
+        let now = Utc::now();
+        db.insert_clipboard("invoice 2024-117 for acme", "firefox", now)
+            .await
+            .unwrap();
+        db.insert_clipboard("git push origin main", "terminal", now)
+            .await
+            .unwrap();
+
+        let results = db
+            .search(
+                "invoice",
+                ContentType::Clipboard,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        match &results[..] {
+            [SearchResult::Clipboard(clipboard)] => {
+                assert_eq!(clipboard.text, "invoice 2024-117 for acme");
+                assert_eq!(clipboard.app_name, "firefox");
+                assert_eq!(clipboard.snippet, "<mark>invoice</mark> 2024-117 for acme");
+                let highlight = &clipboard.highlights[0];
+                assert_eq!(&clipboard.text[highlight.start..highlight.end], "invoice");
+            }
+            _ => panic!("Expected one clipboard result, got {:?}", results),
+        }
+
+        // Without query, every entry
+        let results = db
+            .search(
+                "",
+                ContentType::Clipboard,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        // Outside the time range
+        let results = db
+            .search(
+                "invoice",
+                ContentType::Clipboard,
+                10,
+                0,
+                Some(now + Duration::minutes(1)),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_clipboard_in_all_results() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("").await.unwrap();
+        db.insert_ocr_text(frame_id, "quarterly report on screen", "", "", "", "")
+            .await
+            .unwrap();
+        db.insert_clipboard("quarterly report link", "slack", Utc::now())
+            .await
+            .unwrap();
+
+        let results = db
+            .search(
+                "quarterly",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .any(|result| matches!(result, SearchResult::Clipboard(_))));
+        assert_eq!(
+            db.count_search_results(
+                "quarterly",
+                ContentType::All,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None
+            )
+            .await
+            .unwrap(),
+            2
+        );
+        assert_eq!(
+            db.count_search_results(
+                "quarterly",
+                ContentType::Clipboard,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap(),
+            1
+        );
+
+        // The app filter only applies to OCR results
+        let results = db
+            .search(
+                "quarterly",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                Some("slack"),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_clipboard_search_pages() {
+        let db = setup_test_db().await;
+        let start = Utc::now();
+        for i in 0..5 {
+            db.insert_clipboard(
+                &format!("copied snippet {}", i),
+                "editor",
+                start + Duration::seconds(i),
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut texts = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (results, next) = db
+                .search_page(
+                    "snippet",
+                    ContentType::Clipboard,
+                    2,
+                    cursor.as_ref(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            for result in results {
+                match result {
+                    SearchResult::Clipboard(clipboard) => texts.push(clipboard.text),
+                    _ => panic!("Expected clipboard result"),
+                }
+            }
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        // Most recent first, each entry once
+        assert_eq!(
+            texts,
+            (0..5)
+                .rev()
+                .map(|i| format!("copied snippet {}", i))
+                .collect::<Vec<_>>()
+        );
+    }
+}
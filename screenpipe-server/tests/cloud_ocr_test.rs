@@ -0,0 +1,263 @@
+#[cfg(all(test, feature = "cloud"))]
+mod tests {
+    use axum::http::{HeaderMap, StatusCode, Uri};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use image::{DynamicImage, RgbImage};
+    use screenpipe_vision::{perform_ocr_cloud, CloudOcrConfig, CloudOcrProvider};
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// URL of a server answering `app`
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/", addr)
+    }
+
+    fn config(provider: CloudOcrProvider, endpoint: &str) -> CloudOcrConfig {
+        CloudOcrConfig {
+            provider,
+            api_key: Some("secret".to_string()),
+            endpoint: Some(endpoint.to_string()),
+            timeout_secs: Some(5),
+            max_attempts: Some(1),
+        }
+    }
+
+    fn image() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::new(64, 32))
+    }
+
+    #[test]
+    fn test_cloud_ocr_config() {
+        assert_eq!(
+            "Google".parse::<CloudOcrProvider>(),
+            Ok(CloudOcrProvider::Google)
+        );
+        assert!("tesseract".parse::<CloudOcrProvider>().is_err());
+        assert_eq!(
+            CloudOcrConfig::default().provider,
+            CloudOcrProvider::Unstructured
+        );
+
+        let provider = config(CloudOcrProvider::Google, "http://localhost")
+            .provider()
+            .unwrap();
+        // Stored as the OCR engine of the frames, without the key
+        assert_eq!(format!("{:?}", provider), "GoogleVision");
+
+        let azure = CloudOcrConfig {
+            endpoint: None,
+            ..config(CloudOcrProvider::Azure, "")
+        };
+        assert!(azure.provider().is_err());
+        let no_timeout = CloudOcrConfig {
+            timeout_secs: Some(0),
+            ..config(CloudOcrProvider::Unstructured, "http://localhost")
+        };
+        assert!(no_timeout.provider().is_err());
+
+        std::env::remove_var(CloudOcrProvider::Google.api_key_env());
+        let no_key = CloudOcrConfig {
+            api_key: None,
+            ..config(CloudOcrProvider::Google, "http://localhost")
+        };
+        let error = no_key.provider().unwrap_err().to_string();
+        assert!(error.contains("GOOGLE_VISION_API_KEY"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_unstructured() {
+        let url = serve(Router::new().route(
+            "/general/v0/general",
+            post(|headers: HeaderMap| async move {
+                if headers["unstructured-api-key"] != "secret" {
+                    return Err(StatusCode::UNAUTHORIZED);
+                }
+                Ok(Json(json!([
+                    {
+                        "type": "Title",
+                        "text": "Quarterly report",
+                        "metadata": { "coordinates": {
+                            "points": [[10.0, 5.0], [10.0, 15.0], [50.0, 15.0], [50.0, 5.0]]
+                        } }
+                    },
+                    { "type": "Image", "text": " ", "metadata": {} },
+                    { "type": "NarrativeText", "text": "Revenue grew", "metadata": {} }
+                ])))
+            }),
+        ))
+        .await;
+        let provider = config(
+            CloudOcrProvider::Unstructured,
+            &format!("{}general/v0/general", url),
+        )
+        .provider()
+        .unwrap();
+
+        let (text, data, json_output) = perform_ocr_cloud(provider.as_ref(), &image())
+            .await
+            .unwrap();
+        assert_eq!(text, "Quarterly report\nRevenue grew");
+        assert_eq!(data.data.len(), 2);
+        let title = &data.data[0];
+        assert_eq!(
+            (title.left, title.top, title.width, title.height),
+            (10, 5, 40, 10)
+        );
+        // No confidence reported, kept as text but not as a confident word
+        assert_eq!(title.conf, -1.0);
+        let lines: Vec<Value> = serde_json::from_str(&json_output).unwrap();
+        assert_eq!(lines[1]["text"], "Revenue grew");
+        assert_eq!(lines[1]["confidence"], "100.00");
+
+        let wrong_key = CloudOcrConfig {
+            api_key: Some("wrong".to_string()),
+            ..config(
+                CloudOcrProvider::Unstructured,
+                &format!("{}general/v0/general", url),
+            )
+        };
+        let error = perform_ocr_cloud(wrong_key.provider().unwrap().as_ref(), &image())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("401"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_google_vision() {
+        let word = |text: &str, x: i32, confidence: f64, detected_break: &str| {
+            json!({
+                "boundingBox": { "vertices": [
+                    { "x": x, "y": 2 }, { "x": x + 20, "y": 2 },
+                    { "x": x + 20, "y": 12 }, { "x": x, "y": 12 }
+                ] },
+                "confidence": confidence,
+                "symbols": text.chars().enumerate().map(|(i, c)| {
+                    if i + 1 == text.len() {
+                        json!({ "text": c.to_string(),
+                                "property": { "detectedBreak": { "type": detected_break } } })
+                    } else {
+                        json!({ "text": c.to_string() })
+                    }
+                }).collect::<Vec<_>>()
+            })
+        };
+        let response = json!({ "responses": [{ "fullTextAnnotation": { "pages": [{ "blocks": [{
+            "paragraphs": [{ "words": [
+                word("Hello", 0, 0.98, "SPACE"),
+                word("world", 30, 0.9, "EOL_SURE_SPACE"),
+                word("Bye", 0, 0.5, "LINE_BREAK"),
+            ] }]
+        }] }] } }] });
+        // The paths of Google and Azure have colons, taken by axum as captures
+        let url = serve(Router::new().fallback(
+            move |uri: Uri, Json(request): Json<Value>| async move {
+                assert_eq!(uri.path(), "/v1/images:annotate");
+                assert_eq!(uri.query(), Some("key=secret"));
+                assert_eq!(
+                    request["requests"][0]["features"][0]["type"],
+                    "DOCUMENT_TEXT_DETECTION"
+                );
+                assert!(request["requests"][0]["image"]["content"].is_string());
+                Json(response)
+            },
+        ))
+        .await;
+        let provider = config(CloudOcrProvider::Google, &format!("{}v1", url))
+            .provider()
+            .unwrap();
+
+        let (text, data, json_output) = perform_ocr_cloud(provider.as_ref(), &image())
+            .await
+            .unwrap();
+        assert_eq!(text, "Hello world\nBye");
+        assert_eq!(data.data.len(), 3);
+        assert_eq!(data.data[1].text, "world");
+        assert_eq!((data.data[1].left, data.data[1].width), (30, 20));
+        assert_eq!(data.data[1].line_num, 1);
+        assert_eq!(data.data[2].line_num, 2);
+        assert!((data.data[0].conf - 98.0).abs() < 0.01);
+        let lines: Vec<Value> = serde_json::from_str(&json_output).unwrap();
+        assert_eq!(lines[0]["confidence"], "94.00");
+        assert_eq!(lines[1]["confidence"], "50.00");
+    }
+
+    #[tokio::test]
+    async fn test_google_vision_error() {
+        let url = serve(Router::new().fallback(|| async {
+            Json(json!({
+                "responses": [{ "error": { "code": 3, "message": "Bad image data." } }]
+            }))
+        }))
+        .await;
+        let provider = config(CloudOcrProvider::Google, &url).provider().unwrap();
+        let error = perform_ocr_cloud(provider.as_ref(), &image())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Bad image data."), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_azure_vision_retries() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let url = serve(
+            Router::new().fallback(move |uri: Uri, headers: HeaderMap| async move {
+                assert_eq!(uri.path(), "/computervision/imageanalysis:analyze");
+                assert_eq!(headers["ocp-apim-subscription-key"], "secret");
+                // The first attempt fails
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Err(StatusCode::SERVICE_UNAVAILABLE);
+                }
+                Ok(Json(json!({ "readResult": { "blocks": [{ "lines": [{
+                    "text": "Sign in",
+                    "boundingPolygon": [],
+                    "words": [
+                        { "text": "Sign", "confidence": 0.99, "boundingPolygon": [
+                            { "x": 4, "y": 4 }, { "x": 24, "y": 4 },
+                            { "x": 24, "y": 14 }, { "x": 4, "y": 14 }
+                        ] },
+                        { "text": "in", "confidence": 0.97, "boundingPolygon": [] }
+                    ]
+                }] }] } })))
+            }),
+        )
+        .await;
+        let retried = CloudOcrConfig {
+            max_attempts: Some(2),
+            ..config(CloudOcrProvider::Azure, &url)
+        };
+        let provider = retried.provider().unwrap();
+
+        let (text, data, json_output) = perform_ocr_cloud(provider.as_ref(), &image())
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(text, "Sign in");
+        assert_eq!(
+            (
+                data.data[0].left,
+                data.data[0].top,
+                data.data[0].width,
+                data.data[0].height
+            ),
+            (4, 4, 20, 10)
+        );
+        let lines: Vec<Value> = serde_json::from_str(&json_output).unwrap();
+        assert_eq!(lines[0]["confidence"], "98.00");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_service_fails() {
+        let provider = config(CloudOcrProvider::Unstructured, "http://127.0.0.1:1")
+            .provider()
+            .unwrap();
+        assert!(perform_ocr_cloud(provider.as_ref(), &image())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use screenpipe_server::{CollectionFilters, ContentType, DatabaseManager, NewCollection};
+    use screenpipe_vision::OcrEngine;
+    use std::sync::Arc;
+
+    fn invoices(pinned: bool) -> NewCollection {
+        NewCollection {
+            name: "invoices".to_string(),
+            filters: CollectionFilters {
+                q: Some("invoice".to_string()),
+                ..Default::default()
+            },
+            pinned,
+        }
+    }
+
+    async fn insert_frame(db: &DatabaseManager, text: &str) -> i64 {
+        let frame_id = db.insert_frame("Mail").await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            text,
+            "",
+            "",
+            "",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+        frame_id
+    }
+
+    #[test]
+    fn test_validate_collection() {
+        assert!(invoices(false).validate().is_ok());
+
+        let mut collection = invoices(false);
+        collection.name = " ".to_string();
+        assert!(collection.validate().is_err());
+
+        let mut collection = invoices(false);
+        collection.filters.q = Some("\"invoice".to_string());
+        assert!(collection.validate().is_err());
+
+        let mut collection = invoices(false);
+        collection.filters.min_confidence = Some(120.0);
+        assert!(collection.validate().is_err());
+
+        let mut collection = invoices(false);
+        collection.filters.content_type = vec![ContentType::All, ContentType::OCR];
+        assert!(collection.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_collection_crud() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+
+        let collection = db.insert_collection(&invoices(false)).await.unwrap();
+        assert_eq!(collection.name, "invoices");
+        assert!(collection.tagged_until.is_none());
+        assert_eq!(
+            db.get_collection(collection.id).await.unwrap(),
+            Some(collection.clone())
+        );
+
+        let mut update = invoices(true);
+        update.name = "receipts".to_string();
+        update.filters.content_type = vec![ContentType::OCR, ContentType::Clipboard];
+        let updated = db
+            .update_collection(collection.id, &update)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.name, "receipts");
+        assert_eq!(updated.filters, update.filters);
+        assert!(updated.pinned);
+        assert!(updated.tagged_until.is_some());
+        assert_eq!(db.list_collections().await.unwrap(), vec![updated]);
+        assert!(db.update_collection(42, &update).await.unwrap().is_none());
+
+        let unpinned = db
+            .update_collection(collection.id, &invoices(false))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(unpinned.tagged_until.is_none());
+
+        assert!(db.delete_collection(collection.id).await.unwrap());
+        assert!(!db.delete_collection(collection.id).await.unwrap());
+        assert!(db.list_collections().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tag_pinned_collections() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let pinned = db.insert_collection(&invoices(true)).await.unwrap();
+        let unpinned = db.insert_collection(&invoices(false)).await.unwrap();
+
+        let frame_id = insert_frame(&db, "Invoice 42 from ACME").await;
+        insert_frame(&db, "quarterly report").await;
+        let clipboard_id = db
+            .insert_clipboard("invoice total: 300", "Mail", Utc::now())
+            .await
+            .unwrap();
+        // Recorded long before the collection was pinned
+        db.insert_clipboard("old invoice", "Mail", Utc::now() - Duration::hours(2))
+            .await
+            .unwrap();
+
+        assert_eq!(db.tag_pinned_collections().await.unwrap(), 2);
+        assert_eq!(db.tag_pinned_collections().await.unwrap(), 0);
+
+        let items = db.get_collection_items(pinned.id, 10, 0).await.unwrap();
+        let mut tagged: Vec<_> = items
+            .iter()
+            .map(|item| (item.content_type, item.content_id, item.text.as_deref()))
+            .collect();
+        tagged.sort_by_key(|(content_type, _, _)| content_type.name());
+        assert_eq!(
+            tagged,
+            vec![
+                (
+                    ContentType::Clipboard,
+                    clipboard_id,
+                    Some("invoice total: 300")
+                ),
+                (ContentType::OCR, frame_id, Some("Invoice 42 from ACME")),
+            ]
+        );
+        assert!(items
+            .iter()
+            .all(|item| item.app_name.as_deref() == Some("Mail")));
+        assert!(db
+            .get_collection_items(unpinned.id, 10, 0)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(db.delete_collection(pinned.id).await.unwrap());
+        assert!(db
+            .get_collection_items(pinned.id, 10, 0)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
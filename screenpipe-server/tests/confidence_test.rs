@@ -0,0 +1,204 @@
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use rusty_tesseract::{Data, DataOutput};
+    use screenpipe_server::{
+        mean_confidence, ocr_words, ContentType, DataOutputWrapper, DatabaseManager, FrameRecord,
+        OcrWord, SearchResult, Storage,
+    };
+    #[cfg(feature = "recording")]
+    use screenpipe_server::{ConfidenceFloor, ContentProcessor};
+
+    fn word(text: &str, confidence: f64) -> OcrWord {
+        OcrWord {
+            text: text.to_string(),
+            confidence: Some(confidence),
+            left: 10,
+            top: 10,
+            width: 80,
+            height: 20,
+        }
+    }
+
+    fn frame(text: &str, words: Vec<OcrWord>) -> FrameRecord {
+        FrameRecord {
+            timestamp: Utc::now(),
+            app_name: "firefox".to_string(),
+            text: text.to_string(),
+            raw_text: None,
+            text_json: "[]".to_string(),
+            new_text_json_vs_previous_frame: "[]".to_string(),
+            raw_data_output_from_ocr: "{}".to_string(),
+            ocr_engine: "Tesseract".to_string(),
+            words,
+            windows: Vec::new(),
+            region: None,
+            entities: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_ocr_words() {
+        let data = |level, text: &str, conf| Data {
+            level,
+            page_num: 1,
+            block_num: 1,
+            par_num: 1,
+            line_num: 1,
+            word_num: 1,
+            left: 10,
+            top: 10,
+            width: 80,
+            height: 20,
+            conf,
+            text: text.to_string(),
+        };
+        // Tesseract reports the block and line rows without text nor confidence
+        let data_output = DataOutput {
+            output: "invoice \"i&7\" total".to_string(),
+            data: vec![
+                data(2, "", -1.0),
+                data(4, "", -1.0),
+                data(5, "invoice", 90.0),
+                data(5, " ", 0.0),
+                data(5, "i&7", 30.0),
+                // A cloud OCR word without confidence keeps its box
+                data(5, "total", -1.0),
+            ],
+        };
+        let words = ocr_words(&data_output);
+        let mut total = word("total", 0.0);
+        total.confidence = None;
+        assert_eq!(
+            words,
+            vec![word("invoice", 90.0), word("i&7", 30.0), total.clone()]
+        );
+        assert_eq!(mean_confidence(&words), Some(60.0));
+        assert_eq!(mean_confidence(&[total.clone()]), None);
+        assert_eq!(mean_confidence(&[]), None);
+
+        // Stored without the confidence, and before the boxes were
+        let json = serde_json::to_string(&[total.clone()]).unwrap();
+        assert!(!json.contains("confidence"), "{}", json);
+        let stored: Vec<OcrWord> =
+            serde_json::from_str(r#"[{"text": "invoice", "confidence": 90.0}]"#).unwrap();
+        assert_eq!(stored[0].confidence, Some(90.0));
+        assert_eq!(stored[0].width, 0);
+
+        let raw: serde_json::Value =
+            serde_json::from_str(&DataOutputWrapper { data_output }.to_json()).unwrap();
+        assert_eq!(raw["output"], "invoice \"i&7\"");
+        assert_eq!(raw["data"][2]["text"], "invoice");
+    }
+
+    #[cfg(feature = "recording")]
+    #[test]
+    fn test_confidence_floor() {
+        let floor = ConfidenceFloor::new(50.0);
+        assert!(floor
+            .process_frame(frame("invoice", vec![word("invoice", 90.0)]))
+            .is_some());
+        assert!(floor
+            .process_frame(frame("i&7 ~~", vec![word("i&7", 30.0), word("~~", 20.0)]))
+            .is_none());
+        // No confidence, e.g. Windows OCR
+        assert!(floor.process_frame(frame("invoice", Vec::new())).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_search_min_confidence() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        db.write_frame(frame(
+            "invoice total",
+            vec![word("invoice", 92.0), word("total", 88.0)],
+        ))
+        .await
+        .unwrap();
+        db.write_frame(frame(
+            "invoice t0ta1",
+            vec![word("invoice", 35.0), word("t0ta1", 25.0)],
+        ))
+        .await
+        .unwrap();
+        db.write_frame(frame("invoice", Vec::new())).await.unwrap();
+        db.flush_writes().await;
+
+        let results = db
+            .search(
+                "invoice",
+                ContentType::OCR,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+
+        let results = db
+            .search(
+                "invoice",
+                ContentType::OCR,
+                10,
+                0,
+                None,
+                None,
+                None,
+                Some(50.0),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let mut confidences: Vec<Option<f64>> = results
+            .iter()
+            .map(|result| match result {
+                SearchResult::OCR(ocr) => ocr.confidence,
+                _ => panic!("Expected OCR result"),
+            })
+            .collect();
+        confidences.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        // The frame without confidence is kept
+        assert_eq!(confidences, vec![None, Some(90.0)]);
+
+        assert_eq!(
+            db.count_search_results(
+                "invoice",
+                ContentType::OCR,
+                None,
+                None,
+                None,
+                Some(50.0),
+                None,
+                None,
+            )
+            .await
+            .unwrap(),
+            2
+        );
+        let (page, _) = db
+            .search_page(
+                "invoice",
+                ContentType::All,
+                10,
+                None,
+                None,
+                None,
+                None,
+                Some(50.0),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 2);
+    }
+}
@@ -1,7 +1,10 @@
 #[cfg(test)]
 mod tests {
     use chrono::Utc;
-    use screenpipe_server::{ContentType, DatabaseManager, SearchResult};
+    use screenpipe_server::{
+        ContentType, DatabaseManager, DatabaseSettings, IngestBatch, IngestFrame, IngestSummary,
+        IngestTranscription, SearchResult, TranscriptionSegment,
+    };
 
     async fn setup_test_db() -> DatabaseManager {
         DatabaseManager::new("sqlite::memory:").await.unwrap()
@@ -10,14 +13,28 @@ mod tests {
     #[tokio::test]
     async fn test_insert_and_search_ocr() {
         let db = setup_test_db().await;
-        let _ = db.insert_video_chunk("test_video.mp4").await.unwrap();
+        let _ = db
+            .insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
         let frame_id = db.insert_frame("").await.unwrap();
         db.insert_ocr_text(frame_id, "Hello, world!", "", "", "", "")
             .await
             .unwrap();
 
         let results = db
-            .search("Hello", ContentType::OCR, 100, 0, None, None, None)
+            .search(
+                "Hello",
+                ContentType::OCR,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
             .await
             .unwrap();
         assert_eq!(results.len(), 1);
@@ -38,7 +55,18 @@ mod tests {
             .unwrap();
 
         let results = db
-            .search("audio", ContentType::Audio, 100, 0, None, None, None)
+            .search(
+                "audio",
+                ContentType::Audio,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
             .await
             .unwrap();
         assert_eq!(results.len(), 1);
@@ -55,7 +83,10 @@ mod tests {
         let db = setup_test_db().await;
 
         // Insert OCR data
-        let _ = db.insert_video_chunk("test_video.mp4").await.unwrap();
+        let _ = db
+            .insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
         let frame_id = db.insert_frame("").await.unwrap();
         db.insert_ocr_text(frame_id, "Hello from OCR", "", "", "", "")
             .await
@@ -68,7 +99,18 @@ mod tests {
             .unwrap();
 
         let results = db
-            .search("Hello", ContentType::All, 100, 0, None, None, None)
+            .search(
+                "Hello",
+                ContentType::All,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
             .await
             .unwrap();
         assert_eq!(results.len(), 2);
@@ -93,7 +135,10 @@ mod tests {
         let start_time = Utc::now();
 
         // Insert OCR data
-        let _ = db.insert_video_chunk("test_video.mp4").await.unwrap();
+        let _ = db
+            .insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
         let frame_id1 = db.insert_frame("").await.unwrap();
         db.insert_ocr_text(frame_id1, "Hello from OCR 1", "", "", "", "")
             .await
@@ -135,6 +180,9 @@ mod tests {
                 Some(start_time),
                 Some(end_time),
                 None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -151,6 +199,9 @@ mod tests {
                 Some(mid_time),
                 Some(end_time),
                 None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -171,6 +222,9 @@ mod tests {
                 Some(start_time),
                 Some(end_time),
                 None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -186,6 +240,9 @@ mod tests {
                 Some(start_time),
                 Some(end_time),
                 None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -199,7 +256,10 @@ mod tests {
         let start_time = Utc::now();
 
         // Insert OCR data
-        let _ = db.insert_video_chunk("test_video.mp4").await.unwrap();
+        let _ = db
+            .insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
         let frame_id1 = db.insert_frame("").await.unwrap();
         db.insert_ocr_text(frame_id1, "Hello from OCR 1", "", "", "", "")
             .await
@@ -239,6 +299,9 @@ mod tests {
                 Some(mid_time),
                 Some(end_time),
                 None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -258,9 +321,273 @@ mod tests {
                 Some(start_time),
                 Some(end_time),
                 None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
         assert_eq!(count, 2);
     }
+
+    #[tokio::test]
+    async fn test_database_settings_and_migrations() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("db.sqlite");
+        let path = path.to_string_lossy();
+
+        let settings = DatabaseSettings {
+            page_size: 8192,
+            ..Default::default()
+        };
+        let db = DatabaseManager::with_settings(&path, &settings)
+            .await
+            .unwrap();
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        let synchronous: i64 = sqlx::query_scalar("PRAGMA synchronous")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(synchronous, 1); // NORMAL
+        let page_size: i64 = sqlx::query_scalar("PRAGMA page_size")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(page_size, 8192);
+        let version = db.schema_version().await.unwrap().unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        db.pool.close().await;
+
+        // Reopening an up to date database applies nothing and keeps the data
+        let db = DatabaseManager::new(&path).await.unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), Some(version));
+        let chunks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM video_chunks")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(chunks, 1);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_skips_duplicates() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk_without_codec("local.mp4")
+            .await
+            .unwrap();
+        let timestamp = Utc::now();
+        let batch = IngestBatch {
+            device_id: "laptop".to_string(),
+            frames: vec![IngestFrame {
+                timestamp,
+                file_path: "laptop.mp4".to_string(),
+                offset_index: 0,
+                app_name: "code".to_string(),
+                text: "fn main".to_string(),
+                text_json: "[]".to_string(),
+                ocr_engine: "Tesseract".to_string(),
+                raw_text: None,
+                entities: Vec::new(),
+            }],
+            transcriptions: vec![IngestTranscription {
+                timestamp,
+                file_path: "laptop.mp4".to_string(),
+                offset_index: 0,
+                transcription: "hello from the laptop".to_string(),
+                transcription_engine: "Whisper".to_string(),
+                duration: Some(30.0),
+                language: Some("en".to_string()),
+                segments: Vec::new(),
+                entities: Vec::new(),
+            }],
+        };
+
+        let summary = db.ingest(&batch).await.unwrap();
+        assert_eq!(
+            summary,
+            IngestSummary {
+                frames_inserted: 1,
+                frames_skipped: 0,
+                transcriptions_inserted: 1,
+                transcriptions_skipped: 0,
+            }
+        );
+        // Pushed again after a failure
+        let summary = db.ingest(&batch).await.unwrap();
+        assert_eq!(summary.frames_inserted + summary.transcriptions_inserted, 0);
+        assert_eq!(summary.frames_skipped + summary.transcriptions_skipped, 2);
+
+        let results = db
+            .search(
+                "laptop",
+                ContentType::Audio,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        // The files of another device aren't here to be read, nor transcribed again
+        let SearchResult::Audio(audio) = &results[0] else {
+            panic!("Expected an audio result, got {:?}", results);
+        };
+        assert!(db
+            .get_audio_chunk_path(audio.audio_chunk_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(db.queue_retranscription(None, true).await.unwrap(), 0);
+        // Local recording keeps going to the local chunk
+        let frame_id = db.insert_frame("terminal").await.unwrap();
+        let (file_path, _) = db.get_frame(frame_id).await.unwrap().unwrap();
+        assert_eq!(file_path, "local.mp4");
+        let (last_frame, last_audio) = db.get_latest_timestamps().await.unwrap();
+        assert!(last_frame.is_some());
+        assert!(last_audio.is_none());
+        assert_eq!(db.list_video_chunks().await.unwrap().len(), 1);
+
+        // The same text on another monitor, heard by another device, at the same time
+        let mut other_chunks = batch.clone();
+        other_chunks.frames[0].file_path = "laptop_monitor_2.mp4".to_string();
+        other_chunks.transcriptions[0].file_path = "laptop_output.mp4".to_string();
+        let summary = db.ingest(&other_chunks).await.unwrap();
+        assert_eq!(summary.frames_inserted, 1);
+        assert_eq!(summary.transcriptions_inserted, 1);
+
+        let results = db
+            .search(
+                "main",
+                ContentType::OCR,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        for result in results {
+            let SearchResult::OCR(ocr) = result else {
+                panic!("Expected an OCR result, got {:?}", result);
+            };
+            assert!(db.get_frame(ocr.frame_id).await.unwrap().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcription_segments_round_trip() {
+        let db = setup_test_db().await;
+        let segments = vec![
+            TranscriptionSegment {
+                start: 1.2,
+                end: 1.6,
+                text: "hello".to_string(),
+            },
+            TranscriptionSegment {
+                start: 4.0,
+                end: 4.5,
+                text: "there".to_string(),
+            },
+        ];
+        let transcription = |text: &str, segments: Vec<TranscriptionSegment>| IngestTranscription {
+            timestamp: Utc::now(),
+            file_path: "phone.mp4".to_string(),
+            offset_index: 0,
+            transcription: text.to_string(),
+            transcription_engine: "Deepgram".to_string(),
+            duration: Some(30.0),
+            language: None,
+            segments,
+            entities: Vec::new(),
+        };
+        let batch = IngestBatch {
+            device_id: "phone".to_string(),
+            frames: Vec::new(),
+            transcriptions: vec![
+                transcription("hello there", segments.clone()),
+                transcription("untimed", Vec::new()),
+            ],
+        };
+        db.ingest(&batch).await.unwrap();
+
+        let results = db
+            .search(
+                "hello",
+                ContentType::Audio,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        match &results[..] {
+            [SearchResult::Audio(audio)] => assert_eq!(audio.segments, segments),
+            _ => panic!("Expected one audio result, got {:?}", results),
+        }
+        // Stored as NULL, read as no segments
+        let results = db
+            .search(
+                "untimed",
+                ContentType::Audio,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        match &results[..] {
+            [SearchResult::Audio(audio)] => assert!(audio.segments.is_empty()),
+            _ => panic!("Expected one audio result, got {:?}", results),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_marks() {
+        let db = setup_test_db().await;
+        let now = Utc::now();
+        let standup = db
+            .insert_mark("Standup", now - chrono::Duration::minutes(10))
+            .await
+            .unwrap();
+        let bug = db.insert_mark("bug repro", now).await.unwrap();
+
+        let marks = db.get_marks(None, None, None, 10).await.unwrap();
+        assert_eq!(marks, vec![bug.clone(), standup.clone()]);
+        // Case insensitive, on part of the name
+        let marks = db.get_marks(Some("STAND"), None, None, 10).await.unwrap();
+        assert_eq!(marks, vec![standup.clone()]);
+        let marks = db
+            .get_marks(None, Some(now - chrono::Duration::minutes(1)), None, 10)
+            .await
+            .unwrap();
+        assert_eq!(marks, vec![bug.clone()]);
+
+        assert_eq!(db.get_mark(bug.id).await.unwrap(), Some(bug));
+        assert_eq!(db.get_mark(42).await.unwrap(), None);
+    }
 }
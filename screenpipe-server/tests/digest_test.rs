@@ -0,0 +1,150 @@
+#[cfg(all(test, feature = "integrations"))]
+mod tests {
+    use chrono::{Local, NaiveDate, Utc};
+    use screenpipe_integrations::chat_digest::{
+        build_digest, render_digest, webhook_payload, ChatPlatform, Digest, FlaggedKeyword,
+        KeywordMention, MAX_MENTIONS,
+    };
+    use screenpipe_integrations::obsidian::DailyNote;
+    use screenpipe_server::DatabaseManager;
+    use screenpipe_vision::OcrEngine;
+    use std::sync::Arc;
+
+    fn digest() -> Digest {
+        Digest {
+            date: NaiveDate::from_ymd_opt(2024, 9, 10).unwrap(),
+            note: DailyNote {
+                app_usage: vec![("editor".to_string(), 3), ("browser".to_string(), 1)],
+                transcripts: vec![(Utc::now(), "Let's ship the release".to_string())],
+                ocr_snippets: vec![(Utc::now(), "editor".to_string(), "fn main() {}".to_string())],
+            },
+            flagged: vec![
+                FlaggedKeyword {
+                    keyword: "invoice".to_string(),
+                    count: 12,
+                    mentions: vec![KeywordMention {
+                        timestamp: Utc::now(),
+                        source: "mail".to_string(),
+                        text: "the invoice\nof ACME".to_string(),
+                    }],
+                },
+                FlaggedKeyword {
+                    keyword: "dentist".to_string(),
+                    count: 0,
+                    mentions: Vec::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_render_digest() {
+        let slack = render_digest(ChatPlatform::Slack, &digest());
+        assert!(slack.starts_with("*screenpipe digest of Tuesday, September 10*\n"));
+        assert!(slack.contains("*Apps:* editor 75%, browser 25%"));
+        assert!(slack.contains("*Heard:* 1 transcriptions"));
+        assert!(slack.contains("(editor) fn main() {}"));
+        assert!(slack.contains("*invoice* mentioned 12 times"));
+        assert!(slack.contains("(mail) the invoice of ACME"));
+        // Keywords not mentioned are left out
+        assert!(!slack.contains("dentist"));
+
+        let discord = render_digest(ChatPlatform::Discord, &digest());
+        assert!(discord.contains("**Apps:** editor 75%, browser 25%"));
+        assert!(discord.contains("**invoice** mentioned 12 times"));
+    }
+
+    #[test]
+    fn test_webhook_payload() {
+        assert_eq!(
+            webhook_payload(ChatPlatform::Slack, "hello"),
+            serde_json::json!({ "text": "hello" })
+        );
+        assert_eq!(
+            webhook_payload(ChatPlatform::Discord, "hello"),
+            serde_json::json!({ "content": "hello" })
+        );
+        let long = "a".repeat(3000);
+        let content = webhook_payload(ChatPlatform::Discord, &long)["content"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(content.chars().count(), 2000);
+        assert!(content.ends_with('…'));
+        assert_eq!(
+            webhook_payload(ChatPlatform::Slack, &long)["text"],
+            long.as_str()
+        );
+
+        assert_eq!("Discord".parse::<ChatPlatform>(), Ok(ChatPlatform::Discord));
+        assert!("teams".parse::<ChatPlatform>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build_digest() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for (app_name, text) in [
+            ("mail", "Your invoice from ACME is ready"),
+            ("editor", "fn main() {}"),
+        ] {
+            let frame_id = db.insert_frame(app_name).await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                "",
+                "",
+                app_name,
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(audio_chunk_id, "did they pay the invoice", 0, "whisper")
+            .await
+            .unwrap();
+        // More than are listed
+        for i in 0..MAX_MENTIONS {
+            let frame_id = db.insert_frame("mail").await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                &format!("invoice {}", i),
+                "",
+                "",
+                "",
+                "mail",
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+
+        let today = Local::now().date_naive();
+        let keywords = vec!["invoice".to_string(), "dentist".to_string()];
+        let digest = build_digest(today, &keywords, &db).await.unwrap();
+        assert_eq!(digest.date, today);
+        assert_eq!(digest.note.app_usage.len(), 2);
+
+        let invoice = &digest.flagged[0];
+        assert_eq!(invoice.keyword, "invoice");
+        assert_eq!(invoice.count, MAX_MENTIONS as u64 + 2);
+        assert_eq!(invoice.mentions.len(), MAX_MENTIONS as usize);
+        assert!(invoice
+            .mentions
+            .iter()
+            .all(|mention| mention.text.contains("invoice")));
+        assert!(render_digest(ChatPlatform::Slack, &digest)
+            .contains(&format!("*invoice* mentioned {} times", MAX_MENTIONS + 2)));
+        assert_eq!(digest.flagged[1].count, 0);
+        assert!(digest.flagged[1].mentions.is_empty());
+
+        let yesterday = today.pred_opt().unwrap();
+        let digest = build_digest(yesterday, &keywords, &db).await.unwrap();
+        assert_eq!(digest.flagged[0].count, 0);
+        assert!(digest.flagged[0].mentions.is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+#[cfg(all(test, feature = "audio"))]
+mod tests {
+    use crossbeam::queue::SegQueue;
+    use screenpipe_server::{
+        start_continuous_recording, CaptureRequests, ContentType, DatabaseManager, HealthMonitor,
+        LiveSettings, RecordingConfig, RecordingHandles, SearchResult, Shutdown, VisionStatus,
+        WhisperModelConfig,
+    };
+    use screenpipe_vision::synthetic::SYNTHETIC_APP_NAME;
+    use screenpipe_vision::{CaptureTrigger, OcrEngine};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tokio::sync::watch;
+
+    fn fixture(relative_path: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join(relative_path)
+    }
+
+    // Requires ffmpeg and tesseract, downloads the whisper model on first run
+    #[tokio::test]
+    async fn test_synthetic_capture_to_search() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let frames_dir = temp_dir.path().join("frames");
+        std::fs::create_dir_all(&frames_dir).unwrap();
+        std::fs::copy(
+            fixture("screenpipe-vision/tests/testing_OCR.png"),
+            frames_dir.join("0001.png"),
+        )
+        .unwrap();
+
+        let audio_dir = temp_dir.path().join("audio");
+        std::fs::create_dir_all(&audio_dir).unwrap();
+        std::fs::copy(
+            fixture("screenpipe-audio/test_data/poetic_kapil_gupta.wav"),
+            audio_dir.join("0001.wav"),
+        )
+        .unwrap();
+
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let db = Arc::new(
+            DatabaseManager::new(&temp_dir.path().join("db.sqlite").to_string_lossy())
+                .await
+                .unwrap(),
+        );
+
+        let vision_control = Arc::new(AtomicBool::new(true));
+        let shutdown = Shutdown::new();
+        let mut config = RecordingConfig::new(data_dir.to_string_lossy());
+        config.audio_chunk_duration = Duration::from_secs(5);
+        config.ocr_engine = Arc::new(OcrEngine::Tesseract);
+        config.synthetic_frames_dir = Some(frames_dir);
+        config.synthetic_audio_dir = Some(audio_dir);
+        let handles = RecordingHandles {
+            settings: watch::channel(LiveSettings::default()).1,
+            vision_control: vision_control.clone(),
+            vision_status: Arc::new(VisionStatus::new()),
+            health: Arc::new(HealthMonitor::default()),
+            audio_devices_control: Arc::new(SegQueue::new()),
+            audio_device_configs: watch::channel(HashMap::new()).1,
+            whisper_model: watch::channel(WhisperModelConfig::default()).1,
+            capture_paused: Arc::new(AtomicBool::new(false)),
+            capture_trigger: Arc::new(CaptureTrigger::new()),
+            capture_requests: CaptureRequests::new(),
+            shutdown: shutdown.clone(),
+        };
+        let recording = tokio::spawn(start_continuous_recording(db.clone(), config, handles));
+
+        // Poll the database until both pipelines delivered
+        let deadline = Instant::now() + Duration::from_secs(300);
+        let (ocr_results, audio_results) = loop {
+            let ocr_results = db
+                .search(
+                    "",
+                    ContentType::OCR,
+                    10,
+                    0,
+                    None,
+                    None,
+                    Some(SYNTHETIC_APP_NAME),
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            let audio_results = db
+                .search(
+                    "",
+                    ContentType::Audio,
+                    10,
+                    0,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+
+            if !ocr_results.is_empty() && !audio_results.is_empty() {
+                break (ocr_results, audio_results);
+            }
+            assert!(
+                Instant::now() < deadline,
+                "Timed out waiting for synthetic capture results"
+            );
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        };
+
+        // Finishes the chunks being recorded and returns
+        shutdown.request();
+        tokio::time::timeout(Duration::from_secs(120), recording)
+            .await
+            .expect("Recording didn't stop after the shutdown")
+            .unwrap()
+            .unwrap();
+        assert!(shutdown.wait(Duration::from_secs(1)).await);
+
+        match &ocr_results[0] {
+            SearchResult::OCR(ocr) => {
+                assert!(!ocr.ocr_text.is_empty());
+                assert_eq!(ocr.app_name, SYNTHETIC_APP_NAME);
+            }
+            _ => panic!("Expected OCR result"),
+        }
+        match &audio_results[0] {
+            SearchResult::Audio(audio) => {
+                assert!(!audio.transcription.is_empty());
+                assert!(audio.file_path.ends_with("0001.wav"));
+            }
+            _ => panic!("Expected audio result"),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use screenpipe_server::{
+        decrypt_file, encrypt_chunk_dir, encrypt_database, encrypt_file, is_encrypted,
+        storage_routes, AppState, DatabaseManager, DatabaseSettings, EncryptionKey,
+    };
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_encrypt_chunk_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = EncryptionKey::from_passphrase("correct horse", dir.path()).unwrap();
+        let chunk_dir = dir.path().join("data");
+        std::fs::create_dir(&chunk_dir).unwrap();
+        let chunk = chunk_dir.join("2024-08-24_10-00-00.mp4");
+        std::fs::write(&chunk, b"not really a video").unwrap();
+
+        encrypt_file(&chunk, &key).unwrap();
+        assert!(is_encrypted(&chunk).unwrap());
+        assert_ne!(std::fs::read(&chunk).unwrap(), b"not really a video");
+        // Encrypting twice does nothing
+        encrypt_file(&chunk, &key).unwrap();
+        assert_eq!(decrypt_file(&chunk, &key).unwrap(), b"not really a video");
+
+        // Same passphrase and salt, same key
+        let same_key = EncryptionKey::from_passphrase("correct horse", dir.path()).unwrap();
+        assert_eq!(
+            decrypt_file(&chunk, &same_key).unwrap(),
+            b"not really a video"
+        );
+        let wrong_key = EncryptionKey::from_passphrase("battery staple", dir.path()).unwrap();
+        assert!(decrypt_file(&chunk, &wrong_key).is_err());
+
+        let audio = chunk_dir.join("mic_2024-08-24_10-00-00.mp4");
+        std::fs::write(&audio, b"not really audio").unwrap();
+        // Decrypted copy left over by a crash
+        std::fs::create_dir(chunk_dir.join(".decrypted")).unwrap();
+        std::fs::write(chunk_dir.join(".decrypted/copy.mp4"), b"not really a video").unwrap();
+        assert_eq!(encrypt_chunk_dir(&chunk_dir, &key).unwrap(), 1);
+        assert!(is_encrypted(&audio).unwrap());
+        assert!(!chunk_dir.join(".decrypted").exists());
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_existing_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.sqlite");
+        let path = path.to_string_lossy().into_owned();
+        let db = DatabaseManager::new(&path).await.unwrap();
+        db.insert_video_chunk_without_codec("video.mp4")
+            .await
+            .unwrap();
+        db.pool.close().await;
+
+        let key = EncryptionKey::from_bytes([7; 32]);
+        assert!(encrypt_database(&path, &key).await.unwrap());
+        assert!(!encrypt_database(&path, &key).await.unwrap());
+
+        // Unreadable without the key
+        assert!(DatabaseManager::new(&path).await.is_err());
+        let settings = DatabaseSettings {
+            encryption_key: Some(key),
+            ..Default::default()
+        };
+        let db = DatabaseManager::with_settings(&path, &settings)
+            .await
+            .unwrap();
+        assert_eq!(db.list_video_chunks().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_serve_encrypted_media() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = EncryptionKey::from_bytes([7; 32]);
+        let settings = DatabaseSettings {
+            encryption_key: Some(key.clone()),
+            ..Default::default()
+        };
+        let db = DatabaseManager::with_settings(
+            &dir.path().join("db.sqlite").to_string_lossy(),
+            &settings,
+        )
+        .await
+        .unwrap();
+        let audio = dir.path().join("mic_2024-08-24_10-00-00.mp4");
+        std::fs::write(&audio, b"0123456789 not really audio").unwrap();
+        encrypt_file(&audio, &key).unwrap();
+        let chunk_id = db
+            .insert_audio_chunk(&audio.to_string_lossy())
+            .await
+            .unwrap();
+        let app = storage_routes().with_state(Arc::new(AppState {
+            media_dir: Some(dir.path().to_path_buf()),
+            ..AppState::new(Arc::new(db))
+        }));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get(format!("/audio/{}", chunk_id))
+                    .header("range", "bytes=0-9")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"0123456789");
+
+        let response = app
+            .oneshot(
+                Request::post("/download")
+                    .header("content-type", "application/json")
+                    .body(Body::from(format!(
+                        r#"{{"audio_chunk_ids": [{}]}}"#,
+                        chunk_id
+                    )))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+        let mut content = Vec::new();
+        std::io::Read::read_to_end(
+            &mut archive
+                .by_name("audio/mic_2024-08-24_10-00-00.mp4")
+                .unwrap(),
+            &mut content,
+        )
+        .unwrap();
+        assert_eq!(content, b"0123456789 not really audio");
+        // Still encrypted on disk, and the decrypted copies are removed
+        assert!(is_encrypted(&audio).unwrap());
+        let copies = std::fs::read_dir(dir.path().join(".decrypted")).unwrap();
+        assert_eq!(copies.count(), 0);
+    }
+}
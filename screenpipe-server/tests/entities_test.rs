@@ -0,0 +1,224 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    #[cfg(feature = "recording")]
+    use screenpipe_server::ContentProcessor;
+    use screenpipe_server::{
+        normalize_entity, AudioRecord, ContentType, DatabaseManager, Entity, EntityExtractor,
+        EntityKind, FrameRecord, SearchResult, Storage,
+    };
+
+    fn values(entities: &[Entity], kind: EntityKind) -> Vec<(&str, &str)> {
+        entities
+            .iter()
+            .filter(|entity| entity.kind == kind)
+            .map(|entity| (entity.value.as_str(), entity.normalized.as_str()))
+            .collect()
+    }
+
+    fn frame(text: &str) -> FrameRecord {
+        FrameRecord {
+            timestamp: Utc::now(),
+            app_name: "mail".to_string(),
+            text: text.to_string(),
+            raw_text: None,
+            text_json: "[]".to_string(),
+            new_text_json_vs_previous_frame: "[]".to_string(),
+            raw_data_output_from_ocr: "{}".to_string(),
+            ocr_engine: "Tesseract".to_string(),
+            words: Vec::new(),
+            windows: Vec::new(),
+            region: None,
+            entities: EntityExtractor::new().extract(text),
+        }
+    }
+
+    fn audio(transcription: &str) -> AudioRecord {
+        AudioRecord {
+            timestamp: Utc::now(),
+            file_path: "call.mp4".to_string(),
+            duration: Some(30.0),
+            transcription: transcription.to_string(),
+            transcription_engine: "Whisper".to_string(),
+            language: None,
+            segments: Vec::new(),
+            entities: EntityExtractor::new().extract(transcription),
+            format: None,
+            transcription_error: None,
+        }
+    }
+
+    #[test]
+    fn test_extract_entities() {
+        let entities = EntityExtractor::new().extract(
+            "From: Jane Doe <jane@acme.com>\n\
+             Hi Dr. John Smith, the invoice of ACME Corp. for $1,234.50 is due Sep 8th, 2024, \
+             see https://acme.com/invoices/42. Paid 300 EUR on 2024-09-01 to ACME Corp.",
+        );
+        assert_eq!(
+            values(&entities, EntityKind::Person),
+            vec![("Jane Doe", "jane doe"), ("John Smith", "john smith")]
+        );
+        assert_eq!(
+            values(&entities, EntityKind::Organization),
+            vec![("ACME Corp", "acme corp")]
+        );
+        assert_eq!(
+            values(&entities, EntityKind::Url),
+            vec![(
+                "https://acme.com/invoices/42",
+                "https://acme.com/invoices/42"
+            )]
+        );
+        assert_eq!(
+            values(&entities, EntityKind::Date),
+            vec![
+                ("Sep 8th, 2024", "2024-09-08"),
+                ("2024-09-01", "2024-09-01")
+            ]
+        );
+        assert_eq!(
+            values(&entities, EntityKind::Amount),
+            vec![("$1,234.50", "usd 1234.50"), ("300 EUR", "eur 300")]
+        );
+    }
+
+    #[test]
+    fn test_extract_nothing() {
+        let extractor = EntityExtractor::new();
+        assert!(extractor.extract("").is_empty());
+        assert!(extractor
+            .extract("the build failed again, retrying in 5 minutes")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_normalize_entity() {
+        assert_eq!(normalize_entity("  ACME   Corp. "), "acme corp");
+        assert_eq!(normalize_entity("Jane Doe,"), "jane doe");
+        assert_eq!(
+            EntityKind::from_name("organization"),
+            Some(EntityKind::Organization)
+        );
+        assert_eq!(EntityKind::from_name("planet"), None);
+    }
+
+    #[cfg(feature = "recording")]
+    #[test]
+    fn test_entity_processor() {
+        let extractor = EntityExtractor::new();
+        let mut record = frame("");
+        record.text = "Invoice of ACME Corp".to_string();
+        let record = extractor.process_frame(record).unwrap();
+        assert_eq!(
+            values(&record.entities, EntityKind::Organization),
+            vec![("ACME Corp", "acme corp")]
+        );
+
+        let mut record = audio("");
+        record.transcription = "call Ms. Jane Doe back".to_string();
+        let record = extractor.process_audio(record).unwrap();
+        assert_eq!(
+            values(&record.entities, EntityKind::Person),
+            vec![("Jane Doe", "jane doe")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_and_list_entities() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let start = Utc::now() - Duration::seconds(1);
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        db.write_frame(frame("Invoice of ACME Corp for $1,234.50"))
+            .await
+            .unwrap();
+        db.write_frame(frame("Invoice of Globex Inc for $99"))
+            .await
+            .unwrap();
+        db.write_audio(audio("the invoice of ACME Corp. is paid"))
+            .await
+            .unwrap();
+        db.flush_writes().await;
+
+        let results = db
+            .search(
+                "invoice",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                Some("acme corp."),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().any(|result| matches!(
+            result,
+            SearchResult::OCR(ocr) if ocr.ocr_text == "Invoice of ACME Corp for $1,234.50"
+        )));
+        assert!(results
+            .iter()
+            .any(|result| matches!(result, SearchResult::Audio(_))));
+        assert_eq!(
+            db.count_search_results(
+                "",
+                ContentType::OCR,
+                None,
+                None,
+                None,
+                None,
+                Some("Globex Inc"),
+                None,
+            )
+            .await
+            .unwrap(),
+            1
+        );
+        assert!(db
+            .search(
+                "",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                Some("Initech"),
+                None,
+            )
+            .await
+            .unwrap()
+            .is_empty());
+
+        let organizations = db
+            .list_entities(Some(EntityKind::Organization), None, Some(start), None, 10)
+            .await
+            .unwrap();
+        let names: Vec<(&str, i64)> = organizations
+            .iter()
+            .map(|entity| (entity.normalized.as_str(), entity.mentions))
+            .collect();
+        assert_eq!(names, vec![("acme corp", 2), ("globex inc", 1)]);
+        // The latest form read
+        assert_eq!(organizations[0].value, "ACME Corp");
+
+        let amounts = db
+            .list_entities(None, Some("USD 99"), None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(amounts.len(), 1);
+        assert_eq!(amounts[0].kind, EntityKind::Amount);
+        assert!(db
+            .list_entities(None, None, Some(Utc::now() + Duration::hours(1)), None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
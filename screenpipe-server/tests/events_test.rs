@@ -0,0 +1,150 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use screenpipe_server::{
+        prune_chunks, ChunkKind, DatabaseManager, EventKind, EventLog, HealthMonitor, Subsystem,
+    };
+    use serde_json::json;
+    use std::sync::Arc;
+
+    async fn setup_db() -> Arc<DatabaseManager> {
+        Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap())
+    }
+
+    /// Events the background writer of an `EventLog` wrote so far, waiting for `count`
+    async fn wait_for_events(db: &DatabaseManager, count: i64) {
+        for _ in 0..100 {
+            if db.count_events(&[], None, None).await.unwrap() >= count {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("{} events weren't written", count);
+    }
+
+    #[test]
+    fn test_event_kind_names() {
+        assert_eq!(
+            EventKind::OcrEngineChanged.to_string(),
+            "ocr_engine_changed"
+        );
+        assert_eq!(
+            "chunks_purged".parse::<EventKind>(),
+            Ok(EventKind::ChunksPurged)
+        );
+        assert!("ChunksPurged".parse::<EventKind>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_get_events() {
+        let db = setup_db().await;
+        let started = db
+            .insert_event(
+                EventKind::RecordingStarted,
+                "Recording started with Tesseract OCR",
+                Some(&json!({"ocr_engine": "Tesseract"})),
+            )
+            .await
+            .unwrap();
+        assert_eq!(started.data, Some(json!({"ocr_engine": "Tesseract"})));
+        db.insert_event(EventKind::Error, "Video recording error", None)
+            .await
+            .unwrap();
+        db.insert_event(EventKind::RecordingStopped, "Recording stopped", None)
+            .await
+            .unwrap();
+
+        // Most recent first, ties in insertion order reversed
+        let events = db.get_events(&[], None, None, 10, 0).await.unwrap();
+        let kinds: Vec<EventKind> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EventKind::RecordingStopped,
+                EventKind::Error,
+                EventKind::RecordingStarted
+            ]
+        );
+        assert_eq!(events[2].id, started.id);
+        assert_eq!(events[2].data, started.data);
+        assert_eq!(events[1].data, None);
+
+        let page = db.get_events(&[], None, None, 1, 1).await.unwrap();
+        assert_eq!(page[0].kind, EventKind::Error);
+
+        let kinds = [EventKind::RecordingStarted, EventKind::RecordingStopped];
+        assert_eq!(db.count_events(&kinds, None, None).await.unwrap(), 2);
+        assert_eq!(db.count_events(&[], None, None).await.unwrap(), 3);
+        let later = Utc::now() + Duration::minutes(1);
+        assert_eq!(db.count_events(&[], Some(later), None).await.unwrap(), 0);
+        assert!(db
+            .get_events(
+                &[],
+                None,
+                Some(started.timestamp - Duration::seconds(1)),
+                10,
+                0
+            )
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_health_monitor_records_events() {
+        let db = setup_db().await;
+        let health = HealthMonitor::default().with_events(EventLog::new(db.clone()));
+
+        health.record_error(Subsystem::Ocr, "Tesseract failed");
+        // Repeated within a minute, only in /health
+        health.record_error(Subsystem::Ocr, "Tesseract failed");
+        health.record_error(Subsystem::Audio, "Device disconnected");
+        health.task_restarted(Subsystem::Vision, "Task stalled, restarted");
+        wait_for_events(&db, 3).await;
+        // Lets a duplicate be written, if it was recorded
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let events = db.get_events(&[], None, None, 10, 0).await.unwrap();
+        let kinds: Vec<EventKind> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![EventKind::TaskRestarted, EventKind::Error, EventKind::Error]
+        );
+        assert_eq!(events[2].data, Some(json!({"subsystem": "ocr"})));
+        assert_eq!(events[1].message, "Device disconnected");
+    }
+
+    #[tokio::test]
+    async fn test_prune_records_purge() {
+        let db = setup_db().await;
+        db.insert_video_chunk_without_codec("/nonexistent/old.mp4")
+            .await
+            .unwrap();
+        let before = Utc::now() + Duration::seconds(1);
+
+        prune_chunks(&db, before, ChunkKind::All, true)
+            .await
+            .unwrap();
+        assert_eq!(db.count_events(&[], None, None).await.unwrap(), 0);
+
+        let summary = prune_chunks(&db, before, ChunkKind::All, false)
+            .await
+            .unwrap();
+        assert_eq!(summary.video_chunks, 1);
+        let events = db
+            .get_events(&[EventKind::ChunksPurged], None, None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        let data = events[0].data.as_ref().unwrap();
+        assert_eq!(data["reason"], "prune");
+        assert_eq!(data["video_chunks"], 1);
+        assert_eq!(data["audio_chunks"], 0);
+
+        // Nothing left to prune, nothing recorded
+        prune_chunks(&db, before, ChunkKind::All, false)
+            .await
+            .unwrap();
+        assert_eq!(db.count_events(&[], None, None).await.unwrap(), 1);
+    }
+}
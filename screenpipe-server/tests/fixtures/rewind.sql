@@ -0,0 +1,62 @@
+-- Decrypted copy of a Rewind memoryVault/db-enc.sqlite3, the tables read by the import
+CREATE TABLE segment (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    bundleID TEXT,
+    startDate TEXT NOT NULL,
+    endDate TEXT NOT NULL,
+    windowName TEXT,
+    browserUrl TEXT,
+    browserProfile TEXT,
+    type INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE frame (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    createdAt TEXT NOT NULL,
+    imageFileName TEXT NOT NULL,
+    segmentId INTEGER REFERENCES segment(id) ON DELETE CASCADE,
+    videoId INTEGER,
+    videoFrameIndex INTEGER,
+    isStarred INTEGER NOT NULL DEFAULT 0,
+    encodingStatus TEXT
+);
+CREATE TABLE doc_segment (
+    docid INTEGER NOT NULL,
+    segmentId INTEGER NOT NULL,
+    frameId INTEGER
+);
+CREATE VIRTUAL TABLE searchRanking USING fts5(text, otherText, title, tokenize = 'porter unicode61');
+CREATE TABLE transcript_word (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    segmentId INTEGER NOT NULL REFERENCES segment(id) ON DELETE CASCADE,
+    speechSource TEXT NOT NULL,
+    word TEXT NOT NULL,
+    timeOffset INTEGER NOT NULL,
+    fullTextOffset INTEGER,
+    duration INTEGER NOT NULL
+);
+
+INSERT INTO segment (id, bundleID, startDate, endDate, windowName, browserUrl, type) VALUES
+    (1, 'com.apple.Safari', '2024-03-04T09:15:00.000', '2024-03-04T09:16:00.000', 'Quarterly planning', 'https://docs.google.com/document/d/1', 0),
+    (2, 'com.microsoft.VSCode', '2024-03-04T09:16:00.000', '2024-03-04T09:20:00.000', 'main.rs - screenpipe', NULL, 0),
+    (3, 'us.zoom.xos', '2024-03-04T10:00:00.000', '2024-03-04T10:00:04.500', 'Zoom Meeting', NULL, 1);
+
+INSERT INTO frame (id, createdAt, imageFileName, segmentId, videoId, videoFrameIndex, encodingStatus) VALUES
+    (1, '2024-03-04T09:15:02.123', 'A1B2C3D4.jpg', 1, 1, 0, 'success'),
+    (2, '2024-03-04T09:16:10.500', 'E5F6A7B8.jpg', 2, 1, 1, 'success'),
+    (3, '2024-03-04T09:16:12.500', 'C9D0E1F2.jpg', 2, 1, 2, 'success');
+
+INSERT INTO searchRanking (rowid, text, otherText, title) VALUES
+    (1, 'Quarterly planning - Google Docs Revenue targets for Q2', '', 'Quarterly planning'),
+    (2, 'fn main() { println!("hello screenpipe"); }', '', 'main.rs - screenpipe'),
+    (3, '', '', 'main.rs - screenpipe');
+
+INSERT INTO doc_segment (docid, segmentId, frameId) VALUES
+    (1, 1, 1),
+    (2, 2, 2),
+    (3, 2, 3);
+
+-- Words come in any order, timeOffset is in milliseconds into the segment
+INSERT INTO transcript_word (segmentId, speechSource, word, timeOffset, fullTextOffset, duration) VALUES
+    (3, 'me', 'ship', 600, 6, 300),
+    (3, 'me', 'let''s', 100, 0, 400),
+    (3, 'me', 'it', 1000, 11, 200);
@@ -0,0 +1,49 @@
+-- Copy of Windows Recall's ukg.db, the tables read by the import
+CREATE TABLE App (
+    Id INTEGER PRIMARY KEY,
+    WindowsAppId TEXT,
+    IconUri TEXT,
+    Name TEXT,
+    Path TEXT,
+    Properties TEXT
+);
+CREATE TABLE WindowCapture (
+    Id INTEGER PRIMARY KEY,
+    Name TEXT,
+    ImageToken TEXT,
+    IsForeground INTEGER,
+    WindowId INTEGER,
+    WindowBounds TEXT,
+    WindowTitle TEXT,
+    Properties TEXT,
+    TimeStamp INTEGER,
+    IsProcessed INTEGER,
+    TextRecognitionToken TEXT
+);
+CREATE TABLE WindowCaptureAppRelation (
+    WindowCaptureId INTEGER NOT NULL,
+    AppId INTEGER NOT NULL
+);
+CREATE VIRTUAL TABLE WindowCaptureTextIndex USING fts5(WindowCaptureId UNINDEXED, WindowTitle, Text);
+
+INSERT INTO App (Id, WindowsAppId, Name, Path) VALUES
+    (1, 'Microsoft.Outlook_8wekyb3d8bbwe!Microsoft.OutlookForWindows', 'Outlook', 'C:\Program Files\WindowsApps\Microsoft.OutlookForWindows\olk.exe'),
+    (2, NULL, NULL, 'C:\Windows\System32\notepad.exe');
+
+INSERT INTO WindowCapture (Id, Name, IsForeground, WindowId, WindowTitle, TimeStamp, IsProcessed) VALUES
+    (1, 'WindowCaptureEvent', 1, 65810, 'Inbox - Outlook', 1717232400000, 1),
+    (2, 'WindowCaptureEvent', 1, 131422, 'todo.txt - Notepad', 1717232460000, 1),
+    (3, 'WindowCaptureEvent', 1, 65810, 'Inbox - Outlook', 1717232520000, 1),
+    (4, 'WindowCaptureEvent', 0, 262544, 'Untitled', 1717232580000, 1);
+
+INSERT INTO WindowCaptureAppRelation (WindowCaptureId, AppId) VALUES
+    (1, 1),
+    (2, 2),
+    (3, 1);
+
+-- Capture 3 had no text, capture 4 no app
+INSERT INTO WindowCaptureTextIndex (rowid, WindowCaptureId, WindowTitle, Text) VALUES
+    (1, 1, 'Inbox - Outlook', 'Invoice 1042 from ACME is overdue'),
+    (2, 2, 'todo.txt - Notepad', 'renew passport before june'),
+    (3, 3, 'Inbox - Outlook', ''),
+    (4, 4, 'Untitled', 'loose notes');
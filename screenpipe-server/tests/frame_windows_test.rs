@@ -0,0 +1,183 @@
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use screenpipe_server::{ContentType, DatabaseManager, FrameRecord, SearchResult, Storage};
+    use screenpipe_vision::{CaptureRegion, OcrEngine, WindowRegion};
+    use std::sync::Arc;
+
+    fn window(app_name: &str, z_index: u32, visible: bool, text: &str) -> WindowRegion {
+        WindowRegion {
+            z_index,
+            visible,
+            text: text.to_string(),
+            ..WindowRegion::new(app_name, "", 0, 0, 800, 600)
+        }
+    }
+
+    // The bottom 40px of the screen, where a ticker lives, are cropped
+    fn region() -> CaptureRegion {
+        CaptureRegion {
+            monitor: Some("DELL U2720Q".to_string()),
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1040,
+            screen_width: 1920,
+            screen_height: 1080,
+        }
+    }
+
+    async fn setup_db() -> DatabaseManager {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        // A notification over the editor, the docs behind it aren't on screen
+        db.write_frame(FrameRecord {
+            timestamp: Utc::now(),
+            app_name: "slack".to_string(),
+            text: "lunch? quarterly report draft".to_string(),
+            raw_text: None,
+            text_json: "[]".to_string(),
+            new_text_json_vs_previous_frame: "[]".to_string(),
+            raw_data_output_from_ocr: "{}".to_string(),
+            ocr_engine: "Tesseract".to_string(),
+            words: Vec::new(),
+            windows: vec![
+                window("slack", 0, true, "lunch?"),
+                window("code", 1, true, "quarterly report draft"),
+                window("firefox", 2, false, ""),
+            ],
+            region: Some(region()),
+            entities: Vec::new(),
+        })
+        .await
+        .unwrap();
+        db.flush_writes().await;
+        // Recorded before windows were
+        let frame_id = db.insert_frame("firefox").await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "quarterly numbers",
+            "[]",
+            "[]",
+            "{}",
+            "firefox",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    async fn search(db: &DatabaseManager, query: &str, app_name: Option<&str>) -> Vec<String> {
+        db.search(
+            query,
+            ContentType::OCR,
+            10,
+            0,
+            None,
+            None,
+            app_name,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|result| match result {
+            SearchResult::OCR(ocr) => ocr.app_name,
+            SearchResult::Audio(_) => panic!("audio result"),
+            SearchResult::Clipboard(_) => panic!("clipboard result"),
+            SearchResult::Notification(_) => panic!("notification result"),
+        })
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn test_frame_windows() {
+        let db = setup_db().await;
+        let windows = db.get_frame_windows(1).await.unwrap();
+        let apps: Vec<&str> = windows.iter().map(|w| w.app_name.as_str()).collect();
+        assert_eq!(apps, ["slack", "code", "firefox"]);
+        assert!(!windows[2].visible);
+        assert_eq!(windows[1].text, "quarterly report draft");
+        assert!(db.get_frame_windows(2).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_by_app_matches_its_windows() {
+        let db = setup_db().await;
+        assert_eq!(search(&db, "quarterly", None).await.len(), 2);
+        // Slack was in front, but the report was in the editor
+        assert!(search(&db, "quarterly", Some("slack")).await.is_empty());
+        assert_eq!(search(&db, "lunch", Some("slack")).await, ["slack"]);
+        assert_eq!(search(&db, "report", Some("code")).await, ["slack"]);
+        assert!(search(&db, "lunch", Some("code")).await.is_empty());
+        // Occluded, nothing of it was read
+        assert_eq!(search(&db, "", Some("firefox")).await, ["firefox"]);
+        assert_eq!(search(&db, "", Some("code")).await.len(), 1);
+        assert_eq!(
+            db.count_search_results(
+                "quarterly",
+                ContentType::OCR,
+                None,
+                None,
+                Some("code"),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap(),
+            1
+        );
+        assert_eq!(
+            db.count_search_results(
+                "quarterly",
+                ContentType::OCR,
+                None,
+                None,
+                Some("slack"),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_returns_capture_region() {
+        let db = setup_db().await;
+        let regions: Vec<(String, Option<CaptureRegion>)> = db
+            .search(
+                "quarterly",
+                ContentType::OCR,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|result| match result {
+                SearchResult::OCR(ocr) => (ocr.app_name, ocr.region),
+                SearchResult::Audio(_) => panic!("audio result"),
+                SearchResult::Clipboard(_) => panic!("clipboard result"),
+                SearchResult::Notification(_) => panic!("notification result"),
+            })
+            .collect();
+        assert!(regions.contains(&("slack".to_string(), Some(region()))));
+        // Recorded before regions were
+        assert!(regions.contains(&("firefox".to_string(), None)));
+    }
+}
@@ -0,0 +1,244 @@
+#[cfg(all(test, feature = "grpc"))]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use screenpipe_server::proto::screenpipe_client::ScreenpipeClient;
+    use screenpipe_server::proto::{self, search_result::Content};
+    use screenpipe_server::{
+        serve_grpc, ApiLimits, ApiPlugin, ApiPlugins, AppState, DatabaseManager, EventKind,
+        EventLog, HealthMonitor,
+    };
+    use screenpipe_vision::OcrEngine;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tonic::transport::Channel;
+
+    async fn insert_frame(db: &DatabaseManager, app_name: &str, text: &str) -> i64 {
+        let frame_id = db.insert_frame(app_name).await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            text,
+            "[]",
+            "[]",
+            "{}",
+            app_name,
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+        frame_id
+    }
+
+    /// Answers 401 to every request
+    struct DenyAll;
+
+    impl ApiPlugin for DenyAll {
+        fn name(&self) -> &str {
+            "deny-all"
+        }
+
+        fn on_request(&self, _: &Request<Body>) -> Option<Response> {
+            Some(StatusCode::UNAUTHORIZED.into_response())
+        }
+    }
+
+    async fn setup() -> (Arc<AppState>, ScreenpipeClient<Channel>) {
+        setup_with(ApiPlugins::new()).await
+    }
+
+    async fn setup_with(plugins: ApiPlugins) -> (Arc<AppState>, ScreenpipeClient<Channel>) {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        insert_frame(&db, "firefox", "quarterly report draft").await;
+        insert_frame(&db, "slack", "lunch at noon?").await;
+        let chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(chunk_id, "let's go over the quarterly report", 0)
+            .await
+            .unwrap();
+
+        let app_state = Arc::new(AppState {
+            vision_control: Arc::new(AtomicBool::new(true)),
+            health: Arc::new(HealthMonitor::default().with_events(EventLog::new(db.clone()))),
+            ..AppState::new(db)
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_grpc(
+            app_state.clone(),
+            listener,
+            plugins,
+            ApiLimits::default(),
+        ));
+        let client = ScreenpipeClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+        (app_state, client)
+    }
+
+    #[tokio::test]
+    async fn test_grpc_search() {
+        let (_, mut client) = setup().await;
+        let response = client
+            .search(proto::SearchRequest {
+                query: "quarterly".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.total, 2);
+        assert_eq!(response.results.len(), 2);
+        assert!(response.results.iter().any(|result| matches!(
+            &result.content,
+            Some(Content::Ocr(ocr)) if ocr.app_name == "firefox"
+        )));
+        assert!(response.results.iter().any(|result| matches!(
+            &result.content,
+            Some(Content::Audio(audio)) if audio.transcription.contains("quarterly")
+        )));
+
+        let response = client
+            .search(proto::SearchRequest {
+                content_type: proto::ContentType::Ocr as i32,
+                limit: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.total, 2);
+        assert_eq!(response.results.len(), 1);
+        let next = client
+            .search(proto::SearchRequest {
+                content_type: proto::ContentType::Ocr as i32,
+                limit: 1,
+                cursor: response.next_cursor,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(next.results.len(), 1);
+        assert_ne!(next.results, response.results);
+        assert!(next.next_cursor.is_none());
+
+        let status = client
+            .search(proto::SearchRequest {
+                query: "\"unterminated".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_plugins() {
+        let (state, mut client) = setup_with(ApiPlugins::new().with(DenyAll)).await;
+        let status = client
+            .stop_vision(proto::VisionRequest {})
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        assert!(state.vision_control.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_grpc_vision_control() {
+        let (state, mut client) = setup().await;
+        let status = client
+            .stop_vision(proto::VisionRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!status.is_running);
+        assert!(!state.vision_control.load(Ordering::SeqCst));
+
+        client.start_vision(proto::VisionRequest {}).await.unwrap();
+        let status = client
+            .get_vision_status(proto::VisionRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(status.is_running);
+        assert!(!status.is_paused);
+
+        // In /events like the REST API, written in the background
+        let mut kinds = Vec::new();
+        for _ in 0..50 {
+            let events = state.db.get_events(&[], None, None, 10, 0).await.unwrap();
+            kinds = events.iter().map(|event| event.kind).collect();
+            if kinds.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            kinds,
+            vec![EventKind::VisionStarted, EventKind::VisionStopped]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grpc_stream_ocr() {
+        let (state, mut client) = setup().await;
+        let mut stream = client
+            .stream_ocr(proto::StreamRequest {
+                after_id: None,
+                app_name: Some("code".to_string()),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        // Only what is stored after the stream started, of the app
+        insert_frame(&state.db, "slack", "standup in 5").await;
+        let frame_id = insert_frame(&state.db, "code", "fn main() {}").await;
+        let ocr = tokio::time::timeout(Duration::from_secs(5), stream.message())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(ocr.frame_id, frame_id);
+        assert_eq!(ocr.text, "fn main() {}");
+
+        // Resumes after what was received, from the start otherwise
+        let mut stream = client
+            .stream_ocr(proto::StreamRequest {
+                after_id: Some(0),
+                app_name: None,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let first = stream.message().await.unwrap().unwrap();
+        assert_eq!(first.text, "quarterly report draft");
+    }
+
+    #[tokio::test]
+    async fn test_grpc_stream_transcriptions() {
+        let (state, mut client) = setup().await;
+        let mut stream = client
+            .stream_transcriptions(proto::StreamRequest::default())
+            .await
+            .unwrap()
+            .into_inner();
+        let chunk_id = state.db.insert_audio_chunk("next.mp4").await.unwrap();
+        state
+            .db
+            .insert_audio_transcription(chunk_id, "ship it", 0)
+            .await
+            .unwrap();
+        let audio = tokio::time::timeout(Duration::from_secs(5), stream.message())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(audio.transcription, "ship it");
+        assert_eq!(audio.chunk_id, chunk_id);
+    }
+}
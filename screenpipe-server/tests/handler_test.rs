@@ -0,0 +1,818 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use chrono::{DateTime, Duration, Utc};
+    use screenpipe_server::{
+        offload_chunks, storage_routes, ApiStorage, AppState, BackupLocation, CapturedFrame,
+        ContentProcessor, ContentProcessors, DatabaseManager, EventKind, FrameRecord,
+        HealthCheckResponse, MemoryStorage,
+    };
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::time::Instant;
+    use tower::ServiceExt;
+
+    fn setup_app(storage: Arc<MemoryStorage>) -> Router {
+        storage_routes().with_state(Arc::new(app_state(storage)))
+    }
+
+    fn app_state(storage: Arc<MemoryStorage>) -> AppState<MemoryStorage> {
+        AppState::new(storage)
+    }
+
+    async fn get_json(app: &Router, uri: &str) -> (StatusCode, Value) {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    /// Three frames of two apps and two transcriptions, a minute apart
+    fn sample_storage(start: DateTime<Utc>) -> Arc<MemoryStorage> {
+        let storage = MemoryStorage::new();
+        storage.add_frame(start, "firefox", "quarterly report draft");
+        storage.add_frame(start + Duration::minutes(1), "slack", "lunch at noon?");
+        storage.add_frame(start + Duration::minutes(2), "firefox", "quarterly numbers");
+        storage.add_transcription(
+            start + Duration::minutes(3),
+            "let's go over the quarterly report",
+            Some("en"),
+        );
+        storage.add_transcription(start + Duration::minutes(4), "see you tomorrow", None);
+        Arc::new(storage)
+    }
+
+    fn texts(page: &Value) -> Vec<String> {
+        page["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                let content = &item["content"];
+                content["text"]
+                    .as_str()
+                    .or(content["transcription"].as_str())
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_filters() {
+        let start = Utc::now() - Duration::hours(1);
+        let app = setup_app(sample_storage(start));
+
+        let (status, page) = get_json(&app, "/search?q=quarterly").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["pagination"]["total"], 3);
+        // Most recent first, across content types
+        assert_eq!(
+            texts(&page),
+            vec![
+                "let's go over the quarterly report",
+                "quarterly numbers",
+                "quarterly report draft"
+            ]
+        );
+
+        let (_, page) = get_json(&app, "/search?q=quarterly&content_type=audio").await;
+        assert_eq!(page["pagination"]["total"], 1);
+        assert_eq!(page["data"][0]["type"], "Audio");
+        assert_eq!(page["data"][0]["content"]["language"], "en");
+
+        // app_name only searches the screen
+        let (_, page) = get_json(&app, "/search?q=quarterly&app_name=firefox").await;
+        assert_eq!(page["pagination"]["total"], 2);
+        assert!(page["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|item| item["content"]["app_name"] == "firefox"));
+
+        // engine keeps the text it read, latest is the default
+        let (_, page) = get_json(&app, "/search?q=quarterly&engine=Whisper").await;
+        assert_eq!(page["pagination"]["total"], 1);
+        assert_eq!(page["data"][0]["type"], "Audio");
+        let (_, page) = get_json(&app, "/search?q=quarterly&engine=latest").await;
+        assert_eq!(page["pagination"]["total"], 3);
+
+        let (status, page) = get_json(&app, "/search?q=quarterly&sort=relevance&limit=2").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["pagination"]["total"], 3);
+        assert!(page["pagination"]["next_cursor"].is_null());
+        assert!(page["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|item| item["content"]["relevance"] == 1.0));
+        let (status, _) = get_json(&app, "/search?q=quarterly&recency_boost=1d").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(
+            &app,
+            "/search?q=quarterly&sort=relevance&recency_boost=soon",
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        for uri in [
+            "/search?q=quarterly&sort=relevance&limit=1000&offset=4294967295",
+            "/search?q=quarterly&limit=4294967295",
+        ] {
+            let (status, _) = get_json(&app, uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        }
+
+        let end_time = (start + Duration::seconds(90)).to_rfc3339();
+        let uri = format!("/search?end_time={}", url_encode(&end_time));
+        let (_, page) = get_json(&app, &uri).await;
+        assert_eq!(
+            texts(&page),
+            vec!["lunch at noon?", "quarterly report draft"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_facets() {
+        let start = Utc::now() - Duration::hours(1);
+        let app = setup_app(sample_storage(start));
+
+        let (status, facets) = get_json(&app, "/search/facets?q=quarterly").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            facets["app_name"],
+            json!([{"value": "firefox", "count": 2}])
+        );
+        assert_eq!(
+            facets["content_type"],
+            json!([{"value": "ocr", "count": 2}, {"value": "audio", "count": 1}])
+        );
+        assert_eq!(facets["device"], json!([{"value": "local", "count": 3}]));
+        let days = facets["day"].as_array().unwrap();
+        assert_eq!(
+            days.iter()
+                .map(|day| day["count"].as_i64().unwrap())
+                .sum::<i64>(),
+            3
+        );
+
+        // Several content types add up
+        let (_, by_types) =
+            get_json(&app, "/search/facets?q=quarterly&content_type=ocr,audio").await;
+        assert_eq!(by_types, facets);
+        let (_, facets) = get_json(&app, "/search/facets?q=quarterly&app_name=firefox").await;
+        assert_eq!(
+            facets["content_type"],
+            json!([{"value": "ocr", "count": 2}])
+        );
+
+        let (status, _) = get_json(&app, "/search/facets?content_type=all,ocr").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(&app, "/search/facets?q=foo%20AND").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_search_pagination() {
+        let start = Utc::now() - Duration::hours(1);
+        let app = setup_app(sample_storage(start));
+
+        let (_, first) = get_json(&app, "/search?content_type=ocr&limit=2").await;
+        assert_eq!(texts(&first), vec!["quarterly numbers", "lunch at noon?"]);
+        assert_eq!(first["pagination"]["total"], 3);
+        let cursor = first["pagination"]["next_cursor"].as_str().unwrap();
+
+        let (_, second) = get_json(
+            &app,
+            &format!("/search?content_type=ocr&limit=2&cursor={}", cursor),
+        )
+        .await;
+        assert_eq!(texts(&second), vec!["quarterly report draft"]);
+        assert!(second["pagination"]["next_cursor"].is_null());
+
+        let (_, by_offset) = get_json(&app, "/search?content_type=ocr&limit=2&offset=2").await;
+        assert_eq!(texts(&by_offset), texts(&second));
+
+        let (status, _) =
+            get_json(&app, &format!("/search?limit=2&offset=2&cursor={}", cursor)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(&app, "/search?cursor=not-a-cursor").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_search_content_type_list() {
+        let start = Utc::now() - Duration::hours(1);
+        let app = setup_app(sample_storage(start));
+
+        let (status, page) =
+            get_json(&app, "/search?q=quarterly&content_type=ocr,audio&limit=1").await;
+        assert_eq!(status, StatusCode::OK);
+        let by_type = page["by_type"].as_object().unwrap();
+        assert_eq!(by_type.len(), 2);
+        assert_eq!(texts(&by_type["ocr"]), vec!["quarterly numbers"]);
+        assert_eq!(by_type["ocr"]["pagination"]["total"], 2);
+        assert!(by_type["ocr"]["pagination"]["next_cursor"].is_string());
+        assert_eq!(
+            texts(&by_type["audio"]),
+            vec!["let's go over the quarterly report"]
+        );
+        assert_eq!(by_type["audio"]["pagination"]["total"], 1);
+
+        // A single type keeps the plain page
+        let (_, page) = get_json(&app, "/search?q=quarterly&content_type=audio,audio").await;
+        assert_eq!(page["pagination"]["total"], 1);
+
+        // app_name only searches the screen
+        let (_, page) = get_json(&app, "/search?content_type=ocr,audio&app_name=slack").await;
+        let by_type = page["by_type"].as_object().unwrap();
+        assert_eq!(by_type.keys().collect::<Vec<_>>(), vec!["ocr"]);
+        assert_eq!(texts(&by_type["ocr"]), vec!["lunch at noon?"]);
+
+        for uri in [
+            "/search?content_type=ocr,clipboard",
+            "/search?content_type=all,audio",
+            "/search?content_type=ocr,audio&cursor=abc",
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_parameter_codes() {
+        let app = setup_app(sample_storage(Utc::now()));
+
+        for (uri, code) in [
+            ("/search?start_time=yesterday", "invalid_time"),
+            ("/search?end_time=2024-13-01T00:00:00Z", "invalid_time"),
+            ("/marks?start_time=2024-05-01", "invalid_time"),
+            ("/search?content_type=video", "invalid_content_type"),
+            ("/search?content_type=all,audio", "invalid_content_type"),
+            ("/search?limit=many", "invalid_request"),
+            ("/marks/abc", "invalid_request"),
+            ("/timeline?day=2024-02-30", "invalid_time"),
+            ("/events?kind=error,crash", "invalid_request"),
+        ] {
+            let (status, body) = get_json(&app, uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+            assert_eq!(body["code"], code, "{}: {}", uri, body);
+            assert!(body["error"].is_string(), "{}", uri);
+        }
+
+        let (_, body) = get_json(&app, "/search?start_time=yesterday").await;
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("expected RFC 3339"));
+
+        let (status, body) = get_json(&app, "/marks/12345").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn test_search_error_mapping() {
+        let storage = sample_storage(Utc::now());
+        let app = setup_app(storage.clone());
+
+        // Syntax errors are the client's
+        let (status, body) = get_json(&app, "/search?q=%22unterminated").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid search query"));
+        assert_eq!(body["code"], "invalid_search_query");
+        assert!(body["details"]["syntax"].is_string());
+
+        storage.fail_with("database is locked");
+        let (status, body) = get_json(&app, "/search?q=quarterly").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "internal");
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("database is locked"));
+    }
+
+    #[tokio::test]
+    async fn test_health_and_ingest() {
+        let storage = Arc::new(MemoryStorage::new());
+        let app = setup_app(storage.clone());
+
+        let (_, health) = get_json(&app, "/health").await;
+        let health: HealthCheckResponse = serde_json::from_value(health).unwrap();
+        assert!(health.last_frame_timestamp.is_none());
+
+        let now = Utc::now();
+        storage.add_frame(now, "code", "fn main()");
+        let (_, health) = get_json(&app, "/health").await;
+        let health: HealthCheckResponse = serde_json::from_value(health).unwrap();
+        assert_eq!(health.last_frame_timestamp, Some(now));
+
+        let batch = json!({
+            "device_id": "phone",
+            "frames": [{
+                "timestamp": now,
+                "file_path": "phone/video_1.mp4",
+                "offset_index": 0,
+                "text": "boarding pass"
+            }]
+        });
+        for (frames_inserted, frames_skipped) in [(1, 0), (0, 1)] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/ingest")
+                        .header("content-type", "application/json")
+                        .body(Body::from(batch.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let summary: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(summary["frames_inserted"], frames_inserted);
+            assert_eq!(summary["frames_skipped"], frames_skipped);
+        }
+
+        // Ingested frames are searchable, but say nothing about this machine's recording
+        let (_, page) = get_json(&app, "/search?q=boarding").await;
+        assert_eq!(page["pagination"]["total"], 1);
+        let (_, health) = get_json(&app, "/health").await;
+        let health: HealthCheckResponse = serde_json::from_value(health).unwrap();
+        assert_eq!(health.last_frame_timestamp, Some(now));
+    }
+
+    /// Drops the text of boarding passes
+    struct BoardingPassFilter;
+
+    impl ContentProcessor for BoardingPassFilter {
+        fn name(&self) -> &str {
+            "boarding-pass-filter"
+        }
+
+        fn process_frame(&self, frame: FrameRecord) -> Option<FrameRecord> {
+            (!frame.text.contains("boarding pass")).then_some(frame)
+        }
+    }
+
+    struct Panicking;
+
+    impl ContentProcessor for Panicking {
+        fn name(&self) -> &str {
+            "panicking"
+        }
+
+        fn process_frame(&self, _: FrameRecord) -> Option<FrameRecord> {
+            panic!("broken processor");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ingest_processed() {
+        let storage = Arc::new(MemoryStorage::new());
+        let processors = ContentProcessors::new()
+            .with(Panicking)
+            .with(BoardingPassFilter);
+        let app = storage_routes().with_state(Arc::new(AppState {
+            content_processors: processors,
+            ..app_state(storage)
+        }));
+
+        let (_, health) = get_json(&app, "/health").await;
+        let health: HealthCheckResponse = serde_json::from_value(health).unwrap();
+        assert!(health.content_processor_failures.is_empty());
+
+        let batch = json!({
+            "device_id": "phone",
+            "frames": [{
+                "timestamp": Utc::now(),
+                "file_path": "phone/video_1.mp4",
+                "offset_index": 0,
+                "text": "boarding pass"
+            }, {
+                "timestamp": Utc::now(),
+                "file_path": "phone/video_1.mp4",
+                "offset_index": 1,
+                "text": "gate B12"
+            }]
+        });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/ingest")
+                    .header("content-type", "application/json")
+                    .body(Body::from(batch.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let (_, page) = get_json(&app, "/search?q=boarding").await;
+        assert_eq!(page["pagination"]["total"], 0);
+        let (_, page) = get_json(&app, "/search?q=gate").await;
+        assert_eq!(page["pagination"]["total"], 1);
+        let (_, health) = get_json(&app, "/health").await;
+        let health: HealthCheckResponse = serde_json::from_value(health).unwrap();
+        assert_eq!(
+            health.content_processor_failures,
+            BTreeMap::from([("panicking".to_string(), 2)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_audio_chunk_range_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("audio.mp4");
+        let content: Vec<u8> = (0..100).collect();
+        std::fs::write(&file_path, &content).unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let secret = outside.path().join("secret.mp4");
+        std::fs::write(&secret, b"secret").unwrap();
+        let storage = Arc::new(MemoryStorage::new());
+        // Recorded by this machine, the same path sent by another device, and a chunk
+        // outside of the media directory
+        for (device_id, path) in [
+            ("local", &file_path),
+            ("phone", &file_path),
+            ("local", &secret),
+        ] {
+            storage
+                .ingest(
+                    &serde_json::from_value(json!({
+                        "device_id": device_id,
+                        "transcriptions": [{
+                            "timestamp": Utc::now(),
+                            "file_path": path.to_str().unwrap(),
+                            "transcription": "hello"
+                        }]
+                    }))
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+        let app = storage_routes().with_state(Arc::new(AppState {
+            media_dir: Some(dir.path().to_path_buf()),
+            ..app_state(storage)
+        }));
+
+        let get = |range: Option<&str>, uri: &str| {
+            let mut request = Request::builder().uri(uri);
+            if let Some(range) = range {
+                request = request.header("range", range);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = get(None, "/audio/1").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "audio/mp4");
+        assert_eq!(response.headers()["accept-ranges"], "bytes");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.as_ref(), content.as_slice());
+
+        let response = get(Some("bytes=10-19"), "/audio/1").await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()["content-range"], "bytes 10-19/100");
+        assert_eq!(response.headers()["content-type"], "audio/mp4");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.as_ref(), &content[10..20]);
+
+        let response = get(Some("bytes=200-"), "/audio/1").await.unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        // Ingested chunks name files of their device
+        let response = get(None, "/audio/2").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        // Outside of the media directory
+        let response = get(None, "/audio/3").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        // No such chunk
+        let response = get(None, "/audio/4").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_offloaded_audio_chunk_fetched_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("audio.mp4");
+        let content: Vec<u8> = (0..100).collect();
+        std::fs::write(&file_path, &content).unwrap();
+        let db = DatabaseManager::new(&dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        db.insert_audio_chunk(&file_path.to_string_lossy())
+            .await
+            .unwrap();
+        let remote = BackupLocation::Directory(dir.path().join("remote"));
+        offload_chunks(&db, &remote, Utc::now() + Duration::minutes(1))
+            .await
+            .unwrap();
+        let storage = Arc::new(MemoryStorage::new());
+        storage
+            .ingest(
+                &serde_json::from_value(json!({
+                    "device_id": "local",
+                    "transcriptions": [{
+                        "timestamp": Utc::now(),
+                        "file_path": file_path.to_str().unwrap(),
+                        "transcription": "hello"
+                    }]
+                }))
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+        let get = |state: AppState<MemoryStorage>| {
+            storage_routes().with_state(Arc::new(state)).oneshot(
+                Request::builder()
+                    .uri("/audio/1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let media_dir = Some(dir.path().to_path_buf());
+        // Without --offload-to there's nowhere to fetch it from
+        let response = get(AppState {
+            media_dir: media_dir.clone(),
+            ..app_state(storage.clone())
+        })
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let mut state = AppState {
+            media_dir,
+            ..app_state(storage)
+        };
+        state.offload = Some(Arc::new(remote));
+        let response = get(state).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.as_ref(), content.as_slice());
+        assert_eq!(std::fs::read(&file_path).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_marks() {
+        let start = Utc::now() - Duration::hours(1);
+        let storage = sample_storage(start);
+        let app = setup_app(storage.clone());
+
+        let post = |body: Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/marks")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let response = post(json!({"name": "  bug repro "})).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mark: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(mark["name"], "bug repro");
+        assert_eq!(mark["capture_requested"], true);
+
+        let response = post(json!({"name": ""})).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Marked during the sample content, to read it back with the mark
+        storage
+            .insert_mark("report review", start + Duration::seconds(150))
+            .await
+            .unwrap();
+
+        let (_, marks) = get_json(&app, "/marks").await;
+        let names: Vec<&str> = marks
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|mark| mark["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["bug repro", "report review"]);
+        let (_, marks) = get_json(&app, "/marks?q=REPORT").await;
+        assert_eq!(marks.as_array().unwrap().len(), 1);
+
+        let (status, page) = get_json(&app, "/marks/2?window=60").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["mark"]["name"], "report review");
+        assert_eq!(
+            texts(&json!({"data": page["content"]})),
+            vec!["let's go over the quarterly report", "quarterly numbers"]
+        );
+
+        let (status, _) = get_json(&app, "/marks/3").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let start = Utc::now() - Duration::hours(1);
+        let storage = Arc::new(MemoryStorage::new());
+        storage.add_event(start, EventKind::RecordingStarted, "Recording started");
+        storage.add_event(
+            start + Duration::minutes(1),
+            EventKind::DeviceStarted,
+            "Started recording MacBook Pro Microphone (input)",
+        );
+        storage.add_event(
+            start + Duration::minutes(2),
+            EventKind::Error,
+            "Video recording error",
+        );
+        storage.add_event(
+            start + Duration::minutes(3),
+            EventKind::TaskRestarted,
+            "Task panicked, restarted",
+        );
+        let app = setup_app(storage);
+
+        let kinds = |page: &Value| -> Vec<String> {
+            page["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|event| event["kind"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        // Most recent first
+        let (status, page) = get_json(&app, "/events?limit=2").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(kinds(&page), vec!["task_restarted", "error"]);
+        assert_eq!(page["pagination"]["total"], 4);
+        let (_, page) = get_json(&app, "/events?limit=2&offset=2").await;
+        assert_eq!(kinds(&page), vec!["device_started", "recording_started"]);
+
+        let (_, page) = get_json(&app, "/events?kind=error,%20task_restarted").await;
+        assert_eq!(kinds(&page), vec!["task_restarted", "error"]);
+        assert_eq!(page["pagination"]["total"], 2);
+
+        let uri = format!(
+            "/events?end_time={}",
+            url_encode(&(start + Duration::seconds(90)).to_rfc3339())
+        );
+        let (_, page) = get_json(&app, &uri).await;
+        assert_eq!(kinds(&page), vec!["device_started", "recording_started"]);
+    }
+
+    #[tokio::test]
+    async fn test_capture_now() {
+        let storage = Arc::new(MemoryStorage::new());
+        let post = |app: Router| {
+            app.oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/vision/capture_now")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        // Vision isn't running
+        let response = post(setup_app(storage.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let mut state = app_state(storage);
+        state.vision_control = Arc::new(AtomicBool::new(true));
+        let capture_requests = state.capture_requests.clone();
+        let app = storage_routes().with_state(Arc::new(state));
+        // Stands in for the recording loop storing the requested frame
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            capture_requests.complete(
+                Instant::now(),
+                &CapturedFrame {
+                    frame_id: 42,
+                    timestamp: Utc::now(),
+                    app_name: "firefox".to_string(),
+                    text: "quarterly report".to_string(),
+                },
+            );
+        });
+        let response = post(app).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let frame: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(frame["frame_id"], 42);
+        assert_eq!(frame["app_name"], "firefox");
+        assert_eq!(frame["text"], "quarterly report");
+    }
+
+    #[tokio::test]
+    async fn test_download_zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let video = dir.path().join("video.mp4");
+        let audio = dir.path().join("audio.mp4");
+        std::fs::write(&video, vec![7u8; 200_000]).unwrap();
+        std::fs::write(&audio, b"audio bytes").unwrap();
+        let now = Utc::now();
+        let storage = Arc::new(MemoryStorage::new());
+        // Recorded by this machine
+        storage
+            .ingest(
+                &serde_json::from_value(json!({
+                    "device_id": "local",
+                    "frames": [
+                        {"timestamp": now, "file_path": video, "offset_index": 0, "text": "invoice"},
+                        {"timestamp": now, "file_path": video, "offset_index": 1, "text": "invoice total"},
+                        {"timestamp": now, "file_path": dir.path().join("gone.mp4"), "offset_index": 0, "text": "gone"}
+                    ],
+                    "transcriptions": [
+                        {"timestamp": now, "file_path": audio, "transcription": "hello"}
+                    ]
+                }))
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+        // Sent by another device, naming a file of this machine
+        storage
+            .ingest(
+                &serde_json::from_value(json!({
+                    "device_id": "phone",
+                    "frames": [{"timestamp": now, "file_path": video, "offset_index": 0, "text": "phone"}]
+                }))
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+        let app = storage_routes().with_state(Arc::new(AppState {
+            media_dir: Some(dir.path().to_path_buf()),
+            ..app_state(storage)
+        }));
+
+        let post = |body: Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/download")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let response = post(json!({"frame_ids": [1, 3, 42, 4], "search": {"q": "hello"}}))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/zip");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names, vec!["audio/audio.mp4", "manifest.json", "video/video.mp4"]);
+        let mut content = Vec::new();
+        std::io::Read::read_to_end(
+            &mut archive.by_name("video/video.mp4").unwrap(),
+            &mut content,
+        )
+        .unwrap();
+        assert_eq!(content, vec![7u8; 200_000]);
+
+        let manifest: Value =
+            serde_json::from_reader(archive.by_name("manifest.json").unwrap()).unwrap();
+        let items = manifest["items"].as_array().unwrap();
+        assert_eq!(items.len(), 5);
+        assert_eq!(items[0]["file"], "video/video.mp4");
+        assert_eq!(items[0]["offset_index"], 0);
+        // Gone, no such frame, and ingested from another device
+        assert_eq!(items[1]["error"], "Not found");
+        assert_eq!(items[2]["error"], "Not found");
+        assert_eq!(items[3]["error"], "Not found");
+        assert_eq!(items[4]["type"], "audio");
+        assert_eq!(items[4]["file"], "audio/audio.mp4");
+
+        let response = post(json!({"frame_ids": []})).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    fn url_encode(value: &str) -> String {
+        value.replace('+', "%2B").replace(':', "%3A")
+    }
+}
@@ -0,0 +1,205 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use axum::routing::get;
+    use axum::Router;
+    use chrono::{Duration, Utc};
+    use screenpipe_server::{
+        health_check, AppState, DatabaseManager, HealthCheckResponse, HealthMonitor,
+        HealthThresholds, Subsystem,
+    };
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    /// App started long ago, with a frame and an audio chunk just recorded
+    async fn setup_app(health: Arc<HealthMonitor>) -> Router {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("foo").await.unwrap();
+        db.insert_ocr_text(frame_id, "Test OCR", "{}", "{}", "{}", "foo")
+            .await
+            .unwrap();
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.wav").await.unwrap();
+        db.insert_audio_transcription(audio_chunk_id, "Test Audio", 0)
+            .await
+            .unwrap();
+
+        let app_state = Arc::new(AppState {
+            vision_control: Arc::new(AtomicBool::new(true)),
+            app_start_time: Utc::now() - Duration::seconds(600),
+            health,
+            ..AppState::new(db)
+        });
+        Router::new()
+            .route("/health", get(health_check))
+            .with_state(app_state)
+    }
+
+    async fn get_health(app: Router) -> HealthCheckResponse {
+        let response = app
+            .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn test_health_monitor() {
+        let health = HealthMonitor::default();
+        assert_eq!(health.whisper_backlog(), 0);
+
+        health.chunk_recorded("MacBook Pro Microphone (input)");
+        health.chunk_recorded("MacBook Pro Microphone (input)");
+        health.chunk_recorded("Display 1 (output)");
+        health.chunk_transcribed();
+        assert_eq!(health.whisper_backlog(), 2);
+        assert_eq!(health.audio_devices().len(), 2);
+
+        health.remove_audio_device("Display 1 (output)");
+        assert_eq!(
+            health.audio_devices().into_keys().collect::<Vec<_>>(),
+            vec!["MacBook Pro Microphone (input)"]
+        );
+
+        // More transcriptions than chunks, e.g. of synthetic audio
+        for _ in 0..3 {
+            health.chunk_transcribed();
+        }
+        assert_eq!(health.whisper_backlog(), 0);
+
+        assert!(health.last_error(Subsystem::Ocr).is_none());
+        health.record_error(Subsystem::Ocr, "Tesseract failed");
+        health.record_error(Subsystem::Ocr, "Tesseract failed again");
+        assert_eq!(
+            health.last_error(Subsystem::Ocr).unwrap().message,
+            "Tesseract failed again"
+        );
+        assert!(health.last_error(Subsystem::Audio).is_none());
+
+        health.task_restarted(Subsystem::Vision, "Task panicked, restarted");
+        health.task_restarted(Subsystem::Vision, "Task stalled, restarted");
+        assert_eq!(health.restarts(Subsystem::Vision), 2);
+        assert_eq!(
+            health.last_error(Subsystem::Vision).unwrap().message,
+            "Task stalled, restarted"
+        );
+        assert_eq!(health.restarts(Subsystem::Audio), 0);
+
+        // No data directory, no free space
+        assert!(health.free_disk_bytes().is_none());
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let thresholds: HealthThresholds = toml::from_str("max_ocr_queue = 5").unwrap();
+        assert_eq!(thresholds.max_ocr_queue, 5);
+        assert_eq!(thresholds.frame_stale_secs, 60);
+        assert!(thresholds.validate().is_ok());
+
+        assert!(toml::from_str::<HealthThresholds>("max_queue = 5").is_err());
+        for invalid in ["frame_stale_secs = 0", "min_free_disk_gb = -1.0"] {
+            let thresholds: HealthThresholds = toml::from_str(invalid).unwrap();
+            assert!(thresholds.validate().is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_subsystems() {
+        // Whatever the free space of the machine running the tests
+        let health = Arc::new(HealthMonitor::new(
+            HealthThresholds {
+                min_free_disk_gb: 0.0,
+                ..Default::default()
+            },
+            Some(std::env::temp_dir()),
+        ));
+        health.chunk_recorded("MacBook Pro Microphone (input)");
+        let response = get_health(setup_app(health).await).await;
+
+        assert_eq!(response.status, "Healthy");
+        let names: Vec<Subsystem> = response.subsystems.iter().map(|s| s.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                Subsystem::Vision,
+                Subsystem::Ocr,
+                Subsystem::Audio,
+                Subsystem::Transcription,
+                Subsystem::Database,
+                Subsystem::Disk,
+            ]
+        );
+        assert!(response
+            .subsystems
+            .iter()
+            .all(|s| s.last_error.is_none() && s.restarts == 0));
+        assert_eq!(response.audio_devices.len(), 1);
+        assert_eq!(response.audio_devices[0].status, "OK");
+        assert_eq!(response.whisper_backlog, 1);
+        assert!(response.free_disk_bytes.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_health_degraded() {
+        let health = Arc::new(HealthMonitor::new(
+            HealthThresholds {
+                max_ocr_queue: 2,
+                ..Default::default()
+            },
+            None,
+        ));
+        health.set_ocr_queue_depth(3);
+        health.record_error(Subsystem::Transcription, "Whisper model failed to load");
+        health.task_restarted(Subsystem::Audio, "Task panicked, restarted");
+        let response = get_health(setup_app(health).await).await;
+
+        assert_eq!(response.status, "Degraded");
+        assert!(response.message.contains("3 frames waiting for OCR"));
+        let ocr = &response.subsystems[1];
+        assert_eq!(ocr.status, "Degraded");
+        let transcription = &response.subsystems[3];
+        assert_eq!(transcription.status, "OK");
+        assert_eq!(
+            transcription.last_error.as_ref().unwrap().message,
+            "Whisper model failed to load"
+        );
+        // Restarted, but recording again
+        let audio = &response.subsystems[2];
+        assert_eq!(audio.status, "OK");
+        assert_eq!(audio.restarts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_health_stale_audio_device() {
+        let health = Arc::new(HealthMonitor::new(
+            HealthThresholds {
+                audio_stale_secs: 1,
+                ..Default::default()
+            },
+            None,
+        ));
+        health.chunk_recorded("Display 1 (output)");
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        // Stale even while another device records
+        health.chunk_recorded("MacBook Pro Microphone (input)");
+        let response = get_health(setup_app(health).await).await;
+
+        assert_eq!(response.status, "Unhealthy");
+        assert_eq!(response.audio_status, "Stale");
+        let stale: Vec<&str> = response
+            .audio_devices
+            .iter()
+            .filter(|device| device.status == "Stale")
+            .map(|device| device.device.as_str())
+            .collect();
+        assert_eq!(stale, vec!["Display 1 (output)"]);
+        assert!(response.subsystems[2]
+            .message
+            .contains("Display 1 (output)"));
+    }
+}
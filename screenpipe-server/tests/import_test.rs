@@ -0,0 +1,209 @@
+#[cfg(all(test, feature = "integrations"))]
+mod tests {
+    use screenpipe_integrations::import::{import_history, ImportSource, ImportSummary};
+    use screenpipe_server::{ContentType, DatabaseManager, SearchResult};
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+    use sqlx::Executor;
+    use std::path::Path;
+
+    /// Frames added to the Rewind fixture, more than a page of the import
+    const REWIND_GENERATED_FRAMES: &str = r#"
+        WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1500)
+        INSERT INTO frame (id, createdAt, imageFileName, segmentId)
+        SELECT 100 + i, strftime('%Y-%m-%dT%H:%M:%f', '2024-03-05', '+' || i || ' seconds'),
+            i || '.jpg', 2
+        FROM n;
+        WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1500)
+        INSERT INTO searchRanking (rowid, text) SELECT 100 + i, 'generated frame ' || i FROM n;
+        WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1500)
+        INSERT INTO doc_segment (docid, segmentId, frameId) SELECT 100 + i, 2, 100 + i FROM n;
+    "#;
+
+    /// SQLite database at `path` made of the statements of `sql`
+    async fn fixture_database(path: &Path, sql: &[&str]) {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        for sql in sql {
+            pool.execute(*sql).await.unwrap();
+        }
+        pool.close().await;
+    }
+
+    async fn search(db: &DatabaseManager, q: &str, content_type: ContentType) -> Vec<SearchResult> {
+        db.search(q, content_type, 100, 0, None, None, None, None, None, None)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_import_activitywatch_export() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        let export_path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/activitywatch_export.json"
+        ));
+
+        let summary = import_history(ImportSource::ActivityWatch, export_path, &db)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                frames_inserted: 2,
+                ..Default::default()
+            }
+        );
+        // Importing the same export again adds nothing
+        let summary = import_history(ImportSource::ActivityWatch, export_path, &db)
+            .await
+            .unwrap();
+        assert_eq!(summary.frames_inserted, 0);
+        assert_eq!(summary.frames_skipped, 2);
+
+        let results = db
+            .search(
+                "screenpipe",
+                ContentType::OCR,
+                100,
+                0,
+                None,
+                None,
+                Some("Code"),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let SearchResult::OCR(result) = &results[0] else {
+            panic!("Expected OCR result");
+        };
+        assert_eq!(result.ocr_text, "main.rs - screenpipe");
+        assert_eq!(result.ocr_engine, "activitywatch");
+
+        let results = db
+            .search(
+                "docs",
+                ContentType::OCR,
+                100,
+                0,
+                None,
+                None,
+                Some("firefox"),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_rewind() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        let rewind_path = temp_dir.path().join("db-enc.sqlite3");
+        fixture_database(
+            &rewind_path,
+            &[include_str!("fixtures/rewind.sql"), REWIND_GENERATED_FRAMES],
+        )
+        .await;
+
+        let summary = import_history(ImportSource::Rewind, &rewind_path, &db)
+            .await
+            .unwrap();
+        // The frame without text is left out
+        assert_eq!(
+            summary,
+            ImportSummary {
+                frames_inserted: 1502,
+                transcriptions_inserted: 1,
+                ..Default::default()
+            }
+        );
+        let summary = import_history(ImportSource::Rewind, &rewind_path, &db)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                frames_skipped: 1502,
+                transcriptions_skipped: 1,
+                ..Default::default()
+            }
+        );
+
+        let results = search(&db, "revenue", ContentType::OCR).await;
+        assert_eq!(results.len(), 1);
+        let SearchResult::OCR(result) = &results[0] else {
+            panic!("Expected OCR result");
+        };
+        assert_eq!(result.app_name, "com.apple.Safari");
+        assert_eq!(result.ocr_engine, "rewind");
+        assert_eq!(
+            result.timestamp.to_rfc3339(),
+            "2024-03-04T09:15:02.123+00:00"
+        );
+        // Both sides of the page boundary
+        assert_eq!(
+            search(&db, "\"generated frame 1000\"", ContentType::OCR)
+                .await
+                .len(),
+            1
+        );
+        assert_eq!(
+            search(&db, "\"generated frame 1001\"", ContentType::OCR)
+                .await
+                .len(),
+            1
+        );
+
+        let results = search(&db, "ship", ContentType::Audio).await;
+        assert_eq!(results.len(), 1);
+        let SearchResult::Audio(result) = &results[0] else {
+            panic!("Expected audio result");
+        };
+        assert_eq!(result.transcription, "let's ship it");
+    }
+
+    #[tokio::test]
+    async fn test_import_windows_recall() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        let recall_path = temp_dir.path().join("ukg.db");
+        fixture_database(&recall_path, &[include_str!("fixtures/windows_recall.sql")]).await;
+
+        let summary = import_history(ImportSource::WindowsRecall, &recall_path, &db)
+            .await
+            .unwrap();
+        assert_eq!(summary.frames_inserted, 3);
+
+        let results = search(&db, "invoice", ContentType::OCR).await;
+        assert_eq!(results.len(), 1);
+        let SearchResult::OCR(result) = &results[0] else {
+            panic!("Expected OCR result");
+        };
+        assert_eq!(result.app_name, "Outlook");
+        assert_eq!(result.ocr_engine, "windows-recall");
+        assert_eq!(result.timestamp.timestamp_millis(), 1717232400000);
+
+        // Apps without a name go by their path, captures without an app are kept
+        let results = search(&db, "passport", ContentType::OCR).await;
+        let SearchResult::OCR(result) = &results[0] else {
+            panic!("Expected OCR result");
+        };
+        assert_eq!(result.app_name, r"C:\Windows\System32\notepad.exe");
+        assert_eq!(search(&db, "loose", ContentType::OCR).await.len(), 1);
+    }
+}
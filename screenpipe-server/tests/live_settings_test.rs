@@ -0,0 +1,402 @@
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+    use log::LevelFilter;
+    use screenpipe_server::{
+        load_config_file, max_log_level, monitor_config, watch_config_file, ApiLimits, ConfigFile,
+        HealthThresholds, LiveSettings,
+    };
+    use screenpipe_vision::RegionLength;
+    use std::time::Duration;
+    use tokio::sync::watch;
+
+    fn base() -> LiveSettings {
+        LiveSettings {
+            fps: 1.0,
+            ignored_apps: Vec::new(),
+            max_disk_usage_gb: Some(50.0),
+            log_level: LevelFilter::Info,
+        }
+    }
+
+    #[test]
+    fn test_config_file_overrides_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        assert_eq!(
+            load_config_file(&path, &base(), &ConfigFile::default()).unwrap(),
+            base()
+        );
+
+        std::fs::write(
+            &path,
+            "fps = 0.5\nignored_apps = [\"1Password\"]\nlog_level = \"debug\"\n",
+        )
+        .unwrap();
+        let settings = load_config_file(&path, &base(), &ConfigFile::default()).unwrap();
+        assert_eq!(settings.fps, 0.5);
+        assert!(settings.is_ignored("1password"));
+        assert!(!settings.is_ignored("Safari"));
+        // Not in the file, from the flags
+        assert_eq!(settings.max_disk_usage_gb, Some(50.0));
+        assert_eq!(settings.log_level, LevelFilter::Debug);
+
+        for invalid in ["fps = 0", "log_level = \"loud\"", "fsp = 2.0", "fps ="] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(
+                load_config_file(&path, &base(), &ConfigFile::default()).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_flags_win_over_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "fps = 0.5\nmax_disk_usage = 10\nlog_level = \"warn\"\n",
+        )
+        .unwrap();
+        // --fps 2 --debug
+        let flags = ConfigFile {
+            fps: Some(2.0),
+            log_level: Some("debug".to_string()),
+            ..Default::default()
+        };
+        let settings = load_config_file(&path, &base(), &flags).unwrap();
+        assert_eq!(settings.fps, 2.0);
+        assert_eq!(settings.log_level, LevelFilter::Debug);
+        // Not given, from the file
+        assert_eq!(settings.max_disk_usage_gb, Some(10.0));
+    }
+
+    #[test]
+    fn test_max_log_level() {
+        assert_eq!(max_log_level(LevelFilter::Info, None), LevelFilter::Info);
+        // RUST_LOG isn't capped by the settings
+        assert_eq!(
+            max_log_level(LevelFilter::Info, Some("info,screenpipe_audio=trace")),
+            LevelFilter::Trace
+        );
+        assert_eq!(
+            max_log_level(LevelFilter::Debug, Some("warn")),
+            LevelFilter::Debug
+        );
+    }
+
+    #[test]
+    fn test_config_file_startup_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        assert_eq!(ConfigFile::read(&path).unwrap(), ConfigFile::default());
+
+        std::fs::write(
+            &path,
+            "port = 3035\nocr_engine = \"tesseract\"\naudio_devices = [\"MacBook Pro Microphone (input)\"]\nfps = 0.2\nidle_fps = 0.1\n",
+        )
+        .unwrap();
+        let file = ConfigFile::read(&path).unwrap();
+        assert_eq!(file.port, Some(3035));
+        assert_eq!(file.idle_fps, Some(0.1));
+        assert_eq!(file.ocr_engine.as_deref(), Some("tesseract"));
+        assert_eq!(
+            file.audio_devices,
+            Some(vec!["MacBook Pro Microphone (input)".to_string()])
+        );
+        // Startup keys don't change the settings applied while running
+        assert_eq!(
+            load_config_file(&path, &base(), &ConfigFile::default())
+                .unwrap()
+                .fps,
+            0.2
+        );
+
+        std::fs::write(
+            &path,
+            "ocr_languages = [\"eng\", \"deu\"]\n\n[app_ocr_languages]\nWeChat = [\"chi_sim\", \"eng\"]\n",
+        )
+        .unwrap();
+        let file = ConfigFile::read(&path).unwrap();
+        assert_eq!(
+            file.app_ocr_languages.unwrap()["WeChat"],
+            ["chi_sim".to_string(), "eng".to_string()]
+        );
+
+        std::fs::write(&path, "follow_default_audio = true\n").unwrap();
+        assert_eq!(
+            ConfigFile::read(&path).unwrap().follow_default_audio,
+            Some(true)
+        );
+
+        for invalid in [
+            "offload_after_days = 0",
+            "idle_fps = 0",
+            "follow_default_audio = true\naudio_devices = [\"#1\"]",
+        ] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(ConfigFile::read(&path).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_config_file_monitors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [[monitors]]
+            name = "Built-in Retina Display"
+            enabled = false
+
+            [[monitors]]
+            name = "DELL U2720Q"
+            fps = 0.5
+            crop = "0,0,40,0"
+            "#,
+        )
+        .unwrap();
+        let monitors = ConfigFile::read(&path).unwrap().monitors.unwrap();
+        assert!(
+            !monitor_config(&monitors, "built-in retina display")
+                .unwrap()
+                .enabled
+        );
+        let dell = monitor_config(&monitors, "DELL U2720Q").unwrap();
+        assert!(dell.enabled);
+        assert_eq!(dell.fps, Some(0.5));
+        assert_eq!(
+            dell.capture_crop().unwrap().unwrap().bottom,
+            RegionLength::Pixels(40)
+        );
+        assert!(monitor_config(&monitors, "LG UltraFine").is_none());
+
+        for invalid in [
+            "[[monitors]]\nname = \"DELL U2720Q\"\nfps = 0",
+            "[[monitors]]\nname = \"DELL U2720Q\"\ncrop = \"0,0,40\"",
+            "[[monitors]]\nname = \"DELL U2720Q\"\nscale = 2",
+        ] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(ConfigFile::read(&path).is_err(), "{}", invalid);
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_config_file_audio_device_configs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [audio_device_configs."Jabra Speak 510 (input)"]
+            sample_rate = 16000
+            channels = 1
+            gain = 2.0
+
+            [audio_device_configs."MacBook Pro Microphone (input)"]
+            chunk_duration_secs = 60
+            noise_suppression = true
+            "#,
+        )
+        .unwrap();
+        let configs = ConfigFile::read(&path)
+            .unwrap()
+            .audio_device_configs
+            .unwrap();
+        let jabra = &configs["Jabra Speak 510 (input)"];
+        assert_eq!(jabra.sample_rate, Some(16000));
+        assert_eq!(jabra.gain, Some(2.0));
+        assert_eq!(jabra.chunk_duration_secs, None);
+        assert!(!jabra.preprocessing().is_enabled());
+        let macbook = configs["MacBook Pro Microphone (input)"].preprocessing();
+        assert!(macbook.noise_suppression && !macbook.auto_gain);
+        assert_eq!(
+            configs["MacBook Pro Microphone (input)"].chunk_duration(Duration::from_secs(30)),
+            Duration::from_secs(60)
+        );
+
+        for invalid in [
+            "[audio_device_configs.\"Jabra Speak 510 (input)\"]\nchannels = 0",
+            "[audio_device_configs.\"Jabra Speak 510 (input)\"]\ngain = -1.0",
+            "[audio_device_configs.\"Jabra Speak 510 (input)\"]\nvolume = 2",
+        ] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(ConfigFile::read(&path).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_config_file_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [digest]
+            slack_webhook_url = "https://hooks.slack.com/services/T0/B0/X"
+            time = "08:30"
+            keywords = ["invoice", "ACME"]
+            "#,
+        )
+        .unwrap();
+        let digest = ConfigFile::read(&path).unwrap().digest.unwrap();
+        assert_eq!(
+            digest.send_time().unwrap(),
+            NaiveTime::from_hms_opt(8, 30, 0).unwrap()
+        );
+        assert_eq!(
+            digest.webhook_url("slack"),
+            Some("https://hooks.slack.com/services/T0/B0/X")
+        );
+        assert_eq!(digest.webhook_url("discord"), None);
+        assert_eq!(digest.keywords, vec!["invoice", "ACME"]);
+
+        std::fs::write(
+            &path,
+            "[digest]\ndiscord_webhook_url = \"https://discord.com/api/webhooks/1/x\"\n",
+        )
+        .unwrap();
+        let digest = ConfigFile::read(&path).unwrap().digest.unwrap();
+        assert_eq!(
+            digest.send_time().unwrap(),
+            NaiveTime::from_hms_opt(18, 0, 0).unwrap()
+        );
+        assert!(digest.keywords.is_empty());
+
+        for invalid in [
+            "[digest]\nkeywords = [\"invoice\"]",
+            "[digest]\nslack_webhook_url = \"https://hooks.slack.com/x\"\ntime = \"6pm\"",
+            "[digest]\nslack_webhook_url = \"https://hooks.slack.com/x\"\nchannel = \"general\"",
+        ] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(ConfigFile::read(&path).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_config_file_api_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[api_limits]\nrequests_per_second = 5\nburst = 10").unwrap();
+        let limits = ConfigFile::read(&path).unwrap().api_limits.unwrap();
+        assert_eq!(limits.requests_per_second, 5.0);
+        assert_eq!(limits.burst, 10);
+        // Keys left out keep their default
+        assert_eq!(limits.max_body_bytes, ApiLimits::default().max_body_bytes);
+
+        for invalid in [
+            "[api_limits]\nrequests_per_second = -1",
+            "[api_limits]\nburst = 0",
+            "[api_limits]\nmax_connections = 10",
+        ] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(ConfigFile::read(&path).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_config_file_health() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[health]\naudio_stale_secs = 300\nmin_free_disk_gb = 5",
+        )
+        .unwrap();
+        let health = ConfigFile::read(&path).unwrap().health.unwrap();
+        assert_eq!(health.audio_stale_secs, 300);
+        assert_eq!(health.min_free_disk_gb, 5.0);
+        assert_eq!(
+            health.frame_stale_secs,
+            HealthThresholds::default().frame_stale_secs
+        );
+
+        for invalid in [
+            "[health]\nframe_stale_secs = 0",
+            "[health]\nmin_free_disk_gb = -1",
+            "[health]\nstale_secs = 60",
+        ] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(ConfigFile::read(&path).is_err(), "{}", invalid);
+        }
+    }
+
+    #[cfg(feature = "cloud")]
+    #[test]
+    fn test_config_file_cloud_ocr() {
+        use screenpipe_vision::CloudOcrProvider;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+            ocr_engine = "cloud"
+
+            [cloud_ocr]
+            provider = "azure"
+            api_key = "secret"
+            endpoint = "https://ocr.cognitiveservices.azure.com"
+            max_attempts = 5
+            "#,
+        )
+        .unwrap();
+        let file = ConfigFile::read(&path).unwrap();
+        let cloud_ocr = file.cloud_ocr.unwrap();
+        assert_eq!(cloud_ocr.provider, CloudOcrProvider::Azure);
+        assert_eq!(cloud_ocr.max_attempts, Some(5));
+        assert_eq!(cloud_ocr.timeout_secs, None);
+        assert_eq!(
+            format!("{:?}", cloud_ocr.provider().unwrap()),
+            "AzureVision"
+        );
+
+        std::fs::write(&path, "[cloud_ocr]\napi_key = \"secret\"").unwrap();
+        let cloud_ocr = ConfigFile::read(&path).unwrap().cloud_ocr.unwrap();
+        assert_eq!(cloud_ocr.provider, CloudOcrProvider::Unstructured);
+
+        for invalid in [
+            "[cloud_ocr]\nprovider = \"tesseract\"",
+            "[cloud_ocr]\nregion = \"westeurope\"",
+        ] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(ConfigFile::read(&path).is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_config_file_publishes_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let (sender, mut settings) = watch::channel(base());
+        tokio::spawn(watch_config_file(
+            path.clone(),
+            base(),
+            ConfigFile::default(),
+            sender,
+            Duration::from_millis(20),
+        ));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        std::fs::write(&path, "fps = 2.0\nmax_disk_usage = 10\n").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), settings.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        let current = settings.borrow_and_update().clone();
+        assert_eq!(current.fps, 2.0);
+        assert_eq!(current.max_disk_usage_gb, Some(10.0));
+
+        // Removing the file goes back to the flags
+        std::fs::remove_file(&path).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), settings.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*settings.borrow(), base());
+    }
+}
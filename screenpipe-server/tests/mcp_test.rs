@@ -0,0 +1,176 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_server::{
+        DatabaseManager, McpServer, MCP_PROTOCOL_VERSION, SEARCH_QUERY_SYNTAX,
+    };
+    use screenpipe_vision::OcrEngine;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    async fn setup_server() -> McpServer {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for (app_name, text) in [
+            ("firefox", "quarterly report draft"),
+            ("firefox", "quarterly numbers"),
+            ("slack", "lunch at noon?"),
+        ] {
+            let frame_id = db.insert_frame(app_name).await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "[]",
+                "[]",
+                "{}",
+                app_name,
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+        let chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(chunk_id, "let's go over the quarterly report", 0)
+            .await
+            .unwrap();
+        McpServer::new(Arc::new(db))
+    }
+
+    /// Output of a tool, parsed
+    async fn call(server: &McpServer, name: &str, arguments: Value) -> Value {
+        let response = server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "method": "tools/call",
+                "params": {"name": name, "arguments": arguments},
+            }))
+            .await
+            .unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"]["isError"], false, "{}", response);
+        serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mcp_handshake() {
+        let server = setup_server().await;
+        let response = server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {"protocolVersion": MCP_PROTOCOL_VERSION, "capabilities": {}},
+            }))
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["protocolVersion"], MCP_PROTOCOL_VERSION);
+        assert_eq!(response["result"]["serverInfo"]["name"], "screenpipe");
+        assert!(response["result"]["capabilities"]["tools"].is_object());
+
+        let notification = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        assert!(server.handle(notification).await.is_none());
+
+        let response = server
+            .handle(json!({"jsonrpc": "2.0", "id": "list", "method": "tools/list"}))
+            .await
+            .unwrap();
+        assert_eq!(response["id"], "list");
+        let tools: Vec<&str> = response["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            tools,
+            ["search_screen_history", "get_timeline", "get_app_usage"]
+        );
+        // The model is told the operators the search supports
+        let description = response["result"]["tools"][0]["description"]
+            .as_str()
+            .unwrap();
+        assert!(description.contains(SEARCH_QUERY_SYNTAX));
+
+        let response = server
+            .handle(json!({"jsonrpc": "2.0", "id": 2, "method": "resources/list"}))
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], -32601);
+    }
+
+    #[tokio::test]
+    async fn test_mcp_tools() {
+        let server = setup_server().await;
+
+        let found = call(
+            &server,
+            "search_screen_history",
+            json!({"query": "quarterly", "content_type": "ocr"}),
+        )
+        .await;
+        let results = found["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result["type"] == "ocr"));
+        assert_eq!(results[0]["app_name"], "firefox");
+
+        let found = call(&server, "search_screen_history", json!({"query": "report"})).await;
+        assert_eq!(found["results"].as_array().unwrap().len(), 2);
+
+        let usage = call(&server, "get_app_usage", json!({})).await;
+        assert_eq!(usage["apps"][0]["app_name"], "firefox");
+        assert_eq!(usage["apps"][0]["frames"], 2);
+        assert_eq!(usage["apps"][0]["percent"], 66.7);
+
+        let timeline = call(&server, "get_timeline", json!({})).await;
+        let apps: Vec<&str> = timeline["apps"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|span| span["app_name"].as_str().unwrap())
+            .collect();
+        assert_eq!(apps, ["firefox", "slack"]);
+        assert_eq!(
+            timeline["transcriptions"][0]["text"],
+            "let's go over the quarterly report"
+        );
+
+        // Reported to the model, which can fix its call
+        let response = server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "tools/call",
+                "params": {"name": "get_timeline", "arguments": {"start_time": "yesterday"}},
+            }))
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["isError"], true);
+    }
+
+    #[tokio::test]
+    async fn test_mcp_stdio() {
+        let server = setup_server().await;
+        let input = concat!(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "ping"}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#,
+            "\n",
+            "not json\n",
+        );
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).await.unwrap();
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(
+            responses[0],
+            json!({"jsonrpc": "2.0", "id": 1, "result": {}})
+        );
+        assert_eq!(responses[1]["error"]["code"], -32700);
+    }
+}
@@ -0,0 +1,170 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use screenpipe_server::{ContentType, DatabaseManager, SearchResult};
+
+    async fn setup_test_db() -> DatabaseManager {
+        DatabaseManager::new("sqlite::memory:").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_search_notifications() {
+        let db = setup_test_db().await;
+        let now = Utc::now();
+        db.insert_notification("Slack", "Alice", "the deploy is done", now)
+            .await
+            .unwrap();
+        db.insert_notification("Calendar", "Standup in 5 minutes", "", now)
+            .await
+            .unwrap();
+
+        // In the body
+        let results = db
+            .search(
+                "deploy",
+                ContentType::Notification,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        match &results[..] {
+            [SearchResult::Notification(notification)] => {
+                assert_eq!(notification.app_name, "Slack");
+                assert_eq!(notification.title, "Alice");
+                assert_eq!(notification.snippet, "the <mark>deploy</mark> is done");
+                let highlight = &notification.highlights[0];
+                assert_eq!(&notification.body[highlight.start..highlight.end], "deploy");
+            }
+            _ => panic!("Expected one notification result, got {:?}", results),
+        }
+
+        // In the title
+        let results = db
+            .search(
+                "standup",
+                ContentType::Notification,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        match &results[..] {
+            [SearchResult::Notification(notification)] => {
+                assert_eq!(notification.app_name, "Calendar");
+                assert_eq!(notification.snippet, "<mark>Standup</mark> in 5 minutes");
+            }
+            _ => panic!("Expected one notification result, got {:?}", results),
+        }
+
+        // Outside the time range
+        let results = db
+            .search(
+                "deploy",
+                ContentType::Notification,
+                10,
+                0,
+                None,
+                Some(now - Duration::minutes(1)),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_notifications_in_all_results() {
+        let db = setup_test_db().await;
+        let start = Utc::now();
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(audio_chunk_id, "the build failed again", 0)
+            .await
+            .unwrap();
+        for i in 0..3 {
+            db.insert_notification(
+                "GitHub",
+                "CI",
+                &format!("build {} failed", i),
+                start + Duration::seconds(i),
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            db.count_search_results(
+                "build",
+                ContentType::All,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None
+            )
+            .await
+            .unwrap(),
+            4
+        );
+        assert_eq!(
+            db.count_search_results(
+                "build",
+                ContentType::Notification,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap(),
+            3
+        );
+
+        let mut notifications = 0;
+        let mut cursor = None;
+        loop {
+            let (results, next) = db
+                .search_page(
+                    "build",
+                    ContentType::All,
+                    1,
+                    cursor.as_ref(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            notifications += results
+                .iter()
+                .filter(|result| matches!(result, SearchResult::Notification(_)))
+                .count();
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(notifications, 3);
+    }
+}
@@ -0,0 +1,67 @@
+#[cfg(all(test, feature = "integrations"))]
+mod tests {
+    use chrono::Local;
+    use screenpipe_integrations::obsidian::write_daily_note;
+    use screenpipe_server::DatabaseManager;
+    use screenpipe_vision::OcrEngine;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_write_daily_note() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for (app_name, text) in [
+            ("editor", "fn main() {}"),
+            ("editor", "fn main() {}"),
+            ("editor", "fn main() {}"),
+            ("browser", "Rust documentation"),
+        ] {
+            let frame_id = db.insert_frame(app_name).await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                "",
+                "",
+                app_name,
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(audio_chunk_id, "Let's ship the release", 0, "whisper")
+            .await
+            .unwrap();
+
+        let vault_path = temp_dir.path().join("vault");
+        let today = Local::now().date_naive();
+        let note_path = write_daily_note(&vault_path, today, &db).await.unwrap();
+
+        assert_eq!(
+            note_path,
+            vault_path
+                .join("screenpipe")
+                .join(format!("{}.md", today.format("%Y-%m-%d")))
+        );
+        let note = std::fs::read_to_string(&note_path).unwrap();
+        assert!(note.contains(&format!("date: {}", today.format("%Y-%m-%d"))));
+        assert!(note.contains("- editor: 75%"));
+        assert!(note.contains("- browser: 25%"));
+        assert!(note.contains("Let's ship the release"));
+        // Most seen snippet comes first
+        let main_position = note.find("(editor) fn main() {}").unwrap();
+        let docs_position = note.find("(browser) Rust documentation").unwrap();
+        assert!(main_position < docs_position);
+
+        // Regenerating the note replaces it
+        write_daily_note(&vault_path, today, &db).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&note_path).unwrap(), note);
+    }
+}
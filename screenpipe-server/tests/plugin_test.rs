@@ -0,0 +1,159 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::Router;
+    use screenpipe_server::{ApiPlugin, ApiPluginLayer, ApiPlugins, RequestSummary};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    /// Rejects the requests without the token
+    struct Auth;
+
+    impl ApiPlugin for Auth {
+        fn name(&self) -> &str {
+            "auth"
+        }
+
+        fn on_request(&self, request: &Request<Body>) -> Option<Response> {
+            match request.headers().get("authorization") {
+                Some(token) if token == "Bearer secret" => None,
+                _ => Some(StatusCode::UNAUTHORIZED.into_response()),
+            }
+        }
+    }
+
+    /// Records the hooks it sees, prefixed with its name
+    struct Recorder {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ApiPlugin for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn on_request(&self, request: &Request<Body>) -> Option<Response> {
+            self.log.lock().unwrap().push(format!(
+                "{} request {}",
+                self.name,
+                request.uri().path()
+            ));
+            None
+        }
+
+        fn on_response(&self, request: &RequestSummary, response: &Response, _: Duration) {
+            self.log.lock().unwrap().push(format!(
+                "{} response {} {}",
+                self.name,
+                request.uri.path(),
+                response.status().as_u16()
+            ));
+        }
+    }
+
+    struct Panicking;
+
+    impl ApiPlugin for Panicking {
+        fn name(&self) -> &str {
+            "panicking"
+        }
+
+        fn on_request(&self, _: &Request<Body>) -> Option<Response> {
+            panic!("broken plugin");
+        }
+
+        fn on_response(&self, _: &RequestSummary, _: &Response, _: Duration) {
+            panic!("broken plugin");
+        }
+    }
+
+    fn app(plugins: ApiPlugins) -> Router {
+        Router::new()
+            .route("/search", get(|| async { "results" }))
+            .route(
+                "/broken",
+                get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+            )
+            .layer(ApiPluginLayer::new(plugins))
+    }
+
+    async fn status(app: &Router, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri(uri);
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        app.clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_plugins_order_and_short_circuit() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let recorder = |name| Recorder {
+            name,
+            log: Arc::clone(&log),
+        };
+        let app = app(ApiPlugins::new()
+            .with(recorder("first"))
+            .with(Auth)
+            .with(recorder("last")));
+
+        assert_eq!(
+            status(&app, "/search", Some("secret")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&app, "/broken", Some("secret")).await,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        // Rejected before the last plugin and the handler, observed anyway
+        assert_eq!(
+            status(&app, "/search", Some("wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "first request /search",
+                "last request /search",
+                "last response /search 200",
+                "first response /search 200",
+                "first request /broken",
+                "last request /broken",
+                "last response /broken 500",
+                "first response /broken 500",
+                "first request /search",
+                "last response /search 401",
+                "first response /search 401",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_panicking_plugin_is_isolated() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let plugins = ApiPlugins::new().with(Panicking).with(Recorder {
+            name: "recorder",
+            log: Arc::clone(&log),
+        });
+        let app = app(plugins.clone());
+
+        assert_eq!(status(&app, "/search", None).await, StatusCode::OK);
+        assert_eq!(status(&app, "/search", None).await, StatusCode::OK);
+
+        assert_eq!(log.lock().unwrap().len(), 4);
+        assert_eq!(
+            plugins.failures(),
+            vec![("panicking".to_string(), 4), ("recorder".to_string(), 0)]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(all(test, feature = "audio"))]
+mod tests {
+    // Breaks to compile when something of the public API is removed or moved out of a crate root
+    #[allow(unused_imports)]
+    use screenpipe_audio::{
+        create_whisper_channel, default_device, default_input_device, default_output_device,
+        list_audio_devices, list_synthetic_audio_files, parse_audio_device, pcm_decode,
+        play_synthetic_audio, preprocess_audio, record_and_transcribe, stt, AudioDevice,
+        AudioDeviceConfig, AudioEncoding, AudioFormat, AudioInput, AudioPreprocessing,
+        DeviceControl, DeviceType, TranscriptionLanguage, TranscriptionLanguages,
+        TranscriptionPriority, TranscriptionQueue, TranscriptionResult, WhisperModel,
+        WhisperModelConfig, WhisperModelSize, SYNTHETIC_DEVICE_NAME,
+    };
+    #[allow(unused_imports)]
+    use screenpipe_server::{
+        audio_device_of, backup, build_prompt, disk_usage, enforce_storage_quota, ensure_local,
+        evict_oldest_chunks, export, health_check, load_config_file, mean_confidence,
+        monitor_config, normalize_entity, ocr_words, offload_chunks, open_storage, prune_chunks,
+        question_words, recover_chunks, replay_archive, restore_backup, retrieve_sources,
+        run_anomaly_detection, run_offload, run_session_segmentation, segment_sessions,
+        start_continuous_recording, watch_config_file, Anomaly, AnomalyDetector, AnomalyKind,
+        ApiError, ApiJson, ApiLimitLayer, ApiLimitService, ApiLimits, ApiPath, ApiQuery, AppState,
+        AskResponse, AskSource, AudioDeviceHealth, AudioRecord, AudioResult, BackupLocation,
+        BackupManifest, BackupMedia, BackupSummary, Capabilities, CaptureBackend, CaptureRequests,
+        CaptureResult, CaptureTrigger, CapturedFrame, ChunkKind, ChunkUsage, CircuitBreakerStatus,
+        CircuitState, ClipboardMonitor, ClipboardResult, ConfidenceFloor, ContentProcessor,
+        ContentProcessors, ContentSource, ContentType, DataOutputWrapper, DatabaseError,
+        DatabaseManager, DatabaseSettings, Diagnostics, DigestConfig, DiskUsage, Entity,
+        EntityExtractor, EntityKind, EntitySummary, ErrorCode, Event, EventKind, EventLog,
+        ExportFormat, FrameRecord, HealthCheckResponse, HealthMonitor, HealthThresholds,
+        IdleMonitor, IngestBatch, IngestBucket, IngestCounts, IngestFrame, IngestSummary,
+        IngestTranscription, LiveSettings, Mark, McpServer, MirroredStorage, MonitorConfig,
+        MonitorStatus, MultiWriter, NewRule, NotificationMonitor, NotificationResult, OCRResult,
+        OcrEngine, OcrWord, OffloadSummary, OllamaClient, ProcessedStorage, RecorderControl,
+        RecordingConfig, RecordingHandles, RecoverySummary, ReplaySummary, ResourceMonitor,
+        RestartSignal, RestoreSummary, Rule, RuleEngine, SearchResult, Server, SessionBounds,
+        SpellChecker, SpellDictionary, Storage, Subsystem, SubsystemError, SubsystemHealth,
+        TimelineMinute, TimelineSegment, TimelineState, TranscribedChunk, TranscriptSession,
+        TranscriptionBackfill, VideoCapture, VideoCodec, VideoEncoder, VideoEncoding, VisionStatus,
+        WriteStats, DEFAULT_OLLAMA_MODEL, DEFAULT_OLLAMA_URL, IDLE_BUCKET_ID, LOCAL_DEVICE_ID,
+        MCP_PROTOCOL_VERSION,
+    };
+    #[cfg(feature = "grpc")]
+    #[allow(unused_imports)]
+    use screenpipe_server::{proto, serve_grpc, GrpcService};
+    #[allow(unused_imports)]
+    use screenpipe_vision::{
+        attribute_text, blur_regions, capture_screenshot, continuous_capture, current_windows,
+        elements_to_ocr_output, find_monitor, focused_window_elements, frontmost_app, get_monitor,
+        installed_tesseract_languages, native_language_tag, perceptual_hash,
+        perceptual_hash_similarity, perform_ocr_tesseract, process_ocr_task, redact_words,
+        stack_windows, CaptureCrop, CaptureRegion, CaptureSource, CaptureTrigger, MaskStyle,
+        MaskedRegion, OcrLanguages, PerceptualHash, RegionAnchor, RegionLength, ScreenMask,
+        SensitiveAction, SensitiveCategory, SensitiveContent, SensitiveContentFilter,
+        SyntheticCaptureSource, UiElement, WindowRegion, SYNTHETIC_APP_NAME,
+    };
+    #[cfg(feature = "cloud")]
+    #[allow(unused_imports)]
+    use screenpipe_vision::{
+        perform_ocr_cloud, CloudOcrConfig, CloudOcrLine, CloudOcrProvider, CloudOcrWord,
+        OcrProvider,
+    };
+
+    #[test]
+    fn test_server_reexports_dependency_types() {
+        // Same types as the ones of the underlying crates, not copies
+        let engine: screenpipe_vision::OcrEngine = OcrEngine::Tesseract;
+        assert!(matches!(engine, screenpipe_vision::OcrEngine::Tesseract));
+
+        let device: screenpipe_audio::AudioDevice =
+            AudioDevice::new("MacBook Pro Microphone".to_string(), DeviceType::Input);
+        assert_eq!(device.to_string(), "MacBook Pro Microphone (input)");
+
+        let control: screenpipe_audio::DeviceControl = DeviceControl {
+            is_running: true,
+            is_paused: false,
+        };
+        assert!(control.is_running);
+    }
+}
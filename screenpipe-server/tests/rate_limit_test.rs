@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body, Bytes};
+    use axum::http::{header, Request, StatusCode};
+    use axum::routing::{get, post};
+    use axum::Router;
+    use screenpipe_server::ApiLimits;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    fn app(limits: &ApiLimits) -> Router {
+        limits.apply(
+            Router::new()
+                .route("/search", get(|| async { "results" }))
+                .route("/health", get(|| async { "healthy" }))
+                .route(
+                    "/upload",
+                    post(|body: Bytes| async move { body.len().to_string() }),
+                ),
+        )
+    }
+
+    async fn send(app: &Router, method: &str, uri: &str, body: Vec<u8>) -> (StatusCode, String) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let app = app(&ApiLimits {
+            requests_per_second: 0.5,
+            burst: 2,
+            ..ApiLimits::default()
+        });
+        for _ in 0..2 {
+            assert_eq!(
+                send(&app, "GET", "/search", Vec::new()).await.0,
+                StatusCode::OK
+            );
+        }
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/search").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = response.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=2).contains(&retry_after), "{}", retry_after);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: Value = serde_json::from_slice(&body).unwrap();
+        assert!(error["error"]
+            .as_str()
+            .unwrap()
+            .contains("too many requests"));
+
+        // Health checks still answer
+        assert_eq!(
+            send(&app, "GET", "/health", Vec::new()).await,
+            (StatusCode::OK, "healthy".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_tiny_rate() {
+        let app = app(&ApiLimits {
+            requests_per_second: 1e-20,
+            burst: 1,
+            ..ApiLimits::default()
+        });
+        assert_eq!(
+            send(&app, "GET", "/search", Vec::new()).await.0,
+            StatusCode::OK
+        );
+        let response = app
+            .clone()
+            .oneshot(Request::get("/search").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "86400");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_off() {
+        let app = app(&ApiLimits {
+            requests_per_second: 0.0,
+            burst: 0,
+            ..ApiLimits::default()
+        });
+        for _ in 0..200 {
+            assert_eq!(
+                send(&app, "GET", "/search", Vec::new()).await.0,
+                StatusCode::OK
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_limits() {
+        let app = app(&ApiLimits {
+            max_body_bytes: 1024,
+            max_query_length: 64,
+            ..ApiLimits::default()
+        });
+        assert_eq!(
+            send(&app, "POST", "/upload", vec![b'a'; 1024]).await,
+            (StatusCode::OK, "1024".to_string())
+        );
+        assert_eq!(
+            send(&app, "POST", "/upload", vec![b'a'; 1025]).await.0,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        let query = format!("/search?q={}", "a".repeat(62));
+        assert_eq!(
+            send(&app, "GET", &query, Vec::new()).await.0,
+            StatusCode::OK
+        );
+        let query = format!("/search?q={}", "a".repeat(63));
+        let (status, body) = send(&app, "GET", &query, Vec::new()).await;
+        assert_eq!(status, StatusCode::URI_TOO_LONG);
+        assert!(body.contains("at most 64"), "{}", body);
+    }
+
+    #[test]
+    fn test_validate_limits() {
+        assert!(ApiLimits::default().validate().is_ok());
+        for invalid in [
+            ApiLimits {
+                requests_per_second: -1.0,
+                ..ApiLimits::default()
+            },
+            ApiLimits {
+                requests_per_second: f64::NAN,
+                ..ApiLimits::default()
+            },
+            ApiLimits {
+                burst: 0,
+                ..ApiLimits::default()
+            },
+            ApiLimits {
+                max_body_bytes: 0,
+                ..ApiLimits::default()
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{:?}", invalid);
+        }
+    }
+}
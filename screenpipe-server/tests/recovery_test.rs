@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_server::{recover_chunks, DatabaseManager};
+    use std::path::Path;
+
+    fn mp4_box(kind: &[u8; 4], size: u32) -> Vec<u8> {
+        let mut content = size.to_be_bytes().to_vec();
+        content.extend_from_slice(kind);
+        content.resize(size.max(8) as usize, 0);
+        content
+    }
+
+    fn write_chunk(dir: &Path, name: &str, boxes: &[Vec<u8>]) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, boxes.concat()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_recover_chunks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let finalized = [
+            mp4_box(b"ftyp", 16),
+            mp4_box(b"mdat", 32),
+            mp4_box(b"moov", 24),
+        ];
+
+        let recorded = write_chunk(&data_dir, "2024-08-01_10-00-00.mp4", &finalized);
+        db.insert_video_chunk_without_codec(&recorded)
+            .await
+            .unwrap();
+        // Its row was never written
+        let unknown = write_chunk(&data_dir, "2024-08-01_10-01-00.mp4", &finalized);
+        let orphan = write_chunk(&data_dir, "2024-08-01_10-01-30.mp4", &[]);
+        let empty = write_chunk(&data_dir, "2024-08-01_10-02-00.mp4", &[]);
+        db.insert_video_chunk_without_codec(&empty).await.unwrap();
+        db.insert_frame("firefox").await.unwrap();
+        // Killed before ffmpeg wrote the size of its mdat and its moov
+        let unfinalized = write_chunk(
+            &data_dir,
+            "2024-08-01_10-03-00.mp4",
+            &[mp4_box(b"ftyp", 16), mp4_box(b"mdat", 0), vec![1; 64]],
+        );
+        db.insert_video_chunk_without_codec(&unfinalized)
+            .await
+            .unwrap();
+        let stub = data_dir.join("2024-08-01_10-04-00.mp4");
+        std::fs::write(&stub, b"screenpipe-offloaded-chunk\n{}").unwrap();
+        let audio = write_chunk(&data_dir, "Microphone (input)_2024-08-01_10-00-00.mp4", &[]);
+        let leftover = write_chunk(&data_dir, "2024-08-01_10-05-00.repairing.mp4", &finalized);
+        db.insert_video_chunk_without_codec(
+            &data_dir.join("2024-08-01_09-00-00.mp4").to_string_lossy(),
+        )
+        .await
+        .unwrap();
+
+        let summary = recover_chunks(&db, &data_dir).await.unwrap();
+        assert_eq!(summary.repaired, 0);
+        assert_eq!(summary.removed, 1);
+        assert_eq!(summary.corrupt, 2);
+        assert_eq!(summary.backfilled, 1);
+        assert_eq!(summary.missing, 1);
+
+        assert!(Path::new(&recorded).exists());
+        assert!(!Path::new(&orphan).exists());
+        // Kept with their frames
+        assert!(Path::new(&empty).exists());
+        assert!(Path::new(&unfinalized).exists());
+        assert!(stub.exists());
+        assert!(Path::new(&audio).exists());
+        assert!(!Path::new(&leftover).exists());
+        let file_paths: Vec<String> = db
+            .list_video_chunks()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(_, file_path)| file_path)
+            .collect();
+        assert!(file_paths.contains(&recorded));
+        assert!(file_paths.contains(&unknown));
+        assert!(file_paths.contains(&empty));
+        assert!(file_paths.contains(&unfinalized));
+        assert_eq!(db.list_corrupt_video_chunks().await.unwrap().len(), 2);
+
+        // Nothing left to do on the next start
+        let summary = recover_chunks(&db, &data_dir).await.unwrap();
+        assert_eq!(summary.removed + summary.corrupt + summary.backfilled, 0);
+    }
+}
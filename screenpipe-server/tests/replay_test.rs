@@ -0,0 +1,105 @@
+#[cfg(all(test, feature = "recording"))]
+mod tests {
+    use screenpipe_core::find_ffmpeg_path;
+    use screenpipe_server::{replay_archive, ContentType, DatabaseManager, SearchResult};
+    use screenpipe_vision::OcrEngine;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::sync::Arc;
+
+    // Requires ffmpeg and tesseract
+    #[tokio::test]
+    async fn test_replay_archive_into_scratch_db() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let archive_dir = temp_dir.path().join("archive");
+        let data_dir = archive_dir.join("data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+
+        // Two frames video chunk of the OCR test image
+        let image_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../screenpipe-vision/tests/testing_OCR.png");
+        let video_path = data_dir.join("2024-01-01_00-00-00.mp4");
+        let status = Command::new(find_ffmpeg_path().unwrap())
+            .args(["-loop", "1", "-i"])
+            .arg(&image_path)
+            .args([
+                "-frames:v",
+                "2",
+                "-r",
+                "1",
+                "-vf",
+                "scale=trunc(iw/2)*2:trunc(ih/2)*2",
+                "-c:v",
+                "libx264",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .arg(&video_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let archive_db = DatabaseManager::new(&archive_dir.join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        archive_db
+            .insert_video_chunk_without_codec(&video_path.to_string_lossy())
+            .await
+            .unwrap();
+        archive_db.insert_frame("editor").await.unwrap();
+        archive_db.insert_frame("browser").await.unwrap();
+
+        let output_dir = temp_dir.path().join("replay");
+        let summary = replay_archive(
+            &archive_dir,
+            &output_dir,
+            Arc::new(OcrEngine::Tesseract),
+            #[cfg(feature = "audio")]
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(summary.video_chunks, 1);
+        assert_eq!(summary.frames, 2);
+        assert_eq!(summary.audio_chunks, 0);
+
+        let scratch_db = DatabaseManager::new(&output_dir.join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        let results = scratch_db
+            .search(
+                "",
+                ContentType::OCR,
+                10,
+                0,
+                None,
+                None,
+                Some("browser"),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        match &results[0] {
+            SearchResult::OCR(ocr) => {
+                assert!(!ocr.ocr_text.is_empty());
+                assert_eq!(ocr.offset_index, 1);
+            }
+            _ => panic!("Expected OCR result"),
+        }
+
+        // Replaying into an existing scratch database is refused
+        assert!(replay_archive(
+            &archive_dir,
+            &output_dir,
+            Arc::new(OcrEngine::Tesseract),
+            #[cfg(feature = "audio")]
+            None,
+        )
+        .await
+        .is_err());
+    }
+}
@@ -0,0 +1,616 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use chrono::{Duration, Utc};
+    use screenpipe_server::{
+        create_router, timeline_segments, ActivityEvent, ApiLimits, ApiPlugins, AppState,
+        Capabilities, DatabaseManager, Diagnostics, EntityExtractor, FrameRecord,
+        HealthCheckResponse, LiveSettings, OcrWord, Storage, TimelineState, IDLE_BUCKET_ID,
+        VISION_CAPTURE,
+    };
+    use screenpipe_vision::OcrEngine;
+    use serde_json::{json, Value};
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use tokio::sync::watch;
+    use tower::ServiceExt;
+
+    async fn setup_app() -> (Router, Arc<AppState>) {
+        setup_app_with(|state| state).await
+    }
+
+    /// App of a state of `AppState::new` changed by `configure`
+    async fn setup_app_with(
+        configure: impl FnOnce(AppState) -> AppState,
+    ) -> (Router, Arc<AppState>) {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let app_state = Arc::new(configure(AppState::new(db)));
+        let app = create_router(app_state.clone(), ApiPlugins::new(), &ApiLimits::default());
+        (app, app_state)
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+        send(
+            app,
+            Request::builder().uri(uri).body(Body::empty()).unwrap(),
+        )
+        .await
+    }
+
+    async fn post(app: &Router, uri: &str, body: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        send(app, request).await
+    }
+
+    /// Two frames of two apps and a transcription
+    async fn record_sample(db: &DatabaseManager) {
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for (app_name, text) in [
+            ("firefox", "quarterly report"),
+            ("slack", "quarterly lunch"),
+        ] {
+            let frame_id = db.insert_frame(app_name).await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "{}",
+                "{}",
+                "{}",
+                app_name,
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+        let chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(chunk_id, "the quarterly numbers", 0, "whisper")
+            .await
+            .unwrap();
+        db.flush_writes().await;
+    }
+
+    #[tokio::test]
+    async fn test_search_filters() {
+        let (app, state) = setup_app().await;
+        record_sample(&state.db).await;
+
+        let (status, page) = get(&app, "/v1/search?q=quarterly").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["pagination"]["total"], 3);
+
+        let (_, page) = get(&app, "/v1/search?q=quarterly&content_type=audio").await;
+        assert_eq!(page["pagination"]["total"], 1);
+        assert_eq!(
+            page["data"][0]["content"]["transcription"],
+            "the quarterly numbers"
+        );
+
+        let (_, page) = get(&app, "/v1/search?q=quarterly&app_name=slack").await;
+        assert_eq!(page["pagination"]["total"], 1);
+        assert_eq!(page["data"][0]["content"]["text"], "quarterly lunch");
+
+        let (_, page) = get(&app, "/v1/search?q=quarterly&limit=1").await;
+        assert_eq!(page["data"].as_array().unwrap().len(), 1);
+        assert_eq!(page["pagination"]["total"], 3);
+
+        let start_time = (Utc::now() + Duration::hours(1))
+            .to_rfc3339()
+            .replace('+', "%2B")
+            .replace(':', "%3A");
+        let (_, page) = get(
+            &app,
+            &format!("/v1/search?q=quarterly&start_time={}", start_time),
+        )
+        .await;
+        assert_eq!(page["pagination"]["total"], 0);
+
+        for uri in [
+            "/v1/search?content_type=video",
+            "/v1/search?limit=ten",
+            "/v1/search?cursor=garbage",
+            "/v1/search?content_type=all,ocr",
+        ] {
+            let (status, _) = get(&app, uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fuzzy_search() {
+        let (app, state) = setup_app().await;
+        record_sample(&state.db).await;
+
+        let (_, page) = get(&app, "/v1/search?q=quartely").await;
+        assert_eq!(page["pagination"]["total"], 0);
+
+        let (status, page) = get(&app, "/v1/search?q=quartely&fuzzy=true").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["pagination"]["total"], 3);
+
+        let (status, _) = get(&app, "/v1/search?q=quartely&fuzzy=yes").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_search_boxes() {
+        let (app, state) = setup_app().await;
+        state
+            .db
+            .insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let word = |text: &str, left| OcrWord {
+            text: text.to_string(),
+            confidence: Some(90.0),
+            left,
+            top: 40,
+            width: 60,
+            height: 12,
+        };
+        state
+            .db
+            .write_frame(FrameRecord {
+                timestamp: Utc::now(),
+                app_name: "firefox".to_string(),
+                text: "Quarterly report, draft".to_string(),
+                raw_text: None,
+                text_json: "[]".to_string(),
+                new_text_json_vs_previous_frame: "[]".to_string(),
+                raw_data_output_from_ocr: "{}".to_string(),
+                ocr_engine: "Tesseract".to_string(),
+                words: vec![
+                    word("Quarterly", 10),
+                    word("report,", 80),
+                    word("draft", 150),
+                ],
+                windows: Vec::new(),
+                region: None,
+                entities: Vec::new(),
+            })
+            .await
+            .unwrap();
+        state.db.flush_writes().await;
+
+        let (_, page) = get(&app, "/v1/search?q=report").await;
+        assert!(page["data"][0]["content"].get("boxes").is_none());
+
+        let (status, page) = get(&app, "/v1/search?q=report&include_boxes=true").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            page["data"][0]["content"]["boxes"],
+            json!([{
+                "text": "report,",
+                "confidence": 90.0,
+                "left": 80,
+                "top": 40,
+                "width": 60,
+                "height": 12
+            }])
+        );
+
+        let (_, page) = get(&app, "/v1/search?include_boxes=true").await;
+        assert_eq!(
+            page["data"][0]["content"]["boxes"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+
+        let (status, _) = get(&app, "/v1/search?include_boxes=yes").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_entities() {
+        let (app, state) = setup_app().await;
+        state
+            .db
+            .insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for text in ["Invoice of ACME Corp", "Call Dr. Jane Doe"] {
+            state
+                .db
+                .write_frame(FrameRecord {
+                    timestamp: Utc::now(),
+                    app_name: "mail".to_string(),
+                    text: text.to_string(),
+                    raw_text: None,
+                    text_json: "[]".to_string(),
+                    new_text_json_vs_previous_frame: "[]".to_string(),
+                    raw_data_output_from_ocr: "{}".to_string(),
+                    ocr_engine: "Tesseract".to_string(),
+                    words: Vec::new(),
+                    windows: Vec::new(),
+                    region: None,
+                    entities: EntityExtractor::new().extract(text),
+                })
+                .await
+                .unwrap();
+        }
+        state.db.flush_writes().await;
+
+        let (status, entities) = get(&app, "/v1/entities?kind=organization").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(entities.as_array().unwrap().len(), 1);
+        assert_eq!(entities[0]["kind"], "organization");
+        assert_eq!(entities[0]["normalized"], "acme corp");
+        assert_eq!(entities[0]["mentions"], 1);
+
+        let (_, page) = get(&app, "/v1/search?entity=Jane%20Doe").await;
+        assert_eq!(page["data"].as_array().unwrap().len(), 1);
+        assert_eq!(page["data"][0]["content"]["text"], "Call Dr. Jane Doe");
+
+        let (status, _) = get(&app, "/v1/entities?kind=planet").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let (app, state) = setup_app().await;
+        record_sample(&state.db).await;
+        state
+            .db
+            .segment_transcript_sessions(Duration::minutes(2))
+            .await
+            .unwrap();
+
+        let (status, sessions) = get(&app, "/v1/sessions?limit=5").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(sessions.as_array().unwrap().len(), 1);
+        assert_eq!(sessions[0]["transcript"], "the quarterly numbers");
+        assert_eq!(sessions[0]["audio_devices"], json!(["test_audio"]));
+    }
+
+    #[cfg(feature = "integrations")]
+    #[tokio::test]
+    async fn test_chat_digest_not_configured() {
+        let (app, _) = setup_app().await;
+        let (status, body) = post(&app, "/v1/integrations/slack/test", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("slack"));
+        let (status, _) = post(&app, "/v1/integrations/teams/test", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_ask_without_matches() {
+        let (app, state) = setup_app().await;
+        record_sample(&state.db).await;
+
+        let (status, _) = post(&app, "/v1/ask", r#"{"question": "  "}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        // Nothing to answer from, the model isn't asked
+        let (status, body) = post(&app, "/v1/ask", r#"{"question": "Who won the match?"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["model"], "");
+        assert_eq!(body["sources"], json!([]));
+        assert_eq!(body["frame_ids"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_health_states() {
+        let (app, state) = setup_app().await;
+        let (status, body) = get(&app, "/v1/health").await;
+        assert_eq!(status, StatusCode::OK);
+        let health: HealthCheckResponse = serde_json::from_value(body).unwrap();
+        assert_eq!(health.status, "Loading");
+
+        state.capture_paused.store(true, Ordering::SeqCst);
+        let (_, body) = get(&app, "/v1/health").await;
+        let health: HealthCheckResponse = serde_json::from_value(body).unwrap();
+        assert_eq!(health.status, "Idle");
+        assert!(health.idle);
+        assert_eq!(health.frame_status, "Paused");
+    }
+
+    #[tokio::test]
+    async fn test_vision_control() {
+        let (app, state) = setup_app().await;
+
+        let (status, body) = post(&app, "/v1/vision/start", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["is_running"], true);
+        assert!(state.vision_control.load(Ordering::SeqCst));
+        let (_, body) = get(&app, "/v1/vision/status").await;
+        assert_eq!(body["is_running"], true);
+
+        let (_, body) = post(&app, "/v1/vision/stop", "").await;
+        assert_eq!(body["is_running"], false);
+        assert!(!state.vision_control.load(Ordering::SeqCst));
+
+        // Restored on the next start
+        assert_eq!(
+            state.db.get_capture_states().await.unwrap(),
+            vec![(VISION_CAPTURE.to_string(), false)]
+        );
+    }
+
+    #[cfg(feature = "audio")]
+    #[tokio::test]
+    async fn test_device_errors() {
+        let (app, state) = setup_app().await;
+
+        let (status, body) = post(&app, "/v1/audio/start", r#"{"device_id": "Mic"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Invalid device ID");
+        let (status, _) = post(&app, "/v1/audio/stop", r#"{"device": 1}"#).await;
+        assert!(status.is_client_error());
+        let (status, body) = post(
+            &app,
+            "/v1/audio/status",
+            r#"{"device_id": "Microphone (input)"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Device not found");
+        assert!(state.audio_devices_control.is_empty());
+
+        let (status, _) = post(
+            &app,
+            "/v1/audio/start",
+            r#"{"device_id": "Microphone (input)"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = post(
+            &app,
+            "/v1/audio/status",
+            r#"{"device_id": "Microphone (input)"}"#,
+        )
+        .await;
+        assert_eq!(body["is_running"], true);
+
+        post(
+            &app,
+            "/v1/audio/stop",
+            r#"{"device_id": "Microphone (input)"}"#,
+        )
+        .await;
+        assert_eq!(
+            state.db.get_capture_states().await.unwrap(),
+            vec![("Microphone (input)".to_string(), false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeline() {
+        let (app, state) = setup_app().await;
+        let now = Utc::now();
+        state
+            .db
+            .insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        state.db.insert_frame("firefox").await.unwrap();
+        state.db.insert_frame("firefox").await.unwrap();
+        state
+            .db
+            .insert_audio_chunk_with_duration("test_audio.mp4", Some(90.0))
+            .await
+            .unwrap();
+        state
+            .db
+            .upsert_activity_events(&[ActivityEvent {
+                id: 0,
+                bucket_id: IDLE_BUCKET_ID.to_string(),
+                event_type: "afkstatus".to_string(),
+                timestamp: now - Duration::minutes(30),
+                duration: 600.0,
+                data: json!({"status": "afk"}),
+            }])
+            .await
+            .unwrap();
+
+        // After the inserts, their timestamps are the time they were made
+        let end = Utc::now();
+        let minutes = state
+            .db
+            .timeline(end - Duration::minutes(40), end)
+            .await
+            .unwrap();
+        assert_eq!(minutes.len(), 41);
+        let segments = timeline_segments(&minutes);
+        let states: Vec<TimelineState> = segments.iter().map(|segment| segment.state).collect();
+        assert_eq!(
+            states,
+            vec![
+                TimelineState::Gap,
+                TimelineState::Idle,
+                TimelineState::Gap,
+                TimelineState::Active,
+                TimelineState::Active
+            ]
+        );
+        assert_eq!(segments[1].end - segments[1].start, Duration::minutes(11));
+        // The audio chunk ends now and started 90s ago
+        let recorded = &segments[3..];
+        assert!(recorded.iter().all(|segment| segment.audio));
+        assert_eq!(recorded[1].app_name.as_deref(), Some("firefox"));
+        assert_eq!(recorded[1].frames, 2);
+        assert_eq!(recorded[1].end - recorded[1].start, Duration::minutes(1));
+
+        let (status, body) = get(&app, "/v1/timeline").await;
+        assert_eq!(status, StatusCode::OK);
+        let last = body["segments"].as_array().unwrap().last().unwrap().clone();
+        assert_eq!(last["state"], "active");
+        assert_eq!(last["app_name"], "firefox");
+        let (_, body) = get(&app, "/v1/timeline?day=2999-01-01").await;
+        assert_eq!(body["segments"], json!([]));
+        let (status, _) = get(&app, "/v1/timeline?day=yesterday").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_capabilities() {
+        let (app, _) = setup_app().await;
+        let (status, body) = get(&app, "/v1/capabilities").await;
+        assert_eq!(status, StatusCode::OK);
+        let capabilities: Capabilities = serde_json::from_value(body).unwrap();
+        assert_eq!(capabilities, Capabilities::detect());
+        assert_eq!(capabilities.api_version, "v1");
+        assert!(capabilities.ocr_engines.contains(&"tesseract".to_string()));
+        assert_eq!(
+            capabilities.features.contains(&"audio".to_string()),
+            cfg!(feature = "audio")
+        );
+        assert_eq!(
+            capabilities
+                .transcription_engines
+                .contains(&"whisper".to_string()),
+            cfg!(feature = "audio")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_middleware() {
+        let (app, _) = setup_app().await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/health")
+                    .header("origin", "http://localhost:3000")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/vision/status")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["deprecation"], "true");
+
+        let (status, _) = get(&app, "/v1/nope").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get(&app, "/v1/vision/start").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_behind_cors() {
+        let (_, state) = setup_app().await;
+        let limits = ApiLimits {
+            requests_per_second: 0.01,
+            burst: 1,
+            ..ApiLimits::default()
+        };
+        let app = create_router(state, ApiPlugins::new(), &limits);
+        let search = || {
+            Request::builder()
+                .uri("/v1/search")
+                .header("origin", "http://localhost:3000")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(search()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Browsers can read why they were refused
+        let response = app.clone().oneshot(search()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+
+        let preflight = Request::builder()
+            .method("OPTIONS")
+            .uri("/v1/search")
+            .header("origin", "http://localhost:3000")
+            .header("access-control-request-method", "GET")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(preflight).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_config() {
+        let (settings_sender, settings) = watch::channel(LiveSettings::default());
+        let (app, _) = setup_app_with(|state| AppState {
+            diagnostics: Diagnostics {
+                log_path: None,
+                config: json!({
+                    "fps": 1.0,
+                    "port": 3030,
+                    "cloud_audio_api_key": "sk-secret",
+                }),
+                settings: Some(settings),
+            },
+            ..state
+        })
+        .await;
+        // Reloaded from the config file while running
+        settings_sender.send_modify(|settings| {
+            settings.fps = 0.5;
+            settings.ignored_apps = vec!["1Password".to_string()];
+        });
+
+        let (status, config) = get(&app, "/v1/config").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(config["fps"], 0.5);
+        assert_eq!(config["ignored_apps"], json!(["1Password"]));
+        assert_eq!(config["port"], 3030);
+        assert_eq!(config["log_level"], "info");
+        assert_eq!(config["cloud_audio_api_key"], "[redacted]");
+    }
+
+    #[tokio::test]
+    async fn test_backup_locations() {
+        let (app, _) = setup_app().await;
+        let (status, body) = post(&app, "/v1/backup", "{}").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "conflict");
+
+        let dir = tempfile::tempdir().unwrap();
+        let allowed = dir.path().join("backup").to_string_lossy().into_owned();
+        let (app, _) = setup_app_with(|state| AppState {
+            backup_locations: vec![allowed.clone()],
+            ..state
+        })
+        .await;
+
+        let elsewhere = json!({ "location": dir.path().join("elsewhere") }).to_string();
+        let (status, _) = post(&app, "/v1/backup", &elsewhere).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!dir.path().join("elsewhere").exists());
+
+        let (status, _) = post(
+            &app,
+            "/v1/backup",
+            &json!({ "location": allowed }).to_string(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        std::fs::remove_dir_all(&allowed).unwrap();
+        let (status, _) = post(&app, "/v1/backup", "{}").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(dir.path().join("backup").join("manifest.json").exists());
+    }
+}
@@ -0,0 +1,156 @@
+#[cfg(test)]
+mod tests {
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use chrono::Utc;
+    use screenpipe_server::{ContentType, DatabaseManager, NewRule, Rule, RuleCursor, RuleEngine};
+    use screenpipe_vision::OcrEngine;
+    use serde_json::Value;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    /// URL of a webhook forwarding what it receives to the returned channel
+    async fn webhook() -> (String, mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(payload): Json<Value>| async move {
+                let _ = tx.send(payload);
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/hook", addr), rx)
+    }
+
+    fn rule(id: i64, pattern: &str, is_regex: bool, content_type: ContentType, url: &str) -> Rule {
+        Rule {
+            id,
+            name: format!("rule {}", id),
+            pattern: pattern.to_string(),
+            is_regex,
+            content_type,
+            webhook_url: url.to_string(),
+            cooldown_secs: 300,
+            created_at: Utc::now(),
+        }
+    }
+
+    async fn insert_frame(db: &DatabaseManager, text: &str) -> i64 {
+        let frame_id = db.insert_frame("pagerduty").await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            text,
+            "[]",
+            "[]",
+            "{}",
+            "pagerduty",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+        frame_id
+    }
+
+    async fn next(rx: &mut mpsc::UnboundedReceiver<Value>) -> Option<Value> {
+        tokio::time::timeout(Duration::from_millis(500), rx.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    #[tokio::test]
+    async fn test_rules_send_matches_to_webhook() {
+        let (url, mut rx) = webhook().await;
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("incident.mp4")
+            .await
+            .unwrap();
+        let text = "SEV1: production incident INC-42 opened";
+        // Stored before, not checked
+        insert_frame(&db, text).await;
+        let mut cursor = RuleCursor::now(&db).await.unwrap();
+
+        let engine = RuleEngine::new();
+        engine.set_rules(vec![
+            rule(1, "Production Incident", false, ContentType::All, &url),
+            rule(2, r"INC-\d+", true, ContentType::Audio, &url),
+        ]);
+        let frame_id = insert_frame(&db, text).await;
+        engine.check_stored(&db, &mut cursor).await.unwrap();
+        assert_eq!(cursor.frame_id, frame_id);
+
+        // Only the keyword rule, the regex one watches transcriptions
+        let payload = next(&mut rx).await.unwrap();
+        assert_eq!(payload["type"], "OCR");
+        assert_eq!(payload["content"]["frame_id"], frame_id);
+        assert_eq!(payload["content"]["file_path"], "incident.mp4");
+        assert_eq!(payload["content"]["offset_index"], 1);
+        assert_eq!(payload["content"]["text"], text);
+        assert_eq!(payload["content"]["app_name"], "pagerduty");
+        assert_eq!(payload["rule"]["id"], 1);
+        assert_eq!(payload["matched"], "production incident");
+        assert!(payload["text"].as_str().unwrap().contains("rule 1"));
+        assert!(next(&mut rx).await.is_none());
+
+        // Already checked
+        engine.check_stored(&db, &mut cursor).await.unwrap();
+        assert!(next(&mut rx).await.is_none());
+
+        // Still on screen, within the cooldown
+        insert_frame(&db, text).await;
+        engine.check_stored(&db, &mut cursor).await.unwrap();
+        assert!(next(&mut rx).await.is_none());
+
+        let chunk_id = db.insert_audio_chunk("incident.wav").await.unwrap();
+        db.insert_audio_transcription(chunk_id, "opening INC-43", 0, "Whisper")
+            .await
+            .unwrap();
+        engine.check_stored(&db, &mut cursor).await.unwrap();
+        let payload = next(&mut rx).await.unwrap();
+        assert_eq!(payload["type"], "Audio");
+        assert_eq!(payload["content"]["chunk_id"], chunk_id);
+        assert_eq!(payload["content"]["file_path"], "incident.wav");
+        assert_eq!(payload["rule"]["id"], 2);
+        assert_eq!(payload["matched"], "INC-43");
+
+        // Reloading keeps the cooldown of the rules kept
+        engine.set_rules(vec![
+            rule(1, "Production Incident", false, ContentType::All, &url),
+            rule(3, "incident", false, ContentType::OCR, &url),
+        ]);
+        insert_frame(&db, text).await;
+        engine.check_stored(&db, &mut cursor).await.unwrap();
+        assert_eq!(next(&mut rx).await.unwrap()["rule"]["id"], 3);
+        assert!(next(&mut rx).await.is_none());
+    }
+
+    #[test]
+    fn test_new_rule_validation() {
+        let new_rule = |pattern: &str, is_regex: bool, webhook_url: &str| NewRule {
+            name: "alerts".to_string(),
+            pattern: pattern.to_string(),
+            is_regex,
+            content_type: ContentType::All,
+            webhook_url: webhook_url.to_string(),
+            cooldown_secs: 300,
+        };
+        assert!(new_rule("incident", false, "https://hooks.slack.com/x")
+            .validate()
+            .is_ok());
+        assert!(new_rule(r"INC-\d+", true, "http://localhost:8080")
+            .validate()
+            .is_ok());
+        assert!(new_rule("INC-(", true, "https://hooks.slack.com/x")
+            .validate()
+            .is_err());
+        assert!(new_rule(" ", false, "https://hooks.slack.com/x")
+            .validate()
+            .is_err());
+        assert!(new_rule("incident", false, "file:///etc/passwd")
+            .validate()
+            .is_err());
+    }
+}
@@ -0,0 +1,882 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use screenpipe_server::{
+        parse_search_query, search_syntax_error, ContentType, DatabaseManager, FacetCount,
+        Highlight, IngestBatch, IngestTranscription, SearchCursor, SearchFacets, SearchQueryError,
+        SearchResult,
+    };
+    use screenpipe_vision::OcrEngine;
+    use std::sync::Arc;
+
+    /// Sorted texts of the results
+    fn texts(results: Vec<SearchResult>) -> Vec<String> {
+        let mut texts: Vec<String> = results
+            .into_iter()
+            .map(|result| match result {
+                SearchResult::OCR(ocr) => ocr.ocr_text,
+                SearchResult::Audio(audio) => audio.transcription,
+                SearchResult::Clipboard(clipboard) => clipboard.text,
+                SearchResult::Notification(notification) => notification.body,
+            })
+            .collect();
+        texts.sort();
+        texts
+    }
+
+    /// Relevance of a result of a search by relevance
+    fn relevance(result: &SearchResult) -> f64 {
+        match result {
+            SearchResult::OCR(ocr) => ocr.relevance,
+            SearchResult::Audio(audio) => audio.relevance,
+            SearchResult::Clipboard(clipboard) => clipboard.relevance,
+            SearchResult::Notification(notification) => notification.relevance,
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_search_query() {
+        assert_eq!(parse_search_query("").unwrap(), None);
+        assert_eq!(parse_search_query("  ").unwrap(), None);
+        assert_eq!(
+            parse_search_query("foo bar").unwrap().unwrap(),
+            r#""foo" AND "bar""#
+        );
+        assert_eq!(
+            parse_search_query(r#""exact phrase" OR invoi*"#)
+                .unwrap()
+                .unwrap(),
+            r#""exact phrase" OR "invoi"*"#
+        );
+        assert_eq!(
+            parse_search_query("foo AND bar NOT baz").unwrap().unwrap(),
+            r#""foo" AND "bar" NOT "baz""#
+        );
+        assert_eq!(
+            parse_search_query("foo (bar OR baz)").unwrap().unwrap(),
+            r#""foo" AND ( "bar" OR "baz" )"#
+        );
+        // FTS5 syntax is neutralized
+        assert_eq!(
+            parse_search_query("text:foo - bar^ and").unwrap().unwrap(),
+            r#""text:foo" AND "bar^" AND "and""#
+        );
+
+        for invalid in [
+            "NOT foo",
+            "foo AND",
+            "foo OR OR bar",
+            "(foo",
+            "foo)",
+            "()",
+            r#""foo"#,
+            "fo*o",
+        ] {
+            assert!(
+                parse_search_query(invalid).is_err(),
+                "{} should be invalid",
+                invalid
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_query_syntax() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for text in [
+            "Invoice 42 from ACME",
+            "invoicing software",
+            "ACME quarterly report",
+        ] {
+            let frame_id = db.insert_frame("").await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                "",
+                "",
+                "",
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(audio_chunk_id, "send the quarterly report", 0, "Whisper")
+            .await
+            .unwrap();
+
+        let results = db
+            .search(
+                "invoi*",
+                ContentType::OCR,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            texts(results),
+            vec!["Invoice 42 from ACME", "invoicing software"]
+        );
+
+        let results = db
+            .search(
+                "\"quarterly report\"",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            texts(results),
+            vec!["ACME quarterly report", "send the quarterly report"]
+        );
+
+        let results = db
+            .search(
+                "ACME NOT invoice",
+                ContentType::OCR,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(texts(results), vec!["ACME quarterly report"]);
+
+        let count = db
+            .count_search_results(
+                "acme OR software",
+                ContentType::All,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+
+        // Deleted rows leave the index
+        db.delete_audio_chunk(audio_chunk_id).await.unwrap();
+        let count = db
+            .count_search_results(
+                "quarterly",
+                ContentType::Audio,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        let error = db
+            .search(
+                "foo AND",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(search_syntax_error(&error).is_some());
+        assert!(matches!(&error, sqlx::Error::Decode(e) if e.is::<SearchQueryError>()));
+    }
+
+    #[tokio::test]
+    async fn test_search_index_survives_vacuum() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let mut frame_ids = Vec::new();
+        for text in ["deleted draft", "kept invoice"] {
+            let frame_id = db.insert_frame("").await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                "",
+                "",
+                "",
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+            frame_ids.push(frame_id);
+        }
+        sqlx::query("DELETE FROM ocr_text WHERE frame_id = ?1")
+            .bind(frame_ids[0])
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        // Would renumber the rows if the index used their implicit rowid
+        sqlx::query("VACUUM").execute(&db.pool).await.unwrap();
+
+        let results = db
+            .search(
+                "invoice",
+                ContentType::OCR,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let SearchResult::OCR(result) = &results[0] else {
+            panic!("Expected OCR result");
+        };
+        assert_eq!(result.frame_id, frame_ids[1]);
+        assert_eq!(result.ocr_text, "kept invoice");
+    }
+
+    #[tokio::test]
+    async fn test_search_highlights() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("").await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "Café invoice, second Invoice",
+            "",
+            "",
+            "",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(audio_chunk_id, "send the invoices", 0, "Whisper")
+            .await
+            .unwrap();
+
+        let results = db
+            .search(
+                "invoice*",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        for result in results {
+            match result {
+                SearchResult::OCR(ocr) => {
+                    assert_eq!(
+                        ocr.snippet,
+                        "Café <mark>invoice</mark>, second <mark>Invoice</mark>"
+                    );
+                    // Offsets are in characters, not bytes
+                    assert_eq!(
+                        ocr.highlights,
+                        vec![
+                            Highlight { start: 5, end: 12 },
+                            Highlight { start: 21, end: 28 }
+                        ]
+                    );
+                }
+                SearchResult::Audio(audio) => {
+                    assert_eq!(audio.snippet, "send the <mark>invoices</mark>");
+                    let highlight = &audio.highlights[0];
+                    assert_eq!(
+                        &audio.transcription[highlight.start..highlight.end],
+                        "invoices"
+                    );
+                }
+                SearchResult::Clipboard(_) | SearchResult::Notification(_) => unreachable!(),
+            }
+        }
+
+        // Nothing to highlight without query
+        let results = db
+            .search(
+                "",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        for result in results {
+            match result {
+                SearchResult::OCR(ocr) => {
+                    assert!(ocr.snippet.is_empty() && ocr.highlights.is_empty())
+                }
+                SearchResult::Audio(audio) => {
+                    assert!(audio.snippet.is_empty() && audio.highlights.is_empty())
+                }
+                SearchResult::Clipboard(_) | SearchResult::Notification(_) => unreachable!(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fuzzy_search() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for text in ["Iunch meeting with ACME", "launch party", "lunar calendar"] {
+            let frame_id = db.insert_frame("").await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                "",
+                "",
+                "",
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+
+        let count = db
+            .count_search_results(
+                "lunch meeting",
+                ContentType::All,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        let query = db.fuzzy_search_query("lunch meeting").await.unwrap();
+        let results = db
+            .search(
+                &query,
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(texts(results), vec!["Iunch meeting with ACME"]);
+
+        // "launch" is one insertion away from "lunch", "lunar" two edits
+        let query = db.fuzzy_search_query("lunch").await.unwrap();
+        let results = db
+            .search(
+                &query,
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            texts(results),
+            vec!["Iunch meeting with ACME", "launch party"]
+        );
+
+        // Short words and phrases stay exact
+        let query = db
+            .fuzzy_search_query(r#"ACM "lunch meeting""#)
+            .await
+            .unwrap();
+        let count = db
+            .count_search_results(&query, ContentType::All, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        assert!(db.fuzzy_search_query("lunch AND").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_search_page_cursor() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for i in 0..5 {
+            let frame_id = db.insert_frame("").await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                &format!("screen {}", i),
+                "",
+                "",
+                "",
+                "",
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        for i in 0..3 {
+            db.insert_audio_transcription(audio_chunk_id, &format!("voice {}", i), i, "Whisper")
+                .await
+                .unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<SearchCursor> = None;
+        let mut pages = 0;
+        loop {
+            let (results, next) = db
+                .search_page(
+                    "",
+                    ContentType::All,
+                    3,
+                    cursor.as_ref(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            pages += 1;
+            seen.extend(texts(results));
+            match next {
+                // Cursors survive the trip through the API
+                Some(next) => cursor = SearchCursor::decode(&next.encode()),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 3);
+        seen.sort();
+        assert_eq!(
+            seen,
+            vec![
+                "screen 0", "screen 1", "screen 2", "screen 3", "screen 4", "voice 0", "voice 1",
+                "voice 2"
+            ]
+        );
+
+        let (results, next) = db
+            .search_page(
+                "voice",
+                ContentType::Audio,
+                3,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(next.is_none());
+
+        assert!(SearchCursor::decode("not a cursor").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_search_page_cursor_same_timestamp() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        // Rows of one frame share its timestamp
+        let frame_id = db.insert_frame("").await.unwrap();
+        for i in 0..3 {
+            db.insert_ocr_text(
+                frame_id,
+                &format!("region {}", i),
+                "",
+                "",
+                "",
+                "",
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<SearchCursor> = None;
+        loop {
+            let (results, next) = db
+                .search_page(
+                    "",
+                    ContentType::OCR,
+                    1,
+                    cursor.as_ref(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            seen.extend(texts(results));
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        seen.sort();
+        assert_eq!(seen, vec!["region 0", "region 1", "region 2"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_by_relevance() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        for (text, age) in [
+            ("invoice invoice paid", Duration::days(30)),
+            ("invoice sent to the customer", Duration::zero()),
+        ] {
+            let frame_id = db.insert_frame("").await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                "",
+                "",
+                "",
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+            sqlx::query("UPDATE frames SET timestamp = ?1 WHERE id = ?2")
+                .bind(Utc::now() - age)
+                .bind(frame_id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(audio_chunk_id, "did they pay the invoice", 0, "Whisper")
+            .await
+            .unwrap();
+
+        let results = db
+            .search_by_relevance(
+                "invoice",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        let relevances: Vec<f64> = results.iter().map(relevance).collect();
+        assert!(relevances.windows(2).all(|pair| pair[0] >= pair[1]));
+        // The best match of each content type is the reference
+        let ocr: Vec<&SearchResult> = results
+            .iter()
+            .filter(|result| matches!(result, SearchResult::OCR(_)))
+            .collect();
+        match ocr[0] {
+            SearchResult::OCR(ocr) => {
+                assert_eq!(ocr.ocr_text, "invoice invoice paid");
+                assert_eq!(ocr.relevance, Some(1.0));
+            }
+            _ => unreachable!(),
+        }
+        assert!(relevance(ocr[1]) < 1.0);
+        let audio = results
+            .iter()
+            .find(|result| matches!(result, SearchResult::Audio(_)))
+            .unwrap();
+        assert_eq!(relevance(audio), 1.0);
+
+        // Old matches fade with a recency boost
+        let results = db
+            .search_by_relevance(
+                "invoice",
+                ContentType::OCR,
+                1,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(1.0),
+            )
+            .await
+            .unwrap();
+        assert_eq!(texts(results), vec!["invoice sent to the customer"]);
+
+        // Offset pagination
+        let results = db
+            .search_by_relevance(
+                "invoice",
+                ContentType::OCR,
+                10,
+                1,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(texts(results), vec!["invoice sent to the customer"]);
+
+        // Without a query every result matches as well
+        let results = db
+            .search_by_relevance(
+                "",
+                ContentType::All,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(results.iter().all(|result| relevance(result) == 1.0));
+    }
+
+    #[tokio::test]
+    async fn test_search_facets() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("test_video.mp4")
+            .await
+            .unwrap();
+        let day = |day: u32, hour: u32| Utc.with_ymd_and_hms(2024, 9, day, hour, 0, 0).unwrap();
+        for (app_name, text, timestamp) in [
+            ("chrome", "quarterly report", day(13, 10)),
+            ("chrome", "report draft", day(14, 10)),
+            ("slack", "send the report", day(14, 11)),
+            ("slack", "lunch", day(14, 12)),
+        ] {
+            let frame_id = db.insert_frame(app_name).await.unwrap();
+            db.insert_ocr_text(
+                frame_id,
+                text,
+                "",
+                "",
+                "",
+                app_name,
+                Arc::new(OcrEngine::Tesseract),
+            )
+            .await
+            .unwrap();
+            sqlx::query("UPDATE frames SET timestamp = ?1 WHERE id = ?2")
+                .bind(timestamp)
+                .bind(frame_id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        db.ingest(&IngestBatch {
+            device_id: "laptop".to_string(),
+            frames: Vec::new(),
+            transcriptions: vec![IngestTranscription {
+                timestamp: day(14, 12),
+                file_path: "laptop.mp4".to_string(),
+                offset_index: 0,
+                transcription: "the report is late".to_string(),
+                transcription_engine: "Whisper".to_string(),
+                duration: None,
+                language: None,
+                segments: Vec::new(),
+                entities: Vec::new(),
+            }],
+        })
+        .await
+        .unwrap();
+        db.insert_clipboard("report.pdf", "chrome", day(14, 9))
+            .await
+            .unwrap();
+
+        let counts = |facet: &[FacetCount]| -> Vec<(String, i64)> {
+            facet
+                .iter()
+                .map(|count| (count.value.clone(), count.count))
+                .collect()
+        };
+        let facets = db
+            .search_facets(
+                "report",
+                ContentType::All,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            counts(&facets.app_name),
+            vec![("chrome".to_string(), 3), ("slack".to_string(), 1)]
+        );
+        assert_eq!(
+            counts(&facets.day),
+            vec![("2024-09-13".to_string(), 1), ("2024-09-14".to_string(), 4)]
+        );
+        assert_eq!(
+            counts(&facets.content_type),
+            vec![
+                ("ocr".to_string(), 3),
+                ("audio".to_string(), 1),
+                ("clipboard".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            counts(&facets.device),
+            vec![("local".to_string(), 4), ("laptop".to_string(), 1)]
+        );
+        let total = db
+            .count_search_results(
+                "report",
+                ContentType::All,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(total, 5);
+
+        // The same filters as the search
+        let facets = db
+            .search_facets(
+                "report",
+                ContentType::All,
+                Some(day(14, 0)),
+                None,
+                Some("slack"),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            facets,
+            SearchFacets {
+                app_name: vec![FacetCount {
+                    value: "slack".to_string(),
+                    count: 1
+                }],
+                day: vec![FacetCount {
+                    value: "2024-09-14".to_string(),
+                    count: 1
+                }],
+                content_type: vec![FacetCount {
+                    value: "ocr".to_string(),
+                    count: 1
+                }],
+                device: vec![FacetCount {
+                    value: "local".to_string(),
+                    count: 1
+                }],
+            }
+        );
+        let facets = db
+            .search_facets(
+                "nothing",
+                ContentType::All,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(facets, SearchFacets::default());
+    }
+}
@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Utc};
+    use screenpipe_server::{
+        audio_device_of, segment_sessions, AudioRecord, DatabaseManager, Storage, TranscribedChunk,
+    };
+
+    const MIC: &str = "MacBook Pro Microphone (input)";
+    const SPEAKERS: &str = "MacBook Pro Speakers (output)";
+
+    fn chunk(timestamp: DateTime<Utc>, audio_device: &str) -> TranscribedChunk {
+        TranscribedChunk {
+            timestamp,
+            duration: Some(30.0),
+            audio_device: audio_device.to_string(),
+        }
+    }
+
+    fn audio(timestamp: DateTime<Utc>, audio_device: &str, transcription: &str) -> AudioRecord {
+        AudioRecord {
+            timestamp,
+            file_path: format!(
+                "/data/{}_{}.mp4",
+                audio_device,
+                timestamp.format("%Y-%m-%d_%H-%M-%S")
+            ),
+            duration: Some(30.0),
+            transcription: transcription.to_string(),
+            transcription_engine: "Whisper".to_string(),
+            language: None,
+            segments: Vec::new(),
+            entities: Vec::new(),
+            format: None,
+            transcription_error: None,
+        }
+    }
+
+    #[test]
+    fn test_audio_device_of() {
+        assert_eq!(
+            audio_device_of("/data/MacBook Pro Microphone (input)_2024-09-08_10-00-00.mp4"),
+            MIC
+        );
+        assert_eq!(
+            audio_device_of("/data/Jabra_Evolve (input)_2024-09-08_10-00-00.mp4"),
+            "Jabra_Evolve (input)"
+        );
+        assert_eq!(audio_device_of("imported.mp4"), "imported");
+    }
+
+    #[test]
+    fn test_segment_sessions() {
+        let start = Utc::now();
+        let at = |seconds| start + Duration::seconds(seconds);
+        let sessions = segment_sessions(
+            &[
+                chunk(at(0), MIC),
+                chunk(at(30), MIC),
+                chunk(at(60), SPEAKERS),
+                // 2 minutes after the end of the last chunk, not longer than the gap
+                chunk(at(210), MIC),
+                // Longer
+                chunk(at(400), MIC),
+            ],
+            Duration::minutes(2),
+        );
+        let bounds: Vec<(i64, i64, i64, usize)> = sessions
+            .iter()
+            .map(|session| {
+                (
+                    (session.start_time - start).num_seconds(),
+                    (session.end_time - start).num_seconds(),
+                    session.chunks,
+                    session.audio_devices.len(),
+                )
+            })
+            .collect();
+        assert_eq!(bounds, vec![(0, 240, 4, 2), (400, 430, 1, 1)]);
+        assert_eq!(sessions[0].audio_devices, vec![MIC, SPEAKERS]);
+        assert!(segment_sessions(&[], Duration::minutes(2)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_transcript_sessions() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let start = Utc::now() - Duration::hours(1);
+        let at = |minutes| start + Duration::minutes(minutes);
+        for (timestamp, audio_device, transcription) in [
+            (at(0), MIC, "shall we start"),
+            (at(0), SPEAKERS, "yes, the agenda first"),
+            (at(1), MIC, ""),
+            (at(1), SPEAKERS, "then the budget"),
+            (at(30), MIC, "note to self, call the bank"),
+        ] {
+            db.write_audio(audio(timestamp, audio_device, transcription))
+                .await
+                .unwrap();
+        }
+        db.flush_writes().await;
+
+        let gap = Duration::minutes(2);
+        assert_eq!(db.segment_transcript_sessions(gap).await.unwrap(), 2);
+        let sessions = db.list_sessions(None, None, 10).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].transcript, "note to self, call the bank");
+        let meeting = &sessions[1];
+        assert_eq!(meeting.start_time, at(0));
+        assert_eq!(meeting.end_time, at(1) + Duration::seconds(30));
+        assert_eq!(meeting.chunks, 3);
+        assert_eq!(meeting.audio_devices, vec![MIC, SPEAKERS]);
+        assert_eq!(
+            meeting.transcript,
+            "shall we start\nyes, the agenda first\nthen the budget"
+        );
+
+        // The last session goes on, the others are kept
+        db.write_audio(audio(at(31), MIC, "and the dentist"))
+            .await
+            .unwrap();
+        db.flush_writes().await;
+        assert_eq!(db.segment_transcript_sessions(gap).await.unwrap(), 0);
+        let updated = db.list_sessions(None, None, 10).await.unwrap();
+        assert_eq!(updated[0].id, sessions[0].id);
+        assert_eq!(
+            updated[0].transcript,
+            "note to self, call the bank\nand the dentist"
+        );
+        assert_eq!(updated[1], sessions[1]);
+
+        let recent = db.list_sessions(Some(at(10)), None, 10).await.unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(db.list_sessions(None, None, 1).await.unwrap().len(), 1);
+
+        // Written late, e.g. imported, they join the sessions around them
+        for (timestamp, transcription) in [
+            (at(20), "reminder for later"),
+            (at(3), "any other business"),
+        ] {
+            db.write_audio(audio(timestamp, MIC, transcription))
+                .await
+                .unwrap();
+        }
+        db.flush_writes().await;
+        assert_eq!(db.segment_transcript_sessions(gap).await.unwrap(), 1);
+        let late = db.list_sessions(None, None, 10).await.unwrap();
+        assert_eq!(late.len(), 3);
+        assert_eq!(late[0], updated[0]);
+        assert_eq!(late[1].transcript, "reminder for later");
+        assert_eq!(late[2].id, sessions[1].id);
+        assert_eq!(late[2].chunks, 4);
+        assert_eq!(late[2].end_time, at(3) + Duration::seconds(30));
+    }
+}
@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_server::Shutdown;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_guards() {
+        let shutdown = Shutdown::new();
+        assert!(!shutdown.is_requested());
+        // Nothing to wait for
+        assert!(shutdown.wait(Duration::from_millis(10)).await);
+
+        let task = {
+            let shutdown = shutdown.clone();
+            let guard = shutdown.guard();
+            tokio::spawn(async move {
+                shutdown.requested().await;
+                // Writing what was captured
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                drop(guard);
+            })
+        };
+        assert!(!shutdown.wait(Duration::from_millis(20)).await);
+
+        shutdown.request();
+        assert!(shutdown.is_requested());
+        assert!(shutdown.wait(Duration::from_secs(5)).await);
+        task.await.unwrap();
+
+        // Already requested, resolves right away
+        tokio::time::timeout(Duration::from_secs(1), shutdown.requested())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_child_shutdown() {
+        let shutdown = Shutdown::new();
+        let device = shutdown.child();
+        let other_device = shutdown.child();
+
+        // Stopping one device doesn't stop the recording
+        device.request();
+        assert!(device.is_requested());
+        assert!(!shutdown.is_requested());
+        assert!(!other_device.is_requested());
+
+        shutdown.request();
+        tokio::time::timeout(Duration::from_secs(1), other_device.requested())
+            .await
+            .unwrap();
+    }
+}
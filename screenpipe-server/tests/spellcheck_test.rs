@@ -0,0 +1,167 @@
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "recording")]
+    use chrono::Utc;
+    #[cfg(feature = "recording")]
+    use screenpipe_server::{
+        ContentProcessors, ContentType, DatabaseManager, FrameRecord, ProcessedStorage,
+        SearchResult, Storage,
+    };
+    use screenpipe_server::{SpellChecker, SpellDictionary};
+    #[cfg(feature = "recording")]
+    use std::sync::Arc;
+
+    fn dictionary(language: &str, words: &[(&str, u64)]) -> SpellDictionary {
+        SpellDictionary::new(
+            language,
+            words.iter().map(|(word, count)| (word.to_string(), *count)),
+        )
+    }
+
+    fn english() -> SpellChecker {
+        SpellChecker::new()
+            .with(dictionary(
+                "eng",
+                &[
+                    ("quarterly", 500),
+                    ("report", 900),
+                    ("revenue", 300),
+                    ("meeting", 800),
+                    ("melting", 50),
+                    ("the", 10000),
+                ],
+            ))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_spellcheck_corrects_misread_words() {
+        let checker = english();
+        assert_eq!(
+            checker.correct("Quarteriy rep0rt: the revenue, meetlng at 3pm"),
+            "Quarterly report: the revenue, meeting at 3pm"
+        );
+        // Most used of the words as close
+        assert_eq!(checker.correct("meting"), "meeting");
+        // Names, code, acronyms and numbers are left as read
+        assert_eq!(
+            checker.correct("Rep0rtGen REP0RT re2p0rt 2024 repo"),
+            "Rep0rtGen REP0RT re2p0rt 2024 repo"
+        );
+        // Too far from any word
+        assert_eq!(checker.correct("kubernetes"), "kubernetes");
+    }
+
+    #[test]
+    fn test_spellcheck_languages() {
+        let checker = english()
+            .with(dictionary("deu", &[("bericht", 100), ("Straße", 100)]))
+            .unwrap();
+        assert_eq!(
+            checker.correct("Berlcht Strase report"),
+            "Bericht Straße report"
+        );
+
+        // Turkish lowercases I to ı
+        let turkish = SpellChecker::new()
+            .with(dictionary("tur", &[("ıslak", 10), ("istanbul", 10)]))
+            .unwrap();
+        assert_eq!(
+            turkish.correct("ISLAK Islak Istanbul"),
+            "ISLAK Islak İstanbul"
+        );
+
+        assert!(SpellChecker::new()
+            .with(dictionary("chi_sim", &[("报告", 10)]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_spell_dictionary_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eng.txt");
+        std::fs::write(&path, "# words\nreport 900\nquarterly 500\nrevenue\n").unwrap();
+        let dictionary = SpellDictionary::load("eng", &path).unwrap();
+        assert_eq!(dictionary.len(), 3);
+        assert!(dictionary.contains("Revenue"));
+
+        std::fs::write(&path, "report many\n").unwrap();
+        assert!(SpellDictionary::load("eng", &path).is_err());
+    }
+
+    /// Misread words are found by their correct spelling, and still by what was read
+    #[cfg(feature = "recording")]
+    #[tokio::test]
+    async fn test_spellcheck_search_recall() {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let storage = ProcessedStorage::new(db.clone(), ContentProcessors::new().with(english()));
+        storage
+            .insert_video_chunk("video.mp4", "h264")
+            .await
+            .unwrap();
+        for text in ["Quarteriy rep0rt draft", "quarterly report final"] {
+            storage
+                .write_frame(FrameRecord {
+                    timestamp: Utc::now(),
+                    app_name: "preview".to_string(),
+                    text: text.to_string(),
+                    raw_text: None,
+                    text_json: "[]".to_string(),
+                    new_text_json_vs_previous_frame: "[]".to_string(),
+                    raw_data_output_from_ocr: "{}".to_string(),
+                    ocr_engine: "Tesseract".to_string(),
+                    words: Vec::new(),
+                    windows: Vec::new(),
+                    region: None,
+                    entities: Vec::new(),
+                })
+                .await
+                .unwrap();
+        }
+        storage.flush_writes().await;
+
+        let rows: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT text, raw_text FROM ocr_text ORDER BY frame_id")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "Quarterly report draft".to_string(),
+                    Some("Quarteriy rep0rt draft".to_string())
+                ),
+                ("quarterly report final".to_string(), None),
+            ]
+        );
+
+        let search = |query: &'static str| {
+            let db = db.clone();
+            async move {
+                db.search(
+                    query,
+                    ContentType::OCR,
+                    10,
+                    0,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|result| match result {
+                    SearchResult::OCR(ocr) => ocr.ocr_text,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(search("quarterly report").await.len(), 2);
+        assert_eq!(search("quarteriy").await, vec!["Quarterly report draft"]);
+    }
+}
@@ -0,0 +1,187 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use screenpipe_server::{
+        disk_usage, evict_oldest_chunks, prune_chunks, ChunkKind, ContentType, DatabaseManager,
+    };
+    use screenpipe_vision::OcrEngine;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn write_chunk(dir: &Path, name: &str, size: usize) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, vec![0u8; size]).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_evict_oldest_chunks_until_under_quota() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+
+        let old_video = write_chunk(&data_dir, "old_video.mp4", 1000);
+        db.insert_video_chunk_without_codec(&old_video)
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("foo").await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "Old OCR",
+            "",
+            "",
+            "",
+            "foo",
+            Arc::new(OcrEngine::Tesseract),
+        )
+        .await
+        .unwrap();
+
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let audio = write_chunk(&data_dir, "audio.mp4", 1000);
+        db.insert_audio_chunk(&audio).await.unwrap();
+        let current_video = write_chunk(&data_dir, "current_video.mp4", 1000);
+        db.insert_video_chunk_without_codec(&current_video)
+            .await
+            .unwrap();
+
+        // Under quota, nothing is evicted
+        let freed = evict_oldest_chunks(&db, &data_dir.to_string_lossy(), 3000)
+            .await
+            .unwrap();
+        assert_eq!(freed, 0);
+
+        let freed = evict_oldest_chunks(&db, &data_dir.to_string_lossy(), 2500)
+            .await
+            .unwrap();
+        assert_eq!(freed, 1000);
+        assert!(!Path::new(&old_video).exists());
+        assert!(Path::new(&audio).exists());
+        assert!(Path::new(&current_video).exists());
+
+        let results = db
+            .search(
+                "Old OCR",
+                ContentType::OCR,
+                10,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+
+        // The chunk being written is never evicted
+        let freed = evict_oldest_chunks(&db, &data_dir.to_string_lossy(), 1)
+            .await
+            .unwrap();
+        assert_eq!(freed, 1000);
+        assert!(!Path::new(&audio).exists());
+        assert!(Path::new(&current_video).exists());
+    }
+
+    #[tokio::test]
+    async fn test_disk_usage_and_prune() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+
+        let old_video = write_chunk(&data_dir, "old_video.mp4", 1000);
+        db.insert_video_chunk_without_codec(&old_video)
+            .await
+            .unwrap();
+        db.insert_frame("foo").await.unwrap();
+        let audio = write_chunk(&data_dir, "audio.mp4", 500);
+        db.insert_audio_chunk(&audio).await.unwrap();
+        let current_video = write_chunk(&data_dir, "current_video.mp4", 200);
+        db.insert_video_chunk_without_codec(&current_video)
+            .await
+            .unwrap();
+
+        let usage = disk_usage(&db).await.unwrap();
+        assert_eq!(usage.video.chunks, 2);
+        assert_eq!(usage.video.bytes, 1200);
+        assert!(usage.video.oldest.is_some());
+        assert_eq!(usage.audio.chunks, 1);
+        assert_eq!(usage.audio.bytes, 500);
+        assert!(usage.database_bytes > 0);
+        assert_eq!(usage.total_bytes, usage.database_bytes + 1700);
+
+        // Nothing recorded that long ago
+        let summary = prune_chunks(&db, Utc::now() - Duration::days(1), ChunkKind::All, false)
+            .await
+            .unwrap();
+        assert_eq!(summary.video_chunks + summary.audio_chunks, 0);
+
+        let before = Utc::now() + Duration::minutes(1);
+        let summary = prune_chunks(&db, before, ChunkKind::Video, true)
+            .await
+            .unwrap();
+        assert_eq!(summary.video_chunks, 1);
+        assert_eq!(summary.freed_bytes, 1000);
+        assert!(Path::new(&old_video).exists());
+
+        let summary = prune_chunks(&db, before, ChunkKind::Video, false)
+            .await
+            .unwrap();
+        assert_eq!(summary.video_chunks, 1);
+        assert_eq!(summary.audio_chunks, 0);
+        assert!(!Path::new(&old_video).exists());
+        assert!(Path::new(&audio).exists());
+        // The chunk being written is kept
+        assert!(Path::new(&current_video).exists());
+
+        let usage = disk_usage(&db).await.unwrap();
+        assert_eq!(usage.video.chunks, 1);
+        assert_eq!(usage.video.bytes, 200);
+        assert!(usage.video.oldest.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_chunks_kept_when_their_file_cant_be_deleted() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+
+        // remove_file fails on a directory
+        let stuck = data_dir.join("stuck.mp4");
+        std::fs::create_dir_all(&stuck).unwrap();
+        write_chunk(&stuck, "inside", 1000);
+        let stuck = stuck.to_string_lossy().into_owned();
+        db.insert_audio_chunk(&stuck).await.unwrap();
+        let audio = write_chunk(&data_dir, "audio.mp4", 1000);
+        db.insert_audio_chunk(&audio).await.unwrap();
+
+        // Skipped, the next chunk is evicted
+        let freed = evict_oldest_chunks(&db, &data_dir.to_string_lossy(), 1)
+            .await
+            .unwrap();
+        assert_eq!(freed, 1000);
+        assert!(!Path::new(&audio).exists());
+        let chunks = db.list_audio_chunks().await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].1, stuck);
+
+        let before = Utc::now() + Duration::minutes(1);
+        let summary = prune_chunks(&db, before, ChunkKind::Audio, false)
+            .await
+            .unwrap();
+        assert_eq!(summary.audio_chunks, 0);
+        assert_eq!(summary.failed, vec![stuck]);
+        assert_eq!(db.list_audio_chunks().await.unwrap().len(), 1);
+    }
+}
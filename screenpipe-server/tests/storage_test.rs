@@ -0,0 +1,600 @@
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use screenpipe_server::{
+        open_storage, AudioRecord, ContentType, DatabaseManager, FrameRecord, MirroredStorage,
+        Storage,
+    };
+    #[cfg(feature = "recording")]
+    use screenpipe_server::{
+        ContentProcessor, ContentProcessors, EntityExtractor, IngestBatch, IngestFrame,
+        IngestTranscription, ProcessedStorage, SearchResult,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::sync::Semaphore;
+
+    #[cfg(feature = "recording")]
+    fn frame(text: &str) -> FrameRecord {
+        FrameRecord {
+            timestamp: Utc::now(),
+            app_name: "code".to_string(),
+            text: text.to_string(),
+            raw_text: None,
+            text_json: "[]".to_string(),
+            new_text_json_vs_previous_frame: "[]".to_string(),
+            raw_data_output_from_ocr: "{}".to_string(),
+            ocr_engine: "Tesseract".to_string(),
+            words: Vec::new(),
+            windows: Vec::new(),
+            region: None,
+            entities: Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "recording")]
+    fn audio(transcription: &str) -> AudioRecord {
+        AudioRecord {
+            timestamp: Utc::now(),
+            file_path: format!("{}.mp4", transcription.replace(' ', "_")),
+            duration: Some(30.0),
+            transcription: transcription.to_string(),
+            transcription_engine: "Whisper".to_string(),
+            language: None,
+            segments: Vec::new(),
+            entities: Vec::new(),
+            format: None,
+            transcription_error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirrored_storage() {
+        let local = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let remote = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let storage = MirroredStorage::new(local.clone(), remote.clone());
+
+        storage
+            .insert_video_chunk("video.mp4", "hevc")
+            .await
+            .unwrap();
+        storage
+            .write_frame(FrameRecord {
+                timestamp: Utc::now(),
+                app_name: "code".to_string(),
+                text: "fn main".to_string(),
+                raw_text: None,
+                text_json: "[]".to_string(),
+                new_text_json_vs_previous_frame: "[]".to_string(),
+                raw_data_output_from_ocr: "{}".to_string(),
+                ocr_engine: "Tesseract".to_string(),
+                words: Vec::new(),
+                windows: Vec::new(),
+                region: None,
+                entities: Vec::new(),
+            })
+            .await
+            .unwrap();
+        storage
+            .write_audio(AudioRecord {
+                timestamp: Utc::now(),
+                file_path: "audio.opus".to_string(),
+                duration: Some(30.0),
+                transcription: "hello there".to_string(),
+                transcription_engine: "Whisper".to_string(),
+                language: Some("en".to_string()),
+                segments: Vec::new(),
+                entities: Vec::new(),
+                format: Some("opus".to_string()),
+                transcription_error: None,
+            })
+            .await
+            .unwrap();
+        // Nothing said, the chunk is still recorded
+        storage
+            .write_audio(AudioRecord {
+                timestamp: Utc::now(),
+                file_path: "silence.mp4".to_string(),
+                duration: Some(30.0),
+                transcription: String::new(),
+                transcription_engine: "Whisper".to_string(),
+                language: None,
+                segments: Vec::new(),
+                entities: Vec::new(),
+                format: None,
+                transcription_error: None,
+            })
+            .await
+            .unwrap();
+        storage.flush_writes().await;
+
+        for db in [&local, &remote] {
+            let count = db
+                .count_search_results("", ContentType::All, None, None, None, None, None, None)
+                .await
+                .unwrap();
+            assert_eq!(count, 2);
+            let chunks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audio_chunks")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+            assert_eq!(chunks, 2);
+            let codec: Option<String> = sqlx::query_scalar("SELECT codec FROM video_chunks")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+            assert_eq!(codec.as_deref(), Some("hevc"));
+            let formats: Vec<Option<String>> =
+                sqlx::query_scalar("SELECT format FROM audio_chunks ORDER BY id")
+                    .fetch_all(&db.pool)
+                    .await
+                    .unwrap();
+            assert_eq!(formats, vec![Some("opus".to_string()), None]);
+        }
+    }
+
+    /// Mirror waiting for a permit of `gate` to store each video chunk
+    struct StalledMirror {
+        gate: Semaphore,
+        video_chunks: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Storage for StalledMirror {
+        async fn insert_video_chunk(&self, _: &str, _: &str) -> Result<(), sqlx::Error> {
+            self.gate.acquire().await.unwrap().forget();
+            self.video_chunks.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn write_frame(&self, _: FrameRecord) -> Result<(), sqlx::Error> {
+            Ok(())
+        }
+
+        async fn write_frame_now(&self, _: FrameRecord) -> Result<Option<i64>, sqlx::Error> {
+            Ok(None)
+        }
+
+        async fn write_audio(&self, _: AudioRecord) -> Result<(), sqlx::Error> {
+            Ok(())
+        }
+
+        async fn write_retranscription(&self, _: AudioRecord) -> Result<(), sqlx::Error> {
+            Ok(())
+        }
+
+        async fn write_reocr(&self, _: &str, _: i64, _: FrameRecord) -> Result<(), sqlx::Error> {
+            Ok(())
+        }
+
+        async fn flush_writes(&self) {}
+    }
+
+    #[tokio::test]
+    async fn test_mirror_drops_writes_when_behind() {
+        let local = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let mirror = Arc::new(StalledMirror {
+            gate: Semaphore::new(0),
+            video_chunks: AtomicUsize::new(0),
+        });
+        let storage = MirroredStorage::with_capacity(local.clone(), mirror.clone(), 2);
+
+        for i in 0..10 {
+            storage
+                .insert_video_chunk(&format!("video_{}.mp4", i), "h264")
+                .await
+                .unwrap();
+        }
+        // At most one is being written and two wait
+        let dropped = storage.dropped_writes();
+        assert!(dropped >= 7, "{}", dropped);
+        let chunks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM video_chunks")
+            .fetch_one(&local.pool)
+            .await
+            .unwrap();
+        assert_eq!(chunks, 10);
+
+        mirror.gate.add_permits(10);
+        storage.flush_writes().await;
+        assert_eq!(
+            mirror.video_chunks.load(Ordering::SeqCst) as u64,
+            10 - dropped
+        );
+    }
+
+    /// Masks a word and drops what mentions a password
+    #[cfg(feature = "recording")]
+    struct Redactor;
+
+    #[cfg(feature = "recording")]
+    impl ContentProcessor for Redactor {
+        fn name(&self) -> &str {
+            "redactor"
+        }
+
+        fn process_frame(&self, mut frame: FrameRecord) -> Option<FrameRecord> {
+            if frame.text.contains("password") {
+                return None;
+            }
+            frame.text = frame.text.replace("darn", "****");
+            Some(frame)
+        }
+
+        fn process_audio(&self, mut audio: AudioRecord) -> Option<AudioRecord> {
+            if audio.transcription.contains("password") {
+                return None;
+            }
+            audio.language = Some("en".to_string());
+            Some(audio)
+        }
+    }
+
+    #[cfg(feature = "recording")]
+    struct Panicking;
+
+    #[cfg(feature = "recording")]
+    impl ContentProcessor for Panicking {
+        fn name(&self) -> &str {
+            "panicking"
+        }
+
+        fn process_frame(&self, _: FrameRecord) -> Option<FrameRecord> {
+            panic!("broken processor");
+        }
+    }
+
+    #[cfg(feature = "recording")]
+    #[tokio::test]
+    async fn test_processed_storage() {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        let processors = ContentProcessors::new().with(Panicking).with(Redactor);
+        let storage = ProcessedStorage::new(db.clone(), processors.clone());
+
+        storage
+            .insert_video_chunk("video.mp4", "h264")
+            .await
+            .unwrap();
+        for text in ["darn build failed", "my password is hunter2", "fn main"] {
+            storage.write_frame(frame(text)).await.unwrap();
+        }
+        storage.write_audio(audio("hello there")).await.unwrap();
+        storage.write_audio(audio("the password is")).await.unwrap();
+        storage.flush_writes().await;
+
+        let texts: Vec<String> = sqlx::query_scalar("SELECT text FROM ocr_text ORDER BY frame_id")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(texts, vec!["**** build failed", "", "fn main"]);
+        // The dropped frame keeps its place in the video
+        let offsets: Vec<i64> = sqlx::query_scalar("SELECT offset_index FROM frames ORDER BY id")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(offsets, vec![0, 1, 2]);
+
+        let transcriptions: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT transcription, language FROM audio_transcriptions")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            transcriptions,
+            vec![("hello there".to_string(), Some("en".to_string()))]
+        );
+        let chunks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audio_chunks")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(chunks, 2);
+
+        assert_eq!(
+            processors.failures(),
+            vec![("panicking".to_string(), 3), ("redactor".to_string(), 0)]
+        );
+    }
+
+    #[cfg(feature = "recording")]
+    #[tokio::test]
+    async fn test_processed_ingest() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        let processors = ContentProcessors::new()
+            .with(Panicking)
+            .with(Redactor)
+            .with(EntityExtractor::new());
+        let ingest_frame = |offset_index: i64, text: &str| IngestFrame {
+            timestamp: Utc::now(),
+            file_path: "laptop.mp4".to_string(),
+            offset_index,
+            app_name: "code".to_string(),
+            text: text.to_string(),
+            text_json: String::new(),
+            ocr_engine: "Tesseract".to_string(),
+            raw_text: None,
+            entities: Vec::new(),
+        };
+        let ingest_transcription = |file_path: &str, transcription: &str| IngestTranscription {
+            timestamp: Utc::now(),
+            file_path: file_path.to_string(),
+            offset_index: 0,
+            transcription: transcription.to_string(),
+            transcription_engine: "Whisper".to_string(),
+            duration: None,
+            language: None,
+            segments: Vec::new(),
+            entities: Vec::new(),
+        };
+        let batch = IngestBatch {
+            device_id: "laptop".to_string(),
+            frames: vec![
+                ingest_frame(0, "darn, see https://screenpi.pe"),
+                ingest_frame(1, "my password is hunter2"),
+            ],
+            transcriptions: vec![
+                ingest_transcription("a.mp4", "hello there"),
+                ingest_transcription("b.mp4", "the password is"),
+            ],
+        };
+
+        let summary = db.ingest(&processors.process_ingest(batch)).await.unwrap();
+        assert_eq!(summary.frames_inserted, 2);
+        assert_eq!(summary.transcriptions_inserted, 2);
+
+        let texts: Vec<String> = sqlx::query_scalar("SELECT text FROM ocr_text ORDER BY frame_id")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(texts, vec!["****, see https://screenpi.pe", ""]);
+        let transcriptions: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT transcription, language FROM audio_transcriptions ORDER BY id")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            transcriptions,
+            vec![
+                ("hello there".to_string(), Some("en".to_string())),
+                (String::new(), None)
+            ]
+        );
+        let urls: Vec<String> = sqlx::query_scalar(
+            "SELECT value FROM entities WHERE kind = 'url' AND frame_id IS NOT NULL",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(urls, vec!["https://screenpi.pe"]);
+
+        assert_eq!(
+            processors.failures(),
+            vec![
+                ("panicking".to_string(), 2),
+                ("redactor".to_string(), 0),
+                ("entities".to_string(), 0)
+            ]
+        );
+    }
+
+    #[cfg(feature = "recording")]
+    #[tokio::test]
+    async fn test_retranscription() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.write_audio(audio("hello there")).await.unwrap();
+        db.write_audio(AudioRecord {
+            transcription: String::new(),
+            duration: None,
+            transcription_error: Some("model failed to load".to_string()),
+            ..audio("failed")
+        })
+        .await
+        .unwrap();
+        db.flush_writes().await;
+
+        let chunks = db.chunks_to_retranscribe(3, 10).await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        let (id, file_path, _, requested) = chunks[0].clone();
+        assert_eq!(file_path, "failed.mp4");
+        assert!(!requested);
+
+        // Given up on after 3 attempts, until queued again
+        for _ in 0..3 {
+            db.start_retranscription(id).await.unwrap();
+        }
+        assert!(db.chunks_to_retranscribe(3, 10).await.unwrap().is_empty());
+        assert_eq!(db.queue_retranscription(None, false).await.unwrap(), 1);
+        let chunks = db.chunks_to_retranscribe(3, 10).await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].3);
+
+        db.write_retranscription(AudioRecord {
+            duration: Some(12.5),
+            ..audio("failed")
+        })
+        .await
+        .unwrap();
+        db.flush_writes().await;
+
+        assert!(db.chunks_to_retranscribe(3, 10).await.unwrap().is_empty());
+        assert_eq!(db.queue_retranscription(None, false).await.unwrap(), 0);
+        let (error, duration): (Option<String>, Option<f64>) =
+            sqlx::query_as("SELECT transcription_error, duration FROM audio_chunks WHERE id = ?1")
+                .bind(id)
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(error, None);
+        assert_eq!(duration, Some(12.5));
+        let transcription: String = sqlx::query_scalar(
+            "SELECT transcription FROM audio_transcriptions WHERE audio_chunk_id = ?1",
+        )
+        .bind(id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(transcription, "failed");
+
+        // Transcribed again with another engine, the replaced transcription is kept
+        assert_eq!(db.queue_retranscription(None, true).await.unwrap(), 2);
+        db.write_retranscription(AudioRecord {
+            transcription: "hello world".to_string(),
+            transcription_engine: "Deepgram".to_string(),
+            ..audio("hello there")
+        })
+        .await
+        .unwrap();
+        db.flush_writes().await;
+        for (query, engine, count) in [
+            ("there", None, 0),
+            ("world", None, 1),
+            ("there", Some("Whisper"), 1),
+            ("world", Some("Whisper"), 0),
+            ("world", Some("Deepgram"), 1),
+        ] {
+            let found = db
+                .search(
+                    query,
+                    ContentType::Audio,
+                    10,
+                    0,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    engine,
+                )
+                .await
+                .unwrap();
+            assert_eq!(found.len(), count, "{} {:?}", query, engine);
+            let total = db
+                .count_search_results(
+                    query,
+                    ContentType::Audio,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    engine,
+                )
+                .await
+                .unwrap();
+            assert_eq!(total, count, "{} {:?}", query, engine);
+        }
+
+        // Deleted since, the transcription is dropped
+        db.write_retranscription(audio("deleted")).await.unwrap();
+    }
+
+    #[cfg(feature = "recording")]
+    #[tokio::test]
+    async fn test_reocr() {
+        let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+        db.insert_video_chunk_without_codec("first.mp4")
+            .await
+            .unwrap();
+        db.write_frame(frame("lnvoice 42")).await.unwrap();
+        db.flush_writes().await;
+        // Still recorded, not OCR'd again until the next chunk starts
+        assert_eq!(
+            db.queue_reocr(Utc::now() - chrono::Duration::hours(1), Utc::now())
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(db.next_chunk_to_reocr().await.unwrap(), None);
+        db.insert_video_chunk_without_codec("second.mp4")
+            .await
+            .unwrap();
+        let (video_chunk_id, file_path) = db.next_chunk_to_reocr().await.unwrap().unwrap();
+        assert_eq!(file_path, "first.mp4");
+        let frames = db.frames_to_reocr(video_chunk_id).await.unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].1, 0);
+        assert_eq!(frames[0].4.as_deref(), Some("Tesseract"));
+
+        db.write_reocr(
+            "first.mp4",
+            0,
+            FrameRecord {
+                ocr_engine: "AppleNative".to_string(),
+                ..frame("invoice 42")
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(db.next_chunk_to_reocr().await.unwrap(), None);
+        let (text, ocr_engine): (String, String) =
+            sqlx::query_as("SELECT text, ocr_engine FROM ocr_text")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(text, "invoice 42");
+        assert_eq!(ocr_engine, "AppleNative");
+        // The text read before is kept, searchable by its engine
+        for (query, engine, count) in [
+            ("invoice", None, 1),
+            ("lnvoice", None, 0),
+            ("lnvoice", Some("Tesseract"), 1),
+            ("invoice", Some("Tesseract"), 0),
+            ("invoice", Some("AppleNative"), 1),
+        ] {
+            let found = db
+                .count_search_results(
+                    query,
+                    ContentType::OCR,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    engine,
+                )
+                .await
+                .unwrap();
+            assert_eq!(found, count, "{} {:?}", query, engine);
+        }
+        let (results, _) = db
+            .search_page(
+                "lnvoice",
+                ContentType::All,
+                10,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("Tesseract"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        match &results[0] {
+            SearchResult::OCR(ocr) => assert_eq!(ocr.ocr_engine, "Tesseract"),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // Taken off the queue when the chunk can't be read
+        db.queue_reocr(Utc::now() - chrono::Duration::hours(1), Utc::now())
+            .await
+            .unwrap();
+        db.cancel_reocr(video_chunk_id).await.unwrap();
+        assert_eq!(db.next_chunk_to_reocr().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_open_storage() {
+        let storage = open_storage("sqlite::memory:", "laptop").await.unwrap();
+        storage
+            .insert_video_chunk("video.mp4", "h264")
+            .await
+            .unwrap();
+
+        #[cfg(not(feature = "postgres"))]
+        assert!(open_storage("postgres://localhost/screenpipe", "laptop")
+            .await
+            .is_err());
+    }
+}
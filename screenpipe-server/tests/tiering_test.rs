@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use screenpipe_server::{ensure_local, offload_chunks, BackupLocation, DatabaseManager};
+
+    #[tokio::test]
+    async fn test_offload_and_fetch_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let chunk_dir = dir.path().join("data");
+        std::fs::create_dir_all(&chunk_dir).unwrap();
+        let db = DatabaseManager::new(&dir.path().join("db.sqlite").to_string_lossy())
+            .await
+            .unwrap();
+        let video = chunk_dir.join("2024-08-25_10-00-00.mp4");
+        std::fs::write(&video, vec![7u8; 64 * 1024]).unwrap();
+        db.insert_video_chunk_without_codec(&video.to_string_lossy())
+            .await
+            .unwrap();
+        db.insert_frame("Safari").await.unwrap();
+        // Still being written, never offloaded
+        let current = chunk_dir.join("2024-08-25_10-05-00.mp4");
+        std::fs::write(&current, b"recording").unwrap();
+        db.insert_video_chunk_without_codec(&current.to_string_lossy())
+            .await
+            .unwrap();
+        let audio = chunk_dir.join("mic_2024-08-25_10-00-00.mp4");
+        std::fs::write(&audio, vec![3u8; 32 * 1024]).unwrap();
+        db.insert_audio_chunk(&audio.to_string_lossy())
+            .await
+            .unwrap();
+
+        let remote_dir = dir.path().join("remote");
+        let remote = BackupLocation::Directory(remote_dir.clone());
+        let before = Utc::now() + Duration::minutes(1);
+        let summary = offload_chunks(&db, &remote, before).await.unwrap();
+        assert_eq!(summary.offloaded, 2);
+        assert_eq!(summary.failed, 0);
+        assert!(summary.freed_bytes > 90 * 1024);
+        assert!(std::fs::metadata(&video).unwrap().len() < 4096);
+        assert_eq!(std::fs::read(&current).unwrap(), b"recording");
+        // Their stub frees nothing, the storage quota leaves them alone
+        assert!(db.get_oldest_video_chunk(0).await.unwrap().is_none());
+        assert!(db.get_oldest_audio_chunk(0).await.unwrap().is_none());
+
+        let video_path = video.to_string_lossy();
+        assert!(ensure_local(&remote, &video_path).await.unwrap());
+        assert_eq!(std::fs::read(&video).unwrap(), vec![7u8; 64 * 1024]);
+        assert!(!ensure_local(&remote, &video_path).await.unwrap());
+
+        // Stubbed again without uploading it twice
+        let summary = offload_chunks(&db, &remote, before).await.unwrap();
+        assert_eq!(summary.offloaded, 1);
+        assert_eq!(summary.uploaded_bytes, 0);
+
+        // A corrupt download never replaces the stub
+        let object = std::fs::read_dir(remote_dir.join("audio"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        std::fs::write(&object, b"bit rot").unwrap();
+        let audio_path = audio.to_string_lossy();
+        assert!(ensure_local(&remote, &audio_path).await.is_err());
+        assert!(std::fs::metadata(&audio).unwrap().len() < 4096);
+    }
+}
@@ -0,0 +1,117 @@
+#[cfg(all(test, unix))]
+mod tests {
+    #[cfg(feature = "audio")]
+    use crossbeam::queue::SegQueue;
+    #[cfg(feature = "audio")]
+    use screenpipe_server::WhisperModelConfig;
+    use screenpipe_server::{
+        AnomalyDetector, ApiPlugins, CaptureRequests, CaptureTrigger, DatabaseManager, Diagnostics,
+        RuleEngine, Server, VisionStatus,
+    };
+    #[cfg(feature = "audio")]
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixStream;
+    #[cfg(feature = "audio")]
+    use tokio::sync::watch;
+
+    async fn server(addr: SocketAddr) -> Server {
+        let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+        Server::new(
+            db,
+            addr,
+            Arc::new(AtomicBool::new(true)),
+            Arc::new(VisionStatus::new()),
+            #[cfg(feature = "audio")]
+            Arc::new(SegQueue::new()),
+            #[cfg(feature = "audio")]
+            watch::channel(WhisperModelConfig::default()).0,
+            #[cfg(feature = "audio")]
+            watch::channel(HashMap::new()).0,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(CaptureTrigger::new()),
+            CaptureRequests::new(),
+            RuleEngine::new(),
+            AnomalyDetector::default(),
+            None,
+            Diagnostics::default(),
+        )
+    }
+
+    #[cfg(feature = "audio")]
+    async fn start(server: Server) -> Result<(), std::io::Error> {
+        server.start(HashMap::new(), ApiPlugins::new()).await
+    }
+
+    #[cfg(not(feature = "audio"))]
+    async fn start(server: Server) -> Result<(), std::io::Error> {
+        server.start(ApiPlugins::new()).await
+    }
+
+    /// Raw response to a GET of `path` over the socket, once the server listens
+    async fn get(socket: &Path, path: &str) -> String {
+        let mut stream = None;
+        for _ in 0..50 {
+            match UnixStream::connect(socket).await {
+                Ok(connected) => {
+                    stream = Some(connected);
+                    break;
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
+        let mut stream = stream.expect("server didn't listen on the socket");
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_without_tcp() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("screenpipe.sock");
+        // Left behind by a previous run
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+
+        // Port 1 would need privileges, the server doesn't touch it
+        let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+        let server = server(addr).await.with_unix_socket(&socket).without_tcp();
+        tokio::spawn(start(server));
+
+        let response = get(&socket, "/v1/vision/status").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("\"is_running\":true"));
+        let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // Nothing left of the directory it was bound in
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_listen_errors() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let error = start(server(addr).await.without_tcp()).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        // Not replaced, it isn't a socket
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "keep me").unwrap();
+        let error = start(server(addr).await.with_unix_socket(&path).without_tcp())
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+    }
+}
@@ -0,0 +1,100 @@
+#[cfg(all(test, feature = "recording"))]
+mod tests {
+    use screenpipe_server::{VideoCodec, VideoEncoder, VideoEncoding};
+
+    fn args(encoding: VideoEncoding) -> String {
+        encoding.ffmpeg_args().join(" ")
+    }
+
+    #[test]
+    fn test_video_encoding_args() {
+        let software = VideoEncoding {
+            encoder: VideoEncoder::Software,
+            ..Default::default()
+        };
+        assert_eq!(
+            args(software.clone()),
+            "-vcodec libx264 -preset ultrafast -crf 23"
+        );
+        assert_eq!(
+            args(VideoEncoding {
+                preset: Some("veryfast".to_string()),
+                crf: Some(28),
+                ..software.clone()
+            }),
+            "-vcodec libx264 -preset veryfast -crf 28"
+        );
+        // A bitrate replaces the constant quality
+        assert_eq!(
+            args(VideoEncoding {
+                crf: Some(28),
+                bitrate: Some("2M".to_string()),
+                ..software
+            }),
+            "-vcodec libx264 -preset ultrafast -b:v 2M"
+        );
+
+        let nvenc = VideoEncoding {
+            encoder: VideoEncoder::Nvenc,
+            crf: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(args(nvenc), "-vcodec h264_nvenc -preset p1 -cq 30");
+        // No CRF scale, the default bitrate applies
+        let videotoolbox = VideoEncoding {
+            encoder: VideoEncoder::VideoToolbox,
+            crf: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(args(videotoolbox), "-vcodec h264_videotoolbox -b:v 2M");
+    }
+
+    #[test]
+    fn test_video_codec_args() {
+        let hevc = VideoEncoding {
+            encoder: VideoEncoder::Software,
+            codec: VideoCodec::Hevc,
+            ..Default::default()
+        };
+        assert_eq!(
+            args(hevc.clone()),
+            "-vcodec libx265 -preset ultrafast -tag:v hvc1 -crf 28"
+        );
+        assert_eq!(hevc.codec_name(), "hevc");
+        assert_eq!(
+            args(VideoEncoding {
+                codec: VideoCodec::Av1,
+                ..hevc
+            }),
+            "-vcodec libsvtav1 -preset 12 -crf 35"
+        );
+        assert_eq!(
+            args(VideoEncoding {
+                encoder: VideoEncoder::Nvenc,
+                codec: VideoCodec::Av1,
+                ..Default::default()
+            }),
+            "-vcodec av1_nvenc -preset p1 -cq 35"
+        );
+
+        // VideoToolbox can't write AV1, nor MPEG-4 anything but its own codec
+        let videotoolbox = VideoEncoding {
+            encoder: VideoEncoder::VideoToolbox,
+            codec: VideoCodec::Av1,
+            ..Default::default()
+        };
+        assert_eq!(videotoolbox.ffmpeg_encoder(), None);
+        assert_eq!(
+            args(videotoolbox.clone()),
+            "-vcodec h264_videotoolbox -b:v 2M"
+        );
+        assert_eq!(videotoolbox.codec_name(), "h264");
+        let mpeg4 = VideoEncoding {
+            encoder: VideoEncoder::Mpeg4,
+            codec: VideoCodec::Hevc,
+            ..Default::default()
+        };
+        assert_eq!(args(mpeg4.clone()), "-vcodec mpeg4 -q:v 5");
+        assert_eq!(mpeg4.codec_name(), "mpeg4");
+    }
+}
@@ -3,11 +3,14 @@
 // cargo bench --bench vision_benchmark
 // ! not very useful bench
 
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use screenpipe_vision::{continuous_capture, get_monitor, OcrEngine};
-use tokio::sync::mpsc;
+use screenpipe_vision::{
+    continuous_capture, get_monitor, CaptureSource, CaptureTrigger, OcrEngine, OcrLanguages,
+};
+use tokio::sync::{mpsc, watch};
 use tokio::time::Duration;
 
 async fn benchmark_continuous_capture(duration_secs: u64) -> f64 {
@@ -16,10 +19,21 @@ async fn benchmark_continuous_capture(duration_secs: u64) -> f64 {
     let capture_handle = tokio::spawn(async move {
         continuous_capture(
             result_tx,
-            Duration::from_millis(100),
+            watch::channel(Duration::from_millis(100)).1,
+            None,
             false,
             Arc::new(OcrEngine::Tesseract),
-            get_monitor().await,
+            CaptureSource::Monitor(get_monitor().await),
+            None,
+            OcrLanguages::default(),
+            None,
+            false,
+            None,
+            None,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(CaptureTrigger::new()),
+            1,
+            Arc::new(|_: &str| false),
         )
         .await;
     });
@@ -0,0 +1,79 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/ocr.swift");
+    println!("cargo:rerun-if-changed=src/accessibility.swift");
+    println!("cargo:rerun-if-changed=src/clipboard.swift");
+    // Set when the Swift bridges are linked, see below
+    println!("cargo:rustc-check-cfg=cfg(swift_bridge)");
+
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("macos") {
+        return;
+    }
+
+    // Compile the Apple Vision OCR, accessibility and clipboard bridges into a static lib
+    // linked into every dependent binary
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let lib_path = out_dir.join("libscreenpipe_ocr.a");
+
+    // Without swiftc the crate builds without the bridges: no apple-native engine, no
+    // accessibility tree and no concealed clipboard detection
+    let status = Command::new("swiftc")
+        .args([
+            "-emit-library",
+            "-static",
+            "-O",
+            "-module-name",
+            "screenpipe_ocr",
+            "-o",
+        ])
+        .arg(&lib_path)
+        .args([
+            "src/ocr.swift",
+            "src/accessibility.swift",
+            "src/clipboard.swift",
+        ])
+        .status();
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            println!(
+                "cargo:warning=failed to run swiftc ({}), building without the apple-native \
+                 OCR engine. Are the Xcode command line tools installed?",
+                e
+            );
+            return;
+        }
+    };
+
+    if !status.success() {
+        panic!("failed to compile src/ocr.swift, src/accessibility.swift and src/clipboard.swift");
+    }
+    println!("cargo:rustc-cfg=swift_bridge");
+
+    println!("cargo:rustc-link-search=native={}", out_dir.display());
+    println!("cargo:rustc-link-lib=static=screenpipe_ocr");
+
+    // Swift runtime: shipped with the OS, compatibility shims live in the toolchain
+    println!("cargo:rustc-link-search=native=/usr/lib/swift");
+    if let Ok(output) = Command::new("xcode-select").arg("-p").output() {
+        let developer_dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        for toolchain in [
+            "Toolchains/XcodeDefault.xctoolchain/usr/lib/swift/macosx",
+            "usr/lib/swift/macosx",
+        ] {
+            let path = PathBuf::from(&developer_dir).join(toolchain);
+            if path.exists() {
+                println!("cargo:rustc-link-search=native={}", path.display());
+            }
+        }
+    }
+
+    println!("cargo:rustc-link-lib=framework=Vision");
+    println!("cargo:rustc-link-lib=framework=AppKit");
+    println!("cargo:rustc-link-lib=framework=ApplicationServices");
+    println!("cargo:rustc-link-lib=framework=CoreGraphics");
+    println!("cargo:rustc-link-lib=framework=Foundation");
+}
@@ -0,0 +1,298 @@
+use crate::capture_region::CaptureRegion;
+use crate::utils::DirtyRect;
+use log::debug;
+use rusty_tesseract::{Data, DataOutput};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Elements read at most per frame, the tree of a long document can hold many thousands
+const MAX_ELEMENTS: usize = 2000;
+/// Deepest element read, below are layout containers of little text
+const MAX_DEPTH: u32 = 40;
+
+/// Element of the accessibility tree holding text, e.g. a button label or the value of a
+/// text field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UiElement {
+    /// Role as the platform names it, lowercased, e.g. "statictext" on macOS or "button"
+    /// on Windows
+    pub role: String,
+    pub text: String,
+    /// 0 for the window, 1 for its children and so on
+    pub depth: u32,
+    /// Where the element is on the desktop, `None` when the platform doesn't say
+    #[serde(default)]
+    pub frame: Option<ElementFrame>,
+}
+
+/// Rectangle of a `UiElement` in desktop coordinates: points from the top left of the
+/// main screen on macOS, pixels on Windows, as monitors and windows are positioned
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ElementFrame {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Where a frame was captured, to keep the elements of the accessibility tree it shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenPlacement {
+    /// Position and width of the monitor, in the coordinates of `ElementFrame`
+    pub monitor: (i32, i32, u32),
+    /// Part of the screen the frame shows, see `CaptureCrop`
+    pub region: CaptureRegion,
+    /// Rectangles hidden by the `ScreenMask`, in pixels of the screen
+    pub masks: Vec<DirtyRect>,
+}
+
+impl ScreenPlacement {
+    /// `frame` in pixels of the screen, `None` when its center is off the captured region
+    /// or it overlaps a masked rectangle
+    fn place(&self, frame: &ElementFrame) -> Option<DirtyRect> {
+        let (monitor_x, monitor_y, monitor_width) = self.monitor;
+        // Retina screens are captured at twice their size in points
+        let scale = if monitor_width == 0 {
+            1.0
+        } else {
+            self.region.screen_width as f64 / monitor_width as f64
+        };
+        let left = (frame.x - monitor_x as f64) * scale;
+        let top = (frame.y - monitor_y as f64) * scale;
+        let (width, height) = (frame.width * scale, frame.height * scale);
+        let (center_x, center_y) = (left + width / 2.0, top + height / 2.0);
+        let region = &self.region;
+        if center_x < region.x as f64
+            || center_y < region.y as f64
+            || center_x >= (region.x + region.width) as f64
+            || center_y >= (region.y + region.height) as f64
+        {
+            return None;
+        }
+        let rect = DirtyRect {
+            x: left.max(0.0).round() as u32,
+            y: top.max(0.0).round() as u32,
+            width: width.max(1.0).round() as u32,
+            height: height.max(1.0).round() as u32,
+        };
+        if self.masks.iter().any(|mask| {
+            mask.intersects_box(
+                rect.x as i32,
+                rect.y as i32,
+                rect.width as i32,
+                rect.height as i32,
+            )
+        }) {
+            return None;
+        }
+        Some(rect)
+    }
+}
+
+/// Elements holding text of the focused window, in reading order. Read from AXUIElement on
+/// macOS, which needs the accessibility permission, and UI Automation on Windows. `None`
+/// when the tree can't be read, e.g. without the permission or on Linux.
+pub fn focused_window_elements() -> Option<Vec<UiElement>> {
+    let elements = platform::focused_window_elements()?;
+    debug!("Read {} elements of the focused window", elements.len());
+    Some(
+        elements
+            .into_iter()
+            .map(|element| UiElement {
+                role: element.role.to_lowercase(),
+                text: element.text.trim().to_string(),
+                depth: element.depth,
+                frame: element.frame,
+            })
+            .filter(|element| !element.text.is_empty())
+            .collect(),
+    )
+}
+
+/// Text of the `elements` shown in a `frame_width` pixels wide frame captured at
+/// `placement`, as an OCR engine returns it: one line and one word box per element, and a
+/// record with its `role` per element in the JSON. Like the pixels, the elements off the
+/// captured region or under a masked one are left out, so are the ones without a frame.
+pub fn elements_to_ocr_output(
+    elements: &[UiElement],
+    placement: &ScreenPlacement,
+    frame_width: u32,
+) -> (String, DataOutput, String) {
+    let region = &placement.region;
+    // The frame can be downscaled for OCR
+    let scale = if region.width == 0 {
+        1.0
+    } else {
+        frame_width as f64 / region.width as f64
+    };
+    let placed: Vec<(&UiElement, DirtyRect)> = elements
+        .iter()
+        .filter_map(|element| Some((element, placement.place(element.frame.as_ref()?)?)))
+        .collect();
+    let text = placed
+        .iter()
+        .map(|(element, _)| element.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let records: Vec<HashMap<String, String>> = placed
+        .iter()
+        .map(|(element, _)| {
+            HashMap::from([
+                ("text".to_string(), element.text.clone()),
+                ("role".to_string(), element.role.clone()),
+                // Read as is, not recognized
+                ("confidence".to_string(), "100.00".to_string()),
+            ])
+        })
+        .collect();
+    let json_output = serde_json::to_string_pretty(&records).unwrap();
+    let data = placed
+        .iter()
+        .enumerate()
+        .map(|(i, (element, rect))| Data {
+            level: 5,
+            page_num: 1,
+            block_num: i as i32 + 1,
+            par_num: 1,
+            line_num: 1,
+            word_num: 1,
+            left: ((rect.x as f64 - region.x as f64) * scale).round() as i32,
+            top: ((rect.y as f64 - region.y as f64) * scale).round() as i32,
+            width: (rect.width as f64 * scale).round() as i32,
+            height: (rect.height as f64 * scale).round() as i32,
+            conf: 100.0,
+            text: element.text.clone(),
+        })
+        .collect();
+    let data_output = DataOutput {
+        output: text.clone(),
+        data,
+    };
+    (text, data_output, json_output)
+}
+
+#[cfg(swift_bridge)]
+mod platform {
+    use super::{UiElement, MAX_DEPTH, MAX_ELEMENTS};
+    use log::error;
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+
+    // Implemented in src/accessibility.swift, compiled and linked by build.rs
+    extern "C" {
+        #[link_name = "focused_window_elements"]
+        fn swift_focused_window_elements(max_elements: i64, max_depth: i64) -> *mut c_char;
+        fn free_string(s: *mut c_char);
+    }
+
+    pub fn focused_window_elements() -> Option<Vec<UiElement>> {
+        let json = unsafe {
+            let result = swift_focused_window_elements(MAX_ELEMENTS as i64, MAX_DEPTH as i64);
+            if result.is_null() {
+                return None;
+            }
+            let json = CStr::from_ptr(result).to_string_lossy().into_owned();
+            free_string(result);
+            json
+        };
+        serde_json::from_str(&json)
+            .map_err(|e| error!("Failed to parse the accessibility tree: {}", e))
+            .ok()
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{ElementFrame, UiElement, MAX_DEPTH, MAX_ELEMENTS};
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED,
+    };
+    use windows::Win32::UI::Accessibility::{
+        CUIAutomation, IUIAutomation, IUIAutomationElement, IUIAutomationTreeWalker,
+        IUIAutomationValuePattern, UIA_ValuePatternId,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    pub fn focused_window_elements() -> Option<Vec<UiElement>> {
+        unsafe {
+            // Threads already initialized keep their apartment
+            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+            let automation: IUIAutomation =
+                CoCreateInstance(&CUIAutomation, None, CLSCTX_INPROC_SERVER).ok()?;
+            let window = GetForegroundWindow();
+            if window.0.is_null() {
+                return None;
+            }
+            let root = automation.ElementFromHandle(window).ok()?;
+            let walker = automation.ControlViewWalker().ok()?;
+            let mut elements = Vec::new();
+            collect_elements(&walker, &root, 0, &mut elements);
+            Some(elements)
+        }
+    }
+
+    unsafe fn collect_elements(
+        walker: &IUIAutomationTreeWalker,
+        element: &IUIAutomationElement,
+        depth: u32,
+        elements: &mut Vec<UiElement>,
+    ) {
+        if elements.len() >= MAX_ELEMENTS || depth > MAX_DEPTH {
+            return;
+        }
+        let text = element_text(element);
+        if !text.is_empty() {
+            elements.push(UiElement {
+                role: element
+                    .CurrentLocalizedControlType()
+                    .map(|role| role.to_string())
+                    .unwrap_or_default(),
+                text,
+                depth,
+                frame: element
+                    .CurrentBoundingRectangle()
+                    .ok()
+                    .map(|rect| ElementFrame {
+                        x: rect.left as f64,
+                        y: rect.top as f64,
+                        width: (rect.right - rect.left) as f64,
+                        height: (rect.bottom - rect.top) as f64,
+                    }),
+            });
+        }
+        let mut child = walker.GetFirstChildElement(element).ok();
+        while let Some(current) = child {
+            collect_elements(walker, &current, depth + 1, elements);
+            if elements.len() >= MAX_ELEMENTS {
+                return;
+            }
+            child = walker.GetNextSiblingElement(&current).ok();
+        }
+    }
+
+    /// Value of editable elements, name of the others
+    unsafe fn element_text(element: &IUIAutomationElement) -> String {
+        let value = element
+            .GetCurrentPatternAs::<IUIAutomationValuePattern>(UIA_ValuePatternId)
+            .and_then(|pattern| pattern.CurrentValue())
+            .map(|value| value.to_string())
+            .unwrap_or_default();
+        if !value.trim().is_empty() {
+            return value;
+        }
+        element
+            .CurrentName()
+            .map(|name| name.to_string())
+            .unwrap_or_default()
+    }
+}
+
+// AT-SPI isn't read yet, Linux frames are OCR'd, and so are macOS frames without swiftc
+#[cfg(not(any(swift_bridge, target_os = "windows")))]
+mod platform {
+    use super::UiElement;
+
+    pub fn focused_window_elements() -> Option<Vec<UiElement>> {
+        None
+    }
+}
@@ -0,0 +1,125 @@
+import ApplicationServices
+import Foundation
+
+// Reads the accessibility tree of the focused window of the frontmost app and returns a
+// JSON array of the elements holding text, in reading order:
+// [{"role", "text", "depth", "frame": {"x", "y", "width", "height"}}], the frame in points
+// from the top left of the main screen, absent when the element has no position.
+// Returns nil when the process isn't trusted for accessibility (System Settings > Privacy &
+// Security > Accessibility) or no window is focused.
+// The returned string must be released with `free_string`.
+@_cdecl("focused_window_elements")
+public func focusedWindowElements(maxElements: Int, maxDepth: Int) -> UnsafeMutablePointer<
+  CChar
+>? {
+  guard AXIsProcessTrusted() else {
+    return nil
+  }
+  let systemWide = AXUIElementCreateSystemWide()
+  guard let app = elementAttribute(systemWide, kAXFocusedApplicationAttribute),
+    let window = elementAttribute(app, kAXFocusedWindowAttribute)
+  else {
+    return nil
+  }
+
+  var elements: [[String: Any]] = []
+  collectElements(window, depth: 0, maxElements: maxElements, maxDepth: maxDepth, &elements)
+
+  guard let json = try? JSONSerialization.data(withJSONObject: elements),
+    let string = String(data: json, encoding: .utf8)
+  else {
+    return nil
+  }
+  return strdup(string)
+}
+
+private func collectElements(
+  _ element: AXUIElement, depth: Int, maxElements: Int, maxDepth: Int,
+  _ elements: inout [[String: Any]]
+) {
+  if elements.count >= maxElements || depth > maxDepth {
+    return
+  }
+  if let text = elementText(element) {
+    // Roles are prefixed, e.g. AXStaticText
+    var role = stringAttribute(element, kAXRoleAttribute) ?? ""
+    if role.hasPrefix("AX") {
+      role.removeFirst(2)
+    }
+    var record: [String: Any] = ["role": role, "text": text, "depth": depth]
+    if let frame = elementFrame(element) {
+      record["frame"] = [
+        "x": frame.origin.x, "y": frame.origin.y, "width": frame.size.width,
+        "height": frame.size.height,
+      ]
+    }
+    elements.append(record)
+  }
+  var children: CFTypeRef?
+  guard
+    AXUIElementCopyAttributeValue(element, kAXChildrenAttribute as CFString, &children)
+      == .success,
+    let children = children as? [AXUIElement]
+  else {
+    return
+  }
+  for child in children {
+    collectElements(
+      child, depth: depth + 1, maxElements: maxElements, maxDepth: maxDepth, &elements)
+    if elements.count >= maxElements {
+      return
+    }
+  }
+}
+
+// Value of text fields and static text, title of buttons and windows, else the description
+private func elementText(_ element: AXUIElement) -> String? {
+  for attribute in [kAXValueAttribute, kAXTitleAttribute, kAXDescriptionAttribute] {
+    if let text = stringAttribute(element, attribute),
+      !text.trimmingCharacters(in: .whitespacesAndNewlines).isEmpty
+    {
+      return text
+    }
+  }
+  return nil
+}
+
+private func elementFrame(_ element: AXUIElement) -> CGRect? {
+  var position: CFTypeRef?
+  var size: CFTypeRef?
+  guard
+    AXUIElementCopyAttributeValue(element, kAXPositionAttribute as CFString, &position)
+      == .success,
+    AXUIElementCopyAttributeValue(element, kAXSizeAttribute as CFString, &size) == .success,
+    let position = position, CFGetTypeID(position) == AXValueGetTypeID(),
+    let size = size, CFGetTypeID(size) == AXValueGetTypeID()
+  else {
+    return nil
+  }
+  var origin = CGPoint.zero
+  var extent = CGSize.zero
+  guard AXValueGetValue(position as! AXValue, .cgPoint, &origin),
+    AXValueGetValue(size as! AXValue, .cgSize, &extent)
+  else {
+    return nil
+  }
+  return CGRect(origin: origin, size: extent)
+}
+
+private func stringAttribute(_ element: AXUIElement, _ attribute: String) -> String? {
+  var value: CFTypeRef?
+  guard AXUIElementCopyAttributeValue(element, attribute as CFString, &value) == .success else {
+    return nil
+  }
+  return value as? String
+}
+
+private func elementAttribute(_ element: AXUIElement, _ attribute: String) -> AXUIElement? {
+  var value: CFTypeRef?
+  guard AXUIElementCopyAttributeValue(element, attribute as CFString, &value) == .success,
+    let value = value, CFGetTypeID(value) == AXUIElementGetTypeID()
+  else {
+    return nil
+  }
+  return (value as! AXUIElement)
+}
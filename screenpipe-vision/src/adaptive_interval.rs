@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+/// Average difference with the previous frame under which the screen is considered still,
+/// the same under which `continuous_capture` skips a frame
+const STILL_DIFFERENCE: f64 = 0.006;
+/// Average difference from which the screen is considered changing fast, e.g. scrolling or
+/// a video playing
+const RAPID_DIFFERENCE: f64 = 0.05;
+
+/// Interval between captures following how fast the screen changes. It doubles on every
+/// still frame, up to `slowest`, halves on frames that change a little and drops back to
+/// the configured interval as soon as the screen changes fast.
+#[derive(Debug, Clone)]
+pub struct AdaptiveInterval {
+    slowest: Duration,
+    /// Times the configured interval is stretched
+    factor: f64,
+}
+
+impl AdaptiveInterval {
+    pub fn new(slowest: Duration) -> Self {
+        AdaptiveInterval {
+            slowest,
+            factor: 1.0,
+        }
+    }
+
+    /// Interval until the next capture after a frame differing by `difference` from the
+    /// previous one, `configured` being the fastest
+    pub fn next(&mut self, configured: Duration, difference: f64) -> Duration {
+        let max_factor = (self.slowest.as_secs_f64() / configured.as_secs_f64()).max(1.0);
+        self.factor = if difference >= RAPID_DIFFERENCE {
+            1.0
+        } else if difference < STILL_DIFFERENCE {
+            (self.factor * 2.0).min(max_factor)
+        } else {
+            (self.factor / 2.0).max(1.0)
+        };
+        configured.mul_f64(self.factor.min(max_factor))
+    }
+
+    /// Back to the configured interval, e.g. after a requested capture
+    pub fn reset(&mut self) {
+        self.factor = 1.0;
+    }
+}
@@ -0,0 +1,125 @@
+use crate::ocr_languages::native_language_tag;
+use image::DynamicImage;
+use log::{debug, error};
+use rusty_tesseract::{Data, DataOutput};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+// Implemented in src/ocr.swift, compiled and linked by build.rs
+extern "C" {
+    fn perform_ocr(
+        image_data: *const u8,
+        length: usize,
+        width: i64,
+        height: i64,
+        languages: *const c_char,
+    ) -> *mut c_char;
+    fn free_string(s: *mut c_char);
+}
+
+#[derive(Deserialize)]
+struct VisionObservation {
+    text: String,
+    confidence: f32,
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+}
+
+/// OCRs `image` with Apple Vision, recognizing the Tesseract `languages`, e.g. `["eng", "jpn"]`,
+/// in order of preference. Vision's default, English, when empty.
+pub fn perform_ocr_apple(
+    image: &DynamicImage,
+    languages: &[String],
+) -> (String, DataOutput, String) {
+    // Screen captures are already RGBA8, only convert other formats
+    let rgba = match image.as_rgba8() {
+        Some(buffer) => Cow::Borrowed(buffer),
+        None => Cow::Owned(image.to_rgba8()),
+    };
+    let (width, height) = rgba.dimensions();
+    let raw = rgba.as_raw();
+    let tags: Vec<&str> = languages
+        .iter()
+        .filter_map(|language| {
+            let tag = native_language_tag(language);
+            if tag.is_none() {
+                debug!("Apple Vision doesn't recognize OCR language {}", language);
+            }
+            tag
+        })
+        .collect();
+    let tags = CString::new(tags.join(",")).unwrap_or_default();
+
+    let observations: Vec<VisionObservation> = unsafe {
+        let result = perform_ocr(
+            raw.as_ptr(),
+            raw.len(),
+            width as i64,
+            height as i64,
+            tags.as_ptr(),
+        );
+        if result.is_null() {
+            error!("Apple Vision OCR failed to recognize text");
+            Vec::new()
+        } else {
+            let json = CStr::from_ptr(result).to_string_lossy().into_owned();
+            free_string(result);
+            serde_json::from_str(&json).unwrap_or_else(|e| {
+                error!("Failed to parse Apple Vision OCR output: {}", e);
+                Vec::new()
+            })
+        }
+    };
+
+    // Each Vision observation is a line, map it to a tesseract-like record
+    let data: Vec<Data> = observations
+        .iter()
+        .enumerate()
+        .map(|(i, o)| Data {
+            level: 5,
+            page_num: 1,
+            block_num: 1,
+            par_num: 1,
+            line_num: i as i32 + 1,
+            word_num: 1,
+            left: o.left,
+            top: o.top,
+            width: o.width,
+            height: o.height,
+            conf: o.confidence * 100.0,
+            text: o.text.clone(),
+        })
+        .collect();
+
+    let text = observations
+        .iter()
+        .map(|o| o.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let lines: Vec<HashMap<String, String>> = observations
+        .iter()
+        .map(|o| {
+            HashMap::from([
+                ("text".to_string(), o.text.clone()),
+                (
+                    "confidence".to_string(),
+                    format!("{:.2}", o.confidence * 100.0),
+                ),
+            ])
+        })
+        .collect();
+    let json_output = serde_json::to_string_pretty(&lines).unwrap();
+
+    let data_output = DataOutput {
+        output: text.clone(),
+        data,
+    };
+
+    (text, data_output, json_output)
+}
@@ -1,7 +1,12 @@
 use clap::Parser;
-use screenpipe_vision::{continuous_capture, get_monitor, OcrEngine};
-use std::{sync::Arc, time::Duration};
-use tokio::sync::mpsc::channel;
+use screenpipe_vision::{
+    continuous_capture, get_monitor, CaptureSource, CaptureTrigger, OcrEngine, OcrLanguages,
+};
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+use tokio::sync::{mpsc::channel, watch};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -26,10 +31,21 @@ async fn main() {
     let capture_thread = tokio::spawn(async move {
         continuous_capture(
             result_tx,
-            Duration::from_secs(1),
+            watch::channel(Duration::from_secs(1)).1,
+            None,
             save_text_files,
             Arc::new(OcrEngine::Tesseract),
-            get_monitor().await,
+            CaptureSource::Monitor(get_monitor().await),
+            None,
+            OcrLanguages::default(),
+            None,
+            false,
+            None,
+            None,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(CaptureTrigger::new()),
+            1,
+            Arc::new(|_: &str| false),
         )
         .await
     });
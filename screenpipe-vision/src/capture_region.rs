@@ -0,0 +1,143 @@
+use crate::masked_regions::RegionLength;
+use crate::utils::DirtyRect;
+use image::DynamicImage;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Margins cut off every frame of a monitor before it's deduplicated, OCRed or encoded,
+/// e.g. a ticker at the bottom of the screen. Parsed from `<top>,<right>,<bottom>,<left>`
+/// in pixels or percents of the screen, e.g. `0,0,40,0`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CaptureCrop {
+    pub top: RegionLength,
+    pub right: RegionLength,
+    pub bottom: RegionLength,
+    pub left: RegionLength,
+}
+
+impl CaptureCrop {
+    /// Part of a `screen_width` by `screen_height` frame kept, `None` if the margins
+    /// leave nothing
+    pub fn rect(&self, screen_width: u32, screen_height: u32) -> Option<DirtyRect> {
+        let left = self.left.resolve(screen_width);
+        let right = self.right.resolve(screen_width);
+        let top = self.top.resolve(screen_height);
+        let bottom = self.bottom.resolve(screen_height);
+        let width = screen_width.saturating_sub(left + right);
+        let height = screen_height.saturating_sub(top + bottom);
+        if width == 0 || height == 0 {
+            return None;
+        }
+        Some(DirtyRect {
+            x: left,
+            y: top,
+            width,
+            height,
+        })
+    }
+
+    /// `image` of `monitor` without the margins and the region it shows, the whole image
+    /// if the margins leave nothing
+    pub fn apply(
+        &self,
+        image: DynamicImage,
+        monitor: Option<String>,
+    ) -> (DynamicImage, CaptureRegion) {
+        let (screen_width, screen_height) = (image.width(), image.height());
+        let Some(rect) = self.rect(screen_width, screen_height) else {
+            warn!(
+                "Crop {} leaves nothing of a {}x{} frame, keeping the whole frame",
+                self, screen_width, screen_height
+            );
+            return (
+                image,
+                CaptureRegion::full(monitor, screen_width, screen_height),
+            );
+        };
+        let region = CaptureRegion {
+            monitor,
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+            screen_width,
+            screen_height,
+        };
+        if !region.is_cropped() {
+            return (image, region);
+        }
+        (
+            image.crop_imm(rect.x, rect.y, rect.width, rect.height),
+            region,
+        )
+    }
+}
+
+impl FromStr for CaptureCrop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lengths = s
+            .split(',')
+            .map(RegionLength::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        match lengths[..] {
+            [top, right, bottom, left] => Ok(CaptureCrop {
+                top,
+                right,
+                bottom,
+                left,
+            }),
+            _ => Err(format!(
+                "crop must be <top>,<right>,<bottom>,<left>, got {}",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CaptureCrop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.top, self.right, self.bottom, self.left
+        )
+    }
+}
+
+/// Part of the screen a frame shows, in pixels of the uncropped capture. The OCR
+/// coordinates of a frame are relative to its region, add `x` and `y` to place them on
+/// the screen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRegion {
+    /// Monitor captured, `None` for synthetic frames
+    pub monitor: Option<String>,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub screen_width: u32,
+    pub screen_height: u32,
+}
+
+impl CaptureRegion {
+    /// The whole `width` by `height` screen of `monitor`
+    pub fn full(monitor: Option<String>, width: u32, height: u32) -> Self {
+        CaptureRegion {
+            monitor,
+            x: 0,
+            y: 0,
+            width,
+            height,
+            screen_width: width,
+            screen_height: height,
+        }
+    }
+
+    pub fn is_cropped(&self) -> bool {
+        self.width != self.screen_width || self.height != self.screen_height
+    }
+}
@@ -0,0 +1,46 @@
+/// Whether the app that wrote the clipboard asked clipboard managers not to record it,
+/// like password managers do for the passwords they copy: with the
+/// `org.nspasteboard.ConcealedType` or `TransientType` markers on macOS, the
+/// `ExcludeClipboardContentFromMonitorProcessing` format on Windows. Always false on Linux
+/// and on macOS builds without swiftc.
+pub fn clipboard_is_concealed() -> bool {
+    platform::clipboard_is_concealed()
+}
+
+#[cfg(swift_bridge)]
+mod platform {
+    // Implemented in src/clipboard.swift, compiled and linked by build.rs
+    extern "C" {
+        #[link_name = "clipboard_is_concealed"]
+        fn swift_clipboard_is_concealed() -> bool;
+    }
+
+    pub fn clipboard_is_concealed() -> bool {
+        unsafe { swift_clipboard_is_concealed() }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use windows::core::w;
+    use windows::Win32::System::DataExchange::{
+        IsClipboardFormatAvailable, RegisterClipboardFormatW,
+    };
+
+    pub fn clipboard_is_concealed() -> bool {
+        unsafe {
+            // Registered on first use, the id is the same in every process
+            let format =
+                RegisterClipboardFormatW(w!("ExcludeClipboardContentFromMonitorProcessing"));
+            format != 0 && IsClipboardFormatAvailable(format).is_ok()
+        }
+    }
+}
+
+// Linux, and macOS without swiftc
+#[cfg(not(any(swift_bridge, target_os = "windows")))]
+mod platform {
+    pub fn clipboard_is_concealed() -> bool {
+        false
+    }
+}
@@ -0,0 +1,12 @@
+import AppKit
+
+// Whether the app that wrote the clipboard marked it as not to be recorded by clipboard
+// managers, e.g. a password copied from a password manager, see http://nspasteboard.org
+@_cdecl("clipboard_is_concealed")
+public func clipboardIsConcealed() -> Bool {
+  let types = NSPasteboard.general.types ?? []
+  return types.contains { type in
+    type.rawValue == "org.nspasteboard.ConcealedType"
+      || type.rawValue == "org.nspasteboard.TransientType"
+  }
+}
@@ -1,43 +1,69 @@
 use image::DynamicImage;
 use log::{debug, error};
 use serde_json;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
     time::{Duration, Instant},
 };
 use strsim::levenshtein;
-use tokio::sync::{mpsc::Sender, Mutex}; // Corrected import for Mutex
-use xcap::{Monitor, Window};
+use tokio::sync::{
+    mpsc::{channel, Sender},
+    oneshot, watch, Mutex, Notify,
+}; // Corrected import for Mutex
+use xcap::Monitor;
 
+#[cfg(swift_bridge)]
+use crate::apple::perform_ocr_apple;
+use crate::accessibility::{elements_to_ocr_output, focused_window_elements, ScreenPlacement};
+use crate::adaptive_interval::AdaptiveInterval;
+use crate::capture_region::{CaptureCrop, CaptureRegion};
+use crate::masked_regions::ScreenMask;
+use crate::ocr_languages::OcrLanguages;
+use crate::synthetic::{SyntheticCaptureSource, SYNTHETIC_APP_NAME};
 #[cfg(target_os = "windows")]
 use crate::utils::perform_ocr_windows;
 use crate::utils::OcrEngine;
 use crate::utils::{
-    capture_screenshot, compare_with_previous_image, perform_ocr_tesseract,
-    save_text_files,
+    capture_screenshot, clone_data_output, compare_with_previous_image, detect_dirty_regions,
+    downscale_for_ocr, merge_region_ocr, perceptual_hash, perceptual_hash_similarity,
+    perform_ocr_tesseract_languages, save_text_files, scale_data_output, DirtyRect, PerceptualHash,
 };
-use rusty_tesseract::{Data, DataOutput}; // Add this import
-use screenpipe_integrations::unstructured_ocr::perform_ocr_cloud;
+use crate::windows::{attribute_text, current_windows, frontmost_app, stack_windows, WindowRegion};
+use rusty_tesseract::DataOutput;
+#[cfg(feature = "cloud")]
+use screenpipe_integrations::cloud_ocr::perform_ocr_cloud;
 
 pub struct DataOutputWrapper {
     pub data_output: rusty_tesseract::tesseract::output_data::DataOutput,
 }
 
 impl DataOutputWrapper {
+    /// Tesseract records of the OCR output as JSON
     pub fn to_json(&self) -> String {
-        let data_json: Vec<String> = self.data_output.data.iter().map(|d| {
-            format!(
-                r#"{{"level": {}, "page_num": {}, "block_num": {}, "par_num": {}, "line_num": {}, "word_num": {}, "left": {}, "top": {}, "width": {}, "height": {}, "conf": {}, "text": "{}"}}"#,
-                d.level, d.page_num, d.block_num, d.par_num, d.line_num, d.word_num, d.left, d.top, d.width, d.height, d.conf, d.text
-            )
-        }).collect();
-        format!(
-            r#"{{"output": "{}", "data": [{}]}}"#,
-            self.data_output.output,
-            data_json.join(", ")
-        )
+        let data: Vec<serde_json::Value> = self
+            .data_output
+            .data
+            .iter()
+            .map(|d| {
+                serde_json::json!({
+                    "level": d.level,
+                    "page_num": d.page_num,
+                    "block_num": d.block_num,
+                    "par_num": d.par_num,
+                    "line_num": d.line_num,
+                    "word_num": d.word_num,
+                    "left": d.left,
+                    "top": d.top,
+                    "width": d.width,
+                    "height": d.height,
+                    "conf": d.conf,
+                    "text": d.text,
+                })
+            })
+            .collect();
+        serde_json::json!({"output": self.data_output.output, "data": data}).to_string()
     }
 }
 
@@ -50,6 +76,12 @@ pub struct CaptureResult {
     pub timestamp: Instant,
     pub data_output: DataOutput,
     pub app_name: String,
+    /// Windows on screen, front to back, empty when they aren't known, e.g. synthetic frames
+    pub windows: Vec<WindowRegion>,
+    /// Captured on request of a `CaptureTrigger`
+    pub forced: bool,
+    /// Part of the screen `image` shows
+    pub region: CaptureRegion,
 }
 
 impl Clone for CaptureResult {
@@ -61,29 +93,11 @@ impl Clone for CaptureResult {
             new_text_json: self.new_text_json.clone(),
             frame_number: self.frame_number,
             timestamp: self.timestamp,
-            data_output: DataOutput {
-                output: self.data_output.output.clone(),
-                data: self
-                    .data_output
-                    .data
-                    .iter()
-                    .map(|d| Data {
-                        level: d.level,
-                        page_num: d.page_num,
-                        block_num: d.block_num,
-                        par_num: d.par_num,
-                        line_num: d.line_num,
-                        word_num: d.word_num,
-                        left: d.left,
-                        top: d.top,
-                        width: d.width,
-                        height: d.height,
-                        conf: d.conf,
-                        text: d.text.clone(),
-                    })
-                    .collect(),
-            },
+            data_output: clone_data_output(&self.data_output),
             app_name: self.app_name.clone(),
+            windows: self.windows.clone(),
+            forced: self.forced,
+            region: self.region.clone(),
         }
     }
 }
@@ -99,24 +113,159 @@ pub async fn get_monitor() -> Monitor {
     Monitor::all().unwrap().first().unwrap().clone()
 }
 
+/// The first monitor whose name `enabled` accepts, `None` if it accepts none
+pub fn find_monitor(enabled: impl Fn(&str) -> bool) -> Option<Monitor> {
+    Monitor::all()
+        .unwrap()
+        .into_iter()
+        .find(|monitor| enabled(monitor.name()))
+}
+
+/// Where continuous_capture gets its frames from
+pub enum CaptureSource {
+    Monitor(Monitor),
+    Synthetic(SyntheticCaptureSource),
+}
+
+/// Requests of an immediate capture, e.g. to mark a moment. The next frame is captured
+/// without waiting for the interval, and OCR'd at full resolution even when it looks like
+/// the previous one. Requests made while capture is paused are dropped.
+#[derive(Debug, Default)]
+pub struct CaptureTrigger {
+    requested: AtomicBool,
+    wake: Notify,
+}
+
+impl CaptureTrigger {
+    pub fn new() -> Self {
+        CaptureTrigger::default()
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+    }
+
+    /// Whether a capture was requested since the last call
+    fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Waits for `interval` or a capture request
+    async fn wait(&self, interval: Duration) {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = self.wake.notified() => {}
+        }
+    }
+}
+
+/// `interval` between captures can change while capturing, e.g. when the FPS is reloaded.
+/// With an `idle_interval` it stretches up to it while the screen barely changes, see
+/// `AdaptiveInterval`. `crop` is cut off every frame after `screen_mask` hid its regions.
+/// Frames are OCR'd in the `ocr_languages` of their frontmost app.
 pub async fn continuous_capture(
     result_tx: Sender<CaptureResult>,
-    interval: Duration,
+    interval: watch::Receiver<Duration>,
+    idle_interval: Option<Duration>,
     save_text_files_flag: bool,
     ocr_engine: Arc<OcrEngine>,
-    monitor: Monitor,
+    mut capture_source: CaptureSource,
+    ocr_max_dimension: Option<u32>,
+    ocr_languages: OcrLanguages,
+    dedup_threshold: Option<f64>,
+    differential_ocr: bool,
+    screen_mask: Option<ScreenMask>,
+    crop: Option<CaptureCrop>,
+    capture_paused: Arc<AtomicBool>,
+    capture_trigger: Arc<CaptureTrigger>,
+    ocr_pool_size: usize,
+    is_ignored_app: Arc<dyn Fn(&str) -> bool + Send + Sync>,
 ) {
-    debug!("continuous_capture: Starting using monitor: {:?}", monitor);
+    // Position and width in points of the monitor, to place its windows in the frame
+    let (monitor_geometry, monitor_name) = match &capture_source {
+        CaptureSource::Monitor(monitor) => {
+            debug!("continuous_capture: Starting using monitor: {:?}", monitor);
+            (
+                Some((monitor.x(), monitor.y(), monitor.width())),
+                Some(monitor.name().to_string()),
+            )
+        }
+        CaptureSource::Synthetic(_) => {
+            debug!("continuous_capture: Starting using synthetic frames");
+            (None, None)
+        }
+    };
+    // Differential OCR merges with the previous frame's result, which must be complete first
+    let ocr_pool_size = if differential_ocr {
+        1
+    } else {
+        ocr_pool_size.max(1)
+    };
     let previous_text_json = Arc::new(Mutex::new(None));
-    let ocr_task_running = Arc::new(AtomicBool::new(false));
+    let previous_data_output = Arc::new(Mutex::new(None));
+    // Last frame sent to OCR, dirty regions are computed against it
+    let mut last_ocr_image: Option<Arc<DynamicImage>> = None;
+    let ocr_tasks_running = Arc::new(AtomicUsize::new(0));
+    // Completion signal of the last spawned OCR task, results are forwarded in capture order
+    let mut previous_task_done: Option<oneshot::Receiver<()>> = None;
     let mut frame_counter: u64 = 0;
     // let start_time = Instant::now();
     let mut previous_image: Option<Arc<DynamicImage>> = None;
     let mut max_average: Option<MaxAverageFrame> = None;
     let mut max_avg_value = 0.0;
+    // Perceptual hash of the last frame sent to OCR, used to drop duplicates
+    let mut last_ocr_hash: Option<PerceptualHash> = None;
+    // Never stretches the interval without an idle interval
+    let mut adaptive_interval = AdaptiveInterval::new(idle_interval.unwrap_or_default());
 
     loop {
-        let (image, image_hash, _capture_duration) = capture_screenshot(&monitor).await;
+        let interval = *interval.borrow();
+        let forced = capture_trigger.take();
+        if forced {
+            adaptive_interval.reset();
+        }
+        if capture_paused.load(Ordering::SeqCst) {
+            capture_trigger.wait(interval).await;
+            continue;
+        }
+
+        let (image, image_hash, _capture_duration) = match &mut capture_source {
+            CaptureSource::Monitor(monitor) => capture_screenshot(monitor).await,
+            CaptureSource::Synthetic(source) => source.capture(),
+        };
+        // Hidden before any other stage sees the frame, so masked content is never stored
+        let image = match &screen_mask {
+            Some(mask) => mask.apply(image, monitor_name.as_deref()),
+            None => image,
+        };
+        let (image, region) = match &crop {
+            Some(crop) => crop.apply(image, monitor_name.clone()),
+            None => {
+                let region =
+                    CaptureRegion::full(monitor_name.clone(), image.width(), image.height());
+                (image, region)
+            }
+        };
+        // Single allocation per frame, every later stage shares it through the Arc
+        let image = Arc::new(image);
+
+        let perceptual_hash = dedup_threshold.map(|_| perceptual_hash(&image));
+        if let (false, Some(threshold), Some(current), Some(last)) =
+            (forced, dedup_threshold, &perceptual_hash, &last_ocr_hash)
+        {
+            let similarity = perceptual_hash_similarity(current, last);
+            if similarity >= threshold {
+                debug!(
+                    "Skipping frame {} as duplicate of last OCR frame, similarity: {:.3}",
+                    frame_counter, similarity
+                );
+                frame_counter += 1;
+                capture_trigger.wait(adaptive_interval.next(interval, 0.0)).await;
+                continue;
+            }
+        }
+
         let current_average = compare_with_previous_image(
             &previous_image,
             &image,
@@ -134,32 +283,39 @@ pub async fn continuous_capture(
         };
 
         // Skip the frame if the current average difference is less than 0.006
-        if current_average < 0.006 {
+        if current_average < 0.006 && !forced {
             debug!("Skipping frame {} due to low average difference: {:.3}", frame_counter, current_average);
             frame_counter += 1;
-            tokio::time::sleep(interval).await;
+            capture_trigger.wait(adaptive_interval.next(interval, current_average)).await;
             continue;
         }
 
-        if current_average > max_avg_value {
+        if forced || current_average > max_avg_value {
+            // Listed now, the windows may have moved by the time the frame is OCR'd
+            let (app_name, windows) = frame_windows(monitor_geometry, &region);
             max_average = Some(MaxAverageFrame {
-                image: Arc::new(image.clone()),
+                image: Arc::clone(&image),
                 image_hash,
+                perceptual_hash,
                 frame_number: frame_counter,
                 timestamp: Instant::now(),
                 result_tx: result_tx.clone(),
                 average: current_average,
+                forced,
+                region,
+                app_name,
+                windows,
             });
-            max_avg_value = current_average;
+            // A requested frame isn't replaced while it waits for an OCR task
+            max_avg_value = if forced { f64::INFINITY } else { current_average };
         }
 
-        previous_image = Some(Arc::new(image.clone()));
-        // debug!("ocr_task_running {} BEFORE if if !ocr_task_running.load(Ordering::SeqCst)", ocr_task_running.load(Ordering::SeqCst));
-
-        if !ocr_task_running.load(Ordering::SeqCst) {
+        previous_image = Some(image);
+        if ocr_tasks_running.load(Ordering::SeqCst) < ocr_pool_size {
             // debug!("max_avg_frame {} before if let Some(", max_avg_value);
             if let Some(max_avg_frame) = max_average.take() {
                 // Use take() to move out the value
+                last_ocr_hash = max_avg_frame.perceptual_hash;
                 let ocr_task_data = OcrTaskData {
                     image: max_avg_frame.image.clone(),
                     frame_number: max_avg_frame.frame_number,
@@ -167,30 +323,109 @@ pub async fn continuous_capture(
                     result_tx: result_tx.clone(),
                 };
 
+                let previous_ocr_image = if differential_ocr {
+                    last_ocr_image.replace(Arc::clone(&max_avg_frame.image))
+                } else {
+                    None
+                };
+
                 let previous_text_json_clone = previous_text_json.clone();
-                let ocr_task_running_clone = ocr_task_running.clone();
+                let previous_data_output_clone = previous_data_output.clone();
+                let ocr_tasks_running_clone = ocr_tasks_running.clone();
+                let is_ignored_app = is_ignored_app.clone();
+                let (task_done_tx, task_done_rx) = oneshot::channel();
+                let wait_for_previous = previous_task_done.replace(task_done_rx);
 
-                ocr_task_running.store(true, Ordering::SeqCst);
-                // debug!("ocr_task_running {}", ocr_task_running.load(Ordering::SeqCst));
+                ocr_tasks_running.fetch_add(1, Ordering::SeqCst);
                 let ocr_engine_clone = ocr_engine.clone();
+                let ocr_languages = ocr_languages.clone();
+                let forced = max_avg_frame.forced;
+                let region = max_avg_frame.region;
+                let app_name = max_avg_frame.app_name;
+                let windows = max_avg_frame.windows;
+                // Masked the same way as the pixels, to leave the elements they hid out
+                let masks = screen_mask
+                    .as_ref()
+                    .map(|mask| {
+                        mask.rects(
+                            region.screen_width,
+                            region.screen_height,
+                            monitor_name.as_deref(),
+                        )
+                    })
+                    .unwrap_or_default();
+                let ocr_max_dimension = if forced { None } else { ocr_max_dimension };
                 tokio::spawn(async move {
-                    let w = Window::all().unwrap().first().unwrap().clone();
-                    let app_name = w.app_name();
-                    if let Err(e) = process_ocr_task(
+                    let placement = monitor_geometry.map(|monitor| ScreenPlacement {
+                        monitor,
+                        region: region.clone(),
+                        masks,
+                    });
+                    let app_languages = ocr_languages.for_app(&app_name);
+                    // xcap doesn't report damaged regions, compute them from the previous OCR'd frame
+                    let dirty_regions = previous_ocr_image.and_then(|previous| {
+                        detect_dirty_regions(&previous, &ocr_task_data.image)
+                    });
+                    let (task_result_tx, mut task_result_rx) = channel(1);
+                    if is_ignored_app(&app_name) {
+                        // Not OCR'd, sent without text so the frame still counts as captured.
+                        // The next frame can't be merged with a result that doesn't exist.
+                        debug!(
+                            "Skipping OCR of frame {} of ignored app {}",
+                            ocr_task_data.frame_number, app_name
+                        );
+                        *previous_data_output_clone.lock().await = None;
+                        let _ = task_result_tx
+                            .send(CaptureResult {
+                                image: ocr_task_data.image,
+                                text: String::new(),
+                                text_json: Vec::new(),
+                                new_text_json: Vec::new(),
+                                frame_number: ocr_task_data.frame_number,
+                                timestamp: ocr_task_data.timestamp,
+                                data_output: DataOutput {
+                                    output: String::new(),
+                                    data: Vec::new(),
+                                },
+                                app_name,
+                                windows,
+                                forced,
+                                region: region.clone(),
+                            })
+                            .await;
+                    } else if let Err(e) = process_ocr_task(
                         ocr_task_data.image,
                         ocr_task_data.frame_number,
                         ocr_task_data.timestamp,
-                        ocr_task_data.result_tx,
+                        task_result_tx,
                         &previous_text_json_clone,
                         save_text_files_flag, // Pass the flag here
                         ocr_engine_clone,     // Pass the cloud_ocr flag here
-                        app_name.to_string().to_lowercase(),
+                        app_name.to_lowercase(),
+                        windows,
+                        ocr_max_dimension,
+                        app_languages,
+                        dirty_regions,
+                        &previous_data_output_clone,
+                        placement.as_ref(),
                     )
                     .await
                     {
                         error!("Error processing OCR task: {}", e);
                     }
-                    ocr_task_running_clone.store(false, Ordering::SeqCst);
+                    ocr_tasks_running_clone.fetch_sub(1, Ordering::SeqCst);
+
+                    if let Some(previous) = wait_for_previous {
+                        let _ = previous.await;
+                    }
+                    if let Ok(mut result) = task_result_rx.try_recv() {
+                        result.forced = forced;
+                        result.region = region;
+                        if ocr_task_data.result_tx.send(result).await.is_err() {
+                            error!("Failed to forward OCR result, receiver dropped");
+                        }
+                    }
+                    let _ = task_done_tx.send(());
                 });
 
                 frame_counter = 0; // Reset frame_counter after OCR task is processed
@@ -200,17 +435,137 @@ pub async fn continuous_capture(
         }
 
         frame_counter += 1;
-        tokio::time::sleep(interval).await;
+        capture_trigger.wait(adaptive_interval.next(interval, current_average)).await;
     }
 }
 
 pub struct MaxAverageFrame {
     pub image: Arc<DynamicImage>,
     pub image_hash: u64,
+    pub perceptual_hash: Option<PerceptualHash>,
     pub frame_number: u64,
     pub timestamp: Instant,
     pub result_tx: Sender<CaptureResult>,
     pub average: f64,
+    /// Requested through a `CaptureTrigger`, OCR'd at full resolution
+    pub forced: bool,
+    pub region: CaptureRegion,
+    /// App of the frontmost window when the frame was captured
+    pub app_name: String,
+    /// Windows on screen when the frame was captured, front to back
+    pub windows: Vec<WindowRegion>,
+}
+
+/// App of the frontmost window and the windows on screen, placed in the frame of `region`.
+/// Synthetic frames have no windows.
+fn frame_windows(
+    monitor_geometry: Option<(i32, i32, u32)>,
+    region: &CaptureRegion,
+) -> (String, Vec<WindowRegion>) {
+    let Some(geometry) = monitor_geometry else {
+        return (SYNTHETIC_APP_NAME.to_string(), Vec::new());
+    };
+    let windows = current_windows(geometry, region.screen_width, region.screen_height);
+    let windows = crop_windows(windows, region);
+    // The frontmost window of this monitor, not of another one
+    let app_name = match windows.first() {
+        Some(window) => window.app_name.clone(),
+        None => frontmost_app().unwrap_or_else(|| "unknown".to_string()),
+    };
+    (app_name, windows)
+}
+
+/// `windows` placed in the cropped frame of `region` instead of the whole screen
+fn crop_windows(mut windows: Vec<WindowRegion>, region: &CaptureRegion) -> Vec<WindowRegion> {
+    if !region.is_cropped() {
+        return windows;
+    }
+    for window in &mut windows {
+        window.x -= region.x as i32;
+        window.y -= region.y as i32;
+    }
+    stack_windows(windows, region.width, region.height)
+}
+
+/// Engines reporting word bounding boxes, required to merge region OCR with the previous frame.
+fn supports_region_ocr(ocr_engine: &OcrEngine) -> bool {
+    matches!(ocr_engine, OcrEngine::Tesseract | OcrEngine::AppleNative)
+}
+
+/// Whether the apple-native engine is built in, it needs swiftc on macOS
+pub const APPLE_NATIVE_OCR: bool = cfg!(swift_bridge);
+
+/// Engine OCR'ing the frames whose accessibility tree can't be read
+pub(crate) const NATIVE_OCR_ENGINE: OcrEngine = if APPLE_NATIVE_OCR {
+    OcrEngine::AppleNative
+} else if cfg!(target_os = "windows") {
+    OcrEngine::WindowsNative
+} else {
+    OcrEngine::Tesseract
+};
+
+/// `placement` of the frame on screen, without it the accessibility tree isn't read, e.g.
+/// for frames captured before
+async fn run_ocr_engine(
+    image: &Arc<DynamicImage>,
+    ocr_engine: &OcrEngine,
+    ocr_languages: &[String],
+    placement: Option<&ScreenPlacement>,
+) -> Result<(String, DataOutput, String), std::io::Error> {
+    let ocr_engine = match (ocr_engine, placement) {
+        (OcrEngine::Accessibility, Some(placement)) => {
+            let elements = tokio::task::spawn_blocking(focused_window_elements)
+                .await
+                .ok()
+                .flatten();
+            let output = elements
+                .map(|elements| elements_to_ocr_output(&elements, placement, image.width()));
+            match output {
+                // Apps drawing their own UI, e.g. games, expose an empty tree
+                Some(output) if !output.0.is_empty() => {
+                    debug!("Accessibility tree of the focused window");
+                    return Ok(output);
+                }
+                _ => {
+                    debug!("No accessibility tree on the captured region, falling back to OCR");
+                    &NATIVE_OCR_ENGINE
+                }
+            }
+        }
+        (OcrEngine::Accessibility, None) => &NATIVE_OCR_ENGINE,
+        (ocr_engine, _) => ocr_engine,
+    };
+    match ocr_engine {
+        #[cfg(feature = "cloud")]
+        OcrEngine::Cloud(provider) => {
+            debug!("Cloud OCR with {}", provider.name());
+            perform_ocr_cloud(provider.as_ref(), image)
+                .await
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+        }
+        OcrEngine::Tesseract => {
+            debug!("Local Tesseract OCR");
+            Ok(perform_ocr_tesseract_languages(image, ocr_languages))
+        }
+        #[cfg(target_os = "windows")]
+        OcrEngine::WindowsNative => {
+            debug!("Windows Native OCR");
+            Ok(perform_ocr_windows(image, ocr_languages).await)
+        }
+        #[cfg(swift_bridge)]
+        OcrEngine::AppleNative => {
+            debug!("Apple Native OCR");
+            Ok(perform_ocr_apple(image, ocr_languages))
+        }
+        _ => {
+            error!("Unsupported OCR engine");
+            Err(std::io::Error::new(
+                // TODO we should use anyhow everywhere for error
+                std::io::ErrorKind::Other,
+                "Unsupported OCR engine",
+            ))
+        }
+    }
 }
 
 pub async fn process_ocr_task(
@@ -222,6 +577,12 @@ pub async fn process_ocr_task(
     save_text_files_flag: bool, // Add this parameter
     ocr_engine: Arc<OcrEngine>, // Add this parameter
     app_name: String,
+    mut windows: Vec<WindowRegion>,
+    ocr_max_dimension: Option<u32>,
+    ocr_languages: &[String],
+    dirty_regions: Option<Vec<DirtyRect>>,
+    previous_data_output: &Arc<Mutex<Option<DataOutput>>>,
+    placement: Option<&ScreenPlacement>,
 ) -> Result<(), std::io::Error> {
     let start_time = Instant::now();
 
@@ -230,29 +591,60 @@ pub async fn process_ocr_task(
         "Performing OCR for frame number since beginning of program {}",
         frame_number
     );
-    let (text, data_output, json_output) = match &*ocr_engine {
-        OcrEngine::Unstructured => {
-            debug!("Cloud Unstructured OCR");
-            perform_ocr_cloud(&image_arc).await
+    let mut previous_data_output = previous_data_output.lock().await;
+    let differential = match (&dirty_regions, &*previous_data_output) {
+        (Some(regions), Some(previous)) if supports_region_ocr(&ocr_engine) => {
+            let dirty_area: u64 = regions.iter().map(|r| r.area()).sum();
+            let frame_area = image_arc.width() as u64 * image_arc.height() as u64;
+            // Past half the screen a single full pass is cheaper than many crops
+            if dirty_area * 2 < frame_area {
+                Some((regions, previous))
+            } else {
+                None
+            }
         }
-        OcrEngine::Tesseract => {
-            debug!("Local Tesseract OCR");
-            perform_ocr_tesseract(&image_arc)
+        _ => None,
+    };
+
+    let ocr_result = match differential {
+        Some((regions, previous)) => {
+            debug!(
+                "Differential OCR on {} dirty regions for frame {}",
+                regions.len(),
+                frame_number
+            );
+            let mut region_outputs = Vec::with_capacity(regions.len());
+            for rect in regions {
+                let crop = Arc::new(image_arc.crop_imm(rect.x, rect.y, rect.width, rect.height));
+                let (ocr_image, scale) = downscale_for_ocr(&crop, ocr_max_dimension);
+                let (_, mut region_output, _) = run_ocr_engine(&ocr_image, &ocr_engine, ocr_languages, None).await?;
+                scale_data_output(&mut region_output, scale);
+                region_outputs.push((*rect, region_output));
+            }
+            Ok(merge_region_ocr(previous, &region_outputs))
         }
-        #[cfg(target_os = "windows")]
-        OcrEngine::WindowsNative => {
-            debug!("Windows Native OCR");
-            perform_ocr_windows(&image_arc).await
+        None => {
+            // OCR a downscaled copy on large displays, the stored frame stays at native resolution
+            let (ocr_image, scale) = downscale_for_ocr(&image_arc, ocr_max_dimension);
+            run_ocr_engine(&ocr_image, &ocr_engine, ocr_languages, placement)
+                .await
+                .map(|(text, mut data_output, json_output)| {
+                    scale_data_output(&mut data_output, scale);
+                    (text, data_output, json_output)
+                })
         }
-        _ => {
-            error!("Unsupported OCR engine");
-            return Err(std::io::Error::new(
-                // TODO we should use anyhow everywhere for error
-                std::io::ErrorKind::Other,
-                "Unsupported OCR engine",
-            ));
+    };
+    let (text, data_output, json_output) = match ocr_result {
+        Ok(result) => result,
+        Err(e) => {
+            // The next frame can't be merged with stale data, force a full pass
+            *previous_data_output = None;
+            return Err(e);
         }
     };
+    *previous_data_output = Some(clone_data_output(&data_output));
+    drop(previous_data_output);
+    attribute_text(&mut windows, &data_output);
 
     let current_text_json: Vec<HashMap<String, String>> = serde_json::from_str(&json_output)
         .unwrap_or_else(|e| {
@@ -301,7 +693,7 @@ pub async fn process_ocr_task(
 
     if let Err(e) = result_tx
         .send(CaptureResult {
-            image: image_arc.into(),
+            image: image_arc,
             text: text.clone(),
             text_json: current_text_json,
             new_text_json,
@@ -309,6 +701,9 @@ pub async fn process_ocr_task(
             timestamp,
             data_output,
             app_name,
+            region: CaptureRegion::full(None, image_arc.width(), image_arc.height()),
+            windows,
+            forced: false,
         })
         .await
     {
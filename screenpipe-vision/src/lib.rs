@@ -1,4 +1,54 @@
+//! Screen capture and OCR of screenpipe.
+//!
+//! The items re-exported at the crate root are the public API of this crate and follow
+//! semver. The `core`, `utils` and `apple` modules are implementation details and can
+//! change in any release.
+
+mod accessibility;
+mod adaptive_interval;
+#[cfg(swift_bridge)]
+#[doc(hidden)]
+pub mod apple;
+mod capture_region;
+mod clipboard;
+#[doc(hidden)]
 pub mod core;
+mod masked_regions;
+mod ocr_languages;
+mod sensitive;
+#[doc(hidden)]
+pub mod synthetic;
+#[doc(hidden)]
 pub mod utils;
-pub use core::{continuous_capture, get_monitor, process_ocr_task, CaptureResult};
-pub use utils::{perform_ocr_tesseract, OcrEngine};
+mod windows;
+pub use accessibility::{
+    elements_to_ocr_output, focused_window_elements, ElementFrame, ScreenPlacement, UiElement,
+};
+pub use adaptive_interval::AdaptiveInterval;
+pub use capture_region::{CaptureCrop, CaptureRegion};
+pub use clipboard::clipboard_is_concealed;
+#[cfg(feature = "cloud")]
+pub use screenpipe_integrations::cloud_ocr::{
+    perform_ocr_cloud, CloudOcrConfig, CloudOcrLine, CloudOcrProvider, CloudOcrWord, OcrProvider,
+};
+pub use core::{
+    continuous_capture, find_monitor, get_monitor, process_ocr_task, CaptureResult,
+    CaptureSource, CaptureTrigger, DataOutputWrapper,
+};
+pub use masked_regions::{MaskStyle, MaskedRegion, RegionAnchor, RegionLength, ScreenMask};
+pub use ocr_languages::{installed_tesseract_languages, native_language_tag, OcrLanguages};
+pub use sensitive::{
+    blur_regions, redact_words, SensitiveAction, SensitiveCategory, SensitiveContent,
+    SensitiveContentFilter,
+};
+pub use synthetic::{SyntheticCaptureSource, SYNTHETIC_APP_NAME};
+pub use utils::{
+    capture_screenshot, perceptual_hash, perceptual_hash_similarity, perform_ocr_tesseract,
+    OcrEngine, PerceptualHash,
+};
+pub use windows::{attribute_text, current_windows, frontmost_app, stack_windows, WindowRegion};
+#[cfg(swift_bridge)]
+pub use apple::perform_ocr_apple;
+pub use core::APPLE_NATIVE_OCR;
+#[cfg(target_os = "windows")]
+pub use utils::perform_ocr_windows;
@@ -0,0 +1,271 @@
+use crate::sensitive::blur_regions;
+use crate::utils::DirtyRect;
+use image::{DynamicImage, GenericImage, Rgba};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Corner of the screen a `MaskedRegion` is positioned from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegionAnchor {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl RegionAnchor {
+    pub const ALL: [RegionAnchor; 4] = [
+        RegionAnchor::TopLeft,
+        RegionAnchor::TopRight,
+        RegionAnchor::BottomLeft,
+        RegionAnchor::BottomRight,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegionAnchor::TopLeft => "top-left",
+            RegionAnchor::TopRight => "top-right",
+            RegionAnchor::BottomLeft => "bottom-left",
+            RegionAnchor::BottomRight => "bottom-right",
+        }
+    }
+}
+
+/// Position or size along one axis, in pixels of the captured frame or in percent of the
+/// screen so it keeps covering the same area when the resolution changes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegionLength {
+    Pixels(u32),
+    Percent(f64),
+}
+
+impl RegionLength {
+    pub(crate) fn resolve(&self, extent: u32) -> u32 {
+        match *self {
+            RegionLength::Pixels(pixels) => pixels.min(extent),
+            RegionLength::Percent(percent) => {
+                ((extent as f64 * percent / 100.0).round() as u32).min(extent)
+            }
+        }
+    }
+}
+
+impl FromStr for RegionLength {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<f64>() {
+                Ok(percent) if (0.0..=100.0).contains(&percent) => {
+                    Ok(RegionLength::Percent(percent))
+                }
+                _ => Err(format!("{} is not a percentage between 0% and 100%", s)),
+            },
+            None => s
+                .parse()
+                .map(RegionLength::Pixels)
+                .map_err(|_| format!("{} is neither pixels nor a percentage", s)),
+        }
+    }
+}
+
+impl fmt::Display for RegionLength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionLength::Pixels(pixels) => write!(f, "{}", pixels),
+            RegionLength::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// Rectangle of the screen hidden from recordings, `x` and `y` being its offsets from
+/// `anchor`. Parsed from `[<monitor>@][<anchor>:]<x>,<y>,<width>,<height>`, e.g.
+/// `top-right:0,0,400,150` or `DELL U2720Q@bottom-left:0,0,30%,40%`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MaskedRegion {
+    /// Monitor the region is on, matched by name so it follows the monitor when monitors
+    /// are reordered or unplugged. Every monitor if `None`.
+    pub monitor: Option<String>,
+    pub anchor: RegionAnchor,
+    pub x: RegionLength,
+    pub y: RegionLength,
+    pub width: RegionLength,
+    pub height: RegionLength,
+}
+
+impl MaskedRegion {
+    pub fn applies_to(&self, monitor_name: Option<&str>) -> bool {
+        match (&self.monitor, monitor_name) {
+            (None, _) => true,
+            (Some(monitor), Some(name)) => monitor.eq_ignore_ascii_case(name.trim()),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Pixels covered in a frame of `frame_width` by `frame_height`, `None` if the region
+    /// is entirely off screen
+    pub fn rect(&self, frame_width: u32, frame_height: u32) -> Option<DirtyRect> {
+        let (x, width) = place(
+            self.x.resolve(frame_width),
+            self.width.resolve(frame_width),
+            frame_width,
+            matches!(
+                self.anchor,
+                RegionAnchor::TopRight | RegionAnchor::BottomRight
+            ),
+        );
+        let (y, height) = place(
+            self.y.resolve(frame_height),
+            self.height.resolve(frame_height),
+            frame_height,
+            matches!(
+                self.anchor,
+                RegionAnchor::BottomLeft | RegionAnchor::BottomRight
+            ),
+        );
+        if width == 0 || height == 0 {
+            return None;
+        }
+        Some(DirtyRect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+/// Start and length along an axis of `extent`, clipped to the screen
+fn place(offset: u32, length: u32, extent: u32, from_end: bool) -> (u32, u32) {
+    if from_end {
+        let end = extent.saturating_sub(offset);
+        let start = end.saturating_sub(length);
+        (start, end - start)
+    } else {
+        let start = offset.min(extent);
+        (start, length.min(extent - start))
+    }
+}
+
+impl FromStr for MaskedRegion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (monitor, rest) = match s.rsplit_once('@') {
+            Some((monitor, rest)) => (Some(monitor.trim().to_string()), rest),
+            None => (None, s),
+        };
+        let (anchor, rest) = match rest.split_once(':') {
+            Some((anchor, rest)) => {
+                let anchor = RegionAnchor::ALL
+                    .into_iter()
+                    .find(|a| a.as_str().eq_ignore_ascii_case(anchor.trim()))
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown region anchor {}, expected top-left, top-right, bottom-left or bottom-right",
+                            anchor
+                        )
+                    })?;
+                (anchor, rest)
+            }
+            None => (RegionAnchor::default(), rest),
+        };
+        let lengths = rest
+            .split(',')
+            .map(RegionLength::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        match lengths[..] {
+            [x, y, width, height] => Ok(MaskedRegion {
+                monitor,
+                anchor,
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(format!(
+                "masked region must be [<monitor>@][<anchor>:]<x>,<y>,<width>,<height>, got {}",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MaskedRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(monitor) = &self.monitor {
+            write!(f, "{}@", monitor)?;
+        }
+        write!(
+            f,
+            "{}:{},{},{},{}",
+            self.anchor.as_str(),
+            self.x,
+            self.y,
+            self.width,
+            self.height
+        )
+    }
+}
+
+/// How `ScreenMask` hides its regions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskStyle {
+    Blur,
+    Black,
+}
+
+/// Regions hidden from every captured frame before it's deduplicated, OCRed or encoded,
+/// so their content never reaches the database or the videos
+#[derive(Debug, Clone)]
+pub struct ScreenMask {
+    pub regions: Vec<MaskedRegion>,
+    pub style: MaskStyle,
+}
+
+impl ScreenMask {
+    pub fn new(regions: Vec<MaskedRegion>, style: MaskStyle) -> Self {
+        ScreenMask { regions, style }
+    }
+
+    /// Rectangles of the regions on `monitor_name` in a frame of the given size, resolved
+    /// on every frame so they follow resolution and scaling changes
+    pub fn rects(
+        &self,
+        frame_width: u32,
+        frame_height: u32,
+        monitor_name: Option<&str>,
+    ) -> Vec<DirtyRect> {
+        self.regions
+            .iter()
+            .filter(|region| region.applies_to(monitor_name))
+            .filter_map(|region| region.rect(frame_width, frame_height))
+            .collect()
+    }
+
+    /// `image` of `monitor_name` with the regions hidden, synthetic frames having no monitor
+    pub fn apply(&self, mut image: DynamicImage, monitor_name: Option<&str>) -> DynamicImage {
+        let rects = self.rects(image.width(), image.height(), monitor_name);
+        if rects.is_empty() {
+            return image;
+        }
+        match self.style {
+            MaskStyle::Blur => blur_regions(&image, &rects),
+            MaskStyle::Black => {
+                for rect in rects {
+                    for y in rect.y..rect.y + rect.height {
+                        for x in rect.x..rect.x + rect.width {
+                            image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+                        }
+                    }
+                }
+                image
+            }
+        }
+    }
+}
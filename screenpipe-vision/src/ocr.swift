@@ -0,0 +1,78 @@
+import CoreGraphics
+import Foundation
+import Vision
+
+// Recognizes text in an RGBA8 buffer with VNRecognizeTextRequest and returns a JSON
+// array of observations: [{"text", "confidence", "left", "top", "width", "height"}].
+// Coordinates are in pixels with a top-left origin, like Tesseract output. `languages` are
+// comma separated BCP 47 tags in order of preference, e.g. "en-US,ja-JP", Vision's default
+// when empty.
+// The returned string must be released with `free_string`.
+@_cdecl("perform_ocr")
+public func performOcr(
+  imageData: UnsafePointer<UInt8>, length: Int, width: Int, height: Int,
+  languages: UnsafePointer<CChar>
+) -> UnsafeMutablePointer<CChar>? {
+  let data = Data(bytes: imageData, count: length)
+  guard let provider = CGDataProvider(data: data as CFData),
+    let cgImage = CGImage(
+      width: width,
+      height: height,
+      bitsPerComponent: 8,
+      bitsPerPixel: 32,
+      bytesPerRow: width * 4,
+      space: CGColorSpaceCreateDeviceRGB(),
+      bitmapInfo: CGBitmapInfo(rawValue: CGImageAlphaInfo.premultipliedLast.rawValue),
+      provider: provider,
+      decode: nil,
+      shouldInterpolate: false,
+      intent: .defaultIntent
+    )
+  else {
+    return nil
+  }
+
+  let request = VNRecognizeTextRequest()
+  request.recognitionLevel = .accurate
+  request.usesLanguageCorrection = true
+  let recognitionLanguages = String(cString: languages)
+    .split(separator: ",")
+    .map(String.init)
+  if !recognitionLanguages.isEmpty {
+    request.recognitionLanguages = recognitionLanguages
+  }
+
+  let handler = VNImageRequestHandler(cgImage: cgImage, options: [:])
+  do {
+    try handler.perform([request])
+  } catch {
+    return nil
+  }
+
+  var observations: [[String: Any]] = []
+  for observation in request.results ?? [] {
+    guard let candidate = observation.topCandidates(1).first else { continue }
+    // Vision bounding boxes are normalized with a bottom-left origin
+    let box = observation.boundingBox
+    observations.append([
+      "text": candidate.string,
+      "confidence": candidate.confidence,
+      "left": Int(box.minX * CGFloat(width)),
+      "top": Int((1.0 - box.maxY) * CGFloat(height)),
+      "width": Int(box.width * CGFloat(width)),
+      "height": Int(box.height * CGFloat(height)),
+    ])
+  }
+
+  guard let json = try? JSONSerialization.data(withJSONObject: observations),
+    let jsonString = String(data: json, encoding: .utf8)
+  else {
+    return nil
+  }
+  return strdup(jsonString)
+}
+
+@_cdecl("free_string")
+public func freeString(_ pointer: UnsafeMutablePointer<CChar>?) {
+  free(pointer)
+}
@@ -0,0 +1,97 @@
+use log::debug;
+use std::collections::HashMap;
+use std::process::Command;
+
+/// OCR languages of every app, as Tesseract codes, e.g. `["eng", "chi_sim"]`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OcrLanguages {
+    /// Used by the apps without their own languages, English if empty
+    pub default: Vec<String>,
+    /// By lowercase app name
+    pub apps: HashMap<String, Vec<String>>,
+}
+
+impl OcrLanguages {
+    pub fn new(default: Vec<String>) -> Self {
+        OcrLanguages {
+            default,
+            apps: HashMap::new(),
+        }
+    }
+
+    /// OCRs the windows of `app_name`, compared case-insensitively, with `languages`
+    pub fn set_app(&mut self, app_name: &str, languages: Vec<String>) {
+        self.apps.insert(app_name.to_lowercase(), languages);
+    }
+
+    pub fn for_app(&self, app_name: &str) -> &[String] {
+        self.apps
+            .get(&app_name.to_lowercase())
+            .unwrap_or(&self.default)
+    }
+
+    /// Every language used, without duplicates
+    pub fn all(&self) -> Vec<String> {
+        let mut all: Vec<String> = Vec::new();
+        for language in self.apps.values().flatten().chain(&self.default) {
+            if !all.contains(language) {
+                all.push(language.clone());
+            }
+        }
+        all
+    }
+}
+
+/// BCP 47 tag of a Tesseract language, as Apple Vision and Windows OCR take them, `None` for
+/// the languages neither recognizes
+pub fn native_language_tag(language: &str) -> Option<&'static str> {
+    let tag = match language {
+        "eng" => "en-US",
+        "fra" => "fr-FR",
+        "deu" => "de-DE",
+        "spa" => "es-ES",
+        "ita" => "it-IT",
+        "por" => "pt-BR",
+        "nld" => "nl-NL",
+        "pol" => "pl-PL",
+        "ces" => "cs-CZ",
+        "swe" => "sv-SE",
+        "dan" => "da-DK",
+        "nor" => "nb-NO",
+        "fin" => "fi-FI",
+        "tur" => "tr-TR",
+        "rus" => "ru-RU",
+        "ukr" => "uk-UA",
+        "ell" => "el-GR",
+        "ara" => "ar-SA",
+        "heb" => "he-IL",
+        "hin" => "hi-IN",
+        "tha" => "th-TH",
+        "vie" => "vi-VN",
+        "ind" => "id-ID",
+        "chi_sim" => "zh-Hans",
+        "chi_tra" => "zh-Hant",
+        "jpn" => "ja-JP",
+        "kor" => "ko-KR",
+        _ => return None,
+    };
+    Some(tag)
+}
+
+/// Tesseract languages whose pack is installed, empty if tesseract can't be run
+pub fn installed_tesseract_languages() -> Vec<String> {
+    let output = match Command::new("tesseract").arg("--list-langs").output() {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Failed to list the Tesseract languages: {}", e);
+            return Vec::new();
+        }
+    };
+    // The first line is a header, e.g. List of available languages in "/usr/share/tessdata/" (3):
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1)
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
@@ -0,0 +1,471 @@
+use crate::core::CaptureResult;
+use crate::utils::{clone_data, is_word, words_to_output, DirtyRect};
+use crate::windows::attribute_text;
+use image::imageops;
+use image::{DynamicImage, RgbImage};
+use log::debug;
+use rusty_tesseract::{Data, DataOutput};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Kind of content flagged by `SensitiveContentFilter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SensitiveCategory {
+    PasswordManager,
+    Banking,
+    Nudity,
+}
+
+impl SensitiveCategory {
+    pub const ALL: [SensitiveCategory; 3] = [
+        SensitiveCategory::PasswordManager,
+        SensitiveCategory::Banking,
+        SensitiveCategory::Nudity,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SensitiveCategory::PasswordManager => "password-manager",
+            SensitiveCategory::Banking => "banking",
+            SensitiveCategory::Nudity => "nudity",
+        }
+    }
+}
+
+impl FromStr for SensitiveCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SensitiveCategory::ALL
+            .into_iter()
+            .find(|category| category.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown sensitive content category: {}", s))
+    }
+}
+
+/// What happens to a flagged frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensitiveAction {
+    /// Neither recorded nor OCRed, like the frames of an ignored app
+    Skip,
+    /// The flagged regions are blurred in the video and their words left out of the text
+    Blur,
+    /// The text is kept, the video gets a black frame instead
+    TextOnly,
+}
+
+/// A frame flagged by `SensitiveContentFilter::classify`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensitiveContent {
+    pub category: SensitiveCategory,
+    /// Regions of the content, empty for the whole frame
+    pub regions: Vec<DirtyRect>,
+}
+
+/// Phrases of password manager windows, two of them flag a frame
+const PASSWORD_MANAGER_PHRASES: &[&str] = &[
+    "1password",
+    "bitwarden",
+    "keepass",
+    "lastpass",
+    "dashlane",
+    "keychain access",
+    "master password",
+    "password generator",
+    "generate password",
+    "copy password",
+    "show password",
+    "reveal password",
+    "password strength",
+    "one-time password",
+    "recovery codes",
+    "secure notes",
+];
+
+/// Phrases of banking pages, two of them flag a frame, lines with one are blurred
+const BANKING_PHRASES: &[&str] = &[
+    "account number",
+    "routing number",
+    "sort code",
+    "iban",
+    "swift",
+    "bic",
+    "available balance",
+    "current balance",
+    "card number",
+    "cvv",
+    "cvc",
+    "security code",
+    "expiry date",
+    "online banking",
+    "transfer funds",
+    "recent transactions",
+];
+
+/// Frames with more words are documents or apps, not photos
+const NUDITY_MAX_WORDS: usize = 20;
+/// Share of skin-toned pixels above which a photo is flagged
+const NUDITY_SKIN_RATIO: f64 = 0.45;
+/// Pixels added around the blurred lines
+const REGION_PADDING: u32 = 8;
+const BLUR_SIGMA: f32 = 12.0;
+
+/// Opt-in on-device classifier of sensitive frames (password managers, banking, nudity)
+/// and the action applied to them. It complements the ignored apps for apps whose name
+/// doesn't give them away, like a password manager in a browser. Text categories are
+/// matched on the OCR of the frame, nudity on the share of skin-toned pixels of
+/// frames with little text. These are heuristics, expect misses and false positives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensitiveContentFilter {
+    pub categories: Vec<SensitiveCategory>,
+    pub action: SensitiveAction,
+}
+
+impl SensitiveContentFilter {
+    pub fn new(categories: Vec<SensitiveCategory>, action: SensitiveAction) -> Self {
+        SensitiveContentFilter { categories, action }
+    }
+
+    /// The first category of `categories` matching the frame, None if it looks harmless
+    pub fn classify(
+        &self,
+        image: &DynamicImage,
+        text: &str,
+        data_output: &DataOutput,
+    ) -> Option<SensitiveContent> {
+        let lines = text_lines(text, data_output);
+        self.categories
+            .iter()
+            .find_map(|category| match category {
+                SensitiveCategory::PasswordManager => {
+                    classify_password_manager(&lines).map(|regions| (category, regions))
+                }
+                SensitiveCategory::Banking => {
+                    classify_banking(&lines).map(|regions| (category, regions))
+                }
+                SensitiveCategory::Nudity => {
+                    classify_nudity(image, text).map(|regions| (category, regions))
+                }
+            })
+            .map(|(category, regions)| SensitiveContent {
+                category: *category,
+                regions,
+            })
+    }
+
+    /// The first text category of `categories` matching `text` without a frame, e.g. copied
+    /// to the clipboard, None if it looks harmless. Nudity needs the pixels, never matched.
+    pub fn classify_text(&self, text: &str) -> Option<SensitiveCategory> {
+        let no_boxes = DataOutput {
+            output: text.to_string(),
+            data: Vec::new(),
+        };
+        let lines = text_lines(text, &no_boxes);
+        self.categories
+            .iter()
+            .find(|category| match category {
+                SensitiveCategory::PasswordManager => classify_password_manager(&lines).is_some(),
+                SensitiveCategory::Banking => classify_banking(&lines).is_some(),
+                SensitiveCategory::Nudity => false,
+            })
+            .copied()
+    }
+
+    /// The frame after the action, None if it's skipped
+    pub fn apply(&self, mut result: CaptureResult) -> Option<CaptureResult> {
+        let content = self.classify(&result.image, &result.text, &result.data_output)?;
+        debug!(
+            "Frame {} flagged as {} ({} regions), {:?}",
+            result.frame_number,
+            content.category.as_str(),
+            content.regions.len(),
+            self.action
+        );
+        match self.action {
+            SensitiveAction::Skip => return None,
+            SensitiveAction::Blur => {
+                result.image = Arc::new(blur_regions(&result.image, &content.regions));
+                let (text, data_output, json_output) =
+                    redact_words(&result.data_output, &content.regions);
+                let text_json: Vec<HashMap<String, String>> =
+                    serde_json::from_str(&json_output).unwrap_or_default();
+                let kept: Vec<&String> = text_json.iter().filter_map(|l| l.get("text")).collect();
+                result
+                    .new_text_json
+                    .retain(|line| line.get("text").map_or(false, |t| kept.contains(&t)));
+                result.text = text;
+                result.text_json = text_json;
+                result.data_output = data_output;
+                attribute_text(&mut result.windows, &result.data_output);
+            }
+            SensitiveAction::TextOnly => {
+                let (width, height) = (result.image.width(), result.image.height());
+                result.image = Arc::new(DynamicImage::ImageRgb8(RgbImage::new(width, height)));
+            }
+        }
+        Some(result)
+    }
+}
+
+/// A line of OCR text, with the box of its words when the OCR engine gives them
+struct TextLine {
+    text: String,
+    region: Option<DirtyRect>,
+}
+
+fn text_lines(text: &str, data_output: &DataOutput) -> Vec<TextLine> {
+    let mut lines: Vec<Vec<&Data>> = Vec::new();
+    for word in data_output.data.iter().filter(|d| is_word(d)) {
+        match lines.iter_mut().find(|line| {
+            (
+                line[0].page_num,
+                line[0].block_num,
+                line[0].par_num,
+                line[0].line_num,
+            ) == (word.page_num, word.block_num, word.par_num, word.line_num)
+        }) {
+            Some(line) => line.push(word),
+            None => lines.push(vec![word]),
+        }
+    }
+    if lines.is_empty() {
+        // Engines without word boxes, only the whole frame can be blurred
+        return text
+            .lines()
+            .map(|line| TextLine {
+                text: line.to_lowercase(),
+                region: None,
+            })
+            .collect();
+    }
+    lines
+        .into_iter()
+        .map(|words| TextLine {
+            text: words
+                .iter()
+                .map(|w| w.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase(),
+            region: words
+                .iter()
+                .map(|w| word_rect(w))
+                .reduce(|a, b| a.union(&b)),
+        })
+        .collect()
+}
+
+fn word_rect(word: &Data) -> DirtyRect {
+    DirtyRect {
+        x: word.left.max(0) as u32,
+        y: word.top.max(0) as u32,
+        width: word.width.max(0) as u32,
+        height: word.height.max(0) as u32,
+    }
+}
+
+/// Whether `phrase` is in `line` as whole words, "bic" isn't in "public"
+fn contains_phrase(line: &str, phrase: &str) -> bool {
+    line.match_indices(phrase).any(|(start, _)| {
+        let before = line[..start].chars().next_back();
+        let after = line[start + phrase.len()..].chars().next();
+        !before.map_or(false, char::is_alphanumeric) && !after.map_or(false, char::is_alphanumeric)
+    })
+}
+
+fn distinct_phrases(lines: &[TextLine], phrases: &[&str]) -> usize {
+    phrases
+        .iter()
+        .filter(|phrase| lines.iter().any(|line| contains_phrase(&line.text, phrase)))
+        .count()
+}
+
+/// The whole frame, a password manager shows secrets anywhere in its window
+fn classify_password_manager(lines: &[TextLine]) -> Option<Vec<DirtyRect>> {
+    (distinct_phrases(lines, PASSWORD_MANAGER_PHRASES) >= 2).then(Vec::new)
+}
+
+/// The lines with a banking phrase, card number or IBAN
+fn classify_banking(lines: &[TextLine]) -> Option<Vec<DirtyRect>> {
+    let has_number = |line: &TextLine| has_card_number(&line.text) || has_iban(&line.text);
+    if distinct_phrases(lines, BANKING_PHRASES) < 2 && !lines.iter().any(has_number) {
+        return None;
+    }
+    let flagged: Vec<&TextLine> = lines
+        .iter()
+        .filter(|line| {
+            has_number(line)
+                || BANKING_PHRASES
+                    .iter()
+                    .any(|phrase| contains_phrase(&line.text, phrase))
+        })
+        .collect();
+    if flagged.iter().any(|line| line.region.is_none()) {
+        return Some(Vec::new());
+    }
+    Some(
+        flagged
+            .iter()
+            .filter_map(|line| line.region)
+            .map(pad_region)
+            .collect(),
+    )
+}
+
+fn pad_region(rect: DirtyRect) -> DirtyRect {
+    DirtyRect {
+        x: rect.x.saturating_sub(REGION_PADDING),
+        y: rect.y.saturating_sub(REGION_PADDING),
+        width: rect.width + 2 * REGION_PADDING,
+        height: rect.height + 2 * REGION_PADDING,
+    }
+}
+
+/// A run of 13 to 19 digits, spaces and dashes allowed, passing the Luhn check
+pub(crate) fn has_card_number(line: &str) -> bool {
+    let mut digits = Vec::new();
+    for c in line.chars().chain(std::iter::once('.')) {
+        match c {
+            '0'..='9' => digits.push(c as u8 - b'0'),
+            ' ' | '-' => {}
+            _ => {
+                if (13..=19).contains(&digits.len()) && luhn(&digits) {
+                    return true;
+                }
+                digits.clear();
+            }
+        }
+    }
+    false
+}
+
+fn luhn(digits: &[u8]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            let d = d as u32;
+            if i % 2 == 1 {
+                if d * 2 > 9 {
+                    d * 2 - 9
+                } else {
+                    d * 2
+                }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+/// An IBAN, spaces allowed, passing the mod 97 check
+pub(crate) fn has_iban(line: &str) -> bool {
+    let compact: String = line
+        .to_uppercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("");
+    let bytes = compact.as_bytes();
+    (0..bytes.len()).any(|start| {
+        let candidate = &bytes[start..];
+        candidate.len() >= 15
+            && candidate[..2].iter().all(u8::is_ascii_uppercase)
+            && candidate[2..4].iter().all(u8::is_ascii_digit)
+            && (start == 0 || !bytes[start - 1].is_ascii_alphanumeric())
+            && (15..=candidate.len().min(34)).any(|len| {
+                candidate[..len].iter().all(u8::is_ascii_alphanumeric)
+                    && candidate
+                        .get(len)
+                        .map_or(true, |c| !c.is_ascii_alphanumeric())
+                    && iban_checksum(&candidate[..len])
+            })
+    })
+}
+
+fn iban_checksum(iban: &[u8]) -> bool {
+    let mut remainder = 0u32;
+    for &c in iban[4..].iter().chain(&iban[..4]) {
+        let value = if c.is_ascii_digit() {
+            (c - b'0') as u32
+        } else {
+            (c - b'A') as u32 + 10
+        };
+        remainder = if value >= 10 {
+            (remainder * 100 + value) % 97
+        } else {
+            (remainder * 10 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+/// The whole frame, when it's mostly skin-toned and has little text
+fn classify_nudity(image: &DynamicImage, text: &str) -> Option<Vec<DirtyRect>> {
+    if text.split_whitespace().count() > NUDITY_MAX_WORDS {
+        return None;
+    }
+    (skin_ratio(image) > NUDITY_SKIN_RATIO).then(Vec::new)
+}
+
+/// Share of the pixels of a thumbnail of `image` in the RGB skin-tone range
+pub(crate) fn skin_ratio(image: &DynamicImage) -> f64 {
+    let thumbnail = image.thumbnail(64, 64).to_rgb8();
+    let pixels = thumbnail.pixels().len();
+    if pixels == 0 {
+        return 0.0;
+    }
+    let skin = thumbnail
+        .pixels()
+        .filter(|p| {
+            let [r, g, b] = p.0.map(|c| c as i32);
+            let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+            r > 95 && g > 40 && b > 20 && max - min > 15 && (r - g).abs() > 15 && r > g && r > b
+        })
+        .count();
+    skin as f64 / pixels as f64
+}
+
+/// `image` with `regions` blurred, the whole image if there are none
+pub fn blur_regions(image: &DynamicImage, regions: &[DirtyRect]) -> DynamicImage {
+    if regions.is_empty() {
+        return image.blur(BLUR_SIGMA);
+    }
+    let mut blurred = image.clone();
+    for rect in regions {
+        let x = rect.x.min(image.width());
+        let y = rect.y.min(image.height());
+        let width = rect.width.min(image.width() - x);
+        let height = rect.height.min(image.height() - y);
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let region = image.crop_imm(x, y, width, height).blur(BLUR_SIGMA);
+        imageops::overlay(&mut blurred, &region, x as i64, y as i64);
+    }
+    blurred
+}
+
+/// Text, data and JSON output of the words outside `regions`, nothing if there are none
+pub fn redact_words(
+    data_output: &DataOutput,
+    regions: &[DirtyRect],
+) -> (String, DataOutput, String) {
+    let words = data_output
+        .data
+        .iter()
+        .filter(|d| is_word(d))
+        .filter(|d| {
+            !regions.is_empty()
+                && !regions
+                    .iter()
+                    .any(|rect| rect.intersects_box(d.left, d.top, d.width, d.height))
+        })
+        .map(|d| (clone_data(d), None))
+        .collect();
+    words_to_output(words, false)
+}
@@ -0,0 +1,70 @@
+use image::DynamicImage;
+use log::error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::utils::calculate_hash;
+
+/// App name reported for frames played back from a directory
+pub const SYNTHETIC_APP_NAME: &str = "synthetic";
+
+/// Plays back the images of a directory in a loop instead of capturing a monitor,
+/// so the pipeline can run deterministically without a display (tests, CI).
+pub struct SyntheticCaptureSource {
+    frames: Vec<PathBuf>,
+    next: usize,
+}
+
+impl SyntheticCaptureSource {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        let mut frames: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| {
+                        matches!(
+                            ext.to_lowercase().as_str(),
+                            "png" | "jpg" | "jpeg" | "bmp"
+                        )
+                    })
+                    .unwrap_or(false)
+            })
+            .collect();
+        // Play back in file name order
+        frames.sort();
+
+        if frames.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No images found in {}", dir.display()),
+            ));
+        }
+
+        Ok(Self { frames, next: 0 })
+    }
+
+    /// Number of images played back before wrapping around
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Same output as `capture_screenshot`, wraps around after the last image.
+    pub fn capture(&mut self) -> (DynamicImage, u64, Duration) {
+        let capture_start = Instant::now();
+        let path = &self.frames[self.next % self.frames.len()];
+        self.next += 1;
+
+        let image = image::open(path).unwrap_or_else(|e| {
+            error!("Failed to open synthetic frame {}: {}", path.display(), e);
+            DynamicImage::new_rgba8(1, 1)
+        });
+        let image_hash = calculate_hash(&image);
+        (image, image_hash, capture_start.elapsed())
+    }
+}
@@ -1,8 +1,11 @@
 use crate::core::MaxAverageFrame; // Assuming core.rs is in the same crate under the `core` module
-use image::DynamicImage;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage};
 use image_compare::{Algorithm, Metric, Similarity}; // Added import for Similarity
 use log::{debug, error};
-use rusty_tesseract::{Args, DataOutput, Image}; // Added import for Args, Image, DataOutput
+use rusty_tesseract::{Args, Data, DataOutput, Image}; // Added import for Args, Image, DataOutput
+#[cfg(feature = "cloud")]
+use screenpipe_integrations::cloud_ocr::OcrProvider;
 use serde_json;
 use std::collections::HashMap;
 use std::fs::{self, File};
@@ -15,9 +18,15 @@ use xcap::Monitor;
 
 #[derive(Clone, Debug)]
 pub enum OcrEngine {
-    Unstructured,
+    /// Cloud OCR service, see `CloudOcrConfig`
+    #[cfg(feature = "cloud")]
+    Cloud(Arc<dyn OcrProvider>),
     Tesseract,
     WindowsNative,
+    AppleNative,
+    /// Text of the focused window read from the accessibility tree, OCR with the native
+    /// engine of the platform when it can't be read
+    Accessibility,
 }
 
 impl Default for OcrEngine {
@@ -25,44 +34,88 @@ impl Default for OcrEngine {
         OcrEngine::Tesseract
     }
 }
+
+impl OcrEngine {
+    /// Engine reading frames recorded before, e.g. from a video chunk. Their accessibility
+    /// tree is gone, the native engine reads them instead.
+    pub fn for_recorded_frames(&self) -> OcrEngine {
+        match self {
+            OcrEngine::Accessibility => crate::core::NATIVE_OCR_ENGINE,
+            ocr_engine => ocr_engine.clone(),
+        }
+    }
+}
 pub fn calculate_hash(image: &DynamicImage) -> u64 {
     let mut hasher = DefaultHasher::new();
     image.as_bytes().hash(&mut hasher);
     hasher.finish()
 }
 
+/// 256-bit difference hash (dHash) of a frame, see [`perceptual_hash`].
+pub type PerceptualHash = [u64; 4];
+
+const PHASH_SIZE: u32 = 16;
+
+/// Computes a dHash: each bit tells whether a pixel of a 17x16 grayscale thumbnail
+/// is brighter than its right neighbour. Near-identical frames produce near-identical hashes.
+pub fn perceptual_hash(image: &DynamicImage) -> PerceptualHash {
+    let thumbnail = image
+        .thumbnail_exact(PHASH_SIZE + 1, PHASH_SIZE)
+        .to_luma8();
+    let mut hash: PerceptualHash = [0; 4];
+    for y in 0..PHASH_SIZE {
+        for x in 0..PHASH_SIZE {
+            let bit = (y * PHASH_SIZE + x) as usize;
+            if thumbnail.get_pixel(x, y)[0] > thumbnail.get_pixel(x + 1, y)[0] {
+                hash[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+    }
+    hash
+}
+
+/// Similarity between two perceptual hashes, from 0.0 (opposite) to 1.0 (identical).
+pub fn perceptual_hash_similarity(a: &PerceptualHash, b: &PerceptualHash) -> f64 {
+    let distance: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+    1.0 - distance as f64 / (PHASH_SIZE * PHASH_SIZE) as f64
+}
+
 pub fn compare_images_histogram(image1: &DynamicImage, image2: &DynamicImage) -> f64 {
-    let image_one = image1.to_luma8();
-    let image_two = image2.to_luma8();
-    let result =
-        image_compare::gray_similarity_histogram(Metric::Hellinger, &image_one, &image_two)
-            .expect("Images had different dimensions");
-    result
+    compare_luma_histogram(&image1.to_luma8(), &image2.to_luma8())
 }
 
 pub fn compare_images_ssim(image1: &DynamicImage, image2: &DynamicImage) -> f64 {
-    let image_one = image1.to_luma8();
-    let image_two = image2.to_luma8();
+    compare_luma_ssim(&image1.to_luma8(), &image2.to_luma8())
+}
+
+fn compare_luma_histogram(image_one: &GrayImage, image_two: &GrayImage) -> f64 {
+    image_compare::gray_similarity_histogram(Metric::Hellinger, image_one, image_two)
+        .expect("Images had different dimensions")
+}
+
+fn compare_luma_ssim(image_one: &GrayImage, image_two: &GrayImage) -> f64 {
     let result: Similarity =
-        image_compare::gray_similarity_structure(&Algorithm::MSSIMSimple, &image_one, &image_two)
+        image_compare::gray_similarity_structure(&Algorithm::MSSIMSimple, image_one, image_two)
             .expect("Images had different dimensions");
     result.score
 }
 
-pub fn perform_ocr_tesseract(image: &DynamicImage) -> (String, DataOutput, String) {
-    // debug!("inside perform_ocr");
+fn tesseract_data(image: &DynamicImage, lang: &str) -> Result<DataOutput, String> {
     let args = Args {
-        lang: "eng".to_string(),
+        lang: lang.to_string(),
         config_variables: HashMap::from([("tessedit_create_tsv".into(), "1".into())]),
         dpi: Some(600), // 150 is a balanced option, 600 seems faster surprisingly, the bigger the number the more granualar result
         psm: Some(1), // PSM 1: Automatic page segmentation with OSD. PSM 3: Automatic page segmentation with OSD
         oem: Some(1), //1: Neural nets LSTM engine only,    3: Default, based on what is available. (Default)
     };
 
-    let ocr_image = Image::from_dynamic_image(image).unwrap();
+    let ocr_image = Image::from_dynamic_image(image).map_err(|e| e.to_string())?;
+    rusty_tesseract::image_to_data(&ocr_image, &args).map_err(|e| e.to_string())
+}
 
-    // Extract data output
-    let data_output = rusty_tesseract::image_to_data(&ocr_image, &args).unwrap();
+pub fn perform_ocr_tesseract(image: &DynamicImage) -> (String, DataOutput, String) {
+    // debug!("inside perform_ocr");
+    let data_output = tesseract_data(image, "eng").unwrap();
     // let tsv_output = data_output_to_tsv(&data_output);
 
     // Extract text from data output
@@ -125,6 +178,524 @@ pub fn perform_ocr_tesseract(image: &DynamicImage) -> (String, DataOutput, Strin
     (text, data_output, json_output)
 }
 
+/// Writing system of a recognized word, stored per word with multi-language OCR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Han,
+    Kana,
+    Hangul,
+}
+
+impl Script {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Script::Latin => "latin",
+            Script::Greek => "greek",
+            Script::Cyrillic => "cyrillic",
+            Script::Arabic => "arabic",
+            Script::Hebrew => "hebrew",
+            Script::Devanagari => "devanagari",
+            Script::Thai => "thai",
+            Script::Han => "han",
+            Script::Kana => "kana",
+            Script::Hangul => "hangul",
+        }
+    }
+
+    fn of_char(c: char) -> Option<Script> {
+        match c as u32 {
+            0x41..=0x5A | 0x61..=0x7A | 0xC0..=0x24F | 0x1E00..=0x1EFF => Some(Script::Latin),
+            0x370..=0x3FF | 0x1F00..=0x1FFF => Some(Script::Greek),
+            0x400..=0x52F => Some(Script::Cyrillic),
+            0x590..=0x5FF => Some(Script::Hebrew),
+            0x600..=0x6FF | 0x750..=0x77F | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => {
+                Some(Script::Arabic)
+            }
+            0x900..=0x97F => Some(Script::Devanagari),
+            0xE00..=0xE7F => Some(Script::Thai),
+            0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Some(Script::Kana),
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Some(Script::Hangul),
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F => {
+                Some(Script::Han)
+            }
+            _ => None,
+        }
+    }
+
+    /// Scripts a Tesseract language pack (e.g. "eng", "chi_sim") is expected to read.
+    /// Unknown packs are assumed to be Latin.
+    fn of_language(lang: &str) -> &'static [Script] {
+        match lang {
+            "chi_sim" | "chi_tra" | "chi_sim_vert" | "chi_tra_vert" => &[Script::Han],
+            "jpn" | "jpn_vert" => &[Script::Han, Script::Kana],
+            "kor" | "kor_vert" => &[Script::Hangul, Script::Han],
+            "rus" | "ukr" | "bel" | "bul" | "mkd" | "srp" | "kaz" => &[Script::Cyrillic],
+            "ell" | "grc" => &[Script::Greek],
+            "ara" | "fas" | "urd" => &[Script::Arabic],
+            "heb" | "yid" => &[Script::Hebrew],
+            "hin" | "mar" | "nep" | "san" => &[Script::Devanagari],
+            "tha" => &[Script::Thai],
+            _ => &[Script::Latin],
+        }
+    }
+}
+
+/// Most frequent script of the letters of a word, None for digits and punctuation only.
+pub fn detect_script(text: &str) -> Option<Script> {
+    let mut counts: Vec<(Script, usize)> = Vec::new();
+    for script in text.chars().filter_map(Script::of_char) {
+        match counts.iter_mut().find(|(s, _)| *s == script) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+    // First seen wins ties, max_by_key would keep the last
+    let mut best: Option<(Script, usize)> = None;
+    for (script, count) in counts {
+        if best.map_or(true, |(_, best_count)| count > best_count) {
+            best = Some((script, count));
+        }
+    }
+    best.map(|(script, _)| script)
+}
+
+/// Runs one Tesseract pass per language (e.g. `["eng", "chi_sim"]`) and merges them with
+/// [`merge_language_passes`]. English only if empty. Languages whose pack isn't installed
+/// are skipped.
+pub fn perform_ocr_tesseract_languages(
+    image: &DynamicImage,
+    languages: &[String],
+) -> (String, DataOutput, String) {
+    if languages.len() <= 1 {
+        return match languages.first() {
+            Some(lang) if lang != "eng" => {
+                let data_output = tesseract_data(image, lang).unwrap_or_else(|e| {
+                    error!("Tesseract OCR with language {} failed: {}", lang, e);
+                    DataOutput {
+                        output: String::new(),
+                        data: Vec::new(),
+                    }
+                });
+                merge_language_passes(&[(lang.clone(), data_output)])
+            }
+            _ => perform_ocr_tesseract(image),
+        };
+    }
+    let passes: Vec<(String, DataOutput)> = languages
+        .iter()
+        .filter_map(|lang| match tesseract_data(image, lang) {
+            Ok(data_output) => Some((lang.clone(), data_output)),
+            Err(e) => {
+                error!("Tesseract OCR with language {} failed: {}", lang, e);
+                None
+            }
+        })
+        .collect();
+    merge_language_passes(&passes)
+}
+
+/// Merges the OCR passes of several languages over the same image by bounding box.
+/// Overlapping words of different passes are grouped, and each group keeps the words of a
+/// single pass: preferably one whose words are in the scripts of its language (an English
+/// pass reads CJK as garbage Latin), then the most confident one. The JSON output has the
+/// script of each word in `scripts`.
+pub fn merge_language_passes(passes: &[(String, DataOutput)]) -> (String, DataOutput, String) {
+    struct Candidate {
+        pass: usize,
+        data: Data,
+        script: Option<Script>,
+    }
+    let candidates: Vec<Candidate> = passes
+        .iter()
+        .enumerate()
+        .flat_map(|(pass, (_, output))| {
+            output
+                .data
+                .iter()
+                .filter(|d| is_word(d))
+                .map(move |d| Candidate {
+                    pass,
+                    data: clone_data(d),
+                    script: detect_script(&d.text),
+                })
+        })
+        .collect();
+
+    // Union-find of the words overlapping across passes
+    let mut parent: Vec<usize> = (0..candidates.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for i in 0..candidates.len() {
+        for j in i + 1..candidates.len() {
+            if candidates[i].pass != candidates[j].pass
+                && boxes_overlap(&candidates[i].data, &candidates[j].data)
+            {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+
+    // Per group and pass: whether every word is in the pass' scripts, confidence sum, count
+    let mut scores: HashMap<(usize, usize), (bool, f32, usize)> = HashMap::new();
+    for (i, candidate) in candidates.iter().enumerate() {
+        let group = root(&mut parent, i);
+        let expected = Script::of_language(&passes[candidate.pass].0);
+        let in_script = candidate.script.map_or(true, |s| expected.contains(&s));
+        let score = scores
+            .entry((group, candidate.pass))
+            .or_insert((true, 0.0, 0));
+        score.0 &= in_script;
+        score.1 += candidate.data.conf;
+        score.2 += 1;
+    }
+    let mut winners: HashMap<usize, (usize, bool, f32)> = HashMap::new();
+    for (&(group, pass), &(in_script, conf_sum, count)) in &scores {
+        let conf = conf_sum / count as f32;
+        let better = match winners.get(&group) {
+            Some(&(best_pass, best_in_script, best_conf)) => {
+                (in_script, conf, std::cmp::Reverse(pass))
+                    > (best_in_script, best_conf, std::cmp::Reverse(best_pass))
+            }
+            None => true,
+        };
+        if better {
+            winners.insert(group, (pass, in_script, conf));
+        }
+    }
+
+    let mut words: Vec<(usize, Data, Option<Script>)> = candidates
+        .into_iter()
+        .enumerate()
+        .filter(|(i, candidate)| winners[&root(&mut parent, *i)].0 == candidate.pass)
+        .map(|(_, candidate)| (candidate.pass, candidate.data, candidate.script))
+        .collect();
+    renumber_blocks(words.iter_mut().map(|(pass, word, _)| (*pass, word)));
+    words_to_output(
+        words
+            .into_iter()
+            .map(|(_, word, script)| (word, script))
+            .collect(),
+        true,
+    )
+}
+
+/// True if the boxes of two words cover at least half of the smaller one.
+fn boxes_overlap(a: &Data, b: &Data) -> bool {
+    let width = (a.left + a.width).min(b.left + b.width) - a.left.max(b.left);
+    let height = (a.top + a.height).min(b.top + b.height) - a.top.max(b.top);
+    if width <= 0 || height <= 0 {
+        return false;
+    }
+    let smaller = (a.width as i64 * a.height as i64).min(b.width as i64 * b.height as i64);
+    2 * width as i64 * height as i64 >= smaller
+}
+
+/// Downscales the image so its longest side is at most `max_dimension` pixels.
+/// Returns the image to run OCR on and the factor mapping its coordinates back to native resolution.
+pub fn downscale_for_ocr(
+    image: &Arc<DynamicImage>,
+    max_dimension: Option<u32>,
+) -> (Arc<DynamicImage>, f64) {
+    match max_dimension {
+        Some(max) if max > 0 && image.width().max(image.height()) > max => {
+            let resized = image.resize(max, max, FilterType::Triangle);
+            let scale = image.width() as f64 / resized.width() as f64;
+            debug!(
+                "Downscaled frame from {}x{} to {}x{} for OCR",
+                image.width(),
+                image.height(),
+                resized.width(),
+                resized.height()
+            );
+            (Arc::new(resized), scale)
+        }
+        _ => (Arc::clone(image), 1.0),
+    }
+}
+
+/// Maps bounding boxes computed on a downscaled frame back to native resolution.
+pub fn scale_data_output(data_output: &mut DataOutput, scale: f64) {
+    if scale == 1.0 {
+        return;
+    }
+    for record in data_output.data.iter_mut() {
+        record.left = (record.left as f64 * scale).round() as i32;
+        record.top = (record.top as f64 * scale).round() as i32;
+        record.width = (record.width as f64 * scale).round() as i32;
+        record.height = (record.height as f64 * scale).round() as i32;
+    }
+}
+
+/// A rectangle of the screen that changed since the last OCR'd frame, in native pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl DirtyRect {
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// True if the rectangles overlap or touch.
+    pub fn touches(&self, other: &DirtyRect) -> bool {
+        self.x <= other.x + other.width
+            && other.x <= self.x + self.width
+            && self.y <= other.y + other.height
+            && other.y <= self.y + self.height
+    }
+
+    pub fn union(&self, other: &DirtyRect) -> DirtyRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        DirtyRect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+
+    pub(crate) fn intersects_box(&self, left: i32, top: i32, width: i32, height: i32) -> bool {
+        let (x, y) = (self.x as i64, self.y as i64);
+        let (left, top) = (left as i64, top as i64);
+        left < x + self.width as i64
+            && x < left + width as i64
+            && top < y + self.height as i64
+            && y < top + height as i64
+    }
+}
+
+const DIRTY_TILE_SIZE: u32 = 32;
+const DIRTY_PIXEL_THRESHOLD: u8 = 16;
+
+/// Computes changed regions by comparing grayscale tiles of two frames.
+/// Used when the capture backend does not report dirty regions itself (xcap doesn't).
+/// Returns None when the frames can't be compared.
+pub fn detect_dirty_regions(
+    previous: &DynamicImage,
+    current: &DynamicImage,
+) -> Option<Vec<DirtyRect>> {
+    if previous.width() != current.width() || previous.height() != current.height() {
+        return None;
+    }
+    let (width, height) = (current.width(), current.height());
+    let previous = previous.to_luma8();
+    let current = current.to_luma8();
+
+    // Horizontal runs of changed tiles, padded by a tile so words on the edges are kept whole
+    let mut rects = Vec::new();
+    for row in 0..height.div_ceil(DIRTY_TILE_SIZE) {
+        let y0 = row * DIRTY_TILE_SIZE;
+        let y1 = (y0 + DIRTY_TILE_SIZE).min(height);
+        let mut run: Option<DirtyRect> = None;
+        for col in 0..width.div_ceil(DIRTY_TILE_SIZE) {
+            let x0 = col * DIRTY_TILE_SIZE;
+            let x1 = (x0 + DIRTY_TILE_SIZE).min(width);
+            let changed = (y0..y1).any(|y| {
+                (x0..x1).any(|x| {
+                    previous.get_pixel(x, y)[0].abs_diff(current.get_pixel(x, y)[0])
+                        > DIRTY_PIXEL_THRESHOLD
+                })
+            });
+            if changed {
+                let tile = DirtyRect {
+                    x: x0,
+                    y: y0,
+                    width: x1 - x0,
+                    height: y1 - y0,
+                };
+                run = Some(run.map_or(tile, |r| r.union(&tile)));
+            } else if let Some(r) = run.take() {
+                rects.push(r);
+            }
+        }
+        rects.extend(run);
+    }
+
+    let rects = rects
+        .into_iter()
+        .map(|r| {
+            let x = r.x.saturating_sub(DIRTY_TILE_SIZE);
+            let y = r.y.saturating_sub(DIRTY_TILE_SIZE);
+            DirtyRect {
+                x,
+                y,
+                width: (r.x + r.width + DIRTY_TILE_SIZE).min(width) - x,
+                height: (r.y + r.height + DIRTY_TILE_SIZE).min(height) - y,
+            }
+        })
+        .collect();
+
+    Some(merge_dirty_rects(rects))
+}
+
+/// Merges touching rectangles until none overlap.
+pub fn merge_dirty_rects(mut rects: Vec<DirtyRect>) -> Vec<DirtyRect> {
+    let mut merged = true;
+    while merged {
+        merged = false;
+        let mut i = 0;
+        while i < rects.len() {
+            let mut j = i + 1;
+            while j < rects.len() {
+                if rects[i].touches(&rects[j]) {
+                    let other = rects.swap_remove(j);
+                    rects[i] = rects[i].union(&other);
+                    merged = true;
+                } else {
+                    j += 1;
+                }
+            }
+            i += 1;
+        }
+    }
+    rects
+}
+
+/// Merges the OCR of changed regions into the previous frame's OCR.
+/// Words of the previous frame outside every dirty region are kept, the others are replaced
+/// by the words recognized in the regions (whose coordinates are relative to each region).
+pub fn merge_region_ocr(
+    previous: &DataOutput,
+    regions: &[(DirtyRect, DataOutput)],
+) -> (String, DataOutput, String) {
+    let mut words: Vec<(usize, Data)> = previous
+        .data
+        .iter()
+        .filter(|d| is_word(d))
+        .filter(|d| {
+            !regions
+                .iter()
+                .any(|(rect, _)| rect.intersects_box(d.left, d.top, d.width, d.height))
+        })
+        .map(|d| (0, clone_data(d)))
+        .collect();
+
+    for (i, (rect, output)) in regions.iter().enumerate() {
+        words.extend(output.data.iter().filter(|d| is_word(d)).map(|d| {
+            let mut word = clone_data(d);
+            word.left += rect.x as i32;
+            word.top += rect.y as i32;
+            (i + 1, word)
+        }));
+    }
+
+    renumber_blocks(words.iter_mut().map(|(source, word)| (*source, word)));
+    words_to_output(words.into_iter().map(|(_, w)| (w, None)).collect(), false)
+}
+
+/// Numbers the blocks of words coming from several OCR outputs, told apart by their
+/// `source`, from 1 in order of appearance. Lines are grouped by (block, par, line), a block
+/// of one output then never shares its number with a block of another one.
+fn renumber_blocks<'a>(words: impl Iterator<Item = (usize, &'a mut Data)>) {
+    let mut blocks: HashMap<(usize, i32), i32> = HashMap::new();
+    for (source, word) in words {
+        let next = blocks.len() as i32 + 1;
+        word.block_num = *blocks.entry((source, word.block_num)).or_insert(next);
+    }
+}
+
+/// Groups words into lines ordered top to bottom, left to right. With `with_scripts`, each
+/// JSON line has the space separated script of its words, "common" for digits and punctuation.
+pub(crate) fn words_to_output(
+    words: Vec<(Data, Option<Script>)>,
+    with_scripts: bool,
+) -> (String, DataOutput, String) {
+    let mut lines: Vec<Vec<(Data, Option<Script>)>> = Vec::new();
+    for word in words {
+        match lines.iter_mut().find(|line| {
+            let first = &line[0].0;
+            first.block_num == word.0.block_num
+                && first.par_num == word.0.par_num
+                && first.line_num == word.0.line_num
+        }) {
+            Some(line) => line.push(word),
+            None => lines.push(vec![word]),
+        }
+    }
+    lines.sort_by_key(|line| {
+        (
+            line.iter().map(|(w, _)| w.top).min().unwrap_or(0),
+            line.iter().map(|(w, _)| w.left).min().unwrap_or(0),
+        )
+    });
+
+    let mut json_lines: Vec<HashMap<String, String>> = Vec::new();
+    for line in &lines {
+        let text = line
+            .iter()
+            .map(|(w, _)| w.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let confidence = line.iter().map(|(w, _)| w.conf).sum::<f32>() / line.len() as f32;
+        let mut line_data = HashMap::new();
+        line_data.insert("text".to_string(), text);
+        line_data.insert("confidence".to_string(), format!("{:.2}", confidence));
+        if with_scripts {
+            let scripts = line
+                .iter()
+                .map(|(_, script)| script.map_or("common", |s| s.as_str()))
+                .collect::<Vec<_>>()
+                .join(" ");
+            line_data.insert("scripts".to_string(), scripts);
+        }
+        json_lines.push(line_data);
+    }
+
+    let data_output = DataOutput {
+        output: String::new(),
+        data: lines.into_iter().flatten().map(|(w, _)| w).collect(),
+    };
+    let text = data_output_to_text(&data_output);
+    let json_output = serde_json::to_string_pretty(&json_lines).unwrap();
+    (text, data_output, json_output)
+}
+
+pub(crate) fn is_word(data: &Data) -> bool {
+    data.level == 5 && !data.text.trim().is_empty()
+}
+
+pub fn clone_data(d: &Data) -> Data {
+    Data {
+        level: d.level,
+        page_num: d.page_num,
+        block_num: d.block_num,
+        par_num: d.par_num,
+        line_num: d.line_num,
+        word_num: d.word_num,
+        left: d.left,
+        top: d.top,
+        width: d.width,
+        height: d.height,
+        conf: d.conf,
+        text: d.text.clone(),
+    }
+}
+
+pub fn clone_data_output(data_output: &DataOutput) -> DataOutput {
+    DataOutput {
+        output: data_output.output.clone(),
+        data: data_output.data.iter().map(clone_data).collect(),
+    }
+}
+
 fn data_output_to_text(data_output: &DataOutput) -> String {
     let mut text = String::new();
     for record in &data_output.data {
@@ -156,8 +727,11 @@ pub async fn compare_with_previous_image(
 ) -> f64 {
     let mut current_average = 0.0;
     if let Some(prev_image) = previous_image {
-        let histogram_diff = compare_images_histogram(prev_image, &current_image);
-        let ssim_diff = 1.0 - compare_images_ssim(prev_image, &current_image);
+        // Convert each frame to grayscale once and reuse it for both metrics
+        let prev_luma = prev_image.to_luma8();
+        let current_luma = current_image.to_luma8();
+        let histogram_diff = compare_luma_histogram(&prev_luma, &current_luma);
+        let ssim_diff = 1.0 - compare_luma_ssim(&prev_luma, &current_luma);
         current_average = (histogram_diff + ssim_diff) / 2.0;
         let max_avg_frame_number = max_average.as_ref().map_or(0, |frame| frame.frame_number);
         debug!(
@@ -241,10 +815,19 @@ pub async fn save_text_files(
     }
 }
 
+/// OCRs `image` with Windows OCR in the Tesseract `languages` whose Windows language pack is
+/// installed, the languages of the user profile if none is. Windows OCR reads one language at
+/// a time: with several, the pass reading the most letters in the scripts of its language wins.
 #[cfg(target_os = "windows")]
-pub async fn perform_ocr_windows(image: &DynamicImage) -> (String, DataOutput, String) {
+pub async fn perform_ocr_windows(
+    image: &DynamicImage,
+    languages: &[String],
+) -> (String, DataOutput, String) {
+    use crate::ocr_languages::native_language_tag;
     use std::io::Cursor;
     use windows::{
+        core::HSTRING,
+        Globalization::Language,
         Graphics::Imaging::BitmapDecoder,
         Media::Ocr::OcrEngine as WindowsOcrEngine,
         Storage::Streams::{DataWriter, InMemoryRandomAccessStream},
@@ -269,12 +852,43 @@ pub async fn perform_ocr_windows(image: &DynamicImage) -> (String, DataOutput, S
 
     let bitmap = decoder.GetSoftwareBitmapAsync().unwrap().get().unwrap();
 
-    let engine = WindowsOcrEngine::TryCreateFromUserProfileLanguages().unwrap();
-    let result = engine.RecognizeAsync(&bitmap).unwrap().get().unwrap();
-
-    let text = result.Text().unwrap().to_string();
+    let engines: Vec<(&str, WindowsOcrEngine)> = languages
+        .iter()
+        .filter_map(|language| {
+            let tag = native_language_tag(language)?;
+            let windows_language = Language::CreateLanguage(&HSTRING::from(tag)).ok()?;
+            if !WindowsOcrEngine::IsLanguageSupported(&windows_language).unwrap_or(false) {
+                debug!("The Windows OCR pack of {} isn't installed", language);
+                return None;
+            }
+            let engine = WindowsOcrEngine::TryCreateFromLanguage(&windows_language).ok()?;
+            Some((language.as_str(), engine))
+        })
+        .collect();
 
-    let text = result.Text().unwrap().to_string();
+    let text = if engines.is_empty() {
+        let engine = WindowsOcrEngine::TryCreateFromUserProfileLanguages().unwrap();
+        let result = engine.RecognizeAsync(&bitmap).unwrap().get().unwrap();
+        result.Text().unwrap().to_string()
+    } else {
+        let mut best: Option<(usize, String)> = None;
+        for (language, engine) in engines {
+            let result = engine.RecognizeAsync(&bitmap).unwrap().get().unwrap();
+            let text = result.Text().unwrap().to_string();
+            let scripts = Script::of_language(language);
+            let letters = text
+                .chars()
+                .filter(|c| Script::of_char(*c).map_or(false, |s| scripts.contains(&s)))
+                .count();
+            if best
+                .as_ref()
+                .map_or(true, |(best_letters, _)| letters > *best_letters)
+            {
+                best = Some((letters, text));
+            }
+        }
+        best.map(|(_, text)| text).unwrap_or_default()
+    };
 
     // Create a simple DataOutput structure
     let data_output = DataOutput {
@@ -0,0 +1,167 @@
+use crate::utils::{clone_data, is_word, words_to_output};
+use log::debug;
+use rusty_tesseract::DataOutput;
+use serde::{Deserialize, Serialize};
+use xcap::Window;
+
+/// A window on the captured screen, in pixels of the frame. Windows are stacked front to
+/// back where the platform reports their order, macOS and Windows do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowRegion {
+    pub app_name: String,
+    pub window_name: String,
+    /// 0 for the frontmost window
+    pub z_index: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Part of the window is on screen and not covered by the windows in front of it
+    pub visible: bool,
+    /// OCR text in the visible part of the window, see `attribute_text`
+    pub text: String,
+}
+
+impl WindowRegion {
+    pub fn new(app_name: &str, window_name: &str, x: i32, y: i32, width: u32, height: u32) -> Self {
+        WindowRegion {
+            app_name: app_name.to_lowercase(),
+            window_name: window_name.to_string(),
+            z_index: 0,
+            x,
+            y,
+            width,
+            height,
+            visible: false,
+            text: String::new(),
+        }
+    }
+
+    fn right(&self) -> i64 {
+        self.x as i64 + self.width as i64
+    }
+
+    fn bottom(&self) -> i64 {
+        self.y as i64 + self.height as i64
+    }
+
+    fn contains(&self, x: i64, y: i64) -> bool {
+        self.x as i64 <= x && x < self.right() && self.y as i64 <= y && y < self.bottom()
+    }
+}
+
+/// Clips `windows`, front to back, to a `frame_width` x `frame_height` frame, drops the
+/// ones off screen and finds which are visible
+pub fn stack_windows(
+    windows: Vec<WindowRegion>,
+    frame_width: u32,
+    frame_height: u32,
+) -> Vec<WindowRegion> {
+    let mut stacked: Vec<WindowRegion> = Vec::new();
+    for mut window in windows {
+        let left = (window.x as i64).clamp(0, frame_width as i64);
+        let top = (window.y as i64).clamp(0, frame_height as i64);
+        let right = window.right().clamp(0, frame_width as i64);
+        let bottom = window.bottom().clamp(0, frame_height as i64);
+        if left >= right || top >= bottom {
+            continue;
+        }
+        window.x = left as i32;
+        window.y = top as i32;
+        window.width = (right - left) as u32;
+        window.height = (bottom - top) as u32;
+        window.z_index = stacked.len() as u32;
+        window.visible = !is_covered(&window, &stacked);
+        stacked.push(window);
+    }
+    stacked
+}
+
+/// Whether the windows of `above` cover all of `window`. The edges of the windows split it
+/// in cells, each of them is either covered or not.
+fn is_covered(window: &WindowRegion, above: &[WindowRegion]) -> bool {
+    let mut xs = vec![window.x as i64, window.right()];
+    let mut ys = vec![window.y as i64, window.bottom()];
+    for other in above {
+        xs.extend([other.x as i64, other.right()]);
+        ys.extend([other.y as i64, other.bottom()]);
+    }
+    xs.retain(|x| (window.x as i64..=window.right()).contains(x));
+    ys.retain(|y| (window.y as i64..=window.bottom()).contains(y));
+    xs.sort_unstable();
+    xs.dedup();
+    ys.sort_unstable();
+    ys.dedup();
+    xs.windows(2).all(|x| {
+        ys.windows(2)
+            .all(|y| above.iter().any(|other| other.contains(x[0], y[0])))
+    })
+}
+
+/// Fills the `text` of `windows` with the words of `data_output` in them. A word belongs to
+/// the frontmost window containing its center, words outside every window are left out.
+pub fn attribute_text(windows: &mut [WindowRegion], data_output: &DataOutput) {
+    let mut words: Vec<Vec<_>> = windows.iter().map(|_| Vec::new()).collect();
+    for word in data_output.data.iter().filter(|d| is_word(d)) {
+        let x = word.left as i64 + word.width as i64 / 2;
+        let y = word.top as i64 + word.height as i64 / 2;
+        if let Some(i) = windows
+            .iter()
+            .position(|window| window.visible && window.contains(x, y))
+        {
+            words[i].push((clone_data(word), None));
+        }
+    }
+    for (window, words) in windows.iter_mut().zip(words) {
+        window.text = words_to_output(words, false).0;
+    }
+}
+
+/// Windows on the monitor at `monitor_x`, `monitor_y` of `monitor_width` points, in
+/// pixels of its `frame_width` x `frame_height` frame. Minimized windows are left out.
+pub fn current_windows(
+    (monitor_x, monitor_y, monitor_width): (i32, i32, u32),
+    frame_width: u32,
+    frame_height: u32,
+) -> Vec<WindowRegion> {
+    let windows = match Window::all() {
+        Ok(windows) => windows,
+        Err(e) => {
+            debug!("Failed to list windows: {}", e);
+            return Vec::new();
+        }
+    };
+    // Retina screens are captured at twice their size in points
+    let scale = if monitor_width == 0 {
+        1.0
+    } else {
+        frame_width as f64 / monitor_width as f64
+    };
+    let windows = windows
+        .iter()
+        .filter(|w| !w.is_minimized())
+        .map(|w| {
+            WindowRegion::new(
+                w.app_name(),
+                w.title(),
+                ((w.x() - monitor_x) as f64 * scale).round() as i32,
+                ((w.y() - monitor_y) as f64 * scale).round() as i32,
+                (w.width() as f64 * scale).round() as u32,
+                (w.height() as f64 * scale).round() as u32,
+            )
+        })
+        .collect();
+    stack_windows(windows, frame_width, frame_height)
+}
+
+/// Lowercase name of the app of the frontmost window on any monitor, `None` when the
+/// windows can't be listed
+pub fn frontmost_app() -> Option<String> {
+    let windows = Window::all()
+        .map_err(|e| debug!("Failed to list windows: {}", e))
+        .ok()?;
+    windows
+        .iter()
+        .find(|w| !w.is_minimized())
+        .map(|w| w.app_name().to_lowercase())
+}
@@ -0,0 +1,63 @@
+#[cfg(swift_bridge)]
+#[cfg(test)]
+mod tests {
+    use screenpipe_vision::{perform_ocr_apple, process_ocr_task, OcrEngine};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Instant;
+    use tokio::sync::{mpsc, Mutex};
+
+    fn load_test_image() -> image::DynamicImage {
+        // Use an absolute path that works in both local and CI environments
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests");
+        path.push("testing_OCR.png");
+        println!("Path to testing_OCR.png: {:?}", path);
+        image::open(&path).expect("Failed to open image")
+    }
+
+    #[test]
+    fn test_apple_native_ocr() {
+        let image = load_test_image();
+
+        let (text, data_output, json_output) = perform_ocr_apple(&image, &[]);
+
+        println!("Character length of OCR text: {}", text.len());
+        assert!(!text.is_empty(), "OCR text should not be empty");
+        assert!(!data_output.data.is_empty());
+        assert!(json_output.contains("confidence"));
+    }
+
+    #[tokio::test]
+    async fn test_process_ocr_task_apple() {
+        let image_arc = Arc::new(load_test_image());
+        let frame_number = 1;
+        let timestamp = Instant::now();
+        let (tx, mut rx) = mpsc::channel(1);
+        let previous_text_json = Arc::new(Mutex::new(None));
+        let ocr_engine = Arc::new(OcrEngine::AppleNative);
+        let app_name = "test_app".to_string();
+
+        let result = process_ocr_task(
+            image_arc,
+            frame_number,
+            timestamp,
+            tx,
+            &previous_text_json,
+            false,
+            ocr_engine,
+            app_name,
+            Vec::new(),
+            None,
+            &[],
+            None,
+            &Arc::new(Mutex::new(None)),
+            None,
+        )
+        .await;
+
+        assert!(result.is_ok());
+        let capture_result = rx.recv().await.unwrap();
+        assert!(!capture_result.text.is_empty());
+    }
+}
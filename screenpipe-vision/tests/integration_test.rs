@@ -50,3 +50,558 @@ fn test_ocr_output() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_downscale_for_ocr_maps_back_to_native() {
+    use image::DynamicImage;
+    use rusty_tesseract::{Data, DataOutput};
+    use screenpipe_vision::utils::{downscale_for_ocr, scale_data_output};
+    use std::sync::Arc;
+
+    let image = Arc::new(DynamicImage::new_rgba8(5120, 2880));
+
+    let (ocr_image, scale) = downscale_for_ocr(&image, Some(1920));
+    assert_eq!(ocr_image.width(), 1920);
+    assert_eq!(ocr_image.height(), 1080);
+
+    let mut data_output = DataOutput {
+        output: String::new(),
+        data: vec![Data {
+            level: 5,
+            page_num: 1,
+            block_num: 1,
+            par_num: 1,
+            line_num: 1,
+            word_num: 1,
+            left: 960,
+            top: 540,
+            width: 100,
+            height: 30,
+            conf: 90.0,
+            text: "hello".to_string(),
+        }],
+    };
+    scale_data_output(&mut data_output, scale);
+    assert_eq!(data_output.data[0].left, 2560);
+    assert_eq!(data_output.data[0].top, 1440);
+
+    // Frames already below the limit are left untouched
+    let (same_image, scale) = downscale_for_ocr(&image, Some(8000));
+    assert!(Arc::ptr_eq(&image, &same_image));
+    assert_eq!(scale, 1.0);
+}
+
+#[test]
+fn test_perceptual_hash_similarity() {
+    use image::{DynamicImage, Rgba, RgbaImage};
+    use screenpipe_vision::utils::{perceptual_hash, perceptual_hash_similarity};
+
+    let gradient = RgbaImage::from_fn(640, 480, |x, y| {
+        let v = ((x + y) % 256) as u8;
+        Rgba([v, v, v, 255])
+    });
+    let image = DynamicImage::ImageRgba8(gradient.clone());
+    let mut nearly_identical = gradient;
+    nearly_identical.put_pixel(10, 10, Rgba([255, 0, 0, 255]));
+    let nearly_identical = DynamicImage::ImageRgba8(nearly_identical);
+    let different = DynamicImage::ImageRgba8(RgbaImage::from_fn(640, 480, |x, _| {
+        let v = if (x / 40) % 2 == 0 { 0 } else { 255 };
+        Rgba([v, v, v, 255])
+    }));
+
+    let hash = perceptual_hash(&image);
+    assert_eq!(perceptual_hash_similarity(&hash, &hash), 1.0);
+    assert!(perceptual_hash_similarity(&hash, &perceptual_hash(&nearly_identical)) > 0.95);
+    assert!(perceptual_hash_similarity(&hash, &perceptual_hash(&different)) < 0.9);
+}
+
+#[test]
+fn test_dirty_regions_and_merge() {
+    use image::{DynamicImage, Rgba, RgbaImage};
+    use rusty_tesseract::{Data, DataOutput};
+    use screenpipe_vision::utils::{detect_dirty_regions, merge_region_ocr, DirtyRect};
+
+    let previous = RgbaImage::from_pixel(1280, 720, Rgba([255, 255, 255, 255]));
+    let mut current = previous.clone();
+    for x in 600..700 {
+        for y in 400..420 {
+            current.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+        }
+    }
+    let regions = detect_dirty_regions(
+        &DynamicImage::ImageRgba8(previous),
+        &DynamicImage::ImageRgba8(current),
+    )
+    .unwrap();
+    assert_eq!(regions.len(), 1);
+    let rect = regions[0];
+    assert!(rect.x <= 600 && rect.x + rect.width >= 700);
+    assert!(rect.y <= 400 && rect.y + rect.height >= 420);
+
+    let word = |text: &str, line_num: i32, left: i32, top: i32| Data {
+        level: 5,
+        page_num: 1,
+        block_num: 1,
+        par_num: 1,
+        line_num,
+        word_num: 1,
+        left,
+        top,
+        width: 80,
+        height: 20,
+        conf: 90.0,
+        text: text.to_string(),
+    };
+    let previous_output = DataOutput {
+        output: String::new(),
+        data: vec![word("kept", 1, 10, 10), word("stale", 2, 610, 400)],
+    };
+    let region_output = DataOutput {
+        output: String::new(),
+        data: vec![word("fresh", 1, 600 - rect.x as i32, 400 - rect.y as i32)],
+    };
+
+    let (text, data_output, json_output) =
+        merge_region_ocr(&previous_output, &[(rect, region_output)]);
+    assert_eq!(text, "kept fresh");
+    assert_eq!(data_output.data[1].left, 600);
+    assert!(json_output.contains("fresh"));
+    assert!(!json_output.contains("stale"));
+
+    // A region changed later, far from the first one, its line stays its own
+    let later = DirtyRect {
+        x: 100,
+        y: 600,
+        width: 200,
+        height: 40,
+    };
+    let later_output = DataOutput {
+        output: String::new(),
+        data: vec![word("later", 1, 0, 0)],
+    };
+    let (text, _, json_output) = merge_region_ocr(&data_output, &[(later, later_output)]);
+    assert_eq!(text, "kept fresh later");
+    let lines: Vec<std::collections::HashMap<String, String>> =
+        serde_json::from_str(&json_output).unwrap();
+    assert_eq!(lines.len(), 3);
+}
+
+#[test]
+fn test_merge_language_passes() {
+    use rusty_tesseract::{Data, DataOutput};
+    use screenpipe_vision::utils::{detect_script, merge_language_passes, Script};
+
+    assert_eq!(detect_script("Settings"), Some(Script::Latin));
+    assert_eq!(detect_script("设置"), Some(Script::Han));
+    assert_eq!(detect_script("ファイル"), Some(Script::Kana));
+    assert_eq!(detect_script("12:30"), None);
+
+    let word = |text: &str, left: i32, conf: f32| Data {
+        level: 5,
+        page_num: 1,
+        block_num: 1,
+        par_num: 1,
+        line_num: 1,
+        word_num: 1,
+        left,
+        top: 10,
+        width: 80,
+        height: 20,
+        conf,
+        text: text.to_string(),
+    };
+    // English UI label, then Chinese content the English pass reads as garbage
+    let eng = DataOutput {
+        output: String::new(),
+        data: vec![word("Settings", 10, 95.0), word("it", 200, 40.0)],
+    };
+    let chi_sim = DataOutput {
+        output: String::new(),
+        data: vec![word("Settings", 10, 60.0), word("设置", 205, 85.0)],
+    };
+
+    let (text, data_output, json_output) =
+        merge_language_passes(&[("eng".to_string(), eng), ("chi_sim".to_string(), chi_sim)]);
+    assert_eq!(text, "Settings 设置");
+    assert_eq!(data_output.data[0].conf, 95.0);
+    let lines: Vec<std::collections::HashMap<String, String>> =
+        serde_json::from_str(&json_output).unwrap();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.iter().any(|line| line["scripts"] == "han"));
+}
+
+#[test]
+fn test_ocr_languages() {
+    use screenpipe_vision::{native_language_tag, OcrLanguages};
+
+    let mut languages = OcrLanguages::new(vec!["eng".to_string()]);
+    languages.set_app("WeChat", vec!["chi_sim".to_string(), "eng".to_string()]);
+    assert_eq!(languages.for_app("wechat"), ["chi_sim", "eng"]);
+    assert_eq!(languages.for_app("Firefox"), ["eng"]);
+    assert_eq!(languages.all(), ["chi_sim", "eng"]);
+
+    assert_eq!(native_language_tag("chi_sim"), Some("zh-Hans"));
+    assert_eq!(native_language_tag("deu"), Some("de-DE"));
+    assert_eq!(native_language_tag("osd"), None);
+}
+
+#[test]
+fn test_sensitive_content_filter() {
+    use image::{DynamicImage, Rgb, RgbImage};
+    use rusty_tesseract::{Data, DataOutput};
+    use screenpipe_vision::{SensitiveAction, SensitiveCategory, SensitiveContentFilter};
+    use std::str::FromStr;
+
+    assert_eq!(
+        SensitiveCategory::from_str("password-manager").unwrap(),
+        SensitiveCategory::PasswordManager
+    );
+    assert!(SensitiveCategory::from_str("gossip").is_err());
+
+    let word = |text: &str, line_num: i32, left: i32| Data {
+        level: 5,
+        page_num: 1,
+        block_num: 1,
+        par_num: 1,
+        line_num,
+        word_num: 1,
+        left,
+        top: line_num * 40,
+        width: 60,
+        height: 20,
+        conf: 90.0,
+        text: text.to_string(),
+    };
+    let statement = DataOutput {
+        output: String::new(),
+        data: vec![
+            word("Weekly", 1, 10),
+            word("report", 1, 80),
+            word("Card", 2, 10),
+            word("4111", 2, 80),
+            word("1111", 2, 150),
+            word("1111", 2, 220),
+            word("1111", 2, 290),
+        ],
+    };
+    let text = "Weekly report Card 4111 1111 1111 1111";
+    let white = DynamicImage::ImageRgb8(RgbImage::from_pixel(400, 200, Rgb([255, 255, 255])));
+    let empty = DataOutput {
+        output: String::new(),
+        data: vec![],
+    };
+    let filter =
+        SensitiveContentFilter::new(SensitiveCategory::ALL.to_vec(), SensitiveAction::Blur);
+
+    let content = filter.classify(&white, text, &statement).unwrap();
+    assert_eq!(content.category, SensitiveCategory::Banking);
+    // Only the line with the card number
+    assert_eq!(content.regions.len(), 1);
+    assert!(content.regions[0].y <= 80 && content.regions[0].y + content.regions[0].height >= 100);
+    assert!(content.regions[0].y > 60);
+    let (redacted, _, _) = screenpipe_vision::redact_words(&statement, &content.regions);
+    assert_eq!(redacted, "Weekly report");
+
+    assert!(filter.classify(&white, "Weekly report", &empty).is_none());
+    // Words only, the whole frame is flagged
+    let vault = filter
+        .classify(&white, "Bitwarden\nMaster password", &empty)
+        .unwrap();
+    assert_eq!(vault.category, SensitiveCategory::PasswordManager);
+    assert!(vault.regions.is_empty());
+
+    let skin = DynamicImage::ImageRgb8(RgbImage::from_pixel(400, 200, Rgb([224, 172, 140])));
+    assert_eq!(
+        filter.classify(&skin, "", &empty).unwrap().category,
+        SensitiveCategory::Nudity
+    );
+    let banking_only =
+        SensitiveContentFilter::new(vec![SensitiveCategory::Banking], SensitiveAction::Skip);
+    assert!(banking_only.classify(&skin, "", &empty).is_none());
+
+    // Copied text, without pixels
+    assert_eq!(
+        filter.classify_text("GB82 WEST 1234 5698 7654 32"),
+        Some(SensitiveCategory::Banking)
+    );
+    assert_eq!(
+        filter.classify_text("Bitwarden\nMaster password"),
+        Some(SensitiveCategory::PasswordManager)
+    );
+    assert_eq!(filter.classify_text("see you at 3"), None);
+}
+
+#[test]
+fn test_masked_regions() {
+    use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+    use screenpipe_vision::{MaskStyle, MaskedRegion, RegionAnchor, RegionLength, ScreenMask};
+    use std::str::FromStr;
+
+    let corner = MaskedRegion::from_str("top-right:10,0,25%,100").unwrap();
+    assert_eq!(corner.anchor, RegionAnchor::TopRight);
+    assert_eq!(corner.width, RegionLength::Percent(25.0));
+    assert_eq!(corner.monitor, None);
+    let rect = corner.rect(1920, 1080).unwrap();
+    assert_eq!(
+        (rect.x, rect.y, rect.width, rect.height),
+        (1430, 0, 480, 100)
+    );
+    // Still the top right quarter after a resolution change
+    let rect = corner.rect(3840, 2160).unwrap();
+    assert_eq!((rect.x, rect.width), (2870, 960));
+
+    let pane = MaskedRegion::from_str("DELL U2720Q@0,50%,400,50%").unwrap();
+    assert_eq!(pane.monitor.as_deref(), Some("DELL U2720Q"));
+    assert_eq!(pane.anchor, RegionAnchor::TopLeft);
+    assert!(pane.applies_to(Some("dell u2720q")));
+    assert!(!pane.applies_to(Some("Built-in Retina Display")));
+    assert!(!pane.applies_to(None));
+    // Clipped to the screen, nothing left off screen
+    let rect = MaskedRegion::from_str("bottom-left:100,0,400,80")
+        .unwrap()
+        .rect(300, 200)
+        .unwrap();
+    assert_eq!(
+        (rect.x, rect.y, rect.width, rect.height),
+        (100, 120, 200, 80)
+    );
+    assert!(MaskedRegion::from_str("0,0,10,10")
+        .unwrap()
+        .rect(0, 0)
+        .is_none());
+
+    assert!(MaskedRegion::from_str("0,0,10").is_err());
+    assert!(MaskedRegion::from_str("middle:0,0,10,10").is_err());
+    assert!(MaskedRegion::from_str("0,0,150%,10").is_err());
+    assert_eq!(MaskedRegion::from_str(&pane.to_string()).unwrap(), pane);
+
+    let white = DynamicImage::ImageRgba8(RgbaImage::from_pixel(200, 100, Rgba([255; 4])));
+    let mask = ScreenMask::new(vec![corner, pane], MaskStyle::Black);
+    let masked = mask.apply(white.clone(), Some("Built-in Retina Display"));
+    assert_eq!(masked.get_pixel(189, 0), Rgba([0, 0, 0, 255]));
+    assert_eq!(masked.get_pixel(199, 0), Rgba([255; 4]));
+    assert_eq!(masked.get_pixel(0, 99), Rgba([255; 4]));
+    let masked = mask.apply(white, Some("DELL U2720Q"));
+    assert_eq!(masked.get_pixel(0, 99), Rgba([0, 0, 0, 255]));
+    assert_eq!(masked.get_pixel(0, 0), Rgba([255; 4]));
+}
+
+#[test]
+fn test_capture_crop() {
+    use image::{DynamicImage, RgbaImage};
+    use screenpipe_vision::{CaptureCrop, CaptureRegion, RegionLength};
+    use std::str::FromStr;
+
+    // The ticker at the bottom is cut off, the OCR coordinates keep their screen offset
+    let crop = CaptureCrop::from_str("0,0,40,10%").unwrap();
+    assert_eq!(crop.bottom, RegionLength::Pixels(40));
+    let screen = DynamicImage::ImageRgba8(RgbaImage::new(200, 100));
+    let (image, region) = crop.apply(screen.clone(), Some("DELL U2720Q".to_string()));
+    assert_eq!((image.width(), image.height()), (180, 60));
+    assert_eq!(
+        region,
+        CaptureRegion {
+            monitor: Some("DELL U2720Q".to_string()),
+            x: 20,
+            y: 0,
+            width: 180,
+            height: 60,
+            screen_width: 200,
+            screen_height: 100,
+        }
+    );
+    assert!(region.is_cropped());
+
+    // Nothing left, the whole frame is kept
+    let (image, region) = CaptureCrop::from_str("60%,0,60%,0")
+        .unwrap()
+        .apply(screen, None);
+    assert_eq!((image.width(), image.height()), (200, 100));
+    assert!(!region.is_cropped());
+
+    assert!(CaptureCrop::from_str("0,0,40").is_err());
+    assert_eq!(CaptureCrop::from_str(&crop.to_string()).unwrap(), crop);
+}
+
+#[test]
+fn test_window_regions() {
+    use rusty_tesseract::{Data, DataOutput};
+    use screenpipe_vision::{attribute_text, stack_windows, WindowRegion};
+
+    let windows = stack_windows(
+        vec![
+            // A notification over the editor
+            WindowRegion::new("Slack", "New message", 600, 0, 200, 100),
+            WindowRegion::new("Code", "main.rs", 0, 0, 800, 600),
+            // Behind the editor, not on screen
+            WindowRegion::new("Firefox", "Docs", 100, 100, 300, 300),
+            WindowRegion::new("Finder", "Other monitor", 1100, 0, 400, 400),
+            // Sticks out of the screen
+            WindowRegion::new("Terminal", "zsh", 700, 500, 400, 400),
+        ],
+        1000,
+        800,
+    );
+    let names: Vec<&str> = windows.iter().map(|w| w.app_name.as_str()).collect();
+    assert_eq!(names, ["slack", "code", "firefox", "terminal"]);
+    let visible: Vec<bool> = windows.iter().map(|w| w.visible).collect();
+    assert_eq!(visible, [true, true, false, true]);
+    assert_eq!(windows[3].z_index, 3);
+    assert_eq!((windows[3].width, windows[3].height), (300, 300));
+    // Covered by two windows together
+    let covered = stack_windows(
+        vec![
+            WindowRegion::new("Code", "left", 0, 0, 500, 600),
+            WindowRegion::new("Code", "right", 500, 0, 500, 600),
+            WindowRegion::new("Firefox", "Docs", 200, 100, 600, 300),
+        ],
+        1000,
+        600,
+    );
+    assert!(!covered[2].visible);
+
+    let word = |text: &str, left: i32, top: i32| Data {
+        level: 5,
+        page_num: 1,
+        block_num: 1,
+        par_num: 1,
+        line_num: top,
+        word_num: 1,
+        left,
+        top,
+        width: 60,
+        height: 20,
+        conf: 90.0,
+        text: text.to_string(),
+    };
+    let data_output = DataOutput {
+        output: String::new(),
+        data: vec![
+            word("lunch?", 650, 40),
+            word("fn", 120, 200),
+            word("main", 190, 200),
+            word("cargo", 850, 700),
+        ],
+    };
+    let mut windows = windows;
+    attribute_text(&mut windows, &data_output);
+    let texts: Vec<&str> = windows.iter().map(|w| w.text.as_str()).collect();
+    assert_eq!(texts, ["lunch?", "fn main", "", "cargo"]);
+}
+
+#[test]
+fn test_adaptive_interval() {
+    use screenpipe_vision::AdaptiveInterval;
+    use std::time::Duration;
+
+    let configured = Duration::from_secs(1);
+    let mut interval = AdaptiveInterval::new(Duration::from_secs(5));
+    // Backs off while the screen is still, up to the idle interval
+    let still: Vec<Duration> = (0..4).map(|_| interval.next(configured, 0.0)).collect();
+    assert_eq!(still, [2, 4, 5, 5].map(Duration::from_secs));
+    // Ramps up on small changes, right away on fast ones
+    assert_eq!(interval.next(configured, 0.01), Duration::from_millis(2500));
+    assert_eq!(interval.next(configured, 0.2), configured);
+
+    interval.next(configured, 0.0);
+    interval.reset();
+    assert_eq!(interval.next(configured, 0.01), configured);
+
+    // Without an idle interval slower than the configured one, nothing changes
+    let mut fixed = AdaptiveInterval::new(Duration::ZERO);
+    assert_eq!(fixed.next(configured, 0.0), configured);
+}
+
+#[test]
+fn test_accessibility_elements_to_ocr_output() {
+    use screenpipe_vision::utils::DirtyRect;
+    use screenpipe_vision::{
+        elements_to_ocr_output, CaptureRegion, ElementFrame, ScreenPlacement, UiElement,
+    };
+
+    let element = |role: &str, text: &str, depth, frame: Option<(f64, f64, f64, f64)>| UiElement {
+        role: role.to_string(),
+        text: text.to_string(),
+        depth,
+        frame: frame.map(|(x, y, width, height)| ElementFrame {
+            x,
+            y,
+            width,
+            height,
+        }),
+    };
+    let elements = [
+        element("button", "Reply", 2, Some((200.0, 110.0, 50.0, 20.0))),
+        // Masked
+        element("textfield", "hunter2", 3, Some((900.0, 10.0, 80.0, 20.0))),
+        // Cropped out
+        element("statictext", "Sidebar", 2, Some((100.0, 100.0, 40.0, 20.0))),
+        // On the monitor on the right
+        element(
+            "statictext",
+            "Terminal",
+            1,
+            Some((1200.0, 100.0, 50.0, 20.0)),
+        ),
+        element("statictext", "Unplaced", 1, None),
+        element(
+            "textfield",
+            "see you at 3",
+            3,
+            Some((400.0, 300.0, 200.0, 30.0)),
+        ),
+    ];
+    // A 1000 points wide retina monitor captured at 2000x1200 pixels, the 200 pixels on
+    // the left cropped out and the top right corner masked
+    let placement = ScreenPlacement {
+        monitor: (100, 0, 1000),
+        region: CaptureRegion {
+            monitor: Some("1".to_string()),
+            x: 200,
+            y: 0,
+            width: 1600,
+            height: 1200,
+            screen_width: 2000,
+            screen_height: 1200,
+        },
+        masks: vec![DirtyRect {
+            x: 1400,
+            y: 0,
+            width: 600,
+            height: 200,
+        }],
+    };
+    // OCR'd at half the size
+    let (text, data_output, json_output) = elements_to_ocr_output(&elements, &placement, 800);
+    assert_eq!(text, "Reply\nsee you at 3");
+    assert_eq!(data_output.output, text);
+    let boxes: Vec<_> = data_output
+        .data
+        .iter()
+        .map(|data| {
+            (
+                data.text.as_str(),
+                data.left,
+                data.top,
+                data.width,
+                data.height,
+            )
+        })
+        .collect();
+    assert_eq!(
+        boxes,
+        vec![
+            ("Reply", 0, 110, 50, 20),
+            ("see you at 3", 200, 300, 200, 30)
+        ]
+    );
+    // One line per element
+    assert_eq!(data_output.data[1].block_num, 2);
+
+    let records: Vec<std::collections::HashMap<String, String>> =
+        serde_json::from_str(&json_output).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["text"], "Reply");
+    assert_eq!(records[0]["role"], "button");
+    assert_eq!(records[1]["confidence"], "100.00");
+}
@@ -2,11 +2,14 @@
 #[cfg(test)]
 mod tests {
     use screenpipe_vision::{get_monitor, process_ocr_task, OcrEngine};
+    use std::sync::atomic::AtomicBool;
     use std::sync::Arc;
     use std::{path::PathBuf, time::Instant};
-    use tokio::sync::{mpsc, Mutex};
+    use tokio::sync::{mpsc, watch, Mutex};
 
-    use screenpipe_vision::{continuous_capture, CaptureResult};
+    use screenpipe_vision::{
+        continuous_capture, CaptureResult, CaptureSource, CaptureTrigger, OcrLanguages,
+    };
     use std::time::Duration;
     use tokio::time::timeout;
 
@@ -37,6 +40,12 @@ mod tests {
             false,
             ocr_engine,
             app_name,
+            Vec::new(),
+            None,
+            &[],
+            None,
+            &Arc::new(Mutex::new(None)),
+            None,
         )
         .await;
 
@@ -54,7 +63,7 @@ mod tests {
         let monitor = get_monitor().await;
 
         // Set up test parameters
-        let interval = Duration::from_millis(1000);
+        let (_interval_tx, interval) = watch::channel(Duration::from_millis(1000));
         let save_text_files_flag = false;
         let ocr_engine = Arc::new(OcrEngine::WindowsNative);
 
@@ -62,9 +71,20 @@ mod tests {
         let capture_handle = tokio::spawn(continuous_capture(
             result_tx,
             interval,
+            None,
             save_text_files_flag,
             ocr_engine,
-            monitor,
+            CaptureSource::Monitor(monitor),
+            None,
+            OcrLanguages::default(),
+            None,
+            false,
+            None,
+            None,
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(CaptureTrigger::new()),
+            1,
+            Arc::new(|_: &str| false),
         ));
 
         // Wait for a short duration to allow some captures to occur